@@ -1,12 +1,126 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use media_client::load;
-use media_client::media_types::MediaKeyFrameIteratorDynMut;
+use media_client::media_types::{
+    ImageFormat, JobContext, JobObserver, MediaFrameDecoderOptions, MediaKeyFrame,
+    MediaKeyFrameIteratorDynMut, OutputOptions, Size, TestAssetOptions,
+};
 use std::fs;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// What key frames are written as.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum KeyFrameFormat {
+    Jpeg,
+    /// Lossless.
+    Png,
+    /// Lossless, smaller than PNG but slower to encode.
+    Webp,
+}
+
+impl KeyFrameFormat {
+    fn image_format(self) -> ImageFormat {
+        match self {
+            KeyFrameFormat::Jpeg => ImageFormat::Jpeg,
+            KeyFrameFormat::Png => ImageFormat::Png,
+            KeyFrameFormat::Webp => ImageFormat::WebP,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            KeyFrameFormat::Jpeg => "jpeg",
+            KeyFrameFormat::Png => "png",
+            KeyFrameFormat::Webp => "webp",
+        }
+    }
+}
 
 #[derive(Subcommand, Clone, Debug)]
 pub enum Command {
-    GetKeyFrames { input: String, output_dir: String },
+    GetKeyFrames {
+        input: String,
+        output_dir: String,
+        /// Sync every written frame to disk before moving on.
+        #[arg(long)]
+        durable: bool,
+        #[arg(long, value_enum, default_value_t = KeyFrameFormat::Jpeg)]
+        format: KeyFrameFormat,
+    },
+    /// Writes a JPEG of the frame shown at a point in the input.
+    Thumbnail {
+        input: String,
+        output: String,
+        /// Sync the thumbnail to disk before returning.
+        #[arg(long)]
+        durable: bool,
+        /// Milliseconds from the start of the input.
+        #[arg(long, default_value_t = 0)]
+        timestamp_ms: u64,
+        /// Fit inside this width, 0 to follow the height.
+        #[arg(long, default_value_t = 0)]
+        width: u32,
+        /// Fit inside this height, 0 to follow the width.
+        #[arg(long, default_value_t = 0)]
+        height: u32,
+    },
+    /// Synthesizes a test pattern video, with a tone when asked for, to
+    /// reproduce problems without sharing files. The container comes from
+    /// the output's extension.
+    GenTestAsset {
+        output: String,
+        /// FFmpeg encoder, e.g. libx264 or mpeg2video. Defaults to the
+        /// container's.
+        #[arg(long)]
+        codec: Option<String>,
+        #[arg(long, default_value_t = 5000)]
+        duration_ms: u32,
+        #[arg(long, default_value_t = 30)]
+        fps: u32,
+        #[arg(long, default_value_t = 1280)]
+        width: u32,
+        #[arg(long, default_value_t = 720)]
+        height: u32,
+        #[arg(long, default_value_t = 0)]
+        b_frames: u32,
+        /// Encode interlaced, top field first.
+        #[arg(long)]
+        interlaced: bool,
+        /// Clockwise rotation players apply, a multiple of 90.
+        #[arg(long, default_value_t = 0)]
+        rotation: u32,
+        /// Add a sine tone of this frequency, 0 for no audio.
+        #[arg(long, default_value_t = 0)]
+        tone_hz: u32,
+    },
+}
+
+/// Makes Ctrl-C only flag the request to stop, for commands that check the
+/// flag between frames so nothing is left half written. Commands that don't
+/// keep the default handling and stop straight away.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        eprintln!("Interrupted, finishing the current frame...");
+        flag.store(true, Ordering::SeqCst);
+    })
+    .expect("Failed to set Ctrl-C handler");
+    interrupted
+}
+
+/// Cancels a job once Ctrl-C was pressed, see `interrupt_flag`.
+struct InterruptObserver(Arc<AtomicBool>);
+
+impl JobObserver for InterruptObserver {
+    extern "C" fn is_cancelled(&mut self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    extern "C" fn on_progress(&mut self, _done_us: u64, _total_us: u64) {}
 }
 
 #[derive(Parser)]
@@ -17,7 +131,6 @@ struct Cli {
 
 fn main() {
     use std::env;
-    use std::path::PathBuf;
 
     let lib_name = if cfg!(target_os = "windows") {
         "media_lib.dll"
@@ -36,27 +149,142 @@ fn main() {
     let client = load(&lib_path).unwrap();
 
     match cli.command {
-        Command::GetKeyFrames { input, output_dir } => {
+        Command::GetKeyFrames {
+            input,
+            output_dir,
+            durable,
+            format,
+        } => {
             println!("Getting key frames from {} to {}", input, output_dir);
+            let interrupted = interrupt_flag();
 
             // Create the output directory if it doesn't exist
             if !Path::new(&output_dir).exists() {
                 fs::create_dir_all(&output_dir).expect("Failed to create output directory");
             }
 
-            let mut key_frame_getter = client.get_key_frames(input.as_str()).unwrap();
+            let options = MediaFrameDecoderOptions {
+                image_format: format.image_format(),
+                ..Default::default()
+            };
+            let mut key_frame_getter = client
+                .get_key_frames_with_options(input.as_str(), options)
+                .unwrap();
+
+            let output_options = OutputOptions {
+                atomic: true,
+                durable,
+                ..Default::default()
+            };
+
+            // Writing happens on its own thread so the next frame can be
+            // decoded and encoded while the previous one hits the disk.
+            let (frame_sender, frame_receiver) = mpsc::sync_channel::<(PathBuf, MediaKeyFrame)>(8);
+            let writer = thread::spawn(move || {
+                let mut written = Vec::new();
+                for (output_path, frame) in frame_receiver {
+                    output_options
+                        .write(&output_path, &frame)
+                        .expect("Failed to write frame to output file");
+                    written.push((output_path, frame.len() as u64));
+                }
+                written
+            });
 
             let mut i = 0;
             loop {
+                if interrupted.load(Ordering::SeqCst) {
+                    break;
+                }
                 let frame = key_frame_getter.get_keyframe();
                 if frame.is_none() {
                     break;
                 }
                 let frame = frame.unwrap().unwrap();
-                let output_path = Path::new(&output_dir).join(format!("{}.jpeg", i));
-                fs::write(output_path, frame).expect("Failed to write frame to output file");
+                let output_path =
+                    Path::new(&output_dir).join(format!("{}.{}", i, format.extension()));
+                frame_sender
+                    .send((output_path, frame))
+                    .expect("Writer thread stopped unexpectedly");
                 i += 1;
             }
+
+            // Let the writer drain whatever is still queued before reporting.
+            drop(frame_sender);
+            let written = writer.join().expect("Writer thread panicked");
+
+            let mut report = key_frame_getter.get_report();
+            for (output_path, bytes) in &written {
+                report.add_output(&output_path.to_string_lossy(), *bytes);
+            }
+            println!("{}", report);
+
+            if interrupted.load(Ordering::SeqCst) {
+                println!(
+                    "Interrupted after writing {} key frames to {}",
+                    i, output_dir
+                );
+                std::process::exit(130);
+            }
+            println!("Wrote {} key frames to {}", i, output_dir);
+        }
+        Command::Thumbnail {
+            input,
+            output,
+            durable,
+            timestamp_ms,
+            width,
+            height,
+        } => {
+            let thumbnail = client
+                .extract_thumbnail(input.as_str(), timestamp_ms, Size::new(width, height))
+                .unwrap();
+            let output_options = OutputOptions {
+                atomic: true,
+                durable,
+                ..Default::default()
+            };
+            output_options
+                .write(Path::new(&output), &thumbnail.data)
+                .expect("Failed to write thumbnail");
+            println!(
+                "Wrote a {}x{} thumbnail to {}",
+                thumbnail.size.width, thumbnail.size.height, output
+            );
+        }
+        Command::GenTestAsset {
+            output,
+            codec,
+            duration_ms,
+            fps,
+            width,
+            height,
+            b_frames,
+            interlaced,
+            rotation,
+            tone_hz,
+        } => {
+            let interrupted = interrupt_flag();
+            let options = TestAssetOptions {
+                interlaced,
+                size: Size::new(width, height),
+                duration_ms,
+                frame_rate: fps,
+                b_frames,
+                rotation_degrees: rotation,
+                tone_hz,
+                codec: codec.unwrap_or_default().as_str().into(),
+                ..Default::default()
+            };
+            let job = JobContext::new(
+                stabby::boxed::Box::new(InterruptObserver(interrupted.clone())).into(),
+            );
+            let result = client.generate_test_asset(output.as_str(), options, job);
+            if result.is_err() && interrupted.load(Ordering::SeqCst) {
+                println!("Interrupted, nothing was written to {}", output);
+                std::process::exit(130);
+            }
+            println!("{}", result.unwrap());
         }
     }
 }
@@ -0,0 +1,155 @@
+//! Conversions from the frames media-lib returns to `image` buffers and,
+//! with the `ndarray` feature, arrays, for handing frames to image
+//! processing or inference code.
+
+use image::{DynamicImage, RgbImage};
+use media_types::{RawPixelFormat, RawVideoFrame, VideoFrameBox, VideoFrameDynMut};
+#[cfg(feature = "ndarray")]
+use ndarray::{Array2, Array3};
+
+use crate::frames::Frame;
+use crate::MediaClientError;
+
+/// Decodes an encoded frame, e.g. a `MediaKeyFrame` or the data of an
+/// `EncodedImage`, whichever image format it was encoded as.
+pub fn decode_image(encoded: &[u8]) -> Result<DynamicImage, MediaClientError> {
+    image::load_from_memory(encoded).map_err(|e| MediaClientError::UnknownError(e.to_string()))
+}
+
+/// A raw frame as RGB. 4:2:0 frames are taken to be limited range BT.601,
+/// as the frame encoder does, and alpha is dropped.
+pub fn raw_frame_to_rgb_image(frame: &RawVideoFrame) -> Result<RgbImage, MediaClientError> {
+    let expected = frame.format.frame_bytes(frame.width, frame.height);
+    if frame.data.len() != expected {
+        return Err(MediaClientError::UnknownError(format!(
+            "Frame of {}x{} {:?} should be {} bytes, got {}",
+            frame.width,
+            frame.height,
+            frame.format,
+            expected,
+            frame.data.len()
+        )));
+    }
+
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let pixels = match frame.format {
+        RawPixelFormat::Rgb24 => frame.data.to_vec(),
+        RawPixelFormat::Rgba => frame
+            .data
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect(),
+        RawPixelFormat::Yuv420p => {
+            let chroma_width = width.div_ceil(2);
+            let (luma, chroma) = frame.data.split_at(width * height);
+            let (u, v) = chroma.split_at(chroma_width * height.div_ceil(2));
+            let mut pixels = Vec::with_capacity(width * height * 3);
+            for y in 0..height {
+                for x in 0..width {
+                    let chroma_index = (y / 2) * chroma_width + x / 2;
+                    pixels.extend(yuv_to_rgb(
+                        luma[y * width + x],
+                        u[chroma_index],
+                        v[chroma_index],
+                    ));
+                }
+            }
+            pixels
+        }
+    };
+    RgbImage::from_raw(frame.width, frame.height, pixels)
+        .ok_or_else(|| MediaClientError::UnknownError("Frame has the wrong size".to_string()))
+}
+
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let y = 1.164 * (y as f32 - 16.0);
+    let (u, v) = (u as f32 - 128.0, v as f32 - 128.0);
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    [
+        channel(y + 1.596 * v),
+        channel(y - 0.392 * u - 0.813 * v),
+        channel(y + 2.017 * u),
+    ]
+}
+
+/// One plane of a `VideoFrame` with the padding at the end of each row
+/// left out, `plane_width` bytes by `plane_height` rows. Only right for
+/// 8 bit formats, see `HwSurface::sw_format`.
+pub fn packed_plane(frame: &mut VideoFrameBox, index: u32) -> Result<Vec<u8>, MediaClientError> {
+    let plane_count = frame.plane_count();
+    if index >= plane_count {
+        return Err(MediaClientError::UnknownError(format!(
+            "Frame has {} planes, there's no plane {}",
+            plane_count, index
+        )));
+    }
+    let (width, height) = (
+        frame.plane_width(index) as usize,
+        frame.plane_height(index) as usize,
+    );
+    let stride = frame.stride(index) as usize;
+    let data = frame.data(index);
+    let complete = stride
+        .checked_mul(height)
+        .is_some_and(|size| data.len() >= size);
+    if stride < width || !complete {
+        return Err(MediaClientError::UnknownError(format!(
+            "Plane {} is {}x{} with rows {} bytes apart, but has {} bytes",
+            index,
+            width,
+            height,
+            stride,
+            data.len()
+        )));
+    }
+    // A zero stride only gets here for an empty plane.
+    Ok(data
+        .chunks(stride.max(1))
+        .take(height)
+        .flat_map(|row| &row[..width])
+        .copied()
+        .collect())
+}
+
+/// An RGB image as a `height x width x 3` array, the layout most inference
+/// code expects.
+#[cfg(feature = "ndarray")]
+pub fn rgb_image_to_array(image: RgbImage) -> Result<Array3<u8>, MediaClientError> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    // `RgbImage` is row major without padding, so the buffer has the shape
+    // as it is.
+    Array3::from_shape_vec((height, width, 3), image.into_raw())
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))
+}
+
+/// A plane of a `VideoFrame` as a `plane_height x plane_width` array, see
+/// `packed_plane`.
+#[cfg(feature = "ndarray")]
+pub fn plane_to_array(
+    frame: &mut VideoFrameBox,
+    index: u32,
+) -> Result<Array2<u8>, MediaClientError> {
+    let plane = packed_plane(frame, index)?;
+    let shape = (
+        frame.plane_height(index) as usize,
+        frame.plane_width(index) as usize,
+    );
+    Array2::from_shape_vec(shape, plane).map_err(|e| MediaClientError::UnknownError(e.to_string()))
+}
+
+impl Frame {
+    /// Decodes the frame, see `decode_image`.
+    pub fn to_image(&self) -> Result<DynamicImage, MediaClientError> {
+        decode_image(&self.data)
+    }
+
+    pub fn to_rgb_image(&self) -> Result<RgbImage, MediaClientError> {
+        Ok(self.to_image()?.into_rgb8())
+    }
+
+    /// Decodes the frame into a `height x width x 3` RGB array.
+    #[cfg(feature = "ndarray")]
+    pub fn to_array(&self) -> Result<Array3<u8>, MediaClientError> {
+        rgb_image_to_array(self.to_rgb_image()?)
+    }
+}
@@ -0,0 +1,89 @@
+use media_types::packet::MediaPacketIteratorDynMut;
+use media_types::{
+    AudioSamples, FrameBundle, MediaKeyFrame, MediaKeyFrameIteratorBox,
+    MediaKeyFrameIteratorDynMut, MediaLibError, MediaPacket, MediaPacketIteratorBox, VideoFrameBox,
+};
+
+use crate::MediaClientError;
+
+/// A stabby `Result` returned by media-lib, as a std `Result`.
+pub trait IntoStdResult<T> {
+    fn into_std(self) -> Result<T, MediaClientError>;
+}
+
+/// A stabby `Option<Result>` returned by media-lib's iterators, as a std
+/// `Result<Option>` so the error can be returned with `?`.
+pub trait IntoStdOption<T> {
+    fn into_std(self) -> Result<Option<T>, MediaClientError>;
+}
+
+// stabby's generic bounds differ between versions, so the conversions are
+// written out for each type that crosses the boundary.
+macro_rules! impl_into_std {
+    ($($item:ty),*) => {
+        $(
+            impl IntoStdResult<$item> for stabby::result::Result<$item, MediaLibError> {
+                fn into_std(self) -> Result<$item, MediaClientError> {
+                    self.match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+                }
+            }
+
+            impl IntoStdOption<$item>
+                for stabby::option::Option<stabby::result::Result<$item, MediaLibError>>
+            {
+                fn into_std(self) -> Result<Option<$item>, MediaClientError> {
+                    self.match_owned(|result| result.into_std().map(Some), || Ok(None))
+                }
+            }
+        )*
+    };
+}
+
+impl_into_std!(
+    AudioSamples,
+    MediaKeyFrame,
+    MediaPacket,
+    FrameBundle,
+    VideoFrameBox
+);
+
+/// Adds what was being done when an error happened, e.g. which input or
+/// frame it was for.
+pub trait ResultExt<T> {
+    fn context(self, context: &str) -> Result<T, MediaClientError>;
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T, MediaClientError>;
+}
+
+impl<T> ResultExt<T> for Result<T, MediaClientError> {
+    fn context(self, context: &str) -> Result<T, MediaClientError> {
+        self.with_context(|| context.to_string())
+    }
+
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T, MediaClientError> {
+        self.map_err(|e| MediaClientError::Context(context(), Box::new(e)))
+    }
+}
+
+/// `while let Some(frame) = frames.next_frame()? { ... }` over the frames of
+/// a `MediaKeyFrameIteratorBox`.
+pub trait KeyFrameIteratorExt {
+    fn next_frame(&mut self) -> Result<Option<MediaKeyFrame>, MediaClientError>;
+}
+
+impl KeyFrameIteratorExt for MediaKeyFrameIteratorBox {
+    fn next_frame(&mut self) -> Result<Option<MediaKeyFrame>, MediaClientError> {
+        self.get_keyframe().into_std()
+    }
+}
+
+/// `while let Some(packet) = packets.next_packet()? { ... }` over the packets
+/// of a `MediaPacketIteratorBox`.
+pub trait PacketIteratorExt {
+    fn next_packet(&mut self) -> Result<Option<MediaPacket>, MediaClientError>;
+}
+
+impl PacketIteratorExt for MediaPacketIteratorBox {
+    fn next_packet(&mut self) -> Result<Option<MediaPacket>, MediaClientError> {
+        self.get_packet().into_std()
+    }
+}
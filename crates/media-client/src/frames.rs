@@ -0,0 +1,86 @@
+use media_types::{ImageFormat, MediaKeyFrameIteratorBox, MediaKeyFrameIteratorDynMut, Size};
+
+use crate::ext::IntoStdOption;
+use crate::MediaClientError;
+
+/// A decoded frame, encoded as `format`, owned by the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    /// The `image_format` the frames were asked for.
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Position among the frames the iterator returned, from zero.
+    pub index: u64,
+}
+
+/// The frames of a `MediaKeyFrameIteratorBox` as a std `Iterator`, so
+/// `for`, `take`, `collect` and the rest work on them. Iteration stops after
+/// the first error.
+pub struct FrameIterator {
+    inner: MediaKeyFrameIteratorBox,
+    format: ImageFormat,
+    next_index: u64,
+    done: bool,
+}
+
+impl FrameIterator {
+    /// `format` is the `image_format` `inner` was opened with.
+    pub fn new(inner: MediaKeyFrameIteratorBox, format: ImageFormat) -> Self {
+        FrameIterator {
+            inner,
+            format,
+            next_index: 0,
+            done: false,
+        }
+    }
+
+    /// The iterator underneath, for what `Iterator` doesn't cover such as
+    /// seeking or the processing report.
+    pub fn inner_mut(&mut self) -> &mut MediaKeyFrameIteratorBox {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> MediaKeyFrameIteratorBox {
+        self.inner
+    }
+}
+
+/// For iterators of JPEG frames, as the default options give.
+impl From<MediaKeyFrameIteratorBox> for FrameIterator {
+    fn from(inner: MediaKeyFrameIteratorBox) -> Self {
+        FrameIterator::new(inner, ImageFormat::Jpeg)
+    }
+}
+
+impl Iterator for FrameIterator {
+    type Item = Result<Frame, MediaClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let data = match self.inner.get_keyframe().into_std() {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        let Size { width, height } = self.inner.get_size();
+        let index = self.next_index;
+        self.next_index += 1;
+        Some(Ok(Frame {
+            data: data.iter().copied().collect(),
+            format: self.format,
+            width,
+            height,
+            index,
+        }))
+    }
+}
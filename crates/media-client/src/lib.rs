@@ -1,17 +1,45 @@
 use std::path::PathBuf;
 
 use libloading::Library;
-use media_types::{MediaKeyFrameIterator, MediaLibError, MediaLibInit};
+use media_types::{
+    AnimationFormat, AudioFingerprint, ChannelExportOptions, ContentType, DatasetExportOptions,
+    DecoderUtilization, DecryptionOptions, DetectedSegment, EncodedImage, EncodedStoryboard,
+    FrameEncoderOptions, FrameExportOptions, GpuFrameIteratorBox, HardwareSupport,
+    HardwareSupportQuery, ImageSequenceOptions, InitOptions, JobContext, JpegOptions, KeyframeInfo,
+    LogLevel, MediaAudioIteratorBox, MediaFrameDecoderOptions, MediaFrameEncoderBox,
+    MediaFrameSessionBox, MediaInfo, MediaKeyFrameIteratorBox, MediaLibError, MediaLibInit,
+    MediaPacketIteratorBox, MediaSyncIteratorBox, MediaThreadedDecoderBox, OcrOptions,
+    OnScreenText, OverlayPlacement, OverlayPlacementOptions, PreviewBundle, PreviewBundleOptions,
+    PreviewOptions, ProcessingReport, RawVideoFrame, RedactionOptions, RegionDetectorBox,
+    ResampleOptions, RestreamOptions, ScrubProxyOptions, Size, StoryboardOptions, StreamInfo,
+    SyncOptions, TestAssetOptions, TextRecognizerBox, TimelapseOptions, VideoFingerprint,
+};
 use stabby::libloading::{StabbyLibrary, Symbol};
 
+#[cfg(feature = "image")]
+mod convert;
+mod ext;
+mod frames;
 #[cfg(test)]
 mod test;
+#[cfg(feature = "serde")]
+pub mod typed;
+mod watermark;
+#[cfg(feature = "image")]
+pub use convert::{decode_image, packed_plane, raw_frame_to_rgb_image};
+#[cfg(feature = "ndarray")]
+pub use convert::{plane_to_array, rgb_image_to_array};
+pub use ext::{IntoStdOption, IntoStdResult, KeyFrameIteratorExt, PacketIteratorExt, ResultExt};
+pub use frames::{Frame, FrameIterator};
 pub use media_types;
+pub use watermark::WatermarkCallbacks;
 
 #[derive(Debug)]
 pub enum MediaClientError {
     MediaLibError(MediaLibError),
     UnknownError(String),
+    /// What was being done when the inner error happened, see `ResultExt`.
+    Context(String, Box<MediaClientError>),
 }
 
 impl From<MediaLibError> for MediaClientError {
@@ -30,18 +58,29 @@ impl std::fmt::Display for MediaClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             MediaClientError::MediaLibError(e) => {
+                let no_video_stream = e.to_string();
                 let output = e.to_owned().match_owned(
                     |e| e.to_string(),
                     |e| e.to_string(),
                     |e| e.to_string(),
+                    |_| no_video_stream,
+                    |e| e.to_string(),
                 );
                 write!(f, "{}", output)
             }
             MediaClientError::UnknownError(s) => write!(f, "Unknown error: {}", s),
+            MediaClientError::Context(context, e) => write!(f, "{}: {}", context, e),
+        }
+    }
+}
+impl std::error::Error for MediaClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MediaClientError::Context(_, e) => Some(e.as_ref()),
+            _ => None,
         }
     }
 }
-impl std::error::Error for MediaClientError {}
 
 pub struct MediaClient {
     library: Library,
@@ -51,96 +90,4395 @@ impl MediaClient {
     pub fn get_key_frames(
         &self,
         input: &str,
-    ) -> Result<stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>), MediaClientError>
-    {
+    ) -> Result<MediaKeyFrameIteratorBox, MediaClientError> {
+        self.get_key_frames_with_options(input, MediaFrameDecoderOptions::default())
+    }
+
+    /// `input` is a path or an `http(s)://`, `rtsp://` or `rtmp://` URL,
+    /// the latter needing `InitOptions::network`.
+    pub fn get_key_frames_with_options(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<MediaKeyFrameIteratorBox, MediaClientError> {
         let get_key_frames = unsafe {
             self.library.get_stabbied::<extern "C" fn(
                 stabby::string::String,
+                MediaFrameDecoderOptions,
             ) -> stabby::result::Result<
-                stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>),
+                MediaKeyFrameIteratorBox,
                 MediaLibError,
-            >>(b"get_key_frames")
+            >>(b"get_key_frames_with_options")
         }
         .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
         let input_str = stabby::string::String::from(input);
-        let key_frame_interface = (get_key_frames)(input_str);
+        let key_frame_interface = (get_key_frames)(input_str, options);
         let out = key_frame_interface.match_owned(
             |key_frame_iter| std::result::Result::Ok(key_frame_iter),
             |e| std::result::Result::Err(MediaClientError::MediaLibError(e)),
         );
         out
     }
-}
 
-pub fn load(lib: &PathBuf) -> Result<MediaClient, MediaClientError> {
-    let library = unsafe { libloading::Library::new(lib) }
+    /// Like `get_key_frames_with_options` as a std `Iterator` of owned
+    /// frames.
+    pub fn frames(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<FrameIterator, MediaClientError> {
+        let format = options.image_format;
+        self.get_key_frames_with_options(input, options)
+            .map(|inner| FrameIterator::new(inner, format))
+    }
+
+    /// Like `get_key_frames_with_options` for an input already in memory,
+    /// such as a video downloaded from object storage. The whole input has
+    /// to be in `data`.
+    pub fn get_key_frames_from_memory(
+        &self,
+        data: &[u8],
+        options: MediaFrameDecoderOptions,
+    ) -> Result<MediaKeyFrameIteratorBox, MediaClientError> {
+        let get_key_frames_from_memory = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::vec::Vec<u8>,
+                MediaFrameDecoderOptions,
+            ) -> stabby::result::Result<
+                MediaKeyFrameIteratorBox,
+                MediaLibError,
+            >>(b"get_key_frames_from_memory")
+        }
         .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-    let init_media_lib = unsafe {
-        library
-            .get_stabbied::<extern "C" fn() -> stabby::result::Result<MediaLibInit, MediaLibError>>(
-                b"init_media_lib",
+        let mut bytes = stabby::vec::Vec::with_capacity(data.len());
+        for byte in data {
+            bytes.push(*byte);
+        }
+        (get_key_frames_from_memory)(bytes, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Every frame between `start_us` and `end_us` as JPEGs, last frame
+    /// first, for stepping backwards through a video.
+    pub fn get_frames_reversed(
+        &self,
+        input: &str,
+        start_us: i64,
+        end_us: i64,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<MediaKeyFrameIteratorBox, MediaClientError> {
+        let get_frames_reversed = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                i64,
+                i64,
+                MediaFrameDecoderOptions,
+            ) -> stabby::result::Result<
+                MediaKeyFrameIteratorBox,
+                MediaLibError,
+            >>(b"get_frames_reversed")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (get_frames_reversed)(input_str, start_us, end_us, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Every frame of the input left on the GPU it was decoded on. Fails
+    /// when the input isn't decoded on a GPU.
+    pub fn get_gpu_frames(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<GpuFrameIteratorBox, MediaClientError> {
+        let get_gpu_frames = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                MediaFrameDecoderOptions,
             )
-    }?;
+                -> stabby::result::Result<GpuFrameIteratorBox, MediaLibError>>(
+                b"get_gpu_frames"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-    init_media_lib().match_owned(
-        |init| std::result::Result::Ok(init),
-        |e| std::result::Result::Err(MediaClientError::MediaLibError(e)),
-    )?;
+        let input_str = stabby::string::String::from(input);
+        (get_gpu_frames)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
 
-    let init_logging = unsafe { library.get_stabbied::<extern "C" fn()>(b"init_logging") }?;
-    init_logging();
+    /// Every frame of the input where it was decoded, on the GPU when it's
+    /// decoded on one and in system memory otherwise, e.g. for reading the
+    /// HDR metadata of frames decoded in software.
+    pub fn get_video_frames(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<GpuFrameIteratorBox, MediaClientError> {
+        let get_video_frames = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                MediaFrameDecoderOptions,
+            )
+                -> stabby::result::Result<GpuFrameIteratorBox, MediaLibError>>(
+                b"get_video_frames"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-    Ok(MediaClient { library: library })
-}
+        let input_str = stabby::string::String::from(input);
+        (get_video_frames)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
 
-#[cfg(test)]
-mod tests {
-    use media_types::MediaKeyFrameIteratorDynMut;
+    /// Opens a session for stepping through the input one frame at a time.
+    pub fn open_frame_session(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<MediaFrameSessionBox, MediaClientError> {
+        let open_frame_session = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                MediaFrameDecoderOptions,
+            )
+                -> stabby::result::Result<MediaFrameSessionBox, MediaLibError>>(
+                b"open_frame_session",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-    use super::*;
+        let input_str = stabby::string::String::from(input);
+        (open_frame_session)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
 
-    #[test]
-    fn it_can_get_key_frames() {
-        let lib = test::get_media_client_lib();
-        let client = load(&lib).unwrap();
+    /// Starts encoding raw frames to `output`, on the GPU where there's an
+    /// encoder for it. The container is picked from the output's extension.
+    pub fn new_frame_encoder(
+        &self,
+        output: &str,
+        options: FrameEncoderOptions,
+    ) -> Result<MediaFrameEncoderBox, MediaClientError> {
+        let new_frame_encoder = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                FrameEncoderOptions,
+            )
+                -> stabby::result::Result<MediaFrameEncoderBox, MediaLibError>>(
+                b"new_frame_encoder",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-        // Load the test movie file
-        let test_movie = test::get_test_data_file("test.mp4");
+        let output_str = stabby::string::String::from(output);
+        (new_frame_encoder)(output_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
 
-        // Call get_key_frames with the test movie path
-        let key_frames_result = client.get_key_frames(test_movie.to_str().unwrap());
+    /// Lists keyframe timestamps and byte offsets without decoding anything.
+    pub fn list_keyframes(&self, input: &str) -> Result<Vec<KeyframeInfo>, MediaClientError> {
+        let list_keyframes = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<KeyframeInfo>,
+                MediaLibError,
+            >>(b"list_keyframes")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-        // Assert that the result is Ok
-        assert!(key_frames_result.is_ok(), "Failed to get key frames");
+        let input_str = stabby::string::String::from(input);
+        (list_keyframes)(input_str).match_owned(
+            |keyframes| Ok(keyframes.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
 
-        // Unwrap the result to get the MediaKeyFrameIterator
-        let mut key_frame_iterator = key_frames_result.unwrap();
+    /// Flags the likely intro and credits of an episode. Other episodes of
+    /// the same series are compared against it to find the intro, credits
+    /// can be found without them but with less confidence.
+    pub fn detect_intro_credits(
+        &self,
+        input: &str,
+        other_episodes: &[&str],
+    ) -> Result<Vec<DetectedSegment>, MediaClientError> {
+        let detect_intro_credits = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::vec::Vec<stabby::string::String>,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<DetectedSegment>,
+                MediaLibError,
+            >>(b"detect_intro_credits")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-        // Get the first key frame to ensure it works
-        let first_frame = key_frame_iterator.get_keyframe();
-        assert!(
-            first_frame.is_some(),
-            "No key frames found in the test video"
-        );
+        let input_str = stabby::string::String::from(input);
+        let mut episodes = stabby::vec::Vec::with_capacity(other_episodes.len());
+        for episode in other_episodes {
+            episodes.push(stabby::string::String::from(*episode));
+        }
+        (detect_intro_credits)(input_str, episodes).match_owned(
+            |segments| Ok(segments.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
 
-        assert!(
-            first_frame.unwrap().unwrap().len() > 0,
-            "No key frames found in the test video"
-        );
+    /// Colour bars and 1kHz tone at the head of the input, as segments to
+    /// trim before making proxies. Empty when the input has no leader.
+    pub fn detect_leader(&self, input: &str) -> Result<Vec<DetectedSegment>, MediaClientError> {
+        let detect_leader = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<DetectedSegment>,
+                MediaLibError,
+            >>(b"detect_leader")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
 
-        // Check the dimensions
-        let width = key_frame_iterator.get_width();
-        let height = key_frame_iterator.get_height();
-        assert!(width > 0 && height > 0, "Invalid frame dimensions");
+        let input_str = stabby::string::String::from(input);
+        (detect_leader)(input_str).match_owned(
+            |segments| Ok(segments.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
 
-        loop {
-            let frame = key_frame_iterator.get_keyframe();
-            if frame.is_none() {
-                break;
-            }
-            frame.unwrap().unwrap();
+    /// Reads the text on screen every `options.interval_ms` with the host's
+    /// OCR engine, e.g. to find slates or check burnt in warnings. Samples
+    /// in a row that read the same come back as one entry.
+    pub fn extract_on_screen_text(
+        &self,
+        input: &str,
+        options: OcrOptions,
+        recognizer: TextRecognizerBox,
+        job: JobContext,
+    ) -> Result<Vec<OnScreenText>, MediaClientError> {
+        let extract_on_screen_text = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                OcrOptions,
+                TextRecognizerBox,
+                JobContext,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<OnScreenText>,
+                MediaLibError,
+            >>(b"extract_on_screen_text")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (extract_on_screen_text)(input_str, options, recognizer, job).match_owned(
+            |texts| Ok(texts.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Where to put an overlay such as a watermark: the corners of the
+    /// input's frame, calmest first, by the detail and motion under each.
+    pub fn suggest_overlay_placement(
+        &self,
+        input: &str,
+        options: OverlayPlacementOptions,
+    ) -> Result<Vec<OverlayPlacement>, MediaClientError> {
+        let suggest_overlay_placement = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                OverlayPlacementOptions,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<OverlayPlacement>,
+                MediaLibError,
+            >>(b"suggest_overlay_placement")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (suggest_overlay_placement)(input_str, options).match_owned(
+            |placements| Ok(placements.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Iterates over the input's encoded packets without decoding them.
+    pub fn get_packets(&self, input: &str) -> Result<MediaPacketIteratorBox, MediaClientError> {
+        let get_packets = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            ) -> stabby::result::Result<
+                MediaPacketIteratorBox,
+                MediaLibError,
+            >>(b"get_packets")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (get_packets)(input_str).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Decodes the input's best audio stream to `format`, whatever it's
+    /// stored as.
+    pub fn decode_audio(
+        &self,
+        input: &str,
+        format: ResampleOptions,
+    ) -> Result<MediaAudioIteratorBox, MediaClientError> {
+        let decode_audio = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                ResampleOptions,
+            ) -> stabby::result::Result<
+                MediaAudioIteratorBox,
+                MediaLibError,
+            >>(b"decode_audio")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (decode_audio)(input_str, format)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Decodes `inputs` side by side, e.g. the cameras of a multi-camera
+    /// rig, returning what each of them shows at the same moments. See
+    /// `SyncOptions` for how they're lined up.
+    pub fn get_synced_frames(
+        &self,
+        inputs: &[&str],
+        options: SyncOptions,
+    ) -> Result<MediaSyncIteratorBox, MediaClientError> {
+        let get_synced_frames = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::vec::Vec<stabby::string::String>,
+                SyncOptions,
+            )
+                -> stabby::result::Result<MediaSyncIteratorBox, MediaLibError>>(
+                b"get_synced_frames",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let mut input_strs = stabby::vec::Vec::with_capacity(inputs.len());
+        for input in inputs {
+            input_strs.push(stabby::string::String::from(*input));
+        }
+        (get_synced_frames)(input_strs, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Keyframes of `input` decoded on a worker thread ahead of the caller,
+    /// so taking one doesn't wait for it to be decoded. See
+    /// `frame_queue_size` for how far ahead.
+    pub fn get_threaded_frames(
+        &self,
+        input: &str,
+        options: MediaFrameDecoderOptions,
+    ) -> Result<MediaThreadedDecoderBox, MediaClientError> {
+        let get_threaded_frames = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                MediaFrameDecoderOptions,
+            ) -> stabby::result::Result<
+                MediaThreadedDecoderBox,
+                MediaLibError,
+            >>(b"get_threaded_frames")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (get_threaded_frames)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Computes the chromaprint fingerprint of the input's audio. Requires
+    /// media-lib to be built with the `chromaprint` feature.
+    pub fn fingerprint_audio(&self, input: &str) -> Result<AudioFingerprint, MediaClientError> {
+        let fingerprint_audio = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            )
+                -> stabby::result::Result<AudioFingerprint, MediaLibError>>(
+                b"fingerprint_audio"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (fingerprint_audio)(input_str).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Computes a shot level fingerprint of the input's video, compare two
+    /// of them with `VideoFingerprint::similarity`.
+    pub fn fingerprint_video(
+        &self,
+        input: &str,
+        job: JobContext,
+    ) -> Result<VideoFingerprint, MediaClientError> {
+        let fingerprint_video = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                JobContext,
+            )
+                -> stabby::result::Result<VideoFingerprint, MediaLibError>>(
+                b"fingerprint_video"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (fingerprint_video)(input_str, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// A JPEG preview for any input: a frame of a video chosen as
+    /// `options.poster` asks, the cover art of an audio file, or a rendering
+    /// of the audio otherwise.
+    pub fn get_preview_image(
+        &self,
+        input: &str,
+        options: PreviewOptions,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let get_preview_image = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                PreviewOptions,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"get_preview_image",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (get_preview_image)(input_str, options).match_owned(
+            |preview| Ok(preview.to_vec()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// A single JPEG strip of `count` evenly spaced frames side by side,
+    /// each `cell_width` pixels wide, for timeline UIs.
+    pub fn filmstrip(
+        &self,
+        input: &str,
+        count: u32,
+        cell_width: u32,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let filmstrip = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                u32,
+                u32,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"filmstrip"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (filmstrip)(input_str, count, cell_width).match_owned(
+            |strip| Ok(strip.to_vec()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// A JPEG sprite sheet of a frame every `options.interval_ms` and the
+    /// WebVTT cues locating each tile in it, for seekbar previews.
+    pub fn generate_storyboard(
+        &self,
+        input: &str,
+        options: StoryboardOptions,
+    ) -> Result<EncodedStoryboard, MediaClientError> {
+        let generate_storyboard = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                StoryboardOptions,
+            )
+                -> stabby::result::Result<EncodedStoryboard, MediaLibError>>(
+                b"generate_storyboard"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (generate_storyboard)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// A poster, filmstrip, storyboard (sprite and WebVTT) and animated
+    /// preview of `input` from one decode, optionally also written to
+    /// `options.output_dir`, so a file can be onboarded in one call.
+    pub fn generate_preview_bundle(
+        &self,
+        input: &str,
+        options: PreviewBundleOptions,
+    ) -> Result<PreviewBundle, MediaClientError> {
+        let generate_preview_bundle = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                PreviewBundleOptions,
+            )
+                -> stabby::result::Result<PreviewBundle, MediaLibError>>(
+                b"generate_preview_bundle"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (generate_preview_bundle)(input_str, options)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// A looping animation of `duration_ms` of the input from `start_ms`, at
+    /// `fps` frames a second (at most 50) and fitted inside `size`, for
+    /// hover-preview thumbnails.
+    pub fn generate_preview_animation(
+        &self,
+        input: &str,
+        start_ms: u64,
+        duration_ms: u64,
+        fps: u32,
+        size: Size,
+        format: AnimationFormat,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let generate_preview_animation = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                u64,
+                u64,
+                u32,
+                Size,
+                AnimationFormat,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"generate_preview_animation",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (generate_preview_animation)(input_str, start_ms, duration_ms, fps, size, format)
+            .match_owned(
+                |animation| Ok(animation.to_vec()),
+                |e| Err(MediaClientError::MediaLibError(e)),
+            )
+    }
+
+    /// The frame shown at `timestamp_us` at full resolution, as a JPEG, PNG
+    /// or TIFF. PNG and TIFF keep 16 bits per channel for high bit depth
+    /// sources when `options.high_bit_depth` is set.
+    pub fn export_frame(
+        &self,
+        input: &str,
+        timestamp_us: i64,
+        options: FrameExportOptions,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let export_frame = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                i64,
+                FrameExportOptions,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"export_frame"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (export_frame)(input_str, timestamp_us, options).match_owned(
+            |image| Ok(image.to_vec()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Same as `export_frame` with what `detector` finds in the frame, e.g.
+    /// faces, blurred or filled in per `redaction`.
+    pub fn export_redacted_frame(
+        &self,
+        input: &str,
+        timestamp_us: i64,
+        options: FrameExportOptions,
+        redaction: RedactionOptions,
+        detector: RegionDetectorBox,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let export_redacted_frame = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                i64,
+                FrameExportOptions,
+                RedactionOptions,
+                RegionDetectorBox,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"export_redacted_frame",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (export_redacted_frame)(input_str, timestamp_us, options, redaction, detector).match_owned(
+            |image| Ok(image.to_vec()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// A JPEG thumbnail of the frame shown at `timestamp_ms`, upright and
+    /// scaled to fit inside `target_size`. A zero width or height follows
+    /// the aspect ratio, a zero size keeps the frame's own.
+    pub fn extract_thumbnail(
+        &self,
+        input: &str,
+        timestamp_ms: u64,
+        target_size: Size,
+    ) -> Result<EncodedImage, MediaClientError> {
+        self.extract_thumbnail_with_options(
+            input,
+            timestamp_ms,
+            target_size,
+            JpegOptions::default(),
+        )
+    }
+
+    /// Like `extract_thumbnail`, encoded as `jpeg` asks.
+    pub fn extract_thumbnail_with_options(
+        &self,
+        input: &str,
+        timestamp_ms: u64,
+        target_size: Size,
+        jpeg: JpegOptions,
+    ) -> Result<EncodedImage, MediaClientError> {
+        let extract_thumbnail = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                u64,
+                Size,
+                JpegOptions,
+            )
+                -> stabby::result::Result<EncodedImage, MediaLibError>>(
+                b"extract_thumbnail_with_options",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (extract_thumbnail)(input_str, timestamp_ms, target_size, jpeg)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// JPEG encodes a raw frame at `quality` (1 to 100) with the same
+    /// encoder key frames go through, nvJPEG included.
+    pub fn encode_frame_jpeg(
+        &self,
+        frame: RawVideoFrame,
+        quality: u32,
+    ) -> Result<Vec<u8>, MediaClientError> {
+        let encode_frame_jpeg = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                RawVideoFrame,
+                u32,
+            )
+                -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError>>(
+                b"encode_frame_jpeg",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        (encode_frame_jpeg)(frame, quality).match_owned(
+            |jpeg| Ok(jpeg.to_vec()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Condenses `input` into a time-lapse at `output`, the container and
+    /// codec are picked from the output's extension.
+    pub fn timelapse(
+        &self,
+        input: &str,
+        output: &str,
+        options: TimelapseOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let timelapse = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                TimelapseOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"timelapse"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let output_str = stabby::string::String::from(output);
+        (timelapse)(input_str, output_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Synthesizes a video at `output` as `options` describe, for tests and
+    /// bug reproductions that shouldn't depend on somebody's files. The
+    /// container is picked from the output's extension.
+    pub fn generate_test_asset(
+        &self,
+        output: &str,
+        options: TestAssetOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let generate_test_asset = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                TestAssetOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"generate_test_asset"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let output_str = stabby::string::String::from(output);
+        (generate_test_asset)(output_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Writes the frames of `input` to `output_dir` as numbered images, see
+    /// `ImageSequenceOptions` for which frames and how they're named. The
+    /// report lists every image written.
+    pub fn export_image_sequence(
+        &self,
+        input: &str,
+        output_dir: &str,
+        options: ImageSequenceOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let export_image_sequence = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                ImageSequenceOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"export_image_sequence",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let output_dir_str = stabby::string::String::from(output_dir);
+        (export_image_sequence)(input_str, output_dir_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Writes sampled frames of every one of `inputs` to `output_dir` with
+    /// a manifest of where each came from, for building training datasets.
+    /// Inputs that can't be decoded are reported as warnings rather than
+    /// failing the export.
+    pub fn export_dataset(
+        &self,
+        inputs: &[&str],
+        output_dir: &str,
+        options: DatasetExportOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let export_dataset = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::vec::Vec<stabby::string::String>,
+                stabby::string::String,
+                DatasetExportOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"export_dataset"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let mut input_strs = stabby::vec::Vec::with_capacity(inputs.len());
+        for input in inputs {
+            input_strs.push(stabby::string::String::from(*input));
+        }
+        let output_dir_str = stabby::string::String::from(output_dir);
+        (export_dataset)(input_strs, output_dir_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Whether this machine would decode video like `query` describes on
+    /// the GPU and on which device, for routing jobs before any file is
+    /// opened.
+    pub fn hardware_support(
+        &self,
+        query: HardwareSupportQuery,
+    ) -> Result<HardwareSupport, MediaClientError> {
+        let hardware_support = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                HardwareSupportQuery,
+            )
+                -> stabby::result::Result<HardwareSupport, MediaLibError>>(
+                b"hardware_support"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        (hardware_support)(query).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// How busy the video decoder of each NVIDIA GPU on this machine is,
+    /// counting every process using it, for deciding whether to start
+    /// another hardware decode here or decode in software. Needs media-lib
+    /// built with the `nvml` feature and fails where the NVIDIA driver
+    /// isn't installed.
+    pub fn decoder_utilization(&self) -> Result<Vec<DecoderUtilization>, MediaClientError> {
+        let decoder_utilization = unsafe {
+            self.library
+                .get_stabbied::<extern "C" fn() -> stabby::result::Result<
+                    stabby::vec::Vec<DecoderUtilization>,
+                    MediaLibError,
+                >>(b"decoder_utilization")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        (decoder_utilization)().match_owned(
+            |devices| Ok(devices.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Writes a low resolution proxy of `input` for scrubbing in a web
+    /// player: an HLS playlist at `playlist` (e.g. `proxy.m3u8`) indexing
+    /// the byte ranges of a single fragmented MP4 next to it (`proxy.mp4`).
+    pub fn scrub_proxy(
+        &self,
+        input: &str,
+        playlist: &str,
+        options: ScrubProxyOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let scrub_proxy = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                ScrubProxyOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"scrub_proxy"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let playlist_str = stabby::string::String::from(playlist);
+        (scrub_proxy)(input_str, playlist_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Same as `scrub_proxy` with what `detector` finds in each frame
+    /// hidden before it's encoded, for proxies that may be shared outside
+    /// where the footage can be. The detector runs every
+    /// `redaction.detect_interval` frames.
+    pub fn redacted_scrub_proxy(
+        &self,
+        input: &str,
+        playlist: &str,
+        options: ScrubProxyOptions,
+        redaction: RedactionOptions,
+        detector: RegionDetectorBox,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let redacted_scrub_proxy = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                ScrubProxyOptions,
+                RedactionOptions,
+                RegionDetectorBox,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"redacted_scrub_proxy"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let playlist_str = stabby::string::String::from(playlist);
+        (redacted_scrub_proxy)(input_str, playlist_str, options, redaction, detector, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Exports channels of the input's audio, e.g. each channel of 8 channel
+    /// broadcast audio to its own mono file (`out_ch1.wav`, `out_ch2.wav`,
+    /// ...) or channels 3 and 4 together to `output`. With a `speed` it
+    /// renders sped up previews, such as a 2× preview of a podcast.
+    pub fn export_audio_channels(
+        &self,
+        input: &str,
+        output: &str,
+        options: ChannelExportOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let export_audio_channels = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                ChannelExportOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"export_audio_channels",
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let output_str = stabby::string::String::from(output);
+        (export_audio_channels)(input_str, output_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Publishes the input's video and audio to a live target such as
+    /// rtmp:// or srt:// without re-encoding, returning once the input is
+    /// done. Load the library with `InitOptions::network` set.
+    pub fn restream(
+        &self,
+        input: &str,
+        url: &str,
+        options: RestreamOptions,
+        job: JobContext,
+    ) -> Result<ProcessingReport, MediaClientError> {
+        let restream = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                stabby::string::String,
+                RestreamOptions,
+                JobContext,
+            )
+                -> stabby::result::Result<ProcessingReport, MediaLibError>>(
+                b"restream"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        let url_str = stabby::string::String::from(url);
+        (restream)(input_str, url_str, options, job)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Duration, bitrate and the codec, size, frame rate and pixel format of
+    /// each stream, from the container headers without decoding. Inputs
+    /// whose container has no duration get one from their last timestamps
+    /// or bitrate, see `MediaInfo::duration_source`.
+    pub fn probe(&self, input: &str) -> Result<MediaInfo, MediaClientError> {
+        let probe_media = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            )
+                -> stabby::result::Result<MediaInfo, MediaLibError>>(
+                b"probe_media"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (probe_media)(input_str).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Like `probe` for an input encrypted with keys the caller holds, see
+    /// `DecryptionOptions`.
+    pub fn probe_with_decryption(
+        &self,
+        input: &str,
+        decryption: DecryptionOptions,
+    ) -> Result<MediaInfo, MediaClientError> {
+        let probe_media_with_decryption = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                DecryptionOptions,
+            )
+                -> stabby::result::Result<MediaInfo, MediaLibError>>(
+                b"probe_media_with_decryption"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (probe_media_with_decryption)(input_str, decryption)
+            .match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// The input's video streams, for choosing one with
+    /// `MediaFrameDecoderOptions::stream_index` when there are several
+    /// angles or an embedded preview.
+    pub fn list_video_streams(&self, input: &str) -> Result<Vec<StreamInfo>, MediaClientError> {
+        let list_video_streams = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<StreamInfo>,
+                MediaLibError,
+            >>(b"list_video_streams")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (list_video_streams)(input_str).match_owned(
+            |streams| Ok(streams.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Every caption of the text subtitle stream (SRT, ASS, mov_text...) at
+    /// `stream_index`, as plain text with when it's shown. Bitmap subtitles
+    /// fail, see `probe` for which streams are subtitles.
+    pub fn extract_subtitles(
+        &self,
+        input: &str,
+        stream_index: u32,
+    ) -> Result<Vec<SubtitleCue>, MediaClientError> {
+        let extract_subtitles = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+                u32,
+            ) -> stabby::result::Result<
+                stabby::vec::Vec<SubtitleCue>,
+                MediaLibError,
+            >>(b"extract_subtitles")
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (extract_subtitles)(input_str, stream_index).match_owned(
+            |cues| Ok(cues.iter().cloned().collect()),
+            |e| Err(MediaClientError::MediaLibError(e)),
+        )
+    }
+
+    /// Identifies the container from the first few KB of the file, without
+    /// opening it for decoding.
+    pub fn sniff(&self, input: &str) -> Result<ContentType, MediaClientError> {
+        let sniff = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::string::String,
+            )
+                -> stabby::result::Result<ContentType, MediaLibError>>(
+                b"sniff"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let input_str = stabby::string::String::from(input);
+        (sniff)(input_str).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Like `sniff`, for the start of an input already in memory such as an
+    /// upload in progress.
+    pub fn sniff_bytes(&self, data: &[u8]) -> Result<ContentType, MediaClientError> {
+        let sniff_bytes = unsafe {
+            self.library.get_stabbied::<extern "C" fn(
+                stabby::vec::Vec<u8>,
+            )
+                -> stabby::result::Result<ContentType, MediaLibError>>(
+                b"sniff_bytes"
+            )
+        }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+        let mut bytes = stabby::vec::Vec::with_capacity(data.len());
+        for byte in data {
+            bytes.push(*byte);
+        }
+        (sniff_bytes)(bytes).match_owned(Ok, |e| Err(MediaClientError::MediaLibError(e)))
+    }
+
+    /// Sets the log level for FFmpeg and media-lib at runtime.
+    pub fn set_log_level(&self, level: LogLevel) -> Result<(), MediaClientError> {
+        let set_log_level = unsafe {
+            self.library
+                .get_stabbied::<extern "C" fn(LogLevel)>(b"set_log_level")
+        }?;
+        set_log_level(level);
+        Ok(())
+    }
+
+    /// Sets the log level for one module, e.g. "media_lib::media", or for
+    /// FFmpeg with "ffmpeg".
+    pub fn set_log_filter(&self, module: &str, level: LogLevel) -> Result<(), MediaClientError> {
+        let set_log_filter = unsafe {
+            self.library
+                .get_stabbied::<extern "C" fn(stabby::string::String, LogLevel)>(b"set_log_filter")
+        }?;
+        set_log_filter(stabby::string::String::from(module), level);
+        Ok(())
+    }
+}
+
+/// Names a file the host already has open, for passing as the input to any
+/// method when media-lib can't open paths itself (e.g. under landlock or
+/// seccomp). The descriptor is duplicated, it can be closed once the call
+/// returns.
+#[cfg(unix)]
+pub fn fd_input(fd: std::os::unix::io::RawFd) -> String {
+    format!("fd:{}", fd)
+}
+
+/// Names a file the host already has open, see `fd_input`.
+#[cfg(windows)]
+pub fn handle_input(handle: std::os::windows::io::RawHandle) -> String {
+    format!("handle:{}", handle as usize)
+}
+
+impl Drop for MediaClient {
+    fn drop(&mut self) {
+        // Removes media-lib's temporary files once no client uses them.
+        let release_media_lib = unsafe {
+            self.library
+                .get_stabbied::<extern "C" fn()>(b"release_media_lib")
+        };
+        if let Ok(release_media_lib) = release_media_lib {
+            release_media_lib();
+        }
+    }
+}
+
+pub fn load(lib: &PathBuf) -> Result<MediaClient, MediaClientError> {
+    load_with_options(lib, InitOptions::default())
+}
+
+pub fn load_with_options(
+    lib: &PathBuf,
+    options: InitOptions,
+) -> Result<MediaClient, MediaClientError> {
+    let library = unsafe { libloading::Library::new(lib) }
+        .map_err(|e| MediaClientError::UnknownError(e.to_string()))?;
+
+    let init_media_lib = unsafe {
+        library.get_stabbied::<extern "C" fn(
+            InitOptions,
+        ) -> stabby::result::Result<MediaLibInit, MediaLibError>>(
+            b"init_media_lib_with_options"
+        )
+    }?;
+
+    init_media_lib(options).match_owned(
+        |init| std::result::Result::Ok(init),
+        |e| std::result::Result::Err(MediaClientError::MediaLibError(e)),
+    )?;
+
+    let init_logging = unsafe { library.get_stabbied::<extern "C" fn()>(b"init_logging") }?;
+    init_logging();
+
+    Ok(MediaClient { library: library })
+}
+
+#[cfg(test)]
+mod tests {
+    use media_types::{
+        AudioPreviewStyle, CancelHandleDyn, ChecksumAlgorithm, ChromaSubsampling, Color,
+        DurationSource, GpuFrameIteratorDynMut, HardwareBackend, ImageFormat, JobObserver,
+        ManifestFormat, MediaAudioIteratorDynMut, MediaFrameEncoderDynMut, MediaFrameSessionDynMut,
+        MediaKeyFrameIteratorDynMut, MediaSyncIteratorDynMut, MediaThreadedDecoderDynMut, OcrImage,
+        OutputOptions, RawPixelFormat, SampleFormat, ScalingMode, SegmentKind, StreamHealth,
+        StreamHealthObserver, StreamHealthStatus, SyncClock, TextRecognizer, ToneMapping,
+        VideoFrameDynMut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_can_get_key_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+
+        // Load the test movie file
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        // Call get_key_frames with the test movie path
+        let key_frames_result = client.get_key_frames(test_movie.to_str().unwrap());
+
+        // Assert that the result is Ok
+        assert!(key_frames_result.is_ok(), "Failed to get key frames");
+
+        // Unwrap the result to get the MediaKeyFrameIterator
+        let mut key_frame_iterator = key_frames_result.unwrap();
+
+        // Get the first key frame to ensure it works
+        let first_frame = key_frame_iterator.get_keyframe();
+        assert!(
+            first_frame.is_some(),
+            "No key frames found in the test video"
+        );
+
+        assert!(
+            first_frame.unwrap().unwrap().len() > 0,
+            "No key frames found in the test video"
+        );
+
+        // Check the dimensions
+        let width = key_frame_iterator.get_width();
+        let height = key_frame_iterator.get_height();
+        assert!(width > 0 && height > 0, "Invalid frame dimensions");
+        assert_eq!(
+            key_frame_iterator.get_size(),
+            media_types::Size::new(width, height)
+        );
+
+        loop {
+            let frame = key_frame_iterator.get_keyframe();
+            if frame.is_none() {
+                break;
+            }
+            frame.unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn it_can_fall_back_to_a_waveform_for_audio_only_inputs() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("waveform_fallback");
+        let video = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 2000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(video.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        let audio = dir.join("input.wav");
+        let options = ChannelExportOptions {
+            split: false,
+            ..Default::default()
+        };
+        client
+            .export_audio_channels(
+                video.to_str().unwrap(),
+                audio.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+
+        assert!(client
+            .get_key_frames_with_options(
+                audio.to_str().unwrap(),
+                MediaFrameDecoderOptions::default()
+            )
+            .is_err());
+
+        let options = MediaFrameDecoderOptions {
+            waveform_fallback: true,
+            ..Default::default()
+        };
+        let mut iterator = client
+            .get_key_frames_with_options(audio.to_str().unwrap(), options)
+            .unwrap();
+        assert_eq!(iterator.get_size(), Size::new(640, 360));
+        let preview = iterator.get_keyframe().unwrap().unwrap();
+        assert!(preview.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+        assert!(iterator.get_keyframe().is_none());
+        #[cfg(feature = "image")]
+        {
+            let image = decode_image(&preview).unwrap().to_rgb8();
+            assert_eq!((image.width(), image.height()), (640, 360));
+            // The tone is drawn through the middle over the background.
+            let (middle, top) = (image.get_pixel(320, 180), image.get_pixel(320, 2));
+            assert!(middle.0.iter().all(|&value| value > 0xb0), "{:?}", middle);
+            assert!(top.0.iter().all(|&value| value < 0x50), "{:?}", top);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_sample_a_fixed_number_of_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            sample_count: 5,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        loop {
+            let frame = key_frame_iterator.get_keyframe();
+            if frame.is_none() {
+                break;
+            }
+            let frame = frame.unwrap().unwrap();
+            assert!(!frame.is_empty());
+            frames.push(frame.to_vec());
+        }
+        // Points sharing a keyframe only return it once.
+        assert!(!frames.is_empty() && frames.len() <= 5);
+        for (index, frame) in frames.iter().enumerate() {
+            assert!(!frames[..index].contains(frame), "Frame {} repeated", index);
+        }
+    }
+
+    #[test]
+    fn it_can_step_over_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let count_frames = |options: MediaFrameDecoderOptions| {
+            let mut key_frame_iterator = client
+                .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+                .unwrap();
+            let mut count = 0;
+            while let Some(frame) = key_frame_iterator.get_keyframe().as_ref() {
+                assert!(frame.is_ok());
+                count += 1;
+            }
+            count
+        };
+
+        let keyframes = count_frames(MediaFrameDecoderOptions::default());
+        let every_other_keyframe = count_frames(MediaFrameDecoderOptions {
+            frame_step: 2,
+            ..Default::default()
+        });
+        assert_eq!(every_other_keyframe, (keyframes + 1) / 2);
+    }
+
+    #[test]
+    fn it_can_decode_a_time_range() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            start_time_ms: 1000,
+            end_time_ms: 3000,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+
+        let mut count = 0;
+        while let Some(frame) = key_frame_iterator.get_keyframe().as_ref() {
+            assert!(frame.is_ok());
+            let position_us = key_frame_iterator.get_progress().position_us.unwrap();
+            // The first frame can start a little before 1s, it's the one
+            // still showing then.
+            assert!(position_us > 900_000 && position_us <= 3_000_000);
+            count += 1;
+        }
+        assert!(count > 0);
+
+        let options = MediaFrameDecoderOptions {
+            start_time_ms: 3000,
+            end_time_ms: 1000,
+            ..Default::default()
+        };
+        assert!(client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .is_err());
+    }
+
+    #[test]
+    fn it_writes_diagnostics_when_an_input_fails() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("diagnostics");
+        let broken = dir.join("broken.mp4");
+        std::fs::write(&broken, b"not a video ".repeat(100)).unwrap();
+        let diagnostics = dir.join("diagnostics");
+
+        let options = MediaFrameDecoderOptions {
+            diagnostics_dir: stabby::option::Option::Some(diagnostics.to_str().unwrap().into()),
+            ..Default::default()
+        };
+        assert!(client
+            .get_key_frames_with_options(broken.to_str().unwrap(), options)
+            .is_err());
+
+        let bundles: Vec<_> = std::fs::read_dir(&diagnostics)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(bundles.len(), 1);
+        let bundle = &bundles[0];
+        assert!(bundle
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("broken-"));
+        let error = std::fs::read_to_string(bundle.join("error.txt")).unwrap();
+        assert!(error.contains(broken.to_str().unwrap()));
+        for file in ["probe.txt", "packets.txt", "filter_graph.txt", "log.txt"] {
+            assert!(bundle.join(file).exists(), "{} is missing", file);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_passes_through_frames_that_are_already_srgb() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            convert_to_srgb: true,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let converted = key_frame_iterator.get_keyframe().unwrap().unwrap();
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let unconverted = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert_eq!(&converted[..], &unconverted[..]);
+    }
+
+    #[test]
+    fn it_leaves_sdr_frames_alone_when_tone_mapping() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            tone_mapping: ToneMapping::Hable,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let mapped = key_frame_iterator.get_keyframe().unwrap().unwrap();
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let unmapped = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert_eq!(&mapped[..], &unmapped[..]);
+    }
+
+    #[test]
+    fn it_only_flattens_360_video() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let info = client.probe(test_movie.to_str().unwrap()).unwrap();
+        let video = info
+            .video_stream()
+            .expect("No video stream in the test video");
+        assert!(video.spherical.is_none());
+
+        let options = MediaFrameDecoderOptions {
+            flatten_360: true,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let flattened = key_frame_iterator.get_keyframe().unwrap().unwrap();
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let unflattened = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert_eq!(&flattened[..], &unflattened[..]);
+    }
+
+    #[test]
+    fn it_can_be_told_to_decode_in_software() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut options = MediaFrameDecoderOptions::default();
+        options.hw_device_types.push("software".into());
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(!key_frame_iterator
+            .get_keyframe()
+            .unwrap()
+            .unwrap()
+            .is_empty());
+
+        let options = MediaFrameDecoderOptions {
+            hw_backend: HardwareBackend::SoftwareOnly,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(!key_frame_iterator
+            .get_keyframe()
+            .unwrap()
+            .unwrap()
+            .is_empty());
+
+        let mut options = MediaFrameDecoderOptions::default();
+        options.hw_device_types.push("not-a-device".into());
+        assert!(client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .is_err());
+    }
+
+    #[test]
+    fn it_keeps_the_output_size_with_hw_prescale() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("hw_prescale");
+        let input = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(1920, 1080),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        // Software decoding ignores the prescale, Auto falls back to it on
+        // machines without a GPU, either way frames come out the same size.
+        for hw_backend in [HardwareBackend::SoftwareOnly, HardwareBackend::Auto] {
+            for hw_prescale in [false, true] {
+                let options = MediaFrameDecoderOptions {
+                    hw_backend,
+                    hw_prescale,
+                    target_width: 240,
+                    ..Default::default()
+                };
+                let mut iterator = client
+                    .get_key_frames_with_options(input.to_str().unwrap(), options)
+                    .unwrap();
+                let frame = iterator.get_keyframe().unwrap().unwrap();
+                assert_eq!(iterator.get_size(), Size::new(240, 135));
+                #[cfg(feature = "image")]
+                {
+                    let image = decode_image(&frame).unwrap();
+                    assert_eq!(
+                        (image.width(), image.height()),
+                        (240, 135),
+                        "{:?} prescale {}",
+                        hw_backend,
+                        hw_prescale
+                    );
+                }
+                #[cfg(not(feature = "image"))]
+                assert!(!frame.is_empty());
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_reuse_a_decoder_for_a_similar_file() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            reuse_decoders: true,
+            ..Default::default()
+        };
+        let mut counts = Vec::new();
+        for _ in 0..2 {
+            let mut key_frame_iterator = client
+                .get_key_frames_with_options(test_movie.to_str().unwrap(), options.clone())
+                .unwrap();
+            let mut count = 0;
+            loop {
+                let frame = key_frame_iterator.get_keyframe();
+                if frame.is_none() {
+                    break;
+                }
+                assert!(!frame.unwrap().unwrap().is_empty());
+                count += 1;
+            }
+            counts.push(count);
+        }
+        assert!(counts[0] > 0);
+        assert_eq!(counts[0], counts[1]);
+    }
+
+    #[test]
+    fn it_can_describe_the_pipeline() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            hw_backend: HardwareBackend::SoftwareOnly,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(key_frame_iterator.get_keyframe().unwrap().is_ok());
+
+        let description = key_frame_iterator.get_pipeline_description();
+        assert!(description.contains("decoder: "), "{}", description);
+        assert!(description.contains("hardware: none"), "{}", description);
+        assert!(description.contains("output: yuvj420p"), "{}", description);
+    }
+
+    #[test]
+    fn it_can_letterbox_frames_to_a_square() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            target_width: 320,
+            target_height: 320,
+            scaling_mode: ScalingMode::Fit,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let first_frame = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert!(!first_frame.is_empty());
+        assert_eq!(key_frame_iterator.get_size(), Size::new(320, 320));
+    }
+
+    #[test]
+    fn it_can_crop_and_letterbox_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("scaling-modes");
+        let input = dir.join("input.mp4");
+        let options = TestAssetOptions {
+            size: Size::new(320, 180),
+            duration_ms: 1000,
+            frame_rate: 25,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        let first_frame = |scaling_mode, target: Size| {
+            let options = MediaFrameDecoderOptions {
+                target_width: target.width,
+                target_height: target.height,
+                scaling_mode,
+                ..Default::default()
+            };
+            let mut iterator = client
+                .get_key_frames_with_options(input.to_str().unwrap(), options)
+                .unwrap();
+            let frame = iterator.get_keyframe().unwrap().unwrap();
+            (iterator.get_size(), frame)
+        };
+
+        for scaling_mode in [ScalingMode::Fit, ScalingMode::Fill, ScalingMode::Stretch] {
+            let (size, frame) = first_frame(scaling_mode, Size::new(160, 160));
+            assert_eq!(size, Size::new(160, 160), "{:?}", scaling_mode);
+            #[cfg(feature = "image")]
+            {
+                let image = decode_image(&frame).unwrap().to_rgb8();
+                assert_eq!((image.width(), image.height()), (160, 160));
+                // Letterboxing leaves 35 black rows above the 90 row picture.
+                let top = image.get_pixel(80, 10);
+                let letterboxed = top.0.iter().all(|&value| value < 24);
+                assert_eq!(
+                    letterboxed,
+                    scaling_mode == ScalingMode::Fit,
+                    "{:?} {:?}",
+                    scaling_mode,
+                    top
+                );
+            }
+            #[cfg(not(feature = "image"))]
+            assert!(!frame.is_empty());
+        }
+
+        let (size, _) = first_frame(ScalingMode::FitWidth, Size::new(160, 160));
+        assert_eq!(size, Size::new(160, 90));
+        let (size, _) = first_frame(ScalingMode::FitHeight, Size::new(160, 160));
+        assert_eq!(size, Size::new(284, 160));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn it_can_flatten_transparent_frames() {
+        use media_types::AlphaBackground;
+
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("alpha_background");
+        // Red, but fully transparent.
+        let input = dir.join("transparent.png");
+        image::RgbaImage::from_pixel(64, 64, image::Rgba([0xff, 0, 0, 0]))
+            .save(&input)
+            .unwrap();
+
+        let first_frame = |alpha_background| {
+            let options = MediaFrameDecoderOptions {
+                alpha_background,
+                alpha_color: Color::new(0, 0x80, 0xff),
+                image_format: ImageFormat::Png,
+                target_width: 64,
+                target_height: 64,
+                ..Default::default()
+            };
+            client
+                .frames(input.to_str().unwrap(), options)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap()
+                .to_rgb_image()
+                .unwrap()
+        };
+
+        let ignored = first_frame(AlphaBackground::Ignore);
+        assert_eq!(ignored.get_pixel(0, 0).0, [0xff, 0, 0]);
+
+        let solid = first_frame(AlphaBackground::Solid);
+        for (x, y) in [(0, 0), (63, 0), (0, 63), (63, 63)] {
+            assert_eq!(solid.get_pixel(x, y).0, [0, 0x80, 0xff], "({}, {})", x, y);
+        }
+
+        // 16 pixel squares, light in the top left corner.
+        let checkerboard = first_frame(AlphaBackground::Checkerboard);
+        assert_eq!(checkerboard.get_pixel(0, 0).0, [0xff; 3]);
+        assert_eq!(checkerboard.get_pixel(16, 0).0, [0xcc; 3]);
+        assert_eq!(checkerboard.get_pixel(0, 16).0, [0xcc; 3]);
+        assert_eq!(checkerboard.get_pixel(63, 63).0, [0xff; 3]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_get_lossless_key_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let dir = test::unique_temp_dir("lossless_key_frames");
+        let first_frame = |image_format| {
+            let options = MediaFrameDecoderOptions {
+                image_format,
+                ..Default::default()
+            };
+            let frame = client
+                .frames(test_movie.to_str().unwrap(), options)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(frame.format, image_format);
+            frame
+        };
+        let png = first_frame(ImageFormat::Png);
+        let webp = first_frame(ImageFormat::WebP);
+
+        // Each decodes as what it says it is, at the size of the frames.
+        for (frame, name, codec_name) in [(&png, "frame.png", "png"), (&webp, "frame.webp", "webp")]
+        {
+            let path = dir.join(name);
+            std::fs::write(&path, &frame.data).unwrap();
+            let info = client.probe(path.to_str().unwrap()).unwrap();
+            let stream = info.video_stream().expect("No image in the frame");
+            assert_eq!(&*stream.codec_name, codec_name);
+            assert_eq!((stream.width, stream.height), (frame.width, frame.height));
+        }
+        // Both are lossless, so they're the same picture.
+        #[cfg(feature = "image")]
+        assert_eq!(
+            png.to_rgb_image().unwrap().into_raw(),
+            webp.to_rgb_image().unwrap().into_raw()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_can_store_options_and_probe_results_as_json() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        // Fields left out keep their defaults.
+        let config: typed::MediaFrameDecoderOptions = serde_json::from_str(
+            r#"{"target_width": 320, "image_format": "Png", "hw_device_types": ["software"]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.encode_threads, 2);
+        let options = MediaFrameDecoderOptions::from(config.clone());
+        assert_eq!(options.image_format, ImageFormat::Png);
+        assert_eq!(typed::MediaFrameDecoderOptions::from(&options), config);
+
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        while key_frame_iterator.get_keyframe().is_some() {}
+        let report = typed::ProcessingReport::from(&key_frame_iterator.get_report());
+        assert!(report.frames_returned > 0);
+
+        let info = typed::MediaInfo::from(&client.probe(test_movie.to_str().unwrap()).unwrap());
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(
+            serde_json::from_str::<typed::MediaInfo>(&json).unwrap(),
+            info
+        );
+        assert!(info.streams.iter().any(|stream| stream.kind == "video"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_can_store_every_option_type_as_json() {
+        let bundle: typed::PreviewBundleOptions =
+            serde_json::from_str(r#"{"poster_size": {"width": 640, "height": 360}}"#).unwrap();
+        assert_eq!(
+            bundle.filmstrip_count,
+            PreviewBundleOptions::default().filmstrip_count
+        );
+        let options = PreviewBundleOptions::from(bundle.clone());
+        assert_eq!(options.poster_size, Size::new(640, 360));
+        assert_eq!(typed::PreviewBundleOptions::from(&options), bundle);
+
+        let dataset: typed::DatasetExportOptions = serde_json::from_str(
+            r#"{"manifest_format": "Json", "sequence": {"prefix": "shot_", "image": {"format": "Jpeg"}}}"#,
+        )
+        .unwrap();
+        let options = DatasetExportOptions::from(dataset.clone());
+        assert_eq!(options.sequence.prefix.as_str(), "shot_");
+        assert_eq!(options.sequence.image.format, ImageFormat::Jpeg);
+        assert!(options.sequence.output.atomic);
+        assert_eq!(typed::DatasetExportOptions::from(&options), dataset);
+
+        let sync = typed::SyncOptions {
+            offsets_ms: vec![0, -40],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&sync).unwrap();
+        assert_eq!(
+            serde_json::from_str::<typed::SyncOptions>(&json).unwrap(),
+            sync
+        );
+
+        let health = typed::StreamHealth::from(&StreamHealth::default());
+        let json = serde_json::to_string(&health).unwrap();
+        assert_eq!(
+            serde_json::from_str::<typed::StreamHealth>(&json).unwrap(),
+            health
+        );
+    }
+
+    #[test]
+    fn it_can_tune_jpeg_encoding() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let first_frame = |jpeg| {
+            let options = MediaFrameDecoderOptions {
+                jpeg,
+                ..Default::default()
+            };
+            let mut key_frame_iterator = client
+                .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+                .unwrap();
+            key_frame_iterator.get_keyframe().unwrap().unwrap()
+        };
+        let default = first_frame(JpegOptions::default());
+        // Start of a baseline frame, mozjpeg would go progressive on its own.
+        assert!(default.windows(2).any(|marker| marker == [0xff, 0xc0]));
+        assert!(!default.windows(2).any(|marker| marker == [0xff, 0xc2]));
+        let coarse = first_frame(JpegOptions {
+            quality: 10,
+            ..Default::default()
+        });
+        assert!(coarse.len() < default.len());
+
+        let progressive = first_frame(JpegOptions {
+            progressive: true,
+            subsampling: ChromaSubsampling::Yuv444,
+            quality: 90,
+        });
+        // Start of a progressive frame, with every component sampled 1x1.
+        let start = progressive
+            .windows(2)
+            .position(|marker| marker == [0xff, 0xc2])
+            .unwrap();
+        let components = &progressive[start + 10..start + 19];
+        assert!(components.chunks(3).all(|component| component[1] == 0x11));
+    }
+
+    #[test]
+    fn it_can_encode_a_raw_frame_to_jpeg() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+
+        let (width, height) = (96, 64);
+        let mut data = stabby::vec::Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.push((x * 255 / width) as u8);
+                data.push((y * 255 / height) as u8);
+                data.push(((x + y) % 32 * 8) as u8);
+            }
+        }
+        let frame = RawVideoFrame {
+            data,
+            width,
+            height,
+            format: RawPixelFormat::Rgb24,
+        };
+        let fine = client.encode_frame_jpeg(frame.clone(), 95).unwrap();
+        let coarse = client.encode_frame_jpeg(frame.clone(), 10).unwrap();
+        assert_eq!(&fine[..2], &[0xff, 0xd8]);
+        assert_eq!(&coarse[..2], &[0xff, 0xd8]);
+        assert!(coarse.len() < fine.len());
+
+        let truncated = RawVideoFrame {
+            height: height + 1,
+            ..frame
+        };
+        assert!(client.encode_frame_jpeg(truncated, 75).is_err());
+    }
+
+    #[test]
+    fn it_can_encode_raw_frames_to_a_video() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("encoder");
+        let output = dir.join("encoded.mp4");
+
+        let (width, height) = (96, 64);
+        let mut data = stabby::vec::Vec::new();
+        for _ in 0..width * height {
+            data.extend_from_slice(&[200, 40, 40]);
+        }
+        let frame = RawVideoFrame {
+            data,
+            width,
+            height,
+            format: RawPixelFormat::Rgb24,
+        };
+        let options = FrameEncoderOptions {
+            width,
+            height,
+            frame_rate_numerator: 10,
+            frame_rate_denominator: 1,
+            hardware: false,
+            ..Default::default()
+        };
+        let mut encoder = client
+            .new_frame_encoder(output.to_str().unwrap(), options.clone())
+            .unwrap();
+        for _ in 0..10 {
+            encoder.encode_frame(frame.clone()).into_std().unwrap();
+        }
+        let report = encoder.finish().into_std().unwrap();
+        assert_eq!(report.frames_returned, 10);
+        assert!(encoder.finish().is_err());
+
+        let info = client.probe(output.to_str().unwrap()).unwrap();
+        let video = info.video_stream().expect("No video stream in the output");
+        assert_eq!((video.width, video.height), (width, height));
+        let duration_us = info.duration_us.expect("Output has no duration");
+        assert!((900_000..=1_100_000).contains(&duration_us));
+
+        let frames = client
+            .frames(
+                output.to_str().unwrap(),
+                MediaFrameDecoderOptions::default(),
+            )
+            .unwrap()
+            .collect::<Result<Vec<Frame>, _>>()
+            .unwrap();
+        assert!(!frames.is_empty());
+        #[cfg(feature = "image")]
+        {
+            let decoded = decode_image(&frames[0].data).unwrap().to_rgb8();
+            let pixel = decoded.get_pixel(decoded.width() / 2, decoded.height() / 2);
+            assert!(
+                pixel[0] > 150 && pixel[1] < 90 && pixel[2] < 90,
+                "{:?}",
+                pixel
+            );
+        }
+
+        // The existing output is kept when it's not to be overwritten.
+        let options = FrameEncoderOptions {
+            output: OutputOptions {
+                overwrite: false,
+                ..Default::default()
+            },
+            ..options
+        };
+        assert!(client
+            .new_frame_encoder(output.to_str().unwrap(), options)
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_iterate_frames_with_std_types() -> Result<(), MediaClientError> {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib)?;
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client
+            .get_key_frames(test_movie.to_str().unwrap())
+            .context("opening test.mp4")?;
+        let mut count = 0;
+        while let Some(frame) = key_frame_iterator.next_frame()? {
+            assert!(!frame.is_empty());
+            count += 1;
+        }
+        assert!(count > 0, "No key frames found in the test video");
+
+        let error = client
+            .get_key_frames("missing.mp4")
+            .context("opening missing.mp4")
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("opening missing.mp4: "));
+        Ok(())
+    }
+
+    #[test]
+    fn it_can_collect_frames_from_an_iterator() -> Result<(), MediaClientError> {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib)?;
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let frames = client
+            .frames(
+                test_movie.to_str().unwrap(),
+                MediaFrameDecoderOptions::default(),
+            )?
+            .take(3)
+            .collect::<Result<Vec<Frame>, _>>()?;
+        assert!(!frames.is_empty());
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.index, index as u64);
+            assert_eq!(frame.format, ImageFormat::Jpeg);
+            assert!(!frame.data.is_empty());
+            assert!(frame.width > 0 && frame.height > 0);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn it_can_convert_frames_to_arrays() -> Result<(), MediaClientError> {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib)?;
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            image_format: ImageFormat::Png,
+            ..Default::default()
+        };
+        let frame = client
+            .frames(test_movie.to_str().unwrap(), options)?
+            .next()
+            .unwrap()?;
+        let rgb = frame.to_rgb_image()?;
+        assert_eq!(rgb.dimensions(), (frame.width, frame.height));
+        let array = frame.to_array()?;
+        assert_eq!(
+            array.shape(),
+            &[frame.height as usize, frame.width as usize, 3]
+        );
+        let (x, y) = (frame.width - 1, frame.height / 2);
+        assert_eq!(
+            array
+                .slice(ndarray::s![y as usize, x as usize, ..])
+                .to_vec(),
+            rgb.get_pixel(x, y).0.to_vec()
+        );
+
+        // The same pixels with an alpha channel come back as they were.
+        let mut data = stabby::vec::Vec::new();
+        for pixel in rgb.pixels() {
+            for channel in pixel.0.into_iter().chain([0xff]) {
+                data.push(channel);
+            }
+        }
+        let raw = RawVideoFrame {
+            data,
+            width: frame.width,
+            height: frame.height,
+            format: RawPixelFormat::Rgba,
+        };
+        assert_eq!(raw_frame_to_rgb_image(&raw)?, rgb);
+        Ok(())
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn it_can_convert_raw_yuv_frames_to_rgb() {
+        // A white 2x2 block next to a red one, limited range BT.601.
+        let (width, height) = (4, 2);
+        let mut data = stabby::vec::Vec::new();
+        for value in [235, 235, 81, 81, 235, 235, 81, 81, 128, 90, 128, 240] {
+            data.push(value);
+        }
+        let mut raw = RawVideoFrame {
+            data,
+            width,
+            height,
+            format: RawPixelFormat::Yuv420p,
+        };
+        let rgb = raw_frame_to_rgb_image(&raw).unwrap();
+        assert_eq!(rgb.dimensions(), (width, height));
+        for y in 0..height {
+            assert_eq!(rgb.get_pixel(0, y).0, [255, 255, 255]);
+            assert_eq!(rgb.get_pixel(1, y).0, [255, 255, 255]);
+            let [r, g, b] = rgb.get_pixel(3, y).0;
+            assert!(r > 250 && g < 5 && b < 5, "{:?}", [r, g, b]);
+        }
+
+        // Taller than the data holds.
+        raw.height = 4;
+        assert!(raw_frame_to_rgb_image(&raw).is_err());
+    }
+
+    // A single plane in system memory, rows padded out to `stride`.
+    #[cfg(feature = "image")]
+    struct PaddedPlane {
+        width: u32,
+        height: u32,
+        stride: u32,
+    }
+
+    #[cfg(feature = "image")]
+    impl media_types::VideoFrame for PaddedPlane {
+        extern "C" fn get_hw_surface(&mut self) -> stabby::option::Option<media_types::HwSurface> {
+            None.into()
+        }
+
+        extern "C" fn get_timestamp_us(&mut self) -> i64 {
+            0
+        }
+
+        extern "C" fn plane_count(&mut self) -> u32 {
+            1
+        }
+
+        extern "C" fn plane_width(&mut self, _index: u32) -> u32 {
+            self.width
+        }
+
+        extern "C" fn plane_height(&mut self, _index: u32) -> u32 {
+            self.height
+        }
+
+        extern "C" fn stride(&mut self, _index: u32) -> u32 {
+            self.stride
+        }
+
+        extern "C" fn data(&mut self, _index: u32) -> stabby::vec::Vec<u8> {
+            let mut data = stabby::vec::Vec::new();
+            for byte in 0..self.stride * self.height {
+                data.push(byte as u8);
+            }
+            data
+        }
+
+        extern "C" fn get_color_space(&mut self) -> stabby::string::String {
+            "unknown".into()
+        }
+
+        extern "C" fn get_color_primaries(&mut self) -> stabby::string::String {
+            "unknown".into()
+        }
+
+        extern "C" fn get_color_transfer(&mut self) -> stabby::string::String {
+            "unknown".into()
+        }
+
+        extern "C" fn get_color_range(&mut self) -> stabby::string::String {
+            "pc".into()
+        }
+
+        extern "C" fn get_mastering_display(
+            &mut self,
+        ) -> stabby::option::Option<media_types::MasteringDisplay> {
+            None.into()
+        }
+
+        extern "C" fn get_content_light_level(
+            &mut self,
+        ) -> stabby::option::Option<media_types::ContentLightLevel> {
+            None.into()
+        }
+
+        extern "C" fn get_interlaced_frame(&mut self) -> bool {
+            false
+        }
+
+        extern "C" fn get_top_field_first(&mut self) -> bool {
+            false
+        }
+
+        extern "C" fn get_spherical(
+            &mut self,
+        ) -> stabby::option::Option<media_types::SphericalMetadata> {
+            None.into()
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn it_can_pack_frame_planes() {
+        let plane = |width, height, stride| -> media_types::VideoFrameBox {
+            stabby::boxed::Box::new(PaddedPlane {
+                width,
+                height,
+                stride,
+            })
+            .into()
+        };
+
+        let mut frame = plane(3, 2, 4);
+        assert_eq!(packed_plane(&mut frame, 0).unwrap(), [0, 1, 2, 4, 5, 6]);
+        #[cfg(feature = "ndarray")]
+        assert_eq!(
+            plane_to_array(&mut frame, 0).unwrap(),
+            ndarray::array![[0, 1, 2], [4, 5, 6]]
+        );
+        assert!(packed_plane(&mut frame, 1).is_err());
+
+        // Rows that would overlap.
+        let mut frame = plane(3, 2, 2);
+        assert!(packed_plane(&mut frame, 0).is_err());
+        #[cfg(feature = "ndarray")]
+        assert!(plane_to_array(&mut frame, 0).is_err());
+    }
+
+    #[test]
+    fn it_can_seek_before_pulling_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        key_frame_iterator.seek(1_000_000).unwrap();
+        let seeked = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert!(!seeked.is_empty());
+
+        key_frame_iterator.seek(0).unwrap();
+        let first = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert!(!first.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_flowing_when_the_consumer_keeps_up() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = MediaFrameDecoderOptions {
+            realtime: true,
+            high_watermark_ms: 60_000,
+            low_watermark_ms: 30_000,
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let mut paused = 0;
+        let mut callbacks = WatermarkCallbacks::new(|| paused += 1, || {});
+        while key_frame_iterator.get_keyframe().is_some() {
+            assert_eq!(
+                callbacks.poll(&mut key_frame_iterator),
+                media_types::Backpressure::Flowing
+            );
+        }
+        assert_eq!(paused, 0);
+    }
+
+    #[test]
+    fn it_can_get_key_frames_from_memory() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let data = std::fs::read(test::get_test_data_file("test.mp4")).unwrap();
+
+        let mut key_frame_iterator = client
+            .get_key_frames_from_memory(&data, MediaFrameDecoderOptions::default())
+            .unwrap();
+        let first_frame = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert!(!first_frame.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_can_decode_from_an_open_file() {
+        use std::os::unix::io::AsRawFd;
+
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = std::fs::File::open(test::get_test_data_file("test.mp4")).unwrap();
+
+        let mut key_frame_iterator = client
+            .get_key_frames(&fd_input(test_movie.as_raw_fd()))
+            .unwrap();
+        drop(test_movie);
+        let first_frame = key_frame_iterator.get_keyframe().unwrap().unwrap();
+        assert!(!first_frame.is_empty());
+    }
+
+    #[test]
+    fn it_can_get_frames_in_reverse() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut frame_iterator = client
+            .get_frames_reversed(
+                test_movie.to_str().unwrap(),
+                0,
+                2_000_000,
+                MediaFrameDecoderOptions::default(),
+            )
+            .unwrap();
+
+        let mut count = 0;
+        while let Some(frame) = frame_iterator.get_keyframe().as_ref() {
+            assert!(!frame.as_ref().unwrap().is_empty());
+            count += 1;
+        }
+        assert!(
+            count > 1,
+            "Expected every frame in the range, got {}",
+            count
+        );
+        assert_eq!(frame_iterator.get_report().frames_returned, count);
+    }
+
+    #[test]
+    fn it_can_step_between_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut session = client
+            .open_frame_session(
+                test_movie.to_str().unwrap(),
+                MediaFrameDecoderOptions::default(),
+            )
+            .unwrap();
+        assert!(session.get_frame_count() > 2);
+        assert!(session.previous_frame().is_none());
+
+        let first = session.next_frame().unwrap().unwrap();
+        session.next_frame().unwrap().unwrap();
+        assert_eq!(session.get_frame_index().unwrap(), 1);
+
+        let stepped_back = session.previous_frame().unwrap().unwrap();
+        assert_eq!(session.get_frame_index().unwrap(), 0);
+        assert_eq!(first, stepped_back);
+
+        let checksum = session.get_plane_checksum(0, ChecksumAlgorithm::Crc32);
+        assert!(checksum.is_some());
+        assert!(session
+            .get_plane_checksum(3, ChecksumAlgorithm::Crc32)
+            .is_none());
+        session.next_frame().unwrap().unwrap();
+        session.previous_frame().unwrap().unwrap();
+        assert_eq!(
+            session.get_plane_checksum(0, ChecksumAlgorithm::Crc32),
+            checksum
+        );
+
+        let last = session.get_frame_count() - 1;
+        session.step_to(last).unwrap().unwrap();
+        assert!(session.next_frame().is_none());
+    }
+
+    #[test]
+    fn it_turns_sideways_frames_upright_in_sessions() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("session-rotation");
+        let input = dir.join("input.mp4");
+        let options = TestAssetOptions {
+            size: Size::new(320, 180),
+            duration_ms: 500,
+            frame_rate: 10,
+            rotation_degrees: 90,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        for (auto_rotate, size) in [(true, Size::new(90, 160)), (false, Size::new(90, 51))] {
+            let mut session = client
+                .open_frame_session(
+                    input.to_str().unwrap(),
+                    MediaFrameDecoderOptions {
+                        target_width: 90,
+                        target_height: 0,
+                        auto_rotate,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            assert_eq!(session.get_size(), size);
+            let frame = session.next_frame().unwrap().unwrap();
+            #[cfg(feature = "image")]
+            {
+                let image = decode_image(&frame).unwrap();
+                assert_eq!(Size::new(image.width(), image.height()), size);
+            }
+            #[cfg(not(feature = "image"))]
+            assert!(!frame.is_empty());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_seek_around_a_memory_mapped_file() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let open = |memory_map| {
+            client
+                .open_frame_session(
+                    test_movie.to_str().unwrap(),
+                    MediaFrameDecoderOptions {
+                        memory_map,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let mut mapped = open(true);
+        let mut read = open(false);
+        let last = read.get_frame_count() - 1;
+        assert_eq!(mapped.get_frame_count(), last + 1);
+        for index in [last, 0, last / 2, 1] {
+            assert_eq!(
+                mapped.step_to(index).unwrap().unwrap(),
+                read.step_to(index).unwrap().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn it_can_decode_frames_on_a_worker_thread() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let mut expected = Vec::new();
+        while let Some(frame) = key_frame_iterator.get_keyframe().into_std().unwrap() {
+            expected.push(frame);
+        }
+
+        let options = MediaFrameDecoderOptions {
+            frame_queue_size: 2,
+            ..Default::default()
+        };
+        let mut decoder = client
+            .get_threaded_frames(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert_eq!(decoder.get_size(), key_frame_iterator.get_size());
+        let first = decoder.get_frame().unwrap().unwrap();
+        assert_eq!(&first[..], &expected[0][..]);
+
+        // Whatever comes without waiting is still in order.
+        let mut frames = vec![first];
+        while !decoder.is_finished() {
+            match decoder.try_get_frame().into_std().unwrap() {
+                Some(frame) => frames.push(frame),
+                None => std::thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        }
+        assert_eq!(frames.len(), expected.len());
+        assert!(frames.iter().zip(&expected).all(|(a, b)| a[..] == b[..]));
+        assert_eq!(decoder.get_report().frames_returned, expected.len() as u64);
+    }
+
+    #[test]
+    fn it_turns_the_worker_thread_size_for_sideways_video() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("threaded_rotation");
+        let input = dir.join("sideways.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 2000,
+            frame_rate: 25,
+            rotation_degrees: 90,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        let options = MediaFrameDecoderOptions {
+            frame_queue_size: 1,
+            ..Default::default()
+        };
+        let mut decoder = client
+            .get_threaded_frames(input.to_str().unwrap(), options)
+            .unwrap();
+        decoder.get_frame().unwrap().unwrap();
+        assert_eq!(decoder.get_size(), Size::new(240, 320));
+
+        // Dropped with the worker waiting for room in the queue, which
+        // has to stop it rather than leave it waiting.
+        drop(decoder);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_sync_inputs_with_different_start_offsets() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+        let test_movie = test_movie.to_str().unwrap();
+
+        let mut offsets_ms = stabby::vec::Vec::new();
+        offsets_ms.push(0);
+        offsets_ms.push(500);
+        let options = SyncOptions {
+            clock: SyncClock::Start,
+            interval_ms: 250,
+            offsets_ms,
+            ..Default::default()
+        };
+        let mut iterator = client
+            .get_synced_frames(&[test_movie, test_movie], options)
+            .unwrap();
+        let offsets_us = iterator.get_offsets_us();
+        assert_eq!(&*offsets_us, &[0, 500_000]);
+
+        let mut bundles = 0;
+        while let Some(bundle) = iterator.get_bundle().into_std().unwrap() {
+            for frame in bundle.frames.iter() {
+                assert!(!frame.jpeg.is_empty());
+                let offset_us = if frame.input_index == 0 { 0 } else { 500_000 };
+                assert!(frame.timestamp_us <= bundle.timestamp_us - offset_us);
+            }
+            if bundle.timestamp_us < 500_000 {
+                assert!(bundle.frames.iter().all(|frame| frame.input_index == 0));
+            }
+            bundles += 1;
+        }
+        assert!(bundles > 2);
+    }
+
+    #[test]
+    fn it_can_sniff_the_content_type() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let content_type = client.sniff(test_movie.to_str().unwrap()).unwrap();
+        assert!(content_type.is_recognised());
+        assert_eq!(&*content_type.mime_type, "video/mp4");
+    }
+
+    #[test]
+    fn it_can_probe_the_media_info() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let info = client.probe(test_movie.to_str().unwrap()).unwrap();
+        assert!(info.stream_count() > 0);
+        let video = info
+            .video_stream()
+            .expect("No video stream in the test video");
+        assert!(video.width > 0 && video.height > 0);
+        assert!(!video.codec_name.is_empty());
+        assert_eq!(info.duration_source, DurationSource::Container);
+    }
+
+    #[test]
+    fn it_reports_where_the_duration_came_from() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("duration_source");
+        let video = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 2000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(video.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        // Raw ADTS has no header giving the length, FFmpeg goes by the
+        // bitrate.
+        let audio = dir.join("audio.aac");
+        let options = ChannelExportOptions {
+            split: false,
+            ..Default::default()
+        };
+        client
+            .export_audio_channels(
+                video.to_str().unwrap(),
+                audio.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+
+        for (input, source) in [
+            (&video, DurationSource::Container),
+            (&audio, DurationSource::Bitrate),
+        ] {
+            let info = client.probe(input.to_str().unwrap()).unwrap();
+            assert_eq!(info.duration_source, source, "{:?}", input);
+            let duration_us = info.duration_us.expect("No duration");
+            assert!(
+                (1_500_000..=2_500_000).contains(&duration_us),
+                "{:?} is {}us long",
+                input,
+                duration_us
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_generate_a_test_asset() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("test_asset");
+        let output = dir.join("asset.mp4");
+
+        let options = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 1000,
+            frame_rate: 25,
+            b_frames: 2,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        let report = client
+            .generate_test_asset(output.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+        assert_eq!(report.frames_returned, 25);
+
+        let info = client.probe(output.to_str().unwrap()).unwrap();
+        let video = info.video_stream().expect("No video stream in the asset");
+        assert_eq!((video.width, video.height), (320, 240));
+        assert!(info.streams.iter().any(|stream| &*stream.kind == "audio"));
+
+        // Another encoder and container, without audio.
+        let output = dir.join("asset.ts");
+        let options = TestAssetOptions {
+            size: Size::new(176, 144),
+            duration_ms: 400,
+            frame_rate: 10,
+            codec: "mpeg2video".into(),
+            ..Default::default()
+        };
+        let report = client
+            .generate_test_asset(output.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+        assert_eq!(report.frames_returned, 4);
+
+        let info = client.probe(output.to_str().unwrap()).unwrap();
+        let video = info.video_stream().expect("No video stream in the asset");
+        assert_eq!(&*video.codec_name, "mpeg2video");
+        assert_eq!((video.width, video.height), (176, 144));
+        assert!(!info.streams.iter().any(|stream| &*stream.kind == "audio"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_puts_test_assets_in_place_once_complete() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("test_asset_output");
+        let output = dir.join("asset.mp4");
+
+        // A cancelled job leaves nothing behind.
+        let options = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        let job = JobContext::new(stabby::boxed::Box::new(CancelImmediately).into());
+        match client.generate_test_asset(output.to_str().unwrap(), options.clone(), job) {
+            Err(MediaClientError::MediaLibError(e)) => assert!(e.is_cancelled()),
+            other => panic!("Expected a cancelled job, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        client
+            .generate_test_asset(
+                output.to_str().unwrap(),
+                options.clone(),
+                JobContext::default(),
+            )
+            .unwrap();
+        let written = std::fs::read(&output).unwrap();
+
+        // An existing file is left alone unless it's to be overwritten.
+        let options = TestAssetOptions {
+            frame_rate: 5,
+            output: OutputOptions {
+                overwrite: false,
+                ..Default::default()
+            },
+            ..options
+        };
+        assert!(client
+            .generate_test_asset(output.to_str().unwrap(), options, JobContext::default())
+            .is_err());
+        assert_eq!(std::fs::read(&output).unwrap(), written);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_deinterlaces_interlaced_test_assets() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("deinterlace");
+        let input = dir.join("interlaced.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 2000,
+            frame_rate: 25,
+            interlaced: true,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        let software = MediaFrameDecoderOptions {
+            hw_backend: HardwareBackend::SoftwareOnly,
+            ..Default::default()
+        };
+        let mut frames = client
+            .get_video_frames(input.to_str().unwrap(), software.clone())
+            .unwrap();
+        let mut frame = frames
+            .get_frame()
+            .into_std()
+            .unwrap()
+            .expect("No frames in the asset");
+        assert!(frame.get_interlaced_frame());
+
+        // Key frames are deinterlaced one by one, which changes them.
+        let key_frames = |deinterlace| {
+            let options = MediaFrameDecoderOptions {
+                deinterlace,
+                ..software.clone()
+            };
+            let mut key_frame_iterator = client
+                .get_key_frames_with_options(input.to_str().unwrap(), options)
+                .unwrap();
+            let mut key_frames = Vec::new();
+            loop {
+                let frame = key_frame_iterator.get_keyframe();
+                if frame.is_none() {
+                    break;
+                }
+                key_frames.push(frame.unwrap().unwrap().to_vec());
+            }
+            key_frames
+        };
+        let woven = key_frames(false);
+        let deinterlaced = key_frames(true);
+        assert!(!woven.is_empty());
+        assert_eq!(woven.len(), deinterlaced.len());
+        assert_ne!(woven, deinterlaced);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_export_audio_channels_sped_up() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("channel_speed");
+        let input = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 4000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        // 4× takes two atempo filters.
+        for (speed, expected_us) in [(2.0, 2_000_000), (4.0, 1_000_000)] {
+            let output = dir.join(format!("preview_{}x.wav", speed));
+            let options = ChannelExportOptions {
+                split: false,
+                speed,
+                ..Default::default()
+            };
+            client
+                .export_audio_channels(
+                    input.to_str().unwrap(),
+                    output.to_str().unwrap(),
+                    options,
+                    JobContext::default(),
+                )
+                .unwrap();
+            let info = client.probe(output.to_str().unwrap()).unwrap();
+            let duration_us = info.duration_us.expect("Preview has no duration");
+            assert!(
+                (duration_us - expected_us).abs() < 100_000,
+                "{}x preview is {}us",
+                speed,
+                duration_us
+            );
+        }
+
+        let options = ChannelExportOptions {
+            speed: 0.0,
+            ..Default::default()
+        };
+        assert!(client
+            .export_audio_channels(
+                input.to_str().unwrap(),
+                dir.join("stopped.wav").to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_make_a_proxy_at_double_speed() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("speed");
+        let input = dir.join("input.mp4");
+        let playlist = dir.join("proxy.m3u8");
+
+        let options = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 2000,
+            frame_rate: 25,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        let options = ScrubProxyOptions {
+            speed: 2.0,
+            audio: true,
+            ..Default::default()
+        };
+        let report = client
+            .scrub_proxy(
+                input.to_str().unwrap(),
+                playlist.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+        // Same frame rate, half as long.
+        assert!((24..=26).contains(&report.frames_returned));
+        assert!(report.warnings.is_empty());
+
+        let media = playlist.with_extension("mp4");
+        let info = client.probe(media.to_str().unwrap()).unwrap();
+        assert!(info.streams.iter().any(|stream| &*stream.kind == "audio"));
+        let duration_us = info.duration_us.expect("Proxy has no duration");
+        assert!(
+            (900_000..=1_100_000).contains(&duration_us),
+            "Proxy is {}us long",
+            duration_us
+        );
+
+        let options = ScrubProxyOptions {
+            speed: 8.0,
+            ..Default::default()
+        };
+        assert!(client
+            .scrub_proxy(
+                input.to_str().unwrap(),
+                playlist.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_puts_proxy_files_in_place_once_complete() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("proxy_output");
+        let input = dir.join("input.mp4");
+        let playlist = dir.join("proxy.m3u8");
+        let media = playlist.with_extension("mp4");
+
+        let options = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+        client
+            .scrub_proxy(
+                input.to_str().unwrap(),
+                playlist.to_str().unwrap(),
+                ScrubProxyOptions::default(),
+                JobContext::default(),
+            )
+            .unwrap();
+
+        // The playlist names the media file as it ended up, and nothing
+        // staged is left behind.
+        let written = std::fs::read_to_string(&playlist).unwrap();
+        assert!(written.contains("\"proxy.mp4\""), "{}", written);
+        assert!(!written.contains(".tmp"), "{}", written);
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["input.mp4", "proxy.m3u8", "proxy.mp4"]);
+
+        let media_bytes = std::fs::read(&media).unwrap();
+        let options = ScrubProxyOptions {
+            width: 80,
+            output: OutputOptions {
+                overwrite: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(client
+            .scrub_proxy(
+                input.to_str().unwrap(),
+                playlist.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .is_err());
+        assert_eq!(std::fs::read_to_string(&playlist).unwrap(), written);
+        assert_eq!(std::fs::read(&media).unwrap(), media_bytes);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_make_a_timelapse() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("timelapse");
+        let input = dir.join("input.mp4");
+        let output = dir.join("timelapse.mp4");
+
+        let options = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 2000,
+            frame_rate: 25,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        for blend in [false, true] {
+            let options = TimelapseOptions {
+                speedup: 5,
+                blend,
+                ..Default::default()
+            };
+            let report = client
+                .timelapse(
+                    input.to_str().unwrap(),
+                    output.to_str().unwrap(),
+                    options,
+                    JobContext::default(),
+                )
+                .unwrap();
+            assert_eq!(report.frames_returned, 10);
+            assert_eq!(report.outputs.len(), 1);
+
+            let info = client.probe(output.to_str().unwrap()).unwrap();
+            let video = info
+                .video_stream()
+                .expect("No video stream in the timelapse");
+            assert_eq!((video.width, video.height), (320, 240));
+        }
+
+        // Nothing left behind from staging the output.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_read_packets_without_decoding() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("packets");
+        let input = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 2000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        let info = client.probe(input.to_str().unwrap()).unwrap();
+        let video_index = info.video_stream().unwrap().index;
+
+        let mut packets = client.get_packets(input.to_str().unwrap()).unwrap();
+        assert_eq!(packets.get_stream_count(), 2);
+        let mut video_pts = Vec::new();
+        let mut key_pts = Vec::new();
+        let mut audio_packets = 0;
+        while let Some(packet) = packets.next_packet().unwrap() {
+            assert!(packet.stream_index < 2);
+            assert!(!packet.data.is_empty());
+            assert!(packet.time_base.denominator > 0);
+            if packet.stream_index == video_index {
+                // Without B-frames every frame is presented as it's decoded.
+                assert_eq!(packet.pts, packet.dts);
+                let pts_us = packet.time_base.to_us(packet.pts.unwrap());
+                assert!(packet.is_key || !video_pts.is_empty());
+                if packet.is_key {
+                    key_pts.push(pts_us);
+                }
+                video_pts.push(pts_us);
+            } else {
+                // Every AAC packet can be decoded on its own.
+                assert!(packet.is_key);
+                audio_packets += 1;
+            }
+        }
+        assert_eq!(video_pts.len(), 20);
+        assert!(video_pts
+            .windows(2)
+            .all(|pair| pair[0] + 100_000 == pair[1]));
+        let keyframes = client.list_keyframes(input.to_str().unwrap()).unwrap();
+        assert_eq!(
+            key_pts,
+            keyframes
+                .iter()
+                .map(|keyframe| keyframe.pts_us)
+                .collect::<Vec<_>>()
+        );
+        assert!(audio_packets > 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_restream_to_a_file() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("restream");
+        let input = dir.join("input.mp4");
+        let output = dir.join("restream.flv");
+
+        let options = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 1000,
+            frame_rate: 25,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        let options = RestreamOptions {
+            realtime: false,
+            ..Default::default()
+        };
+        let report = client
+            .restream(
+                input.to_str().unwrap(),
+                output.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+        // 25 video packets and the audio alongside them.
+        assert!(report.frames_returned > 25);
+        assert!(report.warnings.is_empty());
+        assert!(report.outputs[0].bytes > 0);
+
+        let info = client.probe(output.to_str().unwrap()).unwrap();
+        let video = info
+            .video_stream()
+            .expect("No video stream in the restream");
+        assert_eq!((video.width, video.height), (320, 240));
+        assert!(info.streams.iter().any(|stream| &*stream.kind == "audio"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_pick_a_video_stream() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let streams = client
+            .list_video_streams(test_movie.to_str().unwrap())
+            .unwrap();
+        assert!(!streams.is_empty());
+        assert!(streams.iter().all(|stream| &*stream.kind == "video"));
+
+        let options = MediaFrameDecoderOptions {
+            stream_index: stabby::option::Option::Some(streams[0].index),
+            ..Default::default()
+        };
+        let mut key_frame_iterator = client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(key_frame_iterator.get_keyframe().unwrap().is_ok());
+
+        let info = client.probe(test_movie.to_str().unwrap()).unwrap();
+        let options = MediaFrameDecoderOptions {
+            stream_index: stabby::option::Option::Some(info.stream_count() as u32),
+            ..Default::default()
+        };
+        assert!(client
+            .get_key_frames_with_options(test_movie.to_str().unwrap(), options)
+            .is_err());
+    }
+
+    #[test]
+    fn it_can_force_the_demuxer() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("force_format");
+        let input = dir.join("input.ts");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 90),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        // An MPEG-TS file named as if it were an MP4.
+        let misnamed = dir.join("input.mp4");
+        std::fs::rename(&input, &misnamed).unwrap();
+
+        let forced = |format: &str| {
+            let options = MediaFrameDecoderOptions {
+                force_format: stabby::option::Option::Some(format.into()),
+                ..Default::default()
+            };
+            client.get_key_frames_with_options(misnamed.to_str().unwrap(), options)
+        };
+        let mut key_frame_iterator = forced("mpegts").unwrap();
+        assert!(key_frame_iterator.get_keyframe().unwrap().is_ok());
+        // The forced demuxer is used even when it's the wrong one.
+        assert!(forced("wav").is_err());
+        assert!(forced("no-such-demuxer").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_checks_decryption_keys() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        // Keys go unused on an input that isn't encrypted.
+        let mut decryption = DecryptionOptions::default();
+        decryption.keys.push(media_types::DecryptionKey {
+            key_id: "".into(),
+            key: "00112233445566778899aabbccddeeff".into(),
+        });
+        let info = client
+            .probe_with_decryption(test_movie.to_str().unwrap(), decryption)
+            .unwrap();
+        assert!(info.video_stream().is_some());
+
+        let mut decryption = DecryptionOptions::default();
+        decryption.keys.push(media_types::DecryptionKey {
+            key_id: "".into(),
+            key: "not hex".into(),
+        });
+        assert!(client
+            .probe_with_decryption(test_movie.to_str().unwrap(), decryption)
+            .is_err());
+    }
+
+    #[test]
+    fn it_only_extracts_subtitles_from_subtitle_streams() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let info = client.probe(test_movie.to_str().unwrap()).unwrap();
+        let video = info.video_stream().unwrap();
+        assert!(client
+            .extract_subtitles(test_movie.to_str().unwrap(), video.index)
+            .is_err());
+        assert!(client
+            .extract_subtitles(test_movie.to_str().unwrap(), info.stream_count() as u32)
+            .is_err());
+    }
+
+    #[test]
+    fn it_leaves_gpu_frames_on_the_device() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("gpu_frames");
+        let input = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(320, 180),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        let software = MediaFrameDecoderOptions {
+            hw_backend: HardwareBackend::SoftwareOnly,
+            ..Default::default()
+        };
+        assert!(client
+            .get_gpu_frames(input.to_str().unwrap(), software.clone())
+            .is_err());
+
+        // Frames decoded in software come out in system memory instead.
+        let mut frames = client
+            .get_video_frames(input.to_str().unwrap(), software)
+            .unwrap();
+        let mut decoded = 0;
+        while let Some(mut frame) = frames.get_frame().into_std().unwrap() {
+            assert!(frame.get_hw_surface().is_none());
+            assert_eq!(frame.plane_count(), 3);
+            assert_eq!(frame.plane_width(0), 320);
+            assert!(!frame.get_color_transfer().is_empty());
+            assert!(frame.get_mastering_display().is_none());
+            decoded += 1;
+        }
+        assert_eq!(decoded, 10);
+        assert!(frames.get_report().hardware_device.is_none());
+
+        // The rest needs a GPU to decode on.
+        let support = client
+            .hardware_support(HardwareSupportQuery::new("h264"))
+            .unwrap();
+        if support.is_hardware() {
+            let mut frames = client
+                .get_gpu_frames(input.to_str().unwrap(), MediaFrameDecoderOptions::default())
+                .unwrap();
+            let mut timestamps = Vec::new();
+            while let Some(mut frame) = frames.get_frame().into_std().unwrap() {
+                let surface = frame.get_hw_surface().unwrap();
+                assert!(!surface.handles.is_empty());
+                assert_eq!(
+                    Some(&*surface.sw_format),
+                    support.pixel_format.as_ref().map(|format| &**format)
+                );
+                assert_eq!((surface.width, surface.height), (320, 180));
+                timestamps.push(frame.get_timestamp_us());
+            }
+            assert_eq!(timestamps.len(), 10);
+            assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(frames.get_report().hardware_device.is_some());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_query_hardware_support() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+
+        // Whether there's a GPU depends on the machine, the decoder doesn't.
+        let support = client
+            .hardware_support(HardwareSupportQuery::new("h264"))
+            .unwrap();
+        assert_eq!(&*support.decoder, "h264");
+        if !support.is_hardware() {
+            assert!(support.utilization.is_empty());
+        }
+        assert!(client
+            .hardware_support(HardwareSupportQuery::new("not-a-codec"))
+            .is_err());
+    }
+
+    #[test]
+    fn it_reports_decoder_utilization_or_why_it_cant() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+
+        // Needs the nvml feature and an NVIDIA driver, and fails with a
+        // reason without either.
+        match client.decoder_utilization() {
+            Ok(devices) => {
+                for device in devices {
+                    assert!(device.decoder_percent <= 100);
+                    assert!(device.memory_free_bytes <= device.memory_total_bytes);
+                }
+            }
+            Err(MediaClientError::MediaLibError(MediaLibError::UnknownError(reason))) => {
+                assert!(reason.contains("nvml") || reason.contains("nvidia-ml"));
+            }
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_can_list_key_frames() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let keyframes = client.list_keyframes(test_movie.to_str().unwrap()).unwrap();
+
+        assert!(
+            !keyframes.is_empty(),
+            "No key frames found in the test video"
+        );
+        assert!(keyframes
+            .windows(2)
+            .all(|pair| pair[0].pts_us <= pair[1].pts_us));
+    }
+
+    struct CancelImmediately;
+
+    impl JobObserver for CancelImmediately {
+        extern "C" fn is_cancelled(&mut self) -> bool {
+            true
+        }
+
+        extern "C" fn on_progress(&mut self, _done_us: u64, _total_us: u64) {}
+    }
+
+    #[test]
+    fn it_stops_a_cancelled_job() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let job = JobContext::new(stabby::boxed::Box::new(CancelImmediately).into());
+        let result = client.fingerprint_video(test_movie.to_str().unwrap(), job);
+        match result {
+            Err(MediaClientError::MediaLibError(e)) => assert!(e.is_cancelled()),
+            other => panic!("Expected a cancelled job, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    struct RecordProgress(std::sync::Arc<std::sync::Mutex<Vec<u64>>>);
+
+    impl JobObserver for RecordProgress {
+        extern "C" fn is_cancelled(&mut self) -> bool {
+            false
+        }
+
+        extern "C" fn on_progress(&mut self, done_us: u64, _total_us: u64) {
+            self.0.lock().unwrap().push(done_us);
+        }
+    }
+
+    #[test]
+    fn it_runs_iterators_as_jobs() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        key_frame_iterator.set_job(JobContext::new(
+            stabby::boxed::Box::new(RecordProgress(progress.clone())).into(),
+        ));
+        let mut count = 0;
+        while key_frame_iterator
+            .get_keyframe()
+            .into_std()
+            .unwrap()
+            .is_some()
+        {
+            count += 1;
+        }
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.len(), count);
+        assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let cancelled = || JobContext::new(stabby::boxed::Box::new(CancelImmediately).into());
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        key_frame_iterator.set_job(cancelled());
+        match key_frame_iterator.get_keyframe().into_std() {
+            Err(MediaClientError::MediaLibError(e)) => assert!(e.is_cancelled()),
+            other => panic!("Expected a cancelled frame, got {:?}", other.map(|_| ())),
+        }
+
+        let mut session = client
+            .open_frame_session(
+                test_movie.to_str().unwrap(),
+                MediaFrameDecoderOptions::default(),
+            )
+            .unwrap();
+        session.set_job(cancelled());
+        match session.next_frame().into_std() {
+            Err(MediaClientError::MediaLibError(e)) => assert!(e.is_cancelled()),
+            other => panic!("Expected a cancelled frame, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn it_can_cancel_decoding_from_another_thread() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let cancel_handle = key_frame_iterator.get_cancel_handle();
+        std::thread::spawn(move || cancel_handle.cancel())
+            .join()
+            .unwrap();
+
+        let error = key_frame_iterator
+            .get_keyframe()
+            .unwrap()
+            .match_owned(|_| None, Some)
+            .expect("Expected the cancel to stop decoding");
+        assert!(error.is_cancelled());
+        assert!(key_frame_iterator.get_keyframe().is_none());
+    }
+
+    #[test]
+    fn it_can_report_progress() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        assert!(key_frame_iterator.get_progress().position_us.is_none());
+
+        let mut last_percent = 0.0;
+        while let Some(frame) = key_frame_iterator.get_keyframe().as_ref() {
+            assert!(frame.is_ok());
+            let progress = key_frame_iterator.get_progress();
+            assert!(progress.position_us.is_some());
+            let percent = progress.percent.unwrap();
+            assert!(percent >= last_percent);
+            last_percent = percent;
+        }
+        assert_eq!(key_frame_iterator.get_progress().percent.unwrap(), 100.0);
+    }
+
+    struct SendHealth(std::sync::mpsc::Sender<StreamHealth>);
+
+    impl StreamHealthObserver for SendHealth {
+        extern "C" fn on_health_change(&mut self, health: StreamHealth) {
+            let _ = self.0.send(health);
+        }
+    }
+
+    #[test]
+    fn it_can_monitor_stream_health() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let mut key_frame_iterator = client.get_key_frames(test_movie.to_str().unwrap()).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        key_frame_iterator.set_health_observer(stabby::boxed::Box::new(SendHealth(sender)).into());
+        let reported = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .expect("Expected the observer to be told the health");
+        assert_ne!(reported.status, StreamHealthStatus::Stalled);
+
+        while let Some(frame) = key_frame_iterator.get_keyframe().as_ref() {
+            assert!(frame.is_ok());
+        }
+        let health = key_frame_iterator.get_stream_health();
+        assert!(health.bitrate_bps > 0);
+        assert_eq!(health.gaps, 0);
+        assert_eq!(health.reconnects, 0);
+    }
+
+    #[test]
+    fn it_can_fingerprint_video() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let fingerprint = client
+            .fingerprint_video(test_movie.to_str().unwrap(), JobContext::default())
+            .unwrap();
+
+        assert!(
+            !fingerprint.shots.is_empty(),
+            "No shots found in the test video"
+        );
+        assert_eq!(fingerprint.similarity(&fingerprint), 1.0);
+    }
+
+    #[test]
+    fn it_can_suggest_overlay_placement() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let placements = client
+            .suggest_overlay_placement(
+                test_movie.to_str().unwrap(),
+                OverlayPlacementOptions::default(),
+            )
+            .unwrap();
+
+        assert_eq!(placements.len(), 4);
+        assert!(placements
+            .windows(2)
+            .all(|pair| pair[0].activity <= pair[1].activity));
+        assert!(placements
+            .iter()
+            .all(|placement| (0.0..=1.0).contains(&placement.activity)));
+    }
+
+    #[test]
+    fn it_can_extract_a_thumbnail() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let thumbnail = client
+            .extract_thumbnail(test_movie.to_str().unwrap(), 1000, Size::new(160, 160))
+            .unwrap();
+
+        assert!(thumbnail.size.width <= 160 && thumbnail.size.height <= 160);
+        assert!(thumbnail.size.width == 160 || thumbnail.size.height == 160);
+        assert!(thumbnail.data.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+
+        let progressive = client
+            .extract_thumbnail_with_options(
+                test_movie.to_str().unwrap(),
+                1000,
+                Size::new(160, 160),
+                JpegOptions {
+                    progressive: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(progressive.size, thumbnail.size);
+        assert!(progressive
+            .data
+            .windows(2)
+            .any(|marker| marker == [0xff, 0xc2]));
+    }
+
+    #[test]
+    fn it_can_preview_audio_files() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("audio_preview");
+        let video = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 2000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(video.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        let audio = dir.join("input.wav");
+        let options = ChannelExportOptions {
+            split: false,
+            ..Default::default()
+        };
+        client
+            .export_audio_channels(
+                video.to_str().unwrap(),
+                audio.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+
+        // The same audio with a thumbnail of the test movie as its cover art,
+        // in an ID3v2.3 tag ahead of the RIFF header.
+        let test_movie = test::get_test_data_file("test.mp4");
+        let cover = client
+            .extract_thumbnail(test_movie.to_str().unwrap(), 1000, Size::new(160, 160))
+            .unwrap();
+        let mut apic = vec![0];
+        apic.extend_from_slice(b"image/jpeg\0");
+        apic.extend_from_slice(&[3, 0]);
+        apic.extend_from_slice(&cover.data);
+        let mut tag = b"APIC".to_vec();
+        tag.extend_from_slice(&(apic.len() as u32).to_be_bytes());
+        tag.extend_from_slice(&[0, 0]);
+        tag.extend_from_slice(&apic);
+        let syncsafe = (0..4).rev().map(|i| (tag.len() >> (i * 7)) as u8 & 0x7f);
+        let mut tagged = b"ID3\x03\x00\x00".to_vec();
+        tagged.extend(syncsafe);
+        tagged.extend_from_slice(&tag);
+        tagged.extend_from_slice(&std::fs::read(&audio).unwrap());
+        let with_art = dir.join("with_art.wav");
+        std::fs::write(&with_art, tagged).unwrap();
+
+        let background = Color::new(0x00, 0x60, 0x00);
+        let foreground = Color::new(0xff, 0xff, 0xff);
+        let preview = |input: &std::path::Path, audio_style| {
+            let options = PreviewOptions {
+                size: Size::new(160, 160),
+                audio_style,
+                background,
+                foreground,
+                ..Default::default()
+            };
+            let preview = client
+                .get_preview_image(input.to_str().unwrap(), options)
+                .unwrap();
+            assert!(preview.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+            preview
+        };
+
+        let cover_preview = preview(&with_art, AudioPreviewStyle::Waveform);
+        let waveform = preview(&audio, AudioPreviewStyle::Waveform);
+        let spectrogram = preview(&audio, AudioPreviewStyle::Spectrogram);
+        assert_ne!(cover_preview, waveform);
+        assert_ne!(waveform, spectrogram);
+        #[cfg(feature = "image")]
+        {
+            // Cover art keeps its aspect ratio, rendered audio fills the bounds.
+            let image = decode_image(&cover_preview).unwrap().to_rgb8();
+            assert_eq!(
+                Size::new(image.width(), image.height()),
+                cover.size.scale_to_fit(Size::new(160, 160))
+            );
+
+            let is_background =
+                |pixel: &image::Rgb<u8>| pixel[0] < 0x20 && pixel[1] > 0x40 && pixel[2] < 0x20;
+            let image = decode_image(&waveform).unwrap().to_rgb8();
+            assert_eq!((image.width(), image.height()), (160, 160));
+            let corner = image.get_pixel(80, 2);
+            assert!(is_background(corner), "{:?}", corner);
+            let middle = image.get_pixel(80, 80);
+            assert!(middle.0.iter().all(|&value| value > 0xc0), "{:?}", middle);
+
+            // High frequencies at the top are silent, the tone shows low down.
+            let image = decode_image(&spectrogram).unwrap().to_rgb8();
+            assert_eq!((image.width(), image.height()), (160, 160));
+            let corner = image.get_pixel(80, 2);
+            assert!(is_background(corner), "{:?}", corner);
+            let brightest = (0..160).max_by_key(|&y| image.get_pixel(80, y)[0]).unwrap();
+            assert!(brightest > 120, "Tone drawn at row {}", brightest);
+            assert!(image.get_pixel(80, brightest)[0] > 0x80);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_generate_preview_animations() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let gif = client
+            .generate_preview_animation(
+                test_movie.to_str().unwrap(),
+                500,
+                1000,
+                10,
+                Size::new(160, 160),
+                AnimationFormat::Gif,
+            )
+            .unwrap();
+        assert!(gif.starts_with(b"GIF89a"), "Not a GIF");
+        // Logical screen width and height, little endian.
+        let (width, height) = (
+            u16::from_le_bytes([gif[6], gif[7]]),
+            u16::from_le_bytes([gif[8], gif[9]]),
+        );
+        assert!(width <= 160 && height <= 160);
+        // Each frame has a graphic control extension giving its delay. The
+        // same bytes can turn up in image data, so this is a lower bound.
+        let frames = gif
+            .windows(3)
+            .filter(|window| *window == [0x21, 0xF9, 0x04])
+            .count();
+        assert!(frames >= 10);
+        assert_eq!(gif.last(), Some(&0x3B), "GIF has no trailer");
+
+        assert!(client
+            .generate_preview_animation(
+                test_movie.to_str().unwrap(),
+                0,
+                1000,
+                0,
+                Size::new(160, 160),
+                AnimationFormat::Gif,
+            )
+            .is_err());
+        // Longer than a preview.
+        assert!(client
+            .generate_preview_animation(
+                test_movie.to_str().unwrap(),
+                0,
+                10 * 60 * 1000,
+                10,
+                Size::new(160, 160),
+                AnimationFormat::Gif,
+            )
+            .is_err());
+    }
+
+    #[cfg(feature = "libwebp")]
+    #[test]
+    fn it_can_generate_webp_preview_animations() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let webp = client
+            .generate_preview_animation(
+                test_movie.to_str().unwrap(),
+                500,
+                1000,
+                10,
+                Size::new(160, 160),
+                AnimationFormat::WebP,
+            )
+            .unwrap();
+        assert!(webp.starts_with(b"RIFF") && &webp[8..12] == b"WEBP");
+        // Animated WebPs have an ANIM chunk, and a frame for each ANMF.
+        assert!(webp.windows(4).any(|chunk| chunk == b"ANIM"));
+        assert!(webp.windows(4).filter(|chunk| *chunk == b"ANMF").count() >= 10);
+    }
+
+    #[test]
+    fn it_can_make_a_filmstrip() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("filmstrip");
+        let input = dir.join("input.mp4");
+
+        let options = TestAssetOptions {
+            size: Size::new(320, 240),
+            duration_ms: 2000,
+            frame_rate: 25,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), options, JobContext::default())
+            .unwrap();
+
+        let strip = client.filmstrip(input.to_str().unwrap(), 5, 64).unwrap();
+        assert!(strip.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+        #[cfg(feature = "image")]
+        {
+            let image = decode_image(&strip).unwrap();
+            assert_eq!((image.width(), image.height()), (5 * 64, 48));
+        }
+
+        assert!(client.filmstrip(input.to_str().unwrap(), 0, 64).is_err());
+        assert!(client.filmstrip(input.to_str().unwrap(), 5, 0).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_generate_a_storyboard() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let options = StoryboardOptions {
+            interval_ms: 1000,
+            columns: 3,
+            tile_width: 120,
+            sprite_url: "https://example.com/sprite.jpg".into(),
+        };
+        let storyboard = client
+            .generate_storyboard(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        let sprite = &storyboard.sprite;
+        assert!(sprite.data.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+        assert!(sprite.size.width <= 360);
+        assert_eq!(sprite.size.width % 120, 0);
+
+        let lines: Vec<&str> = storyboard.vtt.as_str().lines().collect();
+        assert_eq!(lines[0], "WEBVTT");
+        let cues: Vec<(&str, &str)> = lines
+            .windows(2)
+            .filter(|pair| pair[0].contains(" --> "))
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        assert!(!cues.is_empty());
+        assert_eq!(cues[0].0, "00:00:00.000 --> 00:00:01.000");
+        let tile_height = sprite.size.height / cues.len().div_ceil(3) as u32;
+        assert_eq!(
+            cues[0].1,
+            format!(
+                "https://example.com/sprite.jpg#xywh=0,0,120,{}",
+                tile_height
+            )
+        );
+
+        // Tiles fill each row before starting the next.
+        if cues.len() > 3 {
+            assert!(cues[3].1.contains("#xywh=0,"));
+            assert!(!cues[3].1.contains("#xywh=0,0,"));
+        }
+
+        let options = StoryboardOptions {
+            interval_ms: 0,
+            ..Default::default()
+        };
+        assert!(client
+            .generate_storyboard(test_movie.to_str().unwrap(), options)
+            .is_err());
+    }
+
+    #[test]
+    fn it_can_generate_a_preview_bundle() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+        let output_dir = test::unique_temp_dir("preview_bundle");
+
+        // GIF rather than the default WebP, which needs libwebp.
+        let options = PreviewBundleOptions {
+            animation_format: AnimationFormat::Gif,
+            storyboard_interval_ms: 1000,
+            storyboard_columns: 4,
+            output_dir: stabby::option::Option::Some(output_dir.to_string_lossy().as_ref().into()),
+            ..Default::default()
+        };
+        let bundle = client
+            .generate_preview_bundle(test_movie.to_str().unwrap(), options)
+            .unwrap();
+
+        for image in [&bundle.poster, &bundle.filmstrip, &bundle.storyboard] {
+            assert!(image.data.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+        }
+        assert!(bundle.poster.size.width <= 1280 && bundle.poster.size.height <= 720);
+        assert_eq!(bundle.filmstrip.size.width, 1600);
+        assert!(bundle.animation.starts_with(b"GIF89a"), "Not a GIF");
+        assert!(bundle.warnings.is_empty());
+
+        // A cue per second, each pointing inside the sprite.
+        let vtt = bundle.storyboard_vtt.as_str();
+        assert!(vtt.starts_with("WEBVTT"));
+        let cues: Vec<&str> = vtt
+            .lines()
+            .filter_map(|line| line.strip_prefix("storyboard.jpg#xywh="))
+            .collect();
+        assert!(!cues.is_empty());
+        assert_eq!(vtt.matches(" --> ").count(), cues.len());
+        for cue in cues {
+            let xywh: Vec<u32> = cue.split(',').map(|value| value.parse().unwrap()).collect();
+            assert!(xywh[0] + xywh[2] <= bundle.storyboard.size.width);
+            assert!(xywh[1] + xywh[3] <= bundle.storyboard.size.height);
+        }
+
+        assert_eq!(bundle.outputs.len(), 5);
+        for output in bundle.outputs.iter() {
+            let written = std::fs::metadata(output.path.as_str()).unwrap();
+            assert_eq!(written.len(), output.bytes);
+        }
+        assert!(output_dir.join("preview.gif").exists());
+
+        // Files already there are left alone unless they're to be replaced.
+        let options = PreviewBundleOptions {
+            animation_format: AnimationFormat::Gif,
+            output_dir: stabby::option::Option::Some(output_dir.to_string_lossy().as_ref().into()),
+            output: OutputOptions {
+                overwrite: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(client
+            .generate_preview_bundle(test_movie.to_str().unwrap(), options)
+            .is_err());
+
+        // Without an animation the rest of the bundle is still made.
+        let options = PreviewBundleOptions {
+            animation_fps: 0,
+            ..Default::default()
+        };
+        let bundle = client
+            .generate_preview_bundle(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(bundle.animation.is_empty());
+        assert_eq!(bundle.warnings.len(), 1);
+        assert!(bundle.poster.data.starts_with(&[0xFF, 0xD8]));
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[cfg(feature = "libwebp")]
+    #[test]
+    fn it_makes_webp_preview_bundles_by_default() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+        let output_dir = test::unique_temp_dir("webp_preview_bundle");
+
+        let options = PreviewBundleOptions {
+            output_dir: stabby::option::Option::Some(output_dir.to_string_lossy().as_ref().into()),
+            ..Default::default()
+        };
+        assert_eq!(options.animation_format, AnimationFormat::WebP);
+        let bundle = client
+            .generate_preview_bundle(test_movie.to_str().unwrap(), options)
+            .unwrap();
+        assert!(bundle.warnings.is_empty());
+        let animation = &bundle.animation;
+        assert!(animation.starts_with(b"RIFF") && &animation[8..12] == b"WEBP");
+        assert!(animation.windows(4).any(|chunk| chunk == b"ANIM"));
+        let written = std::fs::read(output_dir.join("preview.webp")).unwrap();
+        assert_eq!(written, &animation[..]);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_export_an_image_sequence() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("image_sequence");
+        let input = dir.join("input.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 90),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        let every_frame = dir.join("every_frame");
+        let report = client
+            .export_image_sequence(
+                input.to_str().unwrap(),
+                every_frame.to_str().unwrap(),
+                ImageSequenceOptions::default(),
+                JobContext::default(),
+            )
+            .unwrap();
+        assert_eq!(report.frames_returned, 10);
+        let mut names: Vec<String> = std::fs::read_dir(&every_frame)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        let expected: Vec<String> = (1..=10).map(|n| format!("frame_{:06}.png", n)).collect();
+        assert_eq!(names, expected);
+
+        let sampled = dir.join("sampled");
+        let options = ImageSequenceOptions {
+            fps: 2,
+            start_number: 0,
+            ..Default::default()
+        };
+        let report = client
+            .export_image_sequence(
+                input.to_str().unwrap(),
+                sampled.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+        assert_eq!(report.frames_returned, 2);
+        assert!(sampled.join("frame_000000.png").exists());
+        assert!(sampled.join("frame_000001.png").exists());
+
+        let options = ImageSequenceOptions {
+            prefix: "../escaped_".into(),
+            ..Default::default()
+        };
+        assert!(client
+            .export_image_sequence(
+                input.to_str().unwrap(),
+                sampled.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .is_err());
+        assert!(!dir.join("escaped_000001.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_export_a_dataset_with_a_manifest() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("dataset");
+        // Names that need quoting in CSV and escaping in JSON.
+        let inputs: Vec<PathBuf> = ["clip \"one\", take 2.mp4", "clip\\two.mp4"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                let asset = TestAssetOptions {
+                    size: Size::new(160, 90),
+                    duration_ms: 1000,
+                    frame_rate: 10,
+                    ..Default::default()
+                };
+                client
+                    .generate_test_asset(path.to_str().unwrap(), asset, JobContext::default())
+                    .unwrap();
+                path
+            })
+            .collect();
+        let input_strs: Vec<&str> = inputs.iter().map(|path| path.to_str().unwrap()).collect();
+
+        let output = dir.join("json");
+        let options = DatasetExportOptions {
+            sequence: ImageSequenceOptions {
+                fps: 2,
+                ..Default::default()
+            },
+            manifest_format: ManifestFormat::Json,
+            scene_scores: true,
+            quality_scores: true,
+        };
+        let report = client
+            .export_dataset(
+                &input_strs,
+                output.to_str().unwrap(),
+                options.clone(),
+                JobContext::default(),
+            )
+            .unwrap();
+        assert_eq!(report.frames_returned, 4);
+
+        let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        let entries = manifest.as_array().unwrap();
+        assert_eq!(entries.len(), 4);
+        for (index, entry) in entries.iter().enumerate() {
+            let file = entry["file"].as_str().unwrap();
+            assert_eq!(file, format!("frame_{:06}.png", index + 1));
+            assert!(output.join(file).exists());
+            assert_eq!(entry["source"].as_str().unwrap(), input_strs[index / 2]);
+            assert_eq!(
+                entry["pts_us"].as_i64().unwrap(),
+                (index as i64 % 2) * 500_000
+            );
+            assert_eq!(
+                entry["frame_index"].as_u64().unwrap(),
+                (index as u64 % 2) * 5
+            );
+            assert_eq!(entry["width"].as_u64().unwrap(), 160);
+            assert_eq!(entry["height"].as_u64().unwrap(), 90);
+            let scene_score = entry["scene_score"].as_f64().unwrap();
+            assert!((0.0..=1.0).contains(&scene_score));
+            if index % 2 == 0 {
+                assert_eq!(scene_score, 1.0);
+            }
+            let quality_score = entry["quality_score"].as_f64().unwrap();
+            assert!((0.0..=1.0).contains(&quality_score));
+        }
+
+        let output = dir.join("csv");
+        let options = DatasetExportOptions {
+            manifest_format: ManifestFormat::Csv,
+            ..options
+        };
+        client
+            .export_dataset(
+                &input_strs,
+                output.to_str().unwrap(),
+                options,
+                JobContext::default(),
+            )
+            .unwrap();
+        let manifest = std::fs::read_to_string(output.join("manifest.csv")).unwrap();
+        let lines: Vec<&str> = manifest.lines().collect();
+        assert_eq!(
+            lines[0],
+            "file,source,pts_us,frame_index,width,height,scene_score,quality_score"
+        );
+        assert_eq!(lines.len(), 5);
+        let quoted = format!("\"{}\"", input_strs[0].replace('"', "\"\""));
+        assert!(lines[1].starts_with(&format!("frame_000001.png,{},0,0,160,90,", quoted)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_export_a_frame_through_a_lut() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+        let dir = test::unique_temp_dir("lut");
+
+        // Turns every colour into its negative, red changing fastest.
+        let lut_path = dir.join("negative.cube");
+        let mut cube = String::from("LUT_3D_SIZE 2\n");
+        for i in 0..8 {
+            cube.push_str(&format!(
+                "{} {} {}\n",
+                1 - (i & 1),
+                1 - ((i >> 1) & 1),
+                1 - ((i >> 2) & 1)
+            ));
+        }
+        std::fs::write(&lut_path, cube).unwrap();
+        let lut: stabby::string::String = lut_path.to_string_lossy().as_ref().into();
+
+        let plain = client
+            .export_frame(test_movie.to_str().unwrap(), 1_000_000, Default::default())
+            .unwrap();
+        let options = FrameExportOptions {
+            lut_path: stabby::option::Option::Some(lut.clone()),
+            ..Default::default()
+        };
+        let graded = client
+            .export_frame(test_movie.to_str().unwrap(), 1_000_000, options.clone())
+            .unwrap();
+        assert!(graded.starts_with(b"\x89PNG"), "Not a PNG");
+        assert_ne!(graded, plain);
+
+        // Image sequences go through the LUT too.
+        let sequence_options = ImageSequenceOptions {
+            fps: 1,
+            image: options,
+            ..Default::default()
+        };
+        let report = client
+            .export_image_sequence(
+                test_movie.to_str().unwrap(),
+                dir.join("frames").to_str().unwrap(),
+                sequence_options,
+                JobContext::default(),
+            )
+            .unwrap();
+        assert!(report.frames_returned > 0);
+
+        let not_a_lut = FrameExportOptions {
+            lut_path: stabby::option::Option::Some(test_movie.to_str().unwrap().into()),
+            ..Default::default()
+        };
+        assert!(client
+            .export_frame(test_movie.to_str().unwrap(), 1_000_000, not_a_lut.clone())
+            .is_err());
+        let sequence_options = ImageSequenceOptions {
+            image: not_a_lut,
+            ..Default::default()
+        };
+        assert!(client
+            .export_image_sequence(
+                test_movie.to_str().unwrap(),
+                dir.join("rejected").to_str().unwrap(),
+                sequence_options,
+                JobContext::default(),
+            )
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct ReadsSlate;
+
+    impl TextRecognizer for ReadsSlate {
+        extern "C" fn recognize(&mut self, image: OcrImage) -> stabby::string::String {
+            assert_eq!(
+                image.pixels.len(),
+                image.width as usize * image.height as usize
+            );
+            " SLATE \n".into()
+        }
+    }
+
+    #[test]
+    fn it_can_extract_on_screen_text() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let test_movie = test::get_test_data_file("test.mp4");
+
+        let texts = client
+            .extract_on_screen_text(
+                test_movie.to_str().unwrap(),
+                OcrOptions::default(),
+                stabby::boxed::Box::new(ReadsSlate).into(),
+                JobContext::default(),
+            )
+            .unwrap();
+
+        assert_eq!(texts.len(), 1, "Identical samples should be merged");
+        assert_eq!(&*texts[0].text, "SLATE");
+        assert!(texts[0].start_us <= texts[0].end_us);
+    }
+
+    #[test]
+    fn it_can_look_for_segments_without_audio() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("segments");
+        let silent = dir.join("silent.mp4");
+        let with_tone = dir.join("tone.mp4");
+
+        for (path, tone_hz) in [(&silent, 0), (&with_tone, 440)] {
+            let options = TestAssetOptions {
+                size: Size::new(320, 240),
+                duration_ms: 2000,
+                frame_rate: 25,
+                tone_hz,
+                ..Default::default()
+            };
+            client
+                .generate_test_asset(path.to_str().unwrap(), options, JobContext::default())
+                .unwrap();
+        }
+
+        // Either side missing audio means there's nothing to match intros
+        // on, but the frames can still be looked at.
+        let segments = client
+            .detect_intro_credits(silent.to_str().unwrap(), &[with_tone.to_str().unwrap()])
+            .unwrap();
+        assert!(segments
+            .iter()
+            .all(|segment| segment.kind != SegmentKind::Intro));
+        let segments = client
+            .detect_intro_credits(with_tone.to_str().unwrap(), &[silent.to_str().unwrap()])
+            .unwrap();
+        assert!(segments
+            .iter()
+            .all(|segment| segment.kind != SegmentKind::Intro));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_decode_audio_to_any_format() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("decode_audio");
+        let input = dir.join("tone.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 90),
+            duration_ms: 1000,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(input.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+
+        for format in [
+            ResampleOptions {
+                sample_rate: 16000,
+                channels: 1,
+                sample_format: SampleFormat::F32,
+            },
+            ResampleOptions {
+                sample_rate: 44100,
+                channels: 2,
+                sample_format: SampleFormat::S16,
+            },
+        ] {
+            let mut samples = client
+                .decode_audio(input.to_str().unwrap(), format)
+                .unwrap();
+            assert_eq!(samples.get_format(), format);
+            let bytes_per_frame = format.channels as usize * format.sample_format.bytes();
+            let mut frames = 0;
+            let mut last_timestamp_us = -1;
+            while let Some(decoded) = samples.get_samples().into_std().unwrap() {
+                assert_eq!(
+                    decoded.data.len(),
+                    decoded.frames as usize * bytes_per_frame
+                );
+                frames += decoded.frames;
+                if let Some(timestamp_us) = decoded.timestamp_us.as_ref() {
+                    assert!(*timestamp_us > last_timestamp_us);
+                    last_timestamp_us = *timestamp_us;
+                }
+            }
+            // A second of audio, give or take the encoder's padding.
+            let expected = format.sample_rate as i64;
+            assert!(
+                (frames as i64 - expected).abs() < expected / 20,
+                "{} frames at {}Hz",
+                frames,
+                format.sample_rate
+            );
+        }
+
+        let silent = dir.join("silent.mp4");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 90),
+            duration_ms: 500,
+            ..Default::default()
+        };
+        client
+            .generate_test_asset(silent.to_str().unwrap(), asset, JobContext::default())
+            .unwrap();
+        assert!(client
+            .decode_audio(silent.to_str().unwrap(), ResampleOptions::default())
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_can_detect_a_leader() {
+        let lib = test::get_media_client_lib();
+        let client = load(&lib).unwrap();
+        let dir = test::unique_temp_dir("leader");
+        let generate = |name: &str, tone_hz: u32| {
+            let path = dir.join(name);
+            let options = TestAssetOptions {
+                size: Size::new(320, 180),
+                duration_ms: 3000,
+                tone_hz,
+                ..Default::default()
+            };
+            client
+                .generate_test_asset(path.to_str().unwrap(), options, JobContext::default())
+                .unwrap();
+            path
+        };
+        let line_up = generate("line_up.mp4", 1000);
+        let other_tone = generate("other_tone.mp4", 440);
+        let silent = generate("silent.mp4", 0);
+
+        let segments = client.detect_leader(line_up.to_str().unwrap()).unwrap();
+        let tone: Vec<_> = segments
+            .iter()
+            .filter(|segment| segment.kind == SegmentKind::Tone)
+            .collect();
+        assert_eq!(tone.len(), 1);
+        assert_eq!(tone[0].start_us, 0);
+        assert!(tone[0].end_us >= 2_500_000, "ends at {}", tone[0].end_us);
+        assert!(tone[0].confidence > 0.9);
+
+        for input in [other_tone, silent] {
+            let segments = client.detect_leader(input.to_str().unwrap()).unwrap();
+            assert!(segments
+                .iter()
+                .all(|segment| segment.kind != SegmentKind::Tone));
         }
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
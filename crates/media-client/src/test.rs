@@ -56,3 +56,19 @@ pub(crate) fn get_test_data_file(file_name: &str) -> std::path::PathBuf {
     let test_data_dir = get_test_data_dir();
     test_data_dir.join(file_name)
 }
+
+#[cfg(test)]
+pub(crate) fn unique_temp_dir(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Tests run in parallel, and more than one test binary may be running.
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let dir = env::temp_dir().join(format!(
+        "media-client-{}-{}-{}",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).expect("Failed to create a temporary directory");
+    dir
+}
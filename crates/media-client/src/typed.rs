@@ -0,0 +1,1847 @@
+//! Std typed mirrors of the options and report types, with serde, so job
+//! configs and results can be stored as JSON or YAML. Each converts to and
+//! from the stabby type media-lib takes or returns, e.g.
+//! `media_types::MediaFrameDecoderOptions::from(config)`.
+//!
+//! Deserializing fills fields that are left out with their defaults, so
+//! configs only need what differs. Types that carry pixels, samples or
+//! encoded bytes, like `EncodedImage` or `PreviewBundle`, aren't mirrored.
+
+use media_types::{
+    AlphaBackground, AnimationFormat, AudioPreviewStyle, ChromaSubsampling, Color, Corner,
+    DurationSource, HardwareBackend, ImageFormat, JpegOptions, LogLevel, ManifestFormat,
+    PosterSelection, Rect, RedactionMethod, SampleFormat, ScalingMode, SegmentKind, Size,
+    StreamHealthStatus, SyncClock, ToneMapping, VideoCodec,
+};
+use serde::{Deserialize, Serialize};
+
+fn string(string: &stabby::string::String) -> String {
+    string.as_str().into()
+}
+
+fn optional_string(string: &stabby::option::Option<stabby::string::String>) -> Option<String> {
+    string.as_ref().map(|string| string.as_str().into())
+}
+
+fn stabby_string(string: &str) -> stabby::string::String {
+    string.into()
+}
+
+fn stabby_optional_string(
+    string: Option<String>,
+) -> stabby::option::Option<stabby::string::String> {
+    string.map(|string| stabby_string(&string)).into()
+}
+
+fn stabby_vec<T>(items: impl IntoIterator<Item = T>) -> stabby::vec::Vec<T> {
+    let mut stabby_vec = stabby::vec::Vec::new();
+    for item in items {
+        stabby_vec.push(item);
+    }
+    stabby_vec
+}
+
+// The enums are plain Rust enums already, serde only needs their shape.
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "HardwareBackend")]
+enum HardwareBackendDef {
+    Auto,
+    SoftwareOnly,
+    Cuda,
+    VideoToolbox,
+    Vaapi,
+    Qsv,
+    D3D11,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ToneMapping")]
+enum ToneMappingDef {
+    Off,
+    Hable,
+    Reinhard,
+    Mobius,
+    Clip,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ScalingMode")]
+enum ScalingModeDef {
+    Stretch,
+    Fit,
+    Fill,
+    FitWidth,
+    FitHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AlphaBackground")]
+enum AlphaBackgroundDef {
+    Ignore,
+    Solid,
+    Checkerboard,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ImageFormat")]
+enum ImageFormatDef {
+    Jpeg,
+    Png,
+    Tiff,
+    WebP,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ChromaSubsampling")]
+enum ChromaSubsamplingDef {
+    Yuv420,
+    Yuv422,
+    Yuv444,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DurationSource")]
+enum DurationSourceDef {
+    Unknown,
+    Container,
+    Scanned,
+    Bitrate,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "LogLevel")]
+enum LogLevelDef {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SampleFormat")]
+enum SampleFormatDef {
+    S16,
+    S32,
+    F32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ManifestFormat")]
+enum ManifestFormatDef {
+    Csv,
+    Json,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "VideoCodec")]
+enum VideoCodecDef {
+    H264,
+    Hevc,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AudioPreviewStyle")]
+enum AudioPreviewStyleDef {
+    Waveform,
+    Spectrogram,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PosterSelection")]
+enum PosterSelectionDef {
+    FirstFrame,
+    Smart,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AnimationFormat")]
+enum AnimationFormatDef {
+    Gif,
+    WebP,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Corner")]
+enum CornerDef {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RedactionMethod")]
+enum RedactionMethodDef {
+    Blur,
+    Delogo,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SyncClock")]
+enum SyncClockDef {
+    WallClock,
+    Timecode,
+    Start,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "StreamHealthStatus")]
+enum StreamHealthStatusDef {
+    Ok,
+    Degraded,
+    Stalled,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SegmentKind")]
+enum SegmentKindDef {
+    Intro,
+    Credits,
+    ColorBars,
+    Tone,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Size")]
+struct SizeDef {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Rect")]
+struct RectDef {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Color")]
+struct ColorDef {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JpegOptions")]
+struct JpegOptionsDef {
+    progressive: bool,
+    #[serde(with = "ChromaSubsamplingDef")]
+    subsampling: ChromaSubsampling,
+    quality: u32,
+}
+
+/// See `media_types::DecryptionKey`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecryptionKey {
+    pub key_id: String,
+    pub key: String,
+}
+
+/// See `media_types::DecryptionOptions`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecryptionOptions {
+    pub keys: Vec<DecryptionKey>,
+    pub aes_iv: Option<String>,
+}
+
+impl From<&media_types::DecryptionOptions> for DecryptionOptions {
+    fn from(options: &media_types::DecryptionOptions) -> Self {
+        DecryptionOptions {
+            keys: options
+                .keys
+                .iter()
+                .map(|key| DecryptionKey {
+                    key_id: string(&key.key_id),
+                    key: string(&key.key),
+                })
+                .collect(),
+            aes_iv: optional_string(&options.aes_iv),
+        }
+    }
+}
+
+impl From<DecryptionOptions> for media_types::DecryptionOptions {
+    fn from(options: DecryptionOptions) -> Self {
+        media_types::DecryptionOptions {
+            keys: stabby_vec(options.keys.iter().map(|key| media_types::DecryptionKey {
+                key_id: stabby_string(&key.key_id),
+                key: stabby_string(&key.key),
+            })),
+            aes_iv: stabby_optional_string(options.aes_iv),
+        }
+    }
+}
+
+/// See `media_types::MediaFrameDecoderOptions`, the fields mean the same.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaFrameDecoderOptions {
+    pub realtime: bool,
+    pub waveform_fallback: bool,
+    pub hw_prescale: bool,
+    pub auto_rotate: bool,
+    pub reuse_decoders: bool,
+    pub convert_to_srgb: bool,
+    pub deinterlace: bool,
+    pub flatten_360: bool,
+    pub memory_map: bool,
+    #[serde(with = "HardwareBackendDef")]
+    pub hw_backend: HardwareBackend,
+    #[serde(with = "ToneMappingDef")]
+    pub tone_mapping: ToneMapping,
+    #[serde(with = "ScalingModeDef")]
+    pub scaling_mode: ScalingMode,
+    #[serde(with = "AlphaBackgroundDef")]
+    pub alpha_background: AlphaBackground,
+    #[serde(with = "ColorDef")]
+    pub alpha_color: Color,
+    #[serde(with = "ImageFormatDef")]
+    pub image_format: ImageFormat,
+    pub target_width: u32,
+    pub target_height: u32,
+    pub max_latency_ms: u32,
+    pub high_watermark_ms: u32,
+    pub low_watermark_ms: u32,
+    pub encode_threads: u32,
+    pub frame_queue_size: u32,
+    pub sample_count: u32,
+    pub frame_step: u32,
+    pub start_time_ms: u32,
+    pub end_time_ms: u32,
+    pub hw_initial_pool_size: u32,
+    pub hw_extra_frames: u32,
+    pub flat_view_fov_degrees: u32,
+    pub network_timeout_ms: u32,
+    pub stall_timeout_ms: u32,
+    pub max_jitter_ms: u32,
+    pub max_fps: f64,
+    pub stream_index: Option<u32>,
+    pub force_format: Option<String>,
+    pub user_agent: Option<String>,
+    pub hw_device: Option<String>,
+    pub hw_device_types: Vec<String>,
+    pub diagnostics_dir: Option<String>,
+    pub decryption: DecryptionOptions,
+    #[serde(with = "JpegOptionsDef")]
+    pub jpeg: JpegOptions,
+}
+
+impl Default for MediaFrameDecoderOptions {
+    fn default() -> Self {
+        MediaFrameDecoderOptions::from(&media_types::MediaFrameDecoderOptions::default())
+    }
+}
+
+impl From<&media_types::MediaFrameDecoderOptions> for MediaFrameDecoderOptions {
+    fn from(options: &media_types::MediaFrameDecoderOptions) -> Self {
+        MediaFrameDecoderOptions {
+            realtime: options.realtime,
+            waveform_fallback: options.waveform_fallback,
+            hw_prescale: options.hw_prescale,
+            auto_rotate: options.auto_rotate,
+            reuse_decoders: options.reuse_decoders,
+            convert_to_srgb: options.convert_to_srgb,
+            deinterlace: options.deinterlace,
+            flatten_360: options.flatten_360,
+            memory_map: options.memory_map,
+            hw_backend: options.hw_backend,
+            tone_mapping: options.tone_mapping,
+            scaling_mode: options.scaling_mode,
+            alpha_background: options.alpha_background,
+            alpha_color: options.alpha_color,
+            image_format: options.image_format,
+            target_width: options.target_width,
+            target_height: options.target_height,
+            max_latency_ms: options.max_latency_ms,
+            high_watermark_ms: options.high_watermark_ms,
+            low_watermark_ms: options.low_watermark_ms,
+            encode_threads: options.encode_threads,
+            frame_queue_size: options.frame_queue_size,
+            sample_count: options.sample_count,
+            frame_step: options.frame_step,
+            start_time_ms: options.start_time_ms,
+            end_time_ms: options.end_time_ms,
+            hw_initial_pool_size: options.hw_initial_pool_size,
+            hw_extra_frames: options.hw_extra_frames,
+            flat_view_fov_degrees: options.flat_view_fov_degrees,
+            network_timeout_ms: options.network_timeout_ms,
+            stall_timeout_ms: options.stall_timeout_ms,
+            max_jitter_ms: options.max_jitter_ms,
+            max_fps: options.max_fps,
+            stream_index: options.stream_index.as_ref().copied(),
+            force_format: optional_string(&options.force_format),
+            user_agent: optional_string(&options.user_agent),
+            hw_device: optional_string(&options.hw_device),
+            hw_device_types: options.hw_device_types.iter().map(string).collect(),
+            diagnostics_dir: optional_string(&options.diagnostics_dir),
+            decryption: DecryptionOptions::from(&options.decryption),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+impl From<MediaFrameDecoderOptions> for media_types::MediaFrameDecoderOptions {
+    fn from(options: MediaFrameDecoderOptions) -> Self {
+        media_types::MediaFrameDecoderOptions {
+            realtime: options.realtime,
+            waveform_fallback: options.waveform_fallback,
+            hw_prescale: options.hw_prescale,
+            auto_rotate: options.auto_rotate,
+            reuse_decoders: options.reuse_decoders,
+            convert_to_srgb: options.convert_to_srgb,
+            deinterlace: options.deinterlace,
+            flatten_360: options.flatten_360,
+            memory_map: options.memory_map,
+            hw_backend: options.hw_backend,
+            tone_mapping: options.tone_mapping,
+            scaling_mode: options.scaling_mode,
+            alpha_background: options.alpha_background,
+            alpha_color: options.alpha_color,
+            image_format: options.image_format,
+            target_width: options.target_width,
+            target_height: options.target_height,
+            max_latency_ms: options.max_latency_ms,
+            high_watermark_ms: options.high_watermark_ms,
+            low_watermark_ms: options.low_watermark_ms,
+            encode_threads: options.encode_threads,
+            frame_queue_size: options.frame_queue_size,
+            sample_count: options.sample_count,
+            frame_step: options.frame_step,
+            start_time_ms: options.start_time_ms,
+            end_time_ms: options.end_time_ms,
+            hw_initial_pool_size: options.hw_initial_pool_size,
+            hw_extra_frames: options.hw_extra_frames,
+            flat_view_fov_degrees: options.flat_view_fov_degrees,
+            network_timeout_ms: options.network_timeout_ms,
+            stall_timeout_ms: options.stall_timeout_ms,
+            max_jitter_ms: options.max_jitter_ms,
+            max_fps: options.max_fps,
+            stream_index: options.stream_index.into(),
+            force_format: stabby_optional_string(options.force_format),
+            user_agent: stabby_optional_string(options.user_agent),
+            hw_device: stabby_optional_string(options.hw_device),
+            hw_device_types: stabby_vec(
+                options
+                    .hw_device_types
+                    .iter()
+                    .map(|name| stabby_string(name)),
+            ),
+            diagnostics_dir: stabby_optional_string(options.diagnostics_dir),
+            decryption: options.decryption.into(),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+/// See `media_types::InitOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InitOptions {
+    #[serde(with = "LogLevelDef")]
+    pub log_level: LogLevel,
+    pub network: bool,
+    pub hardware_preheat: Vec<String>,
+    pub max_alloc_bytes: u64,
+    pub temp_dir: Option<String>,
+}
+
+impl Default for InitOptions {
+    fn default() -> Self {
+        InitOptions::from(&media_types::InitOptions::default())
+    }
+}
+
+impl From<&media_types::InitOptions> for InitOptions {
+    fn from(options: &media_types::InitOptions) -> Self {
+        InitOptions {
+            log_level: options.log_level,
+            network: options.network,
+            hardware_preheat: options.hardware_preheat.iter().map(string).collect(),
+            max_alloc_bytes: options.max_alloc_bytes,
+            temp_dir: optional_string(&options.temp_dir),
+        }
+    }
+}
+
+impl From<InitOptions> for media_types::InitOptions {
+    fn from(options: InitOptions) -> Self {
+        media_types::InitOptions {
+            log_level: options.log_level,
+            network: options.network,
+            hardware_preheat: stabby_vec(
+                options
+                    .hardware_preheat
+                    .iter()
+                    .map(|name| stabby_string(name)),
+            ),
+            max_alloc_bytes: options.max_alloc_bytes,
+            temp_dir: stabby_optional_string(options.temp_dir),
+        }
+    }
+}
+
+/// See `media_types::SphericalMetadata`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SphericalMetadata {
+    pub projection: String,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub roll: f64,
+}
+
+/// See `media_types::StreamInfo`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub kind: String,
+    pub codec_name: String,
+    pub pixel_format: String,
+    pub frame_rate: Option<f64>,
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+    pub is_default: bool,
+    pub is_attached_picture: bool,
+    pub spherical: Option<SphericalMetadata>,
+}
+
+impl From<&media_types::StreamInfo> for StreamInfo {
+    fn from(stream: &media_types::StreamInfo) -> Self {
+        StreamInfo {
+            kind: string(&stream.kind),
+            codec_name: string(&stream.codec_name),
+            pixel_format: string(&stream.pixel_format),
+            frame_rate: stream.frame_rate.as_ref().copied(),
+            index: stream.index,
+            width: stream.width,
+            height: stream.height,
+            is_default: stream.is_default,
+            is_attached_picture: stream.is_attached_picture,
+            spherical: stream
+                .spherical
+                .as_ref()
+                .map(|spherical| SphericalMetadata {
+                    projection: string(&spherical.projection),
+                    yaw: spherical.yaw,
+                    pitch: spherical.pitch,
+                    roll: spherical.roll,
+                }),
+        }
+    }
+}
+
+impl From<StreamInfo> for media_types::StreamInfo {
+    fn from(stream: StreamInfo) -> Self {
+        media_types::StreamInfo {
+            kind: stabby_string(&stream.kind),
+            codec_name: stabby_string(&stream.codec_name),
+            pixel_format: stabby_string(&stream.pixel_format),
+            frame_rate: stream.frame_rate.into(),
+            index: stream.index,
+            width: stream.width,
+            height: stream.height,
+            is_default: stream.is_default,
+            is_attached_picture: stream.is_attached_picture,
+            spherical: stream
+                .spherical
+                .map(|spherical| media_types::SphericalMetadata {
+                    projection: stabby_string(&spherical.projection),
+                    yaw: spherical.yaw,
+                    pitch: spherical.pitch,
+                    roll: spherical.roll,
+                })
+                .into(),
+        }
+    }
+}
+
+/// See `media_types::MediaInfo`, what `MediaClient::probe` returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub format_name: String,
+    pub streams: Vec<StreamInfo>,
+    pub duration_us: Option<i64>,
+    pub bit_rate: Option<u64>,
+    #[serde(with = "DurationSourceDef")]
+    pub duration_source: DurationSource,
+}
+
+impl From<&media_types::MediaInfo> for MediaInfo {
+    fn from(info: &media_types::MediaInfo) -> Self {
+        MediaInfo {
+            format_name: string(&info.format_name),
+            streams: info.streams.iter().map(StreamInfo::from).collect(),
+            duration_us: info.duration_us.as_ref().copied(),
+            bit_rate: info.bit_rate.as_ref().copied(),
+            duration_source: info.duration_source,
+        }
+    }
+}
+
+impl From<MediaInfo> for media_types::MediaInfo {
+    fn from(info: MediaInfo) -> Self {
+        media_types::MediaInfo {
+            format_name: stabby_string(&info.format_name),
+            streams: stabby_vec(info.streams.into_iter().map(media_types::StreamInfo::from)),
+            duration_us: info.duration_us.into(),
+            bit_rate: info.bit_rate.into(),
+            duration_source: info.duration_source,
+        }
+    }
+}
+
+/// See `media_types::OutputRecord`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputRecord {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// See `media_types::ProcessingReport`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessingReport {
+    pub wall_time_ms: u64,
+    pub frames_decoded: u64,
+    pub frames_returned: u64,
+    pub frames_dropped: u64,
+    pub hardware_device: Option<String>,
+    pub warnings: Vec<String>,
+    pub outputs: Vec<OutputRecord>,
+}
+
+impl From<&media_types::ProcessingReport> for ProcessingReport {
+    fn from(report: &media_types::ProcessingReport) -> Self {
+        ProcessingReport {
+            wall_time_ms: report.wall_time_ms,
+            frames_decoded: report.frames_decoded,
+            frames_returned: report.frames_returned,
+            frames_dropped: report.frames_dropped,
+            hardware_device: optional_string(&report.hardware_device),
+            warnings: report.warnings.iter().map(string).collect(),
+            outputs: report
+                .outputs
+                .iter()
+                .map(|output| OutputRecord {
+                    path: string(&output.path),
+                    bytes: output.bytes,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ProcessingReport> for media_types::ProcessingReport {
+    fn from(report: ProcessingReport) -> Self {
+        media_types::ProcessingReport {
+            wall_time_ms: report.wall_time_ms,
+            frames_decoded: report.frames_decoded,
+            frames_returned: report.frames_returned,
+            frames_dropped: report.frames_dropped,
+            hardware_device: stabby_optional_string(report.hardware_device),
+            warnings: stabby_vec(report.warnings.iter().map(|warning| stabby_string(warning))),
+            outputs: stabby_vec(
+                report
+                    .outputs
+                    .iter()
+                    .map(|output| media_types::OutputRecord {
+                        path: stabby_string(&output.path),
+                        bytes: output.bytes,
+                    }),
+            ),
+        }
+    }
+}
+
+/// See `media_types::OutputOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    pub atomic: bool,
+    pub durable: bool,
+    pub overwrite: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions::from(&media_types::OutputOptions::default())
+    }
+}
+
+impl From<&media_types::OutputOptions> for OutputOptions {
+    fn from(options: &media_types::OutputOptions) -> Self {
+        OutputOptions {
+            atomic: options.atomic,
+            durable: options.durable,
+            overwrite: options.overwrite,
+        }
+    }
+}
+
+impl From<OutputOptions> for media_types::OutputOptions {
+    fn from(options: OutputOptions) -> Self {
+        media_types::OutputOptions {
+            atomic: options.atomic,
+            durable: options.durable,
+            overwrite: options.overwrite,
+        }
+    }
+}
+
+/// See `media_types::FrameExportOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameExportOptions {
+    #[serde(with = "ImageFormatDef")]
+    pub format: ImageFormat,
+    pub high_bit_depth: bool,
+    pub lut_path: Option<String>,
+    #[serde(with = "JpegOptionsDef")]
+    pub jpeg: JpegOptions,
+}
+
+impl Default for FrameExportOptions {
+    fn default() -> Self {
+        FrameExportOptions::from(&media_types::FrameExportOptions::default())
+    }
+}
+
+impl From<&media_types::FrameExportOptions> for FrameExportOptions {
+    fn from(options: &media_types::FrameExportOptions) -> Self {
+        FrameExportOptions {
+            format: options.format,
+            high_bit_depth: options.high_bit_depth,
+            lut_path: optional_string(&options.lut_path),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+impl From<FrameExportOptions> for media_types::FrameExportOptions {
+    fn from(options: FrameExportOptions) -> Self {
+        media_types::FrameExportOptions {
+            format: options.format,
+            high_bit_depth: options.high_bit_depth,
+            lut_path: stabby_optional_string(options.lut_path),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+/// See `media_types::ImageSequenceOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageSequenceOptions {
+    pub prefix: String,
+    pub start_number: u64,
+    pub fps: u32,
+    pub fps_denominator: u32,
+    pub pad_width: u32,
+    pub threads: u32,
+    pub image: FrameExportOptions,
+    pub output: OutputOptions,
+}
+
+impl Default for ImageSequenceOptions {
+    fn default() -> Self {
+        ImageSequenceOptions::from(&media_types::ImageSequenceOptions::default())
+    }
+}
+
+impl From<&media_types::ImageSequenceOptions> for ImageSequenceOptions {
+    fn from(options: &media_types::ImageSequenceOptions) -> Self {
+        ImageSequenceOptions {
+            prefix: string(&options.prefix),
+            start_number: options.start_number,
+            fps: options.fps,
+            fps_denominator: options.fps_denominator,
+            pad_width: options.pad_width,
+            threads: options.threads,
+            image: FrameExportOptions::from(&options.image),
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<ImageSequenceOptions> for media_types::ImageSequenceOptions {
+    fn from(options: ImageSequenceOptions) -> Self {
+        media_types::ImageSequenceOptions {
+            prefix: stabby_string(&options.prefix),
+            start_number: options.start_number,
+            fps: options.fps,
+            fps_denominator: options.fps_denominator,
+            pad_width: options.pad_width,
+            threads: options.threads,
+            image: options.image.into(),
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::DatasetExportOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatasetExportOptions {
+    pub sequence: ImageSequenceOptions,
+    #[serde(with = "ManifestFormatDef")]
+    pub manifest_format: ManifestFormat,
+    pub scene_scores: bool,
+    pub quality_scores: bool,
+}
+
+impl Default for DatasetExportOptions {
+    fn default() -> Self {
+        DatasetExportOptions::from(&media_types::DatasetExportOptions::default())
+    }
+}
+
+impl From<&media_types::DatasetExportOptions> for DatasetExportOptions {
+    fn from(options: &media_types::DatasetExportOptions) -> Self {
+        DatasetExportOptions {
+            sequence: ImageSequenceOptions::from(&options.sequence),
+            manifest_format: options.manifest_format,
+            scene_scores: options.scene_scores,
+            quality_scores: options.quality_scores,
+        }
+    }
+}
+
+impl From<DatasetExportOptions> for media_types::DatasetExportOptions {
+    fn from(options: DatasetExportOptions) -> Self {
+        media_types::DatasetExportOptions {
+            sequence: options.sequence.into(),
+            manifest_format: options.manifest_format,
+            scene_scores: options.scene_scores,
+            quality_scores: options.quality_scores,
+        }
+    }
+}
+
+/// See `media_types::PreviewOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewOptions {
+    #[serde(with = "SizeDef")]
+    pub size: Size,
+    #[serde(with = "AudioPreviewStyleDef")]
+    pub audio_style: AudioPreviewStyle,
+    #[serde(with = "PosterSelectionDef")]
+    pub poster: PosterSelection,
+    #[serde(with = "AlphaBackgroundDef")]
+    pub alpha_background: AlphaBackground,
+    #[serde(with = "ColorDef")]
+    pub background: Color,
+    #[serde(with = "ColorDef")]
+    pub foreground: Color,
+    #[serde(with = "JpegOptionsDef")]
+    pub jpeg: JpegOptions,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions::from(&media_types::PreviewOptions::default())
+    }
+}
+
+impl From<&media_types::PreviewOptions> for PreviewOptions {
+    fn from(options: &media_types::PreviewOptions) -> Self {
+        PreviewOptions {
+            size: options.size,
+            audio_style: options.audio_style,
+            poster: options.poster,
+            alpha_background: options.alpha_background,
+            background: options.background,
+            foreground: options.foreground,
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+impl From<PreviewOptions> for media_types::PreviewOptions {
+    fn from(options: PreviewOptions) -> Self {
+        media_types::PreviewOptions {
+            size: options.size,
+            audio_style: options.audio_style,
+            poster: options.poster,
+            alpha_background: options.alpha_background,
+            background: options.background,
+            foreground: options.foreground,
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+/// See `media_types::PreviewBundleOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewBundleOptions {
+    #[serde(with = "AnimationFormatDef")]
+    pub animation_format: AnimationFormat,
+    #[serde(with = "SizeDef")]
+    pub poster_size: Size,
+    pub poster_time_ms: u64,
+    pub filmstrip_count: u32,
+    pub filmstrip_cell_width: u32,
+    pub storyboard_interval_ms: u32,
+    pub storyboard_columns: u32,
+    pub storyboard_tile_width: u32,
+    pub animation_start_ms: u64,
+    pub animation_duration_ms: u64,
+    pub animation_fps: u32,
+    #[serde(with = "SizeDef")]
+    pub animation_size: Size,
+    pub output_dir: Option<String>,
+    pub output: OutputOptions,
+    #[serde(with = "JpegOptionsDef")]
+    pub jpeg: JpegOptions,
+}
+
+impl Default for PreviewBundleOptions {
+    fn default() -> Self {
+        PreviewBundleOptions::from(&media_types::PreviewBundleOptions::default())
+    }
+}
+
+impl From<&media_types::PreviewBundleOptions> for PreviewBundleOptions {
+    fn from(options: &media_types::PreviewBundleOptions) -> Self {
+        PreviewBundleOptions {
+            animation_format: options.animation_format,
+            poster_size: options.poster_size,
+            poster_time_ms: options.poster_time_ms,
+            filmstrip_count: options.filmstrip_count,
+            filmstrip_cell_width: options.filmstrip_cell_width,
+            storyboard_interval_ms: options.storyboard_interval_ms,
+            storyboard_columns: options.storyboard_columns,
+            storyboard_tile_width: options.storyboard_tile_width,
+            animation_start_ms: options.animation_start_ms,
+            animation_duration_ms: options.animation_duration_ms,
+            animation_fps: options.animation_fps,
+            animation_size: options.animation_size,
+            output_dir: optional_string(&options.output_dir),
+            output: OutputOptions::from(&options.output),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+impl From<PreviewBundleOptions> for media_types::PreviewBundleOptions {
+    fn from(options: PreviewBundleOptions) -> Self {
+        media_types::PreviewBundleOptions {
+            animation_format: options.animation_format,
+            poster_size: options.poster_size,
+            poster_time_ms: options.poster_time_ms,
+            filmstrip_count: options.filmstrip_count,
+            filmstrip_cell_width: options.filmstrip_cell_width,
+            storyboard_interval_ms: options.storyboard_interval_ms,
+            storyboard_columns: options.storyboard_columns,
+            storyboard_tile_width: options.storyboard_tile_width,
+            animation_start_ms: options.animation_start_ms,
+            animation_duration_ms: options.animation_duration_ms,
+            animation_fps: options.animation_fps,
+            animation_size: options.animation_size,
+            output_dir: stabby_optional_string(options.output_dir),
+            output: options.output.into(),
+            jpeg: options.jpeg,
+        }
+    }
+}
+
+/// See `media_types::StoryboardOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoryboardOptions {
+    pub interval_ms: u32,
+    pub columns: u32,
+    pub tile_width: u32,
+    pub sprite_url: String,
+}
+
+impl Default for StoryboardOptions {
+    fn default() -> Self {
+        StoryboardOptions::from(&media_types::StoryboardOptions::default())
+    }
+}
+
+impl From<&media_types::StoryboardOptions> for StoryboardOptions {
+    fn from(options: &media_types::StoryboardOptions) -> Self {
+        StoryboardOptions {
+            interval_ms: options.interval_ms,
+            columns: options.columns,
+            tile_width: options.tile_width,
+            sprite_url: string(&options.sprite_url),
+        }
+    }
+}
+
+impl From<StoryboardOptions> for media_types::StoryboardOptions {
+    fn from(options: StoryboardOptions) -> Self {
+        media_types::StoryboardOptions {
+            interval_ms: options.interval_ms,
+            columns: options.columns,
+            tile_width: options.tile_width,
+            sprite_url: stabby_string(&options.sprite_url),
+        }
+    }
+}
+
+/// See `media_types::ScrubProxyOptions`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrubProxyOptions {
+    pub bit_rate: u64,
+    pub width: u32,
+    pub fragment_duration_ms: u32,
+    pub speed: f64,
+    pub audio: bool,
+    pub lut_path: Option<String>,
+    pub output: OutputOptions,
+}
+
+impl Default for ScrubProxyOptions {
+    fn default() -> Self {
+        ScrubProxyOptions::from(&media_types::ScrubProxyOptions::default())
+    }
+}
+
+impl From<&media_types::ScrubProxyOptions> for ScrubProxyOptions {
+    fn from(options: &media_types::ScrubProxyOptions) -> Self {
+        ScrubProxyOptions {
+            bit_rate: options.bit_rate,
+            width: options.width,
+            fragment_duration_ms: options.fragment_duration_ms,
+            speed: options.speed,
+            audio: options.audio,
+            lut_path: optional_string(&options.lut_path),
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<ScrubProxyOptions> for media_types::ScrubProxyOptions {
+    fn from(options: ScrubProxyOptions) -> Self {
+        media_types::ScrubProxyOptions {
+            bit_rate: options.bit_rate,
+            width: options.width,
+            fragment_duration_ms: options.fragment_duration_ms,
+            speed: options.speed,
+            audio: options.audio,
+            lut_path: stabby_optional_string(options.lut_path),
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::TimelapseOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelapseOptions {
+    pub speedup: u32,
+    pub blend: bool,
+    pub output: OutputOptions,
+}
+
+impl Default for TimelapseOptions {
+    fn default() -> Self {
+        TimelapseOptions::from(&media_types::TimelapseOptions::default())
+    }
+}
+
+impl From<&media_types::TimelapseOptions> for TimelapseOptions {
+    fn from(options: &media_types::TimelapseOptions) -> Self {
+        TimelapseOptions {
+            speedup: options.speedup,
+            blend: options.blend,
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<TimelapseOptions> for media_types::TimelapseOptions {
+    fn from(options: TimelapseOptions) -> Self {
+        media_types::TimelapseOptions {
+            speedup: options.speedup,
+            blend: options.blend,
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::ChannelExportOptions`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelExportOptions {
+    pub channels: Vec<u32>,
+    pub split: bool,
+    pub speed: f64,
+    pub output: OutputOptions,
+}
+
+impl Default for ChannelExportOptions {
+    fn default() -> Self {
+        ChannelExportOptions::from(&media_types::ChannelExportOptions::default())
+    }
+}
+
+impl From<&media_types::ChannelExportOptions> for ChannelExportOptions {
+    fn from(options: &media_types::ChannelExportOptions) -> Self {
+        ChannelExportOptions {
+            channels: options.channels.iter().copied().collect(),
+            split: options.split,
+            speed: options.speed,
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<ChannelExportOptions> for media_types::ChannelExportOptions {
+    fn from(options: ChannelExportOptions) -> Self {
+        media_types::ChannelExportOptions {
+            channels: stabby_vec(options.channels),
+            split: options.split,
+            speed: options.speed,
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::FrameEncoderOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameEncoderOptions {
+    pub bit_rate: u64,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate_numerator: u32,
+    pub frame_rate_denominator: u32,
+    #[serde(with = "VideoCodecDef")]
+    pub codec: VideoCodec,
+    pub hardware: bool,
+    pub output: OutputOptions,
+}
+
+impl Default for FrameEncoderOptions {
+    fn default() -> Self {
+        FrameEncoderOptions::from(&media_types::FrameEncoderOptions::default())
+    }
+}
+
+impl From<&media_types::FrameEncoderOptions> for FrameEncoderOptions {
+    fn from(options: &media_types::FrameEncoderOptions) -> Self {
+        FrameEncoderOptions {
+            bit_rate: options.bit_rate,
+            width: options.width,
+            height: options.height,
+            frame_rate_numerator: options.frame_rate_numerator,
+            frame_rate_denominator: options.frame_rate_denominator,
+            codec: options.codec,
+            hardware: options.hardware,
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<FrameEncoderOptions> for media_types::FrameEncoderOptions {
+    fn from(options: FrameEncoderOptions) -> Self {
+        media_types::FrameEncoderOptions {
+            bit_rate: options.bit_rate,
+            width: options.width,
+            height: options.height,
+            frame_rate_numerator: options.frame_rate_numerator,
+            frame_rate_denominator: options.frame_rate_denominator,
+            codec: options.codec,
+            hardware: options.hardware,
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::ResampleOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResampleOptions {
+    pub sample_rate: u32,
+    pub channels: u32,
+    #[serde(with = "SampleFormatDef")]
+    pub sample_format: SampleFormat,
+}
+
+impl Default for ResampleOptions {
+    fn default() -> Self {
+        ResampleOptions::from(&media_types::ResampleOptions::default())
+    }
+}
+
+impl From<&media_types::ResampleOptions> for ResampleOptions {
+    fn from(options: &media_types::ResampleOptions) -> Self {
+        ResampleOptions {
+            sample_rate: options.sample_rate,
+            channels: options.channels,
+            sample_format: options.sample_format,
+        }
+    }
+}
+
+impl From<ResampleOptions> for media_types::ResampleOptions {
+    fn from(options: ResampleOptions) -> Self {
+        media_types::ResampleOptions {
+            sample_rate: options.sample_rate,
+            channels: options.channels,
+            sample_format: options.sample_format,
+        }
+    }
+}
+
+/// See `media_types::OcrOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrOptions {
+    #[serde(with = "RectDef")]
+    pub region: Rect,
+    pub interval_ms: u32,
+    pub upscale: u32,
+    pub stretch_contrast: bool,
+}
+
+impl Default for OcrOptions {
+    fn default() -> Self {
+        OcrOptions::from(&media_types::OcrOptions::default())
+    }
+}
+
+impl From<&media_types::OcrOptions> for OcrOptions {
+    fn from(options: &media_types::OcrOptions) -> Self {
+        OcrOptions {
+            region: options.region,
+            interval_ms: options.interval_ms,
+            upscale: options.upscale,
+            stretch_contrast: options.stretch_contrast,
+        }
+    }
+}
+
+impl From<OcrOptions> for media_types::OcrOptions {
+    fn from(options: OcrOptions) -> Self {
+        media_types::OcrOptions {
+            region: options.region,
+            interval_ms: options.interval_ms,
+            upscale: options.upscale,
+            stretch_contrast: options.stretch_contrast,
+        }
+    }
+}
+
+/// See `media_types::OverlayPlacementOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayPlacementOptions {
+    #[serde(with = "SizeDef")]
+    pub overlay: Size,
+    pub margin: u32,
+    pub samples: u32,
+}
+
+impl Default for OverlayPlacementOptions {
+    fn default() -> Self {
+        OverlayPlacementOptions::from(&media_types::OverlayPlacementOptions::default())
+    }
+}
+
+impl From<&media_types::OverlayPlacementOptions> for OverlayPlacementOptions {
+    fn from(options: &media_types::OverlayPlacementOptions) -> Self {
+        OverlayPlacementOptions {
+            overlay: options.overlay,
+            margin: options.margin,
+            samples: options.samples,
+        }
+    }
+}
+
+impl From<OverlayPlacementOptions> for media_types::OverlayPlacementOptions {
+    fn from(options: OverlayPlacementOptions) -> Self {
+        media_types::OverlayPlacementOptions {
+            overlay: options.overlay,
+            margin: options.margin,
+            samples: options.samples,
+        }
+    }
+}
+
+/// See `media_types::RedactionOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionOptions {
+    pub blur_radius: u32,
+    pub padding: u32,
+    pub detect_interval: u32,
+    pub detection_width: u32,
+    #[serde(with = "RedactionMethodDef")]
+    pub method: RedactionMethod,
+}
+
+impl Default for RedactionOptions {
+    fn default() -> Self {
+        RedactionOptions::from(&media_types::RedactionOptions::default())
+    }
+}
+
+impl From<&media_types::RedactionOptions> for RedactionOptions {
+    fn from(options: &media_types::RedactionOptions) -> Self {
+        RedactionOptions {
+            blur_radius: options.blur_radius,
+            padding: options.padding,
+            detect_interval: options.detect_interval,
+            detection_width: options.detection_width,
+            method: options.method,
+        }
+    }
+}
+
+impl From<RedactionOptions> for media_types::RedactionOptions {
+    fn from(options: RedactionOptions) -> Self {
+        media_types::RedactionOptions {
+            blur_radius: options.blur_radius,
+            padding: options.padding,
+            detect_interval: options.detect_interval,
+            detection_width: options.detection_width,
+            method: options.method,
+        }
+    }
+}
+
+/// See `media_types::RestreamOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestreamOptions {
+    pub max_reconnects: u32,
+    pub reconnect_delay_ms: u32,
+    pub realtime: bool,
+}
+
+impl Default for RestreamOptions {
+    fn default() -> Self {
+        RestreamOptions::from(&media_types::RestreamOptions::default())
+    }
+}
+
+impl From<&media_types::RestreamOptions> for RestreamOptions {
+    fn from(options: &media_types::RestreamOptions) -> Self {
+        RestreamOptions {
+            max_reconnects: options.max_reconnects,
+            reconnect_delay_ms: options.reconnect_delay_ms,
+            realtime: options.realtime,
+        }
+    }
+}
+
+impl From<RestreamOptions> for media_types::RestreamOptions {
+    fn from(options: RestreamOptions) -> Self {
+        media_types::RestreamOptions {
+            max_reconnects: options.max_reconnects,
+            reconnect_delay_ms: options.reconnect_delay_ms,
+            realtime: options.realtime,
+        }
+    }
+}
+
+/// See `media_types::SyncOptions`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncOptions {
+    #[serde(with = "SyncClockDef")]
+    pub clock: SyncClock,
+    pub interval_ms: u32,
+    pub offsets_ms: Vec<i32>,
+    pub decoder: MediaFrameDecoderOptions,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions::from(&media_types::SyncOptions::default())
+    }
+}
+
+impl From<&media_types::SyncOptions> for SyncOptions {
+    fn from(options: &media_types::SyncOptions) -> Self {
+        SyncOptions {
+            clock: options.clock,
+            interval_ms: options.interval_ms,
+            offsets_ms: options.offsets_ms.iter().copied().collect(),
+            decoder: MediaFrameDecoderOptions::from(&options.decoder),
+        }
+    }
+}
+
+impl From<SyncOptions> for media_types::SyncOptions {
+    fn from(options: SyncOptions) -> Self {
+        media_types::SyncOptions {
+            clock: options.clock,
+            interval_ms: options.interval_ms,
+            offsets_ms: stabby_vec(options.offsets_ms),
+            decoder: options.decoder.into(),
+        }
+    }
+}
+
+/// See `media_types::TestAssetOptions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestAssetOptions {
+    pub interlaced: bool,
+    #[serde(with = "SizeDef")]
+    pub size: Size,
+    pub duration_ms: u32,
+    pub frame_rate: u32,
+    pub b_frames: u32,
+    pub rotation_degrees: u32,
+    pub tone_hz: u32,
+    pub codec: String,
+    pub output: OutputOptions,
+}
+
+impl Default for TestAssetOptions {
+    fn default() -> Self {
+        TestAssetOptions::from(&media_types::TestAssetOptions::default())
+    }
+}
+
+impl From<&media_types::TestAssetOptions> for TestAssetOptions {
+    fn from(options: &media_types::TestAssetOptions) -> Self {
+        TestAssetOptions {
+            interlaced: options.interlaced,
+            size: options.size,
+            duration_ms: options.duration_ms,
+            frame_rate: options.frame_rate,
+            b_frames: options.b_frames,
+            rotation_degrees: options.rotation_degrees,
+            tone_hz: options.tone_hz,
+            codec: string(&options.codec),
+            output: OutputOptions::from(&options.output),
+        }
+    }
+}
+
+impl From<TestAssetOptions> for media_types::TestAssetOptions {
+    fn from(options: TestAssetOptions) -> Self {
+        media_types::TestAssetOptions {
+            interlaced: options.interlaced,
+            size: options.size,
+            duration_ms: options.duration_ms,
+            frame_rate: options.frame_rate,
+            b_frames: options.b_frames,
+            rotation_degrees: options.rotation_degrees,
+            tone_hz: options.tone_hz,
+            codec: stabby_string(&options.codec),
+            output: options.output.into(),
+        }
+    }
+}
+
+/// See `media_types::HardwareSupportQuery`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareSupportQuery {
+    pub codec: String,
+    pub profile: Option<String>,
+    pub bit_depth: u32,
+}
+
+impl From<&media_types::HardwareSupportQuery> for HardwareSupportQuery {
+    fn from(query: &media_types::HardwareSupportQuery) -> Self {
+        HardwareSupportQuery {
+            codec: string(&query.codec),
+            profile: optional_string(&query.profile),
+            bit_depth: query.bit_depth,
+        }
+    }
+}
+
+impl From<HardwareSupportQuery> for media_types::HardwareSupportQuery {
+    fn from(query: HardwareSupportQuery) -> Self {
+        media_types::HardwareSupportQuery {
+            codec: stabby_string(&query.codec),
+            profile: stabby_optional_string(query.profile),
+            bit_depth: query.bit_depth,
+        }
+    }
+}
+
+/// See `media_types::DecoderUtilization`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecoderUtilization {
+    pub device_index: u32,
+    pub name: String,
+    pub decoder_percent: u32,
+    pub sampling_period_us: u32,
+    pub memory_free_bytes: u64,
+    pub memory_total_bytes: u64,
+}
+
+/// See `media_types::HardwareSupport`, what `MediaClient::hardware_support`
+/// returns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareSupport {
+    pub decoder: String,
+    pub device: Option<String>,
+    pub pixel_format: Option<String>,
+    pub utilization: Vec<DecoderUtilization>,
+}
+
+impl From<&media_types::HardwareSupport> for HardwareSupport {
+    fn from(support: &media_types::HardwareSupport) -> Self {
+        HardwareSupport {
+            decoder: string(&support.decoder),
+            device: optional_string(&support.device),
+            pixel_format: optional_string(&support.pixel_format),
+            utilization: support
+                .utilization
+                .iter()
+                .map(|utilization| DecoderUtilization {
+                    device_index: utilization.device_index,
+                    name: string(&utilization.name),
+                    decoder_percent: utilization.decoder_percent,
+                    sampling_period_us: utilization.sampling_period_us,
+                    memory_free_bytes: utilization.memory_free_bytes,
+                    memory_total_bytes: utilization.memory_total_bytes,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<HardwareSupport> for media_types::HardwareSupport {
+    fn from(support: HardwareSupport) -> Self {
+        media_types::HardwareSupport {
+            decoder: stabby_string(&support.decoder),
+            device: stabby_optional_string(support.device),
+            pixel_format: stabby_optional_string(support.pixel_format),
+            utilization: stabby_vec(support.utilization.iter().map(|utilization| {
+                media_types::DecoderUtilization {
+                    device_index: utilization.device_index,
+                    name: stabby_string(&utilization.name),
+                    decoder_percent: utilization.decoder_percent,
+                    sampling_period_us: utilization.sampling_period_us,
+                    memory_free_bytes: utilization.memory_free_bytes,
+                    memory_total_bytes: utilization.memory_total_bytes,
+                }
+            })),
+        }
+    }
+}
+
+/// See `media_types::StreamHealth`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamHealth {
+    #[serde(with = "StreamHealthStatusDef")]
+    pub status: StreamHealthStatus,
+    pub bitrate_bps: u64,
+    pub jitter_ms: f64,
+    pub since_last_packet_ms: u64,
+    pub gaps: u32,
+    pub reconnects: u32,
+}
+
+impl From<&media_types::StreamHealth> for StreamHealth {
+    fn from(health: &media_types::StreamHealth) -> Self {
+        StreamHealth {
+            status: health.status,
+            bitrate_bps: health.bitrate_bps,
+            jitter_ms: health.jitter_ms,
+            since_last_packet_ms: health.since_last_packet_ms,
+            gaps: health.gaps,
+            reconnects: health.reconnects,
+        }
+    }
+}
+
+impl From<StreamHealth> for media_types::StreamHealth {
+    fn from(health: StreamHealth) -> Self {
+        media_types::StreamHealth {
+            status: health.status,
+            bitrate_bps: health.bitrate_bps,
+            jitter_ms: health.jitter_ms,
+            since_last_packet_ms: health.since_last_packet_ms,
+            gaps: health.gaps,
+            reconnects: health.reconnects,
+        }
+    }
+}
+
+/// See `media_types::DecodeProgress`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodeProgress {
+    pub position_us: Option<i64>,
+    pub duration_us: Option<i64>,
+    pub percent: Option<f64>,
+}
+
+impl From<&media_types::DecodeProgress> for DecodeProgress {
+    fn from(progress: &media_types::DecodeProgress) -> Self {
+        DecodeProgress {
+            position_us: progress.position_us.as_ref().copied(),
+            duration_us: progress.duration_us.as_ref().copied(),
+            percent: progress.percent.as_ref().copied(),
+        }
+    }
+}
+
+impl From<DecodeProgress> for media_types::DecodeProgress {
+    fn from(progress: DecodeProgress) -> Self {
+        media_types::DecodeProgress {
+            position_us: progress.position_us.into(),
+            duration_us: progress.duration_us.into(),
+            percent: progress.percent.into(),
+        }
+    }
+}
+
+/// See `media_types::KeyframeInfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyframeInfo {
+    pub pts_us: i64,
+    pub byte_offset: Option<u64>,
+}
+
+impl From<&media_types::KeyframeInfo> for KeyframeInfo {
+    fn from(keyframe: &media_types::KeyframeInfo) -> Self {
+        KeyframeInfo {
+            pts_us: keyframe.pts_us,
+            byte_offset: keyframe.byte_offset.as_ref().copied(),
+        }
+    }
+}
+
+impl From<KeyframeInfo> for media_types::KeyframeInfo {
+    fn from(keyframe: KeyframeInfo) -> Self {
+        media_types::KeyframeInfo {
+            pts_us: keyframe.pts_us,
+            byte_offset: keyframe.byte_offset.into(),
+        }
+    }
+}
+
+/// See `media_types::ShotSignature`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShotSignature {
+    pub hash: u64,
+    pub start_us: i64,
+    pub duration_us: i64,
+}
+
+/// See `media_types::VideoFingerprint`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoFingerprint {
+    pub shots: Vec<ShotSignature>,
+    pub duration_us: i64,
+}
+
+impl From<&media_types::VideoFingerprint> for VideoFingerprint {
+    fn from(fingerprint: &media_types::VideoFingerprint) -> Self {
+        VideoFingerprint {
+            shots: fingerprint
+                .shots
+                .iter()
+                .map(|shot| ShotSignature {
+                    hash: shot.hash,
+                    start_us: shot.start_us,
+                    duration_us: shot.duration_us,
+                })
+                .collect(),
+            duration_us: fingerprint.duration_us,
+        }
+    }
+}
+
+impl From<VideoFingerprint> for media_types::VideoFingerprint {
+    fn from(fingerprint: VideoFingerprint) -> Self {
+        media_types::VideoFingerprint {
+            shots: stabby_vec(
+                fingerprint
+                    .shots
+                    .iter()
+                    .map(|shot| media_types::ShotSignature {
+                        hash: shot.hash,
+                        start_us: shot.start_us,
+                        duration_us: shot.duration_us,
+                    }),
+            ),
+            duration_us: fingerprint.duration_us,
+        }
+    }
+}
+
+/// See `media_types::AudioFingerprint`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioFingerprint {
+    pub fingerprint: String,
+    pub duration_secs: f64,
+}
+
+impl From<&media_types::AudioFingerprint> for AudioFingerprint {
+    fn from(fingerprint: &media_types::AudioFingerprint) -> Self {
+        AudioFingerprint {
+            fingerprint: string(&fingerprint.fingerprint),
+            duration_secs: fingerprint.duration_secs,
+        }
+    }
+}
+
+impl From<AudioFingerprint> for media_types::AudioFingerprint {
+    fn from(fingerprint: AudioFingerprint) -> Self {
+        media_types::AudioFingerprint {
+            fingerprint: stabby_string(&fingerprint.fingerprint),
+            duration_secs: fingerprint.duration_secs,
+        }
+    }
+}
+
+/// See `media_types::OnScreenText`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnScreenText {
+    pub start_us: i64,
+    pub end_us: i64,
+    pub text: String,
+}
+
+impl From<&media_types::OnScreenText> for OnScreenText {
+    fn from(text: &media_types::OnScreenText) -> Self {
+        OnScreenText {
+            start_us: text.start_us,
+            end_us: text.end_us,
+            text: string(&text.text),
+        }
+    }
+}
+
+impl From<OnScreenText> for media_types::OnScreenText {
+    fn from(text: OnScreenText) -> Self {
+        media_types::OnScreenText {
+            start_us: text.start_us,
+            end_us: text.end_us,
+            text: stabby_string(&text.text),
+        }
+    }
+}
+
+/// See `media_types::SubtitleCue`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtitleCue {
+    pub start_us: i64,
+    pub end_us: i64,
+    pub text: String,
+}
+
+impl From<&media_types::SubtitleCue> for SubtitleCue {
+    fn from(cue: &media_types::SubtitleCue) -> Self {
+        SubtitleCue {
+            start_us: cue.start_us,
+            end_us: cue.end_us,
+            text: string(&cue.text),
+        }
+    }
+}
+
+impl From<SubtitleCue> for media_types::SubtitleCue {
+    fn from(cue: SubtitleCue) -> Self {
+        media_types::SubtitleCue {
+            start_us: cue.start_us,
+            end_us: cue.end_us,
+            text: stabby_string(&cue.text),
+        }
+    }
+}
+
+/// See `media_types::OverlayPlacement`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverlayPlacement {
+    #[serde(with = "RectDef")]
+    pub region: Rect,
+    pub activity: f32,
+    #[serde(with = "CornerDef")]
+    pub corner: Corner,
+}
+
+impl From<&media_types::OverlayPlacement> for OverlayPlacement {
+    fn from(placement: &media_types::OverlayPlacement) -> Self {
+        OverlayPlacement {
+            region: placement.region,
+            activity: placement.activity,
+            corner: placement.corner,
+        }
+    }
+}
+
+impl From<OverlayPlacement> for media_types::OverlayPlacement {
+    fn from(placement: OverlayPlacement) -> Self {
+        media_types::OverlayPlacement {
+            region: placement.region,
+            activity: placement.activity,
+            corner: placement.corner,
+        }
+    }
+}
+
+/// See `media_types::DetectedSegment`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedSegment {
+    pub start_us: i64,
+    pub end_us: i64,
+    pub confidence: f32,
+    #[serde(with = "SegmentKindDef")]
+    pub kind: SegmentKind,
+}
+
+impl From<&media_types::DetectedSegment> for DetectedSegment {
+    fn from(segment: &media_types::DetectedSegment) -> Self {
+        DetectedSegment {
+            start_us: segment.start_us,
+            end_us: segment.end_us,
+            confidence: segment.confidence,
+            kind: segment.kind,
+        }
+    }
+}
+
+impl From<DetectedSegment> for media_types::DetectedSegment {
+    fn from(segment: DetectedSegment) -> Self {
+        media_types::DetectedSegment {
+            start_us: segment.start_us,
+            end_us: segment.end_us,
+            confidence: segment.confidence,
+            kind: segment.kind,
+        }
+    }
+}
+
+/// See `media_types::ContentType`, what `MediaClient::sniff` returns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentType {
+    pub mime_type: String,
+    pub format_name: String,
+    pub confidence: u32,
+}
+
+impl From<&media_types::ContentType> for ContentType {
+    fn from(content_type: &media_types::ContentType) -> Self {
+        ContentType {
+            mime_type: string(&content_type.mime_type),
+            format_name: string(&content_type.format_name),
+            confidence: content_type.confidence,
+        }
+    }
+}
+
+impl From<ContentType> for media_types::ContentType {
+    fn from(content_type: ContentType) -> Self {
+        media_types::ContentType {
+            mime_type: stabby_string(&content_type.mime_type),
+            format_name: stabby_string(&content_type.format_name),
+            confidence: content_type.confidence,
+        }
+    }
+}
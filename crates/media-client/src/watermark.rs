@@ -0,0 +1,35 @@
+use media_types::{Backpressure, MediaKeyFrameIteratorBox, MediaKeyFrameIteratorDynMut};
+
+/// Calls back when a keyframe iterator's backpressure changes, for hosts
+/// that would rather be told than poll `get_backpressure` themselves. Call
+/// `poll` between frames.
+pub struct WatermarkCallbacks<H: FnMut(), L: FnMut()> {
+    on_high: H,
+    on_low: L,
+    state: Backpressure,
+}
+
+impl<H: FnMut(), L: FnMut()> WatermarkCallbacks<H, L> {
+    /// `on_high` runs when the consumer falls past the high watermark and
+    /// upstream should pause, `on_low` once it's back within the low one.
+    pub fn new(on_high: H, on_low: L) -> Self {
+        WatermarkCallbacks {
+            on_high,
+            on_low,
+            state: Backpressure::Flowing,
+        }
+    }
+
+    /// Runs the callback for any change since the last poll and returns the
+    /// current state.
+    pub fn poll(&mut self, iterator: &mut MediaKeyFrameIteratorBox) -> Backpressure {
+        let state = iterator.get_backpressure();
+        match (self.state, state) {
+            (Backpressure::Flowing, Backpressure::Paused) => (self.on_high)(),
+            (Backpressure::Paused, Backpressure::Flowing) => (self.on_low)(),
+            _ => {}
+        }
+        self.state = state;
+        state
+    }
+}
@@ -1,17 +1,99 @@
+mod logging;
 pub mod media;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 use ffmpeg_next as ffmpeg;
-use media::KeyframeIterator;
-use media_types::{MediaKeyFrameGet, MediaKeyFrameIterator, MediaLibError, MediaLibInit};
+use media::{
+    AudioDecoder, FrameSession, GpuFrame, GpuFrameIterator, HardwareAcceleratedVideoEncoder, Job,
+    KeyframeIterator, PacketIterator, ReverseFrameIterator, SyncedDecoder, ThreadedDecoder,
+};
+use media_types::{
+    AnimationFormat, AudioFingerprint, AudioSamples, AudioSamplesGet, Backpressure, CancelHandle,
+    CancelHandleBox, ChannelExportOptions, ChecksumAlgorithm, ContentLightLevel, ContentType,
+    DatasetExportOptions, DecodeProgress, DecoderUtilization, DecryptionOptions, DetectedSegment,
+    EncodedImage, EncodedStoryboard, FrameBundleGet, FrameEncoderOptions, FrameExportOptions,
+    GpuFrameIteratorBox, HardwareSupport, HardwareSupportQuery, HwSurface, ImageFormat,
+    ImageSequenceOptions, InitOptions, JobContext, JpegOptions, KeyframeInfo, LogLevel,
+    MasteringDisplay, MediaAudioIterator, MediaFrameDecoderOptions, MediaFrameEncoder,
+    MediaFrameSession, MediaInfo, MediaKeyFrameGet, MediaKeyFrameIterator, MediaLibError,
+    MediaLibInit, MediaPacketGet, MediaPacketIterator, MediaSyncIterator, MediaSyncIteratorBox,
+    MediaThreadedDecoder, MediaThreadedDecoderBox, OcrOptions, OnScreenText, OverlayPlacement,
+    OverlayPlacementOptions, PreviewBundle, PreviewBundleOptions, PreviewOptions, ProcessingReport,
+    RawVideoFrame, RedactionOptions, RegionDetectorBox, ResampleOptions, RestreamOptions,
+    ScrubProxyOptions, Size, SphericalMetadata, StoryboardOptions, StreamHealth,
+    StreamHealthObserverBox, StreamInfo, SubtitleCue, SyncOptions, TestAssetOptions,
+    TextRecognizerBox, TimelapseOptions, VideoFingerprint, VideoFrame, VideoFrameBox,
+    VideoFrameGet,
+};
 
 #[stabby::stabby]
 #[stabby::export]
 pub fn init_media_lib() -> stabby::result::Result<MediaLibInit, MediaLibError> {
-    let init = ffmpeg::init();
-    match init {
-        Ok(_) => Ok(MediaLibInit {}).into(),
-        Err(e) => Err(MediaLibError::FFmpegError(e.to_string().into())).into(),
+    init_media_lib_with_options(InitOptions::default())
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn init_media_lib_with_options(
+    options: InitOptions,
+) -> stabby::result::Result<MediaLibInit, MediaLibError> {
+    if let Err(e) = ffmpeg::init() {
+        return Err(MediaLibError::FFmpegError(e.to_string().into())).into();
+    }
+
+    ffmpeg::util::log::set_level(logging::ffmpeg_log_level(options.log_level));
+
+    if options.network {
+        ffmpeg::format::network::init();
+    }
+
+    if options.max_alloc_bytes > 0 {
+        unsafe { ffmpeg::ffi::av_max_alloc(options.max_alloc_bytes as usize) };
+    }
+
+    let temp_dir = options.temp_dir.as_ref().map(|dir| Path::new(&**dir));
+    if let Err(e) = media::start_temp_session(temp_dir) {
+        return Err(e).into();
+    }
+
+    // A device that fails to open here will just fall back to software
+    // decoding later, so this is only worth a warning.
+    for device in options.hardware_preheat.iter() {
+        if let Err(e) = media::preheat_hardware_device(device) {
+            log::warn!("Failed to preheat hardware device {}: {}", &**device, e);
+        }
+    }
+
+    Ok(MediaLibInit {}).into()
+}
+
+/// Lets go of the temporary file session `init_media_lib_with_options`
+/// started, see `InitOptions::temp_dir`.
+#[stabby::stabby]
+#[stabby::export]
+pub fn release_media_lib() {
+    media::end_temp_session();
+}
+
+// Couldn't figure out how to convert a slice to a stabby vec so we're doing it manually
+fn to_stabby_vec<T: Clone>(items: &[T]) -> stabby::vec::Vec<T> {
+    let mut stabby_vec = stabby::vec::Vec::with_capacity(items.len());
+    for item in items {
+        stabby_vec.push(item.clone());
+    }
+    stabby_vec
+}
+
+/// Sets a decoder's interrupt flag, which its job goes by too, see
+/// `MediaInput::interrupt_flag` and `Job::interrupt_with`.
+struct InterruptHandle(Arc<AtomicBool>);
+
+impl CancelHandle for InterruptHandle {
+    extern "C" fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
     }
 }
 
@@ -26,14 +108,7 @@ impl MediaKeyFrameIterator for MediaKeyFrameIteratorWrapper {
         match frame_option {
             Some(frame) => match frame {
                 Ok(frame) => {
-                    // Couldn't figure out how to convert the frame to a stabby vec so we're doing it manually
-                    let frame_bytes = frame.to_vec();
-                    let mut stabby_vec = stabby::vec::Vec::with_capacity(frame_bytes.len());
-                    for (_, byte) in frame_bytes.iter().enumerate() {
-                        stabby_vec.push(*byte);
-                    }
-
-                    stabby::option::Option::Some(stabby::result::Result::Ok(stabby_vec))
+                    stabby::option::Option::Some(stabby::result::Result::Ok(to_stabby_vec(&frame)))
                 }
                 Err(e) => stabby::option::Option::Some(stabby::result::Result::Err(e)),
             },
@@ -42,11 +117,55 @@ impl MediaKeyFrameIterator for MediaKeyFrameIteratorWrapper {
     }
 
     extern "C" fn get_width(&mut self) -> u32 {
-        self.iterator.target_width
+        self.iterator.target_size.width
     }
 
     extern "C" fn get_height(&mut self) -> u32 {
-        self.iterator.target_height
+        self.iterator.target_size.height
+    }
+
+    extern "C" fn get_size(&mut self) -> Size {
+        self.iterator.target_size
+    }
+
+    extern "C" fn get_dropped_frames(&mut self) -> u64 {
+        self.iterator.dropped_frames()
+    }
+
+    extern "C" fn get_report(&mut self) -> ProcessingReport {
+        self.iterator.report()
+    }
+
+    extern "C" fn seek(&mut self, timestamp_us: i64) -> stabby::result::Result<(), MediaLibError> {
+        self.iterator.seek(timestamp_us).into()
+    }
+
+    extern "C" fn get_backpressure(&mut self) -> Backpressure {
+        self.iterator.backpressure()
+    }
+
+    extern "C" fn get_pipeline_description(&mut self) -> stabby::string::String {
+        self.iterator.pipeline_description().into()
+    }
+
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox {
+        stabby::boxed::Box::new(InterruptHandle(self.iterator.interrupt_flag())).into()
+    }
+
+    extern "C" fn get_progress(&mut self) -> DecodeProgress {
+        self.iterator.progress()
+    }
+
+    extern "C" fn get_stream_health(&mut self) -> StreamHealth {
+        self.iterator.stream_health()
+    }
+
+    extern "C" fn set_health_observer(&mut self, observer: StreamHealthObserverBox) {
+        self.iterator.set_health_observer(observer);
+    }
+
+    extern "C" fn set_job(&mut self, job: JobContext) {
+        self.iterator.set_job(job);
     }
 }
 
@@ -57,18 +176,1219 @@ pub fn get_key_frames(
 ) -> stabby::result::Result<
     stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>),
     MediaLibError,
+> {
+    get_key_frames_with_options(path_str, MediaFrameDecoderOptions::default())
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_key_frames_with_options(
+    path_str: stabby::string::String,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>),
+    MediaLibError,
 > {
     let path_str = path_str.to_string();
     let path = Path::new(&path_str);
-    let iterator = KeyframeIterator::new(path).unwrap();
+    let iterator = match KeyframeIterator::new(path, &options) {
+        Ok(iterator) => iterator,
+        Err(e) if e.is_no_video_stream() && options.waveform_fallback => {
+            return match audio_preview(path) {
+                Ok(wrapper) => Ok(stabby::boxed::Box::new(wrapper).into()).into(),
+                Err(preview_error) => {
+                    // No usable audio either, the original error says more.
+                    log::warn!("Failed to render an audio preview: {}", preview_error);
+                    Err(e).into()
+                }
+            };
+        }
+        Err(e) => return Err(e).into(),
+    };
     let wrapper = MediaKeyFrameIteratorWrapper { iterator };
     Ok(stabby::boxed::Box::new(wrapper).into()).into()
 }
 
+/// Lets a buffer handed over the ABI be read through a `Cursor`.
+struct StabbyBytes(stabby::vec::Vec<u8>);
+
+impl AsRef<[u8]> for StabbyBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Like `get_key_frames_with_options` for a whole input held in memory, e.g.
+/// fetched from object storage, without writing it to a file first.
+/// `waveform_fallback` doesn't apply.
 #[stabby::stabby]
 #[stabby::export]
-pub fn init_logging() {
-    let log_level = ffmpeg::util::log::Level::Info;
-    ffmpeg::util::log::set_level(log_level);
-    pretty_env_logger::init();
+pub fn get_key_frames_from_memory(
+    data: stabby::vec::Vec<u8>,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>),
+    MediaLibError,
+> {
+    let reader = Box::new(std::io::Cursor::new(StabbyBytes(data)));
+    match KeyframeIterator::from_reader(reader, &options) {
+        Ok(iterator) => {
+            let wrapper = MediaKeyFrameIteratorWrapper { iterator };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct ReverseFrameIteratorWrapper {
+    iterator: ReverseFrameIterator,
+}
+
+impl MediaKeyFrameIterator for ReverseFrameIteratorWrapper {
+    extern "C" fn get_keyframe(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        to_stabby_frame(self.iterator.get())
+    }
+
+    extern "C" fn get_width(&mut self) -> u32 {
+        self.iterator.target_size.width
+    }
+
+    extern "C" fn get_height(&mut self) -> u32 {
+        self.iterator.target_size.height
+    }
+
+    extern "C" fn get_size(&mut self) -> Size {
+        self.iterator.target_size
+    }
+
+    extern "C" fn get_dropped_frames(&mut self) -> u64 {
+        self.iterator.dropped_frames()
+    }
+
+    extern "C" fn get_report(&mut self) -> ProcessingReport {
+        self.iterator.report()
+    }
+
+    extern "C" fn seek(&mut self, _timestamp_us: i64) -> stabby::result::Result<(), MediaLibError> {
+        Err(MediaLibError::UnknownError(
+            "Reversed frames are fixed to their range and can't seek".into(),
+        ))
+        .into()
+    }
+
+    // Realtime decoding doesn't apply going backwards.
+    extern "C" fn get_backpressure(&mut self) -> Backpressure {
+        Backpressure::Flowing
+    }
+
+    extern "C" fn get_pipeline_description(&mut self) -> stabby::string::String {
+        self.iterator.pipeline_description().into()
+    }
+
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox {
+        stabby::boxed::Box::new(InterruptHandle(self.iterator.interrupt_flag())).into()
+    }
+
+    extern "C" fn get_progress(&mut self) -> DecodeProgress {
+        self.iterator.progress()
+    }
+
+    extern "C" fn get_stream_health(&mut self) -> StreamHealth {
+        self.iterator.stream_health()
+    }
+
+    extern "C" fn set_health_observer(&mut self, observer: StreamHealthObserverBox) {
+        self.iterator.set_health_observer(observer);
+    }
+
+    extern "C" fn set_job(&mut self, job: JobContext) {
+        self.iterator.set_job(job);
+    }
+}
+
+/// Every frame between `start_us` and `end_us` (from the start of the
+/// input) as JPEGs, in reverse presentation order. Realtime decoding and
+/// sampling options don't apply here and are ignored.
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_frames_reversed(
+    path_str: stabby::string::String,
+    start_us: i64,
+    end_us: i64,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>),
+    MediaLibError,
+> {
+    let path_str = path_str.to_string();
+    match ReverseFrameIterator::new(Path::new(&path_str), start_us, end_us, &options) {
+        Ok(iterator) => {
+            let wrapper = ReverseFrameIteratorWrapper { iterator };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+fn to_stabby_frame(
+    frame: Option<Result<Vec<u8>, MediaLibError>>,
+) -> stabby::option::Option<MediaKeyFrameGet> {
+    match frame {
+        Some(Ok(frame)) => {
+            stabby::option::Option::Some(stabby::result::Result::Ok(to_stabby_vec(&frame)))
+        }
+        Some(Err(e)) => stabby::option::Option::Some(stabby::result::Result::Err(e)),
+        None => stabby::option::Option::None(),
+    }
+}
+
+pub struct FrameSessionWrapper {
+    session: FrameSession,
+}
+
+impl MediaFrameSession for FrameSessionWrapper {
+    extern "C" fn next_frame(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        to_stabby_frame(self.session.next_frame())
+    }
+
+    extern "C" fn previous_frame(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        to_stabby_frame(self.session.previous_frame())
+    }
+
+    extern "C" fn step_to(&mut self, frame_index: u64) -> stabby::option::Option<MediaKeyFrameGet> {
+        to_stabby_frame(self.session.step_to(frame_index))
+    }
+
+    extern "C" fn get_frame_index(&mut self) -> stabby::option::Option<u64> {
+        self.session.frame_index().into()
+    }
+
+    extern "C" fn get_frame_count(&mut self) -> u64 {
+        self.session.frame_count()
+    }
+
+    extern "C" fn get_size(&mut self) -> Size {
+        self.session.target_size
+    }
+
+    extern "C" fn get_plane_checksum(
+        &mut self,
+        plane: u32,
+        algorithm: ChecksumAlgorithm,
+    ) -> stabby::option::Option<u32> {
+        self.session.plane_checksum(plane, algorithm).into()
+    }
+
+    extern "C" fn set_job(&mut self, job: JobContext) {
+        self.session.set_job(job);
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn open_frame_session(
+    path_str: stabby::string::String,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<stabby::dynptr!(stabby::boxed::Box<dyn MediaFrameSession>), MediaLibError>
+{
+    let path_str = path_str.to_string();
+    match FrameSession::new(Path::new(&path_str), &options) {
+        Ok(session) => {
+            let wrapper = FrameSessionWrapper { session };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct GpuFrameWrapper {
+    frame: GpuFrame,
+}
+
+impl VideoFrame for GpuFrameWrapper {
+    extern "C" fn get_hw_surface(&mut self) -> stabby::option::Option<HwSurface> {
+        self.frame.surface().into()
+    }
+
+    extern "C" fn get_timestamp_us(&mut self) -> i64 {
+        self.frame.timestamp_us
+    }
+
+    extern "C" fn plane_count(&mut self) -> u32 {
+        self.frame
+            .system_frame()
+            .map_or(0, |frame| frame.planes() as u32)
+    }
+
+    extern "C" fn plane_width(&mut self, index: u32) -> u32 {
+        self.frame
+            .plane(index)
+            .map_or(0, |(frame, index)| frame.plane_width(index))
+    }
+
+    extern "C" fn plane_height(&mut self, index: u32) -> u32 {
+        self.frame
+            .plane(index)
+            .map_or(0, |(frame, index)| frame.plane_height(index))
+    }
+
+    extern "C" fn stride(&mut self, index: u32) -> u32 {
+        self.frame
+            .plane(index)
+            .map_or(0, |(frame, index)| frame.stride(index) as u32)
+    }
+
+    extern "C" fn data(&mut self, index: u32) -> stabby::vec::Vec<u8> {
+        match self.frame.plane(index) {
+            Some((frame, index)) => to_stabby_vec(frame.data(index)),
+            None => stabby::vec::Vec::new(),
+        }
+    }
+
+    extern "C" fn get_color_space(&mut self) -> stabby::string::String {
+        self.frame.color_space().as_str().into()
+    }
+
+    extern "C" fn get_color_primaries(&mut self) -> stabby::string::String {
+        self.frame.color_primaries().as_str().into()
+    }
+
+    extern "C" fn get_color_transfer(&mut self) -> stabby::string::String {
+        self.frame.color_transfer().as_str().into()
+    }
+
+    extern "C" fn get_color_range(&mut self) -> stabby::string::String {
+        self.frame.color_range().as_str().into()
+    }
+
+    extern "C" fn get_mastering_display(&mut self) -> stabby::option::Option<MasteringDisplay> {
+        self.frame.mastering_display().into()
+    }
+
+    extern "C" fn get_content_light_level(&mut self) -> stabby::option::Option<ContentLightLevel> {
+        self.frame.content_light_level().into()
+    }
+
+    extern "C" fn get_interlaced_frame(&mut self) -> bool {
+        self.frame.is_interlaced()
+    }
+
+    extern "C" fn get_top_field_first(&mut self) -> bool {
+        self.frame.is_top_first()
+    }
+
+    extern "C" fn get_spherical(&mut self) -> stabby::option::Option<SphericalMetadata> {
+        self.frame.spherical.clone().into()
+    }
+}
+
+pub struct GpuFrameIteratorWrapper {
+    iterator: GpuFrameIterator,
+}
+
+impl media_types::GpuFrameIterator for GpuFrameIteratorWrapper {
+    extern "C" fn get_frame(&mut self) -> stabby::option::Option<VideoFrameGet> {
+        match self.iterator.get() {
+            Some(Ok(frame)) => {
+                let frame: VideoFrameBox =
+                    stabby::boxed::Box::new(GpuFrameWrapper { frame }).into();
+                stabby::option::Option::Some(stabby::result::Result::Ok(frame))
+            }
+            Some(Err(e)) => stabby::option::Option::Some(stabby::result::Result::Err(e)),
+            None => stabby::option::Option::None(),
+        }
+    }
+
+    extern "C" fn get_report(&mut self) -> ProcessingReport {
+        self.iterator.report()
+    }
+}
+
+/// Every frame of the input left on the GPU it was decoded on, for handing
+/// to GPU consumers without a round trip through system memory. Fails when
+/// the input isn't decoded on a GPU.
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_gpu_frames(
+    path_str: stabby::string::String,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<GpuFrameIteratorBox, MediaLibError> {
+    let path_str = path_str.to_string();
+    match GpuFrameIterator::new(Path::new(&path_str), &options) {
+        Ok(iterator) => {
+            let wrapper = GpuFrameIteratorWrapper { iterator };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+/// Every frame of the input where it was decoded, on the GPU like
+/// `get_gpu_frames` when it's decoded on one and in system memory
+/// otherwise. Colour tags and HDR metadata are filled in either way.
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_video_frames(
+    path_str: stabby::string::String,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<GpuFrameIteratorBox, MediaLibError> {
+    let path_str = path_str.to_string();
+    match GpuFrameIterator::anywhere(Path::new(&path_str), &options) {
+        Ok(iterator) => {
+            let wrapper = GpuFrameIteratorWrapper { iterator };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct FrameEncoderWrapper {
+    encoder: HardwareAcceleratedVideoEncoder,
+}
+
+impl MediaFrameEncoder for FrameEncoderWrapper {
+    extern "C" fn encode_frame(
+        &mut self,
+        frame: RawVideoFrame,
+    ) -> stabby::result::Result<(), MediaLibError> {
+        self.encoder.encode(&frame).into()
+    }
+
+    extern "C" fn get_encoder_name(&mut self) -> stabby::string::String {
+        self.encoder.encoder_name().into()
+    }
+
+    extern "C" fn finish(&mut self) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+        self.encoder.finish().into()
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn new_frame_encoder(
+    output_str: stabby::string::String,
+    options: FrameEncoderOptions,
+) -> stabby::result::Result<stabby::dynptr!(stabby::boxed::Box<dyn MediaFrameEncoder>), MediaLibError>
+{
+    let output_str = output_str.to_string();
+    match HardwareAcceleratedVideoEncoder::new(Path::new(&output_str), &options) {
+        Ok(encoder) => {
+            let wrapper = FrameEncoderWrapper { encoder };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+/// Stands in for the keyframe iterator on inputs without video, returning a
+/// single preview image of the audio.
+pub struct AudioPreviewWrapper {
+    preview: Option<Vec<u8>>,
+    size: Size,
+    wall_time_ms: u64,
+    // Set by the cancel handle, the preview isn't handed out once it is.
+    cancelled: Arc<AtomicBool>,
+    // Set by `set_job`, checked before the preview is handed out.
+    job: Option<Job>,
+}
+
+fn audio_preview(path: &Path) -> Result<AudioPreviewWrapper, MediaLibError> {
+    let started = Instant::now();
+    let options = PreviewOptions::default();
+    let (preview, size) = media::preview_image(path, &options)?;
+    Ok(AudioPreviewWrapper {
+        preview: Some(preview),
+        size,
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        cancelled: Arc::new(AtomicBool::new(false)),
+        job: None,
+    })
+}
+
+impl MediaKeyFrameIterator for AudioPreviewWrapper {
+    extern "C" fn get_keyframe(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        if self.preview.is_some() && self.cancelled.load(Ordering::Relaxed) {
+            self.preview = None;
+            return stabby::option::Option::Some(stabby::result::Result::Err(
+                MediaLibError::Cancelled("Cancelled".into()),
+            ));
+        }
+        if let Some(Err(e)) = self.job.as_mut().map(Job::check) {
+            self.preview = None;
+            return stabby::option::Option::Some(stabby::result::Result::Err(e));
+        }
+        match self.preview.take() {
+            Some(preview) => {
+                stabby::option::Option::Some(stabby::result::Result::Ok(to_stabby_vec(&preview)))
+            }
+            None => stabby::option::Option::None(),
+        }
+    }
+
+    extern "C" fn get_width(&mut self) -> u32 {
+        self.size.width
+    }
+
+    extern "C" fn get_height(&mut self) -> u32 {
+        self.size.height
+    }
+
+    extern "C" fn get_size(&mut self) -> Size {
+        self.size
+    }
+
+    extern "C" fn get_dropped_frames(&mut self) -> u64 {
+        0
+    }
+
+    extern "C" fn get_report(&mut self) -> ProcessingReport {
+        ProcessingReport {
+            wall_time_ms: self.wall_time_ms,
+            frames_decoded: 0,
+            frames_returned: if self.preview.is_none() { 1 } else { 0 },
+            frames_dropped: 0,
+            hardware_device: stabby::option::Option::None(),
+            warnings: stabby::vec::Vec::new(),
+            outputs: stabby::vec::Vec::new(),
+        }
+    }
+
+    // The preview stands for the whole input, there's nowhere to seek to.
+    extern "C" fn seek(&mut self, _timestamp_us: i64) -> stabby::result::Result<(), MediaLibError> {
+        Ok(()).into()
+    }
+
+    extern "C" fn get_backpressure(&mut self) -> Backpressure {
+        Backpressure::Flowing
+    }
+
+    extern "C" fn get_pipeline_description(&mut self) -> stabby::string::String {
+        format!(
+            "output: {}x{} preview image of the audio",
+            self.size.width, self.size.height
+        )
+        .into()
+    }
+    // The preview is rendered before the iterator is handed out, cancelling
+    // keeps it from being handed out.
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox {
+        stabby::boxed::Box::new(InterruptHandle(self.cancelled.clone())).into()
+    }
+
+    // The one image is all there is, it's done once that's been taken.
+    extern "C" fn get_progress(&mut self) -> DecodeProgress {
+        DecodeProgress {
+            position_us: stabby::option::Option::None(),
+            duration_us: stabby::option::Option::None(),
+            percent: Some(if self.preview.is_none() { 100.0 } else { 0.0 }).into(),
+        }
+    }
+
+    // Nothing is streamed, there's no health to watch.
+    extern "C" fn get_stream_health(&mut self) -> StreamHealth {
+        StreamHealth::default()
+    }
+
+    extern "C" fn set_health_observer(&mut self, _observer: StreamHealthObserverBox) {}
+
+    // Same as the cancel handle, only handing out the preview is left.
+    extern "C" fn set_job(&mut self, job: JobContext) {
+        self.job = Some(Job::detached(job).interrupt_with(self.cancelled.clone()));
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn list_keyframes(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<stabby::vec::Vec<KeyframeInfo>, MediaLibError> {
+    let path_str = path_str.to_string();
+    let path = Path::new(&path_str);
+    media::list_keyframes(path)
+        .map(|keyframes| to_stabby_vec(&keyframes))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn detect_intro_credits(
+    path_str: stabby::string::String,
+    other_episodes: stabby::vec::Vec<stabby::string::String>,
+) -> stabby::result::Result<stabby::vec::Vec<DetectedSegment>, MediaLibError> {
+    let path_str = path_str.to_string();
+    let other_episodes: Vec<String> = other_episodes.iter().map(|path| path.to_string()).collect();
+    let other_paths: Vec<&Path> = other_episodes.iter().map(Path::new).collect();
+    media::detect_segments(Path::new(&path_str), &other_paths)
+        .map(|segments| to_stabby_vec(&segments))
+        .into()
+}
+
+/// Reads the text on screen at intervals with the host's OCR engine.
+#[stabby::stabby]
+#[stabby::export]
+pub fn extract_on_screen_text(
+    path_str: stabby::string::String,
+    options: OcrOptions,
+    mut recognizer: TextRecognizerBox,
+    job: JobContext,
+) -> stabby::result::Result<stabby::vec::Vec<OnScreenText>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::extract_on_screen_text(
+        Path::new(&path_str),
+        &options,
+        &mut recognizer,
+        &mut Job::new(job),
+    )
+    .map(|texts| to_stabby_vec(&texts))
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn suggest_overlay_placement(
+    path_str: stabby::string::String,
+    options: OverlayPlacementOptions,
+) -> stabby::result::Result<stabby::vec::Vec<OverlayPlacement>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::suggest_overlay_placement(Path::new(&path_str), &options)
+        .map(|placements| to_stabby_vec(&placements))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn detect_leader(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<stabby::vec::Vec<DetectedSegment>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::detect_leader(Path::new(&path_str))
+        .map(|segments| to_stabby_vec(&segments))
+        .into()
+}
+
+pub struct PacketIteratorWrapper {
+    iterator: PacketIterator,
+}
+
+impl MediaPacketIterator for PacketIteratorWrapper {
+    extern "C" fn get_packet(&mut self) -> stabby::option::Option<MediaPacketGet> {
+        match self.iterator.get() {
+            Some(packet) => stabby::option::Option::Some(packet.into()),
+            None => stabby::option::Option::None(),
+        }
+    }
+
+    extern "C" fn get_stream_count(&mut self) -> u32 {
+        self.iterator.stream_count()
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_packets(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaPacketIterator>),
+    MediaLibError,
+> {
+    let path_str = path_str.to_string();
+    match PacketIterator::new(Path::new(&path_str), None) {
+        Ok(iterator) => {
+            let wrapper = PacketIteratorWrapper { iterator };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct AudioDecoderWrapper {
+    decoder: AudioDecoder,
+}
+
+impl MediaAudioIterator for AudioDecoderWrapper {
+    extern "C" fn get_samples(&mut self) -> stabby::option::Option<AudioSamplesGet> {
+        let frame = match self.decoder.next_frame() {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return stabby::option::Option::Some(stabby::result::Result::Err(e)),
+            None => return stabby::option::Option::None(),
+        };
+        let format = self.decoder.format();
+        // Planes are padded past the samples.
+        let length = frame.samples() * format.channels as usize * format.sample_format.bytes();
+        let samples = AudioSamples {
+            data: to_stabby_vec(&frame.data(0)[..length]),
+            frames: frame.samples() as u32,
+            timestamp_us: self.decoder.timestamp_us().into(),
+        };
+        stabby::option::Option::Some(stabby::result::Result::Ok(samples))
+    }
+
+    extern "C" fn get_format(&mut self) -> ResampleOptions {
+        self.decoder.format()
+    }
+}
+
+/// The best audio stream of the input decoded to `format`, e.g. 16 kHz
+/// mono `F32` for a speech model.
+#[stabby::stabby]
+#[stabby::export]
+pub fn decode_audio(
+    path_str: stabby::string::String,
+    format: ResampleOptions,
+) -> stabby::result::Result<
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaAudioIterator>),
+    MediaLibError,
+> {
+    let path_str = path_str.to_string();
+    match AudioDecoder::with_format(Path::new(&path_str), &format) {
+        Ok(decoder) => {
+            let wrapper = AudioDecoderWrapper { decoder };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct SyncedDecoderWrapper {
+    decoder: SyncedDecoder,
+}
+
+impl MediaSyncIterator for SyncedDecoderWrapper {
+    extern "C" fn get_bundle(&mut self) -> stabby::option::Option<FrameBundleGet> {
+        match self.decoder.get() {
+            Some(bundle) => stabby::option::Option::Some(bundle.into()),
+            None => stabby::option::Option::None(),
+        }
+    }
+
+    extern "C" fn get_offsets_us(&mut self) -> stabby::vec::Vec<i64> {
+        to_stabby_vec(&self.decoder.offsets_us())
+    }
+}
+
+/// Frames of every one of `input_paths` at the same moments, lined up by
+/// `options.clock`.
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_synced_frames(
+    input_paths: stabby::vec::Vec<stabby::string::String>,
+    options: SyncOptions,
+) -> stabby::result::Result<MediaSyncIteratorBox, MediaLibError> {
+    let input_paths: Vec<String> = input_paths.iter().map(|path| path.to_string()).collect();
+    let input_paths: Vec<&Path> = input_paths.iter().map(Path::new).collect();
+    match SyncedDecoder::new(&input_paths, &options) {
+        Ok(decoder) => {
+            let wrapper = SyncedDecoderWrapper { decoder };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+pub struct ThreadedDecoderWrapper {
+    decoder: ThreadedDecoder,
+}
+
+impl ThreadedDecoderWrapper {
+    fn to_stabby(
+        frame: Option<Result<Vec<u8>, MediaLibError>>,
+    ) -> stabby::option::Option<MediaKeyFrameGet> {
+        match frame {
+            Some(Ok(frame)) => {
+                stabby::option::Option::Some(stabby::result::Result::Ok(to_stabby_vec(&frame)))
+            }
+            Some(Err(e)) => stabby::option::Option::Some(stabby::result::Result::Err(e)),
+            None => stabby::option::Option::None(),
+        }
+    }
+}
+
+impl MediaThreadedDecoder for ThreadedDecoderWrapper {
+    extern "C" fn get_frame(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        Self::to_stabby(self.decoder.get())
+    }
+
+    extern "C" fn try_get_frame(&mut self) -> stabby::option::Option<MediaKeyFrameGet> {
+        Self::to_stabby(self.decoder.try_get())
+    }
+
+    extern "C" fn is_finished(&mut self) -> bool {
+        self.decoder.is_finished()
+    }
+
+    extern "C" fn get_size(&mut self) -> Size {
+        self.decoder.size()
+    }
+
+    extern "C" fn get_report(&mut self) -> ProcessingReport {
+        self.decoder.report()
+    }
+
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox {
+        stabby::boxed::Box::new(InterruptHandle(self.decoder.interrupt_flag())).into()
+    }
+}
+
+/// Like `get_key_frames_with_options`, with the frames decoded on a worker
+/// thread ahead of the caller. `waveform_fallback` doesn't apply.
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_threaded_frames(
+    path_str: stabby::string::String,
+    options: MediaFrameDecoderOptions,
+) -> stabby::result::Result<MediaThreadedDecoderBox, MediaLibError> {
+    let path_str = path_str.to_string();
+    match ThreadedDecoder::new(Path::new(&path_str), &options) {
+        Ok(decoder) => {
+            let wrapper = ThreadedDecoderWrapper { decoder };
+            Ok(stabby::boxed::Box::new(wrapper).into()).into()
+        }
+        Err(e) => Err(e).into(),
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn fingerprint_audio(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<AudioFingerprint, MediaLibError> {
+    #[cfg(feature = "chromaprint")]
+    {
+        let path_str = path_str.to_string();
+        media::fingerprint_audio(Path::new(&path_str)).into()
+    }
+
+    #[cfg(not(feature = "chromaprint"))]
+    {
+        let _ = path_str;
+        stabby::result::Result::Err(MediaLibError::UnknownError(
+            "media-lib was built without the chromaprint feature".into(),
+        ))
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn fingerprint_video(
+    path_str: stabby::string::String,
+    job: JobContext,
+) -> stabby::result::Result<VideoFingerprint, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::fingerprint_video(Path::new(&path_str), &mut Job::new(job)).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn get_preview_image(
+    path_str: stabby::string::String,
+    options: PreviewOptions,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::preview_image(Path::new(&path_str), &options)
+        .map(|(preview, _)| to_stabby_vec(&preview))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn filmstrip(
+    path_str: stabby::string::String,
+    count: u32,
+    cell_width: u32,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::filmstrip(Path::new(&path_str), count, cell_width)
+        .map(|strip| to_stabby_vec(&strip))
+        .into()
+}
+
+/// A JPEG sprite sheet of a frame every `options.interval_ms` with the
+/// WebVTT cues players need for seekbar previews.
+#[stabby::stabby]
+#[stabby::export]
+pub fn generate_storyboard(
+    path_str: stabby::string::String,
+    options: StoryboardOptions,
+) -> stabby::result::Result<EncodedStoryboard, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::storyboard(Path::new(&path_str), &options)
+        .map(|(sprite, size, vtt)| EncodedStoryboard {
+            sprite: EncodedImage {
+                data: to_stabby_vec(&sprite),
+                size,
+                format: ImageFormat::Jpeg,
+            },
+            vtt: vtt.as_str().into(),
+        })
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn generate_preview_bundle(
+    path_str: stabby::string::String,
+    options: PreviewBundleOptions,
+) -> stabby::result::Result<PreviewBundle, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::preview_bundle(Path::new(&path_str), &options).into()
+}
+
+/// A looping GIF or WebP of `duration_ms` of the input from `start_ms`, at
+/// `fps` frames a second and fitted inside `size`, for hover previews.
+#[stabby::stabby]
+#[stabby::export]
+pub fn generate_preview_animation(
+    path_str: stabby::string::String,
+    start_ms: u64,
+    duration_ms: u64,
+    fps: u32,
+    size: Size,
+    format: AnimationFormat,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::preview_animation(
+        Path::new(&path_str),
+        start_ms,
+        duration_ms,
+        fps,
+        size,
+        format,
+    )
+    .map(|animation| to_stabby_vec(&animation))
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn export_frame(
+    path_str: stabby::string::String,
+    timestamp_us: i64,
+    options: FrameExportOptions,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::export_frame(Path::new(&path_str), timestamp_us, &options)
+        .map(|image| to_stabby_vec(&image))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn export_redacted_frame(
+    path_str: stabby::string::String,
+    timestamp_us: i64,
+    options: FrameExportOptions,
+    redaction: RedactionOptions,
+    mut detector: RegionDetectorBox,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::export_redacted_frame(
+        Path::new(&path_str),
+        timestamp_us,
+        &options,
+        &redaction,
+        &mut detector,
+    )
+    .map(|image| to_stabby_vec(&image))
+    .into()
+}
+
+/// A JPEG thumbnail of the frame shown at `timestamp_ms`, fitted inside
+/// `target_size`.
+#[stabby::stabby]
+#[stabby::export]
+pub fn extract_thumbnail(
+    path_str: stabby::string::String,
+    timestamp_ms: u64,
+    target_size: Size,
+) -> stabby::result::Result<EncodedImage, MediaLibError> {
+    extract_thumbnail_with_options(path_str, timestamp_ms, target_size, JpegOptions::default())
+}
+
+/// Like `extract_thumbnail`, encoded as `jpeg` asks.
+#[stabby::stabby]
+#[stabby::export]
+pub fn extract_thumbnail_with_options(
+    path_str: stabby::string::String,
+    timestamp_ms: u64,
+    target_size: Size,
+    jpeg: JpegOptions,
+) -> stabby::result::Result<EncodedImage, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::extract_thumbnail(Path::new(&path_str), timestamp_ms, target_size, &jpeg)
+        .map(|(data, size)| EncodedImage {
+            data: to_stabby_vec(&data),
+            size,
+            format: ImageFormat::Jpeg,
+        })
+        .into()
+}
+
+/// JPEG encodes a raw frame from the host at `quality` (1 to 100), with
+/// nvJPEG when it's available.
+#[stabby::stabby]
+#[stabby::export]
+pub fn encode_frame_jpeg(
+    frame: RawVideoFrame,
+    quality: u32,
+) -> stabby::result::Result<stabby::vec::Vec<u8>, MediaLibError> {
+    media::encode_raw_frame(&frame, quality)
+        .map(|jpeg| to_stabby_vec(&jpeg))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn timelapse(
+    input_path_str: stabby::string::String,
+    output_path_str: stabby::string::String,
+    options: TimelapseOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    let output_path_str = output_path_str.to_string();
+    media::timelapse(
+        Path::new(&input_path_str),
+        Path::new(&output_path_str),
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn generate_test_asset(
+    output_path_str: stabby::string::String,
+    options: TestAssetOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let output_path_str = output_path_str.to_string();
+    media::generate_test_asset(Path::new(&output_path_str), &options, &mut Job::new(job)).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn export_image_sequence(
+    input_path_str: stabby::string::String,
+    output_dir_str: stabby::string::String,
+    options: ImageSequenceOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    let output_dir_str = output_dir_str.to_string();
+    media::export_image_sequence(
+        Path::new(&input_path_str),
+        Path::new(&output_dir_str),
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn export_dataset(
+    input_paths: stabby::vec::Vec<stabby::string::String>,
+    output_dir_str: stabby::string::String,
+    options: DatasetExportOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_paths: Vec<String> = input_paths.iter().map(|path| path.to_string()).collect();
+    let input_paths: Vec<&Path> = input_paths.iter().map(Path::new).collect();
+    let output_dir_str = output_dir_str.to_string();
+    media::export_dataset(
+        &input_paths,
+        Path::new(&output_dir_str),
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+/// Whether video like `query` describes would be decoded on the GPU on
+/// this machine, without needing a file.
+#[stabby::stabby]
+#[stabby::export]
+pub fn hardware_support(
+    query: HardwareSupportQuery,
+) -> stabby::result::Result<HardwareSupport, MediaLibError> {
+    media::hardware_support(&query).into()
+}
+
+/// How busy the video decoder of each NVIDIA GPU here is, see
+/// `DecoderUtilization`.
+#[stabby::stabby]
+#[stabby::export]
+pub fn decoder_utilization(
+) -> stabby::result::Result<stabby::vec::Vec<DecoderUtilization>, MediaLibError> {
+    #[cfg(feature = "nvml")]
+    {
+        media::decoder_utilization()
+            .map(|devices| to_stabby_vec(&devices))
+            .into()
+    }
+
+    #[cfg(not(feature = "nvml"))]
+    {
+        stabby::result::Result::Err(MediaLibError::UnknownError(
+            "media-lib was built without the nvml feature".into(),
+        ))
+    }
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn scrub_proxy(
+    input_path_str: stabby::string::String,
+    playlist_path_str: stabby::string::String,
+    options: ScrubProxyOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    let playlist_path_str = playlist_path_str.to_string();
+    media::scrub_proxy(
+        Path::new(&input_path_str),
+        Path::new(&playlist_path_str),
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn redacted_scrub_proxy(
+    input_path_str: stabby::string::String,
+    playlist_path_str: stabby::string::String,
+    options: ScrubProxyOptions,
+    redaction: RedactionOptions,
+    mut detector: RegionDetectorBox,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    let playlist_path_str = playlist_path_str.to_string();
+    media::redacted_scrub_proxy(
+        Path::new(&input_path_str),
+        Path::new(&playlist_path_str),
+        &options,
+        &redaction,
+        &mut detector,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn export_audio_channels(
+    input_path_str: stabby::string::String,
+    output_path_str: stabby::string::String,
+    options: ChannelExportOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    let output_path_str = output_path_str.to_string();
+    media::export_audio_channels(
+        Path::new(&input_path_str),
+        Path::new(&output_path_str),
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn restream(
+    input_path_str: stabby::string::String,
+    url: stabby::string::String,
+    options: RestreamOptions,
+    job: JobContext,
+) -> stabby::result::Result<ProcessingReport, MediaLibError> {
+    let input_path_str = input_path_str.to_string();
+    media::restream(
+        Path::new(&input_path_str),
+        &url,
+        &options,
+        &mut Job::new(job),
+    )
+    .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn probe_media(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<MediaInfo, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::probe_media(Path::new(&path_str)).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn probe_media_with_decryption(
+    path_str: stabby::string::String,
+    decryption: DecryptionOptions,
+) -> stabby::result::Result<MediaInfo, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::probe_media_with_decryption(Path::new(&path_str), &decryption).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn list_video_streams(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<stabby::vec::Vec<StreamInfo>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::list_video_streams(Path::new(&path_str))
+        .map(|streams| to_stabby_vec(&streams))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn extract_subtitles(
+    path_str: stabby::string::String,
+    stream_index: u32,
+) -> stabby::result::Result<stabby::vec::Vec<SubtitleCue>, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::extract_subtitles(Path::new(&path_str), stream_index)
+        .map(|cues| to_stabby_vec(&cues))
+        .into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn sniff(
+    path_str: stabby::string::String,
+) -> stabby::result::Result<ContentType, MediaLibError> {
+    let path_str = path_str.to_string();
+    media::sniff_file(Path::new(&path_str)).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn sniff_bytes(
+    data: stabby::vec::Vec<u8>,
+) -> stabby::result::Result<ContentType, MediaLibError> {
+    media::sniff_bytes(&data, None).into()
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn init_logging() {
+    // FFmpeg's own level is set by init_media_lib_with_options.
+    logging::init();
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn set_log_level(level: LogLevel) {
+    logging::set_level(level);
+}
+
+#[stabby::stabby]
+#[stabby::export]
+pub fn set_log_filter(module: stabby::string::String, level: LogLevel) {
+    logging::set_filter(&module, level);
 }
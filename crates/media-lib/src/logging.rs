@@ -0,0 +1,274 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use ffmpeg_next as ffmpeg;
+use log::{LevelFilter, Log, Metadata, Record};
+use media_types::LogLevel;
+use pretty_env_logger::env_logger::{self, filter};
+
+// FFmpeg logs through av_log rather than the log crate, a filter for this
+// module name sets FFmpeg's own level instead.
+const FFMPEG_MODULE: &str = "ffmpeg";
+// Lines kept for `tail`.
+const TAIL_LINES: usize = 200;
+
+/// Levels set at runtime, layered on top of whatever RUST_LOG asked for.
+struct LogSettings {
+    level: Option<LevelFilter>,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+static SETTINGS: Mutex<LogSettings> = Mutex::new(LogSettings {
+    level: None,
+    modules: BTreeMap::new(),
+});
+
+static FILTER: RwLock<Option<filter::Filter>> = RwLock::new(None);
+
+static TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+// Off until something wants the tail, so lines aren't formatted and kept
+// for nothing.
+static KEEP_TAIL: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Tag of the job running on this thread, see `set_tag`.
+    static TAG: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn ffmpeg_log_level(level: LogLevel) -> ffmpeg::util::log::Level {
+    match level {
+        LogLevel::Off => ffmpeg::util::log::Level::Quiet,
+        LogLevel::Error => ffmpeg::util::log::Level::Error,
+        LogLevel::Warn => ffmpeg::util::log::Level::Warning,
+        LogLevel::Info => ffmpeg::util::log::Level::Info,
+        LogLevel::Debug => ffmpeg::util::log::Level::Debug,
+        LogLevel::Trace => ffmpeg::util::log::Level::Trace,
+    }
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+/// The filter for `settings` on top of `rust_log`, a RUST_LOG style spec.
+fn build_filter(settings: &LogSettings, rust_log: Option<&str>) -> filter::Filter {
+    // Same default as env_logger when RUST_LOG isn't set.
+    let mut builder = filter::Builder::new();
+    builder.filter_level(LevelFilter::Error);
+    if let Some(spec) = rust_log {
+        builder.parse(spec);
+    }
+    if let Some(level) = settings.level {
+        builder.filter_level(level);
+    }
+    for (module, level) in &settings.modules {
+        builder.filter_module(module, *level);
+    }
+    builder.build()
+}
+
+fn apply(settings: &LogSettings) {
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let filter = build_filter(settings, rust_log.as_deref());
+    log::set_max_level(filter.filter());
+    if let Ok(mut current) = FILTER.write() {
+        *current = Some(filter);
+    }
+}
+
+/// Formats like pretty_env_logger but filters against levels that can be
+/// changed after the logger is installed.
+struct RuntimeFilteredLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for RuntimeFilteredLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        FILTER
+            .read()
+            .is_ok_and(|filter| filter.as_ref().is_some_and(|f| f.enabled(metadata)))
+    }
+
+    fn log(&self, record: &Record) {
+        let matches = FILTER
+            .read()
+            .is_ok_and(|filter| filter.as_ref().is_some_and(|f| f.matches(record)));
+        if !matches {
+            return;
+        }
+        if KEEP_TAIL.load(Ordering::Relaxed) {
+            if let Ok(mut lines) = TAIL.lock() {
+                if lines.len() == TAIL_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(format!(
+                    "{} {} {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        }
+        TAG.with_borrow(|tag| match tag {
+            Some(tag) => self.inner.log(
+                &Record::builder()
+                    .args(format_args!("[{}] {}", tag, record.args()))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.inner.log(record),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger. Calling this again, e.g. from a second client in the
+/// same process, leaves the existing logger in place.
+pub fn init() {
+    let inner = pretty_env_logger::formatted_builder()
+        .filter_level(LevelFilter::Trace)
+        .build();
+    if log::set_boxed_logger(Box::new(RuntimeFilteredLogger { inner })).is_err() {
+        return;
+    }
+    if let Ok(settings) = SETTINGS.lock() {
+        apply(&settings);
+    }
+}
+
+/// Sets the level for FFmpeg and every media-lib module without an override
+/// of its own.
+pub fn set_level(level: LogLevel) {
+    ffmpeg::util::log::set_level(ffmpeg_log_level(level));
+    if let Ok(mut settings) = SETTINGS.lock() {
+        settings.level = Some(level_filter(level));
+        apply(&settings);
+    }
+}
+
+/// Sets the level for a single module path, e.g. "media_lib::media", or for
+/// FFmpeg itself with "ffmpeg".
+pub fn set_filter(module: &str, level: LogLevel) {
+    if module == FFMPEG_MODULE {
+        ffmpeg::util::log::set_level(ffmpeg_log_level(level));
+        return;
+    }
+    if let Ok(mut settings) = SETTINGS.lock() {
+        settings
+            .modules
+            .insert(module.to_string(), level_filter(level));
+        apply(&settings);
+    }
+}
+
+/// Prefixes this thread's log lines with `tag` until it's set again,
+/// returning the tag it replaces.
+pub fn set_tag(tag: Option<String>) -> Option<String> {
+    TAG.with_borrow_mut(|current| std::mem::replace(current, tag))
+}
+
+/// Keeps the lines logged from now on for `tail`.
+pub fn keep_tail() {
+    KEEP_TAIL.store(true, Ordering::Relaxed);
+}
+
+/// The last lines logged since `keep_tail`, oldest first, for diagnostics
+/// written when something fails.
+pub fn tail() -> Vec<String> {
+    TAIL.lock()
+        .map(|lines| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn settings(level: Option<LevelFilter>, modules: &[(&str, LevelFilter)]) -> LogSettings {
+        LogSettings {
+            level,
+            modules: modules
+                .iter()
+                .map(|(module, level)| (module.to_string(), *level))
+                .collect(),
+        }
+    }
+
+    fn enabled(filter: &filter::Filter, target: &str, level: Level) -> bool {
+        filter.enabled(&Metadata::builder().target(target).level(level).build())
+    }
+
+    #[test]
+    fn it_defaults_to_errors_only() {
+        let filter = build_filter(&settings(None, &[]), None);
+        assert!(enabled(&filter, "media_lib", Level::Error));
+        assert!(!enabled(&filter, "media_lib", Level::Warn));
+    }
+
+    #[test]
+    fn it_matches_modules_by_prefix() {
+        let filter = build_filter(
+            &settings(
+                Some(LevelFilter::Warn),
+                &[("media_lib::media", LevelFilter::Debug)],
+            ),
+            None,
+        );
+        assert!(enabled(&filter, "media_lib::media", Level::Debug));
+        assert!(enabled(&filter, "media_lib::media::preview", Level::Debug));
+        assert!(!enabled(&filter, "media_lib::media::preview", Level::Trace));
+        assert!(!enabled(&filter, "media_lib::logging", Level::Debug));
+        assert!(enabled(&filter, "media_lib::logging", Level::Warn));
+    }
+
+    #[test]
+    fn it_prefers_the_longest_matching_module() {
+        let filter = build_filter(
+            &settings(
+                None,
+                &[
+                    ("media_lib", LevelFilter::Error),
+                    ("media_lib::media", LevelFilter::Trace),
+                ],
+            ),
+            None,
+        );
+        assert!(enabled(&filter, "media_lib::media::input", Level::Trace));
+        assert!(!enabled(&filter, "media_lib::logging", Level::Warn));
+    }
+
+    #[test]
+    fn it_layers_runtime_levels_over_rust_log() {
+        let filter = build_filter(
+            &settings(Some(LevelFilter::Warn), &[]),
+            Some("debug,media_lib::media=trace"),
+        );
+        // The runtime level replaces RUST_LOG's default level...
+        assert!(!enabled(&filter, "media_lib::logging", Level::Info));
+        assert!(enabled(&filter, "media_lib::logging", Level::Warn));
+        // ...but module levels from RUST_LOG still apply.
+        assert!(enabled(&filter, "media_lib::media::input", Level::Trace));
+
+        let filter = build_filter(
+            &settings(None, &[("media_lib::media", LevelFilter::Off)]),
+            Some("media_lib::media=trace"),
+        );
+        assert!(!enabled(&filter, "media_lib::media::input", Level::Error));
+    }
+}
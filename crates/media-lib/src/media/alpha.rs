@@ -0,0 +1,113 @@
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{AlphaBackground, Color, MediaLibError};
+
+use super::ffmpeg_error;
+
+// Size of the checkerboard squares in pixels, at the source resolution.
+const CHECKER_SIZE: usize = 16;
+const CHECKER_LIGHT: Color = Color {
+    r: 0xff,
+    g: 0xff,
+    b: 0xff,
+};
+const CHECKER_DARK: Color = Color {
+    r: 0xcc,
+    g: 0xcc,
+    b: 0xcc,
+};
+
+pub fn has_alpha(format: Pixel) -> bool {
+    format.descriptor().is_some_and(|descriptor| unsafe {
+        (*descriptor.as_ptr()).flags & ffmpeg_next::ffi::AV_PIX_FMT_FLAG_ALPHA as u64 != 0
+    })
+}
+
+fn background_at(x: usize, y: usize, background: AlphaBackground, color: Color) -> Color {
+    match background {
+        AlphaBackground::Checkerboard if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 => {
+            CHECKER_LIGHT
+        }
+        AlphaBackground::Checkerboard => CHECKER_DARK,
+        _ => color,
+    }
+}
+
+/// Composites frames with transparency over a background as RGB24, keeping
+/// the scaler that unpacks them to RGBA between frames.
+pub struct AlphaFlattener {
+    background: AlphaBackground,
+    color: Color,
+    scaler: Option<Context>,
+}
+
+impl AlphaFlattener {
+    pub fn new(background: AlphaBackground, color: Color) -> Self {
+        AlphaFlattener {
+            background,
+            color,
+            scaler: None,
+        }
+    }
+
+    /// Returns none when there's nothing to do, either because the frame is
+    /// opaque or the alpha channel is to be ignored.
+    pub fn apply(&mut self, frame: &Video) -> Result<Option<Video>, MediaLibError> {
+        if self.background == AlphaBackground::Ignore || !has_alpha(frame.format()) {
+            return Ok(None);
+        }
+
+        let (width, height) = (frame.width(), frame.height());
+        // Inputs can change format or size midway.
+        if self.scaler.as_ref().is_some_and(|scaler| {
+            let input = scaler.input();
+            (input.format, input.width, input.height) != (frame.format(), width, height)
+        }) {
+            self.scaler = None;
+        }
+        let scaler = match &mut self.scaler {
+            Some(scaler) => scaler,
+            None => self.scaler.insert(
+                Context::get(
+                    frame.format(),
+                    width,
+                    height,
+                    Pixel::RGBA,
+                    width,
+                    height,
+                    Flags::POINT,
+                )
+                .map_err(ffmpeg_error)?,
+            ),
+        };
+        let mut rgba = Video::empty();
+        scaler.run(frame, &mut rgba).map_err(ffmpeg_error)?;
+
+        let mut flattened = Video::new(Pixel::RGB24, width, height);
+        let rgba_stride = rgba.stride(0);
+        let rgb_stride = flattened.stride(0);
+        let source = rgba.data(0);
+        let destination = flattened.data_mut(0);
+        for y in 0..height as usize {
+            let source_row = &source[y * rgba_stride..][..width as usize * 4];
+            let destination_row = &mut destination[y * rgb_stride..][..width as usize * 3];
+            for (x, (pixel, out)) in source_row
+                .chunks_exact(4)
+                .zip(destination_row.chunks_exact_mut(3))
+                .enumerate()
+            {
+                let under = background_at(x, y, self.background, self.color);
+                let alpha = pixel[3] as u32;
+                let blend = |over: u8, under: u8| {
+                    ((over as u32 * alpha + under as u32 * (255 - alpha) + 127) / 255) as u8
+                };
+                out[0] = blend(pixel[0], under.r);
+                out[1] = blend(pixel[1], under.g);
+                out[2] = blend(pixel[2], under.b);
+            }
+        }
+
+        Ok(Some(flattened))
+    }
+}
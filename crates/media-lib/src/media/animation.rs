@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::{Dictionary, Rational};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use media_types::{AnimationFormat, MediaFrameDecoderOptions, MediaLibError, Size};
+
+use super::canvas::FrameScaler;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::io_error;
+use super::temp::TempPath;
+use super::video_writer::{VideoWriter, WriterSettings};
+
+// GIF delays are in hundredths of a second and browsers slow down anything
+// under 2, so faster animations just play wrong.
+const MAX_FPS: u32 = 50;
+// Previews are a few seconds, the whole animation is returned in memory.
+const MAX_DURATION_MS: u64 = 60_000;
+
+fn image_error(e: image::ImageError) -> MediaLibError {
+    MediaLibError::UnknownError(e.to_string().into())
+}
+
+/// Where the animation's frames go as they're picked. Both write each frame
+/// out to a temporary file as it comes, only the finished animation is
+/// read back.
+enum AnimationEncoder {
+    Gif {
+        encoder: GifEncoder<File>,
+        delay: Delay,
+        output: TempPath,
+    },
+    WebP {
+        writer: VideoWriter,
+        // The writer needs a file to mux into.
+        output: TempPath,
+    },
+}
+
+impl AnimationEncoder {
+    fn new(format: AnimationFormat, size: Size, fps: u32) -> Result<Self, MediaLibError> {
+        match format {
+            AnimationFormat::Gif => {
+                let output = TempPath::new("gif")?;
+                let mut encoder = GifEncoder::new(File::create(output.path()).map_err(io_error)?);
+                encoder.set_repeat(Repeat::Infinite).map_err(image_error)?;
+                Ok(AnimationEncoder::Gif {
+                    encoder,
+                    delay: Delay::from_numer_denom_ms(1000, fps),
+                    output,
+                })
+            }
+            AnimationFormat::WebP => {
+                let output = TempPath::new("webp")?;
+                let mut muxer_options = Dictionary::new();
+                // The muxer plays once by default, zero loops forever.
+                muxer_options.set("loop", "0");
+                let writer = VideoWriter::with_settings(
+                    output.path(),
+                    size,
+                    Rational::new(fps as i32, 1),
+                    WriterSettings {
+                        format: Some("webp"),
+                        muxer_options,
+                        ..WriterSettings::default()
+                    },
+                )?;
+                Ok(AnimationEncoder::WebP { writer, output })
+            }
+        }
+    }
+
+    /// What frames are scaled to before they're added.
+    fn pixel_format(&self) -> Pixel {
+        match self {
+            AnimationEncoder::Gif { .. } => Pixel::RGBA,
+            AnimationEncoder::WebP { writer, .. } => writer.format(),
+        }
+    }
+
+    /// Adds `frame`, already scaled to the animation's size and format.
+    fn add(&mut self, frame: &mut Video) -> Result<(), MediaLibError> {
+        match self {
+            AnimationEncoder::Gif { encoder, delay, .. } => {
+                let (width, height) = (frame.width(), frame.height());
+                let row_bytes = width as usize * 4;
+                let pixels = frame
+                    .data(0)
+                    .chunks(frame.stride(0))
+                    .take(height as usize)
+                    .flat_map(|row| &row[..row_bytes])
+                    .copied()
+                    .collect();
+                let image = RgbaImage::from_raw(width, height, pixels).ok_or_else(|| {
+                    MediaLibError::UnknownError("Scaled frame has the wrong size".into())
+                })?;
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, *delay))
+                    .map_err(image_error)
+            }
+            AnimationEncoder::WebP { writer, .. } => writer.write(frame),
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, MediaLibError> {
+        match self {
+            AnimationEncoder::Gif {
+                encoder, output, ..
+            } => {
+                // The trailer is written when the encoder is dropped.
+                drop(encoder);
+                let gif = std::fs::read(output.path()).map_err(io_error)?;
+                if gif.last() != Some(&0x3B) {
+                    return Err(MediaLibError::UnknownError("GIF was cut short".into()));
+                }
+                Ok(gif)
+            }
+            AnimationEncoder::WebP { writer, output } => {
+                writer.finish()?;
+                std::fs::read(output.path()).map_err(io_error)
+            }
+        }
+    }
+}
+
+/// The frames of an animation picked as an input is decoded, see
+/// `preview_animation`.
+pub struct PreviewAnimation {
+    encoder: AnimationEncoder,
+    scaler: FrameScaler,
+    start_us: i64,
+    fps: u32,
+    frame_count: u64,
+    index: u64,
+}
+
+impl PreviewAnimation {
+    pub fn new(
+        input_size: Size,
+        start_ms: u64,
+        duration_ms: u64,
+        fps: u32,
+        size: Size,
+        format: AnimationFormat,
+    ) -> Result<Self, MediaLibError> {
+        if duration_ms == 0
+            || duration_ms > MAX_DURATION_MS
+            || fps == 0
+            || fps > MAX_FPS
+            || size.is_empty()
+        {
+            return Err(MediaLibError::UnknownError(
+                format!(
+                    "An animation needs a duration of up to {}ms, a frame rate between 1 and {} \
+                     and a size",
+                    MAX_DURATION_MS, MAX_FPS
+                )
+                .into(),
+            ));
+        }
+
+        // Even, so GIFs are the same size as the 4:2:0 WebPs.
+        let frame_size = input_size.scale_to_fit(size);
+        let frame_size = Size::new(
+            (frame_size.width & !1).max(2),
+            (frame_size.height & !1).max(2),
+        );
+        let encoder = AnimationEncoder::new(format, frame_size, fps)?;
+        Ok(PreviewAnimation {
+            scaler: FrameScaler::new(encoder.pixel_format(), 1, frame_size, frame_size),
+            encoder,
+            start_us: start_ms as i64 * 1000,
+            fps,
+            frame_count: (duration_ms * fps as u64).div_ceil(1000),
+            index: 0,
+        })
+    }
+
+    /// Takes `frame`, shown `elapsed_us` into the input, for every
+    /// animation frame from then until the next.
+    pub fn add(&mut self, frame: &Video, elapsed_us: i64) -> Result<(), MediaLibError> {
+        let (start_us, fps) = (self.start_us, self.fps as u64);
+        let target_us = |index: u64| start_us + (index * 1_000_000 / fps) as i64;
+        if self.index == self.frame_count || elapsed_us < target_us(self.index) {
+            return Ok(());
+        }
+        let mut scaled = self.scaler.run(frame)?;
+        while self.index < self.frame_count && elapsed_us >= target_us(self.index) {
+            self.encoder.add(&mut scaled)?;
+            self.index += 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.index == self.frame_count
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, MediaLibError> {
+        if self.index == 0 {
+            return Err(MediaLibError::UnknownError(
+                format!("Input has no frames after {}ms", self.start_us / 1000).into(),
+            ));
+        }
+        self.encoder.finish()
+    }
+}
+
+/// A looping animation of `duration_ms`, up to a minute, of the input from
+/// `start_ms`, at `fps` frames a second and fitted inside `size`, for hover
+/// previews. Each animation frame is the first decoded frame at or after
+/// its time, so inputs with a lower frame rate repeat frames rather than
+/// skip them.
+pub fn preview_animation(
+    input_path: &Path,
+    start_ms: u64,
+    duration_ms: u64,
+    fps: u32,
+    size: Size,
+    format: AnimationFormat,
+) -> Result<Vec<u8>, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+    let mut animation = PreviewAnimation::new(
+        video_decoder.size(),
+        start_ms,
+        duration_ms,
+        fps,
+        size,
+        format,
+    )?;
+    let start_us = start_ms as i64 * 1000;
+    if start_us > 0 {
+        video_decoder.seek(start_us)?;
+    }
+
+    let start_time_us = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        let decoded = decoded?;
+        let Some(time_us) = video_decoder.frame_time_us(&decoded) else {
+            continue;
+        };
+        // The frame an exact seek lands on can start a little before
+        // `start_ms`, it's still the one shown then.
+        animation.add(&decoded, (time_us - start_time_us).max(start_us))?;
+        if animation.is_full() {
+            break;
+        }
+    }
+    animation.finish()
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use ffmpeg_next::codec;
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::util::frame::audio::Audio;
+use ffmpeg_next::Rational;
+use media_types::{MediaLibError, ResampleOptions, SampleFormat};
+
+use super::ffmpeg_error;
+use super::input::{open_input, MediaInput};
+use super::resampler::Resampler;
+use super::timestamp;
+
+/// Decodes the best audio stream of an input to mono 16 bit samples at a
+/// fixed sample rate, or another format set by `with_format`, whatever the
+/// source format.
+pub struct AudioDecoder {
+    ictx: MediaInput,
+    decoder: ffmpeg_next::decoder::Audio,
+    audio_stream_index: usize,
+    time_base: Rational,
+    resampler: Resampler,
+    format: ResampleOptions,
+    // Of the frame `next_frame` returned last, see `timestamp_us`.
+    timestamp_us: Option<i64>,
+    eof_sent: bool,
+    flushed: bool,
+}
+
+fn mono(sample_rate: u32) -> ResampleOptions {
+    ResampleOptions {
+        sample_rate,
+        channels: 1,
+        sample_format: SampleFormat::S16,
+    }
+}
+
+impl AudioDecoder {
+    pub fn new(input_path: &Path, sample_rate: u32) -> Result<Self, MediaLibError> {
+        Self::with_format(input_path, &mono(sample_rate))
+    }
+
+    /// Like `new`, but none for an input without an audio stream, for
+    /// callers that can do without. Other failures are still errors.
+    pub fn open(input_path: &Path, sample_rate: u32) -> Result<Option<Self>, MediaLibError> {
+        Self::open_with_format(input_path, &mono(sample_rate))
+    }
+
+    /// Decodes to `format` rather than mono 16 bit, see `next_frame`.
+    pub fn with_format(input_path: &Path, format: &ResampleOptions) -> Result<Self, MediaLibError> {
+        Self::open_with_format(input_path, format)?
+            .ok_or_else(|| MediaLibError::FFmpegError("No audio stream found".into()))
+    }
+
+    fn open_with_format(
+        input_path: &Path,
+        format: &ResampleOptions,
+    ) -> Result<Option<Self>, MediaLibError> {
+        let ictx = open_input(input_path, None)?;
+        let (audio_stream_index, time_base, parameters) = {
+            let Some(stream) = ictx.streams().best(ffmpeg_next::media::Type::Audio) else {
+                return Ok(None);
+            };
+            (stream.index(), stream.time_base(), stream.parameters())
+        };
+
+        let decoder = codec::context::Context::from_parameters(parameters)
+            .and_then(|context| context.decoder().audio())
+            .map_err(ffmpeg_error)?;
+
+        Ok(Some(AudioDecoder {
+            ictx,
+            decoder,
+            audio_stream_index,
+            time_base,
+            resampler: Resampler::new(format)?,
+            format: *format,
+            timestamp_us: None,
+            eof_sent: false,
+            flushed: false,
+        }))
+    }
+
+    /// The format samples are decoded to.
+    pub fn format(&self) -> ResampleOptions {
+        self.format
+    }
+
+    /// When the frame `next_frame` returned last starts, from the start of
+    /// the input. Resampling delays it by a few samples at most. None when
+    /// the stream doesn't say, and for the samples the resampler held back.
+    pub fn timestamp_us(&self) -> Option<i64> {
+        self.timestamp_us
+    }
+
+    /// Container duration in microseconds, if known.
+    pub fn duration_us(&self) -> Option<i64> {
+        let duration = self.ictx.duration();
+        (duration > 0).then_some(duration)
+    }
+
+    fn send_next_packet(&mut self) -> Result<(), MediaLibError> {
+        loop {
+            let next_packet = self
+                .ictx
+                .packets()
+                .next()
+                .map(|(stream, packet)| (stream.index(), packet));
+            match next_packet {
+                Some((stream_index, packet)) if stream_index == self.audio_stream_index => {
+                    match self.decoder.send_packet(&packet) {
+                        Ok(()) => return Ok(()),
+                        // A damaged packet shouldn't cost us the rest of the
+                        // stream.
+                        Err(ffmpeg_next::Error::InvalidData) => {
+                            log::warn!("Skipping undecodable audio packet");
+                        }
+                        Err(e) => return Err(ffmpeg_error(e)),
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.decoder.send_eof().map_err(ffmpeg_error)?;
+                    self.eof_sent = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// The samples the resampler held back, once the decoder is done.
+    fn flush(&mut self) -> Option<Result<Audio, MediaLibError>> {
+        if std::mem::replace(&mut self.flushed, true) {
+            return None;
+        }
+        self.timestamp_us = None;
+        self.resampler.flush().transpose()
+    }
+
+    /// The samples of the next decoded frame, none once the stream is done.
+    pub fn next_samples(&mut self) -> Option<Result<Vec<i16>, MediaLibError>> {
+        self.next_frame()
+            .map(|frame| frame.map(|frame| frame.plane::<i16>(0).to_vec()))
+    }
+
+    /// The next decoded frame in the output format, none once the stream
+    /// is done.
+    pub fn next_frame(&mut self) -> Option<Result<Audio, MediaLibError>> {
+        let mut decoded = Audio::empty();
+        loop {
+            match self.decoder.receive_frame(&mut decoded) {
+                Ok(()) => {
+                    let start_time = match unsafe { (*self.ictx.as_ptr()).start_time } {
+                        ffmpeg_next::ffi::AV_NOPTS_VALUE => 0,
+                        start_time => start_time,
+                    };
+                    self.timestamp_us = decoded
+                        .timestamp()
+                        .map(|pts| timestamp::to_us(pts, self.time_base) - start_time);
+                    return Some(self.resampler.run(&decoded));
+                }
+                Err(ffmpeg_next::Error::Eof) => return self.flush(),
+                Err(ffmpeg_next::Error::Other { errno: EAGAIN }) if !self.eof_sent => {}
+                Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => return self.flush(),
+                Err(e) => return Some(Err(ffmpeg_error(e))),
+            }
+
+            if let Err(e) = self.send_next_packet() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use media_types::{AudioPreviewStyle, Color, MediaLibError, PreviewOptions, Size};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use super::audio_decoder::AudioDecoder;
+
+// Plenty for a thumbnail sized picture of the audio.
+const PREVIEW_SAMPLE_RATE: u32 = 8000;
+// 10ms blocks.
+const SAMPLES_PER_PEAK: usize = 80;
+// 32ms windows, giving 128 frequency bands up to 4kHz.
+const SPECTRUM_WINDOW: usize = 256;
+// Quietest level shown in a spectrogram, anything below is background.
+const SPECTRUM_FLOOR_DB: f32 = -90.0;
+
+/// A packed 8 bit RGB image.
+pub struct RgbImage {
+    pub size: Size,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn new(size: Size, background: Color) -> Self {
+        let pixels = [background.r, background.g, background.b]
+            .repeat(size.width as usize * size.height as usize);
+        RgbImage { size, pixels }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Color) {
+        let offset = (y * self.size.width as usize + x) * 3;
+        self.pixels[offset..offset + 3].copy_from_slice(&[color.r, color.g, color.b]);
+    }
+}
+
+/// Loudest sample in each of `columns` equal slices of `peaks`.
+fn column_peaks(peaks: &[u16], columns: usize) -> Vec<u16> {
+    (0..columns)
+        .map(|column| {
+            let start = column * peaks.len() / columns;
+            let end = ((column + 1) * peaks.len() / columns).max(start + 1);
+            peaks
+                .get(start..end.min(peaks.len()))
+                .and_then(|slice| slice.iter().max().copied())
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+fn render_waveform(
+    audio_decoder: &mut AudioDecoder,
+    options: &PreviewOptions,
+) -> Result<RgbImage, MediaLibError> {
+    // Only the loudest sample matters at thumbnail size, keep one peak per
+    // block rather than the whole stream.
+    let mut peaks = Vec::new();
+    let mut block_peak = 0;
+    let mut block_samples = 0;
+    while let Some(samples) = audio_decoder.next_samples() {
+        for sample in samples? {
+            block_peak = block_peak.max(sample.unsigned_abs());
+            block_samples += 1;
+            if block_samples == SAMPLES_PER_PEAK {
+                peaks.push(block_peak);
+                block_peak = 0;
+                block_samples = 0;
+            }
+        }
+    }
+    if block_samples > 0 {
+        peaks.push(block_peak);
+    }
+    if peaks.is_empty() {
+        return Err(MediaLibError::FFmpegError(
+            "No audio could be decoded".into(),
+        ));
+    }
+
+    let mut image = RgbImage::new(options.size, options.background);
+    let height = options.size.height as usize;
+    let center = height / 2;
+    for (x, peak) in column_peaks(&peaks, options.size.width as usize)
+        .into_iter()
+        .enumerate()
+    {
+        // At least one pixel so silence still shows as a line.
+        let half_extent = ((peak as usize * center) / i16::MAX as usize).max(1);
+        for y in center.saturating_sub(half_extent)..(center + half_extent).min(height) {
+            image.set(x, y, options.foreground);
+        }
+    }
+    Ok(image)
+}
+
+/// Computes spectra of consecutive windows while keeping memory bounded: once
+/// `capacity` spectra are held every other one is dropped and only every
+/// `stride`th window is analysed from then on.
+struct SpectrumSampler {
+    fft: std::sync::Arc<dyn Fft<f32>>,
+    hann: Vec<f32>,
+    pending: Vec<i16>,
+    window_index: usize,
+    stride: usize,
+    capacity: usize,
+    spectra: Vec<Vec<f32>>,
+}
+
+impl SpectrumSampler {
+    fn new(capacity: usize) -> Self {
+        let hann = (0..SPECTRUM_WINDOW)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / SPECTRUM_WINDOW as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        SpectrumSampler {
+            fft: FftPlanner::new().plan_fft_forward(SPECTRUM_WINDOW),
+            hann,
+            pending: Vec::with_capacity(SPECTRUM_WINDOW),
+            window_index: 0,
+            stride: 1,
+            capacity: capacity.max(2),
+            spectra: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        for sample in samples {
+            self.pending.push(*sample);
+            if self.pending.len() == SPECTRUM_WINDOW {
+                self.finish_window();
+            }
+        }
+    }
+
+    fn finish_window(&mut self) {
+        if self.window_index % self.stride == 0 {
+            let spectrum = self.spectrum();
+            self.spectra.push(spectrum);
+            if self.spectra.len() >= self.capacity {
+                self.spectra = std::mem::take(&mut self.spectra)
+                    .into_iter()
+                    .step_by(2)
+                    .collect();
+                self.stride *= 2;
+            }
+        }
+        self.window_index += 1;
+        self.pending.clear();
+    }
+
+    /// Magnitude of each frequency band scaled to 0.0 (at or below the floor)
+    /// to 1.0 (full scale).
+    fn spectrum(&self) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = self
+            .pending
+            .iter()
+            .chain(std::iter::repeat(&0))
+            .zip(&self.hann)
+            .map(|(sample, weight)| Complex::new(*sample as f32 / i16::MAX as f32 * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        // A full scale sine peaks at a quarter of the window with a Hann
+        // window applied.
+        let full_scale = SPECTRUM_WINDOW as f32 / 4.0;
+        buffer[..SPECTRUM_WINDOW / 2]
+            .iter()
+            .map(|bin| {
+                let db = 20.0 * (bin.norm() / full_scale).max(1e-9).log10();
+                ((db - SPECTRUM_FLOOR_DB) / -SPECTRUM_FLOOR_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    fn finish(mut self) -> Vec<Vec<f32>> {
+        // Very short inputs may not fill a single window.
+        if self.spectra.is_empty() && !self.pending.is_empty() {
+            let spectrum = self.spectrum();
+            self.spectra.push(spectrum);
+        }
+        self.spectra
+    }
+}
+
+fn render_spectrogram(
+    audio_decoder: &mut AudioDecoder,
+    options: &PreviewOptions,
+) -> Result<RgbImage, MediaLibError> {
+    let width = options.size.width as usize;
+    let height = options.size.height as usize;
+
+    let mut sampler = SpectrumSampler::new(width * 2);
+    while let Some(samples) = audio_decoder.next_samples() {
+        sampler.push(&samples?);
+    }
+    let spectra = sampler.finish();
+    if spectra.is_empty() {
+        return Err(MediaLibError::FFmpegError(
+            "No audio could be decoded".into(),
+        ));
+    }
+
+    let mut image = RgbImage::new(options.size, options.background);
+    for x in 0..width {
+        let spectrum = &spectra[x * spectra.len() / width];
+        for y in 0..height {
+            // Low frequencies at the bottom.
+            let band = (height - 1 - y) * spectrum.len() / height;
+            let color = options.background.mix(options.foreground, spectrum[band]);
+            image.set(x, y, color);
+        }
+    }
+    Ok(image)
+}
+
+/// Draws the best audio stream of the input as a waveform or spectrogram
+/// filling `options.size`.
+pub fn render_audio_preview(
+    input_path: &Path,
+    options: &PreviewOptions,
+) -> Result<RgbImage, MediaLibError> {
+    if options.size.is_empty() {
+        return Err(MediaLibError::ImageError(
+            "Preview size must not be empty".into(),
+        ));
+    }
+
+    let mut audio_decoder = AudioDecoder::new(input_path, PREVIEW_SAMPLE_RATE)?;
+    match options.audio_style {
+        AudioPreviewStyle::Waveform => render_waveform(&mut audio_decoder, options),
+        AudioPreviewStyle::Spectrogram => render_spectrogram(&mut audio_decoder, options),
+    }
+}
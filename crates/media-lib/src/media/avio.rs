@@ -0,0 +1,366 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::raw::{c_int, c_void};
+
+use ffmpeg_next::ffi::{
+    av_free, av_malloc, avio_alloc_context, avio_context_free, AVIOContext, AVERROR, AVERROR_EOF,
+    AVSEEK_FORCE, AVSEEK_SIZE,
+};
+use ffmpeg_next::util::error::{EINVAL, EIO, ENOMEM, ENOSYS};
+use media_types::MediaLibError;
+
+// Same as FFmpeg's own file protocol.
+const BUFFER_SIZE: usize = 32 * 1024;
+
+const SEEK_SET: c_int = 0;
+const SEEK_CUR: c_int = 1;
+const SEEK_END: c_int = 2;
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// A file read at its own position, so sharing the file description with the
+/// caller (as a duplicated descriptor does) doesn't move their offset or
+/// ours.
+pub struct FileSource {
+    file: File,
+    position: u64,
+}
+
+impl FileSource {
+    pub fn new(file: File) -> Self {
+        FileSource { file, position: 0 }
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+impl Read for FileSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = read_at(&self.file, buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for FileSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek before the start of the file",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// A regular file mapped into memory, read by copying out of the mapping
+/// rather than with a read system call each time. The file's length is
+/// only looked at when it's mapped, so it must not change while it's read:
+/// touching pages past the end of a file that shrank since kills the
+/// process. Files that may still be written to are for `FileSource`.
+pub struct MappedSource {
+    mapping: Mapping,
+    position: u64,
+}
+
+impl MappedSource {
+    /// Fails for files that can't be mapped, empty ones among them. The
+    /// mapping outlives `file`.
+    pub fn new(file: File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "File is too big to map"))?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Empty files can't be mapped",
+            ));
+        }
+        Ok(MappedSource {
+            mapping: Mapping::new(&file, len)?,
+            position: 0,
+        })
+    }
+}
+
+impl Read for MappedSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.mapping.as_slice();
+        let start = self.position.min(data.len() as u64) as usize;
+        let read = buf.len().min(data.len() - start);
+        buf[..read].copy_from_slice(&data[start..start + read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for MappedSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => (self.mapping.len as u64).checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek before the start of the file",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// A read only view of a whole file, unmapped when dropped. It stays valid
+/// after the file is closed.
+struct Mapping {
+    address: *mut c_void,
+    len: usize,
+}
+
+// Only ever read, from whichever thread has the reader.
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.address as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn new(file: &File, len: usize) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping { address, len })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address, self.len) };
+    }
+}
+
+#[cfg(windows)]
+impl Mapping {
+    fn new(file: &File, len: usize) -> io::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Memory::{
+            CreateFileMappingW, MapViewOfFile, FILE_MAP_READ, PAGE_READONLY,
+        };
+
+        unsafe {
+            let section = CreateFileMappingW(
+                file.as_raw_handle() as _,
+                std::ptr::null(),
+                PAGE_READONLY,
+                0,
+                0,
+                std::ptr::null(),
+            );
+            if section == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let view = MapViewOfFile(section, FILE_MAP_READ, 0, 0, len);
+            let error = io::Error::last_os_error();
+            // The view holds on to the section itself.
+            CloseHandle(section);
+            if view.Value.is_null() {
+                return Err(error);
+            }
+            Ok(Mapping {
+                address: view.Value,
+                len,
+            })
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Memory::{UnmapViewOfFile, MEMORY_MAPPED_VIEW_ADDRESS};
+
+        unsafe {
+            UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+                Value: self.address,
+            })
+        };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+impl Mapping {
+    fn new(_file: &File, _len: usize) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Memory mapping isn't supported on this platform",
+        ))
+    }
+}
+
+struct Reader {
+    inner: Box<dyn ReadSeek>,
+    seekable: bool,
+}
+
+impl Reader {
+    fn size(&mut self) -> io::Result<u64> {
+        let current = self.inner.stream_position()?;
+        let size = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(current))?;
+        Ok(size)
+    }
+}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let reader = &mut *(opaque as *mut Reader);
+    let buf = std::slice::from_raw_parts_mut(buf, buf_size.max(0) as usize);
+    match reader.inner.read(buf) {
+        Ok(0) => AVERROR_EOF,
+        Ok(read) => read as c_int,
+        Err(e) => {
+            log::warn!("Failed to read input: {}", e);
+            AVERROR(EIO)
+        }
+    }
+}
+
+unsafe extern "C" fn seek(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let reader = &mut *(opaque as *mut Reader);
+    if !reader.seekable {
+        return AVERROR(ENOSYS) as i64;
+    }
+
+    let result = match whence & !(AVSEEK_FORCE as c_int) {
+        whence if whence == AVSEEK_SIZE as c_int => reader.size(),
+        SEEK_SET => reader.inner.seek(SeekFrom::Start(offset as u64)),
+        SEEK_CUR => reader.inner.seek(SeekFrom::Current(offset)),
+        SEEK_END => reader.inner.seek(SeekFrom::End(offset)),
+        _ => return AVERROR(EINVAL) as i64,
+    };
+    match result {
+        Ok(position) => position as i64,
+        Err(e) => {
+            log::warn!("Failed to seek input: {}", e);
+            AVERROR(EIO) as i64
+        }
+    }
+}
+
+/// An AVIO context reading from Rust instead of a URL. It has to outlive the
+/// format context using it, see `MediaInput`.
+pub struct CustomIo {
+    context: *mut AVIOContext,
+    reader: *mut Reader,
+}
+
+impl CustomIo {
+    /// Pipes and other streams that can't seek are read front to back, the
+    /// demuxer is told not to try.
+    pub fn new(inner: Box<dyn ReadSeek>, seekable: bool) -> Result<Self, MediaLibError> {
+        let reader = Box::into_raw(Box::new(Reader { inner, seekable }));
+        unsafe {
+            let buffer = av_malloc(BUFFER_SIZE) as *mut u8;
+            let context = if buffer.is_null() {
+                std::ptr::null_mut()
+            } else {
+                avio_alloc_context(
+                    buffer,
+                    BUFFER_SIZE as c_int,
+                    0,
+                    reader as *mut c_void,
+                    Some(read_packet),
+                    None,
+                    Some(seek),
+                )
+            };
+            if context.is_null() {
+                av_free(buffer as *mut c_void);
+                drop(Box::from_raw(reader));
+                return Err(MediaLibError::FFmpegError(
+                    ffmpeg_next::Error::from(AVERROR(ENOMEM)).to_string().into(),
+                ));
+            }
+            (*context).seekable = if seekable { 1 } else { 0 };
+            Ok(CustomIo { context, reader })
+        }
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut AVIOContext {
+        self.context
+    }
+}
+
+impl Drop for CustomIo {
+    fn drop(&mut self) {
+        unsafe {
+            // FFmpeg may have swapped the buffer for one of its own, free
+            // whichever it has now.
+            av_free((*self.context).buffer as *mut c_void);
+            avio_context_free(&mut self.context);
+            drop(Box::from_raw(self.reader));
+        }
+    }
+}
+
+// The reader is only ever used by the thread driving the demuxer.
+unsafe impl Send for CustomIo {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_files_read_like_the_file() {
+        let path = std::env::temp_dir().join(format!("media-lib-mapped-{}", std::process::id()));
+        let data: Vec<u8> = (0..3 * BUFFER_SIZE).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let mut mapped = MappedSource::new(File::open(&path).unwrap()).unwrap();
+        let mut read = Vec::new();
+        mapped.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+
+        let mut buf = vec![0; 16];
+        mapped.seek(SeekFrom::End(-8)).unwrap();
+        assert_eq!(mapped.read(&mut buf).unwrap(), 8);
+        assert_eq!(buf[..8], data[data.len() - 8..]);
+        assert_eq!(mapped.read(&mut buf).unwrap(), 0);
+        assert!(mapped
+            .seek(SeekFrom::Current(-(data.len() as i64) - 1))
+            .is_err());
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    AnimationFormat, EncodedImage, ImageFormat, JpegOptions, MediaFrameDecoderOptions,
+    MediaLibError, OutputOptions, OutputRecord, PreviewBundle, PreviewBundleOptions, Size,
+};
+
+use super::animation::PreviewAnimation;
+use super::filmstrip::Filmstrip;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::jpeg::{pixel_format, JpegEncoder};
+use super::storyboard::Storyboard;
+use super::{ffmpeg_error, io_error};
+use crate::to_stabby_vec;
+
+// What the storyboard's cues call the sprite, and the file it's written to.
+const SPRITE_NAME: &str = "storyboard.jpg";
+
+/// `frame` fitted inside `bounds` as a JPEG.
+fn encode_poster(
+    frame: &Video,
+    bounds: Size,
+    jpeg: &JpegOptions,
+) -> Result<EncodedImage, MediaLibError> {
+    let size = Size::new(frame.width(), frame.height()).scale_to_fit(bounds);
+    let mut scaled = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        pixel_format(jpeg.subsampling),
+        size.width,
+        size.height,
+        Flags::BICUBIC,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut scaled))
+    .map_err(ffmpeg_error)?;
+    Ok(encoded_jpeg(
+        JpegEncoder::with_options(jpeg).encode(&scaled)?,
+        size,
+    ))
+}
+
+fn encoded_jpeg(data: Vec<u8>, size: Size) -> EncodedImage {
+    EncodedImage {
+        data: to_stabby_vec(&data),
+        size,
+        format: ImageFormat::Jpeg,
+    }
+}
+
+fn animation_warning(e: MediaLibError) -> stabby::string::String {
+    format!("Left out the animation: {}", e).as_str().into()
+}
+
+/// Writes `data` to `name` in `directory` as `output` says, returning what
+/// was written.
+fn write_output(
+    directory: &Path,
+    name: &str,
+    data: &[u8],
+    output: &OutputOptions,
+) -> Result<OutputRecord, MediaLibError> {
+    let path = directory.join(name);
+    output.write(&path, data).map_err(io_error)?;
+    Ok(OutputRecord {
+        path: path.to_string_lossy().as_ref().into(),
+        bytes: data.len() as u64,
+    })
+}
+
+/// A poster, filmstrip, storyboard and animated preview of `input_path`,
+/// everything a media platform shows for a file before it's played, from a
+/// single decode of the input from start to end. The animation is a
+/// nice-to-have: if it can't be made the rest of the bundle still is, with
+/// a warning saying why.
+pub fn preview_bundle(
+    input_path: &Path,
+    options: &PreviewBundleOptions,
+) -> Result<PreviewBundle, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let duration_us = video_decoder.duration_us().ok_or_else(|| {
+        MediaLibError::FFmpegError("Input has no known duration to sample across".into())
+    })?;
+    let input_size = video_decoder.size();
+    let mut filmstrip = Filmstrip::new(
+        input_size,
+        duration_us,
+        options.filmstrip_count,
+        options.filmstrip_cell_width,
+    )?;
+    let mut storyboard = Storyboard::new(
+        input_size,
+        duration_us,
+        options.storyboard_interval_ms,
+        options.storyboard_columns,
+        options.storyboard_tile_width,
+    )?;
+    let mut warnings = stabby::vec::Vec::new();
+    let mut animation = match PreviewAnimation::new(
+        input_size,
+        options.animation_start_ms,
+        options.animation_duration_ms,
+        options.animation_fps,
+        options.animation_size,
+        options.animation_format,
+    ) {
+        Ok(animation) => Some(animation),
+        Err(e) => {
+            warnings.push(animation_warning(e));
+            None
+        }
+    };
+    let poster_us = (options.poster_time_ms as i64 * 1000).min(duration_us / 2);
+    let mut poster = None;
+
+    let start_time_us = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        let decoded = decoded?;
+        let Some(time_us) = video_decoder.frame_time_us(&decoded) else {
+            continue;
+        };
+        let elapsed_us = time_us - start_time_us;
+        if poster.is_none() && elapsed_us >= poster_us {
+            poster = Some(encode_poster(&decoded, options.poster_size, &options.jpeg)?);
+        }
+        filmstrip.add(&decoded, elapsed_us)?;
+        storyboard.add(&decoded, elapsed_us)?;
+        if let Some(Err(e)) = animation.as_mut().map(|a| a.add(&decoded, elapsed_us)) {
+            warnings.push(animation_warning(e));
+            animation = None;
+        }
+        let animation_done = !animation.as_ref().is_some_and(|a| !a.is_full());
+        if poster.is_some() && filmstrip.is_full() && storyboard.is_full() && animation_done {
+            break;
+        }
+    }
+
+    let animation = match animation.map(PreviewAnimation::finish) {
+        Some(Ok(animation)) => animation,
+        Some(Err(e)) => {
+            warnings.push(animation_warning(e));
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+
+    let mut bundle = PreviewBundle {
+        poster: poster.ok_or_else(|| {
+            MediaLibError::UnknownError("Input has no frames to make a poster from".into())
+        })?,
+        filmstrip: encoded_jpeg(filmstrip.encode(&options.jpeg)?, filmstrip.size()),
+        storyboard: encoded_jpeg(storyboard.encode(&options.jpeg)?, storyboard.size()),
+        storyboard_vtt: storyboard.vtt(SPRITE_NAME).as_str().into(),
+        animation: to_stabby_vec(&animation),
+        warnings,
+        outputs: stabby::vec::Vec::new(),
+    };
+
+    if let Some(directory) = options.output_dir.as_ref() {
+        let directory = Path::new(directory.as_str());
+        std::fs::create_dir_all(directory).map_err(io_error)?;
+        let animation_name = match options.animation_format {
+            AnimationFormat::Gif => "preview.gif",
+            AnimationFormat::WebP => "preview.webp",
+        };
+        let mut files: Vec<(&str, &[u8])> = vec![
+            ("poster.jpg", &bundle.poster.data[..]),
+            ("filmstrip.jpg", &bundle.filmstrip.data[..]),
+            (SPRITE_NAME, &bundle.storyboard.data[..]),
+            ("storyboard.vtt", bundle.storyboard_vtt.as_str().as_bytes()),
+        ];
+        if !bundle.animation.is_empty() {
+            files.push((animation_name, &bundle.animation[..]));
+        }
+        let mut outputs = stabby::vec::Vec::new();
+        for (name, data) in files {
+            outputs.push(write_output(directory, name, data, &options.output)?);
+        }
+        bundle.outputs = outputs;
+    }
+    Ok(bundle)
+}
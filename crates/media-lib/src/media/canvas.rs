@@ -0,0 +1,215 @@
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{Color, JpegOptions, MediaFrameDecoderOptions, MediaLibError, Rect, Size};
+
+use super::audio_preview::RgbImage;
+use super::ffmpeg_error;
+use super::filter_graph::FilterGraph;
+use super::preview::encode_rgb;
+use super::still;
+
+// Height of the frames returned when the options don't give a size.
+const DEFAULT_TARGET_HEIGHT: u32 = 360;
+
+/// The size frames are fitted to with `options.scaling_mode`.
+pub fn requested_size(options: &MediaFrameDecoderOptions) -> Size {
+    match (options.target_width, options.target_height) {
+        (0, 0) => Size::new(0, DEFAULT_TARGET_HEIGHT),
+        (width, height) => Size::new(width, height),
+    }
+}
+
+/// The crop and pad filters centering frames of `scaled` on a black
+/// canvas of `target`, cropping what's larger and letterboxing what's
+/// smaller. Both keep offsets on the chroma grid. None when the two match.
+fn canvas_spec(scaled: Size, target: Size) -> Option<String> {
+    let cropped = Size::new(
+        scaled.width.min(target.width),
+        scaled.height.min(target.height),
+    );
+    let mut filters = Vec::new();
+    if cropped != scaled {
+        filters.push(format!("crop={}:{}", cropped.width, cropped.height));
+    }
+    if cropped != target {
+        filters.push(format!(
+            "pad={}:{}:(ow-iw)/2:(oh-ih)/2:black",
+            target.width, target.height
+        ));
+    }
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+/// Turns decoded frames upright, then scales them to `pixel_format` (see
+/// `jpeg::pixel_format`) and the size the caller asked for in a filter
+/// graph, centered on a black canvas when letterboxing or cropping. The graph is built for the first
+/// frame and again if frames change format or size.
+pub struct FrameScaler {
+    spec: String,
+    graph: Option<FilterGraph>,
+}
+
+impl FrameScaler {
+    /// Frames with EXIF `orientation` (see `still::orientation_filters`)
+    /// are scaled to `scaled_size` once upright and centered in
+    /// `target_size`, see `ScalingMode::apply`.
+    pub fn new(pixel_format: Pixel, orientation: u8, scaled_size: Size, target_size: Size) -> Self {
+        let format = pixel_format
+            .descriptor()
+            .map_or("yuvj420p", |descriptor| descriptor.name());
+        let filters = [
+            still::orientation_filters(orientation).map(str::to_string),
+            Some(format!(
+                "scale={}:{}:flags=bilinear,format={}",
+                scaled_size.width, scaled_size.height, format
+            )),
+            canvas_spec(scaled_size, target_size),
+        ];
+        FrameScaler {
+            spec: filters.into_iter().flatten().collect::<Vec<_>>().join(","),
+            graph: None,
+        }
+    }
+
+    pub fn run(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let graph = match self.graph.take() {
+            Some(graph) if graph.accepts(frame) => self.graph.insert(graph),
+            _ => self.graph.insert(FilterGraph::new(frame, &self.spec)?),
+        };
+        graph.run(frame)
+    }
+}
+
+/// Equal tiles laid out in rows of `columns` on one RGB image, the sheet
+/// filmstrips and storyboards are composited on.
+pub struct TileSheet {
+    image: RgbImage,
+    tile_size: Size,
+    columns: u32,
+    // Created from the first frame, since that's when we know what we're
+    // converting from.
+    scaler: Option<Context>,
+}
+
+impl TileSheet {
+    /// Room for `tiles` tiles of `tile_size`, in as many rows as they need.
+    pub fn new(tile_size: Size, columns: u32, tiles: u32) -> Self {
+        let columns = columns.clamp(1, tiles.max(1));
+        let size = Size::new(
+            tile_size.width * columns,
+            tile_size.height * tiles.div_ceil(columns),
+        );
+        TileSheet {
+            image: RgbImage::new(size, Color::default()),
+            tile_size,
+            columns,
+            scaler: None,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.image.size
+    }
+
+    /// Where tile `index` is on the sheet, counting along each row.
+    pub fn tile_rect(&self, index: u32) -> Rect {
+        Rect::new(
+            index % self.columns * self.tile_size.width,
+            index / self.columns * self.tile_size.height,
+            self.tile_size.width,
+            self.tile_size.height,
+        )
+    }
+
+    /// `frame` scaled to the tile size as RGB24, ready to `place`.
+    pub fn scale(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let scaler = match &mut self.scaler {
+            Some(scaler) => scaler,
+            None => self.scaler.insert(
+                Context::get(
+                    frame.format(),
+                    frame.width(),
+                    frame.height(),
+                    Pixel::RGB24,
+                    self.tile_size.width,
+                    self.tile_size.height,
+                    Flags::BILINEAR,
+                )
+                .map_err(ffmpeg_error)?,
+            ),
+        };
+        let mut tile = Video::empty();
+        scaler.run(frame, &mut tile).map_err(ffmpeg_error)?;
+        Ok(tile)
+    }
+
+    /// Copies a tile from `scale` into place `index`.
+    pub fn place(&mut self, tile: &Video, index: u32) {
+        let rect = self.tile_rect(index);
+        let image_stride = self.image.size.width as usize * 3;
+        let row_bytes = rect.width as usize * 3;
+        let offset = rect.y as usize * image_stride + rect.x as usize * 3;
+        for (y, row) in tile
+            .data(0)
+            .chunks(tile.stride(0))
+            .take(rect.height as usize)
+            .enumerate()
+        {
+            let start = offset + y * image_stride;
+            self.image.pixels[start..start + row_bytes].copy_from_slice(&row[..row_bytes]);
+        }
+    }
+
+    /// The sheet as a JPEG.
+    pub fn encode(&self, jpeg: &JpegOptions) -> Result<Vec<u8>, MediaLibError> {
+        encode_rgb(&self.image, jpeg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_pads_letterboxed_frames() {
+        assert_eq!(
+            canvas_spec(Size::new(320, 180), Size::new(320, 320)).as_deref(),
+            Some("pad=320:320:(ow-iw)/2:(oh-ih)/2:black")
+        );
+    }
+
+    #[test]
+    fn it_crops_filled_frames() {
+        assert_eq!(
+            canvas_spec(Size::new(569, 320), Size::new(320, 320)).as_deref(),
+            Some("crop=320:320")
+        );
+    }
+
+    #[test]
+    fn it_leaves_frames_that_fit_alone() {
+        assert_eq!(canvas_spec(Size::new(640, 360), Size::new(640, 360)), None);
+    }
+
+    #[test]
+    fn it_lays_tiles_out_in_rows() {
+        let sheet = TileSheet::new(Size::new(160, 90), 4, 10);
+        assert_eq!(sheet.size(), Size::new(640, 270));
+        assert_eq!(sheet.tile_rect(0), Rect::new(0, 0, 160, 90));
+        assert_eq!(sheet.tile_rect(3), Rect::new(480, 0, 160, 90));
+        assert_eq!(sheet.tile_rect(9), Rect::new(160, 180, 160, 90));
+
+        // Fewer tiles than columns make one row just wide enough.
+        let sheet = TileSheet::new(Size::new(160, 90), 4, 2);
+        assert_eq!(sheet.size(), Size::new(320, 90));
+    }
+
+    #[test]
+    fn it_crops_and_pads_when_only_one_side_fits() {
+        assert_eq!(
+            canvas_spec(Size::new(400, 100), Size::new(300, 200)).as_deref(),
+            Some("crop=300:100,pad=300:200:(ow-iw)/2:(oh-ih)/2:black")
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use ffmpeg_next::codec::{self, encoder};
+use ffmpeg_next::format::{self, context::Input};
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::util::frame::audio::Audio;
+use ffmpeg_next::{filter, ChannelLayout, Rational};
+use media_types::{ChannelExportOptions, MediaLibError, OutputOptions, ProcessingReport};
+
+use super::ffmpeg_error;
+use super::filter_graph::build_graph;
+use super::input::open_input;
+use super::job::Job;
+use super::output::StagedOutput;
+use super::timestamp::{self, Sequence};
+use super::video_writer::write_packets;
+
+// FFmpeg's default layout for each channel count, which `pan` needs by name.
+// A file can't hold more selected channels than this.
+const LAYOUT_NAMES: &[&str] = &["mono", "stereo", "2.1", "4.0", "5.0", "5.1", "6.1", "7.1"];
+// The tempo range a single atempo takes in every FFmpeg version.
+const ATEMPO_RANGE: (f64, f64) = (0.5, 2.0);
+
+/// `out.wav` becomes `out_ch3.wav` for channel 3.
+fn channel_output_path(output_path: &Path, channel: u32) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match output_path.extension() {
+        Some(extension) => format!("{}_ch{}.{}", stem, channel, extension.to_string_lossy()),
+        None => format!("{}_ch{}", stem, channel),
+    };
+    output_path.with_file_name(name)
+}
+
+/// An atempo chain playing `speed` times faster without changing the
+/// pitch, none at 1.0. Speeds outside what one atempo takes are split
+/// across several.
+pub fn tempo_spec(speed: f64) -> Option<String> {
+    if speed == 1.0 {
+        return None;
+    }
+    let (min, max) = ATEMPO_RANGE;
+    let mut remaining = speed;
+    let mut filters = Vec::new();
+    while remaining > max || remaining < min {
+        let step = if remaining > max { max } else { min };
+        filters.push(format!("atempo={}", step));
+        remaining /= step;
+    }
+    filters.push(format!("atempo={}", remaining));
+    Some(filters.join(","))
+}
+
+/// One output file: a `pan` graph picking its channels out of the decoded
+/// audio (and changing its tempo when asked to), and an encoder picked
+/// from the output path's extension.
+struct ChannelWriter {
+    path: PathBuf,
+    graph: filter::Graph,
+    octx: format::context::Output,
+    output: StagedOutput,
+    encoder: encoder::Audio,
+    time_base: Rational,
+    timestamps: Sequence,
+}
+
+impl ChannelWriter {
+    /// `channels` are zero based indexes into the decoded audio.
+    fn new(
+        path: PathBuf,
+        decoder: &ffmpeg_next::decoder::Audio,
+        input_time_base: Rational,
+        channels: &[u32],
+        tempo: Option<&str>,
+        output_options: &OutputOptions,
+    ) -> Result<Self, MediaLibError> {
+        let layout_name = channels
+            .len()
+            .checked_sub(1)
+            .and_then(|index| LAYOUT_NAMES.get(index))
+            .ok_or_else(|| {
+                MediaLibError::UnknownError(
+                    format!(
+                        "Between 1 and {} channels can be exported to one file",
+                        LAYOUT_NAMES.len()
+                    )
+                    .into(),
+                )
+            })?;
+        let channel_layout = ChannelLayout::default(channels.len() as i32);
+
+        let output = StagedOutput::new(&path, output_options)?;
+        let mut octx = format::output(output.staging_path()).map_err(ffmpeg_error)?;
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        let codec_id = octx.format().codec(&path, ffmpeg_next::media::Type::Audio);
+        let codec = encoder::find(codec_id).ok_or_else(|| {
+            MediaLibError::FFmpegError(format!("No encoder available for {:?}", codec_id).into())
+        })?;
+        let sample_format = codec
+            .audio()
+            .ok()
+            .and_then(|audio| audio.formats())
+            .and_then(|mut formats| formats.next())
+            .unwrap_or(decoder.format());
+        let time_base = Rational(1, decoder.rate() as i32);
+
+        let mut ost = octx.add_stream(codec).map_err(ffmpeg_error)?;
+        let mut audio = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .audio()
+            .map_err(ffmpeg_error)?;
+        audio.set_rate(decoder.rate() as i32);
+        audio.set_channel_layout(channel_layout);
+        audio.set_format(sample_format);
+        audio.set_time_base(time_base);
+        if global_header {
+            audio.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+
+        let encoder = audio.open().map_err(ffmpeg_error)?;
+        ost.set_parameters(&encoder);
+        ost.set_time_base(time_base);
+
+        // Every output channel is mapped explicitly, so nothing is mixed or
+        // reordered behind our back.
+        let mapping: Vec<String> = channels
+            .iter()
+            .enumerate()
+            .map(|(output, input)| format!("c{}=c{}", output, input))
+            .collect();
+        let mut spec = format!("pan={}|{}", layout_name, mapping.join("|"));
+        if let Some(tempo) = tempo {
+            spec = format!("{},{}", spec, tempo);
+        }
+        let graph = Self::graph(&spec, decoder, input_time_base, &encoder)?;
+
+        octx.write_header().map_err(ffmpeg_error)?;
+
+        Ok(ChannelWriter {
+            path,
+            graph,
+            octx,
+            output,
+            encoder,
+            time_base,
+            timestamps: Sequence::new(),
+        })
+    }
+
+    fn graph(
+        spec: &str,
+        decoder: &ffmpeg_next::decoder::Audio,
+        input_time_base: Rational,
+        encoder: &encoder::Audio,
+    ) -> Result<filter::Graph, MediaLibError> {
+        let input_layout = decoder.channel_layout();
+        // Inputs that don't say what their channels are only have a count.
+        let layout_arg = if input_layout.is_empty() {
+            format!("channels={}", decoder.channels())
+        } else {
+            format!("channel_layout=0x{:x}", input_layout.bits())
+        };
+        let args = format!(
+            "time_base={}:sample_rate={}:sample_fmt={}:{}",
+            input_time_base,
+            decoder.rate(),
+            decoder.format().name(),
+            layout_arg
+        );
+
+        // The encoder's sample format, rate and channels.
+        let spec = format!(
+            "{},aformat=sample_fmts={}:sample_rates={}:channel_layouts=0x{:x}",
+            spec,
+            encoder.format().name(),
+            encoder.rate(),
+            encoder.channel_layout().bits()
+        );
+        let mut graph = build_graph("abuffer", &args, "abuffersink", &spec)?;
+
+        // Encoders such as AAC take a fixed number of samples per frame.
+        let variable_frame_size = encoder.codec().is_some_and(|codec| {
+            codec
+                .capabilities()
+                .contains(codec::capabilities::Capabilities::VARIABLE_FRAME_SIZE)
+        });
+        if !variable_frame_size {
+            if let Some(mut sink) = graph.get("out") {
+                sink.sink().set_frame_size(encoder.frame_size());
+            }
+        }
+        Ok(graph)
+    }
+
+    fn add(&mut self, decoded: &Audio) -> Result<(), MediaLibError> {
+        self.graph
+            .get("in")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter input missing".into()))?
+            .source()
+            .add(decoded)
+            .map_err(ffmpeg_error)?;
+        self.encode_filtered()
+    }
+
+    fn encode_filtered(&mut self) -> Result<(), MediaLibError> {
+        let mut filtered = Audio::empty();
+        loop {
+            let received = self
+                .graph
+                .get("out")
+                .ok_or_else(|| MediaLibError::FFmpegError("Filter output missing".into()))?
+                .sink()
+                .frame(&mut filtered);
+            match received {
+                Ok(()) => {
+                    // Timed by sample count, the same as the input at a
+                    // constant rate and immune to gaps in its timestamps.
+                    filtered.set_pts(Some(self.timestamps.take(filtered.samples() as i64)));
+                    self.encoder.send_frame(&filtered).map_err(ffmpeg_error)?;
+                    self.write_packets()?;
+                }
+                Err(ffmpeg_next::Error::Other { errno: EAGAIN }) | Err(ffmpeg_next::Error::Eof) => {
+                    return Ok(())
+                }
+                Err(e) => return Err(ffmpeg_error(e)),
+            }
+        }
+    }
+
+    fn write_packets(&mut self) -> Result<(), MediaLibError> {
+        write_packets(&mut self.encoder, &mut self.octx, 0, self.time_base)
+    }
+
+    /// Drains the graph and encoder and finalises the file. Returns its path
+    /// and size.
+    fn finish(mut self) -> Result<(PathBuf, u64), MediaLibError> {
+        if let Some(mut input) = self.graph.get("in") {
+            input.source().flush().map_err(ffmpeg_error)?;
+        }
+        self.encode_filtered()?;
+        self.encoder.send_eof().map_err(ffmpeg_error)?;
+        self.write_packets()?;
+        self.octx.write_trailer().map_err(ffmpeg_error)?;
+        // Closes the file before it's moved into place.
+        drop(self.octx);
+        self.output.commit()?;
+
+        let bytes = std::fs::metadata(&self.path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        Ok((self.path, bytes))
+    }
+}
+
+/// Decodes the best audio stream and feeds every frame to each writer.
+fn decode_into(
+    ictx: &mut Input,
+    stream_index: usize,
+    decoder: &mut ffmpeg_next::decoder::Audio,
+    writers: &mut [ChannelWriter],
+    warnings: &mut Vec<String>,
+    job: &mut Job,
+) -> Result<u64, MediaLibError> {
+    let duration = Some(ictx.duration()).filter(|duration| *duration > 0);
+    let mut frames_decoded = 0;
+    let mut decoded = Audio::empty();
+    let mut receive = |decoder: &mut ffmpeg_next::decoder::Audio,
+                       writers: &mut [ChannelWriter]|
+     -> Result<(), MediaLibError> {
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            frames_decoded += 1;
+            for writer in writers.iter_mut() {
+                writer.add(&decoded)?;
+            }
+        }
+        Ok(())
+    };
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        job.check()?;
+        if let Some(pts) = packet.pts() {
+            job.progress(timestamp::to_us(pts, stream.time_base()), duration);
+        }
+        match decoder.send_packet(&packet) {
+            Ok(()) => {}
+            // A damaged packet shouldn't cost us the rest of the stream.
+            Err(ffmpeg_next::Error::InvalidData) => {
+                warnings.push(format!(
+                    "Skipped undecodable audio packet at {:?}",
+                    packet.pts()
+                ));
+                continue;
+            }
+            Err(e) => return Err(ffmpeg_error(e)),
+        }
+        receive(decoder, writers)?;
+    }
+    decoder.send_eof().map_err(ffmpeg_error)?;
+    receive(decoder, writers)?;
+    Ok(frames_decoded)
+}
+
+/// Exports channels of the input's best audio stream, either each to its own
+/// mono file next to `output_path` (`out_ch3.wav` for channel 3) or together
+/// to `output_path` in the order given. The codec comes from the output
+/// path's extension.
+pub fn export_audio_channels(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ChannelExportOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    let started = Instant::now();
+    let mut ictx = open_input(input_path, None)?;
+    let (stream_index, time_base, parameters) = {
+        let stream = ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Audio)
+            .ok_or_else(|| MediaLibError::FFmpegError("No audio stream found".into()))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+    let mut decoder = codec::context::Context::from_parameters(parameters)
+        .and_then(|context| context.decoder().audio())
+        .map_err(ffmpeg_error)?;
+
+    let channel_count = decoder.channels() as u32;
+    let channels: Vec<u32> = if options.channels.is_empty() {
+        (1..=channel_count).collect()
+    } else {
+        options.channels.iter().copied().collect()
+    };
+    if let Some(channel) = channels
+        .iter()
+        .find(|channel| **channel == 0 || **channel > channel_count)
+    {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Channel {} doesn't exist, the input has channels 1 to {}",
+                channel, channel_count
+            )
+            .into(),
+        ));
+    }
+
+    if !(options.speed.is_finite() && options.speed > 0.0) {
+        return Err(MediaLibError::UnknownError(
+            format!("Invalid speed {}, it has to be above zero", options.speed).into(),
+        ));
+    }
+    let tempo = tempo_spec(options.speed);
+
+    let mut writers = Vec::new();
+    if options.split {
+        for channel in &channels {
+            writers.push(ChannelWriter::new(
+                channel_output_path(output_path, *channel),
+                &decoder,
+                time_base,
+                &[channel - 1],
+                tempo.as_deref(),
+                &options.output,
+            )?);
+        }
+    } else {
+        let indexes: Vec<u32> = channels.iter().map(|channel| channel - 1).collect();
+        writers.push(ChannelWriter::new(
+            output_path.to_path_buf(),
+            &decoder,
+            time_base,
+            &indexes,
+            tempo.as_deref(),
+            &options.output,
+        )?);
+    }
+
+    let mut warnings = Vec::new();
+    let frames_decoded = decode_into(
+        &mut ictx,
+        stream_index,
+        &mut decoder,
+        &mut writers,
+        &mut warnings,
+        job,
+    )?;
+
+    let mut report = ProcessingReport {
+        wall_time_ms: 0,
+        frames_decoded,
+        frames_returned: frames_decoded,
+        frames_dropped: 0,
+        hardware_device: None.into(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    for writer in writers {
+        let (path, bytes) = writer.finish()?;
+        report.add_output(&path.to_string_lossy(), bytes);
+    }
+    for warning in &warnings {
+        report.warnings.push(warning.as_str().into());
+    }
+    report.wall_time_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_chains_atempo_for_big_tempo_changes() {
+        for (speed, spec) in [
+            (1.0, None),
+            (1.5, Some("atempo=1.5")),
+            (2.0, Some("atempo=2")),
+            (0.5, Some("atempo=0.5")),
+            (3.0, Some("atempo=2,atempo=1.5")),
+            (4.0, Some("atempo=2,atempo=2")),
+            (0.25, Some("atempo=0.5,atempo=0.5")),
+            (8.0, Some("atempo=2,atempo=2,atempo=2")),
+        ] {
+            assert_eq!(tempo_spec(speed).as_deref(), spec, "{}", speed);
+        }
+    }
+
+    #[test]
+    fn it_numbers_channel_files() {
+        assert_eq!(
+            channel_output_path(Path::new("/tmp/out.wav"), 3),
+            Path::new("/tmp/out_ch3.wav")
+        );
+        assert_eq!(
+            channel_output_path(Path::new("/tmp/out"), 1),
+            Path::new("/tmp/out_ch1")
+        );
+    }
+}
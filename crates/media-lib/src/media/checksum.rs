@@ -0,0 +1,39 @@
+use ffmpeg_next::ffi::{
+    av_adler32_update, av_crc, av_crc_get_table, av_image_get_linesize, AVCRCId,
+};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::ChecksumAlgorithm;
+
+/// Checksum of the visible bytes of one plane, row by row so the padding at
+/// the end of each row (which differs between runs and machines) is left
+/// out. None for a plane the frame doesn't have.
+pub fn plane_checksum(frame: &Video, plane: usize, algorithm: ChecksumAlgorithm) -> Option<u32> {
+    if plane >= frame.planes() {
+        return None;
+    }
+    let row_bytes =
+        unsafe { av_image_get_linesize(frame.format().into(), frame.width() as i32, plane as i32) };
+    if row_bytes <= 0 {
+        return None;
+    }
+
+    let rows = frame
+        .data(plane)
+        .chunks(frame.stride(plane))
+        .take(frame.plane_height(plane) as usize)
+        .map(|row| &row[..row_bytes as usize]);
+
+    let checksum = match algorithm {
+        ChecksumAlgorithm::Crc32 => {
+            let table = unsafe { av_crc_get_table(AVCRCId::AV_CRC_32_IEEE_LE) };
+            let crc = rows.fold(u32::MAX, |crc, row| unsafe {
+                av_crc(table, crc, row.as_ptr(), row.len())
+            });
+            crc ^ u32::MAX
+        }
+        ChecksumAlgorithm::Adler32 => rows.fold(1, |adler, row| unsafe {
+            av_adler32_update(adler, row.as_ptr(), row.len())
+        }),
+    };
+    Some(checksum)
+}
@@ -0,0 +1,167 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+
+use libloading::Library;
+use media_types::{AudioFingerprint, MediaLibError};
+
+use super::audio_decoder::AudioDecoder;
+use super::dylib::{get_symbol, load_first};
+
+// Chromaprint is loaded at runtime rather than linked so the library still
+// loads on machines without it installed.
+#[cfg(target_os = "windows")]
+const CHROMAPRINT_LIBRARIES: &[&str] = &["chromaprint.dll", "libchromaprint.dll"];
+#[cfg(target_os = "macos")]
+const CHROMAPRINT_LIBRARIES: &[&str] = &["libchromaprint.dylib", "libchromaprint.1.dylib"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const CHROMAPRINT_LIBRARIES: &[&str] = &["libchromaprint.so", "libchromaprint.so.1"];
+
+// CHROMAPRINT_ALGORITHM_DEFAULT, the one AcoustID lookups expect.
+const CHROMAPRINT_ALGORITHM_DEFAULT: i32 = 1;
+
+// Chromaprint works on 11025Hz mono internally, resampling to that up front
+// keeps the amount of audio we hand over small.
+const FINGERPRINT_SAMPLE_RATE: u32 = 11025;
+// Same as fpcalc, the start of a track is plenty to identify it.
+const MAX_FINGERPRINT_SECONDS: u32 = 120;
+const MAX_FINGERPRINT_SAMPLES: usize = (FINGERPRINT_SAMPLE_RATE * MAX_FINGERPRINT_SECONDS) as usize;
+
+type ChromaprintContext = *mut c_void;
+
+struct ChromaprintApi {
+    new: unsafe extern "C" fn(i32) -> ChromaprintContext,
+    free: unsafe extern "C" fn(ChromaprintContext),
+    start: unsafe extern "C" fn(ChromaprintContext, i32, i32) -> i32,
+    feed: unsafe extern "C" fn(ChromaprintContext, *const i16, i32) -> i32,
+    finish: unsafe extern "C" fn(ChromaprintContext) -> i32,
+    get_fingerprint: unsafe extern "C" fn(ChromaprintContext, *mut *mut c_char) -> i32,
+    dealloc: unsafe extern "C" fn(*mut c_void),
+    // The library must outlive the function pointers above.
+    _chromaprint: Library,
+}
+
+impl ChromaprintApi {
+    fn load() -> Result<Self, MediaLibError> {
+        let chromaprint = load_first(CHROMAPRINT_LIBRARIES)?;
+
+        unsafe {
+            Ok(ChromaprintApi {
+                new: get_symbol(&chromaprint, b"chromaprint_new\0")?,
+                free: get_symbol(&chromaprint, b"chromaprint_free\0")?,
+                start: get_symbol(&chromaprint, b"chromaprint_start\0")?,
+                feed: get_symbol(&chromaprint, b"chromaprint_feed\0")?,
+                finish: get_symbol(&chromaprint, b"chromaprint_finish\0")?,
+                get_fingerprint: get_symbol(&chromaprint, b"chromaprint_get_fingerprint\0")?,
+                dealloc: get_symbol(&chromaprint, b"chromaprint_dealloc\0")?,
+                _chromaprint: chromaprint,
+            })
+        }
+    }
+}
+
+// Chromaprint calls return 1 on success and 0 on failure.
+fn check_chromaprint(status: i32, call: &str) -> Result<(), MediaLibError> {
+    if status != 1 {
+        return Err(MediaLibError::UnknownError(
+            format!("{} failed", call).into(),
+        ));
+    }
+    Ok(())
+}
+
+struct Fingerprinter {
+    api: ChromaprintApi,
+    context: ChromaprintContext,
+}
+
+impl Fingerprinter {
+    fn new(sample_rate: u32, channels: u32) -> Result<Self, MediaLibError> {
+        let api = ChromaprintApi::load()?;
+        let context = unsafe { (api.new)(CHROMAPRINT_ALGORITHM_DEFAULT) };
+        if context.is_null() {
+            return Err(MediaLibError::UnknownError("chromaprint_new failed".into()));
+        }
+
+        let fingerprinter = Fingerprinter { api, context };
+        check_chromaprint(
+            unsafe { (fingerprinter.api.start)(context, sample_rate as i32, channels as i32) },
+            "chromaprint_start",
+        )?;
+        Ok(fingerprinter)
+    }
+
+    fn feed(&mut self, samples: &[i16]) -> Result<(), MediaLibError> {
+        check_chromaprint(
+            unsafe { (self.api.feed)(self.context, samples.as_ptr(), samples.len() as i32) },
+            "chromaprint_feed",
+        )
+    }
+
+    /// Finishes the stream and returns the compressed, base64 encoded
+    /// fingerprint.
+    fn finish(&mut self) -> Result<String, MediaLibError> {
+        check_chromaprint(
+            unsafe { (self.api.finish)(self.context) },
+            "chromaprint_finish",
+        )?;
+
+        let mut fingerprint: *mut c_char = std::ptr::null_mut();
+        check_chromaprint(
+            unsafe { (self.api.get_fingerprint)(self.context, &mut fingerprint) },
+            "chromaprint_get_fingerprint",
+        )?;
+        if fingerprint.is_null() {
+            return Err(MediaLibError::UnknownError(
+                "chromaprint returned an empty fingerprint".into(),
+            ));
+        }
+
+        let result = unsafe { CStr::from_ptr(fingerprint) }
+            .to_string_lossy()
+            .to_string();
+        unsafe { (self.api.dealloc)(fingerprint as *mut c_void) };
+        Ok(result)
+    }
+}
+
+impl Drop for Fingerprinter {
+    fn drop(&mut self) {
+        unsafe { (self.api.free)(self.context) };
+    }
+}
+
+/// Computes the chromaprint fingerprint of the best audio stream in the
+/// input, using up to the first two minutes of audio.
+pub fn fingerprint_audio(input_path: &Path) -> Result<AudioFingerprint, MediaLibError> {
+    let mut fingerprinter = Fingerprinter::new(FINGERPRINT_SAMPLE_RATE, 1)?;
+    let mut audio_decoder = AudioDecoder::new(input_path, FINGERPRINT_SAMPLE_RATE)?;
+
+    let mut fed_samples = 0;
+    while fed_samples < MAX_FINGERPRINT_SAMPLES {
+        let Some(samples) = audio_decoder.next_samples() else {
+            break;
+        };
+        let samples = samples?;
+        let count = samples.len().min(MAX_FINGERPRINT_SAMPLES - fed_samples);
+        fingerprinter.feed(&samples[..count])?;
+        fed_samples += count;
+    }
+
+    if fed_samples == 0 {
+        return Err(MediaLibError::FFmpegError(
+            "No audio could be decoded".into(),
+        ));
+    }
+
+    // Prefer the container's idea of the duration, we stop decoding after
+    // MAX_FINGERPRINT_SECONDS so the decoded length is only a lower bound.
+    let duration_secs = match audio_decoder.duration_us() {
+        Some(duration_us) => duration_us as f64 / 1_000_000.0,
+        None => fed_samples as f64 / FINGERPRINT_SAMPLE_RATE as f64,
+    };
+
+    Ok(AudioFingerprint {
+        fingerprint: fingerprinter.finish()?.as_str().into(),
+        duration_secs,
+    })
+}
@@ -0,0 +1,367 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use media_types::{
+    DatasetExportOptions, ManifestFormat, MediaFrameDecoderOptions, MediaLibError, ProcessingReport,
+};
+
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::image_sequence::SequenceWriter;
+use super::io_error;
+use super::job::Job;
+use super::poster::{luma_thumbnail, Exposure};
+
+// Luma standard deviation that counts as full marks for quality, about what
+// a detailed, well exposed frame has.
+const FULL_QUALITY_DETAIL: f64 = 64.0;
+
+/// A line of the manifest.
+struct Entry {
+    file: String,
+    source: String,
+    pts_us: i64,
+    frame_index: u64,
+    width: u32,
+    height: u32,
+    scene_score: Option<f64>,
+    quality_score: Option<f64>,
+}
+
+/// Quoted when it has to be, per RFC 4180.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            // Control characters, and the line separators JavaScript
+            // doesn't take in strings.
+            c if (c as u32) < 0x20 || c == '\u{7f}' || c == '\u{2028}' || c == '\u{2029}' => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// A score as a JSON or CSV number, null or empty when it isn't one.
+fn score_text(score: f64, missing: &str) -> String {
+    if score.is_finite() {
+        format!("{:.4}", score)
+    } else {
+        missing.to_string()
+    }
+}
+
+fn write_csv(entries: &[Entry], options: &DatasetExportOptions) -> String {
+    let mut manifest = String::from("file,source,pts_us,frame_index,width,height");
+    if options.scene_scores {
+        manifest.push_str(",scene_score");
+    }
+    if options.quality_scores {
+        manifest.push_str(",quality_score");
+    }
+    manifest.push('\n');
+
+    for entry in entries {
+        let _ = write!(
+            manifest,
+            "{},{},{},{},{},{}",
+            csv_field(&entry.file),
+            csv_field(&entry.source),
+            entry.pts_us,
+            entry.frame_index,
+            entry.width,
+            entry.height
+        );
+        for score in [entry.scene_score, entry.quality_score]
+            .into_iter()
+            .flatten()
+        {
+            let _ = write!(manifest, ",{}", score_text(score, ""));
+        }
+        manifest.push('\n');
+    }
+    manifest
+}
+
+fn write_json(entries: &[Entry]) -> String {
+    let mut manifest = String::from("[\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let _ = write!(
+            manifest,
+            "  {{\"file\": {}, \"source\": {}, \"pts_us\": {}, \"frame_index\": {}, \"width\": {}, \"height\": {}",
+            json_string(&entry.file),
+            json_string(&entry.source),
+            entry.pts_us,
+            entry.frame_index,
+            entry.width,
+            entry.height
+        );
+        if let Some(score) = entry.scene_score {
+            let _ = write!(manifest, ", \"scene_score\": {}", score_text(score, "null"));
+        }
+        if let Some(score) = entry.quality_score {
+            let _ = write!(
+                manifest,
+                ", \"quality_score\": {}",
+                score_text(score, "null")
+            );
+        }
+        manifest.push_str(if i + 1 == entries.len() {
+            "}\n"
+        } else {
+            "},\n"
+        });
+    }
+    manifest.push_str("]\n");
+    manifest
+}
+
+/// Mean difference between two luma thumbnails, 0 for the same picture and
+/// 1 for black against white.
+fn scene_change(previous: &[f64], current: &[f64]) -> f64 {
+    let total: f64 = previous
+        .iter()
+        .zip(current)
+        .map(|(a, b)| (a - b).abs())
+        .sum();
+    total / (current.len().max(1) as f64 * 255.0)
+}
+
+/// Samples the frames of one input into the dataset.
+fn export_input(
+    input_path: &Path,
+    sequence: &mut SequenceWriter,
+    entries: &mut Vec<Entry>,
+    options: &DatasetExportOptions,
+    report: &mut ProcessingReport,
+    job: &mut Job,
+) -> Result<(), MediaLibError> {
+    let source = input_path.to_string_lossy().to_string();
+    let opened = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    };
+    let mut video_decoder = match opened {
+        Ok(video_decoder) => video_decoder,
+        Err(e) => {
+            log::warn!("Leaving {} out of the dataset: {}", source, e);
+            report
+                .warnings
+                .push(format!("Skipped {}: {}", source, e).as_str().into());
+            return Ok(());
+        }
+    };
+    video_decoder.keyframes_only = false;
+    sequence.next_input();
+
+    let start_time = video_decoder.start_time_us();
+    let mut previous_thumbnail: Option<Vec<f64>> = None;
+    let mut frame_index: u64 = 0;
+    let written_before = entries.len();
+    // A damaged input keeps the images taken before the damage.
+    let stop = |report: &mut ProcessingReport, entries: &[Entry], e: MediaLibError| {
+        let images = entries.len() - written_before;
+        log::warn!("Stopped reading {} after {} images: {}", source, images, e);
+        report.warnings.push(
+            format!("Stopped reading {} after {} images: {}", source, images, e)
+                .as_str()
+                .into(),
+        );
+    };
+    while let Some(decoded) = video_decoder.get_frame() {
+        job.check()?;
+        let decoded = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                stop(report, entries, e);
+                break;
+            }
+        };
+        let time_us = video_decoder
+            .frame_time_us(&decoded)
+            .map(|time| time - start_time);
+        if let Some(time_us) = time_us {
+            job.progress(time_us, video_decoder.duration_us());
+        }
+        frame_index += 1;
+        if !sequence.wants(time_us) {
+            continue;
+        }
+
+        let mut entry = Entry {
+            file: String::new(),
+            source: source.clone(),
+            pts_us: time_us.unwrap_or_default(),
+            frame_index: frame_index - 1,
+            width: decoded.width(),
+            height: decoded.height(),
+            scene_score: None,
+            quality_score: None,
+        };
+        if options.scene_scores || options.quality_scores {
+            let thumbnail = match luma_thumbnail(&decoded) {
+                Ok(thumbnail) => thumbnail,
+                Err(e) => {
+                    stop(report, entries, e);
+                    break;
+                }
+            };
+            if options.scene_scores {
+                entry.scene_score = Some(match &previous_thumbnail {
+                    Some(previous) => scene_change(previous, &thumbnail),
+                    None => 1.0,
+                });
+            }
+            if options.quality_scores {
+                let detail = Exposure::of_thumbnail(&thumbnail).detail;
+                entry.quality_score = Some((detail / FULL_QUALITY_DETAIL).min(1.0));
+            }
+            previous_thumbnail = Some(thumbnail);
+        }
+        let path = sequence.write(decoded)?;
+        entry.file = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        entries.push(entry);
+    }
+
+    report.frames_decoded += video_decoder.frames_decoded;
+    if report.hardware_device.is_none() {
+        report.hardware_device = video_decoder
+            .hardware_device_name()
+            .map(|name| name.as_str().into())
+            .into();
+    }
+    for warning in &video_decoder.warnings {
+        report
+            .warnings
+            .push(format!("{}: {}", source, warning).as_str().into());
+    }
+    Ok(())
+}
+
+/// Writes sampled frames of every input to `output_dir` as numbered images,
+/// with a manifest next to them giving the source, timestamp, frame index
+/// and size of each (plus scores when asked for), for building training
+/// datasets from a set of videos. An input that can't be decoded is left
+/// out with a warning instead of failing the export, the manifest then has
+/// the frames written before the failure.
+pub fn export_dataset(
+    input_paths: &[&Path],
+    output_dir: &Path,
+    options: &DatasetExportOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    let started = Instant::now();
+    let mut sequence = SequenceWriter::new(output_dir, &options.sequence)?;
+    let mut entries = Vec::new();
+    let mut report = ProcessingReport {
+        wall_time_ms: 0,
+        frames_decoded: 0,
+        frames_returned: 0,
+        frames_dropped: 0,
+        hardware_device: stabby::option::Option::None(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+
+    for input_path in input_paths {
+        export_input(
+            input_path,
+            &mut sequence,
+            &mut entries,
+            options,
+            &mut report,
+            job,
+        )?;
+    }
+    let outputs = sequence.finish()?;
+
+    let (manifest_name, manifest) = match options.manifest_format {
+        ManifestFormat::Csv => ("manifest.csv", write_csv(&entries, options)),
+        ManifestFormat::Json => ("manifest.json", write_json(&entries)),
+    };
+    let manifest_path = output_dir.join(manifest_name);
+    options
+        .sequence
+        .output
+        .write(&manifest_path, manifest.as_bytes())
+        .map_err(io_error)?;
+
+    report.wall_time_ms = started.elapsed().as_millis() as u64;
+    report.frames_returned = outputs.len() as u64;
+    for (path, bytes) in &outputs {
+        report.add_output(&path.to_string_lossy(), *bytes);
+    }
+    report.add_output(&manifest_path.to_string_lossy(), manifest.len() as u64);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, source: &str, score: f64) -> Entry {
+        Entry {
+            file: file.to_string(),
+            source: source.to_string(),
+            pts_us: 500_000,
+            frame_index: 15,
+            width: 640,
+            height: 360,
+            scene_score: Some(score),
+            quality_score: None,
+        }
+    }
+
+    #[test]
+    fn it_escapes_json_strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("a \"b\"\\c\nd\u{1}\u{2028}"),
+            "\"a \\\"b\\\"\\\\c\\nd\\u0001\\u2028\""
+        );
+    }
+
+    #[test]
+    fn it_quotes_csv_fields() {
+        assert_eq!(csv_field("plain.mp4"), "plain.mp4");
+        assert_eq!(csv_field("a,b \"c\".mp4"), "\"a,b \"\"c\"\".mp4\"");
+    }
+
+    #[test]
+    fn it_writes_scores_that_arent_numbers_as_missing() {
+        let entries = [entry("frame_000001.png", "a.mp4", f64::NAN)];
+        let options = DatasetExportOptions {
+            scene_scores: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_csv(&entries, &options),
+            "file,source,pts_us,frame_index,width,height,scene_score\n\
+             frame_000001.png,a.mp4,500000,15,640,360,\n"
+        );
+        assert_eq!(
+            write_json(&entries),
+            "[\n  {\"file\": \"frame_000001.png\", \"source\": \"a.mp4\", \"pts_us\": 500000, \
+             \"frame_index\": 15, \"width\": 640, \"height\": 360, \"scene_score\": null}\n]\n"
+        );
+    }
+}
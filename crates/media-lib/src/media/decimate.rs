@@ -0,0 +1,75 @@
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Rational;
+use media_types::{MediaFrameDecoderOptions, MediaLibError};
+
+use super::filter_graph::FilterGraph;
+
+/// The select filters keeping one frame in `frame_step`, then the first
+/// frame of each `1 / max_fps` interval. Unlike fps, select never
+/// duplicates frames of inputs already below the rate. None when neither
+/// is set.
+fn decimate_spec(frame_step: u32, max_fps: f64) -> Option<String> {
+    let mut filters = Vec::new();
+    if frame_step > 1 {
+        filters.push(format!("select='not(mod(n\\,{}))'", frame_step));
+    }
+    if max_fps > 0.0 {
+        filters.push(format!(
+            "select='isnan(prev_selected_t)+gt(floor(t*{0})\\,floor(prev_selected_t*{0}))'",
+            max_fps
+        ));
+    }
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+/// Drops decoded frames the caller doesn't want, see `frame_step` and
+/// `max_fps`, before they're downloaded, scaled or encoded.
+pub struct Decimator {
+    spec: String,
+    time_base: Rational,
+    // Built for the first frame, and again after a seek.
+    graph: Option<FilterGraph>,
+}
+
+impl Decimator {
+    /// None when the options keep every frame.
+    pub fn new(
+        options: &MediaFrameDecoderOptions,
+        time_base: Rational,
+    ) -> Result<Option<Self>, MediaLibError> {
+        if !(options.max_fps.is_finite() && options.max_fps >= 0.0) {
+            return Err(MediaLibError::UnknownError(
+                format!("Invalid max_fps {}", options.max_fps).into(),
+            ));
+        }
+        Ok(
+            decimate_spec(options.frame_step, options.max_fps).map(|spec| Decimator {
+                spec,
+                time_base,
+                graph: None,
+            }),
+        )
+    }
+
+    /// Starts counting frames and intervals again, e.g. after a seek.
+    pub fn reset(&mut self) {
+        self.graph = None;
+    }
+
+    /// Whether `frame` is one to keep. Frames are judged by their best
+    /// effort timestamps.
+    pub fn keep(&mut self, frame: &Video) -> Result<bool, MediaLibError> {
+        let graph = match self.graph.take() {
+            Some(graph) if graph.accepts(frame) => self.graph.insert(graph),
+            _ => self.graph.insert(FilterGraph::with_time_base(
+                frame,
+                self.time_base,
+                &self.spec,
+            )?),
+        };
+        graph.add(frame, frame.timestamp())?;
+        // select passes frames straight through, so one in gives at most
+        // one out right away.
+        Ok(graph.receive()?.is_some())
+    }
+}
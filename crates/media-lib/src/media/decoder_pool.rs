@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ffmpeg_next::{codec, Rational};
+use media_types::{HardwareBackend, MediaFrameDecoderOptions};
+
+use super::hardware_accelerated_video_decoder::OpenDecoder;
+
+// Decoders kept at most, the least recently returned go first. Each one can
+// hold a GPU's frame pool.
+const CAPACITY: usize = 8;
+// Decoders unused for this long are closed, a burst of similar inputs
+// shouldn't keep GPU memory for good once it's over.
+const MAX_IDLE: Duration = Duration::from_secs(30);
+
+struct Pooled {
+    key: PoolKey,
+    decoder: OpenDecoder,
+    returned: Instant,
+}
+
+static POOL: Mutex<VecDeque<Pooled>> = Mutex::new(VecDeque::new());
+
+/// What has to match for a decoder opened for one input to decode another:
+/// the stream's codec parameters and the options that decide how the
+/// decoder is set up.
+#[derive(PartialEq)]
+pub struct PoolKey {
+    codec: codec::Id,
+    // Decoders keep the packet time base they were opened with.
+    time_base: Rational,
+    width: i32,
+    height: i32,
+    format: i32,
+    extradata: Vec<u8>,
+    hw_backend: HardwareBackend,
+    hw_device: Option<String>,
+    hw_device_types: Vec<String>,
+    hw_initial_pool_size: u32,
+    hw_extra_frames: u32,
+}
+
+impl PoolKey {
+    pub fn new(
+        parameters: &codec::Parameters,
+        time_base: Rational,
+        options: &MediaFrameDecoderOptions,
+    ) -> Self {
+        let raw = unsafe { &*parameters.as_ptr() };
+        let extradata = if raw.extradata.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(raw.extradata, raw.extradata_size as usize) }
+                .to_vec()
+        };
+        PoolKey {
+            codec: parameters.id(),
+            time_base,
+            width: raw.width,
+            height: raw.height,
+            format: raw.format,
+            extradata,
+            hw_backend: options.hw_backend,
+            hw_device: options.hw_device.as_ref().map(|device| device.to_string()),
+            hw_device_types: options
+                .hw_device_types
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            hw_initial_pool_size: options.hw_initial_pool_size,
+            hw_extra_frames: options.hw_extra_frames,
+        }
+    }
+}
+
+/// Closes the decoders that have been idle for `MAX_IDLE`, the pool is
+/// oldest first.
+fn expire(pool: &mut VecDeque<Pooled>, now: Instant) {
+    while pool
+        .front()
+        .is_some_and(|pooled| now.duration_since(pooled.returned) >= MAX_IDLE)
+    {
+        pool.pop_front();
+    }
+}
+
+/// A pooled decoder for inputs like `key`, taken out of the pool.
+pub fn take(key: &PoolKey) -> Option<OpenDecoder> {
+    let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+    expire(&mut pool, Instant::now());
+    let index = pool.iter().rposition(|pooled| pooled.key == *key)?;
+    pool.remove(index).map(|pooled| pooled.decoder)
+}
+
+/// Keeps a flushed decoder for the next input like `key`.
+pub fn put(key: PoolKey, decoder: OpenDecoder) {
+    let mut pool = POOL.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    expire(&mut pool, now);
+    if pool.len() == CAPACITY {
+        pool.pop_front();
+    }
+    pool.push_back(Pooled {
+        key,
+        decoder,
+        returned: now,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keys_decoders_by_time_base() {
+        let parameters = codec::Parameters::new();
+        let options = MediaFrameDecoderOptions::default();
+        let key = PoolKey::new(&parameters, Rational(1, 90000), &options);
+        assert!(key == PoolKey::new(&parameters, Rational(1, 90000), &options));
+        assert!(key != PoolKey::new(&parameters, Rational(1, 1000), &options));
+    }
+}
@@ -0,0 +1,106 @@
+use ffmpeg_next::util::frame::video::Video;
+use media_types::MediaLibError;
+
+use super::filter_graph::FilterGraph;
+
+/// Deinterlacing filters, best first. bwdif is in every recent FFmpeg,
+/// yadif in older ones too. Both output one frame per frame.
+const FILTERS: &[&str] = &[
+    "bwdif=mode=send_frame:parity=auto:deint=all",
+    "yadif=mode=send_frame:parity=auto:deint=all",
+];
+
+/// Filters deinterlacing each frame from its own lines alone, for frames
+/// too far apart to compare. estdif follows edges, builds without it
+/// double the top field's lines.
+const SPATIAL_FILTERS: &[&str] = &[
+    "estdif=mode=frame:parity=auto:deint=all",
+    "field=type=top,scale=w=iw:h=ih*2",
+];
+
+/// Deinterlaces frames flagged as interlaced so they don't come out with
+/// combing. Progressive frames pass through untouched.
+///
+/// The filters look at the frames either side of the one they work on, and
+/// only return a frame once the next one is in. Each frame is fed in twice
+/// so it comes straight back out, deinterlaced against the frame before it
+/// and itself.
+pub struct Deinterlacer {
+    filters: &'static [&'static str],
+    // Built for the first frame, and again if frames change format or size.
+    graph: Option<FilterGraph>,
+    // Set once none of the filters work for the input, frames are passed
+    // through as they are from then on.
+    failed: bool,
+    frames_fed: i64,
+}
+
+impl Deinterlacer {
+    /// With `spatial_only` frames aren't compared with the one before,
+    /// e.g. when they're key frames seconds apart.
+    pub fn new(spatial_only: bool) -> Self {
+        Deinterlacer {
+            filters: if spatial_only {
+                SPATIAL_FILTERS
+            } else {
+                FILTERS
+            },
+            graph: None,
+            failed: false,
+            frames_fed: 0,
+        }
+    }
+
+    fn graph_for(&mut self, frame: &Video) -> Result<&mut FilterGraph, MediaLibError> {
+        match &mut self.graph {
+            Some(graph) if graph.accepts(frame) => Ok(graph),
+            slot => {
+                let mut error = MediaLibError::FFmpegError("No deinterlacing filter".into());
+                for spec in self.filters {
+                    match FilterGraph::new(frame, spec) {
+                        Ok(graph) => return Ok(slot.insert(graph)),
+                        Err(e) => error = e,
+                    }
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn deinterlace(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let wanted_pts = self.frames_fed;
+        let graph = self.graph_for(frame)?;
+        graph.add(frame, Some(wanted_pts))?;
+        graph.add(frame, Some(wanted_pts + 1))?;
+        self.frames_fed += 2;
+
+        // The copy of the frame before comes out first, it's of no use.
+        loop {
+            let mut deinterlaced = graph
+                .receive()?
+                .ok_or_else(|| MediaLibError::FFmpegError("Deinterlacing gave no frame".into()))?;
+            if deinterlaced.pts() == Some(wanted_pts) {
+                deinterlaced.set_pts(frame.pts());
+                return Ok(deinterlaced);
+            }
+        }
+    }
+
+    /// `frame` deinterlaced, none when it isn't interlaced or can't be
+    /// deinterlaced. Failing is logged once rather than failing the decode,
+    /// a frame with combing is still worth having.
+    pub fn apply(&mut self, frame: &Video) -> Option<Video> {
+        if self.failed || !frame.is_interlaced() {
+            return None;
+        }
+        match self.deinterlace(frame) {
+            Ok(deinterlaced) => Some(deinterlaced),
+            Err(e) => {
+                log::warn!("Failed to deinterlace, leaving frames as they are: {}", e);
+                self.failed = true;
+                self.graph = None;
+                None
+            }
+        }
+    }
+}
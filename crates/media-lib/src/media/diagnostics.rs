@@ -0,0 +1,178 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use media_types::MediaLibError;
+
+use super::io_error;
+use super::packets::PacketIterator;
+use super::probe::probe_media;
+
+// Packets listed in packets.txt, enough to see how the stream starts.
+const PACKETS: usize = 50;
+
+/// What went wrong decoding an input, gathered by the decoder that failed.
+pub struct FailureReport<'a> {
+    /// None for inputs read from memory, which can't be opened again to
+    /// probe them.
+    pub input_path: Option<&'a Path>,
+    pub force_format: Option<&'a str>,
+    pub error: &'a MediaLibError,
+    pub hardware_device: Option<String>,
+    pub filter_graph: Option<String>,
+}
+
+fn probe_text(input_path: &Path) -> String {
+    match probe_media(input_path) {
+        Ok(info) => format!("{:#?}\n", info),
+        Err(e) => format!("Probe failed: {:?}\n", e),
+    }
+}
+
+fn packets_text(input_path: &Path, force_format: Option<&str>) -> String {
+    let mut packets = match PacketIterator::new(input_path, force_format) {
+        Ok(packets) => packets,
+        Err(e) => return format!("Failed to open: {:?}\n", e),
+    };
+    let mut text = String::from("stream\tpts\tdts\tduration\tsize\tposition\tkey\tcorrupt\n");
+    for _ in 0..PACKETS {
+        match packets.get() {
+            Some(Ok(packet)) => {
+                let _ = writeln!(
+                    text,
+                    "{}\t{:?}\t{:?}\t{:?}\t{}\t{:?}\t{}\t{}",
+                    packet.stream_index,
+                    packet.pts,
+                    packet.dts,
+                    packet.duration,
+                    packet.data.len(),
+                    packet.byte_offset,
+                    packet.is_key,
+                    packet.is_corrupt
+                );
+            }
+            Some(Err(e)) => {
+                let _ = writeln!(text, "Read failed: {:?}", e);
+                break;
+            }
+            None => break,
+        }
+    }
+    text
+}
+
+/// How busy each NVIDIA GPU was, since a failing hardware decode is often
+/// one too many for the GPU it ran on.
+#[cfg(feature = "nvml")]
+fn utilization_text() -> String {
+    let devices = match super::decoder_utilization() {
+        Ok(devices) => devices,
+        Err(e) => return format!("Decoder utilization: {:?}\n", e),
+    };
+    let mut text = String::new();
+    for device in devices {
+        let _ = writeln!(
+            text,
+            "GPU {} ({}): decoder {}% busy, {} of {} MiB free",
+            device.device_index,
+            device.name,
+            device.decoder_percent,
+            device.memory_free_bytes >> 20,
+            device.memory_total_bytes >> 20
+        );
+    }
+    text
+}
+
+/// Writes a bundle describing `report` to a new directory in `directory`,
+/// named after the input and the time, and returns its path. Files that
+/// can't be produced say why instead, so the bundle is always complete.
+pub fn write_bundle(directory: &Path, report: &FailureReport) -> Result<PathBuf, MediaLibError> {
+    let stem = report
+        .input_path
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "reader".to_string());
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let bundle = directory.join(format!("{}-{}", stem, millis));
+    fs::create_dir_all(&bundle).map_err(io_error)?;
+
+    let mut error = format!("{:?}\n", report.error);
+    if let Some(input_path) = report.input_path {
+        let _ = writeln!(error, "Input: {}", input_path.display());
+    }
+    let _ = writeln!(
+        error,
+        "Hardware device: {}",
+        report.hardware_device.as_deref().unwrap_or("none")
+    );
+    #[cfg(feature = "nvml")]
+    if report.hardware_device.as_deref() == Some("cuda") {
+        error.push_str(&utilization_text());
+    }
+    fs::write(bundle.join("error.txt"), error).map_err(io_error)?;
+
+    let (probe, packets) = match report.input_path {
+        Some(input_path) => (
+            probe_text(input_path),
+            packets_text(input_path, report.force_format),
+        ),
+        None => {
+            let unavailable = "Not available for inputs read from memory\n".to_string();
+            (unavailable.clone(), unavailable)
+        }
+    };
+    fs::write(bundle.join("probe.txt"), probe).map_err(io_error)?;
+    fs::write(bundle.join("packets.txt"), packets).map_err(io_error)?;
+
+    let filter_graph = report
+        .filter_graph
+        .clone()
+        .unwrap_or_else(|| "No filter graph was in use\n".to_string());
+    fs::write(bundle.join("filter_graph.txt"), filter_graph).map_err(io_error)?;
+
+    let mut log = crate::logging::tail().join("\n");
+    log.push('\n');
+    fs::write(bundle.join("log.txt"), log).map_err(io_error)?;
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_writes_every_file_for_inputs_read_from_memory() {
+        let directory = std::env::temp_dir().join(format!("diagnostics-{}", std::process::id()));
+        let error = MediaLibError::FFmpegError("Invalid data found".into());
+        let report = FailureReport {
+            input_path: None,
+            force_format: None,
+            error: &error,
+            hardware_device: Some("cuda".to_string()),
+            filter_graph: None,
+        };
+        let bundle = write_bundle(&directory, &report).unwrap();
+
+        assert!(bundle
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("reader-"));
+        let error = fs::read_to_string(bundle.join("error.txt")).unwrap();
+        assert!(error.contains("Invalid data found"));
+        assert!(error.contains("Hardware device: cuda"));
+        for file in ["probe.txt", "packets.txt"] {
+            let text = fs::read_to_string(bundle.join(file)).unwrap();
+            assert_eq!(text, "Not available for inputs read from memory\n");
+        }
+        let filter_graph = fs::read_to_string(bundle.join("filter_graph.txt")).unwrap();
+        assert_eq!(filter_graph, "No filter graph was in use\n");
+        assert!(bundle.join("log.txt").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
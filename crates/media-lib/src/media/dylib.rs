@@ -0,0 +1,27 @@
+use libloading::Library;
+use media_types::MediaLibError;
+
+/// Loads the first library from `candidates` that can be found on this
+/// machine. Optional native backends are loaded this way rather than linked so
+/// media-lib still loads where they aren't installed.
+pub fn load_first(candidates: &[&str]) -> Result<Library, MediaLibError> {
+    let mut last_error = String::new();
+    for name in candidates {
+        match unsafe { Library::new(name) } {
+            Ok(library) => return Ok(library),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    Err(MediaLibError::UnknownError(
+        format!("Failed to load any of {:?}: {}", candidates, last_error).into(),
+    ))
+}
+
+/// Copies a function pointer out of `library`. The caller must keep the
+/// library alive for as long as the pointer is used.
+pub unsafe fn get_symbol<T: Copy>(library: &Library, name: &[u8]) -> Result<T, MediaLibError> {
+    library
+        .get::<T>(name)
+        .map(|symbol| *symbol)
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use super::frame_export::encode_image;
+use super::jpeg::JpegEncoder;
+use crate::MediaLibError;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{FrameExportOptions, ImageFormat, MediaFrameDecoderOptions};
+
+type Encoded = Result<Vec<u8>, MediaLibError>;
+type Job = (Video, SyncSender<Encoded>);
+
+enum Pending {
+    Ready(Encoded),
+    Waiting(Receiver<Encoded>),
+}
+
+/// Encodes a frame in the format frames are returned in. JPEG keeps its own
+/// encoder for the GPU backend, the lossless formats go through the same
+/// path as exported frames.
+pub enum FrameEncoder {
+    Jpeg(JpegEncoder),
+    Image(FrameExportOptions),
+}
+
+impl FrameEncoder {
+    pub fn new(options: &MediaFrameDecoderOptions) -> Self {
+        match options.image_format {
+            ImageFormat::Jpeg => FrameEncoder::Jpeg(JpegEncoder::with_options(&options.jpeg)),
+            format => FrameEncoder::Image(FrameExportOptions {
+                format,
+                high_bit_depth: false,
+                lut_path: None.into(),
+                jpeg: options.jpeg,
+            }),
+        }
+    }
+
+    pub fn encode(&mut self, frame: &Video) -> Encoded {
+        match self {
+            FrameEncoder::Jpeg(encoder) => encoder.encode(frame),
+            FrameEncoder::Image(options) => encode_image(frame, options),
+        }
+    }
+}
+
+/// Encodes frames to the options' `image_format` on a small pool of worker
+/// threads so decoding the next frame overlaps with encoding the previous
+/// ones. Results are handed back in the order the frames were submitted.
+///
+/// With zero threads frames are encoded on the calling thread as they are
+/// submitted.
+///
+/// The pts of each frame is kept with its result, see `last_pts`.
+pub struct EncodePool {
+    jobs: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    inline_encoder: Option<FrameEncoder>,
+    pending: VecDeque<(Pending, Option<i64>)>,
+    capacity: usize,
+    last_pts: Option<i64>,
+}
+
+impl EncodePool {
+    pub fn new(options: &MediaFrameDecoderOptions) -> Self {
+        let threads = options.encode_threads as usize;
+        if threads == 0 {
+            return EncodePool {
+                jobs: None,
+                workers: Vec::new(),
+                inline_encoder: Some(FrameEncoder::new(options)),
+                pending: VecDeque::new(),
+                capacity: 1,
+                last_pts: None,
+            };
+        }
+
+        // Enough in flight to keep every worker busy while the caller decodes.
+        let capacity = threads * 2;
+        let (jobs, job_receiver) = mpsc::sync_channel::<Job>(capacity);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads)
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let options = options.clone();
+                std::thread::spawn(move || {
+                    // Each worker owns its encoder, the GPU backend in particular
+                    // can't be shared across threads.
+                    let mut encoder = FrameEncoder::new(&options);
+                    loop {
+                        let job = match job_receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
+                        };
+                        let Ok((frame, result_sender)) = job else {
+                            break;
+                        };
+                        // The consumer may have gone away, nothing to do then.
+                        let _ = result_sender.send(encoder.encode(&frame));
+                    }
+                })
+            })
+            .collect();
+
+        EncodePool {
+            jobs: Some(jobs),
+            workers,
+            inline_encoder: None,
+            pending: VecDeque::new(),
+            capacity,
+            last_pts: None,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.capacity
+    }
+
+    pub fn submit(&mut self, frame: Video) {
+        let pts = frame.pts();
+        if let Some(encoder) = &mut self.inline_encoder {
+            self.pending
+                .push_back((Pending::Ready(encoder.encode(&frame)), pts));
+            return;
+        }
+
+        let (result_sender, result_receiver) = mpsc::sync_channel(1);
+        let sent = self
+            .jobs
+            .as_ref()
+            .is_some_and(|jobs| jobs.send((frame, result_sender)).is_ok());
+
+        if sent {
+            self.pending
+                .push_back((Pending::Waiting(result_receiver), pts));
+        } else {
+            self.push_result(Err(MediaLibError::UnknownError(
+                "Encode workers have stopped".into(),
+            )));
+        }
+    }
+
+    /// Queues a result that doesn't need encoding, e.g. a decode error, so it
+    /// is returned in order with the frames around it.
+    pub fn push_result(&mut self, result: Encoded) {
+        self.pending.push_back((Pending::Ready(result), None));
+    }
+
+    /// Forgets every frame submitted so far, e.g. after a seek. Workers still
+    /// encoding one of them finish and the result is thrown away.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Blocks until the oldest submitted frame has been encoded.
+    pub fn next(&mut self) -> Option<Encoded> {
+        let (pending, pts) = self.pending.pop_front()?;
+        let result = match pending {
+            Pending::Ready(result) => result,
+            Pending::Waiting(receiver) => receiver.recv().unwrap_or_else(|_| {
+                Err(MediaLibError::UnknownError(
+                    "Encode worker exited unexpectedly".into(),
+                ))
+            }),
+        };
+        if result.is_ok() {
+            self.last_pts = pts;
+        }
+        Some(result)
+    }
+
+    /// The pts the frame `next` returned last had when it was submitted,
+    /// none before the first frame or when it had none.
+    pub fn last_pts(&self) -> Option<i64> {
+        self.last_pts
+    }
+}
+
+impl Drop for EncodePool {
+    fn drop(&mut self) {
+        // Closing the job channel lets the workers run off the end of their
+        // loops.
+        self.jobs.take();
+        self.pending.clear();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use ffmpeg_next::codec::{self, encoder};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::{Codec, Rational};
+use media_types::{
+    FrameEncoderOptions, MediaLibError, ProcessingReport, RawPixelFormat, RawVideoFrame, Size,
+    VideoCodec,
+};
+
+use super::video_writer::{VideoWriter, WriterSettings};
+
+// Encoders tried in order when hardware is allowed. They all take frames in
+// system memory, opening one fails quickly when its GPU isn't there.
+const H264_HARDWARE_ENCODERS: &[&str] =
+    &["h264_nvenc", "h264_videotoolbox", "h264_qsv", "h264_amf"];
+const HEVC_HARDWARE_ENCODERS: &[&str] =
+    &["hevc_nvenc", "hevc_videotoolbox", "hevc_qsv", "hevc_amf"];
+
+fn raw_format(format: RawPixelFormat) -> Pixel {
+    match format {
+        RawPixelFormat::Rgb24 => Pixel::RGB24,
+        RawPixelFormat::Rgba => Pixel::RGBA,
+        RawPixelFormat::Yuv420p => Pixel::YUV420P,
+    }
+}
+
+/// Encoders to try for `codec`, best first: the GPU ones when allowed, then
+/// x264/x265, then whatever FFmpeg has for the codec.
+fn candidates(codec: VideoCodec, hardware: bool) -> Vec<Codec> {
+    let (hardware_names, software_name, id) = match codec {
+        VideoCodec::H264 => (H264_HARDWARE_ENCODERS, "libx264", codec::Id::H264),
+        VideoCodec::Hevc => (HEVC_HARDWARE_ENCODERS, "libx265", codec::Id::HEVC),
+    };
+    let hardware_names: &[&str] = if hardware { hardware_names } else { &[] };
+    let mut candidates: Vec<Codec> = hardware_names
+        .iter()
+        .chain(&[software_name])
+        .filter_map(|name| encoder::find_by_name(name))
+        .collect();
+    if let Some(fallback) = encoder::find(id) {
+        if !candidates
+            .iter()
+            .any(|codec| codec.name() == fallback.name())
+        {
+            candidates.push(fallback);
+        }
+    }
+    candidates
+}
+
+/// Copies a tightly packed raw frame into an FFmpeg frame.
+pub fn raw_to_video(frame: &RawVideoFrame) -> Result<Video, MediaLibError> {
+    let expected = frame.format.frame_bytes(frame.width, frame.height);
+    if frame.width == 0 || frame.height == 0 || frame.data.len() != expected {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Frame of {}x{} {:?} should be {} bytes, got {}",
+                frame.width,
+                frame.height,
+                frame.format,
+                expected,
+                frame.data.len()
+            )
+            .into(),
+        ));
+    }
+
+    let mut video = Video::new(raw_format(frame.format), frame.width, frame.height);
+    let width = frame.width as usize;
+    let height = frame.height as usize;
+    let planes: Vec<(usize, usize)> = match frame.format {
+        RawPixelFormat::Rgb24 => vec![(width * 3, height)],
+        RawPixelFormat::Rgba => vec![(width * 4, height)],
+        RawPixelFormat::Yuv420p => {
+            let chroma = (width.div_ceil(2), height.div_ceil(2));
+            vec![(width, height), chroma, chroma]
+        }
+    };
+    let mut remaining: &[u8] = &frame.data;
+    for (plane, (row_bytes, rows)) in planes.into_iter().enumerate() {
+        let stride = video.stride(plane);
+        let data = video.data_mut(plane);
+        for (y, row) in remaining[..row_bytes * rows]
+            .chunks_exact(row_bytes)
+            .enumerate()
+        {
+            data[y * stride..y * stride + row_bytes].copy_from_slice(row);
+        }
+        remaining = &remaining[row_bytes * rows..];
+    }
+    Ok(video)
+}
+
+fn already_finished() -> MediaLibError {
+    MediaLibError::UnknownError("Encoder has already finished".into())
+}
+
+/// Encodes raw frames to H.264 or HEVC, on the GPU when an encoder for it
+/// opens and with x264/x265 otherwise, into the container the output path's
+/// extension names. The muxing is `VideoWriter`'s.
+pub struct HardwareAcceleratedVideoEncoder {
+    // Taken by `finish`.
+    writer: Option<VideoWriter>,
+    encoder_name: String,
+    pub hardware_accelerated: bool,
+    output_path: PathBuf,
+    started: Instant,
+}
+
+impl HardwareAcceleratedVideoEncoder {
+    pub fn new(output_path: &Path, options: &FrameEncoderOptions) -> Result<Self, MediaLibError> {
+        if options.frame_rate_numerator == 0 || options.frame_rate_denominator == 0 {
+            return Err(MediaLibError::UnknownError(
+                "Frame rate must be above zero".into(),
+            ));
+        }
+        let size = Size::new(options.width, options.height);
+        // 4:2:0 needs even dimensions, which the writer rounds down to.
+        if size.width < 2 || size.height < 2 {
+            return Err(MediaLibError::UnknownError(
+                format!("Invalid output size {}x{}", options.width, options.height).into(),
+            ));
+        }
+        let frame_rate = Rational::new(
+            options.frame_rate_numerator as i32,
+            options.frame_rate_denominator as i32,
+        );
+
+        let settings = WriterSettings {
+            encoders: candidates(options.codec, options.hardware),
+            bit_rate: options.bit_rate,
+            output: options.output.clone(),
+            ..WriterSettings::default()
+        };
+        if settings.encoders.is_empty() {
+            return Err(MediaLibError::FFmpegError(
+                format!("No encoder available for {:?}", options.codec).into(),
+            ));
+        }
+        let writer = VideoWriter::with_settings(output_path, size, frame_rate, settings)?;
+        let encoder_name = writer.encoder_name().to_string();
+        let hardware_accelerated = H264_HARDWARE_ENCODERS
+            .iter()
+            .chain(HEVC_HARDWARE_ENCODERS)
+            .any(|name| *name == encoder_name);
+        log::info!("Encoding with {}", encoder_name);
+
+        Ok(HardwareAcceleratedVideoEncoder {
+            writer: Some(writer),
+            encoder_name,
+            hardware_accelerated,
+            output_path: output_path.to_path_buf(),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn encoder_name(&self) -> &str {
+        &self.encoder_name
+    }
+
+    pub fn encode(&mut self, frame: &RawVideoFrame) -> Result<(), MediaLibError> {
+        let writer = self.writer.as_mut().ok_or_else(already_finished)?;
+        let video = raw_to_video(frame)?;
+        let mut converted = writer.convert(&video)?;
+        writer.write(&mut converted)
+    }
+
+    /// Flushes the encoder and finalises the file.
+    pub fn finish(&mut self) -> Result<ProcessingReport, MediaLibError> {
+        let writer = self.writer.take().ok_or_else(already_finished)?;
+        let frames_written = writer.finish()?;
+
+        let mut report = ProcessingReport {
+            wall_time_ms: self.started.elapsed().as_millis() as u64,
+            frames_decoded: 0,
+            frames_returned: frames_written,
+            frames_dropped: 0,
+            hardware_device: None.into(),
+            warnings: stabby::vec::Vec::new(),
+            outputs: stabby::vec::Vec::new(),
+        };
+        let bytes = std::fs::metadata(&self.output_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        report.add_output(&self.output_path.to_string_lossy(), bytes);
+        Ok(report)
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::path::Path;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{JpegOptions, MediaFrameDecoderOptions, MediaLibError, Size};
+
+use super::canvas::TileSheet;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::sample_points;
+
+/// `count` evenly spaced frames of an input side by side, filled in as the
+/// input is decoded from start to end. Each cell takes the first frame at
+/// or after its sample point.
+pub struct Filmstrip {
+    strip: TileSheet,
+    targets: VecDeque<i64>,
+    cell_index: u32,
+}
+
+impl Filmstrip {
+    pub fn new(
+        input_size: Size,
+        duration_us: i64,
+        count: u32,
+        cell_width: u32,
+    ) -> Result<Self, MediaLibError> {
+        if count == 0 || cell_width == 0 {
+            return Err(MediaLibError::UnknownError(
+                "A filmstrip needs at least one cell with a non-zero width".into(),
+            ));
+        }
+        Ok(Filmstrip {
+            strip: TileSheet::new(input_size.scale_to_width(cell_width), count, count),
+            targets: sample_points(0, duration_us, count),
+            cell_index: 0,
+        })
+    }
+
+    fn is_due(&self, elapsed_us: i64) -> bool {
+        self.targets
+            .front()
+            .is_some_and(|target| elapsed_us >= *target)
+    }
+
+    /// Takes `frame`, shown `elapsed_us` into the input, for any cells it
+    /// covers.
+    pub fn add(&mut self, frame: &Video, elapsed_us: i64) -> Result<(), MediaLibError> {
+        if !self.is_due(elapsed_us) {
+            return Ok(());
+        }
+        let cell = self.strip.scale(frame)?;
+
+        // Short inputs can have fewer frames than cells, a frame fills every
+        // sample point it covers.
+        while self.is_due(elapsed_us) {
+            self.targets.pop_front();
+            self.strip.place(&cell, self.cell_index);
+            self.cell_index += 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub fn size(&self) -> Size {
+        self.strip.size()
+    }
+
+    /// The strip as a JPEG.
+    pub fn encode(&self, jpeg: &JpegOptions) -> Result<Vec<u8>, MediaLibError> {
+        self.strip.encode(jpeg)
+    }
+}
+
+/// A single JPEG of `count` evenly spaced frames side by side, each
+/// `cell_width` pixels wide. The input is decoded once from start to end,
+/// see `Filmstrip`.
+pub fn filmstrip(input_path: &Path, count: u32, cell_width: u32) -> Result<Vec<u8>, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let duration_us = video_decoder.duration_us().ok_or_else(|| {
+        MediaLibError::FFmpegError("Input has no known duration to sample across".into())
+    })?;
+    let mut strip = Filmstrip::new(video_decoder.size(), duration_us, count, cell_width)?;
+
+    let start_time_us = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        let decoded = decoded?;
+        let Some(time_us) = video_decoder.frame_time_us(&decoded) else {
+            continue;
+        };
+        strip.add(&decoded, time_us - start_time_us)?;
+        if strip.is_full() {
+            break;
+        }
+    }
+
+    strip.encode(&JpegOptions::default())
+}
@@ -0,0 +1,197 @@
+use ffmpeg_next::ffi::{
+    av_buffersrc_parameters_alloc, av_buffersrc_parameters_set, av_frame_clone, av_free,
+};
+use ffmpeg_next::filter;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Rational;
+use media_types::{MediaLibError, Size};
+
+use super::ffmpeg_error;
+
+/// A graph running `spec` from a `source` filter set up with `args` to a
+/// `sink` filter, e.g. `abuffer` and `abuffersink` for audio.
+pub fn build_graph(
+    source: &str,
+    args: &str,
+    sink: &str,
+    spec: &str,
+) -> Result<filter::Graph, MediaLibError> {
+    build_graph_with(source, args, sink, spec, |_| Ok(()))
+}
+
+/// A graph of the source filters in `spec`, e.g. `testsrc2`, feeding a
+/// `sink` filter named "out".
+pub fn source_graph(spec: &str, sink: &str) -> Result<filter::Graph, MediaLibError> {
+    let buffer_sink = filter::find(sink).ok_or_else(|| {
+        MediaLibError::FFmpegError(format!("{} filter not available", sink).into())
+    })?;
+    let mut graph = filter::Graph::new();
+    graph.add(&buffer_sink, "out", "").map_err(ffmpeg_error)?;
+    graph
+        .input("out", 0)
+        .and_then(|parser| parser.parse(spec))
+        .map_err(ffmpeg_error)?;
+    graph.validate().map_err(ffmpeg_error)?;
+    Ok(graph)
+}
+
+/// `build_graph`, with `set_up` given the source before `spec` is linked
+/// to it.
+fn build_graph_with(
+    source: &str,
+    args: &str,
+    sink: &str,
+    spec: &str,
+    set_up: impl FnOnce(&mut filter::Context) -> Result<(), MediaLibError>,
+) -> Result<filter::Graph, MediaLibError> {
+    let find = |name: &str| {
+        filter::find(name).ok_or_else(|| {
+            MediaLibError::FFmpegError(format!("{} filter not available", name).into())
+        })
+    };
+    let mut graph = filter::Graph::new();
+    graph.add(&find(sink)?, "out", "").map_err(ffmpeg_error)?;
+    let mut source = graph
+        .add(&find(source)?, "in", args)
+        .map_err(ffmpeg_error)?;
+    set_up(&mut source)?;
+    graph
+        .output("in", 0)
+        .and_then(|parser| parser.input("out", 0))
+        .and_then(|parser| parser.parse(spec))
+        .map_err(ffmpeg_error)?;
+    graph.validate().map_err(ffmpeg_error)?;
+    Ok(graph)
+}
+
+/// An FFmpeg filter graph running `spec` between a buffer source and a
+/// buffer sink, for video frames of one format and size. Hardware frames
+/// are filtered where they are, nothing is downloaded.
+pub struct FilterGraph {
+    graph: filter::Graph,
+    format: Pixel,
+    size: Size,
+    // Numbers the frames `run` adds.
+    frames_run: i64,
+}
+
+impl FilterGraph {
+    /// A graph for frames like `frame` that come out one for one, see `run`.
+    pub fn new(frame: &Video, spec: &str) -> Result<Self, MediaLibError> {
+        Self::with_time_base(frame, Rational(1, 1), spec)
+    }
+
+    /// A graph for frames like `frame` with timestamps in `time_base`, for
+    /// filters that go by time.
+    pub fn with_time_base(
+        frame: &Video,
+        time_base: Rational,
+        spec: &str,
+    ) -> Result<Self, MediaLibError> {
+        let args = format!(
+            "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect=1/1",
+            frame.width(),
+            frame.height(),
+            unsafe { (*frame.as_ptr()).format },
+            time_base
+        );
+        // Hardware frames need the source to know their device, software
+        // frames leave it unset.
+        let graph = build_graph_with("buffer", &args, "buffersink", spec, |source| unsafe {
+            let parameters = av_buffersrc_parameters_alloc();
+            if parameters.is_null() {
+                return Err(MediaLibError::FFmpegError(
+                    "Failed to allocate filter parameters".into(),
+                ));
+            }
+            (*parameters).hw_frames_ctx = (*frame.as_ptr()).hw_frames_ctx;
+            let result = av_buffersrc_parameters_set(source.as_mut_ptr(), parameters);
+            av_free(parameters as *mut _);
+            if result < 0 {
+                return Err(ffmpeg_error(ffmpeg_next::Error::from(result)));
+            }
+            Ok(())
+        })?;
+        Ok(FilterGraph {
+            graph,
+            format: frame.format(),
+            size: Size::new(frame.width(), frame.height()),
+            frames_run: 0,
+        })
+    }
+
+    /// Whether `frame` can go into the graph, it has to be rebuilt for
+    /// frames of another format or size.
+    pub fn accepts(&self, frame: &Video) -> bool {
+        frame.format() == self.format && Size::new(frame.width(), frame.height()) == self.size
+    }
+
+    /// The graph as FFmpeg prints it, for diagnostics.
+    pub fn dump(&self) -> String {
+        self.graph.dump()
+    }
+
+    /// Adds `frame` with timestamp `pts`. It's referenced rather than
+    /// copied, hardware frames can't be copied.
+    pub fn add(&mut self, frame: &Video, pts: Option<i64>) -> Result<(), MediaLibError> {
+        let input = unsafe { av_frame_clone(frame.as_ptr()) };
+        if input.is_null() {
+            return Err(MediaLibError::FFmpegError(
+                "Failed to reference frame".into(),
+            ));
+        }
+        let mut input = unsafe { Video::wrap(input) };
+        input.set_pts(pts);
+        self.graph
+            .get("in")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter input missing".into()))?
+            .source()
+            .add(&input)
+            .map_err(ffmpeg_error)
+    }
+
+    /// Ends the input, so the frames filters held back come out of
+    /// `receive`.
+    pub fn flush(&mut self) -> Result<(), MediaLibError> {
+        self.graph
+            .get("in")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter input missing".into()))?
+            .source()
+            .flush()
+            .map_err(ffmpeg_error)
+    }
+
+    /// The next filtered frame, none until more frames are added, or at all
+    /// once everything has come out after `flush`.
+    pub fn receive(&mut self) -> Result<Option<Video>, MediaLibError> {
+        let mut filtered = Video::empty();
+        let received = self
+            .graph
+            .get("out")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter output missing".into()))?
+            .sink()
+            .frame(&mut filtered);
+        match received {
+            Ok(()) => Ok(Some(filtered)),
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) | Err(ffmpeg_next::Error::Eof) => {
+                Ok(None)
+            }
+            Err(e) => Err(ffmpeg_error(e)),
+        }
+    }
+
+    /// Runs `frame` through filters that give one frame for each frame in.
+    /// Frames go in numbered one after another and come out with `frame`'s
+    /// own timestamp.
+    pub fn run(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        self.add(frame, Some(self.frames_run))?;
+        self.frames_run += 1;
+        let mut filtered = self
+            .receive()?
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter gave no frame".into()))?;
+        filtered.set_pts(frame.pts());
+        Ok(filtered)
+    }
+}
@@ -0,0 +1,167 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    hamming_distance, MediaFrameDecoderOptions, MediaLibError, ShotSignature, VideoFingerprint,
+};
+
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::job::Job;
+
+// The hash is taken from the low frequencies of a 32x32 grayscale thumbnail.
+const HASH_INPUT_SIZE: usize = 32;
+const HASH_SIZE: usize = 8;
+
+// Consecutive keyframes further apart than this (out of 64 bits) start a new
+// shot. Encoders usually place a keyframe on scene cuts so keyframes are a
+// cheap stand-in for shot boundaries.
+const SHOT_CHANGE_DISTANCE: u32 = 20;
+
+fn dct_table() -> [[f64; HASH_INPUT_SIZE]; HASH_SIZE] {
+    let mut table = [[0.0; HASH_INPUT_SIZE]; HASH_SIZE];
+    for (u, row) in table.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = ((2 * x + 1) as f64 * u as f64 * PI / (2 * HASH_INPUT_SIZE) as f64).cos();
+        }
+    }
+    table
+}
+
+/// DCT based perceptual hash of a 32x32 grayscale image: one bit per low
+/// frequency coefficient, set when it is above the median.
+fn perceptual_hash(pixels: &[[f64; HASH_INPUT_SIZE]; HASH_INPUT_SIZE]) -> u64 {
+    let table = dct_table();
+
+    // The DCT is separable, transform the rows first and then the columns,
+    // only keeping the low frequencies we hash.
+    let mut rows = [[0.0; HASH_SIZE]; HASH_INPUT_SIZE];
+    for (transformed, row) in rows.iter_mut().zip(pixels) {
+        for (value, basis) in transformed.iter_mut().zip(&table) {
+            *value = row.iter().zip(basis).map(|(p, c)| p * c).sum();
+        }
+    }
+
+    let mut coefficients = [0.0; HASH_SIZE * HASH_SIZE];
+    for (output, basis) in coefficients.chunks_mut(HASH_SIZE).zip(&table) {
+        for (u, value) in output.iter_mut().enumerate() {
+            *value = rows.iter().zip(basis).map(|(row, c)| row[u] * c).sum();
+        }
+    }
+
+    // The DC term is just the average brightness, leave it out of the median.
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, coefficient)| **coefficient > median)
+        .fold(0u64, |hash, (bit, _)| hash | (1 << bit))
+}
+
+struct FrameHasher {
+    scaler: Option<Context>,
+}
+
+impl FrameHasher {
+    fn hash(&mut self, frame: &Video) -> Result<u64, MediaLibError> {
+        let scaler = match &mut self.scaler {
+            Some(scaler) => scaler,
+            None => self.scaler.insert(
+                Context::get(
+                    frame.format(),
+                    frame.width(),
+                    frame.height(),
+                    Pixel::GRAY8,
+                    HASH_INPUT_SIZE as u32,
+                    HASH_INPUT_SIZE as u32,
+                    Flags::AREA,
+                )
+                .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?,
+            ),
+        };
+
+        let mut thumbnail = Video::empty();
+        scaler
+            .run(frame, &mut thumbnail)
+            .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+
+        let stride = thumbnail.stride(0);
+        let data = thumbnail.data(0);
+        let mut pixels = [[0.0; HASH_INPUT_SIZE]; HASH_INPUT_SIZE];
+        for (y, row) in pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = data[y * stride + x] as f64;
+            }
+        }
+        Ok(perceptual_hash(&pixels))
+    }
+}
+
+/// Builds a shot level fingerprint of the input from its keyframes.
+pub fn fingerprint_video(
+    input_path: &Path,
+    job: &mut Job,
+) -> Result<VideoFingerprint, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    let mut hasher = FrameHasher { scaler: None };
+
+    let mut shots: Vec<ShotSignature> = Vec::new();
+    let mut first_timestamp_us = None;
+    let mut last_timestamp_us = 0;
+    let mut previous_hash = None;
+
+    while let Some(frame) = video_decoder.get_frame() {
+        job.check()?;
+        let frame = frame?;
+        let Some(timestamp_us) = video_decoder.frame_time_us(&frame) else {
+            continue;
+        };
+        let start_us = timestamp_us - *first_timestamp_us.get_or_insert(timestamp_us);
+        last_timestamp_us = start_us;
+        job.progress(start_us, video_decoder.duration_us());
+
+        let hash = hasher.hash(&frame)?;
+        let same_shot = previous_hash
+            .is_some_and(|previous| hamming_distance(previous, hash) <= SHOT_CHANGE_DISTANCE);
+        if !same_shot {
+            shots.push(ShotSignature {
+                hash,
+                start_us,
+                duration_us: 0,
+            });
+        }
+        previous_hash = Some(hash);
+    }
+
+    let duration_us = video_decoder
+        .duration_us()
+        .unwrap_or(last_timestamp_us)
+        .max(last_timestamp_us);
+
+    let ends: Vec<i64> = shots
+        .iter()
+        .skip(1)
+        .map(|shot| shot.start_us)
+        .chain(std::iter::once(duration_us))
+        .collect();
+    for (shot, end_us) in shots.iter_mut().zip(ends) {
+        shot.duration_us = end_us - shot.start_us;
+    }
+
+    let mut stabby_shots = stabby::vec::Vec::with_capacity(shots.len());
+    for shot in shots {
+        stabby_shots.push(shot);
+    }
+
+    Ok(VideoFingerprint {
+        shots: stabby_shots,
+        duration_us,
+    })
+}
@@ -0,0 +1,232 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use image::{DynamicImage, ImageBuffer, Luma, Rgb};
+use media_types::{
+    FrameExportOptions, ImageFormat, JpegOptions, MediaFrameDecoderOptions, MediaLibError,
+    RedactionOptions, RegionDetectorBox, ScalingMode, Size,
+};
+
+use super::audio_preview::RgbImage;
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::jpeg::{pixel_format, JpegEncoder};
+use super::lut::Lut3d;
+use super::preview::encode_rgb;
+use super::redaction::Redactor;
+use super::still::PictureCorrection;
+
+/// Bits per sample of the first component, e.g. 10 for P010.
+fn bit_depth(format: Pixel) -> u32 {
+    format
+        .descriptor()
+        .map(|descriptor| unsafe { (*descriptor.as_ptr()).comp[0].depth } as u32)
+        .unwrap_or(8)
+}
+
+fn is_gray(format: Pixel) -> bool {
+    format
+        .descriptor()
+        .is_some_and(|descriptor| descriptor.nb_components() == 1)
+}
+
+/// Converts `frame` to `format` at its own size. Only the layout changes, so
+/// there's nothing to dither when the output has at least as many bits.
+fn convert(frame: &Video, format: Pixel) -> Result<Video, MediaLibError> {
+    let mut converted = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        format,
+        frame.width(),
+        frame.height(),
+        Flags::BICUBIC | Flags::ACCURATE_RND | Flags::FULL_CHR_H_INT,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut converted))
+    .map_err(ffmpeg_error)?;
+    Ok(converted)
+}
+
+/// The first plane of a packed frame without its row padding.
+fn packed_bytes(frame: &Video, bytes_per_pixel: usize) -> Vec<u8> {
+    let row_bytes = frame.width() as usize * bytes_per_pixel;
+    frame
+        .data(0)
+        .chunks(frame.stride(0))
+        .take(frame.height() as usize)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect()
+}
+
+/// Same as `packed_bytes` for little endian 16 bit samples.
+fn packed_words(frame: &Video, samples_per_pixel: usize) -> Vec<u16> {
+    packed_bytes(frame, samples_per_pixel * 2)
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+/// The frame in the pixel layout it will be written with, gray sources stay
+/// gray and 16 bit is only used when asked for and the source has the bits.
+fn to_image(frame: &Video, high_bit_depth: bool) -> Result<DynamicImage, MediaLibError> {
+    let (width, height) = (frame.width(), frame.height());
+    let gray = is_gray(frame.format());
+    let image = match (gray, high_bit_depth && bit_depth(frame.format()) > 8) {
+        (true, true) => ImageBuffer::<Luma<u16>, _>::from_raw(
+            width,
+            height,
+            packed_words(&convert(frame, Pixel::GRAY16LE)?, 1),
+        )
+        .map(DynamicImage::ImageLuma16),
+        (false, true) => ImageBuffer::<Rgb<u16>, _>::from_raw(
+            width,
+            height,
+            packed_words(&convert(frame, Pixel::RGB48LE)?, 3),
+        )
+        .map(DynamicImage::ImageRgb16),
+        (true, false) => ImageBuffer::<Luma<u8>, _>::from_raw(
+            width,
+            height,
+            packed_bytes(&convert(frame, Pixel::GRAY8)?, 1),
+        )
+        .map(DynamicImage::ImageLuma8),
+        (false, false) => ImageBuffer::<Rgb<u8>, _>::from_raw(
+            width,
+            height,
+            packed_bytes(&convert(frame, Pixel::RGB24)?, 3),
+        )
+        .map(DynamicImage::ImageRgb8),
+    };
+    image.ok_or_else(|| MediaLibError::UnknownError("Converted frame has the wrong size".into()))
+}
+
+/// Encodes a frame at full resolution in the format the options ask for.
+pub fn encode_image(frame: &Video, options: &FrameExportOptions) -> Result<Vec<u8>, MediaLibError> {
+    let format = match options.format {
+        ImageFormat::Jpeg => {
+            return encode_rgb(
+                &RgbImage {
+                    size: Size::new(frame.width(), frame.height()),
+                    pixels: packed_bytes(&convert(frame, Pixel::RGB24)?, 3),
+                },
+                &options.jpeg,
+            );
+        }
+        ImageFormat::Png => image::ImageFormat::Png,
+        ImageFormat::Tiff => image::ImageFormat::Tiff,
+        ImageFormat::WebP => image::ImageFormat::WebP,
+    };
+    let high_bit_depth = options.high_bit_depth && options.format != ImageFormat::WebP;
+
+    let mut encoded = Cursor::new(Vec::new());
+    to_image(frame, high_bit_depth)?
+        .write_to(&mut encoded, format)
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+    Ok(encoded.into_inner())
+}
+
+/// Decodes the frame shown at `timestamp_us`, from the start of the input.
+fn frame_at(input_path: &Path, timestamp_us: i64) -> Result<Video, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+    video_decoder.seek_to_keyframe(timestamp_us)?;
+
+    // Decode forward from the keyframe, the wanted frame is the last one that
+    // starts at or before the timestamp.
+    let target_us = video_decoder.start_time_us() + timestamp_us;
+    let mut shown: Option<Video> = None;
+    while let Some(decoded) = video_decoder.get_frame() {
+        let decoded = decoded?;
+        let is_after = video_decoder
+            .frame_time_us(&decoded)
+            .is_some_and(|time_us| time_us > target_us);
+        if is_after && shown.is_some() {
+            break;
+        }
+        shown = Some(decoded);
+        if is_after {
+            break;
+        }
+    }
+
+    shown.ok_or_else(|| {
+        MediaLibError::FFmpegError(format!("No frame found at {}us", timestamp_us).into())
+    })
+}
+
+/// `frame` through the options' LUT, as is without one.
+fn grade(frame: Video, options: &FrameExportOptions) -> Result<Video, MediaLibError> {
+    match Lut3d::from_option(&options.lut_path)? {
+        Some(mut lut) => lut.apply(frame),
+        None => Ok(frame),
+    }
+}
+
+/// Decodes the frame shown at `timestamp_us` (from the start of the input)
+/// and encodes it with `encode_image`.
+pub fn export_frame(
+    input_path: &Path,
+    timestamp_us: i64,
+    options: &FrameExportOptions,
+) -> Result<Vec<u8>, MediaLibError> {
+    let frame = grade(frame_at(input_path, timestamp_us)?, options)?;
+    encode_image(&frame, options)
+}
+
+/// Same as `export_frame` with what `detector` finds in the frame blurred or
+/// filled in.
+pub fn export_redacted_frame(
+    input_path: &Path,
+    timestamp_us: i64,
+    options: &FrameExportOptions,
+    redaction: &RedactionOptions,
+    detector: &mut RegionDetectorBox,
+) -> Result<Vec<u8>, MediaLibError> {
+    let frame = frame_at(input_path, timestamp_us)?;
+    let frame = Redactor::new(detector, redaction)?.apply(frame, timestamp_us)?;
+    encode_image(&grade(frame, options)?, options)
+}
+
+/// A JPEG of the frame shown at `timestamp_ms`, upright and scaled to fit
+/// inside `target_size`, and the size it came out at. A zero width or height
+/// follows the aspect ratio, a zero size keeps the frame's own.
+pub fn extract_thumbnail(
+    input_path: &Path,
+    timestamp_ms: u64,
+    target_size: Size,
+    jpeg: &JpegOptions,
+) -> Result<(Vec<u8>, Size), MediaLibError> {
+    let frame = frame_at(input_path, timestamp_ms as i64 * 1000)?;
+    let frame = match PictureCorrection::for_frame(&frame, true) {
+        Some(correction) => correction.apply(&frame)?,
+        None => frame,
+    };
+
+    let source = Size::new(frame.width(), frame.height());
+    let size = if target_size.width == 0 && target_size.height == 0 {
+        source
+    } else {
+        ScalingMode::Fit.apply(source, target_size).0
+    };
+    let mut scaled = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        pixel_format(jpeg.subsampling),
+        size.width,
+        size.height,
+        Flags::BICUBIC,
+    )
+    .and_then(|mut scaler| scaler.run(&frame, &mut scaled))
+    .map_err(ffmpeg_error)?;
+
+    Ok((JpegEncoder::with_options(jpeg).encode(&scaled)?, size))
+}
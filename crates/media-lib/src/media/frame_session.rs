@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::path::Path;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{ChecksumAlgorithm, JobContext, MediaFrameDecoderOptions, MediaLibError, Size};
+
+use super::alpha::AlphaFlattener;
+use super::canvas::{self, FrameScaler};
+use super::checksum::plane_checksum;
+use super::encode_pool::FrameEncoder;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::job::Job;
+use super::jpeg;
+use super::keyframe_index::FrameIndex;
+use super::still;
+
+// Enough to step back and forth across a GOP boundary without going back to
+// the file, the frames are kept scaled down so this stays small.
+const MAX_CACHED_GOPS: usize = 3;
+
+type Item = Result<Vec<u8>, MediaLibError>;
+
+/// The decoded frames of one GOP, by frame index.
+struct CachedGop {
+    frames: Range<usize>,
+    scaled: Vec<Option<Video>>,
+}
+
+/// Frame accurate navigation over a seekable input. Frames are numbered in
+/// presentation order from zero, stepping decodes the whole GOP around the
+/// requested frame and keeps the last few GOPs for stepping back.
+pub struct FrameSession {
+    video_decoder: HardwareAcceleratedVideoDecoder,
+    index: FrameIndex,
+    scaler: FrameScaler,
+    encoder: FrameEncoder,
+    cache: VecDeque<CachedGop>,
+    current: Option<usize>,
+    pub target_size: Size,
+    alpha: AlphaFlattener,
+    // Set by `set_job`, checked before each frame is decoded.
+    job: Option<Job>,
+}
+
+impl FrameSession {
+    pub fn new(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let force_format = options.force_format.as_ref().map(|format| &**format);
+        let index = FrameIndex::build(input_path, force_format)?;
+
+        // Every frame has to be decoded for stepping to be exact.
+        let options = MediaFrameDecoderOptions {
+            realtime: false,
+            ..options.clone()
+        };
+        let mut video_decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::new(input_path, &options) }?;
+        video_decoder.keyframes_only = false;
+
+        let orientation = if options.auto_rotate {
+            video_decoder.orientation()
+        } else {
+            1
+        };
+        let (scaled_size, target_size) = options.scaling_mode.apply(
+            still::upright_size(video_decoder.size(), orientation),
+            canvas::requested_size(&options),
+        );
+
+        Ok(FrameSession {
+            video_decoder,
+            index,
+            scaler: FrameScaler::new(
+                jpeg::pixel_format(options.jpeg.subsampling),
+                orientation,
+                scaled_size,
+                target_size,
+            ),
+            encoder: FrameEncoder::new(&options),
+            cache: VecDeque::new(),
+            current: None,
+            target_size,
+            alpha: AlphaFlattener::new(options.alpha_background, options.alpha_color),
+            job: None,
+        })
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.index.frame_times_us.len() as u64
+    }
+
+    /// Index of the frame returned last, none before the first step.
+    pub fn frame_index(&self) -> Option<u64> {
+        self.current.map(|index| index as u64)
+    }
+
+    /// Checksum of one plane of the current frame, computed from the cached
+    /// scaled frame when asked for.
+    pub fn plane_checksum(&self, plane: u32, algorithm: ChecksumAlgorithm) -> Option<u32> {
+        let index = self.current?;
+        let gop = self.cache.iter().find(|gop| gop.frames.contains(&index))?;
+        let frame = gop.scaled[index - gop.frames.start].as_ref()?;
+        plane_checksum(frame, plane as usize, algorithm)
+    }
+
+    fn run_scaler(&mut self, decoded: &Video) -> Result<Video, MediaLibError> {
+        let flattened = self.alpha.apply(decoded)?;
+        let frame = flattened.as_ref().unwrap_or(decoded);
+
+        self.scaler.run(frame)
+    }
+
+    fn decode_gop(&mut self, frames: Range<usize>) -> Result<CachedGop, MediaLibError> {
+        let keyframe_us = self.index.frame_times_us[frames.start];
+        let last_us = self.index.frame_times_us[frames.end - 1];
+        self.video_decoder
+            .seek_to_keyframe(keyframe_us - self.video_decoder.start_time_us())?;
+
+        let mut scaled = Vec::new();
+        scaled.resize_with(frames.len(), || None);
+        while let Some(decoded) = self.video_decoder.get_frame() {
+            if let Some(job) = &mut self.job {
+                job.check()?;
+            }
+            let decoded = decoded?;
+            let Some(time_us) = self.video_decoder.frame_time_us(&decoded) else {
+                continue;
+            };
+            if time_us > last_us {
+                break;
+            }
+            let Some(index) = self.index.frame_at(time_us) else {
+                continue;
+            };
+            if frames.contains(&index) {
+                scaled[index - frames.start] = Some(self.run_scaler(&decoded)?);
+            }
+        }
+
+        Ok(CachedGop { frames, scaled })
+    }
+
+    /// Cancellation, a deadline and log tagging for the frames still to be
+    /// decoded, replacing any job set before. Stepping has no progress to
+    /// report.
+    pub fn set_job(&mut self, job: JobContext) {
+        self.job = Some(Job::detached(job));
+    }
+
+    /// Moves to frame `index` and returns it as a JPEG.
+    pub fn step_to(&mut self, index: u64) -> Option<Item> {
+        let _scope = self.job.as_ref().map(Job::enter);
+        let index = index as usize;
+        if index >= self.index.frame_times_us.len() {
+            return Some(Err(MediaLibError::UnknownError(
+                format!(
+                    "Frame {} is out of range, the input has {} frames",
+                    index,
+                    self.index.frame_times_us.len()
+                )
+                .into(),
+            )));
+        }
+
+        let cached = self
+            .cache
+            .iter()
+            .position(|gop| gop.frames.contains(&index));
+        let gop = match cached {
+            // Most recently used goes to the back.
+            Some(position) => self.cache.remove(position)?,
+            None => {
+                let frames = self.index.gop_of(index);
+                match self.decode_gop(frames) {
+                    Ok(gop) => gop,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+        };
+        self.cache.push_back(gop);
+        while self.cache.len() > MAX_CACHED_GOPS {
+            self.cache.pop_front();
+        }
+
+        self.current = Some(index);
+        let gop = self.cache.back()?;
+        let frame = gop.scaled[index - gop.frames.start].as_ref();
+        Some(match frame {
+            Some(frame) => self.encoder.encode(frame),
+            None => Err(MediaLibError::FFmpegError(
+                format!("Frame {} could not be decoded", index).into(),
+            )),
+        })
+    }
+
+    /// The frame after the current one, or the first frame before any step.
+    /// None at the end of the input.
+    pub fn next_frame(&mut self) -> Option<Item> {
+        let next = self.current.map_or(0, |index| index + 1);
+        if next >= self.index.frame_times_us.len() {
+            return None;
+        }
+        self.step_to(next as u64)
+    }
+
+    /// The frame before the current one, none at the start of the input.
+    pub fn previous_frame(&mut self) -> Option<Item> {
+        let previous = self.current?.checked_sub(1)?;
+        self.step_to(previous as u64)
+    }
+}
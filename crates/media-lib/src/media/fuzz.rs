@@ -0,0 +1,151 @@
+//! Entry points for the cargo-fuzz targets in `crates/media-lib/fuzz`, see
+//! the README for running them.
+
+use std::ffi::c_int;
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+use ffmpeg_next::ffi::{avformat_find_stream_info, AVDictionary, AVFormatContext};
+use ffmpeg_next::Dictionary;
+use media_types::{HardwareBackend, MediaFrameDecoderOptions, MediaInfo, MediaLibError};
+
+use super::avio::ReadSeek;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::probe::probe_reader;
+
+/// Bytes of an input that are read, the rest is cut off. Small inputs keep
+/// runs quick and what they cover comes from the headers and first
+/// packets anyway.
+pub const MAX_INPUT_BYTES: usize = 1 << 20;
+
+/// Largest frame, in pixels, decoders are allowed to allocate. Bigger
+/// dimensions in a fuzzed header fail to open rather than take gigabytes.
+pub(crate) const MAX_PIXELS: i64 = 4096 * 4096;
+
+/// Largest single allocation FFmpeg may make, anything bigger fails as out
+/// of memory instead of tripping the fuzzer's own limit.
+const MAX_ALLOC_BYTES: usize = 64 << 20;
+
+/// Frames decoded from one input at most.
+const MAX_FRAMES: usize = 32;
+
+static INIT: OnceLock<Result<(), String>> = OnceLock::new();
+
+// FFmpeg's logging is process wide, silenced so every malformed input
+// doesn't print a screenful.
+fn init() -> Result<(), MediaLibError> {
+    INIT.get_or_init(|| {
+        ffmpeg_next::init().map_err(|e| e.to_string())?;
+        ffmpeg_next::util::log::set_level(ffmpeg_next::util::log::Level::Quiet);
+        unsafe { ffmpeg_next::ffi::av_max_alloc(MAX_ALLOC_BYTES) };
+        Ok(())
+    })
+    .clone()
+    .map_err(|e| MediaLibError::FFmpegError(format!("Failed to initialize FFmpeg: {}", e).into()))
+}
+
+fn reader(data: &[u8]) -> Box<dyn ReadSeek> {
+    let data = &data[..data.len().min(MAX_INPUT_BYTES)];
+    Box::new(Cursor::new(data.to_vec()))
+}
+
+/// `avformat_find_stream_info` with the decoders it opens to look at the
+/// streams held to the same limits as `decode_bytes`. Streams only found
+/// while reading packets aren't covered, FFmpeg takes options for the ones
+/// the header lists.
+pub(crate) unsafe fn find_stream_info(ps: *mut AVFormatContext) -> c_int {
+    let mut options: Vec<*mut AVDictionary> = (0..(*ps).nb_streams)
+        .map(|_| {
+            let mut stream_options = Dictionary::new();
+            stream_options.set("max_pixels", &MAX_PIXELS.to_string());
+            stream_options.set("threads", "1");
+            stream_options.disown()
+        })
+        .collect();
+    let result = avformat_find_stream_info(ps, options.as_mut_ptr());
+    // Whatever the decoders didn't use is left in there.
+    for stream_options in options {
+        Dictionary::own(stream_options);
+    }
+    result
+}
+
+/// Probes `data` the way `probe_media` probes a file, for cargo-fuzz and
+/// AFL harnesses. Errors are expected from most inputs, only crashes,
+/// hangs and leaks are findings.
+pub fn probe_bytes(data: &[u8]) -> Result<MediaInfo, MediaLibError> {
+    init()?;
+    probe_reader(reader(data))
+}
+
+/// Decodes the best video stream of `data` in software, up to
+/// `MAX_FRAMES` frames, and returns how many it got. Nothing is shared
+/// between calls, so an input decodes the same way wherever it's replayed.
+pub fn decode_bytes(data: &[u8]) -> Result<usize, MediaLibError> {
+    init()?;
+    let options = MediaFrameDecoderOptions {
+        hw_backend: HardwareBackend::SoftwareOnly,
+        reuse_decoders: false,
+        ..Default::default()
+    };
+    let mut decoder =
+        unsafe { HardwareAcceleratedVideoDecoder::from_reader(reader(data), &options) }?;
+    let mut frames = 0;
+    while frames < MAX_FRAMES {
+        match decoder.get_frame() {
+            Some(Ok(_)) => frames += 1,
+            Some(Err(e)) => return Err(e),
+            None => break,
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::{generate_test_asset, Job};
+    use media_types::{JobContext, Size, TestAssetOptions};
+
+    fn asset_bytes() -> Vec<u8> {
+        let path =
+            std::env::temp_dir().join(format!("media-lib-fuzz-seed-{}.mp4", std::process::id()));
+        let options = TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 1000,
+            frame_rate: 10,
+            tone_hz: 440,
+            ..Default::default()
+        };
+        generate_test_asset(&path, &options, &mut Job::detached(JobContext::default())).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn it_probes_and_decodes_a_valid_input() {
+        let bytes = asset_bytes();
+        let info = probe_bytes(&bytes).unwrap();
+        assert_eq!(info.streams.len(), 2);
+        assert_eq!(decode_bytes(&bytes).unwrap(), 10);
+    }
+
+    #[test]
+    fn it_survives_cut_off_and_corrupted_inputs() {
+        let bytes = asset_bytes();
+        // Errors are fine, these only have to come back.
+        for len in [0, 1, 8, 64, 512, bytes.len() / 2, bytes.len() - 1] {
+            let _ = probe_bytes(&bytes[..len]);
+            let _ = decode_bytes(&bytes[..len]);
+        }
+        for stride in [7, 61, 509] {
+            let mut corrupted = bytes.clone();
+            for byte in corrupted.iter_mut().step_by(stride) {
+                *byte ^= 0xa5;
+            }
+            let _ = probe_bytes(&corrupted);
+            let _ = decode_bytes(&corrupted);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ffmpeg_next::ffi::{av_hwframe_transfer_data, AVHWFramesContext, AVPixelFormat};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    ContentLightLevel, HwSurface, HwSurfaceKind, MasteringDisplay, MediaFrameDecoderOptions,
+    MediaLibError, ProcessingReport, SphericalMetadata, ToneMapping,
+};
+
+use super::canvas;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::hdr;
+
+// Hardware pixel formats and the handles their frames carry.
+const SURFACE_KINDS: &[(AVPixelFormat, HwSurfaceKind)] = &[
+    (AVPixelFormat::AV_PIX_FMT_CUDA, HwSurfaceKind::Cuda),
+    (
+        AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX,
+        HwSurfaceKind::VideoToolbox,
+    ),
+    (AVPixelFormat::AV_PIX_FMT_VAAPI, HwSurfaceKind::Vaapi),
+    (AVPixelFormat::AV_PIX_FMT_D3D11, HwSurfaceKind::D3D11),
+    (AVPixelFormat::AV_PIX_FMT_QSV, HwSurfaceKind::Qsv),
+];
+
+/// A decoded frame as it came off the device.
+pub struct GpuFrame {
+    frame: Video,
+    // `frame` in system memory, downloaded when its pixels are first asked
+    // for.
+    downloaded: Option<Video>,
+    pub timestamp_us: i64,
+    pub spherical: Option<SphericalMetadata>,
+}
+
+impl GpuFrame {
+    /// The handles of the frame on its device, none for frames in system
+    /// memory.
+    pub fn surface(&self) -> Option<HwSurface> {
+        let raw = unsafe { &*self.frame.as_ptr() };
+        let (_, kind) = SURFACE_KINDS
+            .iter()
+            .find(|(format, _)| *format as i32 == raw.format)?;
+        if raw.hw_frames_ctx.is_null() {
+            return None;
+        }
+        let sw_format =
+            unsafe { (*((*raw.hw_frames_ctx).data as *mut AVHWFramesContext)).sw_format };
+
+        let mut surface = HwSurface {
+            handles: stabby::vec::Vec::new(),
+            pitches: stabby::vec::Vec::new(),
+            sw_format: Pixel::from(sw_format)
+                .descriptor()
+                .map(|descriptor| descriptor.name())
+                .unwrap_or_default()
+                .into(),
+            width: self.frame.width(),
+            height: self.frame.height(),
+            kind: *kind,
+        };
+        match kind {
+            HwSurfaceKind::Cuda => {
+                for (plane, pitch) in raw.data.iter().zip(raw.linesize) {
+                    if plane.is_null() {
+                        break;
+                    }
+                    surface.handles.push(*plane as usize as u64);
+                    surface.pitches.push(pitch as u32);
+                }
+            }
+            // The texture and its index in the array.
+            HwSurfaceKind::D3D11 => {
+                surface.handles.push(raw.data[0] as usize as u64);
+                surface.handles.push(raw.data[1] as usize as u64);
+            }
+            // The rest keep their one handle in the fourth data pointer.
+            _ => surface.handles.push(raw.data[3] as usize as u64),
+        }
+        Some(surface)
+    }
+
+    /// The frame in system memory, downloading it the first time for frames
+    /// on the device. None when the download fails.
+    pub fn system_frame(&mut self) -> Option<&Video> {
+        if unsafe { (*self.frame.as_ptr()).hw_frames_ctx.is_null() } {
+            return Some(&self.frame);
+        }
+        if self.downloaded.is_none() {
+            let mut downloaded = Video::empty();
+            let res = unsafe {
+                av_hwframe_transfer_data(downloaded.as_mut_ptr(), self.frame.as_ptr(), 0)
+            };
+            if res < 0 {
+                log::warn!("Failed to download frame from the device: {}", res);
+                return None;
+            }
+            self.downloaded = Some(downloaded);
+        }
+        self.downloaded.as_ref()
+    }
+
+    /// The system memory frame and `index` as a plane index, none past the
+    /// last plane.
+    pub fn plane(&mut self, index: u32) -> Option<(&Video, usize)> {
+        let frame = self.system_frame()?;
+        ((index as usize) < frame.planes()).then_some((frame, index as usize))
+    }
+
+    // Decoders tag frames on the device the same as ones in system memory,
+    // none of these need a download.
+
+    pub fn color_space(&self) -> String {
+        hdr::color_space(&self.frame)
+    }
+
+    pub fn color_primaries(&self) -> String {
+        hdr::color_primaries(&self.frame)
+    }
+
+    pub fn color_transfer(&self) -> String {
+        hdr::color_transfer(&self.frame)
+    }
+
+    pub fn color_range(&self) -> String {
+        hdr::color_range(&self.frame)
+    }
+
+    pub fn mastering_display(&self) -> Option<MasteringDisplay> {
+        hdr::mastering_display(&self.frame)
+    }
+
+    pub fn content_light_level(&self) -> Option<ContentLightLevel> {
+        hdr::content_light_level(&self.frame)
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.frame.is_interlaced()
+    }
+
+    pub fn is_top_first(&self) -> bool {
+        self.frame.is_top_first()
+    }
+}
+
+/// Every frame of an input without downloading it from the device, for
+/// consumers such as inference that work on the GPU themselves.
+pub struct GpuFrameIterator {
+    video_decoder: HardwareAcceleratedVideoDecoder,
+    // The same for every frame, it's read from the container.
+    spherical: Option<SphericalMetadata>,
+    started: Instant,
+    frames_returned: u64,
+}
+
+impl GpuFrameIterator {
+    /// Fails when the input isn't decoded on a GPU. `hw_prescale` scales
+    /// frames to the requested size on the device.
+    pub fn new(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let iterator = Self::anywhere(input_path, options)?;
+        if !iterator.video_decoder.hardware_accelerated {
+            return Err(MediaLibError::FFmpegError(
+                "Input isn't decoded on a GPU, frames can't stay on one".into(),
+            ));
+        }
+        Ok(iterator)
+    }
+
+    /// Like `new`, with the frames of inputs decoded in software handed out
+    /// in system memory rather than failing. They're tagged and carry HDR
+    /// metadata the same way.
+    pub fn anywhere(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let mut video_decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::new(input_path, options) }?;
+        video_decoder.keyframes_only = false;
+        video_decoder.download = false;
+        if options.hw_prescale {
+            let (scaled_size, _) = options
+                .scaling_mode
+                .apply(video_decoder.size(), canvas::requested_size(options));
+            // Frames stay on the device, they can't be tone mapped there.
+            video_decoder.set_prescale(scaled_size, ToneMapping::Off);
+        }
+        Ok(GpuFrameIterator {
+            spherical: video_decoder.spherical(),
+            video_decoder,
+            started: Instant::now(),
+            frames_returned: 0,
+        })
+    }
+
+    pub fn get(&mut self) -> Option<Result<GpuFrame, MediaLibError>> {
+        let frame = match self.video_decoder.get_frame()? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        let timestamp_us = self
+            .video_decoder
+            .frame_time_us(&frame)
+            .map(|time| time - self.video_decoder.start_time_us())
+            .unwrap_or_default();
+        self.frames_returned += 1;
+        Some(Ok(GpuFrame {
+            frame,
+            downloaded: None,
+            timestamp_us,
+            spherical: self.spherical.clone(),
+        }))
+    }
+
+    pub fn report(&self) -> ProcessingReport {
+        let mut warnings = stabby::vec::Vec::with_capacity(self.video_decoder.warnings.len());
+        for warning in &self.video_decoder.warnings {
+            warnings.push(warning.as_str().into());
+        }
+
+        ProcessingReport {
+            wall_time_ms: self.started.elapsed().as_millis() as u64,
+            frames_decoded: self.video_decoder.frames_decoded,
+            frames_returned: self.frames_returned,
+            frames_dropped: self.video_decoder.dropped_frames,
+            hardware_device: self
+                .video_decoder
+                .hardware_device_name()
+                .map(|name| name.as_str().into())
+                .into(),
+            warnings,
+            outputs: stabby::vec::Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_next::color::{Primaries, Range, Space, TransferCharacteristic};
+    use ffmpeg_next::ffi::{
+        av_content_light_metadata_create_side_data, av_mastering_display_metadata_create_side_data,
+        AVRational,
+    };
+
+    // A frame of HDR10 video as decoders hand it out: BT.2020 with the PQ
+    // transfer, and the mastering display and light levels as side data.
+    fn hdr10_frame() -> Video {
+        let mut frame = Video::new(Pixel::YUV420P10LE, 64, 36);
+        frame.set_color_space(Space::BT2020NCL);
+        frame.set_color_primaries(Primaries::BT2020);
+        frame.set_color_transfer_characteristic(TransferCharacteristic::SMPTE2084);
+        frame.set_color_range(Range::MPEG);
+        let ratio = |num, den| AVRational { num, den };
+        unsafe {
+            let display = &mut *av_mastering_display_metadata_create_side_data(frame.as_mut_ptr());
+            display.display_primaries = [
+                [ratio(34000, 50000), ratio(16000, 50000)],
+                [ratio(13250, 50000), ratio(34500, 50000)],
+                [ratio(7500, 50000), ratio(3000, 50000)],
+            ];
+            display.white_point = [ratio(15635, 50000), ratio(16450, 50000)];
+            display.min_luminance = ratio(50, 10000);
+            display.max_luminance = ratio(1000, 1);
+            display.has_primaries = 1;
+            display.has_luminance = 1;
+            let light = &mut *av_content_light_metadata_create_side_data(frame.as_mut_ptr());
+            light.MaxCLL = 1000;
+            light.MaxFALL = 400;
+        }
+        frame
+    }
+
+    #[test]
+    fn it_describes_hdr_frames_in_system_memory() {
+        let frame = GpuFrame {
+            frame: hdr10_frame(),
+            downloaded: None,
+            timestamp_us: 0,
+            spherical: None,
+        };
+        assert_eq!(frame.color_space(), "bt2020nc");
+        assert_eq!(frame.color_primaries(), "bt2020");
+        assert_eq!(frame.color_transfer(), "smpte2084");
+        assert_eq!(frame.color_range(), "tv");
+
+        let display = frame.mastering_display().unwrap();
+        assert_eq!((display.red_x, display.red_y), (0.68, 0.32));
+        assert_eq!(
+            (display.white_point_x, display.white_point_y),
+            (0.3127, 0.329)
+        );
+        assert_eq!(
+            (display.min_luminance, display.max_luminance),
+            (0.005, 1000.0)
+        );
+        let light = frame.content_light_level().unwrap();
+        assert_eq!((light.max_content, light.max_frame_average), (1000, 400));
+
+        // SDR frames have no HDR metadata to give.
+        let frame = GpuFrame {
+            frame: Video::new(Pixel::YUV420P, 64, 36),
+            downloaded: None,
+            timestamp_us: 0,
+            spherical: None,
+        };
+        assert_eq!(frame.color_transfer(), "unknown");
+        assert!(frame.mastering_display().is_none());
+        assert!(frame.content_light_level().is_none());
+    }
+
+    #[test]
+    fn it_has_no_surface_for_frames_in_system_memory() {
+        let mut frame = GpuFrame {
+            frame: Video::new(Pixel::NV12, 64, 36),
+            downloaded: None,
+            timestamp_us: 0,
+            spherical: None,
+        };
+        assert!(frame.surface().is_none());
+        // Nothing to download, the frame is handed back as it is.
+        assert_eq!(frame.system_frame().map(Video::format), Some(Pixel::NV12));
+        assert!(frame.downloaded.is_none());
+    }
+
+    #[test]
+    fn it_describes_the_planes_of_frames_in_system_memory() {
+        let mut frame = GpuFrame {
+            frame: Video::new(Pixel::YUV420P, 64, 36),
+            downloaded: None,
+            timestamp_us: 0,
+            spherical: None,
+        };
+        let planes: Vec<(u32, u32)> = (0..3)
+            .map(|index| {
+                let (frame, index) = frame.plane(index).unwrap();
+                assert!(frame.stride(index) >= frame.plane_width(index) as usize);
+                assert!(
+                    frame.data(index).len()
+                        >= frame.stride(index) * frame.plane_height(index) as usize
+                );
+                (frame.plane_width(index), frame.plane_height(index))
+            })
+            .collect();
+        assert_eq!(planes, [(64, 36), (32, 18), (32, 18)]);
+        assert!(frame.plane(3).is_none());
+
+        let mut frame = GpuFrame {
+            frame: Video::new(Pixel::NV12, 64, 36),
+            downloaded: None,
+            timestamp_us: 0,
+            spherical: None,
+        };
+        assert_eq!(frame.system_frame().map(Video::planes), Some(2));
+        assert!(frame.plane(2).is_none());
+    }
+}
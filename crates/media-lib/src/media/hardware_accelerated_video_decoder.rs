@@ -1,18 +1,40 @@
+use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
-use std::{path::Path, ptr::null_mut};
+use std::ptr::null_mut;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use ffmpeg_next::ffi::av_hwframe_transfer_data;
-use ffmpeg_next::Codec;
+use super::avio::ReadSeek;
+use super::decimate::Decimator;
+use super::decoder_pool::{self, PoolKey};
+use super::diagnostics::{self, FailureReport};
+use super::health::HealthMonitor;
+use super::hw_scale::HardwareScaler;
+use super::input::{
+    no_video_stream, open_input_with_options, open_reader, InputOptions, MediaInput,
+};
+use super::spherical;
+use super::still;
+use super::timestamp::{self, Continuity, Discontinuity};
+use super::tonemap;
+use ffmpeg_next::ffi::{av_frame_copy_props, av_hwframe_transfer_data};
+use ffmpeg_next::util::error::EAGAIN;
 use ffmpeg_next::{
     codec::{self, context::Context},
     ffi::{
-        av_buffer_unref, av_hwdevice_ctx_create, avcodec_get_hw_config, AVBufferRef, AVHWDeviceType,
+        av_buffer_ref, av_buffer_unref, av_hwdevice_ctx_create, av_hwdevice_find_type_by_name,
+        av_hwdevice_get_type_name, av_hwframe_ctx_init, avcodec_get_hw_config,
+        avcodec_get_hw_frames_parameters, AVBufferRef, AVCodecContext, AVCodecHWConfig,
+        AVHWDeviceType, AVHWFramesContext, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX,
     },
 };
-use media_types::MediaLibError;
-
-// The error status sent when the decoder needs more data
-const NEED_MORE_DATA: i32 = 35;
+use ffmpeg_next::{Codec, Dictionary, Rational};
+use media_types::{
+    Backpressure, HardwareBackend, MediaFrameDecoderOptions, MediaLibError, Size,
+    SphericalMetadata, StreamHealth, StreamHealthObserverBox, ToneMapping,
+};
 
 // We in fact do use this function but it's passed into a c style callback.
 #[allow(unused)]
@@ -31,6 +53,12 @@ extern "C" fn get_hw_format(
             break;
         }
         if format == pix_fmt {
+            let initial_pool_size = unsafe { (*state).initial_pool_size };
+            if initial_pool_size > 0 {
+                if let Err(e) = unsafe { init_hw_frames(ctx, format, initial_pool_size) } {
+                    log::warn!("Failed to size the hardware frame pool: {}", e);
+                }
+            }
             return format;
         }
         i += 1;
@@ -38,204 +66,1437 @@ extern "C" fn get_hw_format(
     ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_NONE
 }
 
+/// Sets up the decoder's hardware frames itself instead of leaving it to
+/// FFmpeg, so the pool can be sized. When this fails FFmpeg falls back to
+/// its own pool.
+unsafe fn init_hw_frames(
+    ctx: *mut AVCodecContext,
+    format: ffmpeg_next::ffi::AVPixelFormat,
+    initial_pool_size: i32,
+) -> Result<(), ffmpeg_next::Error> {
+    let mut frames_ref: *mut AVBufferRef = null_mut();
+    let result =
+        avcodec_get_hw_frames_parameters(ctx, (*ctx).hw_device_ctx, format, &mut frames_ref);
+    if result < 0 {
+        return Err(ffmpeg_next::Error::from(result));
+    }
+
+    let frames = (*frames_ref).data as *mut AVHWFramesContext;
+    (*frames).initial_pool_size = initial_pool_size;
+    let result = av_hwframe_ctx_init(frames_ref);
+    if result < 0 {
+        av_buffer_unref(&mut frames_ref);
+        return Err(ffmpeg_next::Error::from(result));
+    }
+
+    // get_format runs again on e.g. a resolution change.
+    av_buffer_unref(&mut (*ctx).hw_frames_ctx);
+    (*ctx).hw_frames_ctx = frames_ref;
+    Ok(())
+}
+
+/// Tracks how far behind real time we are when decoding in realtime mode.
+struct RealtimeClock {
+    max_latency: Duration,
+    // Wall clock time and presentation time (in microseconds) of the first
+    // video packet, everything else is measured relative to these.
+    anchor: Option<(Instant, i64)>,
+    // Latencies (in microseconds) at which backpressure starts and stops,
+    // none without watermarks.
+    watermarks: Option<(i64, i64)>,
+    paused: bool,
+}
+
+impl RealtimeClock {
+    fn is_late(&mut self, pts_us: i64) -> bool {
+        let (started, first_pts_us) = *self.anchor.get_or_insert((Instant::now(), pts_us));
+        let wall_elapsed_us = started.elapsed().as_micros() as i64;
+        let media_elapsed_us = pts_us - first_pts_us;
+        let latency_us = wall_elapsed_us - media_elapsed_us;
+
+        if let Some((low_us, high_us)) = self.watermarks {
+            if latency_us >= high_us && !self.paused {
+                log::debug!("Consumer is {}us behind, pausing upstream", latency_us);
+                self.paused = true;
+            } else if latency_us <= low_us && self.paused {
+                log::debug!("Consumer caught up, resuming upstream");
+                self.paused = false;
+            }
+        }
+        latency_us > self.max_latency.as_micros() as i64
+    }
+}
+
 #[repr(C)]
 struct DecoderContextState {
     device_type: AVHWDeviceType,
     pix_fmt: ffmpeg_next::ffi::AVPixelFormat,
+    // Zero leaves the hardware frames to FFmpeg.
+    initial_pool_size: i32,
 }
 
+/// An opened decoder and the hardware it decodes on, what the decoder pool
+/// keeps between inputs.
+pub struct OpenDecoder {
+    pub video_decoder: codec::decoder::Video,
+    pub codec: Codec,
+    pub hw_device_ctx: *mut AVBufferRef,
+    pub hardware_accelerated: bool,
+    pub pix_fmt: ffmpeg_next::ffi::AVPixelFormat,
+    pub device_type: AVHWDeviceType,
+}
+
+// Only ever used by whoever took it out of the pool.
+unsafe impl Send for OpenDecoder {}
+
 pub struct HardwareAcceleratedVideoDecoder {
     pub hardware_accelerated: bool,
     pub eof_sent: bool,
-    video_decoder: codec::decoder::Video,
+    // Handed to the decoder pool on drop when `pool_key` is set.
+    video_decoder: ManuallyDrop<codec::decoder::Video>,
     codec: Codec,
-    ictx: ffmpeg_next::format::context::Input,
+    ictx: MediaInput,
     hw_device_ctx: *mut AVBufferRef,
     pub pix_fmt: ffmpeg_next::ffi::AVPixelFormat,
     pub device_type: AVHWDeviceType,
     pub video_stream_index: usize,
+    time_base: Rational,
+    frame_rate: Rational,
+    /// Only return keyframes, on by default. Turn it off to get every
+    /// decoded frame.
+    pub keyframes_only: bool,
+    /// Download hardware frames to system memory, on by default. Turn it
+    /// off to get frames as they are on the device.
+    pub download: bool,
+    // Size hardware frames are scaled to before downloading, and how HDR
+    // frames are tone mapped once they are, see `set_prescale`.
+    prescale: Option<Size>,
+    prescale_tone_mapping: ToneMapping,
+    hw_scaler: Option<HardwareScaler>,
+    // Set when only some frames are wanted, see `frame_step` and `max_fps`.
+    decimator: Option<Decimator>,
+    // Set by `seek`, frames before this (in the stream's timeline) are
+    // decoded but not returned.
+    seek_target_us: Option<i64>,
+    // Set by `set_end`, decoding stops at the first frame after this (from
+    // the start of the input).
+    end_us: Option<i64>,
+    realtime: Option<RealtimeClock>,
+    // Set once packets have been dropped, decoding can only resume from a
+    // keyframe after that.
+    waiting_for_keyframe: bool,
+    pub dropped_frames: u64,
+    pub frames_decoded: u64,
+    // Decode timestamps of the video packets, for spotting discontinuities.
+    dts_continuity: Continuity,
+    pub warnings: Vec<String>,
+    health: HealthMonitor,
+    // Where to write diagnostics when decoding fails, taken when they're
+    // written so a failing file only gets one bundle.
+    diagnostics_dir: Option<PathBuf>,
+    input_path: Option<PathBuf>,
+    force_format: Option<String>,
+    // Set when the decoder goes back to the pool once this input is done.
+    pool_key: Option<PoolKey>,
 }
 
-impl HardwareAcceleratedVideoDecoder {
-    pub unsafe fn new(input_path: &Path) -> Result<Self, MediaLibError> {
-        // Input stream for the file.
-        let ictx = ffmpeg_next::format::input(input_path)
-            .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+/// Writes a diagnostics bundle for a decoder that failed if the options ask
+/// for one. Failing to write it is only logged, the caller gets the decode
+/// error either way.
+fn write_diagnostics(directory: &Path, report: &FailureReport) {
+    match diagnostics::write_bundle(directory, report) {
+        Ok(bundle) => log::warn!("Wrote decoder diagnostics to {}", bundle.display()),
+        Err(e) => log::warn!("Failed to write decoder diagnostics: {:?}", e),
+    }
+}
 
-        // Find the video stream
-        let input = ictx
-            .streams()
-            .best(ffmpeg_next::media::Type::Video)
-            .ok_or_else(|| MediaLibError::FFmpegError("No video stream found".into()))?;
+/// Where the options want diagnostics written, which starts keeping the log
+/// tail that goes in them.
+fn diagnostics_dir(options: &MediaFrameDecoderOptions) -> Option<PathBuf> {
+    let directory = options.diagnostics_dir.as_ref()?;
+    crate::logging::keep_tail();
+    Some(PathBuf::from(&**directory))
+}
 
-        let video_stream_index = input.index();
+// DRM render nodes tried for VAAPI when no device is given. On servers with
+// more than one GPU the first is often one without a VA driver.
+const DRM_RENDER_NODES: std::ops::Range<u32> = 128..136;
 
-        let mut decoder_context = Context::from_parameters(input.parameters())
-            .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+// Enough to tell what's wrong with a file without a badly broken one
+// growing the list without bound.
+const MAX_WARNINGS: usize = 100;
+// Gaps between packets longer than this are reported as discontinuities.
+const MAX_PACKET_GAP_US: i64 = 10_000_000;
 
-        let decoder_context_ptr = decoder_context.as_mut_ptr();
+/// FFmpeg's name for `format`, e.g. "nv12".
+fn pixel_format_name(format: ffmpeg_next::ffi::AVPixelFormat) -> &'static str {
+    ffmpeg_next::format::Pixel::from(format)
+        .descriptor()
+        .map(|descriptor| descriptor.name())
+        .unwrap_or("none")
+}
 
-        // The object underlying decoder is just the decoder context
-        let decoder = decoder_context.decoder();
+/// Opens a hardware device of `device_type`. `device` picks which one the
+/// way FFmpeg's `-init_hw_device` does, None takes FFmpeg's default except
+/// for VAAPI, where each render node is tried in turn. For QSV `device` is
+/// the GPU it runs on (a render node on Linux), Quick Sync opens its own
+/// session on top.
+pub unsafe fn create_hardware_device(
+    device_type: AVHWDeviceType,
+    device: Option<&str>,
+) -> Result<*mut AVBufferRef, MediaLibError> {
+    let is_qsv = device_type == AVHWDeviceType::AV_HWDEVICE_TYPE_QSV;
+    let probes_render_nodes = device_type == AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI
+        || (is_qsv && cfg!(target_os = "linux"));
+    let candidates: Vec<Option<String>> = match device {
+        Some(device) => vec![Some(device.to_string())],
+        None if probes_render_nodes => {
+            let nodes: Vec<Option<String>> = DRM_RENDER_NODES
+                .map(|node| format!("/dev/dri/renderD{}", node))
+                .filter(|path| Path::new(path).exists())
+                .map(Some)
+                .collect();
+            if nodes.is_empty() {
+                vec![None]
+            } else {
+                nodes
+            }
+        }
+        None => vec![None],
+    };
 
-        let video_decoder = decoder
-            .video()
-            .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+    let mut error = ffmpeg_next::Error::Unknown;
+    for candidate in candidates {
+        let mut device_options = Dictionary::new();
+        let c_device = match (&candidate, is_qsv) {
+            (Some(candidate), true) => {
+                device_options.set("child_device", candidate);
+                None
+            }
+            (candidate, _) => candidate
+                .as_deref()
+                .map(std::ffi::CString::new)
+                .transpose()
+                .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?,
+        };
+        let c_device_ptr = c_device.as_ref().map_or(null(), |device| device.as_ptr());
+        let mut hw_device_ctx: *mut AVBufferRef = null_mut();
+        let device_options = device_options.disown();
+        let result = av_hwdevice_ctx_create(
+            &mut hw_device_ctx,
+            device_type,
+            c_device_ptr,
+            device_options,
+            0,
+        );
+        Dictionary::own(device_options);
+        if result >= 0 {
+            if let Some(candidate) = &candidate {
+                log::debug!("Opened {:?} device {}", device_type, candidate);
+            }
+            return Ok(hw_device_ctx);
+        }
+        error = ffmpeg_next::Error::from(result);
+        log::debug!(
+            "Failed to open {:?} device {}: {}",
+            device_type,
+            candidate.as_deref().unwrap_or("(default)"),
+            error
+        );
+    }
+    Err(MediaLibError::FFmpegError(error.to_string().into()))
+}
 
-        // Now that we have the decoder context, we can try to initialize hardware acceleration
-        let codec = video_decoder
-            .codec()
-            .ok_or(MediaLibError::FFmpegError("Failed to find codec".into()))?;
+/// A device opened by `preheat_hardware_device`. It's kept for the life of
+/// the process and decoders take their own reference to it.
+struct PreheatedDevice {
+    device_type: AVHWDeviceType,
+    device: *mut AVBufferRef,
+}
 
-        let mut i = 0;
-        let mut hardware_accelerated = false;
-        let mut hw_pixel_format = ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_NONE;
-        let mut device_type = AVHWDeviceType::AV_HWDEVICE_TYPE_NONE;
-        let mut hw_device_ctx: *mut AVBufferRef = std::ptr::null_mut();
+// The buffer is only read and referenced once it's in the cache, which
+// FFmpeg allows from any thread.
+unsafe impl Send for PreheatedDevice {}
 
-        loop {
-            let config = avcodec_get_hw_config(codec.as_ptr(), i);
-            if config.is_null() {
-                break;
+static PREHEATED: Mutex<Vec<PreheatedDevice>> = Mutex::new(Vec::new());
+
+/// Caches `device` as the preheated device of `device_type`, taking over
+/// the reference.
+fn keep_preheated(device_type: AVHWDeviceType, device: *mut AVBufferRef) {
+    let mut preheated = PREHEATED.lock().unwrap_or_else(|e| e.into_inner());
+    preheated.push(PreheatedDevice {
+        device_type,
+        device,
+    });
+}
+
+/// A new reference to the preheated device of `device_type`, if there is one.
+fn preheated_device(device_type: AVHWDeviceType) -> Option<*mut AVBufferRef> {
+    let preheated = PREHEATED.lock().unwrap_or_else(|e| e.into_inner());
+    preheated
+        .iter()
+        .find(|preheated| preheated.device_type == device_type)
+        .map(|preheated| unsafe { av_buffer_ref(preheated.device) })
+        .filter(|device| !device.is_null())
+}
+
+/// A device of `device_type` for a decoder: the preheated one when the
+/// options don't ask for a particular device, otherwise a new one.
+unsafe fn open_device(
+    device_type: AVHWDeviceType,
+    hw_device: Option<&str>,
+) -> Result<*mut AVBufferRef, MediaLibError> {
+    if hw_device.is_none() {
+        if let Some(device) = preheated_device(device_type) {
+            return Ok(device);
+        }
+    }
+    create_hardware_device(device_type, hw_device)
+}
+
+/// Opens a hardware device of the given type (e.g. "cuda") and keeps it,
+/// so the driver is loaded and initialised before the first decode and
+/// decoders that don't ask for a particular device share it.
+pub fn preheat_hardware_device(name: &str) -> Result<(), MediaLibError> {
+    let c_name = std::ffi::CString::new(name)
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+
+    unsafe {
+        let device_type = av_hwdevice_find_type_by_name(c_name.as_ptr());
+        if device_type == AVHWDeviceType::AV_HWDEVICE_TYPE_NONE {
+            return Err(MediaLibError::FFmpegError(
+                format!("Unknown hardware device type '{}'", name).into(),
+            ));
+        }
+        if let Some(mut device) = preheated_device(device_type) {
+            av_buffer_unref(&mut device);
+            return Ok(());
+        }
+
+        let device = create_hardware_device(device_type, None).map_err(|e| {
+            MediaLibError::FFmpegError(
+                format!("Failed to open hardware device '{}': {}", name, e).into(),
+            )
+        })?;
+        keep_preheated(device_type, device);
+    }
+    Ok(())
+}
+
+/// Where a device type goes in the order devices are tried, lowest first.
+/// Decoders list them in FFmpeg's own order, which puts DXVA2 ahead of
+/// D3D11VA although D3D11VA is the better choice on any Windows with both.
+fn device_preference(device_type: AVHWDeviceType) -> u8 {
+    match device_type {
+        AVHWDeviceType::AV_HWDEVICE_TYPE_DXVA2 => 1,
+        _ => 0,
+    }
+}
+
+/// A way of decoding an input, see `MediaFrameDecoderOptions::hw_device_types`.
+#[derive(Clone, Copy)]
+pub enum Backend {
+    QuickSync,
+    Device(AVHWDeviceType),
+    Software,
+}
+
+impl Backend {
+    pub fn name(self) -> String {
+        match self {
+            Backend::QuickSync => "qsv".to_string(),
+            Backend::Device(device_type) => device_type_name(device_type),
+            Backend::Software => "software".to_string(),
+        }
+    }
+}
+
+fn parse_backend(name: &str) -> Result<Backend, MediaLibError> {
+    if name == "software" {
+        return Ok(Backend::Software);
+    }
+    let c_name = std::ffi::CString::new(name)
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+    match unsafe { av_hwdevice_find_type_by_name(c_name.as_ptr()) } {
+        AVHWDeviceType::AV_HWDEVICE_TYPE_NONE => Err(MediaLibError::FFmpegError(
+            format!("Unknown hardware device type '{}'", name).into(),
+        )),
+        AVHWDeviceType::AV_HWDEVICE_TYPE_QSV => Ok(Backend::QuickSync),
+        device_type => Ok(Backend::Device(device_type)),
+    }
+}
+
+/// FFmpeg's name for `device_type`, e.g. "cuda".
+fn device_type_name(device_type: AVHWDeviceType) -> String {
+    let name = unsafe { av_hwdevice_get_type_name(device_type) };
+    if name.is_null() {
+        return format!("{:?}", device_type);
+    }
+    unsafe { std::ffi::CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The hardware configs of `codec` that decode on a device context, in the
+/// order they're tried by default.
+pub fn device_configs(codec: &Codec) -> Vec<&'static AVCodecHWConfig> {
+    let mut configs = Vec::new();
+    let mut i = 0;
+    loop {
+        let config = unsafe { avcodec_get_hw_config(codec.as_ptr(), i) };
+        if config.is_null() {
+            break;
+        }
+        i += 1;
+        let config = unsafe { &*config };
+        if config.methods & AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32 != 0 {
+            configs.push(config);
+        }
+    }
+    configs.sort_by_key(|config| device_preference(config.device_type));
+    configs
+}
+
+// Quick Sync is only tried where Intel ships it, there's none on macOS.
+const QUICK_SYNC_PLATFORM: bool = cfg!(any(target_os = "linux", target_os = "windows"));
+
+/// Where a backend goes in the default order, lowest first. NVIDIA's
+/// decoders only open with an NVIDIA card in the machine, and when there is
+/// one it's the GPU to use. Quick Sync comes next, ahead of VAAPI and
+/// D3D11VA, which would drive the same Intel GPU less well.
+fn backend_preference(backend: Backend) -> u8 {
+    match backend {
+        Backend::Device(AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA) => 0,
+        Backend::QuickSync => 1,
+        Backend::Device(device_type) => 2 + device_preference(device_type),
+        Backend::Software => u8::MAX,
+    }
+}
+
+/// The native decoder's `device_types` and Quick Sync in the order that
+/// suits the platform, then software.
+pub fn default_backends(device_types: &[AVHWDeviceType]) -> Vec<Backend> {
+    let mut backends: Vec<Backend> = device_types
+        .iter()
+        .map(|device_type| Backend::Device(*device_type))
+        .collect();
+    if QUICK_SYNC_PLATFORM {
+        backends.push(Backend::QuickSync);
+    }
+    backends.push(Backend::Software);
+    backends.sort_by_key(|backend| backend_preference(*backend));
+    backends
+}
+
+// Quick Sync decoders by the codec they decode.
+const QUICK_SYNC_DECODERS: &[(codec::Id, &str)] = &[
+    (codec::Id::H264, "h264_qsv"),
+    (codec::Id::HEVC, "hevc_qsv"),
+    (codec::Id::AV1, "av1_qsv"),
+    (codec::Id::VP9, "vp9_qsv"),
+    (codec::Id::VP8, "vp8_qsv"),
+    (codec::Id::MPEG2VIDEO, "mpeg2_qsv"),
+    (codec::Id::VC1, "vc1_qsv"),
+    (codec::Id::MJPEG, "mjpeg_qsv"),
+];
+
+/// Intel's Quick Sync decoder for `id` and a QSV device to run it on, when
+/// FFmpeg was built with it and there's an Intel GPU to open.
+pub unsafe fn quick_sync_decoder(
+    id: codec::Id,
+    hw_device: Option<&str>,
+) -> Option<(Codec, *mut AVBufferRef)> {
+    let (_, name) = QUICK_SYNC_DECODERS
+        .iter()
+        .find(|(decodes, _)| *decodes == id)?;
+    let codec = ffmpeg_next::decoder::find_by_name(name)?;
+    match open_device(AVHWDeviceType::AV_HWDEVICE_TYPE_QSV, hw_device) {
+        Ok(device) => Some((codec, device)),
+        Err(e) => {
+            log::debug!("Quick Sync unavailable for {}: {}", name, e);
+            None
+        }
+    }
+}
+
+// Far more surfaces than any decoder needs. Larger pools are taken to be a
+// mistake rather than left to fail on the GPU.
+const MAX_HW_POOL_SIZE: u32 = 256;
+
+/// Rejects hardware frame pools larger than `MAX_HW_POOL_SIZE`, counting
+/// the extra frames.
+fn check_hw_pool_size(options: &MediaFrameDecoderOptions) -> Result<(), MediaLibError> {
+    let size = options
+        .hw_initial_pool_size
+        .saturating_add(options.hw_extra_frames);
+    if size > MAX_HW_POOL_SIZE {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Hardware frame pool of {} surfaces is over the limit of {}",
+                size, MAX_HW_POOL_SIZE
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Points the decoder context at `hw_device_ctx` and has it pick
+/// `pix_fmt` frames, with the pool sized as the options ask.
+unsafe fn attach_hardware(
+    ctx: *mut AVCodecContext,
+    device_type: AVHWDeviceType,
+    pix_fmt: ffmpeg_next::ffi::AVPixelFormat,
+    hw_device_ctx: *mut AVBufferRef,
+    options: &MediaFrameDecoderOptions,
+) {
+    // Extra frames go on top of whichever pool size is used.
+    let initial_pool_size = match options.hw_initial_pool_size {
+        0 => 0,
+        size => (size + options.hw_extra_frames) as i32,
+    };
+    let state = DecoderContextState {
+        device_type,
+        pix_fmt,
+        initial_pool_size,
+    };
+
+    // We then need to inject the state into the decoder context
+    (*ctx).opaque = Box::into_raw(Box::new(state)) as *mut std::ffi::c_void;
+    (*ctx).get_format = Some(get_hw_format);
+
+    // Now we need to set the hw_device_ctx in the decoder context
+    (*ctx).hw_device_ctx = hw_device_ctx;
+    if options.hw_extra_frames > 0 {
+        (*ctx).extra_hw_frames = options.hw_extra_frames as i32;
+    }
+}
+
+/// Opens a decoder for `parameters` on the first backend the options allow
+/// that works. A backend whose device opens but whose decoder then doesn't
+/// falls through to the next one.
+unsafe fn open_decoder(
+    parameters: codec::Parameters,
+    options: &MediaFrameDecoderOptions,
+) -> Result<OpenDecoder, MediaLibError> {
+    // Checked before any device is tried, each would fail the same way.
+    check_hw_pool_size(options)?;
+    let hw_device = options.hw_device.as_ref().map(|device| &**device);
+
+    // Quick Sync decodes with its own decoders (h264_qsv...) rather than as
+    // a hwaccel of the native one.
+    let id = parameters.id();
+    let configs = ffmpeg_next::decoder::find(id)
+        .map(|native| device_configs(&native))
+        .unwrap_or_default();
+    let backends = match options.hw_backend {
+        HardwareBackend::Auto if options.hw_device_types.is_empty() => {
+            let device_types: Vec<AVHWDeviceType> =
+                configs.iter().map(|config| config.device_type).collect();
+            default_backends(&device_types)
+        }
+        HardwareBackend::Auto => options
+            .hw_device_types
+            .iter()
+            .map(|name| parse_backend(name))
+            .collect::<Result<Vec<_>, _>>()?,
+        HardwareBackend::SoftwareOnly => vec![Backend::Software],
+        forced => vec![parse_backend(
+            forced.device_type_name().unwrap_or("software"),
+        )?],
+    };
+    // Fuzzing builds only ever decode in software, a device would make runs
+    // depend on the machine and hide the software decoders behind it.
+    let backends = if cfg!(feature = "fuzzing") {
+        vec![Backend::Software]
+    } else {
+        backends
+    };
+    let tried: Vec<String> = backends.iter().map(|backend| backend.name()).collect();
+
+    for backend in backends {
+        if let Backend::Software = backend {
+            return open_on(parameters, None, options);
+        }
+        if let Some(hardware) = open_backend(backend, id, &configs, hw_device) {
+            match open_on(parameters.clone(), Some(hardware), options) {
+                Ok(opened) => return Ok(opened),
+                Err(e) => log::info!("Not decoding with {}: {}", backend.name(), e),
             }
+        }
+    }
+    Err(MediaLibError::FFmpegError(
+        format!("Couldn't decode the input with {}", tried.join(" or ")).into(),
+    ))
+}
 
-            let hw_config = &*config;
-            // Check if this config uses a hardware device context
-            if (hw_config.methods
-                & ffmpeg_next::ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32)
-                != 0
-            {
-                // This is a valid hardware acceleration method
-                hw_pixel_format = hw_config.pix_fmt;
-                device_type = hw_config.device_type;
-
-                let state = DecoderContextState {
-                    device_type,
-                    pix_fmt: hw_pixel_format,
-                };
-
-                // We then need to inject the state into the decoder context
-                (*decoder_context_ptr).opaque =
-                    Box::into_raw(Box::new(state)) as *mut std::ffi::c_void;
-                (*decoder_context_ptr).get_format = Some(get_hw_format);
-
-                // try to initialize the hardware acceleration
-                if av_hwdevice_ctx_create(&mut hw_device_ctx, device_type, null(), null_mut(), 0)
-                    < 0
-                {
-                    // This particular hardware acceleration type isn't supported
-                    log::error!(
-                        "Hardware acceleration type {:?} isn't supported",
-                        device_type
-                    );
-                    // Revert our shared state to the default
-                    hw_pixel_format = ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_NONE;
-                    continue;
+/// A device a decoder is opened on, and the decoder to open when it isn't
+/// the native one.
+pub struct Hardware {
+    pub device_type: AVHWDeviceType,
+    pub pix_fmt: ffmpeg_next::ffi::AVPixelFormat,
+    pub device: *mut AVBufferRef,
+    pub codec: Option<Codec>,
+}
+
+/// Opens the device `backend` decodes `id` on, with `configs` the native
+/// decoder's. None when there's no decoder or device for it, or for
+/// software, which needs neither.
+pub unsafe fn open_backend(
+    backend: Backend,
+    id: codec::Id,
+    configs: &[&AVCodecHWConfig],
+    hw_device: Option<&str>,
+) -> Option<Hardware> {
+    match backend {
+        Backend::Software => None,
+        Backend::QuickSync => match quick_sync_decoder(id, hw_device) {
+            Some((codec, device)) => Some(Hardware {
+                device_type: AVHWDeviceType::AV_HWDEVICE_TYPE_QSV,
+                pix_fmt: ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_QSV,
+                device,
+                codec: Some(codec),
+            }),
+            None => {
+                log::info!("Not decoding with qsv: no Quick Sync decoder or device");
+                None
+            }
+        },
+        Backend::Device(candidate) => {
+            let Some(config) = configs
+                .iter()
+                .find(|config| config.device_type == candidate)
+            else {
+                log::info!(
+                    "Not decoding with {}: the {:?} decoder doesn't support it",
+                    device_type_name(candidate),
+                    id
+                );
+                return None;
+            };
+            match open_device(candidate, hw_device) {
+                Ok(device) => Some(Hardware {
+                    device_type: candidate,
+                    pix_fmt: config.pix_fmt,
+                    device,
+                    codec: None,
+                }),
+                Err(e) => {
+                    log::info!("Not decoding with {}: {}", device_type_name(candidate), e);
+                    None
                 }
+            }
+        }
+    }
+}
+
+/// Opens a decoder for `parameters` on `hardware`, in software without.
+/// The decoder context owns the device, so it's released along with the
+/// context when opening fails.
+unsafe fn open_on(
+    parameters: codec::Parameters,
+    hardware: Option<Hardware>,
+    options: &MediaFrameDecoderOptions,
+) -> Result<OpenDecoder, MediaLibError> {
+    let mut decoder_context = Context::from_parameters(parameters)
+        .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+    let decoder_context_ptr = decoder_context.as_mut_ptr();
+    if let Some(hardware) = &hardware {
+        attach_hardware(
+            decoder_context_ptr,
+            hardware.device_type,
+            hardware.pix_fmt,
+            hardware.device,
+            options,
+        );
+    }
+
+    // One thread decodes the same way every run, and frames too big to be
+    // worth fuzzing fail before they're allocated.
+    #[cfg(feature = "fuzzing")]
+    {
+        (*decoder_context_ptr).thread_count = 1;
+        (*decoder_context_ptr).max_pixels = super::fuzz::MAX_PIXELS;
+    }
+
+    // The object underlying decoder is just the decoder context
+    let decoder = decoder_context.decoder();
+
+    let video_decoder = match hardware.as_ref().and_then(|hardware| hardware.codec) {
+        Some(codec) => decoder.open_as(codec).and_then(|opened| opened.video()),
+        None => decoder.video(),
+    }
+    .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
 
-                // Now we need to set the hw_device_ctx in the decoder context
-                (*decoder_context_ptr).hw_device_ctx = hw_device_ctx;
+    let codec = video_decoder
+        .codec()
+        .ok_or(MediaLibError::FFmpegError("Failed to find codec".into()))?;
 
-                log::info!("Hardware acceleration type {:?} is supported", device_type);
-                println!("Hardware acceleration type {:?} is supported", device_type);
-                hardware_accelerated = true;
-                break;
+    if let Some(hardware) = &hardware {
+        log::info!(
+            "Hardware acceleration type {:?} is supported",
+            hardware.device_type
+        );
+    }
+
+    Ok(match hardware {
+        Some(hardware) => OpenDecoder {
+            video_decoder,
+            codec,
+            hw_device_ctx: hardware.device,
+            hardware_accelerated: true,
+            pix_fmt: hardware.pix_fmt,
+            device_type: hardware.device_type,
+        },
+        None => OpenDecoder {
+            video_decoder,
+            codec,
+            hw_device_ctx: null_mut(),
+            hardware_accelerated: false,
+            pix_fmt: ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_NONE,
+            device_type: AVHWDeviceType::AV_HWDEVICE_TYPE_NONE,
+        },
+    })
+}
+
+impl HardwareAcceleratedVideoDecoder {
+    pub unsafe fn new(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let diagnostics_dir = diagnostics_dir(options);
+        // Input stream for the file or URL.
+        let force_format = options.force_format.as_ref().map(|format| &**format);
+        let input_options = InputOptions {
+            timeout_ms: options.network_timeout_ms,
+            user_agent: options.user_agent.as_ref().map(|user_agent| &**user_agent),
+            memory_map: options.memory_map,
+        };
+        let opened = open_input_with_options(
+            input_path,
+            force_format,
+            &input_options,
+            &options.decryption,
+        )
+        .and_then(|ictx| Self::with_input(ictx, options));
+        match opened {
+            Ok(mut decoder) => {
+                decoder.input_path = Some(input_path.to_path_buf());
+                Ok(decoder)
             }
+            Err(e) => {
+                if let Some(directory) = diagnostics_dir {
+                    let report = FailureReport {
+                        input_path: Some(input_path),
+                        force_format,
+                        error: &e,
+                        hardware_device: None,
+                        filter_graph: None,
+                    };
+                    write_diagnostics(&directory, &report);
+                }
+                Err(e)
+            }
+        }
+    }
 
-            i += 1;
+    /// Decodes from a reader instead of a file, e.g. a video fetched from
+    /// object storage into memory.
+    pub unsafe fn from_reader(
+        reader: Box<dyn ReadSeek>,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let diagnostics_dir = diagnostics_dir(options);
+        let force_format = options.force_format.as_ref().map(|format| &**format);
+        let opened =
+            open_reader(reader, force_format).and_then(|ictx| Self::with_input(ictx, options));
+        if let (Err(e), Some(directory)) = (&opened, diagnostics_dir) {
+            let report = FailureReport {
+                input_path: None,
+                force_format,
+                error: e,
+                hardware_device: None,
+                filter_graph: None,
+            };
+            write_diagnostics(&directory, &report);
         }
+        opened
+    }
+
+    unsafe fn with_input(
+        ictx: MediaInput,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        // Find the video stream
+        let input = match options.stream_index.as_ref() {
+            Some(&index) => ictx
+                .stream(index as usize)
+                .filter(|stream| stream.parameters().medium() == ffmpeg_next::media::Type::Video)
+                .ok_or_else(|| {
+                    MediaLibError::FFmpegError(
+                        format!("Input has no video stream {}", index).into(),
+                    )
+                })?,
+            None => ictx
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .ok_or_else(|| no_video_stream(&ictx))?,
+        };
+
+        let video_stream_index = input.index();
+        let time_base = input.time_base();
+        let frame_rate = input.avg_frame_rate();
+
+        let pool_key = options
+            .reuse_decoders
+            .then(|| PoolKey::new(&input.parameters(), time_base, options));
+        let opened = match pool_key.as_ref().and_then(decoder_pool::take) {
+            Some(pooled) => {
+                log::debug!("Reusing a pooled {} decoder", pooled.codec.name());
+                pooled
+            }
+            None => open_decoder(input.parameters(), options)?,
+        };
+        let decimator = Decimator::new(options, time_base)?;
 
         Ok(HardwareAcceleratedVideoDecoder {
-            codec,
+            codec: opened.codec,
             ictx,
-            video_decoder,
-            hw_device_ctx,
-            hardware_accelerated,
-            pix_fmt: hw_pixel_format,
-            device_type,
+            video_decoder: ManuallyDrop::new(opened.video_decoder),
+            hw_device_ctx: opened.hw_device_ctx,
+            hardware_accelerated: opened.hardware_accelerated,
+            pix_fmt: opened.pix_fmt,
+            device_type: opened.device_type,
             eof_sent: false,
             video_stream_index,
+            time_base,
+            frame_rate,
+            keyframes_only: true,
+            download: true,
+            prescale: None,
+            prescale_tone_mapping: ToneMapping::Off,
+            hw_scaler: None,
+            decimator,
+            seek_target_us: None,
+            end_us: None,
+            realtime: options.realtime.then(|| RealtimeClock {
+                max_latency: Duration::from_millis(options.max_latency_ms as u64),
+                anchor: None,
+                watermarks: (options.high_watermark_ms > 0).then(|| {
+                    let high_us = options.high_watermark_ms as i64 * 1000;
+                    // The low watermark can't be above the high one.
+                    let low_us = (options.low_watermark_ms as i64 * 1000).min(high_us);
+                    (low_us, high_us)
+                }),
+                paused: false,
+            }),
+            waiting_for_keyframe: false,
+            dropped_frames: 0,
+            frames_decoded: 0,
+            dts_continuity: Continuity::new(),
+            warnings: Vec::new(),
+            health: HealthMonitor::new(options),
+            diagnostics_dir: diagnostics_dir(options),
+            input_path: None,
+            force_format: options
+                .force_format
+                .as_ref()
+                .map(|format| format.to_string()),
+            pool_key,
         })
     }
 
     pub fn get_frame(&mut self) -> Option<Result<ffmpeg_next::frame::Video, MediaLibError>> {
-        while !self.eof_sent {
-            match self.ictx.packets().next() {
-                Some((stream, packet)) => {
-                    if stream.index() == self.video_stream_index {
-                        if let Err(e) = self.video_decoder.send_packet(&packet) {
+        if self.ictx.is_interrupted() {
+            self.pool_key = None;
+            return Some(Err(MediaLibError::Cancelled("Cancelled".into())));
+        }
+        let result = self.decode_frame();
+        if let (Some(Ok(frame)), Some(end_us)) = (&result, self.end_us) {
+            let start_us = self.start_time_us();
+            if self
+                .frame_time_us(frame)
+                .is_some_and(|time_us| time_us - start_us > end_us)
+            {
+                return None;
+            }
+        }
+        if let Some(Err(e)) = &result {
+            // A decoder that failed isn't trusted with the next input.
+            self.pool_key = None;
+            if !e.is_cancelled() {
+                self.write_diagnostics(e);
+            }
+        }
+        result
+    }
+
+    /// Set from any thread to stop decoding, blocked reads are aborted and
+    /// `get_frame` fails with `Cancelled` from then on.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.ictx.interrupt_flag()
+    }
+
+    fn write_diagnostics(&mut self, error: &MediaLibError) {
+        let Some(directory) = self.diagnostics_dir.take() else {
+            return;
+        };
+        let report = FailureReport {
+            input_path: self.input_path.as_deref(),
+            force_format: self.force_format.as_deref(),
+            error,
+            hardware_device: self.hardware_device_name(),
+            filter_graph: self.hw_scaler.as_ref().map(HardwareScaler::dump),
+        };
+        write_diagnostics(&directory, &report);
+    }
+
+    fn decode_frame(&mut self) -> Option<Result<ffmpeg_next::frame::Video, MediaLibError>> {
+        // Goes round again for every frame skipped, until one is returned.
+        loop {
+            while !self.eof_sent {
+                match self.ictx.next_packet() {
+                    Some(Err(e)) => return Some(Err(e)),
+                    Some(Ok((stream_index, packet))) => {
+                        self.health.on_packet(packet.size());
+                        if self.ictx.recovered() {
+                            self.health.on_reconnect();
+                        }
+                        if stream_index == self.video_stream_index {
+                            self.check_packet(&packet);
+                            if self.should_drop(&packet) {
+                                continue;
+                            }
+                            if let Err(e) = self.video_decoder.send_packet(&packet) {
+                                return Some(Err(MediaLibError::FFmpegError(e.to_string().into())));
+                            }
+                            break; // Exit the loop after sending a video packet
+                        }
+                        // Continue looping if it's not a video packet
+                    }
+                    None => {
+                        if let Err(e) = self.video_decoder.send_eof() {
                             return Some(Err(MediaLibError::FFmpegError(e.to_string().into())));
                         }
-                        break; // Exit the loop after sending a video packet
+                        self.eof_sent = true;
+                        break; // Exit the loop after sending EOF
+                    }
+                }
+            }
+
+            let mut decoded = ffmpeg_next::frame::Video::empty();
+            return match self.video_decoder.receive_frame(&mut decoded) {
+                Ok(_) => {
+                    self.frames_decoded += 1;
+                    let frame_format = unsafe { *decoded.as_ptr() }.format;
+                    let is_key = decoded.is_key();
+
+                    // The frame a seek lands on is returned even when it isn't a
+                    // keyframe, it's what the caller asked for.
+                    if let Some(target_us) = self.seek_target_us {
+                        if self.is_before(&decoded, target_us) {
+                            continue;
+                        }
+                        self.seek_target_us = None;
+                    } else if self.keyframes_only && !is_key {
+                        continue;
+                    }
+                    if let Some(decimator) = &mut self.decimator {
+                        match decimator.keep(&decoded) {
+                            Ok(true) => {}
+                            Ok(false) => continue,
+                            Err(e) => return Some(Err(e)),
+                        }
+                    }
+
+                    if self.hardware_accelerated && frame_format == self.pix_fmt as i32 {
+                        // okay now we need to transfer the frame to a software frame
+                        let mut prescaled = self.prescale_frame(&decoded);
+                        if let Some(mut downloaded) = prescaled
+                            .take_if(|frame| unsafe { (*frame.as_ptr()).hw_frames_ctx.is_null() })
+                        {
+                            // The scaler downloaded it already, see `HardwareScaler`.
+                            unsafe {
+                                av_frame_copy_props(downloaded.as_mut_ptr(), decoded.as_ptr())
+                            };
+                            if self
+                                .hw_scaler
+                                .as_ref()
+                                .is_some_and(HardwareScaler::tone_maps)
+                            {
+                                tonemap::tag_as_mapped(&mut downloaded);
+                            }
+                            return Some(Ok(downloaded));
+                        }
+                        if !self.download {
+                            return Some(Ok(match prescaled {
+                                Some(mut scaled) => {
+                                    unsafe {
+                                        av_frame_copy_props(scaled.as_mut_ptr(), decoded.as_ptr())
+                                    };
+                                    scaled
+                                }
+                                None => decoded,
+                            }));
+                        }
+                        let source = prescaled.as_ref().unwrap_or(&decoded);
+                        let mut sw_frame = ffmpeg_next::frame::Video::empty();
+                        unsafe {
+                            let res =
+                                av_hwframe_transfer_data(sw_frame.as_mut_ptr(), source.as_ptr(), 0);
+                            if res < 0 {
+                                return Some(Err(MediaLibError::FFmpegError(
+                                    format!("Failed to transfer frame: {}", res).into(),
+                                )));
+                            }
+                            // The transfer only copies pixels, keep the timestamps.
+                            av_frame_copy_props(sw_frame.as_mut_ptr(), decoded.as_ptr());
+
+                            return Some(Ok(sw_frame));
+                        };
                     }
-                    // Continue looping if it's not a video packet
+
+                    Some(Ok(decoded))
                 }
-                None => {
-                    if let Err(e) = self.video_decoder.send_eof() {
-                        return Some(Err(MediaLibError::FFmpegError(e.to_string().into())));
+                Err(ffmpeg_next::Error::Other { errno }) => {
+                    if errno == EAGAIN {
+                        continue;
+                    } else {
+                        Some(Err(MediaLibError::FFmpegError(errno.to_string().into())))
                     }
-                    self.eof_sent = true;
-                    break; // Exit the loop after sending EOF
                 }
-            }
+                Err(ffmpeg_next::Error::Eof) => None,
+                Err(e) => Some(Err(MediaLibError::FFmpegError(e.to_string().into()))),
+            };
         }
+    }
 
-        let mut decoded = ffmpeg_next::frame::Video::empty();
-        match self.video_decoder.receive_frame(&mut decoded) {
-            Ok(_) => {
-                let frame_format = unsafe { *decoded.as_ptr() }.format;
-                let is_key = decoded.is_key();
+    /// How the input has been arriving, see `HealthMonitor`.
+    pub fn stream_health(&self) -> StreamHealth {
+        self.health.health()
+    }
 
-                if !is_key {
-                    return self.get_frame();
+    pub fn set_health_observer(&mut self, observer: StreamHealthObserverBox) {
+        self.health.set_observer(observer);
+    }
+
+    /// Scales hardware frames down to `size` on the device before they are
+    /// downloaded. Only takes effect when decoding on hardware and `size` is
+    /// smaller than the input, frames are otherwise returned at full size.
+    /// HDR frames are tone mapped with `tone_mapping` after scaling, which
+    /// downloads them whatever `download` is.
+    pub fn set_prescale(&mut self, size: Size, tone_mapping: ToneMapping) {
+        let full_size = self.size();
+        if !self.hardware_accelerated
+            || size.is_empty()
+            || (size.width >= full_size.width && size.height >= full_size.height)
+        {
+            return;
+        }
+        // The scale filters mostly need even sizes.
+        self.prescale = Some(Size::new((size.width + 1) & !1, (size.height + 1) & !1));
+        self.prescale_tone_mapping = tone_mapping;
+        self.hw_scaler = None;
+    }
+
+    /// Ends decoding after the frame shown at `end_us` (from the start of
+    /// the input), `get_frame` returns none for any frame after it.
+    pub fn set_end(&mut self, end_us: i64) {
+        self.end_us = Some(end_us);
+    }
+
+    /// `decoded` scaled on the device, none when it's to be downloaded as
+    /// is. Falls back to full size downloads for good once scaling fails.
+    fn prescale_frame(
+        &mut self,
+        decoded: &ffmpeg_next::frame::Video,
+    ) -> Option<ffmpeg_next::frame::Video> {
+        let size = self.prescale?;
+        if !self
+            .hw_scaler
+            .as_ref()
+            .is_some_and(|scaler| scaler.is_for(decoded))
+        {
+            match HardwareScaler::new(decoded, self.device_type, size, self.prescale_tone_mapping) {
+                Ok(scaler) => {
+                    if let Some(reason) = scaler.fallback_reason() {
+                        self.warn(format!(
+                            "Scaling on the device is unavailable ({}), scaling in software",
+                            reason
+                        ));
+                    }
+                    self.hw_scaler = Some(scaler);
                 }
+                Err(e) => {
+                    self.warn(format!("Scaling on the device is unavailable: {}", e));
+                    self.prescale = None;
+                    return None;
+                }
+            }
+        }
 
-                if self.hardware_accelerated && frame_format == self.pix_fmt as i32 {
-                    // okay now we need to transfer the frame to a software frame
-                    let mut sw_frame = ffmpeg_next::frame::Video::empty();
-                    unsafe {
-                        let res =
-                            av_hwframe_transfer_data(sw_frame.as_mut_ptr(), decoded.as_ptr(), 0);
-                        if res < 0 {
-                            return Some(Err(MediaLibError::FFmpegError(
-                                format!("Failed to transfer frame: {}", res).into(),
-                            )));
-                        }
+        let scaled = self.hw_scaler.as_mut()?.run(decoded);
+        match scaled {
+            Ok(scaled) => Some(scaled),
+            Err(e) => {
+                self.warn(format!("Failed to scale on the device: {}", e));
+                self.prescale = None;
+                self.hw_scaler = None;
+                None
+            }
+        }
+    }
 
-                        return Some(Ok(sw_frame));
-                    };
+    fn warn(&mut self, warning: String) {
+        if self.warnings.len() < MAX_WARNINGS {
+            log::warn!("{}", warning);
+            self.warnings.push(warning);
+        } else if self.warnings.len() == MAX_WARNINGS {
+            self.warnings
+                .push("Further warnings suppressed".to_string());
+        }
+    }
+
+    /// Records corrupt packets and timestamp discontinuities in the video
+    /// stream as warnings.
+    fn check_packet(&mut self, packet: &ffmpeg_next::Packet) {
+        let dts_us = packet
+            .dts()
+            .map(|dts| timestamp::to_us(dts, self.time_base));
+
+        if packet.is_corrupt() {
+            self.warn(format!("Corrupt video packet at {:?}us", dts_us));
+        }
+
+        if let Some(dts) = dts_us {
+            match self.dts_continuity.check(dts, MAX_PACKET_GAP_US) {
+                Some(Discontinuity::Backwards { from_us, to_us }) => {
+                    self.health.on_gap();
+                    self.warn(format!(
+                        "Timestamps went backwards from {}us to {}us",
+                        from_us, to_us
+                    ));
+                }
+                Some(Discontinuity::Gap { from_us, to_us }) => {
+                    self.health.on_gap();
+                    self.warn(format!(
+                        "Gap of {}us in timestamps at {}us",
+                        to_us - from_us,
+                        to_us
+                    ));
                 }
+                None => {}
+            }
+            self.health.on_video_packet(dts);
+        }
+    }
 
-                // if we're hardware accelerated, we need to convert the frame to a software frame
-                // if is_key {
-                Some(Ok(decoded))
-                // } else {
-                //     println!("nutbar get frame");
-                //     self.get_frame()
-                // }
-            }
-            Err(ffmpeg_next::Error::Other { errno }) => {
-                if errno == NEED_MORE_DATA {
-                    println!("nutbar need more data");
-                    self.get_frame()
+    /// Hardware device type used for decoding, none when decoding in software.
+    /// How the input is being decoded, for logging: demuxer, decoder,
+    /// hardware path, pixel formats and the scale filter graph. The graph is
+    /// only set up with the first frame, describe the pipeline after that
+    /// to see it.
+    pub fn pipeline_description(&self) -> String {
+        let raw = unsafe { &*self.video_decoder.as_ptr() };
+        let mut lines = vec![
+            format!("input: {}", self.ictx.format().name()),
+            format!("decoder: {}", self.codec.name()),
+        ];
+        if self.hardware_accelerated {
+            lines.push(format!(
+                "hardware: {} ({} frames of {}), {}",
+                device_type_name(self.device_type),
+                pixel_format_name(self.pix_fmt),
+                pixel_format_name(raw.sw_pix_fmt),
+                if self.download {
+                    "downloaded to system memory"
                 } else {
-                    Some(Err(MediaLibError::FFmpegError(errno.to_string().into())))
+                    "left on the device"
                 }
+            ));
+        } else {
+            lines.push(format!(
+                "hardware: none, decoding {} in software",
+                pixel_format_name(raw.pix_fmt)
+            ));
+        }
+        match (self.prescale, &self.hw_scaler) {
+            (Some(size), Some(scaler)) => {
+                lines.push(format!(
+                    "prescale: {}x{} {}{}",
+                    size.width,
+                    size.height,
+                    match scaler.fallback_reason() {
+                        Some(reason) => format!("in software after downloading ({})", reason),
+                        None => "on the device".to_string(),
+                    },
+                    if scaler.tone_maps() {
+                        ", then tone mapped to SDR in software"
+                    } else {
+                        ""
+                    }
+                ));
+                lines.push(format!("filter graph:\n{}", scaler.dump().trim_end()));
             }
-            Err(ffmpeg_next::Error::Eof) => None,
-            Err(e) => Some(Err(MediaLibError::FFmpegError(e.to_string().into()))),
+            (Some(size), None) => lines.push(format!(
+                "prescale: {}x{}, set up with the first frame",
+                size.width, size.height
+            )),
+            (None, _) => lines.push("prescale: none".to_string()),
+        }
+        lines.join("\n")
+    }
+
+    pub fn hardware_device_name(&self) -> Option<String> {
+        self.hardware_accelerated
+            .then(|| device_type_name(self.device_type))
+    }
+
+    /// Applies the realtime drop policy to a video packet. Once we're late
+    /// every packet is dropped until a keyframe that is on time comes along,
+    /// at which point the decoder is flushed and starts again from there.
+    fn should_drop(&mut self, packet: &ffmpeg_next::Packet) -> bool {
+        let Some(clock) = &mut self.realtime else {
+            return false;
+        };
+        let Some(pts) = packet.pts() else {
+            return false;
+        };
+
+        let pts_us = timestamp::to_us(pts, self.time_base);
+        if clock.is_late(pts_us) || (self.waiting_for_keyframe && !packet.is_key()) {
+            self.waiting_for_keyframe = true;
+            self.dropped_frames += 1;
+            return true;
+        }
+
+        if self.waiting_for_keyframe {
+            log::info!(
+                "Resuming realtime decode after dropping {} frames",
+                self.dropped_frames
+            );
+            self.video_decoder.flush();
+            self.waiting_for_keyframe = false;
+        }
+        false
+    }
+
+    /// Whether the host should hold off feeding the input, only ever
+    /// `Paused` in realtime mode with watermarks set.
+    pub fn backpressure(&self) -> Backpressure {
+        match &self.realtime {
+            Some(clock) if clock.paused => Backpressure::Paused,
+            _ => Backpressure::Flowing,
+        }
+    }
+
+    /// Presentation time of a decoded frame in microseconds, in the stream's
+    /// own timeline.
+    pub fn frame_time_us(&self, frame: &ffmpeg_next::frame::Video) -> Option<i64> {
+        frame
+            .timestamp()
+            .map(|pts| timestamp::to_us(pts, self.time_base))
+    }
+
+    /// Container duration in microseconds, if known.
+    pub fn duration_us(&self) -> Option<i64> {
+        let duration = self.ictx.duration();
+        (duration > 0).then_some(duration)
+    }
+
+    /// Start time of the input in microseconds, subtract it from
+    /// `frame_time_us` to measure from the start of the input.
+    pub fn start_time_us(&self) -> i64 {
+        let start_time = unsafe { (*self.ictx.as_ptr()).start_time };
+        if start_time == ffmpeg_next::ffi::AV_NOPTS_VALUE {
+            0
+        } else {
+            start_time
         }
     }
 
-    pub fn width(&self) -> u32 {
-        self.video_decoder.width()
+    /// Seeks to the keyframe at or before `timestamp_us`, measured from the
+    /// start of the input. The next call to `get_frame` returns that keyframe.
+    pub fn seek_to_keyframe(&mut self, timestamp_us: i64) -> Result<(), MediaLibError> {
+        let target = self.start_time_us() + timestamp_us;
+        self.ictx
+            .seek(target, ..target)
+            .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+
+        // Drop anything buffered from before the seek.
+        self.video_decoder.flush();
+        self.dts_continuity.reset();
+        self.eof_sent = false;
+        self.waiting_for_keyframe = false;
+        self.seek_target_us = None;
+        if let Some(decimator) = &mut self.decimator {
+            decimator.reset();
+        }
+        Ok(())
+    }
+
+    /// Seeks to exactly `timestamp_us`, measured from the start of the
+    /// input. The decoder restarts from the keyframe before it and decodes
+    /// forward, the next call to `get_frame` returns the frame shown at that
+    /// time.
+    pub fn seek(&mut self, timestamp_us: i64) -> Result<(), MediaLibError> {
+        self.seek_to_keyframe(timestamp_us)?;
+        self.seek_target_us = Some(self.start_time_us() + timestamp_us);
+        Ok(())
+    }
+
+    /// Whether `frame` is over before `target_us`, so the frame after it is
+    /// still at or before the target. Without a known frame rate only frames
+    /// that start before the target count.
+    fn is_before(&self, frame: &ffmpeg_next::frame::Video, target_us: i64) -> bool {
+        let Some(time_us) = self.frame_time_us(frame) else {
+            return false;
+        };
+        let frame_duration_us = timestamp::frame_duration_us(self.frame_rate).unwrap_or(1);
+        time_us + frame_duration_us <= target_us
+    }
+
+    /// A metadata tag of the video stream, or of the input or its other
+    /// streams when the video stream doesn't have it (e.g. `timecode` on a
+    /// tmcd track).
+    pub fn tag(&self, key: &str) -> Option<String> {
+        let tag_of = |metadata: ffmpeg_next::DictionaryRef| metadata.get(key).map(str::to_string);
+        self.ictx
+            .stream(self.video_stream_index)
+            .and_then(|stream| tag_of(stream.metadata()))
+            .or_else(|| tag_of(self.ictx.metadata()))
+            .or_else(|| {
+                self.ictx
+                    .streams()
+                    .find_map(|stream| tag_of(stream.metadata()))
+            })
     }
 
-    pub fn height(&self) -> u32 {
-        self.video_decoder.height()
+    /// The spherical video metadata of the video stream, none unless it's
+    /// 360° video.
+    pub fn spherical(&self) -> Option<SphericalMetadata> {
+        self.ictx
+            .stream(self.video_stream_index)
+            .and_then(|stream| spherical::spherical_metadata(&stream))
+    }
+
+    /// The EXIF orientation that shows the video stream upright, see
+    /// `still::stream_orientation`.
+    pub fn orientation(&self) -> u8 {
+        self.ictx
+            .stream(self.video_stream_index)
+            .map_or(1, |stream| still::stream_orientation(&stream))
+    }
+
+    /// Average frame rate of the video stream, 0/0 if the container
+    /// doesn't say.
+    pub fn frame_rate(&self) -> Rational {
+        self.frame_rate
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.video_decoder.width(), self.video_decoder.height())
     }
 
     pub fn format(&self) -> ffmpeg_next::format::Pixel {
         self.video_decoder.format()
     }
 }
+
+impl Drop for HardwareAcceleratedVideoDecoder {
+    fn drop(&mut self) {
+        let mut video_decoder = unsafe { ManuallyDrop::take(&mut self.video_decoder) };
+        if let Some(key) = self.pool_key.take() {
+            video_decoder.flush();
+            let decoder = OpenDecoder {
+                video_decoder,
+                codec: self.codec,
+                hw_device_ctx: self.hw_device_ctx,
+                hardware_accelerated: self.hardware_accelerated,
+                pix_fmt: self.pix_fmt,
+                device_type: self.device_type,
+            };
+            decoder_pool::put(key, decoder);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(backends: &[Backend]) -> Vec<String> {
+        backends.iter().map(|backend| backend.name()).collect()
+    }
+
+    #[test]
+    fn it_orders_backends_by_platform() {
+        let backends = default_backends(&[
+            AVHWDeviceType::AV_HWDEVICE_TYPE_DXVA2,
+            AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+            AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+        ]);
+        let expected: &[&str] = if QUICK_SYNC_PLATFORM {
+            &["cuda", "qsv", "vaapi", "dxva2", "software"]
+        } else {
+            &["cuda", "vaapi", "dxva2", "software"]
+        };
+        assert_eq!(names(&backends), expected);
+    }
+
+    #[test]
+    fn it_shares_preheated_devices() {
+        let device_type = AVHWDeviceType::AV_HWDEVICE_TYPE_VDPAU;
+        unsafe {
+            let device = ffmpeg_next::ffi::av_buffer_alloc(1);
+            keep_preheated(device_type, device);
+
+            let mut shared = open_device(device_type, None).unwrap();
+            assert_eq!((*shared).data, (*device).data);
+            assert_eq!(ffmpeg_next::ffi::av_buffer_get_ref_count(device), 2);
+            av_buffer_unref(&mut shared);
+            assert_eq!(ffmpeg_next::ffi::av_buffer_get_ref_count(device), 1);
+        }
+        assert!(preheated_device(AVHWDeviceType::AV_HWDEVICE_TYPE_DRM).is_none());
+    }
+
+    #[test]
+    fn it_sizes_the_hardware_frame_pool() {
+        let options = MediaFrameDecoderOptions {
+            hw_initial_pool_size: 32,
+            hw_extra_frames: 4,
+            ..Default::default()
+        };
+        assert!(check_hw_pool_size(&options).is_ok());
+        unsafe {
+            let mut ctx = ffmpeg_next::ffi::avcodec_alloc_context3(null());
+            attach_hardware(
+                ctx,
+                AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+                ffmpeg_next::ffi::AVPixelFormat::AV_PIX_FMT_CUDA,
+                null_mut(),
+                &options,
+            );
+            let state = Box::from_raw((*ctx).opaque as *mut DecoderContextState);
+            assert_eq!(state.initial_pool_size, 36);
+            assert_eq!((*ctx).extra_hw_frames, 4);
+            (*ctx).opaque = null_mut();
+            ffmpeg_next::ffi::avcodec_free_context(&mut ctx);
+        }
+
+        let too_big = [
+            (MAX_HW_POOL_SIZE + 1, 0),
+            (0, MAX_HW_POOL_SIZE + 1),
+            (MAX_HW_POOL_SIZE, 1),
+            (u32::MAX, u32::MAX),
+        ];
+        for (hw_initial_pool_size, hw_extra_frames) in too_big {
+            let options = MediaFrameDecoderOptions {
+                hw_initial_pool_size,
+                hw_extra_frames,
+                ..Default::default()
+            };
+            assert!(check_hw_pool_size(&options).is_err());
+        }
+    }
+}
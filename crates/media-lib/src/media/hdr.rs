@@ -0,0 +1,84 @@
+use ffmpeg_next::ffi::{
+    av_frame_get_side_data, AVContentLightMetadata, AVFrameSideDataType,
+    AVMasteringDisplayMetadata, AVRational,
+};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{ContentLightLevel, MasteringDisplay};
+
+/// FFmpeg's name for a colour property, "unknown" for values it has no
+/// name for.
+fn color_name(name: Option<&'static str>) -> String {
+    name.unwrap_or("unknown").to_string()
+}
+
+pub fn color_space(frame: &Video) -> String {
+    color_name(frame.color_space().name())
+}
+
+pub fn color_primaries(frame: &Video) -> String {
+    color_name(frame.color_primaries().name())
+}
+
+pub fn color_transfer(frame: &Video) -> String {
+    color_name(frame.color_transfer_characteristic().name())
+}
+
+pub fn color_range(frame: &Video) -> String {
+    color_name(frame.color_range().name())
+}
+
+fn to_f64(value: AVRational) -> f64 {
+    if value.den == 0 {
+        return 0.0;
+    }
+    value.num as f64 / value.den as f64
+}
+
+/// The side data of `frame` of type `kind` as a `T`, none when it has none.
+unsafe fn side_data<T>(frame: &Video, kind: AVFrameSideDataType) -> Option<&T> {
+    let side_data = av_frame_get_side_data(frame.as_ptr(), kind);
+    if side_data.is_null() || (*side_data).size < std::mem::size_of::<T>() {
+        return None;
+    }
+    Some(&*((*side_data).data as *const T))
+}
+
+pub fn mastering_display(frame: &Video) -> Option<MasteringDisplay> {
+    let metadata: &AVMasteringDisplayMetadata = unsafe {
+        side_data(
+            frame,
+            AVFrameSideDataType::AV_FRAME_DATA_MASTERING_DISPLAY_METADATA,
+        )
+    }?;
+    if metadata.has_primaries == 0 && metadata.has_luminance == 0 {
+        return None;
+    }
+
+    // FFmpeg leaves whichever half is missing zeroed.
+    let [red, green, blue] = metadata.display_primaries;
+    Some(MasteringDisplay {
+        red_x: to_f64(red[0]),
+        red_y: to_f64(red[1]),
+        green_x: to_f64(green[0]),
+        green_y: to_f64(green[1]),
+        blue_x: to_f64(blue[0]),
+        blue_y: to_f64(blue[1]),
+        white_point_x: to_f64(metadata.white_point[0]),
+        white_point_y: to_f64(metadata.white_point[1]),
+        min_luminance: to_f64(metadata.min_luminance),
+        max_luminance: to_f64(metadata.max_luminance),
+    })
+}
+
+pub fn content_light_level(frame: &Video) -> Option<ContentLightLevel> {
+    let metadata: &AVContentLightMetadata = unsafe {
+        side_data(
+            frame,
+            AVFrameSideDataType::AV_FRAME_DATA_CONTENT_LIGHT_LEVEL,
+        )
+    }?;
+    Some(ContentLightLevel {
+        max_content: metadata.MaxCLL,
+        max_frame_average: metadata.MaxFALL,
+    })
+}
@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use media_types::{
+    MediaFrameDecoderOptions, StreamHealth, StreamHealthObserverBox, StreamHealthObserverDynMut,
+    StreamHealthStatus,
+};
+
+// Bitrate is averaged over this much of the past, and a gap or reconnect
+// keeps the input `Degraded` for as long.
+const WINDOW: Duration = Duration::from_secs(5);
+// How often the monitor thread checks for a stall.
+const TICK: Duration = Duration::from_millis(250);
+
+/// What's been seen of the input so far.
+struct Stats {
+    stall_timeout: Duration,
+    max_jitter_us: f64,
+    started: Instant,
+    // Arrival time and size of every packet within `WINDOW`.
+    arrivals: VecDeque<(Instant, usize)>,
+    last_packet: Option<Instant>,
+    // Arrival time and decode timestamp of the last video packet.
+    last_video: Option<(Instant, i64)>,
+    jitter_us: f64,
+    gaps: u32,
+    reconnects: u32,
+    // When the last gap or reconnect happened.
+    last_incident: Option<Instant>,
+}
+
+impl Stats {
+    fn health(&mut self, now: Instant) -> StreamHealth {
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) > WINDOW)
+        {
+            self.arrivals.pop_front();
+        }
+        let bits = self
+            .arrivals
+            .iter()
+            .map(|(_, size)| *size as u64)
+            .sum::<u64>()
+            * 8;
+        // Until the window has filled the bitrate is over what's passed.
+        let seconds = now
+            .saturating_duration_since(self.started)
+            .min(WINDOW)
+            .as_secs_f64()
+            .max(TICK.as_secs_f64());
+        let since_last_packet =
+            now.saturating_duration_since(self.last_packet.unwrap_or(self.started));
+
+        let status = if since_last_packet >= self.stall_timeout {
+            StreamHealthStatus::Stalled
+        } else if self.jitter_us > self.max_jitter_us
+            || self
+                .last_incident
+                .is_some_and(|at| now.saturating_duration_since(at) < WINDOW)
+        {
+            StreamHealthStatus::Degraded
+        } else {
+            StreamHealthStatus::Ok
+        };
+        StreamHealth {
+            status,
+            bitrate_bps: (bits as f64 / seconds) as u64,
+            jitter_ms: self.jitter_us / 1000.0,
+            since_last_packet_ms: since_last_packet.as_millis() as u64,
+            gaps: self.gaps,
+            reconnects: self.reconnects,
+        }
+    }
+}
+
+/// Keeps track of how a live input is arriving: bitrate, jitter, gaps and
+/// reconnects. With an observer set a thread of its own reports status
+/// changes, including stalls while the decoder is blocked reading.
+pub struct HealthMonitor {
+    stats: Arc<Mutex<Stats>>,
+    // Dropping the sender stops the thread.
+    observer_thread: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+fn lock(stats: &Mutex<Stats>) -> MutexGuard<'_, Stats> {
+    // The stats stay usable even if a thread panicked holding them.
+    stats.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl HealthMonitor {
+    pub fn new(options: &MediaFrameDecoderOptions) -> Self {
+        HealthMonitor {
+            stats: Arc::new(Mutex::new(Stats {
+                stall_timeout: Duration::from_millis(options.stall_timeout_ms as u64),
+                max_jitter_us: options.max_jitter_ms as f64 * 1000.0,
+                started: Instant::now(),
+                arrivals: VecDeque::new(),
+                last_packet: None,
+                last_video: None,
+                jitter_us: 0.0,
+                gaps: 0,
+                reconnects: 0,
+                last_incident: None,
+            })),
+            observer_thread: None,
+        }
+    }
+
+    /// A packet of `size` bytes from any stream has been read.
+    pub fn on_packet(&self, size: usize) {
+        let now = Instant::now();
+        let mut stats = lock(&self.stats);
+        stats.arrivals.push_back((now, size));
+        stats.last_packet = Some(now);
+    }
+
+    /// A video packet with decode timestamp `dts_us` has been read. Jitter
+    /// is estimated the way RTP does (RFC 3550), from how far the time
+    /// between arrivals strays from the time between timestamps.
+    pub fn on_video_packet(&self, dts_us: i64) {
+        let now = Instant::now();
+        let mut stats = lock(&self.stats);
+        if let Some((last_at, last_dts_us)) = stats.last_video {
+            let arrival_us = now.saturating_duration_since(last_at).as_micros() as f64;
+            let deviation_us = (arrival_us - (dts_us - last_dts_us) as f64).abs();
+            stats.jitter_us += (deviation_us - stats.jitter_us) / 16.0;
+        }
+        stats.last_video = Some((now, dts_us));
+    }
+
+    /// The video timestamps jumped. Jitter isn't measured across the jump.
+    pub fn on_gap(&self) {
+        let mut stats = lock(&self.stats);
+        stats.gaps += 1;
+        stats.last_video = None;
+        stats.last_incident = Some(Instant::now());
+    }
+
+    pub fn on_reconnect(&self) {
+        let mut stats = lock(&self.stats);
+        stats.reconnects += 1;
+        stats.last_video = None;
+        stats.last_incident = Some(Instant::now());
+    }
+
+    pub fn health(&self) -> StreamHealth {
+        lock(&self.stats).health(Instant::now())
+    }
+
+    /// Reports the health to `observer` now and on every status change
+    /// after, replacing the observer set before.
+    pub fn set_observer(&mut self, mut observer: StreamHealthObserverBox) {
+        self.stop_observer();
+        let stats = self.stats.clone();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut reported = None;
+            loop {
+                let health = lock(&stats).health(Instant::now());
+                if reported != Some(health.status) {
+                    reported = Some(health.status);
+                    observer.on_health_change(health);
+                }
+                if stopped.recv_timeout(TICK) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+        });
+        self.observer_thread = Some((stop, thread));
+    }
+
+    fn stop_observer(&mut self) {
+        if let Some((stop, thread)) = self.observer_thread.take() {
+            drop(stop);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.stop_observer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stalls after 2s, degrades above 50ms of jitter.
+    fn stats(started: Instant) -> Stats {
+        Stats {
+            stall_timeout: Duration::from_secs(2),
+            max_jitter_us: 50_000.0,
+            started,
+            arrivals: VecDeque::new(),
+            last_packet: None,
+            last_video: None,
+            jitter_us: 0.0,
+            gaps: 0,
+            reconnects: 0,
+            last_incident: None,
+        }
+    }
+
+    #[test]
+    fn it_is_ok_while_packets_arrive_evenly() {
+        let started = Instant::now();
+        let mut stats = stats(started);
+        let now = started + Duration::from_secs(1);
+        stats.arrivals.push_back((now, 1000));
+        stats.last_packet = Some(now);
+        stats.jitter_us = 49_000.0;
+
+        let health = stats.health(now);
+        assert_eq!(health.status, StreamHealthStatus::Ok);
+        // 1000 bytes over the one second since starting.
+        assert_eq!(health.bitrate_bps, 8000);
+        assert_eq!(health.jitter_ms, 49.0);
+    }
+
+    #[test]
+    fn it_is_degraded_above_the_jitter_threshold() {
+        let started = Instant::now();
+        let mut stats = stats(started);
+        stats.last_packet = Some(started);
+        stats.jitter_us = 51_000.0;
+        assert_eq!(stats.health(started).status, StreamHealthStatus::Degraded);
+    }
+
+    #[test]
+    fn it_is_degraded_for_a_window_after_a_gap() {
+        let started = Instant::now();
+        let mut stats = stats(started);
+        stats.gaps = 1;
+        stats.last_incident = Some(started);
+        for seconds in [0, 1, 4] {
+            let now = started + Duration::from_secs(seconds);
+            stats.last_packet = Some(now);
+            assert_eq!(stats.health(now).status, StreamHealthStatus::Degraded);
+        }
+        let now = started + WINDOW;
+        stats.last_packet = Some(now);
+        let health = stats.health(now);
+        assert_eq!(health.status, StreamHealthStatus::Ok);
+        assert_eq!(health.gaps, 1);
+    }
+
+    #[test]
+    fn it_is_stalled_once_nothing_arrives_for_the_timeout() {
+        let started = Instant::now();
+        let mut stats = stats(started);
+        stats.last_packet = Some(started);
+        stats.jitter_us = 100_000.0;
+
+        let health = stats.health(started + Duration::from_millis(1999));
+        assert_eq!(health.status, StreamHealthStatus::Degraded);
+        let health = stats.health(started + Duration::from_millis(2000));
+        assert_eq!(health.status, StreamHealthStatus::Stalled);
+        assert_eq!(health.since_last_packet_ms, 2000);
+    }
+
+    #[test]
+    fn it_only_averages_the_bitrate_over_the_window() {
+        let started = Instant::now();
+        let mut stats = stats(started);
+        stats.arrivals.push_back((started, 1_000_000));
+        let now = started + WINDOW + Duration::from_secs(1);
+        stats.arrivals.push_back((now, 5000));
+        stats.last_packet = Some(now);
+
+        let health = stats.health(now);
+        assert_eq!(health.bitrate_bps, 5000 * 8 / WINDOW.as_secs());
+        assert_eq!(stats.arrivals.len(), 1);
+    }
+
+    #[test]
+    fn it_counts_reconnects_as_incidents() {
+        let monitor = HealthMonitor::new(&MediaFrameDecoderOptions::default());
+        monitor.on_packet(100);
+        monitor.on_reconnect();
+        let health = monitor.health();
+        assert_eq!(health.reconnects, 1);
+        assert_eq!(health.status, StreamHealthStatus::Degraded);
+    }
+}
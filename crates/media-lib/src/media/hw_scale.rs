@@ -0,0 +1,206 @@
+use ffmpeg_next::ffi::{AVHWDeviceType, AVHWFramesContext};
+use ffmpeg_next::filter;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{MediaLibError, Size, ToneMapping};
+
+use super::filter_graph::FilterGraph;
+use super::tonemap::{is_hdr, tonemap_spec};
+
+/// Filters that scale frames without leaving the device, best first. They
+/// all take `w` and `h`. Empty for devices FFmpeg can't scale on.
+fn scale_filters(device_type: AVHWDeviceType) -> &'static [&'static str] {
+    match device_type {
+        AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA => &["scale_cuda"],
+        AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI => &["scale_vaapi"],
+        // vpp_qsv is in builds where scale_qsv isn't, e.g. with oneVPL only.
+        AVHWDeviceType::AV_HWDEVICE_TYPE_QSV => &["scale_qsv", "vpp_qsv"],
+        AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX => &["scale_vt"],
+        AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN => &["scale_vulkan"],
+        // Only in newer FFmpeg, older builds download D3D11 frames at full
+        // size and scale them in system memory.
+        AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA => &["scale_d3d11"],
+        _ => &[],
+    }
+}
+
+/// The filter spec that scales on a device of `device_type`, with the first
+/// of its scale filters this FFmpeg has.
+fn device_spec(device_type: AVHWDeviceType, size: Size) -> Result<String, MediaLibError> {
+    let candidates = scale_filters(device_type);
+    if candidates.is_empty() {
+        return Err(MediaLibError::FFmpegError(
+            format!("No hardware scaler for device {:?}", device_type).into(),
+        ));
+    }
+    let name = candidates
+        .iter()
+        .copied()
+        .find(|name| filter::find(name).is_some())
+        .ok_or_else(|| {
+            MediaLibError::FFmpegError(
+                format!("{} filter not available", candidates.join(" or ")).into(),
+            )
+        })?;
+    Ok(format!("{}=w={}:h={}", name, size.width, size.height))
+}
+
+/// The name of the format `frame` is stored in on the device, what it
+/// downloads as.
+fn download_format(frame: &Video) -> Result<&'static str, MediaLibError> {
+    let sw_format = unsafe {
+        let frames_ref = (*frame.as_ptr()).hw_frames_ctx;
+        if frames_ref.is_null() {
+            return Err(MediaLibError::FFmpegError(
+                "Frame has no hardware frames context".into(),
+            ));
+        }
+        (*((*frames_ref).data as *mut AVHWFramesContext)).sw_format
+    };
+    Pixel::from(sw_format)
+        .descriptor()
+        .map(|descriptor| descriptor.name())
+        .ok_or_else(|| MediaLibError::FFmpegError("Unknown download format".into()))
+}
+
+/// The filter spec that downloads `frame` as it is stored on the device and
+/// scales it in software.
+fn download_spec(frame: &Video, size: Size) -> Result<String, MediaLibError> {
+    Ok(format!(
+        "hwdownload,format={},scale=w={}:h={}",
+        download_format(frame)?,
+        size.width,
+        size.height
+    ))
+}
+
+/// `spec` followed by tone mapping with `tone_map`. Frames still on the
+/// device are downloaded first, tone mapping runs in software on the
+/// frames once they're small.
+fn tone_mapped_spec(
+    frame: &Video,
+    spec: &str,
+    on_device: bool,
+    tone_map: &str,
+) -> Result<String, MediaLibError> {
+    Ok(if on_device {
+        format!(
+            "{},hwdownload,format={},{}",
+            spec,
+            download_format(frame)?,
+            tone_map
+        )
+    } else {
+        format!("{},{}", spec, tone_map)
+    })
+}
+
+/// Scales hardware frames on the GPU before they're downloaded, so large
+/// inputs never need a full size frame in system memory. When the device
+/// can't scale, frames are downloaded and scaled in software in the same
+/// graph instead. HDR frames can be tone mapped once they're scaled, in
+/// the same graph too.
+pub struct HardwareScaler {
+    // Rebuilt when the stream changes resolution.
+    graph: FilterGraph,
+    // Why scaling on the device failed, when the graph downloads instead.
+    fallback_reason: Option<String>,
+    tone_maps: bool,
+}
+
+impl HardwareScaler {
+    /// Sets up a graph for frames like `frame`, which has to be a hardware
+    /// frame from a device of `device_type`. `size` should be even, most of
+    /// the scale filters require it. HDR frames are tone mapped with
+    /// `tone_mapping` after scaling, or left as they are when that can't be
+    /// done, e.g. without zimg.
+    pub fn new(
+        frame: &Video,
+        device_type: AVHWDeviceType,
+        size: Size,
+        tone_mapping: ToneMapping,
+    ) -> Result<Self, MediaLibError> {
+        let tone_map = if is_hdr(frame.color_transfer_characteristic()) {
+            tonemap_spec(tone_mapping)
+        } else {
+            None
+        };
+        if let Some(tone_map) = &tone_map {
+            match Self::build(frame, device_type, size, Some(tone_map)) {
+                Ok(scaler) => return Ok(scaler),
+                Err(e) => log::warn!("Failed to tone map after scaling on the device: {}", e),
+            }
+        }
+        Self::build(frame, device_type, size, None)
+    }
+
+    fn build(
+        frame: &Video,
+        device_type: AVHWDeviceType,
+        size: Size,
+        tone_map: Option<&str>,
+    ) -> Result<Self, MediaLibError> {
+        let with_tone_map = |spec: String, on_device: bool| match tone_map {
+            Some(tone_map) => tone_mapped_spec(frame, &spec, on_device, tone_map),
+            None => Ok(spec),
+        };
+        let on_device = device_spec(device_type, size)
+            .and_then(|spec| with_tone_map(spec, true))
+            .and_then(|spec| FilterGraph::new(frame, &spec));
+        let (graph, fallback_reason) = match on_device {
+            Ok(graph) => (graph, None),
+            Err(e) => {
+                let spec = with_tone_map(download_spec(frame, size)?, false)?;
+                (FilterGraph::new(frame, &spec)?, Some(e.to_string()))
+            }
+        };
+        Ok(HardwareScaler {
+            graph,
+            fallback_reason,
+            tone_maps: tone_map.is_some(),
+        })
+    }
+
+    /// Whether frames come out tone mapped to SDR BT.709, and downloaded.
+    pub fn tone_maps(&self) -> bool {
+        self.tone_maps
+    }
+
+    /// Why the device couldn't scale, none when it does. Frames come out
+    /// already downloaded when it can't.
+    pub fn fallback_reason(&self) -> Option<&str> {
+        self.fallback_reason.as_deref()
+    }
+
+    /// The graph as FFmpeg prints it, for diagnostics.
+    pub fn dump(&self) -> String {
+        self.graph.dump()
+    }
+
+    pub fn is_for(&self, frame: &Video) -> bool {
+        self.graph.accepts(frame)
+    }
+
+    /// The scaled frame, still on the device unless the graph downloads.
+    pub fn run(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        self.graph.run(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tone_maps_once_frames_are_scaled() {
+        let tone_map = tonemap_spec(ToneMapping::Hable).unwrap();
+        let frame = Video::new(Pixel::YUV420P, 64, 64);
+        assert_eq!(
+            tone_mapped_spec(&frame, "scale=w=32:h=32", false, &tone_map).unwrap(),
+            format!("scale=w=32:h=32,{}", tone_map)
+        );
+        // Frames on the device need their download format, software frames
+        // have none.
+        assert!(tone_mapped_spec(&frame, "scale_cuda=w=32:h=32", true, &tone_map).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    ImageFormat, ImageSequenceOptions, MediaFrameDecoderOptions, MediaLibError, ProcessingReport,
+};
+
+use super::frame_export::encode_image;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::io_error;
+use super::job::Job;
+use super::lut::Lut3d;
+
+type Written = Result<(PathBuf, u64), MediaLibError>;
+
+// Digits in the largest image number.
+const MAX_PAD_WIDTH: u32 = 20;
+
+fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::Tiff => "tiff",
+        ImageFormat::WebP => "webp",
+    }
+}
+
+/// Encodes and writes images on worker threads, the decoder only waits for
+/// them when they fall a few images behind.
+struct ImageWriters {
+    jobs: Option<mpsc::SyncSender<(Video, PathBuf)>>,
+    written: Receiver<Written>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ImageWriters {
+    fn new(options: &ImageSequenceOptions) -> Self {
+        let threads = match options.threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads as usize,
+        };
+        let (jobs, job_receiver) = mpsc::sync_channel::<(Video, PathBuf)>(threads * 2);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (written_sender, written) = mpsc::channel();
+
+        let workers = (0..threads)
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let written_sender = written_sender.clone();
+                let options = options.clone();
+                std::thread::spawn(move || loop {
+                    let job = match job_receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    let Ok((frame, path)) = job else {
+                        break;
+                    };
+                    // A panic would otherwise leave a gap in the sequence with
+                    // nothing to say why.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let image = encode_image(&frame, &options.image)?;
+                        options.output.write(&path, &image).map_err(io_error)?;
+                        Ok((path.clone(), image.len() as u64))
+                    }))
+                    .unwrap_or_else(|_| {
+                        Err(MediaLibError::UnknownError(
+                            format!("Writing {} panicked", path.display()).into(),
+                        ))
+                    });
+                    if written_sender.send(result).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+
+        ImageWriters {
+            jobs: Some(jobs),
+            written,
+            workers,
+        }
+    }
+
+    fn submit(&mut self, frame: Video, path: PathBuf) -> Result<(), MediaLibError> {
+        let sent = self
+            .jobs
+            .as_ref()
+            .is_some_and(|jobs| jobs.send((frame, path)).is_ok());
+        if sent {
+            Ok(())
+        } else {
+            Err(MediaLibError::UnknownError(
+                "Image writer threads have stopped".into(),
+            ))
+        }
+    }
+
+    /// Images written so far, failing on the first one that couldn't be.
+    fn collect(&mut self, outputs: &mut Vec<(PathBuf, u64)>) -> Result<(), MediaLibError> {
+        for written in self.written.try_iter() {
+            outputs.push(written?);
+        }
+        Ok(())
+    }
+
+    /// Waits for every image submitted to be written.
+    fn finish(mut self, outputs: &mut Vec<(PathBuf, u64)>) -> Result<(), MediaLibError> {
+        self.jobs.take();
+        let mut panicked = false;
+        for worker in self.workers.drain(..) {
+            panicked |= worker.join().is_err();
+        }
+        self.collect(outputs)?;
+        if panicked {
+            return Err(MediaLibError::UnknownError(
+                "An image writer thread panicked".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ImageWriters {
+    fn drop(&mut self) {
+        // Closing the job channel lets the workers run off the end of their
+        // loops.
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Picks the frames of an image sequence, names them and writes them, in
+/// one numbering across however many inputs go into it.
+pub struct SequenceWriter {
+    writers: ImageWriters,
+    outputs: Vec<(PathBuf, u64)>,
+    output_dir: PathBuf,
+    options: ImageSequenceOptions,
+    // From `options.image.lut_path`, run on the decoding thread before
+    // frames are handed to the writers.
+    lut: Option<Lut3d>,
+    number: u64,
+    // Interval of the input the next image comes from, when sampling.
+    next_interval: u64,
+}
+
+impl SequenceWriter {
+    /// Creates `output_dir` if needed.
+    pub fn new(output_dir: &Path, options: &ImageSequenceOptions) -> Result<Self, MediaLibError> {
+        if options.fps > 0 && options.fps_denominator == 0 {
+            return Err(MediaLibError::UnknownError(
+                "Frame rate denominator must be above zero".into(),
+            ));
+        }
+        let prefix: &str = &options.prefix;
+        if prefix.contains(['/', '\\', '\0']) || prefix.contains("..") {
+            return Err(MediaLibError::UnknownError(
+                format!("Image name prefix '{}' isn't a plain file name", prefix).into(),
+            ));
+        }
+        if options.pad_width > MAX_PAD_WIDTH {
+            return Err(MediaLibError::UnknownError(
+                format!(
+                    "Numbers can't be padded to {} digits, {} at most",
+                    options.pad_width, MAX_PAD_WIDTH
+                )
+                .into(),
+            ));
+        }
+        let lut = Lut3d::from_option(&options.image.lut_path)?;
+        std::fs::create_dir_all(output_dir).map_err(io_error)?;
+        Ok(SequenceWriter {
+            writers: ImageWriters::new(options),
+            outputs: Vec::new(),
+            output_dir: output_dir.to_path_buf(),
+            options: options.clone(),
+            lut,
+            number: options.start_number,
+            next_interval: 0,
+        })
+    }
+
+    /// Starts sampling over for the next input, the numbering carries on.
+    pub fn next_input(&mut self) {
+        self.next_interval = 0;
+    }
+
+    /// Whether the frame shown at `time_us` (from the start of the input)
+    /// is in the sequence. Frames without a timestamp can't be placed when
+    /// sampling, they're left out.
+    pub fn wants(&mut self, time_us: Option<i64>) -> bool {
+        if self.options.fps == 0 {
+            return true;
+        }
+        let Some(time_us) = time_us else {
+            return false;
+        };
+        let interval = time_us.max(0) as u64 * self.options.fps as u64
+            / (self.options.fps_denominator as u64 * 1_000_000);
+        if interval < self.next_interval {
+            return false;
+        }
+        self.next_interval = interval + 1;
+        true
+    }
+
+    /// Name of the next image, e.g. `frame_000001.png`.
+    fn file_name(&self) -> String {
+        format!(
+            "{}{:0width$}.{}",
+            &*self.options.prefix,
+            self.number,
+            extension(self.options.image.format),
+            width = self.options.pad_width as usize
+        )
+    }
+
+    /// Hands `frame` to the writers, through the LUT if there is one,
+    /// returning the path it will be written to. Fails when an image
+    /// written earlier couldn't be.
+    pub fn write(&mut self, frame: Video) -> Result<PathBuf, MediaLibError> {
+        self.writers.collect(&mut self.outputs)?;
+        let frame = match &mut self.lut {
+            Some(lut) => lut.apply(frame)?,
+            None => frame,
+        };
+        let path = self.output_dir.join(self.file_name());
+        self.writers.submit(frame, path.clone())?;
+        self.number += 1;
+        Ok(path)
+    }
+
+    /// Waits for every image to be written, returning them with their
+    /// sizes.
+    pub fn finish(mut self) -> Result<Vec<(PathBuf, u64)>, MediaLibError> {
+        let mut outputs = std::mem::take(&mut self.outputs);
+        self.writers.finish(&mut outputs)?;
+        outputs.sort();
+        Ok(outputs)
+    }
+}
+
+/// Writes the frames of `input_path` to `output_dir` as numbered images,
+/// every frame or the first of each interval `options.fps` gives, for tools
+/// that work on image files such as dataset pipelines. The directory is
+/// created if needed.
+pub fn export_image_sequence(
+    input_path: &Path,
+    output_dir: &Path,
+    options: &ImageSequenceOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    let started = Instant::now();
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let mut sequence = SequenceWriter::new(output_dir, options)?;
+    let start_time = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        job.check()?;
+        let decoded = decoded?;
+        let time_us = video_decoder
+            .frame_time_us(&decoded)
+            .map(|time| time - start_time);
+        if let Some(time_us) = time_us {
+            job.progress(time_us, video_decoder.duration_us());
+        }
+        if sequence.wants(time_us) {
+            sequence.write(decoded)?;
+        }
+    }
+    let outputs = sequence.finish()?;
+
+    let mut report = ProcessingReport {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        frames_decoded: video_decoder.frames_decoded,
+        frames_returned: outputs.len() as u64,
+        frames_dropped: 0,
+        hardware_device: video_decoder
+            .hardware_device_name()
+            .map(|name| name.as_str().into())
+            .into(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    for warning in &video_decoder.warnings {
+        report.warnings.push(warning.as_str().into());
+    }
+    for (path, bytes) in &outputs {
+        report.add_output(&path.to_string_lossy(), *bytes);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("image-sequence-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn it_names_images_in_the_output_directory() {
+        let dir = temp_dir("names");
+        let options = ImageSequenceOptions {
+            prefix: "shot_".into(),
+            start_number: 7,
+            pad_width: 4,
+            threads: 1,
+            ..Default::default()
+        };
+        let sequence = SequenceWriter::new(&dir, &options).unwrap();
+        assert_eq!(sequence.file_name(), "shot_0007.png");
+        sequence.finish().unwrap();
+
+        for prefix in ["../shot_", "shots/", "shots\\", ".."] {
+            let options = ImageSequenceOptions {
+                prefix: prefix.into(),
+                ..Default::default()
+            };
+            assert!(SequenceWriter::new(&dir, &options).is_err(), "{}", prefix);
+        }
+        let options = ImageSequenceOptions {
+            pad_width: MAX_PAD_WIDTH + 1,
+            ..Default::default()
+        };
+        assert!(SequenceWriter::new(&dir, &options).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_samples_the_first_frame_of_each_interval() {
+        let dir = temp_dir("sampling");
+        let options = ImageSequenceOptions {
+            fps: 2,
+            threads: 1,
+            ..Default::default()
+        };
+        let mut sequence = SequenceWriter::new(&dir, &options).unwrap();
+        let wanted: Vec<i64> = (0..20)
+            .map(|frame| frame * 100_000)
+            .filter(|time_us| sequence.wants(Some(*time_us)))
+            .collect();
+        assert_eq!(wanted, [0, 500_000, 1_000_000, 1_500_000]);
+        assert!(!sequence.wants(None));
+
+        // Sampling starts over with each input.
+        sequence.next_input();
+        assert!(sequence.wants(Some(0)));
+        sequence.finish().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
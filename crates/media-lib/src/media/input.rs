@@ -0,0 +1,614 @@
+use std::ffi::{c_int, c_void, CString};
+use std::fs::File;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::MediaLibError;
+use ffmpeg_next::ffi::{
+    av_find_input_format, avformat_alloc_context, avformat_open_input, AVFMT_FLAG_CUSTOM_IO,
+};
+use ffmpeg_next::format::context::Input;
+use ffmpeg_next::{Dictionary, Packet};
+use media_types::DecryptionOptions;
+
+use super::avio::{CustomIo, FileSource, MappedSource, ReadSeek};
+
+// Inputs named like this are a file the caller already has open rather than
+// a path, for hosts sandboxed away from the filesystem (landlock, seccomp).
+#[cfg(unix)]
+const HANDLE_PREFIX: &str = "fd:";
+#[cfg(windows)]
+const HANDLE_PREFIX: &str = "handle:";
+
+// Inputs opened through FFmpeg's network protocols rather than as files.
+const NETWORK_SCHEMES: &[&str] = &["http", "https", "rtsp", "rtsps", "rtmp", "rtmps"];
+
+// Bytes and microseconds of input `open_input_quick` reads to find the
+// streams. Zero would mean FFmpeg's defaults, so these are small instead.
+const QUICK_PROBE_SIZE: &str = "32768";
+const QUICK_ANALYZE_DURATION_US: &str = "100000";
+
+// Read errors in a row `next_packet` skips before giving up on the input,
+// enough for a damaged stretch of a file or a few dropped network packets.
+const MAX_READ_ERRORS: u32 = 64;
+
+/// The network protocol `input_path` is a URL for, if any.
+fn network_scheme(input_path: &Path) -> Option<String> {
+    let (scheme, _) = input_path.to_str()?.split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    NETWORK_SCHEMES.contains(&scheme.as_str()).then_some(scheme)
+}
+
+/// Whether `input_path` is a URL read through FFmpeg's network protocols.
+pub fn is_network_input(input_path: &Path) -> bool {
+    network_scheme(input_path).is_some()
+}
+
+/// How inputs are opened, the network options only apply to URLs and
+/// `memory_map` only to local files.
+#[derive(Default)]
+pub struct InputOptions<'a> {
+    /// Zero keeps the protocol's own timeout.
+    pub timeout_ms: u32,
+    pub user_agent: Option<&'a str>,
+    /// See `MediaFrameDecoderOptions::memory_map`.
+    pub memory_map: bool,
+}
+
+impl InputOptions<'_> {
+    /// The protocol options for `scheme`, each protocol names them its own
+    /// way.
+    fn dictionary(&self, scheme: &str) -> Dictionary<'static> {
+        let mut dictionary = Dictionary::new();
+        let is_rtsp = scheme.starts_with("rtsp");
+        if self.timeout_ms > 0 {
+            let timeout_us = (self.timeout_ms as u64 * 1000).to_string();
+            dictionary.set("rw_timeout", &timeout_us);
+            // RTSP reads through its own sockets, which only go by this.
+            if is_rtsp {
+                dictionary.set("timeout", &timeout_us);
+            }
+        }
+        if let Some(user_agent) = self.user_agent {
+            if scheme.starts_with("http") || is_rtsp {
+                dictionary.set("user_agent", user_agent);
+            }
+        }
+        dictionary
+    }
+}
+
+/// Fails unless `value` is 16 bytes written as 32 hex digits, the form
+/// FFmpeg takes keys and IVs in.
+fn check_hex(what: &str, value: &str) -> Result<(), MediaLibError> {
+    if value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(());
+    }
+    Err(MediaLibError::UnknownError(
+        format!("{} must be 32 hex digits", what).into(),
+    ))
+}
+
+/// Adds the demuxer and protocol options carrying `decryption`'s keys: the
+/// key without a key ID is the MP4 demuxer's `decryption_key` (and the
+/// crypto protocol's `key`), the others go in `decryption_keys` by key ID.
+fn add_decryption_options(
+    dictionary: &mut Dictionary,
+    decryption: &DecryptionOptions,
+) -> Result<(), MediaLibError> {
+    let mut by_key_id = Vec::new();
+    for key in decryption.keys.iter() {
+        check_hex("Decryption key", &key.key)?;
+        if key.key_id.is_empty() {
+            dictionary.set("decryption_key", &key.key);
+            if decryption.aes_iv.is_some() {
+                dictionary.set("key", &key.key);
+            }
+        } else {
+            check_hex("Key ID", &key.key_id)?;
+            by_key_id.push(format!("{}={}", key.key_id, key.key));
+        }
+    }
+    if !by_key_id.is_empty() {
+        dictionary.set("decryption_keys", &by_key_id.join(":"));
+    }
+    if let Some(iv) = decryption.aes_iv.as_ref() {
+        check_hex("AES IV", iv)?;
+        if !decryption.keys.iter().any(|key| key.key_id.is_empty()) {
+            return Err(MediaLibError::UnknownError(
+                "AES-128 decryption needs a key without a key ID".into(),
+            ));
+        }
+        dictionary.set("iv", iv);
+    }
+    Ok(())
+}
+
+/// `input_path` read through the crypto protocol, which decrypts AES-128
+/// as it reads: `crypto:` for files and `crypto+` before the URL for inputs
+/// read through another protocol.
+fn crypto_path(input_path: &Path) -> Result<PathBuf, MediaLibError> {
+    let path = input_path.to_string_lossy();
+    if path.starts_with(HANDLE_PREFIX) {
+        return Err(MediaLibError::UnknownError(
+            "AES-128 decryption needs a path or URL, not an open file".into(),
+        ));
+    }
+    // A playlist is plain text, it's the segments it lists that are
+    // encrypted, and the HLS demuxer decrypts those itself with the keys
+    // the playlist names.
+    let name = path.split(['?', '#']).next().unwrap_or_default();
+    let name = name.to_ascii_lowercase();
+    if name.ends_with(".m3u8") || name.ends_with(".m3u") {
+        return Err(MediaLibError::UnknownError(
+            "AES-128 decryption is for encrypted files and HLS segments, not playlists".into(),
+        ));
+    }
+    Ok(match network_scheme(input_path) {
+        Some(_) => PathBuf::from(format!("crypto+{}", path)),
+        None => PathBuf::from(format!("crypto:{}", path)),
+    })
+}
+
+/// A demuxer along with the custom IO it reads through, if any. Derefs to
+/// the `Input` so it's used the same either way.
+pub struct MediaInput {
+    // Fields drop in order, the demuxer has to be closed before its IO or
+    // the flag its interrupt callback reads go.
+    input: Input,
+    _io: Option<CustomIo>,
+    interrupted: Arc<AtomicBool>,
+    // Whether the last packet read came after read errors.
+    recovered: bool,
+}
+
+impl MediaInput {
+    /// Setting the flag aborts whatever the demuxer is blocked on, e.g. a
+    /// network read, and `next_packet` fails with `Cancelled` from then on.
+    /// Can be set from any thread.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    /// Whether the packet `next_packet` last returned came after reads
+    /// failed, i.e. a live input dropped and the protocol got it back.
+    pub fn recovered(&self) -> bool {
+        self.recovered
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// The next packet and the index of its stream, none at the end of the
+    /// input. Unlike `packets()` it stops once interrupted, and fails rather
+    /// than skipping read errors forever once `MAX_READ_ERRORS` come in a
+    /// row.
+    pub fn next_packet(&mut self) -> Option<Result<(usize, Packet), MediaLibError>> {
+        let mut packet = Packet::empty();
+        let mut errors = 0;
+        loop {
+            if self.is_interrupted() {
+                return Some(Err(MediaLibError::Cancelled("Cancelled".into())));
+            }
+            match packet.read(&mut self.input) {
+                Ok(()) => {
+                    self.recovered = errors > 0;
+                    return Some(Ok((packet.stream(), packet)));
+                }
+                Err(ffmpeg_next::Error::Eof) => return None,
+                Err(e) => {
+                    errors += 1;
+                    if errors >= MAX_READ_ERRORS {
+                        return Some(Err(MediaLibError::FFmpegError(
+                            format!("Gave up after {} read errors in a row: {}", errors, e).into(),
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn is_interrupted(opaque: *mut c_void) -> c_int {
+    (*(opaque as *const AtomicBool)).load(Ordering::Relaxed) as c_int
+}
+
+impl Deref for MediaInput {
+    type Target = Input;
+
+    fn deref(&self) -> &Input {
+        &self.input
+    }
+}
+
+impl DerefMut for MediaInput {
+    fn deref_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+}
+
+fn handle_error(input_path: &Path, e: impl std::fmt::Display) -> MediaLibError {
+    MediaLibError::UnknownError(
+        format!("Invalid file handle '{}': {}", input_path.display(), e).into(),
+    )
+}
+
+/// The file behind an `fd:N` (`handle:N` on Windows) input, duplicated so
+/// the caller can close theirs once we return. None for ordinary paths.
+#[cfg(unix)]
+fn open_handle(input_path: &Path) -> Result<Option<File>, MediaLibError> {
+    use std::os::unix::io::{BorrowedFd, RawFd};
+
+    let Some(fd) = input_path
+        .to_str()
+        .and_then(|path| path.strip_prefix(HANDLE_PREFIX))
+    else {
+        return Ok(None);
+    };
+    let fd: RawFd = fd.parse().map_err(|e| handle_error(input_path, e))?;
+    if fd < 0 {
+        return Err(handle_error(input_path, "descriptors can't be negative"));
+    }
+    unsafe { BorrowedFd::borrow_raw(fd) }
+        .try_clone_to_owned()
+        .map(|fd| Some(File::from(fd)))
+        .map_err(|e| handle_error(input_path, e))
+}
+
+#[cfg(windows)]
+fn open_handle(input_path: &Path) -> Result<Option<File>, MediaLibError> {
+    use std::os::windows::io::{BorrowedHandle, RawHandle};
+
+    let Some(handle) = input_path
+        .to_str()
+        .and_then(|path| path.strip_prefix(HANDLE_PREFIX))
+    else {
+        return Ok(None);
+    };
+    let handle: usize = handle.parse().map_err(|e| handle_error(input_path, e))?;
+    unsafe { BorrowedHandle::borrow_raw(handle as RawHandle) }
+        .try_clone_to_owned()
+        .map(|handle| Some(File::from(handle)))
+        .map_err(|e| handle_error(input_path, e))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn open_handle(_input_path: &Path) -> Result<Option<File>, MediaLibError> {
+    Ok(None)
+}
+
+/// Custom IO for inputs that aren't a path FFmpeg can open itself, and
+/// for local files read from memory with `memory_map`.
+fn custom_io(input_path: &Path, memory_map: bool) -> Result<Option<CustomIo>, MediaLibError> {
+    let file = match open_handle(input_path)? {
+        Some(file) => file,
+        None if memory_map && !is_network_input(input_path) => match File::open(input_path) {
+            Ok(file) => file,
+            // Not a file after all (an image sequence pattern, a protocol
+            // URL), FFmpeg opens it or fails with its usual error.
+            Err(e) => {
+                log::warn!(
+                    "Not memory mapping {}, leaving it to FFmpeg: {}",
+                    input_path.display(),
+                    e
+                );
+                return Ok(None);
+            }
+        },
+        None => return Ok(None),
+    };
+    // Pipes and sockets are read as they come, only regular files seek.
+    let seekable = file.metadata().is_ok_and(|metadata| metadata.is_file());
+    let reader: Box<dyn ReadSeek> = if !seekable {
+        if memory_map {
+            log::warn!(
+                "Not memory mapping {}, it isn't a regular file",
+                input_path.display()
+            );
+        }
+        Box::new(file)
+    } else if memory_map {
+        match file.try_clone().and_then(MappedSource::new) {
+            Ok(mapped) => Box::new(mapped),
+            Err(e) => {
+                log::warn!(
+                    "Not memory mapping {}, reading it with buffered reads instead: {}",
+                    input_path.display(),
+                    e
+                );
+                Box::new(FileSource::new(file))
+            }
+        }
+    } else {
+        Box::new(FileSource::new(file))
+    };
+    CustomIo::new(reader, seekable).map(Some)
+}
+
+/// Opens `input_path` for demuxing. With `force_format` set the named demuxer
+/// is used instead of probing, for files with a missing or wrong extension.
+/// `fd:N` on Unix and `handle:N` on Windows read from a file the caller
+/// already has open.
+pub fn open_input(
+    input_path: &Path,
+    force_format: Option<&str>,
+) -> Result<MediaInput, MediaLibError> {
+    open_input_with_options(
+        input_path,
+        force_format,
+        &InputOptions::default(),
+        &DecryptionOptions::default(),
+    )
+}
+
+/// Like `open_input`, also taking `http(s)://`, `rtsp://` and `rtmp://`
+/// URLs opened with `options`, and encrypted inputs `decryption` has the
+/// keys for.
+pub fn open_input_with_options(
+    input_path: &Path,
+    force_format: Option<&str>,
+    options: &InputOptions,
+    decryption: &DecryptionOptions,
+) -> Result<MediaInput, MediaLibError> {
+    let mut protocol_options = match network_scheme(input_path) {
+        Some(scheme) => options.dictionary(&scheme),
+        None => Dictionary::new(),
+    };
+    add_decryption_options(&mut protocol_options, decryption)?;
+    if decryption.aes_iv.is_some() {
+        return open(
+            &crypto_path(input_path)?,
+            None,
+            force_format,
+            protocol_options,
+        );
+    }
+    let io = custom_io(input_path, options.memory_map)?;
+    open(input_path, io, force_format, protocol_options)
+}
+
+/// Like `open_input` with stream probing cut short after the first packets,
+/// for formats whose headers describe every stream such as audio files.
+/// Much quicker on large files where FFmpeg would otherwise read a few
+/// seconds of every stream.
+pub fn open_input_quick(input_path: &Path) -> Result<MediaInput, MediaLibError> {
+    let mut options = Dictionary::new();
+    options.set("probesize", QUICK_PROBE_SIZE);
+    options.set("analyzeduration", QUICK_ANALYZE_DURATION_US);
+    let io = custom_io(input_path, false)?;
+    open(input_path, io, None, options)
+}
+
+/// Like `open_input` for an input that isn't a file at all, e.g. one held
+/// in memory. Formats are probed from the content alone, there's no
+/// extension to go by.
+pub fn open_reader(
+    reader: Box<dyn ReadSeek>,
+    force_format: Option<&str>,
+) -> Result<MediaInput, MediaLibError> {
+    let io = CustomIo::new(reader, true)?;
+    open(Path::new(""), Some(io), force_format, Dictionary::new())
+}
+
+fn open(
+    input_path: &Path,
+    io: Option<CustomIo>,
+    force_format: Option<&str>,
+    // Protocol and demuxer options.
+    protocol_options: Dictionary,
+) -> Result<MediaInput, MediaLibError> {
+    let path = CString::new(input_path.to_string_lossy().as_bytes())
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+
+    let format = match force_format {
+        Some(name) => {
+            let c_name = CString::new(name)
+                .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+            let format = unsafe { av_find_input_format(c_name.as_ptr()) };
+            if format.is_null() {
+                return Err(MediaLibError::FFmpegError(
+                    format!("Unknown input format '{}'", name).into(),
+                ));
+            }
+            format
+        }
+        None => ptr::null(),
+    };
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    unsafe {
+        let mut ps = avformat_alloc_context();
+        if ps.is_null() {
+            return Err(MediaLibError::FFmpegError(
+                "Failed to allocate the input".into(),
+            ));
+        }
+        if let Some(io) = &io {
+            (*ps).pb = io.as_mut_ptr();
+            (*ps).flags |= AVFMT_FLAG_CUSTOM_IO as i32;
+        }
+        (*ps).interrupt_callback.callback = Some(is_interrupted);
+        (*ps).interrupt_callback.opaque = Arc::as_ptr(&interrupted) as *mut c_void;
+        // FFmpeg frees the context itself when this fails.
+        let mut protocol_options = protocol_options.disown();
+        let result =
+            avformat_open_input(&mut ps, path.as_ptr(), format as _, &mut protocol_options);
+        // Whatever the protocol didn't use is left in there.
+        Dictionary::own(protocol_options);
+        if result < 0 {
+            let e = ffmpeg_next::Error::from(result);
+            return Err(MediaLibError::FFmpegError(match force_format {
+                Some(name) => format!("Failed to open input as '{}': {}", name, e).into(),
+                None => format!("Failed to open input: {}", e).into(),
+            }));
+        }
+
+        // From here on dropping the context closes the input.
+        let input = MediaInput {
+            input: Input::wrap(ps),
+            _io: io,
+            interrupted,
+            recovered: false,
+        };
+        // Fuzzed headers can claim frames of any size, the decoders that
+        // look at the streams are held to what `decode_bytes` allows.
+        #[cfg(feature = "fuzzing")]
+        let result = super::fuzz::find_stream_info(ps);
+        #[cfg(not(feature = "fuzzing"))]
+        let result = ffmpeg_next::ffi::avformat_find_stream_info(ps, ptr::null_mut());
+        if result < 0 {
+            // Probing picked a demuxer but it couldn't make sense of the
+            // streams, name it so a wrong guess is easy to spot.
+            return Err(MediaLibError::FFmpegError(
+                format!(
+                    "Failed to read streams as '{}': {}",
+                    input.format().name(),
+                    ffmpeg_next::Error::from(result)
+                )
+                .into(),
+            ));
+        }
+
+        Ok(input)
+    }
+}
+
+pub fn stream_kind(medium: ffmpeg_next::media::Type) -> &'static str {
+    match medium {
+        ffmpeg_next::media::Type::Video => "video",
+        ffmpeg_next::media::Type::Audio => "audio",
+        ffmpeg_next::media::Type::Subtitle => "subtitle",
+        ffmpeg_next::media::Type::Data => "data",
+        ffmpeg_next::media::Type::Attachment => "attachment",
+        ffmpeg_next::media::Type::Unknown => "unknown",
+    }
+}
+
+/// The `NoVideoStream` error for `ictx`, listing the kinds of streams it does
+/// have so callers can tell e.g. a podcast from a broken file.
+pub fn no_video_stream(ictx: &Input) -> MediaLibError {
+    let mut stream_kinds = stabby::vec::Vec::new();
+    for stream in ictx.streams() {
+        stream_kinds.push(stream_kind(stream.parameters().medium()).into());
+    }
+    MediaLibError::NoVideoStream(stream_kinds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+    use crate::media::{generate_test_asset, Job};
+    use ffmpeg_next::ffi::{av_dict_free, avio_closep, avio_open2, avio_write, AVIO_FLAG_WRITE};
+    use media_types::{
+        DecryptionKey, JobContext, MediaFrameDecoderOptions, Size, TestAssetOptions,
+    };
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f";
+    const IV: &str = "0f0e0d0c0b0a09080706050403020100";
+
+    /// `plain` encrypted whole with AES-128, the way HLS segments are,
+    /// written through the crypto protocol.
+    fn encrypt(plain: &Path, encrypted: &Path) {
+        let data = std::fs::read(plain).unwrap();
+        let url = CString::new(format!("crypto:{}", encrypted.display())).unwrap();
+        let mut options = Dictionary::new();
+        options.set("key", KEY);
+        options.set("iv", IV);
+        unsafe {
+            let mut options = options.disown();
+            let mut io = ptr::null_mut();
+            let opened = avio_open2(
+                &mut io,
+                url.as_ptr(),
+                AVIO_FLAG_WRITE as c_int,
+                ptr::null(),
+                &mut options,
+            );
+            av_dict_free(&mut options);
+            assert!(opened >= 0, "Failed to open {}", encrypted.display());
+            avio_write(io, data.as_ptr(), data.len() as c_int);
+            avio_closep(&mut io);
+        }
+    }
+
+    /// Every frame of `input_path` decoded with `decryption`.
+    fn count_frames(input_path: &Path, decryption: DecryptionOptions) -> usize {
+        let options = MediaFrameDecoderOptions {
+            decryption,
+            ..Default::default()
+        };
+        let mut decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::new(input_path, &options) }.unwrap();
+        decoder.keyframes_only = false;
+        let mut frames = 0;
+        while let Some(frame) = decoder.get_frame() {
+            frame.unwrap();
+            frames += 1;
+        }
+        frames
+    }
+
+    fn aes_decryption() -> DecryptionOptions {
+        let mut keys = stabby::vec::Vec::new();
+        keys.push(DecryptionKey {
+            key_id: "".into(),
+            key: KEY.into(),
+        });
+        DecryptionOptions {
+            keys,
+            aes_iv: stabby::option::Option::Some(IV.into()),
+        }
+    }
+
+    #[test]
+    fn it_decodes_inputs_encrypted_with_aes_128() {
+        let dir = std::env::temp_dir().join(format!("media-lib-aes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plain = dir.join("plain.ts");
+        let asset = TestAssetOptions {
+            size: Size::new(160, 90),
+            duration_ms: 1000,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        generate_test_asset(&plain, &asset, &mut Job::detached(JobContext::default())).unwrap();
+        let encrypted = dir.join("encrypted.ts");
+        encrypt(&plain, &encrypted);
+
+        let frames = count_frames(&plain, DecryptionOptions::default());
+        assert!(frames > 0);
+        assert_eq!(count_frames(&encrypted, aes_decryption()), frames);
+        // Without the key it's noise.
+        assert!(open_input(&encrypted, None).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_reads_urls_through_the_crypto_protocol() {
+        assert_eq!(
+            crypto_path(Path::new("/media/segment.ts")).unwrap(),
+            PathBuf::from("crypto:/media/segment.ts")
+        );
+        assert_eq!(
+            crypto_path(Path::new("https://example.com/segment0.ts")).unwrap(),
+            PathBuf::from("crypto+https://example.com/segment0.ts")
+        );
+        for playlist in [
+            "/media/index.m3u8",
+            "https://example.com/live/index.M3U8?token=abc",
+        ] {
+            let opened = open_input_with_options(
+                Path::new(playlist),
+                None,
+                &InputOptions::default(),
+                &aes_decryption(),
+            );
+            assert!(opened.is_err(), "{} was opened", playlist);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use media_types::{JobContext, JobObserverDynMut, MediaLibError};
+
+use crate::logging;
+
+/// Applies a job's log tag on this thread until it's dropped, putting back
+/// the tag from before.
+pub struct TagScope {
+    previous_tag: Option<String>,
+}
+
+impl Drop for TagScope {
+    fn drop(&mut self) {
+        logging::set_tag(self.previous_tag.take());
+    }
+}
+
+/// A running job's `JobContext`. Its log tag applies on this thread until
+/// it's dropped.
+pub struct Job {
+    context: JobContext,
+    started: Instant,
+    // Set once the job is cancelled, by `check` or from another thread
+    // through a `CancelHandle`, see `interrupt_with`.
+    cancelled: Arc<AtomicBool>,
+    // Held for the job's lifetime, none for detached jobs.
+    _scope: Option<TagScope>,
+}
+
+impl Job {
+    pub fn new(context: JobContext) -> Self {
+        let mut job = Job::detached(context);
+        job._scope = Some(job.enter());
+        job
+    }
+
+    /// A job whose work is spread over many calls, e.g. an iterator's, each
+    /// of which applies the log tag for itself with `enter`.
+    pub fn detached(context: JobContext) -> Self {
+        Job {
+            context,
+            started: Instant::now(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            _scope: None,
+        }
+    }
+
+    /// Has the job go by a decoder's interrupt flag, so a `CancelHandle`
+    /// setting it cancels the job, and the job being cancelled also aborts
+    /// a read the decoder is blocked on.
+    pub fn interrupt_with(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancelled = flag;
+        self
+    }
+
+    /// Applies the log tag until the scope is dropped.
+    pub fn enter(&self) -> TagScope {
+        let tag = (!self.context.tag.is_empty()).then(|| self.context.tag.to_string());
+        TagScope {
+            previous_tag: logging::set_tag(tag),
+        }
+    }
+
+    /// Call between units of work, fails once the job is cancelled or past
+    /// its deadline.
+    pub fn check(&mut self) -> Result<(), MediaLibError> {
+        if self.cancelled.load(Ordering::Relaxed) || self.context.observer.is_cancelled() {
+            self.cancelled.store(true, Ordering::Relaxed);
+            return Err(MediaLibError::Cancelled("Cancelled".into()));
+        }
+        let deadline = Duration::from_millis(self.context.deadline_ms);
+        if self.context.deadline_ms > 0 && self.started.elapsed() > deadline {
+            self.cancelled.store(true, Ordering::Relaxed);
+            return Err(MediaLibError::Cancelled(
+                format!("Deadline of {}ms exceeded", self.context.deadline_ms).into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reports `done_us` of the input processed out of `total_us`, when
+    /// known.
+    pub fn progress(&mut self, done_us: i64, total_us: Option<i64>) {
+        let total_us = total_us.unwrap_or(0).max(0) as u64;
+        self.context
+            .observer
+            .on_progress(done_us.max(0) as u64, total_us);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use media_types::JobObserver;
+
+    struct CancelledObserver;
+
+    impl JobObserver for CancelledObserver {
+        extern "C" fn is_cancelled(&mut self) -> bool {
+            true
+        }
+
+        extern "C" fn on_progress(&mut self, _done_us: u64, _total_us: u64) {}
+    }
+
+    #[test]
+    fn it_is_cancelled_through_the_interrupt_flag() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut job = Job::detached(JobContext::default()).interrupt_with(flag.clone());
+        assert!(job.check().is_ok());
+        flag.store(true, Ordering::Relaxed);
+        assert!(job.check().is_err_and(|e| e.is_cancelled()));
+    }
+
+    #[test]
+    fn it_sets_the_interrupt_flag_when_the_observer_cancels() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let observer = stabby::boxed::Box::new(CancelledObserver).into();
+        let mut job = Job::detached(JobContext::new(observer)).interrupt_with(flag.clone());
+        assert!(job.check().is_err_and(|e| e.is_cancelled()));
+        assert!(flag.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::MediaLibError;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{ChromaSubsampling, JpegOptions, RawVideoFrame};
+use mozjpeg::CompInfoExt;
+
+use super::encoder::raw_to_video;
+#[cfg(feature = "nvjpeg")]
+use super::nvjpeg::NvJpegEncoder;
+
+/// A single 8-bit image plane with `stride` bytes per row.
+pub struct Plane<'a> {
+    pub data: Cow<'a, [u8]>,
+    pub stride: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Plane<'a> {
+    pub fn row(&self, row: usize) -> &[u8] {
+        let start = row * self.stride;
+        &self.data[start..start + self.width]
+    }
+}
+
+/// The full range planar layout frames are scaled to so they go into the
+/// encoder as they are with `subsampling`.
+pub fn pixel_format(subsampling: ChromaSubsampling) -> Pixel {
+    match subsampling {
+        ChromaSubsampling::Yuv420 => Pixel::YUVJ420P,
+        ChromaSubsampling::Yuv422 => Pixel::YUVJ422P,
+        ChromaSubsampling::Yuv444 => Pixel::YUVJ444P,
+    }
+}
+
+/// How the chroma of a YUV `format` is subsampled, none for other formats.
+pub fn subsampling(format: Pixel) -> Option<ChromaSubsampling> {
+    match format {
+        Pixel::YUV420P | Pixel::YUVJ420P | Pixel::NV12 => Some(ChromaSubsampling::Yuv420),
+        Pixel::YUV422P | Pixel::YUVJ422P => Some(ChromaSubsampling::Yuv422),
+        Pixel::YUV444P | Pixel::YUVJ444P => Some(ChromaSubsampling::Yuv444),
+        _ => None,
+    }
+}
+
+/// Splits a YUV frame into separate Y, U and V planes. Planar input is
+/// borrowed as is, NV12 has its interleaved chroma plane split in two.
+fn yuv_planes(frame: &Video) -> Result<([Plane<'_>; 3], ChromaSubsampling), MediaLibError> {
+    let subsampling = subsampling(frame.format()).ok_or_else(|| {
+        MediaLibError::ImageError(
+            format!(
+                "Unsupported pixel format for JPEG encoding: {:?}",
+                frame.format()
+            )
+            .into(),
+        )
+    })?;
+    let (shift_x, shift_y) = subsampling.chroma_shift();
+    let width = frame.width() as usize;
+    let height = frame.height() as usize;
+    let chroma_width = (width + (1 << shift_x) - 1) >> shift_x;
+    let chroma_height = (height + (1 << shift_y) - 1) >> shift_y;
+
+    let luma = Plane {
+        data: Cow::Borrowed(frame.data(0)),
+        stride: frame.stride(0),
+        width,
+        height,
+    };
+    if frame.format() != Pixel::NV12 {
+        let chroma = |plane| Plane {
+            data: Cow::Borrowed(frame.data(plane)),
+            stride: frame.stride(plane),
+            width: chroma_width,
+            height: chroma_height,
+        };
+        return Ok(([luma, chroma(1), chroma(2)], subsampling));
+    }
+
+    let uv = frame.data(1);
+    let uv_stride = frame.stride(1);
+    let mut u = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v = Vec::with_capacity(chroma_width * chroma_height);
+    for row in 0..chroma_height {
+        let start = row * uv_stride;
+        for pair in uv[start..start + chroma_width * 2].chunks_exact(2) {
+            u.push(pair[0]);
+            v.push(pair[1]);
+        }
+    }
+    let split = |data| Plane {
+        data: Cow::Owned(data),
+        stride: chroma_width,
+        width: chroma_width,
+        height: chroma_height,
+    };
+    Ok(([luma, split(u), split(v)], subsampling))
+}
+
+// libjpeg's raw data mode consumes whole MCUs so every plane has to be padded
+// out to the component's block size. We repeat the edge pixels rather than
+// padding with black to avoid bleeding at the borders.
+fn pad_plane(plane: &Plane, padded_width: usize, padded_height: usize) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(padded_width * padded_height);
+    for row in 0..padded_height {
+        let line = plane.row(row.min(plane.height - 1));
+        padded.extend_from_slice(line);
+        let edge = line[plane.width - 1];
+        padded.resize(padded.len() + padded_width - plane.width, edge);
+    }
+    padded
+}
+
+fn get_jpeg_buffer(
+    planes: &[Plane; 3],
+    subsampling: ChromaSubsampling,
+    width: u32,
+    height: u32,
+    options: &JpegOptions,
+) -> Result<Vec<u8>, MediaLibError> {
+    std::panic::catch_unwind(|| {
+        let mut comp = mozjpeg::Compress::new(mozjpeg::ColorSpace::JCS_YCbCr);
+        if options.progressive {
+            comp.set_progressive_mode();
+        } else {
+            // mozjpeg's own defaults write progressive scans too, only its
+            // fastest profile writes baseline. Huffman tables are still
+            // optimized, they don't make the JPEG progressive.
+            comp.set_fastest_defaults();
+            comp.set_optimize_coding(true);
+        }
+
+        comp.set_size(width as usize, height as usize);
+        comp.set_quality(options.quality as f32);
+        comp.set_raw_data_in(true);
+        // Pixels of the image each chroma sample covers.
+        let (shift_x, shift_y) = subsampling.chroma_shift();
+        let pixels = (1 << shift_x, 1 << shift_y);
+        comp.set_chroma_sampling_pixel_sizes(pixels, pixels);
+        let mut comp = comp
+            .start_compress(Vec::new())
+            .map_err(|e| MediaLibError::ImageError(e.to_string().into()))?; // any io::Write will work
+
+        let padded: Vec<Vec<u8>> = comp
+            .components()
+            .iter()
+            .zip(planes.iter())
+            .map(|(component, plane)| {
+                pad_plane(plane, component.row_stride(), component.col_stride())
+            })
+            .collect();
+        let padded_refs: Vec<&[u8]> = padded.iter().map(|plane| plane.as_slice()).collect();
+
+        if !comp.write_raw_data(&padded_refs) {
+            return Err(MediaLibError::ImageError(
+                "Failed to write raw YUV data".into(),
+            ));
+        }
+
+        let writer = comp
+            .finish()
+            .map_err(|e| MediaLibError::ImageError(e.to_string().into()))?;
+        Ok(writer)
+    })
+    .map_err(|e| MediaLibError::UnknownError(format!("Panic in get_jpeg_buffer: {:?}", e).into()))?
+}
+
+/// Encodes YUV frames (planar 4:2:0, 4:2:2 or 4:4:4, or NV12) to JPEG
+/// without converting to RGB first, keeping the frame's chroma layout.
+///
+/// Frames should use full range (JPEG) levels, e.g. `YUVJ420P`, otherwise the
+/// output will look washed out.
+///
+/// When built with the `nvjpeg` feature and a CUDA capable device is present
+/// the encoding is done on the GPU with nvJPEG. If nvJPEG can't be loaded, or
+/// fails at any point, we transparently fall back to mozjpeg.
+pub struct JpegEncoder {
+    options: JpegOptions,
+    #[cfg(feature = "nvjpeg")]
+    nvjpeg: Option<NvJpegEncoder>,
+}
+
+impl JpegEncoder {
+    pub fn new() -> Self {
+        JpegEncoder::with_options(&JpegOptions::default())
+    }
+
+    pub fn with_options(options: &JpegOptions) -> Self {
+        let options = JpegOptions {
+            quality: options.quality.clamp(1, 100),
+            ..*options
+        };
+
+        #[cfg(feature = "nvjpeg")]
+        let nvjpeg = if options.progressive {
+            None
+        } else {
+            match NvJpegEncoder::new(options.quality, options.subsampling) {
+                Ok(encoder) => {
+                    log::info!("Using nvJPEG for JPEG encoding");
+                    Some(encoder)
+                }
+                Err(e) => {
+                    log::info!("nvJPEG unavailable, falling back to mozjpeg: {}", e);
+                    None
+                }
+            }
+        };
+
+        JpegEncoder {
+            options,
+            #[cfg(feature = "nvjpeg")]
+            nvjpeg,
+        }
+    }
+
+    pub fn encode(&mut self, frame: &Video) -> Result<Vec<u8>, MediaLibError> {
+        let width = frame.width();
+        let height = frame.height();
+        let (planes, subsampling) = yuv_planes(frame)?;
+
+        #[cfg(feature = "nvjpeg")]
+        if let Some(nvjpeg) = &mut self.nvjpeg {
+            match nvjpeg.encode_yuv(&planes, subsampling, width, height) {
+                Ok(buffer) => return Ok(buffer),
+                Err(e) => {
+                    // Once the GPU path has failed we don't keep retrying it for
+                    // every frame.
+                    log::error!("nvJPEG encode failed, falling back to mozjpeg: {}", e);
+                    self.nvjpeg = None;
+                }
+            }
+        }
+
+        get_jpeg_buffer(&planes, subsampling, width, height, &self.options)
+    }
+}
+
+thread_local! {
+    // Hosts call `encode_raw_frame` once per frame, setting nvJPEG up again
+    // for each would cost more than the encode itself.
+    static RAW_FRAME_ENCODER: RefCell<Option<JpegEncoder>> = const { RefCell::new(None) };
+}
+
+/// Encodes a raw frame handed over by a host, in any of the raw pixel
+/// formats, at `quality` from 1 to 100.
+pub fn encode_raw_frame(frame: &RawVideoFrame, quality: u32) -> Result<Vec<u8>, MediaLibError> {
+    let video = raw_to_video(frame)?;
+    let mut converted = Video::empty();
+    Context::get(
+        video.format(),
+        video.width(),
+        video.height(),
+        Pixel::YUVJ420P,
+        video.width(),
+        video.height(),
+        Flags::BICUBIC | Flags::ACCURATE_RND,
+    )
+    .and_then(|mut scaler| scaler.run(&video, &mut converted))
+    .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
+    let quality = quality.clamp(1, 100);
+    RAW_FRAME_ENCODER.with_borrow_mut(|encoder| {
+        if encoder
+            .as_ref()
+            .is_some_and(|encoder| encoder.options.quality != quality)
+        {
+            *encoder = None;
+        }
+        encoder
+            .get_or_insert_with(|| {
+                JpegEncoder::with_options(&JpegOptions {
+                    quality,
+                    ..JpegOptions::default()
+                })
+            })
+            .encode(&converted)
+    })
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use super::input::{no_video_stream, open_input};
+use super::timestamp;
+use crate::MediaLibError;
+use media_types::KeyframeInfo;
+
+/// Lists the keyframes of the best video stream by walking the packet
+/// metadata only, nothing is decoded.
+pub fn list_keyframes(input_path: &Path) -> Result<Vec<KeyframeInfo>, MediaLibError> {
+    let mut ictx = open_input(input_path, None)?;
+
+    let (video_stream_index, time_base) = {
+        let stream = ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or_else(|| no_video_stream(&ictx))?;
+        (stream.index(), stream.time_base())
+    };
+
+    let mut keyframes = Vec::new();
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != video_stream_index || !packet.is_key() {
+            continue;
+        }
+        // Some containers only carry decode timestamps on keyframes.
+        let Some(pts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+
+        let position = packet.position();
+        keyframes.push(KeyframeInfo {
+            pts_us: timestamp::to_us(pts, time_base),
+            byte_offset: (position >= 0).then_some(position as u64).into(),
+        });
+    }
+
+    Ok(keyframes)
+}
+
+/// Presentation times of every frame of the best video stream, in the
+/// stream's timeline, built from packet metadata like `list_keyframes`.
+pub struct FrameIndex {
+    /// Every frame in presentation order.
+    pub frame_times_us: Vec<i64>,
+    /// The keyframes among them, also in presentation order.
+    pub keyframe_times_us: Vec<i64>,
+}
+
+impl FrameIndex {
+    pub fn build(input_path: &Path, force_format: Option<&str>) -> Result<Self, MediaLibError> {
+        let mut ictx = open_input(input_path, force_format)?;
+
+        let (video_stream_index, time_base) = {
+            let stream = ictx
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .ok_or_else(|| no_video_stream(&ictx))?;
+            (stream.index(), stream.time_base())
+        };
+
+        let mut frame_times_us = Vec::new();
+        let mut keyframe_times_us = Vec::new();
+        for (stream, packet) in ictx.packets() {
+            if stream.index() != video_stream_index {
+                continue;
+            }
+            let Some(pts) = packet.pts().or(packet.dts()) else {
+                continue;
+            };
+
+            let time_us = timestamp::to_us(pts, time_base);
+            frame_times_us.push(time_us);
+            if packet.is_key() {
+                keyframe_times_us.push(time_us);
+            }
+        }
+
+        // Packets come in decode order.
+        frame_times_us.sort_unstable();
+        frame_times_us.dedup();
+        keyframe_times_us.sort_unstable();
+        keyframe_times_us.dedup();
+
+        Ok(FrameIndex {
+            frame_times_us,
+            keyframe_times_us,
+        })
+    }
+
+    /// Index of the frame shown at `time_us`.
+    pub fn frame_at(&self, time_us: i64) -> Option<usize> {
+        self.frame_times_us.binary_search(&time_us).ok()
+    }
+
+    /// Frame index range of the GOP holding frame `index`, from its keyframe
+    /// up to the next one.
+    pub fn gop_of(&self, index: usize) -> std::ops::Range<usize> {
+        let time_us = self.frame_times_us[index];
+        let next_keyframe = self
+            .keyframe_times_us
+            .partition_point(|keyframe| *keyframe <= time_us);
+
+        let start = next_keyframe
+            .checked_sub(1)
+            .and_then(|keyframe| self.frame_at(self.keyframe_times_us[keyframe]))
+            .unwrap_or(0);
+        let end = self
+            .keyframe_times_us
+            .get(next_keyframe)
+            .and_then(|keyframe| self.frame_at(*keyframe))
+            .unwrap_or(self.frame_times_us.len());
+        start..end
+    }
+}
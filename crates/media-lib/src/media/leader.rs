@@ -0,0 +1,315 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{DetectedSegment, MediaFrameDecoderOptions, MediaLibError, SegmentKind};
+
+use super::audio_decoder::AudioDecoder;
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+
+// A leader has to start this close to the head of the file to count, and
+// nothing past the window is read.
+const START_WINDOW_US: i64 = 30_000_000;
+const SEARCH_WINDOW_US: i64 = 180_000_000;
+// Shorter runs are more likely a shot that happens to match.
+const MIN_LEADER_US: i64 = 1_000_000;
+
+// Frames are checked this often and compared at this size, 10 pixels a bar.
+const BARS_SAMPLE_US: i64 = 250_000;
+const BARS_WIDTH: u32 = 70;
+const BARS_HEIGHT: u32 = 42;
+// The seven bars of the top two thirds, left to right: which of red, green
+// and blue are lit.
+const BARS: [[bool; 3]; 7] = [
+    [true, true, true],   // white
+    [true, true, false],  // yellow
+    [false, true, true],  // cyan
+    [false, true, false], // green
+    [true, false, true],  // magenta
+    [true, false, false], // red
+    [false, false, true], // blue
+];
+// Lit and unlit channels relative to the white bar, which covers both 75%
+// and 100% bars.
+const LIT_SHARE: f64 = 0.6;
+const UNLIT_SHARE: f64 = 0.35;
+const MIN_WHITE: f64 = 100.0;
+
+// Tone is looked for in 100ms blocks at 8kHz, where 1kHz falls exactly on
+// bin 100.
+const TONE_SAMPLE_RATE: u32 = 8000;
+const TONE_BLOCK_SAMPLES: usize = 800;
+const TONE_BLOCK_US: i64 = 100_000;
+const TONE_HZ: f64 = 1000.0;
+// Share of a block's power that has to be at 1kHz, and the quietest tone
+// taken, around -30dBFS. Line-up tone is usually -20 or -18dBFS.
+const MIN_TONE_SHARE: f64 = 0.8;
+const MIN_TONE_RMS: f64 = 1000.0;
+
+/// The first run of matching samples that starts within `START_WINDOW_US`
+/// and lasts at least `MIN_LEADER_US`, a single miss doesn't end it. Gives
+/// the start, end and share of samples in it that matched.
+fn leading_run(samples: &[(i64, bool)], spacing_us: i64) -> Option<(i64, i64, f32)> {
+    let first = samples
+        .iter()
+        .position(|(time_us, matched)| *matched && *time_us < START_WINDOW_US)?;
+    let mut last = first;
+    let mut matched = 1;
+    for (index, (_, is_match)) in samples.iter().enumerate().skip(first + 1) {
+        if *is_match {
+            matched += 1;
+            last = index;
+        } else if index > last + 1 {
+            break;
+        }
+    }
+    let start_us = samples[first].0;
+    let end_us = samples[last].0 + spacing_us;
+    (end_us - start_us >= MIN_LEADER_US)
+        .then(|| (start_us, end_us, matched as f32 / (last - first + 1) as f32))
+}
+
+/// Whether `frame` shows SMPTE or EBU colour bars, from the seven bars
+/// across the top of the picture.
+fn is_color_bars(frame: &Video) -> Result<bool, MediaLibError> {
+    let mut rgb = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        Pixel::RGB24,
+        BARS_WIDTH,
+        BARS_HEIGHT,
+        Flags::AREA,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut rgb))
+    .map_err(ffmpeg_error)?;
+
+    let stride = rgb.stride(0);
+    let data = rgb.data(0);
+    let bar_width = (BARS_WIDTH / 7) as usize;
+    // Two rows, both inside the top two thirds every bars pattern shares.
+    for row in [BARS_HEIGHT as usize / 5, BARS_HEIGHT as usize * 9 / 20] {
+        let means: Vec<[f64; 3]> = (0..BARS.len())
+            .map(|bar| {
+                // The middle of the bar, clear of blurred edges.
+                let columns =
+                    bar * bar_width + bar_width / 4..(bar + 1) * bar_width - bar_width / 4;
+                let mut sum = [0.0; 3];
+                for column in columns.clone() {
+                    for (channel, total) in sum.iter_mut().enumerate() {
+                        *total += data[row * stride + column * 3 + channel] as f64;
+                    }
+                }
+                sum.map(|total| total / columns.len() as f64)
+            })
+            .collect();
+
+        let white = means[0].iter().copied().fold(f64::MAX, f64::min);
+        if white < MIN_WHITE {
+            return Ok(false);
+        }
+        for (mean, lit) in means.iter().zip(BARS) {
+            for (value, lit) in mean.iter().zip(lit) {
+                let share = value / white;
+                if (lit && share < LIT_SHARE) || (!lit && share > UNLIT_SHARE) {
+                    return Ok(false);
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Colour bars at the head of the input, sampled every `BARS_SAMPLE_US`.
+fn leading_bars(input_path: &Path) -> Result<Option<(i64, i64, f32)>, MediaLibError> {
+    let mut video_decoder = match unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    } {
+        Ok(video_decoder) => video_decoder,
+        Err(e) if e.is_no_video_stream() => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    video_decoder.keyframes_only = false;
+
+    let start_time = video_decoder.start_time_us();
+    let mut samples = Vec::new();
+    let mut next_sample_us = 0;
+    while let Some(frame) = video_decoder.get_frame() {
+        let frame = frame?;
+        let Some(time_us) = video_decoder.frame_time_us(&frame) else {
+            continue;
+        };
+        let time_us = time_us - start_time;
+        if time_us >= SEARCH_WINDOW_US {
+            break;
+        }
+        if time_us < next_sample_us {
+            continue;
+        }
+        next_sample_us = time_us + BARS_SAMPLE_US;
+        let is_bars = is_color_bars(&frame)?;
+        samples.push((time_us, is_bars));
+
+        // Nothing to find once the window for a start has passed without
+        // bars, or once they've ended.
+        let any_bars = samples.iter().any(|(_, bars)| *bars);
+        if !any_bars && time_us >= START_WINDOW_US {
+            break;
+        }
+        let ended =
+            samples.len() >= 2 && samples[samples.len() - 2..].iter().all(|(_, bars)| !bars);
+        if any_bars && ended {
+            break;
+        }
+    }
+    Ok(leading_run(&samples, BARS_SAMPLE_US))
+}
+
+/// Share of a block's power at `TONE_HZ` and its RMS level, the former
+/// from the Goertzel algorithm.
+fn tone_share(block: &[i16]) -> (f64, f64) {
+    let coefficient = 2.0 * (2.0 * std::f64::consts::PI * TONE_HZ / TONE_SAMPLE_RATE as f64).cos();
+    let (mut previous, mut before_previous) = (0.0, 0.0);
+    let mut power = 0.0;
+    for sample in block {
+        let sample = *sample as f64;
+        power += sample * sample;
+        let current = sample + coefficient * previous - before_previous;
+        before_previous = previous;
+        previous = current;
+    }
+    let length = block.len() as f64;
+    let magnitude_squared = previous * previous + before_previous * before_previous
+        - coefficient * previous * before_previous;
+    let mean_square = power / length;
+    // A sine of amplitude A has a magnitude of A * N / 2 and a mean square
+    // of A^2 / 2.
+    let tone_mean_square = 2.0 * magnitude_squared / (length * length);
+    if mean_square == 0.0 {
+        return (0.0, 0.0);
+    }
+    (tone_mean_square / mean_square, mean_square.sqrt())
+}
+
+/// 1kHz line-up tone at the head of the input.
+fn leading_tone(input_path: &Path) -> Result<Option<(i64, i64, f32)>, MediaLibError> {
+    let Some(mut decoder) = AudioDecoder::open(input_path, TONE_SAMPLE_RATE)? else {
+        return Ok(None);
+    };
+
+    let mut samples = Vec::new();
+    let mut block = Vec::with_capacity(TONE_BLOCK_SAMPLES);
+    'decode: while let Some(decoded) = decoder.next_samples() {
+        for sample in decoded? {
+            block.push(sample);
+            if block.len() < TONE_BLOCK_SAMPLES {
+                continue;
+            }
+            let time_us = samples.len() as i64 * TONE_BLOCK_US;
+            let (share, rms) = tone_share(&block);
+            samples.push((time_us, share >= MIN_TONE_SHARE && rms >= MIN_TONE_RMS));
+            block.clear();
+            if time_us >= SEARCH_WINDOW_US {
+                break 'decode;
+            }
+        }
+    }
+    Ok(leading_run(&samples, TONE_BLOCK_US))
+}
+
+/// Finds colour bars and line-up tone at the head of the input, the
+/// technical leader ingest trims before making proxies. Each is reported
+/// as a segment from where it starts to where it ends.
+pub fn detect_leader(input_path: &Path) -> Result<Vec<DetectedSegment>, MediaLibError> {
+    let mut segments = Vec::new();
+    let found = [
+        (leading_bars(input_path)?, SegmentKind::ColorBars),
+        (leading_tone(input_path)?, SegmentKind::Tone),
+    ];
+    for (run, kind) in found {
+        if let Some((start_us, end_us, share)) = run {
+            segments.push(DetectedSegment {
+                start_us,
+                end_us,
+                confidence: share,
+                kind,
+            });
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_runs_at_the_head() {
+        let samples: Vec<(i64, bool)> = (0..20)
+            .map(|index| {
+                (
+                    index * BARS_SAMPLE_US,
+                    (2..12).contains(&index) && index != 6,
+                )
+            })
+            .collect();
+        let (start_us, end_us, share) = leading_run(&samples, BARS_SAMPLE_US).unwrap();
+        assert_eq!(start_us, 2 * BARS_SAMPLE_US);
+        assert_eq!(end_us, 12 * BARS_SAMPLE_US);
+        assert_eq!(share, 0.9);
+
+        // Too short to be a leader.
+        let short: Vec<(i64, bool)> = (0..20)
+            .map(|index| (index * BARS_SAMPLE_US, index < 2))
+            .collect();
+        assert_eq!(leading_run(&short, BARS_SAMPLE_US), None);
+    }
+
+    fn frame(pixel: impl Fn(u32) -> [u8; 3]) -> Video {
+        let (width, height) = (BARS_WIDTH * 4, BARS_HEIGHT * 4);
+        let mut frame = Video::new(Pixel::RGB24, width, height);
+        let stride = frame.stride(0);
+        for row in 0..height as usize {
+            for column in 0..width {
+                let offset = row * stride + column as usize * 3;
+                frame.data_mut(0)[offset..offset + 3].copy_from_slice(&pixel(column));
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn it_recognizes_color_bars() {
+        let bars = frame(|column| {
+            let bar = (column * 7 / (BARS_WIDTH * 4)) as usize;
+            BARS[bar].map(|lit| if lit { 191 } else { 16 })
+        });
+        assert!(is_color_bars(&bars).unwrap());
+
+        let gray = frame(|_| [128, 128, 128]);
+        assert!(!is_color_bars(&gray).unwrap());
+    }
+
+    fn sine(hz: f64) -> Vec<i16> {
+        (0..TONE_BLOCK_SAMPLES)
+            .map(|index| {
+                let time = index as f64 / TONE_SAMPLE_RATE as f64;
+                (8000.0 * (2.0 * std::f64::consts::PI * hz * time).sin()) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_measures_how_much_of_a_block_is_tone() {
+        let (share, rms) = tone_share(&sine(TONE_HZ));
+        assert!(share > 0.95, "share {}", share);
+        assert!((rms - 8000.0 / 2f64.sqrt()).abs() < 50.0, "rms {}", rms);
+
+        let (share, _) = tone_share(&sine(440.0));
+        assert!(share < MIN_TONE_SHARE, "share {}", share);
+        assert_eq!(tone_share(&[0; TONE_BLOCK_SAMPLES]), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::MediaLibError;
+
+use super::filter_graph::FilterGraph;
+
+/// `value` with a backslash before each of `special` and backslashes.
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The lut3d filter spec for the LUT at `path`. The path is escaped once
+/// for the option parser and again for the graph parser, so any file name
+/// works.
+fn lut3d_spec(path: &Path) -> String {
+    let path = escape(&path.to_string_lossy(), "':");
+    format!("lut3d=file={}:interp=tetrahedral", escape(&path, "'[],;"))
+}
+
+/// Fails unless `path` is a .cube file with a 3D table, so a wrong path is
+/// reported as such rather than as whatever lut3d makes of it.
+fn check_cube(path: &Path) -> Result<(), MediaLibError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        MediaLibError::UnknownError(format!("Failed to read LUT {}: {}", path.display(), e).into())
+    })?;
+    let is_3d = contents
+        .lines()
+        .any(|line| line.trim_start().starts_with("LUT_3D_SIZE"));
+    if !is_3d {
+        return Err(MediaLibError::UnknownError(
+            format!("{} is not a 3D .cube LUT", path.display()).into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Runs every frame through a 3D LUT, e.g. to turn log footage from a
+/// camera into something viewable for proxies and stills. Frames come out
+/// as RGB.
+pub struct Lut3d {
+    spec: String,
+    // Built for the first frame, and again if frames change format or size.
+    graph: Option<FilterGraph>,
+}
+
+impl Lut3d {
+    pub fn open(path: &Path) -> Result<Self, MediaLibError> {
+        check_cube(path)?;
+        Ok(Lut3d {
+            spec: lut3d_spec(path),
+            graph: None,
+        })
+    }
+
+    /// The LUT from `path` when there is one, for options where it's
+    /// optional.
+    pub fn from_option(
+        path: &stabby::option::Option<stabby::string::String>,
+    ) -> Result<Option<Self>, MediaLibError> {
+        path.as_ref()
+            .map(|path| Lut3d::open(Path::new(&**path)))
+            .transpose()
+    }
+
+    pub fn apply(&mut self, frame: Video) -> Result<Video, MediaLibError> {
+        let graph = match self.graph.take() {
+            Some(graph) if graph.accepts(&frame) => self.graph.insert(graph),
+            _ => self.graph.insert(FilterGraph::new(&frame, &self.spec)?),
+        };
+        graph.run(&frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg_next::format::Pixel;
+
+    use super::*;
+
+    // A .cube file with a LUT turning every colour into its negative.
+    fn negative_lut(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("media-lib-{}-{}.cube", name, std::process::id()));
+        let mut cube = String::from("LUT_3D_SIZE 2\n");
+        // Red changes fastest.
+        for i in 0..8 {
+            cube.push_str(&format!(
+                "{} {} {}\n",
+                1 - (i & 1),
+                1 - ((i >> 1) & 1),
+                1 - ((i >> 2) & 1)
+            ));
+        }
+        std::fs::write(&path, cube).unwrap();
+        path
+    }
+
+    #[test]
+    fn it_runs_frames_through_the_lut() {
+        let path = negative_lut("negative");
+        let mut lut = Lut3d::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut frame = Video::new(Pixel::RGB24, 16, 16);
+        for pixel in frame.data_mut(0).chunks_exact_mut(3) {
+            pixel.copy_from_slice(&[200, 100, 50]);
+        }
+        let graded = lut.apply(frame).unwrap();
+        assert_eq!(graded.format(), Pixel::RGB24);
+        let pixel = &graded.data(0)[..3];
+        for (channel, expected) in pixel.iter().zip([55u8, 155, 205]) {
+            assert!(channel.abs_diff(expected) <= 2, "{:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn it_rejects_files_without_a_3d_table() {
+        let path = std::env::temp_dir().join(format!("media-lib-1d-{}.cube", std::process::id()));
+        std::fs::write(&path, "LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").unwrap();
+        assert!(Lut3d::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_escapes_paths_for_the_graph() {
+        assert_eq!(
+            lut3d_spec(Path::new("/luts/a'b:c,d.cube")),
+            "lut3d=file=/luts/a\\\\\\'b\\\\:c\\,d.cube:interp=tetrahedral"
+        );
+    }
+}
@@ -1,104 +1,521 @@
+mod alpha;
+mod animation;
+mod audio_decoder;
+mod audio_preview;
+mod avio;
+mod bundle;
+mod canvas;
+mod channels;
+mod checksum;
+#[cfg(feature = "chromaprint")]
+mod chromaprint;
+mod dataset;
+mod decimate;
+mod decoder_pool;
+mod deinterlace;
+mod diagnostics;
+#[cfg(any(feature = "nvjpeg", feature = "chromaprint", feature = "nvml"))]
+mod dylib;
+mod encode_pool;
+mod encoder;
+mod filmstrip;
+mod filter_graph;
+mod fingerprint;
+mod frame_export;
+mod frame_session;
+#[cfg(feature = "fuzzing")]
+mod fuzz;
+mod gpu_frames;
 mod hardware_accelerated_video_decoder;
+mod hdr;
+mod health;
+mod hw_scale;
+mod image_sequence;
+mod input;
+mod job;
+mod jpeg;
+mod keyframe_index;
+mod leader;
+mod lut;
+#[cfg(feature = "nvjpeg")]
+mod nvjpeg;
+#[cfg(feature = "nvml")]
+mod nvml;
+mod ocr;
+mod output;
+mod overlay;
+mod packets;
+mod poster;
+mod preview;
+mod probe;
+mod proxy;
+mod redaction;
+mod resampler;
+mod restream;
+mod reverse;
+mod segments;
+mod sniff;
+mod speed;
+mod spherical;
+mod srgb;
+mod still;
+mod storyboard;
+mod subtitles;
+mod support;
+mod sync;
+mod temp;
+mod test_asset;
+mod threaded;
+mod timelapse;
+mod timestamp;
+mod tonemap;
+mod video_writer;
 use crate::MediaLibError;
-use ffmpeg::software::scaling::{context::Context, flag::Flags};
+pub use animation::preview_animation;
+pub use audio_decoder::AudioDecoder;
+pub use avio::ReadSeek;
+pub use bundle::preview_bundle;
+pub use channels::export_audio_channels;
+#[cfg(feature = "chromaprint")]
+pub use chromaprint::fingerprint_audio;
+pub use dataset::export_dataset;
+use encode_pool::EncodePool;
+pub use encoder::HardwareAcceleratedVideoEncoder;
 use ffmpeg::util::frame::video::Video;
 use ffmpeg_next::{self as ffmpeg};
+pub use filmstrip::filmstrip;
+pub use fingerprint::fingerprint_video;
+pub use frame_export::{export_frame, export_redacted_frame, extract_thumbnail};
+pub use frame_session::FrameSession;
+#[cfg(feature = "fuzzing")]
+pub use fuzz::{decode_bytes, probe_bytes, MAX_INPUT_BYTES};
+pub use gpu_frames::{GpuFrame, GpuFrameIterator};
+pub use hardware_accelerated_video_decoder::preheat_hardware_device;
 use hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+pub use image_sequence::export_image_sequence;
+pub use job::Job;
+pub use jpeg::encode_raw_frame;
+pub use keyframe_index::list_keyframes;
+pub use leader::detect_leader;
+use media_types::{
+    Backpressure, DecodeProgress, JobContext, MediaFrameDecoderOptions, ProcessingReport,
+    ScalingMode, Size, StreamHealth, StreamHealthObserverBox,
+};
+#[cfg(feature = "nvml")]
+pub use nvml::decoder_utilization;
+pub use ocr::extract_on_screen_text;
+pub use overlay::suggest_overlay_placement;
+pub use packets::PacketIterator;
+pub use preview::preview_image;
+pub use probe::{list_video_streams, probe_media, probe_media_with_decryption, probe_reader};
+pub use proxy::{redacted_scrub_proxy, scrub_proxy};
+pub use restream::restream;
+pub use reverse::ReverseFrameIterator;
+pub use segments::detect_segments;
+pub use sniff::{sniff_bytes, sniff_file};
+use std::collections::VecDeque;
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+pub use storyboard::storyboard;
+pub use subtitles::extract_subtitles;
+pub use support::hardware_support;
+pub use sync::SyncedDecoder;
+pub use temp::{end_temp_session, start_temp_session, TempPath};
+pub use test_asset::generate_test_asset;
+pub use threaded::ThreadedDecoder;
+pub use timelapse::timelapse;
 
 pub struct KeyframeIterator {
-    scaler: Option<Context>,
+    scaler: Option<canvas::FrameScaler>,
+    // What the scaler converts to, see `jpeg::pixel_format`.
+    pixel_format: ffmpeg_next::format::Pixel,
     video_decoder: HardwareAcceleratedVideoDecoder,
-    pub target_width: u32,
-    pub target_height: u32,
+    encode_pool: EncodePool,
+    decoder_done: bool,
+    // Remaining seek points (in microseconds) when sampling a fixed number of
+    // frames across the duration.
+    sample_points: Option<VecDeque<i64>>,
+    // Time of the keyframe the last sample point landed on.
+    last_sample_us: Option<i64>,
+    pub target_size: Size,
+    // What frames are scaled to before they're centered in `target_size`,
+    // the two only differ when letterboxing or cropping.
+    scaled_size: Size,
+    requested_size: Size,
+    scaling_mode: ScalingMode,
+    auto_rotate: bool,
+    alpha: alpha::AlphaFlattener,
+    // Set when wide gamut frames are to be converted, see `convert_to_srgb`.
+    srgb_converter: Option<srgb::SrgbConverter>,
+    // Set by `set_job`, checked before each frame is decoded.
+    job: Option<Job>,
+    // Set when HDR frames are to be tone mapped, see `tone_mapping`.
+    tone_mapper: Option<tonemap::ToneMapper>,
+    // Set when interlaced frames are to be deinterlaced, see `deinterlace`.
+    deinterlacer: Option<deinterlace::Deinterlacer>,
+    // Set when equirectangular frames are to be flattened, see
+    // `flatten_360`.
+    reprojector: Option<spherical::Reprojector>,
+    started: Instant,
+    frames_returned: u64,
+    // Set once `get` has returned none.
+    finished: bool,
 }
 
 type Item = Result<Vec<u8>, MediaLibError>;
 
-fn get_jpeg_buffer(slice: &[u8], width: u32, height: u32) -> Result<Vec<u8>, MediaLibError> {
-    std::panic::catch_unwind(|| {
-        let mut comp = mozjpeg::Compress::new(mozjpeg::ColorSpace::JCS_EXT_RGBA);
-
-        comp.set_size(width as usize, height as usize);
-        let mut comp = comp
-            .start_compress(Vec::new())
-            .map_err(|e| MediaLibError::ImageError(e.to_string().into()))?; // any io::Write will work
-
-        // replace with your image data
-        comp.write_scanlines(&slice)
-            .map_err(|e| MediaLibError::ImageError(e.to_string().into()))?;
-
-        let writer = comp
-            .finish()
-            .map_err(|e| MediaLibError::ImageError(e.to_string().into()))?;
-        Ok(writer)
-    })
-    .map_err(|e| MediaLibError::UnknownError(format!("Panic in get_jpeg_buffer: {:?}", e).into()))?
+/// For `map_err` on FFmpeg calls, which all fail the same way.
+pub(crate) fn ffmpeg_error(e: ffmpeg::Error) -> MediaLibError {
+    MediaLibError::FFmpegError(e.to_string().into())
+}
+
+/// The same for filesystem calls.
+pub(crate) fn io_error(e: std::io::Error) -> MediaLibError {
+    MediaLibError::UnknownError(e.to_string().into())
+}
+
+/// `done_us` of `total_us` as a percentage, none without a total.
+fn percent(done_us: i64, total_us: i64) -> Option<f64> {
+    (total_us > 0).then(|| (done_us as f64 * 100.0 / total_us as f64).clamp(0.0, 100.0))
+}
+
+/// Midpoints of `count` equal slices of `start_us` to `end_us`, which keeps
+/// samples away from the (often black) very first and last frames.
+fn sample_points(start_us: i64, end_us: i64, count: u32) -> VecDeque<i64> {
+    (0..count as i64)
+        .map(|i| start_us + (end_us - start_us) * (2 * i + 1) / (2 * count as i64))
+        .collect()
 }
 
 impl KeyframeIterator {
-    pub fn new(input_path: &Path) -> Result<Self, MediaLibError> {
-        let video_decoder = unsafe { HardwareAcceleratedVideoDecoder::new(input_path) }?;
+    pub fn new(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let video_decoder = unsafe { HardwareAcceleratedVideoDecoder::new(input_path, options) }?;
+        Self::with_decoder(video_decoder, options)
+    }
+
+    /// Keyframes of an input read from memory (or any other reader) rather
+    /// than a file.
+    pub fn from_reader(
+        reader: Box<dyn ReadSeek>,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let video_decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::from_reader(reader, options) }?;
+        Self::with_decoder(video_decoder, options)
+    }
+
+    fn with_decoder(
+        mut video_decoder: HardwareAcceleratedVideoDecoder,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let requested_size = canvas::requested_size(options);
+        let reprojector = match video_decoder.spherical() {
+            Some(metadata) if options.flatten_360 => spherical::Reprojector::new(
+                &metadata,
+                options.flat_view_fov_degrees,
+                video_decoder.size(),
+            ),
+            _ => None,
+        };
+        let source_size = reprojector
+            .as_ref()
+            .map_or(video_decoder.size(), |reprojector| {
+                reprojector.output_size()
+            });
+        let (scaled_size, target_size) = options.scaling_mode.apply(source_size, requested_size);
+        // The flat view is taken from the whole panorama, which has to
+        // reach it at full size.
+        if options.hw_prescale && reprojector.is_none() {
+            video_decoder.set_prescale(scaled_size, options.tone_mapping);
+        }
+
+        let start_us = options.start_time_ms as i64 * 1000;
+        let end_us = (options.end_time_ms > 0).then(|| options.end_time_ms as i64 * 1000);
+        if end_us.is_some_and(|end_us| end_us < start_us) {
+            return Err(MediaLibError::UnknownError(
+                format!(
+                    "Invalid time range {}ms to {}ms",
+                    options.start_time_ms, options.end_time_ms
+                )
+                .into(),
+            ));
+        }
+        if let Some(end_us) = end_us {
+            video_decoder.set_end(end_us);
+        }
+        if start_us > 0 {
+            video_decoder.seek(start_us)?;
+        }
 
-        // TODO: make this configurable
-        let target_height = 360;
-        let aspect_ratio = video_decoder.width() as f32 / video_decoder.height() as f32;
-        let target_width = (target_height as f32 * aspect_ratio).round() as u32;
+        let sample_points = match (options.sample_count, end_us.or(video_decoder.duration_us())) {
+            (0, _) => None,
+            (count, Some(end_us)) => Some(sample_points(start_us, end_us, count)),
+            (_, None) => {
+                log::warn!(
+                    "Input has no known duration, returning every keyframe instead of sampling"
+                );
+                None
+            }
+        };
 
+        // Key frames are too far apart to deinterlace against each other.
+        let deinterlacer = options
+            .deinterlace
+            .then(|| deinterlace::Deinterlacer::new(video_decoder.keyframes_only));
         Ok(KeyframeIterator {
             video_decoder,
+            encode_pool: EncodePool::new(options),
+            decoder_done: false,
+            sample_points,
+            last_sample_us: None,
             scaler: None,
-            target_width,
-            target_height,
+            pixel_format: jpeg::pixel_format(options.jpeg.subsampling),
+            target_size,
+            scaled_size,
+            requested_size,
+            scaling_mode: options.scaling_mode,
+            auto_rotate: options.auto_rotate,
+            alpha: alpha::AlphaFlattener::new(options.alpha_background, options.alpha_color),
+            srgb_converter: options.convert_to_srgb.then(srgb::SrgbConverter::default),
+            tone_mapper: tonemap::ToneMapper::new(options.tone_mapping),
+            deinterlacer,
+            reprojector,
+            started: Instant::now(),
+            frames_returned: 0,
+            finished: false,
+            job: None,
         })
     }
 
-    fn run_scaler(&mut self, reference_frame: &Video) -> Result<Video, MediaLibError> {
-        // This is a little clunky but we don't really know up front what the
-        // format will be so we need to check and conditionally create the
-        // scaler once we know what the format is.
-        let scaler_ref = match &mut self.scaler {
+    fn run_scaler(&mut self, decoded: &Video) -> Result<Video, MediaLibError> {
+        let deinterlaced = self
+            .deinterlacer
+            .as_mut()
+            .and_then(|deinterlacer| deinterlacer.apply(decoded));
+        let decoded = deinterlaced.as_ref().unwrap_or(decoded);
+        // Tone mapped frames are BT.709 already.
+        let converted = match self
+            .tone_mapper
+            .as_mut()
+            .and_then(|mapper| mapper.apply(decoded))
+        {
+            Some(mapped) => Some(mapped),
+            None => self
+                .srgb_converter
+                .as_mut()
+                .and_then(|converter| converter.apply(decoded)),
+        };
+        let converted = converted.as_ref().unwrap_or(decoded);
+        let flattened = self.alpha.apply(converted)?;
+        let flattened = flattened.as_ref().unwrap_or(converted);
+        let correction = still::PictureCorrection::for_frame(decoded, self.auto_rotate);
+        let corrected = match &correction {
+            Some(correction) => correction.correct_colors(flattened)?,
+            None => None,
+        };
+        let corrected = corrected.as_ref().unwrap_or(flattened);
+        let reprojected = self
+            .reprojector
+            .as_mut()
+            .and_then(|reprojector| reprojector.apply(corrected));
+        let reference_frame = reprojected.as_ref().unwrap_or(corrected);
+
+        // Sideways frames are only known to be sideways once the first one
+        // is decoded, so the scaler waits for it.
+        let scaler = match &mut self.scaler {
             Some(scaler) => scaler,
             None => {
-                let scaler = Context::get(
-                    reference_frame.format(),
-                    reference_frame.width(),
-                    reference_frame.height(),
-                    ffmpeg_next::format::Pixel::RGBA,
-                    self.target_width,
-                    self.target_height,
-                    Flags::BILINEAR,
-                )
-                .map_err(|e| MediaLibError::FFmpegError(e.to_string().into()))?;
-                self.scaler = Some(scaler);
-                self.scaler
-                    .as_mut()
-                    .ok_or(MediaLibError::FFmpegError("Failed to create scaler".into()))?
+                // Photos and videos taken sideways are turned upright by the
+                // scaler's filters, and come out the other way round.
+                let orientation = correction
+                    .as_ref()
+                    .map_or(1, |correction| correction.orientation());
+                if correction.is_some_and(|correction| correction.is_transposed()) {
+                    (self.scaled_size, self.target_size) = self.scaling_mode.apply(
+                        still::upright_size(
+                            Size::new(reference_frame.width(), reference_frame.height()),
+                            orientation,
+                        ),
+                        self.requested_size,
+                    );
+                }
+                // Full range YUV goes straight into the JPEG encoder without
+                // any further colour conversion.
+                self.scaler.insert(canvas::FrameScaler::new(
+                    self.pixel_format,
+                    orientation,
+                    self.scaled_size,
+                    self.target_size,
+                ))
             }
         };
+        scaler.run(reference_frame)
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        self.video_decoder.dropped_frames
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        self.video_decoder.backpressure()
+    }
+
+    /// See `HardwareAcceleratedVideoDecoder::interrupt_flag`.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.video_decoder.interrupt_flag()
+    }
+
+    pub fn stream_health(&self) -> StreamHealth {
+        self.video_decoder.stream_health()
+    }
+
+    pub fn set_health_observer(&mut self, observer: StreamHealthObserverBox) {
+        self.video_decoder.set_health_observer(observer);
+    }
+
+    /// Continues from the frame shown at `timestamp_us` (from the start of
+    /// the input), dropping frames decoded ahead of the old position. Ends
+    /// sampling, every keyframe after the seek is returned.
+    pub fn seek(&mut self, timestamp_us: i64) -> Result<(), MediaLibError> {
+        self.video_decoder.seek(timestamp_us)?;
+        self.encode_pool.clear();
+        self.sample_points = None;
+        self.decoder_done = false;
+        self.finished = false;
+        Ok(())
+    }
+
+    /// Where the frame returned last is in the input.
+    pub fn progress(&self) -> DecodeProgress {
+        let position_us = self.encode_pool.last_pts();
+        let duration_us = self.video_decoder.duration_us();
+        let percent = match (position_us, duration_us) {
+            (_, Some(_)) if self.finished => Some(100.0),
+            (Some(position_us), Some(duration_us)) => percent(position_us, duration_us),
+            (None, Some(_)) => Some(0.0),
+            (_, None) => None,
+        };
+        DecodeProgress {
+            position_us: position_us.into(),
+            duration_us: duration_us.into(),
+            percent: percent.into(),
+        }
+    }
+
+    /// See `HardwareAcceleratedVideoDecoder::pipeline_description`, plus
+    /// what decoded frames are turned into.
+    pub fn pipeline_description(&self) -> String {
+        format!(
+            "{}\noutput: yuvj420p {}x{} on a {}x{} canvas, JPEG",
+            self.video_decoder.pipeline_description(),
+            self.scaled_size.width,
+            self.scaled_size.height,
+            self.target_size.width,
+            self.target_size.height
+        )
+    }
+
+    pub fn report(&self) -> ProcessingReport {
+        let mut warnings = stabby::vec::Vec::with_capacity(self.video_decoder.warnings.len());
+        for warning in &self.video_decoder.warnings {
+            warnings.push(warning.as_str().into());
+        }
 
-        let mut rgb_frame = Video::empty();
-        if let Err(e) = scaler_ref.run(&reference_frame, &mut rgb_frame) {
-            return Err(MediaLibError::FFmpegError(e.to_string().into()));
+        ProcessingReport {
+            wall_time_ms: self.started.elapsed().as_millis() as u64,
+            frames_decoded: self.video_decoder.frames_decoded,
+            frames_returned: self.frames_returned,
+            frames_dropped: self.video_decoder.dropped_frames,
+            hardware_device: self
+                .video_decoder
+                .hardware_device_name()
+                .map(|name| name.as_str().into())
+                .into(),
+            warnings,
+            outputs: stabby::vec::Vec::new(),
         }
+    }
+
+    fn next_scaled(&mut self) -> Option<Result<Video, MediaLibError>> {
+        loop {
+            if let Some(Err(e)) = self.job.as_mut().map(Job::check) {
+                return Some(Err(e));
+            }
+            if let Some(sample_points) = &mut self.sample_points {
+                let timestamp_us = sample_points.pop_front()?;
+                if let Err(e) = self.video_decoder.seek_to_keyframe(timestamp_us) {
+                    return Some(Err(e));
+                }
+            }
 
-        Ok(rgb_frame)
+            let decoded = match self.video_decoder.get_frame()? {
+                Ok(decoded) => decoded,
+                Err(e) => return Some(Err(e)),
+            };
+            // Carried through the encode pool for `progress`.
+            let time_us = self
+                .video_decoder
+                .frame_time_us(&decoded)
+                .map(|time_us| time_us - self.video_decoder.start_time_us());
+            if self.sample_points.is_some() && time_us.is_some() {
+                // Points closer together than the keyframes land on the same
+                // one, which is only returned once.
+                if self
+                    .last_sample_us
+                    .is_some_and(|last_sample_us| time_us <= Some(last_sample_us))
+                {
+                    continue;
+                }
+                self.last_sample_us = time_us;
+            }
+            return Some(self.run_scaler(&decoded).map(|mut scaled| {
+                scaled.set_pts(time_us);
+                scaled
+            }));
+        }
+    }
+
+    /// Cancellation, a deadline, progress and log tagging for the frames
+    /// still to come, replacing any job set before.
+    pub fn set_job(&mut self, job: JobContext) {
+        self.job = Some(Job::detached(job).interrupt_with(self.interrupt_flag()));
     }
 
     pub fn get(&mut self) -> Option<Item> {
-        let result = self.video_decoder.get_frame()?;
+        let _scope = self.job.as_ref().map(Job::enter);
+        // Decode ahead while the encode pool has room so decoding overlaps
+        // with encoding the frames already handed off.
+        while !self.decoder_done && !self.encode_pool.is_full() {
+            match self.next_scaled() {
+                Some(Ok(scaled_frame)) => self.encode_pool.submit(scaled_frame),
+                // Frames still being encoded aren't wanted after a cancel.
+                Some(Err(e)) if e.is_cancelled() => {
+                    self.encode_pool.clear();
+                    self.encode_pool.push_result(Err(e));
+                    self.decoder_done = true;
+                }
+                Some(Err(e)) => self.encode_pool.push_result(Err(e)),
+                None => self.decoder_done = true,
+            }
+        }
+
+        let result = self.encode_pool.next();
         match result {
-            Ok(decoded) => match self.run_scaler(&decoded) {
-                Ok(rgb_frame) => {
-                    let width = rgb_frame.width();
-                    let height = rgb_frame.height();
-                    let buffer = rgb_frame.data(0);
-                    let jpeg_buffer = get_jpeg_buffer(buffer, width, height);
-                    Some(jpeg_buffer)
+            Some(Ok(_)) => {
+                self.frames_returned += 1;
+                if let (Some(job), Some(position_us)) = (&mut self.job, self.encode_pool.last_pts())
+                {
+                    job.progress(position_us, self.video_decoder.duration_us());
                 }
-                Err(e) => Some(Err(MediaLibError::FFmpegError(e.to_string().into()))),
-            },
-            Err(e) => Some(Err(MediaLibError::FFmpegError(e.to_string().into()))),
+            }
+            None => self.finished = true,
+            Some(Err(_)) => {}
         }
+        result
     }
 }
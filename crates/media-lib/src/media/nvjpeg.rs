@@ -0,0 +1,329 @@
+use std::ffi::c_void;
+use std::ptr::null_mut;
+
+use libloading::Library;
+use media_types::{ChromaSubsampling, MediaLibError};
+
+use super::dylib::{get_symbol, load_first};
+use super::jpeg::Plane;
+
+// nvJPEG and the CUDA runtime are loaded at runtime rather than linked so the
+// library still loads on machines without a CUDA install.
+#[cfg(target_os = "windows")]
+const NVJPEG_LIBRARIES: &[&str] = &["nvjpeg64_12.dll", "nvjpeg64_11.dll"];
+#[cfg(not(target_os = "windows"))]
+const NVJPEG_LIBRARIES: &[&str] = &["libnvjpeg.so", "libnvjpeg.so.12", "libnvjpeg.so.11"];
+
+#[cfg(target_os = "windows")]
+const CUDART_LIBRARIES: &[&str] = &["cudart64_12.dll", "cudart64_110.dll"];
+#[cfg(not(target_os = "windows"))]
+const CUDART_LIBRARIES: &[&str] = &["libcudart.so", "libcudart.so.12", "libcudart.so.11.0"];
+
+const NVJPEG_STATUS_SUCCESS: i32 = 0;
+const CUDA_SUCCESS: i32 = 0;
+// cudaMemcpyKind::cudaMemcpyHostToDevice
+const CUDA_MEMCPY_HOST_TO_DEVICE: i32 = 1;
+// nvjpegChromaSubsampling_t
+const NVJPEG_CSS_444: i32 = 0;
+const NVJPEG_CSS_422: i32 = 1;
+const NVJPEG_CSS_420: i32 = 2;
+const NVJPEG_MAX_COMPONENT: usize = 4;
+
+type NvJpegHandle = *mut c_void;
+type NvJpegEncoderState = *mut c_void;
+type NvJpegEncoderParams = *mut c_void;
+type CudaStream = *mut c_void;
+
+#[repr(C)]
+struct NvJpegImage {
+    channel: [*mut u8; NVJPEG_MAX_COMPONENT],
+    pitch: [usize; NVJPEG_MAX_COMPONENT],
+}
+
+struct NvJpegApi {
+    create_simple: unsafe extern "C" fn(*mut NvJpegHandle) -> i32,
+    destroy: unsafe extern "C" fn(NvJpegHandle) -> i32,
+    encoder_state_create:
+        unsafe extern "C" fn(NvJpegHandle, *mut NvJpegEncoderState, CudaStream) -> i32,
+    encoder_state_destroy: unsafe extern "C" fn(NvJpegEncoderState) -> i32,
+    encoder_params_create:
+        unsafe extern "C" fn(NvJpegHandle, *mut NvJpegEncoderParams, CudaStream) -> i32,
+    encoder_params_destroy: unsafe extern "C" fn(NvJpegEncoderParams) -> i32,
+    encoder_params_set_quality: unsafe extern "C" fn(NvJpegEncoderParams, i32, CudaStream) -> i32,
+    encoder_params_set_sampling_factors:
+        unsafe extern "C" fn(NvJpegEncoderParams, i32, CudaStream) -> i32,
+    encode_yuv: unsafe extern "C" fn(
+        NvJpegHandle,
+        NvJpegEncoderState,
+        NvJpegEncoderParams,
+        *const NvJpegImage,
+        i32,
+        i32,
+        i32,
+        CudaStream,
+    ) -> i32,
+    encode_retrieve_bitstream: unsafe extern "C" fn(
+        NvJpegHandle,
+        NvJpegEncoderState,
+        *mut u8,
+        *mut usize,
+        CudaStream,
+    ) -> i32,
+    cuda_malloc: unsafe extern "C" fn(*mut *mut c_void, usize) -> i32,
+    cuda_free: unsafe extern "C" fn(*mut c_void) -> i32,
+    cuda_memcpy: unsafe extern "C" fn(*mut c_void, *const c_void, usize, i32) -> i32,
+    cuda_stream_synchronize: unsafe extern "C" fn(CudaStream) -> i32,
+    // The libraries must outlive the function pointers above.
+    _nvjpeg: Library,
+    _cudart: Library,
+}
+
+impl NvJpegApi {
+    fn load() -> Result<Self, MediaLibError> {
+        let nvjpeg = load_first(NVJPEG_LIBRARIES)?;
+        let cudart = load_first(CUDART_LIBRARIES)?;
+
+        unsafe {
+            Ok(NvJpegApi {
+                create_simple: get_symbol(&nvjpeg, b"nvjpegCreateSimple\0")?,
+                destroy: get_symbol(&nvjpeg, b"nvjpegDestroy\0")?,
+                encoder_state_create: get_symbol(&nvjpeg, b"nvjpegEncoderStateCreate\0")?,
+                encoder_state_destroy: get_symbol(&nvjpeg, b"nvjpegEncoderStateDestroy\0")?,
+                encoder_params_create: get_symbol(&nvjpeg, b"nvjpegEncoderParamsCreate\0")?,
+                encoder_params_destroy: get_symbol(&nvjpeg, b"nvjpegEncoderParamsDestroy\0")?,
+                encoder_params_set_quality: get_symbol(
+                    &nvjpeg,
+                    b"nvjpegEncoderParamsSetQuality\0",
+                )?,
+                encoder_params_set_sampling_factors: get_symbol(
+                    &nvjpeg,
+                    b"nvjpegEncoderParamsSetSamplingFactors\0",
+                )?,
+                encode_yuv: get_symbol(&nvjpeg, b"nvjpegEncodeYUV\0")?,
+                encode_retrieve_bitstream: get_symbol(&nvjpeg, b"nvjpegEncodeRetrieveBitstream\0")?,
+                cuda_malloc: get_symbol(&cudart, b"cudaMalloc\0")?,
+                cuda_free: get_symbol(&cudart, b"cudaFree\0")?,
+                cuda_memcpy: get_symbol(&cudart, b"cudaMemcpy\0")?,
+                cuda_stream_synchronize: get_symbol(&cudart, b"cudaStreamSynchronize\0")?,
+                _nvjpeg: nvjpeg,
+                _cudart: cudart,
+            })
+        }
+    }
+}
+
+fn check_nvjpeg(status: i32, call: &str) -> Result<(), MediaLibError> {
+    if status != NVJPEG_STATUS_SUCCESS {
+        return Err(MediaLibError::ImageError(
+            format!("{} failed with status {}", call, status).into(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_cuda(status: i32, call: &str) -> Result<(), MediaLibError> {
+    if status != CUDA_SUCCESS {
+        return Err(MediaLibError::ImageError(
+            format!("{} failed with CUDA error {}", call, status).into(),
+        ));
+    }
+    Ok(())
+}
+
+fn css(subsampling: ChromaSubsampling) -> i32 {
+    match subsampling {
+        ChromaSubsampling::Yuv420 => NVJPEG_CSS_420,
+        ChromaSubsampling::Yuv422 => NVJPEG_CSS_422,
+        ChromaSubsampling::Yuv444 => NVJPEG_CSS_444,
+    }
+}
+
+/// GPU JPEG encoder backed by nvJPEG. All work happens on the default CUDA
+/// stream.
+pub struct NvJpegEncoder {
+    api: NvJpegApi,
+    handle: NvJpegHandle,
+    state: NvJpegEncoderState,
+    params: NvJpegEncoderParams,
+}
+
+impl NvJpegEncoder {
+    pub fn new(quality: u32, subsampling: ChromaSubsampling) -> Result<Self, MediaLibError> {
+        let api = NvJpegApi::load()?;
+        let stream: CudaStream = null_mut();
+
+        // Build up the encoder incrementally so Drop cleans up whatever was
+        // created if a later step fails.
+        let mut encoder = NvJpegEncoder {
+            api,
+            handle: null_mut(),
+            state: null_mut(),
+            params: null_mut(),
+        };
+
+        unsafe {
+            check_nvjpeg(
+                (encoder.api.create_simple)(&mut encoder.handle),
+                "nvjpegCreateSimple",
+            )?;
+            check_nvjpeg(
+                (encoder.api.encoder_state_create)(encoder.handle, &mut encoder.state, stream),
+                "nvjpegEncoderStateCreate",
+            )?;
+            check_nvjpeg(
+                (encoder.api.encoder_params_create)(encoder.handle, &mut encoder.params, stream),
+                "nvjpegEncoderParamsCreate",
+            )?;
+            check_nvjpeg(
+                (encoder.api.encoder_params_set_quality)(encoder.params, quality as i32, stream),
+                "nvjpegEncoderParamsSetQuality",
+            )?;
+            check_nvjpeg(
+                (encoder.api.encoder_params_set_sampling_factors)(
+                    encoder.params,
+                    css(subsampling),
+                    stream,
+                ),
+                "nvjpegEncoderParamsSetSamplingFactors",
+            )?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// `subsampling` is the layout of `planes`, the output has the one the
+    /// encoder was made with.
+    pub fn encode_yuv(
+        &mut self,
+        planes: &[Plane; 3],
+        subsampling: ChromaSubsampling,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, MediaLibError> {
+        // Pack the planes tightly on the host so they can be uploaded with a
+        // single copy.
+        let mut host_buffer =
+            Vec::with_capacity(planes.iter().map(|p| p.width * p.height).sum::<usize>());
+        for plane in planes.iter() {
+            for row in 0..plane.height {
+                host_buffer.extend_from_slice(plane.row(row));
+            }
+        }
+
+        let stream: CudaStream = null_mut();
+        let mut device_buffer: *mut c_void = null_mut();
+
+        unsafe {
+            check_cuda(
+                (self.api.cuda_malloc)(&mut device_buffer, host_buffer.len()),
+                "cudaMalloc",
+            )?;
+
+            let result = self.encode_device_buffer(
+                device_buffer,
+                &host_buffer,
+                planes,
+                subsampling,
+                width,
+                height,
+                stream,
+            );
+            (self.api.cuda_free)(device_buffer);
+            result
+        }
+    }
+
+    unsafe fn encode_device_buffer(
+        &mut self,
+        device_buffer: *mut c_void,
+        host_buffer: &[u8],
+        planes: &[Plane; 3],
+        subsampling: ChromaSubsampling,
+        width: u32,
+        height: u32,
+        stream: CudaStream,
+    ) -> Result<Vec<u8>, MediaLibError> {
+        check_cuda(
+            (self.api.cuda_memcpy)(
+                device_buffer,
+                host_buffer.as_ptr() as *const c_void,
+                host_buffer.len(),
+                CUDA_MEMCPY_HOST_TO_DEVICE,
+            ),
+            "cudaMemcpy",
+        )?;
+
+        let mut image = NvJpegImage {
+            channel: [null_mut(); NVJPEG_MAX_COMPONENT],
+            pitch: [0; NVJPEG_MAX_COMPONENT],
+        };
+        let mut offset = 0;
+        for (i, plane) in planes.iter().enumerate() {
+            image.channel[i] = (device_buffer as *mut u8).add(offset);
+            image.pitch[i] = plane.width;
+            offset += plane.width * plane.height;
+        }
+
+        check_nvjpeg(
+            (self.api.encode_yuv)(
+                self.handle,
+                self.state,
+                self.params,
+                &image,
+                css(subsampling),
+                width as i32,
+                height as i32,
+                stream,
+            ),
+            "nvjpegEncodeYUV",
+        )?;
+
+        // First call just reports the size of the bitstream.
+        let mut length: usize = 0;
+        check_nvjpeg(
+            (self.api.encode_retrieve_bitstream)(
+                self.handle,
+                self.state,
+                null_mut(),
+                &mut length,
+                stream,
+            ),
+            "nvjpegEncodeRetrieveBitstream",
+        )?;
+        check_cuda(
+            (self.api.cuda_stream_synchronize)(stream),
+            "cudaStreamSynchronize",
+        )?;
+
+        let mut buffer = vec![0u8; length];
+        check_nvjpeg(
+            (self.api.encode_retrieve_bitstream)(
+                self.handle,
+                self.state,
+                buffer.as_mut_ptr(),
+                &mut length,
+                stream,
+            ),
+            "nvjpegEncodeRetrieveBitstream",
+        )?;
+        buffer.truncate(length);
+
+        Ok(buffer)
+    }
+}
+
+impl Drop for NvJpegEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.params.is_null() {
+                (self.api.encoder_params_destroy)(self.params);
+            }
+            if !self.state.is_null() {
+                (self.api.encoder_state_destroy)(self.state);
+            }
+            if !self.handle.is_null() {
+                (self.api.destroy)(self.handle);
+            }
+        }
+    }
+}
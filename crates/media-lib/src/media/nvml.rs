@@ -0,0 +1,151 @@
+use std::ffi::{c_char, c_void, CStr};
+
+use libloading::Library;
+use media_types::{DecoderUtilization, MediaLibError};
+
+use super::dylib::{get_symbol, load_first};
+
+// NVML ships with the driver rather than the CUDA toolkit, so it's there
+// on any machine that can decode on an NVIDIA GPU.
+#[cfg(target_os = "windows")]
+const NVML_LIBRARIES: &[&str] = &["nvml.dll"];
+#[cfg(not(target_os = "windows"))]
+const NVML_LIBRARIES: &[&str] = &["libnvidia-ml.so.1", "libnvidia-ml.so"];
+
+const NVML_SUCCESS: i32 = 0;
+// NVML_DEVICE_NAME_V2_BUFFER_SIZE
+const NVML_DEVICE_NAME_BUFFER_SIZE: usize = 96;
+
+type NvmlDevice = *mut c_void;
+
+#[repr(C)]
+#[derive(Default)]
+struct NvmlMemory {
+    total: u64,
+    free: u64,
+    _used: u64,
+}
+
+struct NvmlApi {
+    init: unsafe extern "C" fn() -> i32,
+    shutdown: unsafe extern "C" fn() -> i32,
+    error_string: unsafe extern "C" fn(i32) -> *const c_char,
+    device_count: unsafe extern "C" fn(*mut u32) -> i32,
+    device_handle: unsafe extern "C" fn(u32, *mut NvmlDevice) -> i32,
+    device_name: unsafe extern "C" fn(NvmlDevice, *mut c_char, u32) -> i32,
+    decoder_utilization: unsafe extern "C" fn(NvmlDevice, *mut u32, *mut u32) -> i32,
+    memory_info: unsafe extern "C" fn(NvmlDevice, *mut NvmlMemory) -> i32,
+    // The library must outlive the function pointers above.
+    _nvml: Library,
+}
+
+impl NvmlApi {
+    fn load() -> Result<Self, MediaLibError> {
+        let nvml = load_first(NVML_LIBRARIES)?;
+
+        unsafe {
+            Ok(NvmlApi {
+                init: get_symbol(&nvml, b"nvmlInit_v2\0")?,
+                shutdown: get_symbol(&nvml, b"nvmlShutdown\0")?,
+                error_string: get_symbol(&nvml, b"nvmlErrorString\0")?,
+                device_count: get_symbol(&nvml, b"nvmlDeviceGetCount_v2\0")?,
+                device_handle: get_symbol(&nvml, b"nvmlDeviceGetHandleByIndex_v2\0")?,
+                device_name: get_symbol(&nvml, b"nvmlDeviceGetName\0")?,
+                decoder_utilization: get_symbol(&nvml, b"nvmlDeviceGetDecoderUtilization\0")?,
+                memory_info: get_symbol(&nvml, b"nvmlDeviceGetMemoryInfo\0")?,
+                _nvml: nvml,
+            })
+        }
+    }
+
+    unsafe fn check(&self, status: i32, call: &str) -> Result<(), MediaLibError> {
+        if status == NVML_SUCCESS {
+            return Ok(());
+        }
+        let message = (self.error_string)(status);
+        let message = if message.is_null() {
+            format!("error {}", status)
+        } else {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        };
+        Err(MediaLibError::UnknownError(
+            format!("{} failed: {}", call, message).into(),
+        ))
+    }
+
+    unsafe fn device(&self, index: u32) -> Result<DecoderUtilization, MediaLibError> {
+        let mut device: NvmlDevice = std::ptr::null_mut();
+        self.check(
+            (self.device_handle)(index, &mut device),
+            "nvmlDeviceGetHandleByIndex",
+        )?;
+
+        let mut name = [0 as c_char; NVML_DEVICE_NAME_BUFFER_SIZE];
+        self.check(
+            (self.device_name)(device, name.as_mut_ptr(), name.len() as u32),
+            "nvmlDeviceGetName",
+        )?;
+        let mut decoder_percent = 0;
+        let mut sampling_period_us = 0;
+        self.check(
+            (self.decoder_utilization)(device, &mut decoder_percent, &mut sampling_period_us),
+            "nvmlDeviceGetDecoderUtilization",
+        )?;
+        let mut memory = NvmlMemory::default();
+        self.check(
+            (self.memory_info)(device, &mut memory),
+            "nvmlDeviceGetMemoryInfo",
+        )?;
+
+        Ok(DecoderUtilization {
+            device_index: index,
+            name: CStr::from_ptr(name.as_ptr())
+                .to_string_lossy()
+                .as_ref()
+                .into(),
+            decoder_percent,
+            sampling_period_us,
+            memory_free_bytes: memory.free,
+            memory_total_bytes: memory.total,
+        })
+    }
+}
+
+/// How busy the video decoder of each NVIDIA GPU on this machine is, for
+/// schedulers deciding whether to start another hardware decode here or
+/// decode in software. Fails where NVML (the driver) isn't installed.
+pub fn decoder_utilization() -> Result<Vec<DecoderUtilization>, MediaLibError> {
+    let api = NvmlApi::load()?;
+    unsafe {
+        // Counted, so this doesn't get in the way of anyone else in the
+        // process using NVML.
+        api.check((api.init)(), "nvmlInit")?;
+        let mut count = 0;
+        let devices = api
+            .check((api.device_count)(&mut count), "nvmlDeviceGetCount")
+            .and_then(|()| (0..count).map(|index| api.device(index)).collect());
+        (api.shutdown)();
+        devices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_every_gpu_or_why_it_cant() {
+        // Whether there's an NVIDIA driver depends on the machine.
+        match decoder_utilization() {
+            Ok(devices) => {
+                for (index, device) in devices.iter().enumerate() {
+                    assert_eq!(device.device_index, index as u32);
+                    assert!(!device.name.is_empty());
+                    assert!(device.decoder_percent <= 100);
+                    assert!(device.memory_free_bytes <= device.memory_total_bytes);
+                }
+            }
+            Err(e) => assert!(matches!(e, MediaLibError::UnknownError(_)), "{:?}", e),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    MediaFrameDecoderOptions, MediaLibError, OcrImage, OcrOptions, OnScreenText, Rect, Size,
+    TextRecognizerBox, TextRecognizerDynMut,
+};
+
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::job::Job;
+
+// Share of the darkest and brightest pixels clipped when stretching
+// contrast, so a few specks don't decide the range.
+const CLIP_SHARE: f64 = 0.01;
+// Largest side an enlarged region can have, far more than OCR engines want
+// and within what the scaler takes.
+const MAX_UPSCALED_SIDE: u32 = 16_384;
+
+fn convert(frame: &Video, width: u32, height: u32) -> Result<Video, MediaLibError> {
+    let mut gray = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        Pixel::GRAY8,
+        width,
+        height,
+        Flags::BICUBIC,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut gray))
+    .map_err(ffmpeg_error)?;
+    Ok(gray)
+}
+
+/// The size `region` is enlarged to, an error when that's too large to
+/// scale.
+fn upscaled_size(region: Rect, upscale: u32) -> Result<Size, MediaLibError> {
+    let upscale = upscale.max(1);
+    region
+        .width
+        .checked_mul(upscale)
+        .zip(region.height.checked_mul(upscale))
+        .map(|(width, height)| Size::new(width, height))
+        .filter(|size| size.width <= MAX_UPSCALED_SIDE && size.height <= MAX_UPSCALED_SIDE)
+        .ok_or_else(|| {
+            MediaLibError::UnknownError(
+                format!(
+                    "A {}x{} region enlarged {} times is larger than {}px",
+                    region.width, region.height, upscale, MAX_UPSCALED_SIDE
+                )
+                .into(),
+            )
+        })
+}
+
+/// `region` of the frame in grayscale, enlarged `upscale` times.
+fn prepare(frame: &Video, region: Rect, upscale: u32) -> Result<Video, MediaLibError> {
+    // Frames can be smaller than the stream said, e.g. after a resolution
+    // change.
+    if !region.fits_in(Size::new(frame.width(), frame.height())) {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Region {:?} is outside the {}x{} frame",
+                region,
+                frame.width(),
+                frame.height()
+            )
+            .into(),
+        ));
+    }
+    let gray = convert(frame, frame.width(), frame.height())?;
+    let mut cropped = Video::new(Pixel::GRAY8, region.width, region.height);
+    let (source_stride, target_stride) = (gray.stride(0), cropped.stride(0));
+    let (x, width) = (region.x as usize, region.width as usize);
+    for row in 0..region.height as usize {
+        let from = (region.y as usize + row) * source_stride + x;
+        let to = row * target_stride;
+        let source = gray
+            .data(0)
+            .get(from..from + width)
+            .ok_or_else(|| MediaLibError::FFmpegError("Frame is smaller than its size".into()))?;
+        cropped.data_mut(0)[to..to + width].copy_from_slice(source);
+    }
+    if upscale <= 1 {
+        return Ok(cropped);
+    }
+    let size = upscaled_size(region, upscale)?;
+    convert(&cropped, size.width, size.height)
+}
+
+/// Stretches levels so the darkest and brightest pixels, bar a few, become
+/// black and white.
+fn stretch_contrast(pixels: &mut [u8]) {
+    let mut histogram = [0usize; 256];
+    for pixel in pixels.iter() {
+        histogram[*pixel as usize] += 1;
+    }
+    let clipped = (pixels.len() as f64 * CLIP_SHARE) as usize;
+    let level_at = |levels: &mut dyn Iterator<Item = usize>| {
+        let mut seen = 0;
+        levels
+            .find(|level| {
+                seen += histogram[*level];
+                seen > clipped
+            })
+            .unwrap_or(0)
+    };
+    let low = level_at(&mut (0..256));
+    let high = level_at(&mut (0..256).rev());
+    if high <= low {
+        return;
+    }
+    let range = (high - low) as f64;
+    for pixel in pixels.iter_mut() {
+        let stretched = (*pixel as f64 - low as f64) * 255.0 / range;
+        *pixel = stretched.clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Reads the text on screen every `options.interval_ms` with the host's
+/// `recognizer`. Samples in a row that read the same are merged, so a slate
+/// or a burnt in warning comes back once with the time it was shown.
+pub fn extract_on_screen_text(
+    input_path: &Path,
+    options: &OcrOptions,
+    recognizer: &mut TextRecognizerBox,
+    job: &mut Job,
+) -> Result<Vec<OnScreenText>, MediaLibError> {
+    if options.interval_ms == 0 {
+        return Err(MediaLibError::UnknownError(
+            "Sample interval must be above zero".into(),
+        ));
+    }
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    let frame_size = video_decoder.size();
+    let region = if options.region.size().is_empty() {
+        Rect::from_size(frame_size)
+    } else {
+        options.region
+    };
+    if !region.fits_in(frame_size) {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Region {:?} is outside the {}x{} frame",
+                region, frame_size.width, frame_size.height
+            )
+            .into(),
+        ));
+    }
+    upscaled_size(region, options.upscale)?;
+    let duration_us = video_decoder
+        .duration_us()
+        .ok_or_else(|| MediaLibError::FFmpegError("Input has no duration to sample over".into()))?;
+
+    let interval_us = options.interval_ms as i64 * 1000;
+    let start_time = video_decoder.start_time_us();
+    let mut texts: Vec<OnScreenText> = Vec::new();
+    // Whether the previous sample read the last entry's text.
+    let mut showing = false;
+    let mut timestamp_us = 0;
+    while timestamp_us < duration_us {
+        job.check()?;
+        job.progress(timestamp_us, Some(duration_us));
+        video_decoder.seek_to_keyframe(timestamp_us)?;
+        let Some(frame) = video_decoder.get_frame().transpose()? else {
+            break;
+        };
+        let shown_us = video_decoder
+            .frame_time_us(&frame)
+            .map(|time| time - start_time)
+            .unwrap_or(timestamp_us);
+        timestamp_us += interval_us;
+
+        let prepared = prepare(&frame, region, options.upscale)?;
+        let (width, height) = (prepared.width() as usize, prepared.height() as usize);
+        let mut pixels: Vec<u8> = prepared
+            .data(0)
+            .chunks(prepared.stride(0))
+            .take(height)
+            .flat_map(|row| &row[..width])
+            .copied()
+            .collect();
+        if options.stretch_contrast {
+            stretch_contrast(&mut pixels);
+        }
+        let mut image_pixels = stabby::vec::Vec::with_capacity(pixels.len());
+        for pixel in pixels {
+            image_pixels.push(pixel);
+        }
+        let text = recognizer.recognize(OcrImage {
+            pixels: image_pixels,
+            width: width as u32,
+            height: height as u32,
+        });
+
+        let text = text.trim();
+        if text.is_empty() {
+            showing = false;
+            continue;
+        }
+        match texts.last_mut() {
+            Some(last) if showing && &*last.text == text => last.end_us = shown_us,
+            _ => texts.push(OnScreenText {
+                start_us: shown_us,
+                end_us: shown_us,
+                text: text.into(),
+            }),
+        }
+        showing = true;
+    }
+    Ok(texts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_bounds_enlarged_regions() {
+        let region = Rect::new(0, 0, 640, 120);
+        assert_eq!(upscaled_size(region, 0).unwrap(), Size::new(640, 120));
+        assert_eq!(upscaled_size(region, 3).unwrap(), Size::new(1920, 360));
+        assert!(upscaled_size(region, 40).is_err());
+        assert!(upscaled_size(Rect::new(0, 0, u32::MAX, 1), 2).is_err());
+    }
+
+    #[test]
+    fn it_stretches_contrast() {
+        let mut pixels: Vec<u8> = (0..200).map(|index| 100 + (index % 50) as u8).collect();
+        stretch_contrast(&mut pixels);
+        assert_eq!(pixels.iter().min(), Some(&0));
+        assert_eq!(pixels.iter().max(), Some(&255));
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use media_types::{MediaLibError, OutputOptions};
+
+use super::io_error;
+
+/// A file produced a piece at a time, e.g. by a muxer, written according to
+/// `OutputOptions` rather than with `OutputOptions::write`. It's written at
+/// `staging_path` and only ends up at its destination once committed, and a
+/// staged file dropped before then is removed.
+pub struct StagedOutput {
+    path: PathBuf,
+    staging: PathBuf,
+    options: OutputOptions,
+    committed: bool,
+}
+
+impl StagedOutput {
+    /// Fails up front if there's a file at `path` that isn't to be
+    /// overwritten.
+    pub fn new(path: &Path, options: &OutputOptions) -> Result<Self, MediaLibError> {
+        options.check_destination(path).map_err(io_error)?;
+        Ok(StagedOutput {
+            path: path.to_path_buf(),
+            staging: options.staging_path(path),
+            options: options.clone(),
+            committed: false,
+        })
+    }
+
+    /// Where the file is written until it's committed.
+    pub fn staging_path(&self) -> &Path {
+        &self.staging
+    }
+
+    /// Puts the complete file in place. Whatever wrote it must have closed
+    /// it first.
+    pub fn commit(mut self) -> Result<(), MediaLibError> {
+        self.options
+            .commit(&self.staging, &self.path)
+            .map_err(io_error)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.options.discard(&self.staging, &self.path);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    Corner, MediaFrameDecoderOptions, MediaLibError, OverlayPlacement, OverlayPlacementOptions,
+    Rect, Size,
+};
+
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+
+// Frames are compared at this width, enough to tell a face or a scoreboard
+// from sky.
+const ANALYSIS_WIDTH: u32 = 160;
+// Largest detail (horizontal plus vertical step) and motion a pixel can have.
+const MAX_ACTIVITY: f64 = 3.0 * 255.0;
+
+/// Per-pixel detail and motion summed over the sampled frames, in grayscale
+/// at the analysis size.
+struct ActivityMap {
+    size: Size,
+    activity: Vec<f64>,
+    samples: u32,
+    // Created for the first frame and kept while frames stay alike.
+    scaler: Option<Context>,
+}
+
+impl ActivityMap {
+    fn new(size: Size) -> Self {
+        ActivityMap {
+            size,
+            activity: vec![0.0; size.width as usize * size.height as usize],
+            samples: 0,
+            scaler: None,
+        }
+    }
+
+    /// `frame` in grayscale at the analysis size, row after row.
+    fn gray(&mut self, frame: &Video) -> Result<Vec<u8>, MediaLibError> {
+        let (width, height) = (self.size.width as usize, self.size.height as usize);
+        let scaler = match self.scaler.take() {
+            Some(scaler)
+                if scaler.input().format == frame.format()
+                    && scaler.input().width == frame.width()
+                    && scaler.input().height == frame.height() =>
+            {
+                self.scaler.insert(scaler)
+            }
+            _ => self.scaler.insert(
+                Context::get(
+                    frame.format(),
+                    frame.width(),
+                    frame.height(),
+                    Pixel::GRAY8,
+                    self.size.width,
+                    self.size.height,
+                    Flags::AREA,
+                )
+                .map_err(ffmpeg_error)?,
+            ),
+        };
+        let mut gray = Video::empty();
+        scaler.run(frame, &mut gray).map_err(ffmpeg_error)?;
+        let stride = gray.stride(0);
+        Ok(gray
+            .data(0)
+            .chunks(stride)
+            .take(height)
+            .flat_map(|row| &row[..width])
+            .copied()
+            .collect())
+    }
+
+    /// Adds the detail in `frame` and the motion from it to `next`, the
+    /// frame right after it. Only detail counts when the input ends at
+    /// `frame`.
+    fn add(&mut self, frame: &Video, next: Option<&Video>) -> Result<(), MediaLibError> {
+        let (width, height) = (self.size.width as usize, self.size.height as usize);
+        let pixels = self.gray(frame)?;
+        let next = next.map(|next| self.gray(next)).transpose()?;
+
+        let at = |x: usize, y: usize| pixels[y * width + x] as f64;
+        for y in 0..height {
+            for x in 0..width {
+                let value = at(x, y);
+                let detail = (at((x + 1).min(width - 1), y) - value).abs()
+                    + (at(x, (y + 1).min(height - 1)) - value).abs();
+                let motion = next
+                    .as_ref()
+                    .map(|next| (next[y * width + x] as f64 - value).abs())
+                    .unwrap_or(0.0);
+                self.activity[y * width + x] += detail + motion;
+            }
+        }
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Mean activity under `region`, given in pixels of a frame of
+    /// `frame_size`, from 0.0 to 1.0.
+    fn mean(&self, region: Rect, frame_size: Size) -> f32 {
+        let scale =
+            |value: u32, from: u32, to: u32| (value as u64 * to as u64 / from as u64) as usize;
+        let x0 = scale(region.x, frame_size.width, self.size.width);
+        let y0 = scale(region.y, frame_size.height, self.size.height);
+        let x1 = scale(region.x + region.width, frame_size.width, self.size.width)
+            .clamp(x0 + 1, self.size.width as usize);
+        let y1 = scale(
+            region.y + region.height,
+            frame_size.height,
+            self.size.height,
+        )
+        .clamp(y0 + 1, self.size.height as usize);
+
+        let width = self.size.width as usize;
+        let total: f64 = (y0..y1)
+            .flat_map(|y| self.activity[y * width + x0..y * width + x1].iter())
+            .sum();
+        let pixels = ((x1 - x0) * (y1 - y0)) as f64;
+        (total / pixels / self.samples.max(1) as f64 / MAX_ACTIVITY) as f32
+    }
+}
+
+/// The next keyframe and the frame right after it, so motion is measured
+/// between neighbouring frames rather than keyframes seconds apart. None
+/// at the end of the input.
+fn next_sample(
+    video_decoder: &mut HardwareAcceleratedVideoDecoder,
+) -> Result<Option<(Video, Option<Video>)>, MediaLibError> {
+    video_decoder.keyframes_only = true;
+    let Some(keyframe) = video_decoder.get_frame().transpose()? else {
+        return Ok(None);
+    };
+    video_decoder.keyframes_only = false;
+    let next = video_decoder.get_frame().transpose()?;
+    Ok(Some((keyframe, next)))
+}
+
+/// The region an overlay would take up in each corner.
+fn corner_regions(
+    frame_size: Size,
+    options: &OverlayPlacementOptions,
+) -> Result<Vec<(Corner, Rect)>, MediaLibError> {
+    let width = match options.overlay.width {
+        0 => frame_size.width / 5,
+        width => width,
+    };
+    let height = match options.overlay.height {
+        0 => frame_size.height / 10,
+        height => height,
+    };
+    let margin = options.margin;
+    if width == 0
+        || height == 0
+        || width + 2 * margin > frame_size.width
+        || height + 2 * margin > frame_size.height
+    {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "A {}x{} overlay with a {}px margin doesn't fit in a {}x{} frame",
+                width, height, margin, frame_size.width, frame_size.height
+            )
+            .into(),
+        ));
+    }
+    let right = frame_size.width - width - margin;
+    let bottom = frame_size.height - height - margin;
+    Ok(vec![
+        (Corner::TopLeft, Rect::new(margin, margin, width, height)),
+        (Corner::TopRight, Rect::new(right, margin, width, height)),
+        (Corner::BottomLeft, Rect::new(margin, bottom, width, height)),
+        (Corner::BottomRight, Rect::new(right, bottom, width, height)),
+    ])
+}
+
+/// Ranks the corners of the input's frame for placing an overlay, calmest
+/// first, by how much detail and motion sampled frames have under each. A
+/// watermark placed in the first won't cover faces, captions or a
+/// scoreboard if the input has a corner free of them.
+pub fn suggest_overlay_placement(
+    input_path: &Path,
+    options: &OverlayPlacementOptions,
+) -> Result<Vec<OverlayPlacement>, MediaLibError> {
+    if options.samples == 0 {
+        return Err(MediaLibError::UnknownError(
+            "At least one frame has to be sampled".into(),
+        ));
+    }
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    let frame_size = video_decoder.size();
+    let regions = corner_regions(frame_size, options)?;
+
+    let analysis_size = frame_size.scale_to_width(ANALYSIS_WIDTH.min(frame_size.width));
+    let mut map = ActivityMap::new(analysis_size);
+    match video_decoder.duration_us() {
+        Some(duration_us) => {
+            let samples = options.samples as i64;
+            for sample in 0..samples {
+                let timestamp_us = duration_us * (2 * sample + 1) / (2 * samples);
+                video_decoder.seek_to_keyframe(timestamp_us)?;
+                if let Some((frame, next)) = next_sample(&mut video_decoder)? {
+                    map.add(&frame, next.as_ref())?;
+                }
+            }
+        }
+        // Without a duration to spread them over, the first keyframes do.
+        None => {
+            while map.samples < options.samples {
+                let Some((frame, next)) = next_sample(&mut video_decoder)? else {
+                    break;
+                };
+                map.add(&frame, next.as_ref())?;
+            }
+        }
+    }
+    if map.samples == 0 {
+        return Err(MediaLibError::FFmpegError(
+            "No frames could be decoded to sample".into(),
+        ));
+    }
+
+    let mut placements: Vec<OverlayPlacement> = regions
+        .into_iter()
+        .map(|(corner, region)| OverlayPlacement {
+            region,
+            activity: map.mean(region, frame_size),
+            corner,
+        })
+        .collect();
+    placements.sort_by(|a, b| a.activity.total_cmp(&b.activity));
+    Ok(placements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_frame(size: u32, value: u8, bright_corner: bool) -> Video {
+        let mut frame = Video::new(Pixel::GRAY8, size, size);
+        let stride = frame.stride(0);
+        for (y, row) in frame.data_mut(0).chunks_mut(stride).enumerate() {
+            for (x, pixel) in row[..size as usize].iter_mut().enumerate() {
+                let in_corner = x < size as usize / 2 && y < size as usize / 2;
+                *pixel = if bright_corner && in_corner {
+                    255
+                } else {
+                    value
+                };
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn it_measures_motion_between_neighbouring_frames() {
+        let size = Size::new(16, 16);
+        let full = Rect::from_size(size);
+        let corner = Rect::new(0, 0, 4, 4);
+        let opposite = Rect::new(12, 12, 4, 4);
+
+        let mut map = ActivityMap::new(size);
+        map.add(
+            &gray_frame(16, 128, false),
+            Some(&gray_frame(16, 128, true)),
+        )
+        .unwrap();
+        assert!(map.mean(corner, size) > 0.1);
+        assert_eq!(map.mean(opposite, size), 0.0);
+
+        // Samples far apart differ, but that isn't motion.
+        let mut map = ActivityMap::new(size);
+        map.add(&gray_frame(16, 20, false), None).unwrap();
+        map.add(&gray_frame(16, 200, false), None).unwrap();
+        assert_eq!(map.mean(full, size), 0.0);
+        assert_eq!(map.samples, 2);
+    }
+}
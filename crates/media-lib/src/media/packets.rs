@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::Packet;
+use media_types::{MediaLibError, MediaPacket, TimeBase};
+
+use super::ffmpeg_error;
+use super::input::{open_input, MediaInput};
+
+/// Hands out the demuxed packets of every stream without decoding them.
+pub struct PacketIterator {
+    ictx: MediaInput,
+}
+
+impl PacketIterator {
+    pub fn new(input_path: &Path, force_format: Option<&str>) -> Result<Self, MediaLibError> {
+        Ok(PacketIterator {
+            ictx: open_input(input_path, force_format)?,
+        })
+    }
+
+    pub fn stream_count(&self) -> u32 {
+        self.ictx.nb_streams()
+    }
+
+    /// The next packet, none at the end of the input. Read errors are
+    /// returned rather than skipped, a damaged input would otherwise look
+    /// like it ends there.
+    pub fn get(&mut self) -> Option<Result<MediaPacket, MediaLibError>> {
+        let mut packet = Packet::empty();
+        loop {
+            match packet.read(&mut self.ictx) {
+                Ok(()) => break,
+                Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => {}
+                Err(ffmpeg_next::Error::Eof) => return None,
+                Err(e) => return Some(Err(ffmpeg_error(e))),
+            }
+        }
+        let stream = self.ictx.stream(packet.stream())?;
+        let time_base = stream.time_base();
+        let position = packet.position();
+
+        Some(Ok(MediaPacket {
+            data: crate::to_stabby_vec(packet.data().unwrap_or_default()),
+            pts: packet.pts().into(),
+            dts: packet.dts().into(),
+            // FFmpeg uses 0 for an unknown duration.
+            duration: (packet.duration() > 0).then_some(packet.duration()).into(),
+            byte_offset: (position >= 0).then_some(position as u64).into(),
+            time_base: TimeBase {
+                numerator: time_base.numerator(),
+                denominator: time_base.denominator(),
+            },
+            stream_index: stream.index() as u32,
+            is_key: packet.is_key(),
+            is_corrupt: packet.is_corrupt(),
+        }))
+    }
+}
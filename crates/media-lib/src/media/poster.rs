@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{MediaFrameDecoderOptions, MediaLibError};
+
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::input::open_input;
+use super::timestamp;
+
+// How far into a chapter to look, the first second or two is often still a
+// fade in.
+const CHAPTER_OFFSET_US: i64 = 5_000_000;
+// Chapters with these words in their title are skipped.
+const SKIPPED_CHAPTERS: &[&str] = &["intro", "opening", "recap", "previously", "credits"];
+// Keyframes looked at from the start when there are no usable chapters.
+const MAX_SCANNED_KEYFRAMES: usize = 30;
+
+// Frames are judged on a small grayscale thumbnail.
+const THUMBNAIL_SIZE: u32 = 32;
+// Average brightness outside this range is a black or white frame.
+const MIN_BRIGHTNESS: f64 = 32.0;
+const MAX_BRIGHTNESS: f64 = 224.0;
+// Below this standard deviation the frame is mostly flat, e.g. a logo on a
+// plain background or a title card.
+const MIN_DETAIL: f64 = 24.0;
+
+/// The luma of `frame` scaled down to a `THUMBNAIL_SIZE` square, row by
+/// row.
+pub fn luma_thumbnail(frame: &Video) -> Result<Vec<f64>, MediaLibError> {
+    let mut gray = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        Pixel::GRAY8,
+        THUMBNAIL_SIZE,
+        THUMBNAIL_SIZE,
+        Flags::AREA,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut gray))
+    .map_err(ffmpeg_error)?;
+
+    let stride = gray.stride(0);
+    Ok(gray
+        .data(0)
+        .chunks(stride)
+        .take(THUMBNAIL_SIZE as usize)
+        .flat_map(|row| &row[..THUMBNAIL_SIZE as usize])
+        .map(|value| *value as f64)
+        .collect())
+}
+
+/// Brightness and detail of a frame, as the mean and standard deviation of
+/// its luma.
+pub struct Exposure {
+    pub brightness: f64,
+    pub detail: f64,
+}
+
+impl Exposure {
+    pub fn measure(frame: &Video) -> Result<Self, MediaLibError> {
+        Ok(Self::of_thumbnail(&luma_thumbnail(frame)?))
+    }
+
+    /// Same as `measure` for a thumbnail from `luma_thumbnail`.
+    pub fn of_thumbnail(pixels: &[f64]) -> Self {
+        let brightness = pixels.iter().sum::<f64>() / pixels.len() as f64;
+        let variance = pixels
+            .iter()
+            .map(|value| (value - brightness).powi(2))
+            .sum::<f64>()
+            / pixels.len() as f64;
+        Exposure {
+            brightness,
+            detail: variance.sqrt(),
+        }
+    }
+
+    fn is_presentable(&self) -> bool {
+        (MIN_BRIGHTNESS..=MAX_BRIGHTNESS).contains(&self.brightness) && self.detail >= MIN_DETAIL
+    }
+}
+
+/// Times (from the start of the input) shortly after each chapter starts,
+/// leaving out intros, recaps and credits.
+fn chapter_candidates(input_path: &Path, start_time_us: i64) -> Result<Vec<i64>, MediaLibError> {
+    let ictx = open_input(input_path, None)?;
+
+    let candidates = ictx
+        .chapters()
+        .filter(|chapter| {
+            let title = chapter
+                .metadata()
+                .get("title")
+                .unwrap_or_default()
+                .to_lowercase();
+            !SKIPPED_CHAPTERS.iter().any(|word| title.contains(word))
+        })
+        .map(|chapter| {
+            let start = timestamp::to_us(chapter.start(), chapter.time_base());
+            let end = timestamp::to_us(chapter.end(), chapter.time_base());
+            // Short chapters get their midpoint instead.
+            (start + CHAPTER_OFFSET_US).min((start + end) / 2) - start_time_us
+        })
+        .collect();
+    Ok(candidates)
+}
+
+/// Picks a representative frame for a poster: shortly after the start of
+/// the first chapter that isn't an intro, or else the first keyframe that
+/// isn't black or mostly flat. Falls back to the most detailed frame seen.
+pub fn select_poster_frame(input_path: &Path) -> Result<Video, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+
+    let mut best: Option<(f64, Video)> = None;
+    let mut consider = |frame: Video| -> Result<Option<Video>, MediaLibError> {
+        let exposure = Exposure::measure(&frame)?;
+        if exposure.is_presentable() {
+            return Ok(Some(frame));
+        }
+        if !best
+            .as_ref()
+            .is_some_and(|(detail, _)| exposure.detail <= *detail)
+        {
+            best = Some((exposure.detail, frame));
+        }
+        Ok(None)
+    };
+
+    let start_time_us = video_decoder.start_time_us();
+    for timestamp_us in chapter_candidates(input_path, start_time_us)? {
+        video_decoder.seek_to_keyframe(timestamp_us)?;
+        if let Some(frame) = video_decoder.get_frame().transpose()? {
+            if let Some(poster) = consider(frame)? {
+                return Ok(poster);
+            }
+        }
+    }
+
+    video_decoder.seek_to_keyframe(0)?;
+    for _ in 0..MAX_SCANNED_KEYFRAMES {
+        let Some(frame) = video_decoder.get_frame().transpose()? else {
+            break;
+        };
+        if let Some(poster) = consider(frame)? {
+            return Ok(poster);
+        }
+    }
+
+    best.map(|(_, frame)| frame)
+        .ok_or_else(|| MediaLibError::FFmpegError("No frames could be decoded".into()))
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use ffmpeg_next::codec::{self, packet::Packet};
+use ffmpeg_next::format::context::Input;
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    JpegOptions, MediaFrameDecoderOptions, MediaLibError, PosterSelection, PreviewOptions, Size,
+};
+
+use super::alpha::AlphaFlattener;
+use super::audio_preview::{render_audio_preview, RgbImage};
+use super::ffmpeg_error;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::input::open_input;
+use super::jpeg::{pixel_format, JpegEncoder};
+use super::poster::select_poster_frame;
+use super::still::PictureCorrection;
+
+/// Scales `frame` to fit inside `bounds` and encodes it as a JPEG of the
+/// returned size.
+fn encode_to_fit(
+    frame: &Video,
+    bounds: Size,
+    jpeg: &JpegOptions,
+) -> Result<(Vec<u8>, Size), MediaLibError> {
+    let size = Size::new(frame.width(), frame.height()).scale_to_fit(bounds);
+    let mut scaler = Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        pixel_format(jpeg.subsampling),
+        size.width,
+        size.height,
+        Flags::BILINEAR,
+    )
+    .map_err(ffmpeg_error)?;
+
+    let mut scaled = Video::empty();
+    scaler.run(frame, &mut scaled).map_err(ffmpeg_error)?;
+    Ok((JpegEncoder::with_options(jpeg).encode(&scaled)?, size))
+}
+
+/// Flattens any transparency as the options ask and turns photos and
+/// sideways videos upright in sRGB, then encodes to fit.
+fn encode_picture(
+    frame: &Video,
+    options: &PreviewOptions,
+) -> Result<(Vec<u8>, Size), MediaLibError> {
+    let flattened =
+        AlphaFlattener::new(options.alpha_background, options.background).apply(frame)?;
+    let flattened = flattened.as_ref().unwrap_or(frame);
+    let corrected = PictureCorrection::for_frame(frame, true)
+        .map(|correction| correction.apply(flattened))
+        .transpose()?;
+    encode_to_fit(
+        corrected.as_ref().unwrap_or(flattened),
+        options.size,
+        &options.jpeg,
+    )
+}
+
+fn rgb_frame(image: &RgbImage) -> Video {
+    let mut frame = Video::new(Pixel::RGB24, image.size.width, image.size.height);
+    let row_bytes = image.size.width as usize * 3;
+    let stride = frame.stride(0);
+    let data = frame.data_mut(0);
+    for (y, row) in image.pixels.chunks_exact(row_bytes).enumerate() {
+        data[y * stride..y * stride + row_bytes].copy_from_slice(row);
+    }
+    frame
+}
+
+pub fn encode_rgb(image: &RgbImage, jpeg: &JpegOptions) -> Result<Vec<u8>, MediaLibError> {
+    encode_to_fit(&rgb_frame(image), image.size, jpeg).map(|(jpeg, _)| jpeg)
+}
+
+/// Decodes the cover art stored with stream `stream_index`.
+fn decode_attached_picture(ictx: &Input, stream_index: usize) -> Result<Video, MediaLibError> {
+    let stream = ictx
+        .stream(stream_index)
+        .ok_or_else(|| MediaLibError::FFmpegError("Cover art stream not found".into()))?;
+
+    // The picture isn't interleaved with the other packets, the demuxer keeps
+    // it on the stream.
+    let attached_pic = unsafe { &(*stream.as_ptr()).attached_pic };
+    if attached_pic.data.is_null() || attached_pic.size <= 0 {
+        return Err(MediaLibError::FFmpegError(
+            "Cover art stream is empty".into(),
+        ));
+    }
+    let data = unsafe { std::slice::from_raw_parts(attached_pic.data, attached_pic.size as usize) };
+
+    let mut decoder = codec::context::Context::from_parameters(stream.parameters())
+        .and_then(|context| context.decoder().video())
+        .map_err(ffmpeg_error)?;
+    decoder
+        .send_packet(&Packet::copy(data))
+        .map_err(ffmpeg_error)?;
+    decoder.send_eof().map_err(ffmpeg_error)?;
+
+    let mut picture = Video::empty();
+    decoder.receive_frame(&mut picture).map_err(ffmpeg_error)?;
+    Ok(picture)
+}
+
+fn first_video_frame(input_path: &Path) -> Result<Video, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.get_frame().unwrap_or_else(|| {
+        Err(MediaLibError::FFmpegError(
+            "No frames could be decoded".into(),
+        ))
+    })
+}
+
+/// A JPEG preview for any input: a frame of a video chosen as
+/// `options.poster` asks, the cover art of an audio file, or failing that a
+/// rendering of the audio itself. Pictures keep their aspect ratio inside
+/// `options.size`, so the size of the JPEG is returned with it.
+pub fn preview_image(
+    input_path: &Path,
+    options: &PreviewOptions,
+) -> Result<(Vec<u8>, Size), MediaLibError> {
+    let ictx = open_input(input_path, None)?;
+
+    let mut has_video = false;
+    let mut cover_art = None;
+    for stream in ictx.streams() {
+        if stream.parameters().medium() != ffmpeg_next::media::Type::Video {
+            continue;
+        }
+        if stream.disposition().contains(Disposition::ATTACHED_PIC) {
+            cover_art.get_or_insert(stream.index());
+        } else {
+            has_video = true;
+        }
+    }
+
+    if has_video {
+        let frame = match options.poster {
+            PosterSelection::FirstFrame => first_video_frame(input_path)?,
+            PosterSelection::Smart => select_poster_frame(input_path)?,
+        };
+        return encode_picture(&frame, options);
+    }
+
+    if let Some(stream_index) = cover_art {
+        match decode_attached_picture(&ictx, stream_index) {
+            Ok(picture) => return encode_picture(&picture, options),
+            Err(e) => log::warn!("Failed to decode cover art, rendering the audio: {}", e),
+        }
+    }
+
+    let image = render_audio_preview(input_path, options)?;
+    encode_to_fit(&rgb_frame(&image), image.size, &options.jpeg)
+}
@@ -0,0 +1,218 @@
+use std::ffi::c_int;
+use std::path::Path;
+
+use ffmpeg_next::codec;
+use ffmpeg_next::ffi::{
+    av_seek_frame, avio_size, AVDurationEstimationMethod, AVSEEK_FLAG_BYTE, AV_NOPTS_VALUE,
+};
+use ffmpeg_next::format::stream::{Disposition, Stream};
+use media_types::{DecryptionOptions, DurationSource, MediaInfo, MediaLibError, StreamInfo};
+
+use super::avio::ReadSeek;
+use super::ffmpeg_error;
+use super::input::{
+    open_input, open_input_quick, open_input_with_options, open_reader, stream_kind, InputOptions,
+    MediaInput,
+};
+use super::sniff::sniff_file;
+use super::spherical::spherical_metadata;
+use super::timestamp;
+
+// How much of the end of the input is read for its last timestamps when
+// the container doesn't record a duration.
+const SCAN_BYTES: i64 = 4 << 20;
+
+fn stream_info(stream: &Stream) -> Result<StreamInfo, MediaLibError> {
+    let parameters = stream.parameters();
+    let medium = parameters.medium();
+    let disposition = stream.disposition();
+    let mut info = StreamInfo {
+        kind: stream_kind(medium).into(),
+        codec_name: parameters.id().name().into(),
+        pixel_format: "".into(),
+        frame_rate: None.into(),
+        index: stream.index() as u32,
+        width: 0,
+        height: 0,
+        is_default: disposition.contains(Disposition::DEFAULT),
+        is_attached_picture: disposition.contains(Disposition::ATTACHED_PIC),
+        spherical: None.into(),
+    };
+    if medium != ffmpeg_next::media::Type::Video {
+        return Ok(info);
+    }
+
+    // A codec context is only filled in from the parameters, nothing is
+    // opened or decoded.
+    let video = codec::context::Context::from_parameters(parameters)
+        .and_then(|context| context.decoder().video())
+        .map_err(ffmpeg_error)?;
+    info.width = video.width();
+    info.height = video.height();
+    info.pixel_format = video
+        .format()
+        .descriptor()
+        .map(|descriptor| descriptor.name())
+        .unwrap_or_default()
+        .into();
+
+    let frame_rate = stream.avg_frame_rate();
+    info.frame_rate = (frame_rate.numerator() > 0 && frame_rate.denominator() > 0)
+        .then(|| f64::from(frame_rate))
+        .into();
+    info.spherical = spherical_metadata(stream).into();
+    Ok(info)
+}
+
+/// Size of the input in bytes, none when it can't seek (pipes, live
+/// streams).
+fn input_size(ictx: &MediaInput) -> Option<i64> {
+    let size = unsafe {
+        let pb = (*ictx.as_ptr()).pb;
+        if pb.is_null() {
+            return None;
+        }
+        avio_size(pb)
+    };
+    (size > 0).then_some(size)
+}
+
+/// Duration from the last timestamps in the input, read from its final
+/// few MB. Leaves the demuxer somewhere near the end.
+fn scan_duration(ictx: &mut MediaInput, size: i64) -> Option<i64> {
+    let start_time = match unsafe { (*ictx.as_ptr()).start_time } {
+        AV_NOPTS_VALUE => 0,
+        start_time => start_time,
+    };
+    let result = unsafe {
+        av_seek_frame(
+            ictx.as_mut_ptr(),
+            -1,
+            (size - SCAN_BYTES).max(0),
+            AVSEEK_FLAG_BYTE as c_int,
+        )
+    };
+    if result < 0 {
+        return None;
+    }
+
+    let mut end_us: Option<i64> = None;
+    while let Some(Ok((index, packet))) = ictx.next_packet() {
+        let Some(pts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+        let Some(stream) = ictx.stream(index) else {
+            continue;
+        };
+        let end = timestamp::end_us(pts, packet.duration(), stream.time_base());
+        end_us = Some(end_us.map_or(end, |end_us| end_us.max(end)));
+    }
+    end_us
+        .map(|end_us| end_us - start_time)
+        .filter(|duration| *duration > 0)
+}
+
+/// Duration from the size and the bitrate of the container or, failing
+/// that, of its streams added up.
+fn bitrate_duration(ictx: &MediaInput, size: i64) -> Option<i64> {
+    let bit_rate = match ictx.bit_rate() {
+        bit_rate if bit_rate > 0 => bit_rate,
+        _ => ictx
+            .streams()
+            .map(|stream| unsafe { (*stream.parameters().as_ptr()).bit_rate })
+            .filter(|bit_rate| *bit_rate > 0)
+            .sum(),
+    };
+    (bit_rate > 0).then(|| (size as i128 * 8 * 1_000_000 / bit_rate as i128) as i64)
+}
+
+/// The input's duration and where it came from. When the container has
+/// none, it's read from the last timestamps or else extrapolated from the
+/// bitrate, both only for inputs that can seek.
+fn duration(ictx: &mut MediaInput) -> (Option<i64>, DurationSource) {
+    // In AV_TIME_BASE (microseconds) and unset when unknown.
+    let duration = ictx.duration();
+    if duration != AV_NOPTS_VALUE && duration >= 0 {
+        // FFmpeg falls back on the bitrate itself for some formats, e.g.
+        // MP3 without a Xing header.
+        let method = unsafe { (*ictx.as_ptr()).duration_estimation_method };
+        return match method {
+            AVDurationEstimationMethod::AVFMT_DURATION_FROM_BITRATE => {
+                (Some(duration), DurationSource::Bitrate)
+            }
+            _ => (Some(duration), DurationSource::Container),
+        };
+    }
+    let Some(size) = input_size(ictx) else {
+        return (None, DurationSource::Unknown);
+    };
+    if let Some(duration) = scan_duration(ictx, size) {
+        return (Some(duration), DurationSource::Scanned);
+    }
+    match bitrate_duration(ictx, size) {
+        Some(duration) => (Some(duration), DurationSource::Bitrate),
+        None => (None, DurationSource::Unknown),
+    }
+}
+
+/// Whether the input looks like an audio file from its first bytes, such
+/// inputs describe their streams in their headers.
+fn is_audio_file(input_path: &Path) -> bool {
+    sniff_file(input_path).is_ok_and(|content_type| content_type.mime_type.starts_with("audio/"))
+}
+
+/// Describes the container and its streams from the headers, as far as
+/// FFmpeg's probing gets without decoding frames. Audio files are only
+/// probed from their first packets, which is enough for them and much
+/// quicker on long recordings.
+pub fn probe_media(input_path: &Path) -> Result<MediaInfo, MediaLibError> {
+    probe_media_with_decryption(input_path, &DecryptionOptions::default())
+}
+
+/// Like `probe_media` for an encrypted input `decryption` has the keys for.
+pub fn probe_media_with_decryption(
+    input_path: &Path,
+    decryption: &DecryptionOptions,
+) -> Result<MediaInfo, MediaLibError> {
+    let mut ictx = if decryption.is_empty() && is_audio_file(input_path) {
+        open_input_quick(input_path)?
+    } else {
+        open_input_with_options(input_path, None, &InputOptions::default(), decryption)?
+    };
+    describe(&mut ictx)
+}
+
+/// Like `probe_media` for an input read from memory (or any other reader).
+pub fn probe_reader(reader: Box<dyn ReadSeek>) -> Result<MediaInfo, MediaLibError> {
+    let mut ictx = open_reader(reader, None)?;
+    describe(&mut ictx)
+}
+
+fn describe(ictx: &mut MediaInput) -> Result<MediaInfo, MediaLibError> {
+    let mut streams = stabby::vec::Vec::new();
+    for stream in ictx.streams() {
+        streams.push(stream_info(&stream)?);
+    }
+
+    // In bits per second and unset when unknown.
+    let bit_rate = ictx.bit_rate();
+    let (duration_us, duration_source) = duration(ictx);
+    Ok(MediaInfo {
+        format_name: ictx.format().name().into(),
+        streams,
+        duration_us: duration_us.into(),
+        bit_rate: (bit_rate > 0).then_some(bit_rate as u64).into(),
+        duration_source,
+    })
+}
+
+/// The video streams of the input, for picking one to decode with
+/// `MediaFrameDecoderOptions::stream_index` when it has several angles or
+/// an embedded preview.
+pub fn list_video_streams(input_path: &Path) -> Result<Vec<StreamInfo>, MediaLibError> {
+    let ictx = open_input(input_path, None)?;
+    ictx.streams()
+        .filter(|stream| stream.parameters().medium() == ffmpeg_next::media::Type::Video)
+        .map(|stream| stream_info(&stream))
+        .collect()
+}
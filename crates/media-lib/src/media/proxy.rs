@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::{Dictionary, Rational};
+use media_types::{
+    MediaFrameDecoderOptions, MediaLibError, OutputOptions, ProcessingReport, RedactionOptions,
+    RegionDetectorBox, ResampleOptions, SampleFormat, ScrubProxyOptions, Size,
+};
+
+use super::audio_decoder::AudioDecoder;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::io_error;
+use super::job::Job;
+use super::lut::Lut3d;
+use super::output::StagedOutput;
+use super::redaction::Redactor;
+use super::speed::{check_speed, AudioTempo, VideoSpeed};
+use super::video_writer::{VideoWriter, WriterSettings};
+
+// Used when the input doesn't say what its frame rate is.
+const DEFAULT_FRAME_RATE: Rational = Rational(30, 1);
+// Proxy audio is resampled to this whatever the input's rate.
+const AUDIO_SAMPLE_RATE: u32 = 48000;
+
+/// The proxy's size, `width` wide at the input's aspect ratio and never
+/// larger than the input.
+fn proxy_size(input: Size, width: u32) -> Size {
+    if width == 0 || width >= input.width || input.width == 0 {
+        return input;
+    }
+    let height = (input.height as u64 * width as u64 + input.width as u64 / 2) / input.width as u64;
+    Size::new(width, (height as u32).max(2))
+}
+
+/// The input's audio, tempo adjusted to the proxy's speed and written
+/// alongside the video.
+struct ProxyAudio {
+    decoder: AudioDecoder,
+    tempo: AudioTempo,
+    decoded_all: bool,
+}
+
+impl ProxyAudio {
+    /// Writes audio until there's as much as `video_us` of it, or all of it
+    /// when None.
+    fn write_until(
+        &mut self,
+        writer: &mut VideoWriter,
+        video_us: Option<i64>,
+    ) -> Result<(), MediaLibError> {
+        loop {
+            while let Some(mut samples) = self.tempo.next()? {
+                writer.write_audio(&mut samples)?;
+            }
+            let caught_up = video_us
+                .is_some_and(|video_us| writer.audio_position_us().unwrap_or_default() >= video_us);
+            if self.decoded_all || caught_up {
+                return Ok(());
+            }
+            match self.decoder.next_frame() {
+                Some(samples) => self.tempo.add(&samples?)?,
+                None => {
+                    self.tempo.flush()?;
+                    self.decoded_all = true;
+                }
+            }
+        }
+    }
+}
+
+/// Encodes one proxy frame, then the audio that goes with it.
+fn write_frame(
+    writer: &mut VideoWriter,
+    audio: &mut Option<ProxyAudio>,
+    frame: &Video,
+) -> Result<(), MediaLibError> {
+    let mut converted = writer.convert(frame)?;
+    writer.write(&mut converted)?;
+    let video_us = writer.position_us();
+    match audio {
+        Some(audio) => audio.write_until(writer, Some(video_us)),
+        None => Ok(()),
+    }
+}
+
+/// The fragmented MP4 holding the media, next to the playlist.
+fn media_path(playlist_path: &Path) -> PathBuf {
+    playlist_path.with_extension("mp4")
+}
+
+/// Has the playlist at `playlist_path` name the media file by its final
+/// name rather than the one it was staged under.
+fn retarget_playlist(
+    playlist_path: &Path,
+    staged_media_path: &Path,
+    media_path: &Path,
+) -> Result<(), MediaLibError> {
+    let (Some(staged_name), Some(name)) = (staged_media_path.file_name(), media_path.file_name())
+    else {
+        return Ok(());
+    };
+    if staged_name == name {
+        return Ok(());
+    }
+    let playlist = std::fs::read_to_string(playlist_path).map_err(io_error)?;
+    let playlist = playlist.replace(&*staged_name.to_string_lossy(), &name.to_string_lossy());
+    std::fs::write(playlist_path, playlist).map_err(io_error)
+}
+
+/// Writes a low resolution proxy of `input_path` as a single fragmented MP4
+/// next to `playlist_path` (same name, `.mp4`) and an HLS playlist at
+/// `playlist_path` giving the byte range of every fragment, so a web player
+/// can jump to any point with one range request. Fragments start on a
+/// keyframe every `fragment_duration_ms`. Proxies at another `speed` are
+/// retimed in the same pass, with the audio's tempo changed to match.
+pub fn scrub_proxy(
+    input_path: &Path,
+    playlist_path: &Path,
+    options: &ScrubProxyOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    write_proxy(input_path, playlist_path, options, None, job)
+}
+
+/// Same as `scrub_proxy` with what `detector` finds in each frame blurred
+/// or filled in before it's encoded, for proxies of footage with faces or
+/// number plates that can't leave the building as shot.
+pub fn redacted_scrub_proxy(
+    input_path: &Path,
+    playlist_path: &Path,
+    options: &ScrubProxyOptions,
+    redaction: &RedactionOptions,
+    detector: &mut RegionDetectorBox,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    let redactor = Redactor::new(detector, redaction)?;
+    write_proxy(input_path, playlist_path, options, Some(redactor), job)
+}
+
+fn write_proxy(
+    input_path: &Path,
+    playlist_path: &Path,
+    options: &ScrubProxyOptions,
+    mut redactor: Option<Redactor<'_>>,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    if options.fragment_duration_ms == 0 {
+        return Err(MediaLibError::UnknownError(
+            "Fragment duration must be above zero".into(),
+        ));
+    }
+    check_speed(options.speed)?;
+
+    let mut lut = Lut3d::from_option(&options.lut_path)?;
+    let started = Instant::now();
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let frame_rate = match video_decoder.frame_rate() {
+        rate if rate.numerator() > 0 && rate.denominator() > 0 => rate,
+        _ => DEFAULT_FRAME_RATE,
+    };
+    let gop_size = (f64::from(frame_rate) * options.fragment_duration_ms as f64 / 1000.0)
+        .round()
+        .max(1.0) as u32;
+
+    // Both files are staged, and the media file is put in place first so the
+    // playlist never names one that isn't there yet.
+    let media_path = media_path(playlist_path);
+    let media_output = StagedOutput::new(&media_path, &options.output)?;
+    let playlist_output = StagedOutput::new(playlist_path, &options.output)?;
+    let mut muxer_options = Dictionary::new();
+    muxer_options.set("hls_segment_type", "fmp4");
+    // One file, with the init section and fragments addressed by byte range.
+    muxer_options.set("hls_flags", "single_file+independent_segments");
+    muxer_options.set(
+        "hls_segment_filename",
+        &media_output.staging_path().to_string_lossy(),
+    );
+    muxer_options.set(
+        "hls_time",
+        &(options.fragment_duration_ms as f64 / 1000.0).to_string(),
+    );
+    muxer_options.set("hls_playlist_type", "vod");
+    muxer_options.set("hls_list_size", "0");
+
+    let mut warnings = Vec::new();
+    let audio_decoder = if options.audio {
+        let format = ResampleOptions {
+            sample_rate: AUDIO_SAMPLE_RATE,
+            channels: 2,
+            sample_format: SampleFormat::F32,
+        };
+        match AudioDecoder::with_format(input_path, &format) {
+            Ok(decoder) => Some(decoder),
+            // Inputs without audio still get a proxy.
+            Err(e) => {
+                warnings.push(format!("Proxy has no audio: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let settings = WriterSettings {
+        format: Some("hls"),
+        gop_size,
+        bit_rate: options.bit_rate,
+        muxer_options,
+        audio_sample_rate: audio_decoder.as_ref().map_or(0, |_| AUDIO_SAMPLE_RATE),
+        // The playlist is already being written at its staging path.
+        output: OutputOptions {
+            atomic: false,
+            durable: false,
+            overwrite: true,
+        },
+        ..WriterSettings::default()
+    };
+    let size = proxy_size(video_decoder.size(), options.width);
+    let mut writer =
+        VideoWriter::with_settings(playlist_output.staging_path(), size, frame_rate, settings)?;
+    let mut audio = match (audio_decoder, writer.audio_encoder()) {
+        (Some(decoder), Some(encoder)) => Some(ProxyAudio {
+            tempo: AudioTempo::new(options.speed, encoder)?,
+            decoder,
+            decoded_all: false,
+        }),
+        _ => None,
+    };
+    let mut speed = (options.speed != 1.0).then(|| VideoSpeed::new(options.speed, frame_rate));
+
+    let start_time = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        job.check()?;
+        let decoded = decoded?;
+        let time_us = video_decoder
+            .frame_time_us(&decoded)
+            .map(|time| time - start_time);
+        if let Some(time_us) = time_us {
+            job.progress(time_us, video_decoder.duration_us());
+        }
+        let decoded = match redactor.as_mut() {
+            Some(redactor) => redactor.apply(decoded, time_us.unwrap_or_default())?,
+            None => decoded,
+        };
+        let decoded = match lut.as_mut() {
+            Some(lut) => lut.apply(decoded)?,
+            None => decoded,
+        };
+        match (speed.as_mut(), time_us) {
+            (Some(speed), Some(time_us)) => {
+                speed.add(&decoded, time_us)?;
+                while let Some(frame) = speed.next()? {
+                    write_frame(&mut writer, &mut audio, &frame)?;
+                }
+            }
+            // Frames without a timestamp can't be retimed.
+            (Some(_), None) => {}
+            (None, _) => write_frame(&mut writer, &mut audio, &decoded)?,
+        }
+    }
+    if let Some(speed) = speed.as_mut() {
+        speed.flush()?;
+        while let Some(frame) = speed.next()? {
+            write_frame(&mut writer, &mut audio, &frame)?;
+        }
+    }
+    if let Some(audio) = audio.as_mut() {
+        audio.write_until(&mut writer, None)?;
+    }
+    let frames_written = writer.finish()?;
+    retarget_playlist(
+        playlist_output.staging_path(),
+        media_output.staging_path(),
+        &media_path,
+    )?;
+    media_output.commit()?;
+    playlist_output.commit()?;
+
+    let mut report = ProcessingReport {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        frames_decoded: video_decoder.frames_decoded,
+        frames_returned: frames_written,
+        frames_dropped: 0,
+        hardware_device: video_decoder
+            .hardware_device_name()
+            .map(|name| name.as_str().into())
+            .into(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    for warning in video_decoder.warnings.iter().chain(&warnings) {
+        report.warnings.push(warning.as_str().into());
+    }
+    for path in [playlist_path, media_path.as_path()] {
+        let bytes = std::fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        report.add_output(&path.to_string_lossy(), bytes);
+    }
+    Ok(report)
+}
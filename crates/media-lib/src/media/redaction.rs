@@ -0,0 +1,459 @@
+use ffmpeg_next::ffi::{
+    av_frame_make_writable, AV_PIX_FMT_FLAG_BE, AV_PIX_FMT_FLAG_BITSTREAM, AV_PIX_FMT_FLAG_HWACCEL,
+    AV_PIX_FMT_FLAG_PAL,
+};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    DetectionImage, MediaLibError, Rect, RedactionMethod, RedactionOptions, RegionDetectorBox,
+    RegionDetectorDynMut, Size,
+};
+
+use super::ffmpeg_error;
+use super::filter_graph::FilterGraph;
+
+// Smallest side of a region, anything smaller is barely a sample across
+// once chroma is subsampled.
+const MIN_REGION_SIZE: u32 = 4;
+
+/// `frame` as packed RGB at `size`, for the detector.
+fn detection_image(
+    frame: &Video,
+    size: Size,
+    pts_us: i64,
+) -> Result<DetectionImage, MediaLibError> {
+    let mut rgb = Video::empty();
+    Context::get(
+        frame.format(),
+        frame.width(),
+        frame.height(),
+        Pixel::RGB24,
+        size.width,
+        size.height,
+        Flags::BILINEAR,
+    )
+    .and_then(|mut scaler| scaler.run(frame, &mut rgb))
+    .map_err(ffmpeg_error)?;
+
+    let row_bytes = size.width as usize * 3;
+    let mut pixels = stabby::vec::Vec::new();
+    for row in rgb.data(0).chunks(rgb.stride(0)).take(size.height as usize) {
+        for byte in &row[..row_bytes] {
+            pixels.push(*byte);
+        }
+    }
+    Ok(DetectionImage {
+        pixels,
+        pts_us,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// `region` from the detector's image mapped onto a `frame` sized frame,
+/// padded and clipped to it. Sides are kept even for subsampled chroma.
+/// None when nothing of it is left inside the frame.
+fn frame_region(region: Rect, detected: Size, frame: Size, padding: u32) -> Option<Rect> {
+    if detected.is_empty() || frame.width < MIN_REGION_SIZE || frame.height < MIN_REGION_SIZE {
+        return None;
+    }
+    let scale_x = |x: u32| (x as u64 * frame.width as u64 / detected.width as u64) as u32;
+    let scale_y = |y: u32| (y as u64 * frame.height as u64 / detected.height as u64) as u32;
+    let left = scale_x(region.x).saturating_sub(padding) & !1;
+    let top = scale_y(region.y).saturating_sub(padding) & !1;
+    let right = (scale_x(region.x.saturating_add(region.width)) + padding).min(frame.width);
+    let bottom = (scale_y(region.y.saturating_add(region.height)) + padding).min(frame.height);
+    if left >= right || top >= bottom {
+        return None;
+    }
+    let width = ((right - left).max(MIN_REGION_SIZE) & !1).min(frame.width - left);
+    let height = ((bottom - top).max(MIN_REGION_SIZE) & !1).min(frame.height - top);
+    Some(Rect::new(
+        left.min(frame.width - width),
+        top.min(frame.height - height),
+        width,
+        height,
+    ))
+}
+
+/// Where a pixel format keeps one of its components, for reading and
+/// writing the samples of a region directly.
+struct Component {
+    plane: usize,
+    // Bytes from one pixel's sample to the next, and to the first one.
+    step: usize,
+    offset: usize,
+    wide: bool,
+    // Log2 of how many pixels share a sample, across and down.
+    shift: (u32, u32),
+}
+
+impl Component {
+    /// `format`'s components, formats whose samples aren't whole bytes in
+    /// native byte order aren't supported.
+    fn all(format: Pixel) -> Result<Vec<Component>, MediaLibError> {
+        let unsupported = (AV_PIX_FMT_FLAG_BITSTREAM
+            | AV_PIX_FMT_FLAG_PAL
+            | AV_PIX_FMT_FLAG_HWACCEL
+            | AV_PIX_FMT_FLAG_BE) as u64;
+        let descriptor = format
+            .descriptor()
+            .map(|descriptor| unsafe { &*descriptor.as_ptr() })
+            .filter(|descriptor| descriptor.flags & unsupported == 0)
+            .ok_or_else(|| {
+                MediaLibError::UnknownError(format!("Can't redact {:?} frames", format).into())
+            })?;
+        let chroma_shift = (
+            descriptor.log2_chroma_w as u32,
+            descriptor.log2_chroma_h as u32,
+        );
+        Ok(descriptor.comp[..descriptor.nb_components as usize]
+            .iter()
+            .enumerate()
+            .map(|(i, component)| Component {
+                plane: component.plane as usize,
+                step: component.step as usize,
+                offset: component.offset as usize,
+                wide: component.depth > 8,
+                // Only the chroma components, the second and third, are
+                // subsampled.
+                shift: if i == 1 || i == 2 {
+                    chroma_shift
+                } else {
+                    (0, 0)
+                },
+            })
+            .collect())
+    }
+
+    /// The size of the component's plane in samples.
+    fn size(&self, frame: Size) -> Size {
+        let (x, y) = self.shift;
+        Size::new(
+            (frame.width + (1 << x) - 1) >> x,
+            (frame.height + (1 << y) - 1) >> y,
+        )
+    }
+
+    /// `region`'s samples in the component's plane.
+    fn region(&self, region: Rect) -> Rect {
+        let (x, y) = self.shift;
+        let left = region.x >> x;
+        let top = region.y >> y;
+        Rect::new(
+            left,
+            top,
+            ((region.x + region.width + (1 << x) - 1) >> x) - left,
+            ((region.y + region.height + (1 << y) - 1) >> y) - top,
+        )
+    }
+
+    fn index(&self, frame: &Video, x: u32, y: u32) -> usize {
+        y as usize * frame.stride(self.plane) + x as usize * self.step + self.offset
+    }
+
+    fn get(&self, frame: &Video, x: u32, y: u32) -> u32 {
+        let index = self.index(frame, x, y);
+        let data = frame.data(self.plane);
+        if self.wide {
+            u16::from_ne_bytes([data[index], data[index + 1]]) as u32
+        } else {
+            data[index] as u32
+        }
+    }
+
+    fn set(&self, frame: &mut Video, x: u32, y: u32, value: u32) {
+        let index = self.index(frame, x, y);
+        let data = frame.data_mut(self.plane);
+        if self.wide {
+            data[index..index + 2].copy_from_slice(&(value as u16).to_ne_bytes());
+        } else {
+            data[index] = value as u8;
+        }
+    }
+}
+
+/// Gives `frame` buffers of its own to write to, the decoder may still be
+/// holding on to the ones it came with.
+fn make_writable(frame: &mut Video) -> Result<(), MediaLibError> {
+    let result = unsafe { av_frame_make_writable(frame.as_mut_ptr()) };
+    if result < 0 {
+        return Err(ffmpeg_error(ffmpeg_next::Error::from(result)));
+    }
+    Ok(())
+}
+
+/// Copies `regions` of `from` over the same regions of `frame`.
+fn copy_regions(frame: &mut Video, from: &Video, regions: &[Rect]) -> Result<(), MediaLibError> {
+    for component in Component::all(frame.format())? {
+        for region in regions {
+            let region = component.region(*region);
+            for y in region.y..region.y + region.height {
+                for x in region.x..region.x + region.width {
+                    let value = component.get(from, x, y);
+                    component.set(frame, x, y, value);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fills `regions` of `frame` in from the samples around them the way
+/// FFmpeg's delogo does, each sample a mix of the borders across and down
+/// weighted towards the nearer ones. Regions are kept a sample off the
+/// frame's edges so there's a border on every side.
+fn fill_regions(frame: &mut Video, regions: &[Rect]) -> Result<(), MediaLibError> {
+    let frame_size = Size::new(frame.width(), frame.height());
+    for component in Component::all(frame.format())? {
+        let plane = component.size(frame_size);
+        for region in regions {
+            let region = component.region(*region);
+            let left = region.x.max(1);
+            let top = region.y.max(1);
+            let right = (region.x + region.width).min(plane.width.saturating_sub(1));
+            let bottom = (region.y + region.height).min(plane.height.saturating_sub(1));
+            for y in top..bottom {
+                let (above, below) = (y - top + 1, bottom - y);
+                for x in left..right {
+                    let (before, after) = (x - left + 1, right - x);
+                    let across = (component.get(frame, left - 1, y) * after
+                        + component.get(frame, right, y) * before)
+                        / (before + after);
+                    let down = (component.get(frame, x, top - 1) * below
+                        + component.get(frame, x, bottom) * above)
+                        / (above + below);
+                    let to_side = before.min(after);
+                    let to_edge = above.min(below);
+                    let value = (across * to_edge + down * to_side) / (to_side + to_edge);
+                    component.set(frame, x, y, value);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Filter spec blurring whole frames of `size`. boxblur refuses a radius
+/// above half the chroma plane's smaller side.
+fn blur_spec(size: Size, radius: u32) -> String {
+    let radius = radius.min(size.width.min(size.height) / 4).max(1);
+    format!("boxblur={}:1", radius)
+}
+
+/// Hides what the host's detector finds in each frame, with a blur or a
+/// fill from the region's border. Frames without anything to hide pass
+/// through untouched.
+pub struct Redactor<'a> {
+    detector: &'a mut RegionDetectorBox,
+    options: RedactionOptions,
+    // Regions found by the last detection, in the detector's pixels, and
+    // the size of the image it was given.
+    detected: Vec<Rect>,
+    detected_size: Size,
+    frames_until_detection: u32,
+    // Blurs whole frames, the blurred regions are copied back over the
+    // frame. Only rebuilt for frames of another format or size, however
+    // the regions move.
+    blur: Option<FilterGraph>,
+    frames_redacted: u64,
+}
+
+impl<'a> Redactor<'a> {
+    pub fn new(
+        detector: &'a mut RegionDetectorBox,
+        options: &RedactionOptions,
+    ) -> Result<Self, MediaLibError> {
+        if options.detect_interval == 0 {
+            return Err(MediaLibError::UnknownError(
+                "Detection interval must be above zero".into(),
+            ));
+        }
+        Ok(Redactor {
+            detector,
+            options: options.clone(),
+            detected: Vec::new(),
+            detected_size: Size::default(),
+            frames_until_detection: 0,
+            blur: None,
+            frames_redacted: 0,
+        })
+    }
+
+    /// Frames that had at least one region hidden.
+    pub fn frames_redacted(&self) -> u64 {
+        self.frames_redacted
+    }
+
+    fn detect(&mut self, frame: &Video, pts_us: i64) -> Result<(), MediaLibError> {
+        let source = Size::new(frame.width(), frame.height());
+        let size = match self.options.detection_width {
+            width if width == 0 || width >= source.width => source,
+            width => Size::new(width & !1, source.scale_to_width(width).height.max(2)),
+        };
+        let image = detection_image(frame, size, pts_us)?;
+        self.detected = self.detector.detect(image).iter().copied().collect();
+        self.detected_size = size;
+        Ok(())
+    }
+
+    /// `frame` with what the detector finds hidden. `pts_us` is handed to
+    /// the detector as is, e.g. for trackers that want to know how far
+    /// apart frames are.
+    pub fn apply(&mut self, mut frame: Video, pts_us: i64) -> Result<Video, MediaLibError> {
+        if self.frames_until_detection == 0 {
+            self.detect(&frame, pts_us)?;
+            self.frames_until_detection = self.options.detect_interval;
+        }
+        self.frames_until_detection -= 1;
+
+        let size = Size::new(frame.width(), frame.height());
+        let regions: Vec<Rect> = self
+            .detected
+            .iter()
+            .filter_map(|region| {
+                frame_region(*region, self.detected_size, size, self.options.padding)
+            })
+            .collect();
+        if regions.is_empty() {
+            return Ok(frame);
+        }
+
+        match self.options.method {
+            RedactionMethod::Blur => {
+                let blur = match &mut self.blur {
+                    Some(blur) if blur.accepts(&frame) => blur,
+                    slot => slot.insert(FilterGraph::new(
+                        &frame,
+                        &blur_spec(size, self.options.blur_radius),
+                    )?),
+                };
+                let blurred = blur.run(&frame)?;
+                make_writable(&mut frame)?;
+                copy_regions(&mut frame, &blurred, &regions)?;
+            }
+            RedactionMethod::Delogo => {
+                make_writable(&mut frame)?;
+                fill_regions(&mut frame, &regions)?;
+            }
+        }
+        self.frames_redacted += 1;
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use media_types::RegionDetector;
+
+    // Finds each of `regions` in turn, one per detection.
+    struct MovingDetector {
+        regions: Vec<Rect>,
+        detections: usize,
+    }
+
+    impl RegionDetector for MovingDetector {
+        extern "C" fn detect(&mut self, _image: DetectionImage) -> stabby::vec::Vec<Rect> {
+            let mut found = stabby::vec::Vec::new();
+            found.push(self.regions[self.detections % self.regions.len()]);
+            self.detections += 1;
+            found
+        }
+    }
+
+    // Noise, so hiding a region changes nearly every sample in it.
+    fn noise_frame(size: u32) -> Video {
+        let mut frame = Video::new(Pixel::YUV420P, size, size);
+        let mut state = 1u32;
+        for plane in 0..3 {
+            for byte in frame.data_mut(plane) {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                *byte = (state >> 16) as u8;
+            }
+        }
+        frame
+    }
+
+    fn luma(frame: &Video, x: u32, y: u32) -> u8 {
+        frame.data(0)[y as usize * frame.stride(0) + x as usize]
+    }
+
+    /// Luma samples inside `region` that changed, and whether any outside
+    /// it did.
+    fn changes(before: &Video, after: &Video, region: Rect) -> (usize, bool) {
+        let mut inside = 0;
+        let mut outside = false;
+        for y in 0..before.height() {
+            for x in 0..before.width() {
+                if luma(before, x, y) != luma(after, x, y) {
+                    let in_region = (region.x..region.x + region.width).contains(&x)
+                        && (region.y..region.y + region.height).contains(&y);
+                    if in_region {
+                        inside += 1;
+                    } else {
+                        outside = true;
+                    }
+                }
+            }
+        }
+        (inside, outside)
+    }
+
+    fn redacts_where_the_detector_looks(method: RedactionMethod) {
+        let regions = vec![Rect::new(8, 8, 16, 16), Rect::new(40, 40, 16, 16)];
+        let mut detector: RegionDetectorBox = stabby::boxed::Box::new(MovingDetector {
+            regions: regions.clone(),
+            detections: 0,
+        })
+        .into();
+        let options = RedactionOptions {
+            padding: 0,
+            detection_width: 0,
+            method,
+            ..RedactionOptions::default()
+        };
+        let mut redactor = Redactor::new(&mut detector, &options).unwrap();
+        let frame = noise_frame(64);
+
+        // The region moves between frames, what's hidden moves with it.
+        for (pts_us, region) in regions.into_iter().enumerate() {
+            let redacted = redactor.apply(frame.clone(), pts_us as i64).unwrap();
+            let (inside, outside) = changes(&frame, &redacted, region);
+            assert!(inside > 16 * 16 / 2, "{:?} {} changed", method, inside);
+            assert!(!outside, "{:?} changed pixels outside {:?}", method, region);
+        }
+        assert_eq!(redactor.frames_redacted(), 2);
+    }
+
+    #[test]
+    fn it_blurs_the_regions_found() {
+        redacts_where_the_detector_looks(RedactionMethod::Blur);
+    }
+
+    #[test]
+    fn it_fills_in_the_regions_found() {
+        redacts_where_the_detector_looks(RedactionMethod::Delogo);
+    }
+
+    #[test]
+    fn it_maps_regions_onto_the_frame() {
+        let region = frame_region(
+            Rect::new(10, 10, 20, 20),
+            Size::new(100, 100),
+            Size::new(200, 200),
+            4,
+        );
+        assert_eq!(region, Some(Rect::new(16, 16, 48, 48)));
+        assert_eq!(
+            frame_region(
+                Rect::new(200, 200, 20, 20),
+                Size::new(100, 100),
+                Size::new(200, 200),
+                0
+            ),
+            None
+        );
+    }
+}
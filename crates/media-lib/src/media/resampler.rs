@@ -0,0 +1,152 @@
+use ffmpeg_next::format::{sample, Sample};
+use ffmpeg_next::software::resampling;
+use ffmpeg_next::util::frame::audio::Audio;
+use ffmpeg_next::ChannelLayout;
+use media_types::{MediaLibError, ResampleOptions, SampleFormat};
+
+use super::ffmpeg_error;
+
+fn sample_format(format: SampleFormat) -> Sample {
+    match format {
+        SampleFormat::S16 => Sample::I16(sample::Type::Packed),
+        SampleFormat::S32 => Sample::I32(sample::Type::Packed),
+        SampleFormat::F32 => Sample::F32(sample::Type::Packed),
+    }
+}
+
+/// Layout of `frame`'s channels, FFmpeg's default for the count when the
+/// decoder didn't say which channels they are.
+fn channel_layout(frame: &Audio) -> ChannelLayout {
+    let layout = frame.channel_layout();
+    if layout.is_empty() {
+        ChannelLayout::default(frame.channels() as i32)
+    } else {
+        layout
+    }
+}
+
+/// Converts decoded audio to one sample rate, channel layout and sample
+/// format with swresample. The converter is set up from the first frame
+/// and again whenever the input format changes mid-stream.
+pub struct Resampler {
+    format: Sample,
+    layout: ChannelLayout,
+    sample_rate: u32,
+    context: Option<resampling::Context>,
+}
+
+impl Resampler {
+    pub fn new(options: &ResampleOptions) -> Result<Self, MediaLibError> {
+        if options.sample_rate == 0 || options.channels == 0 {
+            return Err(MediaLibError::UnknownError(
+                format!(
+                    "Invalid output of {} channels at {}Hz",
+                    options.channels, options.sample_rate
+                )
+                .into(),
+            ));
+        }
+        Ok(Resampler {
+            format: sample_format(options.sample_format),
+            layout: ChannelLayout::default(options.channels as i32),
+            sample_rate: options.sample_rate,
+            context: None,
+        })
+    }
+
+    /// `decoded` in the output format. swresample holds back a few samples
+    /// for its filter, `flush` returns them at the end.
+    pub fn run(&mut self, decoded: &Audio) -> Result<Audio, MediaLibError> {
+        let input = resampling::context::Definition {
+            format: decoded.format(),
+            channel_layout: channel_layout(decoded),
+            rate: decoded.rate(),
+        };
+        let context = match &mut self.context {
+            Some(context) if *context.input() == input => context,
+            _ => self.context.insert(
+                resampling::Context::get(
+                    input.format,
+                    input.channel_layout,
+                    input.rate,
+                    self.format,
+                    self.layout,
+                    self.sample_rate,
+                )
+                .map_err(ffmpeg_error)?,
+            ),
+        };
+
+        let mut resampled = Audio::empty();
+        context.run(decoded, &mut resampled).map_err(ffmpeg_error)?;
+        Ok(resampled)
+    }
+
+    /// The samples still held back, none when there are none.
+    pub fn flush(&mut self) -> Result<Option<Audio>, MediaLibError> {
+        let Some(context) = &mut self.context else {
+            return Ok(None);
+        };
+        let mut resampled = Audio::empty();
+        context.flush(&mut resampled).map_err(ffmpeg_error)?;
+        Ok((resampled.samples() > 0).then_some(resampled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo_tone(samples: usize) -> Audio {
+        let mut frame = Audio::new(
+            Sample::I16(sample::Type::Packed),
+            samples,
+            ChannelLayout::STEREO,
+        );
+        frame.set_rate(48000);
+        for (index, value) in frame.plane_mut::<(i16, i16)>(0).iter_mut().enumerate() {
+            let sample = ((index as f64 / 48.0).sin() * 10000.0) as i16;
+            *value = (sample, sample);
+        }
+        frame
+    }
+
+    #[test]
+    fn it_converts_rate_layout_and_format() {
+        let options = ResampleOptions {
+            sample_rate: 16000,
+            channels: 1,
+            sample_format: SampleFormat::F32,
+        };
+        let mut resampler = Resampler::new(&options).unwrap();
+        let mut frames = 0;
+        for _ in 0..10 {
+            let resampled = resampler.run(&stereo_tone(4800)).unwrap();
+            assert_eq!(resampled.format(), Sample::F32(sample::Type::Packed));
+            assert_eq!(resampled.channels(), 1);
+            assert_eq!(resampled.rate(), 16000);
+            assert!(resampled
+                .plane::<f32>(0)
+                .iter()
+                .all(|sample| (-1.0..=1.0).contains(sample)));
+            frames += resampled.samples();
+        }
+        if let Some(rest) = resampler.flush().unwrap() {
+            frames += rest.samples();
+        }
+        // A second in, a second out.
+        assert!((15_900..=16_100).contains(&frames), "{} frames", frames);
+    }
+
+    #[test]
+    fn it_rejects_empty_outputs() {
+        for (sample_rate, channels) in [(0, 1), (16000, 0)] {
+            let options = ResampleOptions {
+                sample_rate,
+                channels,
+                sample_format: SampleFormat::S16,
+            };
+            assert!(Resampler::new(&options).is_err());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ffmpeg_next::format::context::{Input, Output};
+use ffmpeg_next::{codec, encoder, format, media, Rational};
+use media_types::{MediaLibError, ProcessingReport, RestreamOptions};
+
+use super::ffmpeg_error;
+use super::input::open_input;
+use super::job::Job;
+use super::timestamp::{self, Monotonic};
+
+/// Container to use for a live target, FFmpeg can't guess these from the
+/// URL alone.
+fn container_for(url: &str) -> Option<&'static str> {
+    let scheme = url.split("://").next()?.to_ascii_lowercase();
+    match scheme.as_str() {
+        "rtmp" | "rtmps" => Some("flv"),
+        "srt" | "udp" | "rtp" => Some("mpegts"),
+        _ => None,
+    }
+}
+
+/// An input stream copied to the output, with its index there.
+struct MappedStream {
+    input_index: usize,
+    output_index: usize,
+    time_base: Rational,
+    // A copy, so outputs can be reopened while the input is being read.
+    parameters: codec::Parameters,
+    is_video: bool,
+}
+
+/// Picks the best video and audio streams, which is all FLV can carry.
+fn map_streams(ictx: &Input) -> Vec<MappedStream> {
+    [media::Type::Video, media::Type::Audio]
+        .into_iter()
+        .filter_map(|kind| ictx.streams().best(kind).map(|stream| (kind, stream)))
+        .enumerate()
+        .map(|(output_index, (kind, stream))| MappedStream {
+            input_index: stream.index(),
+            output_index,
+            time_base: stream.time_base(),
+            parameters: stream.parameters().clone(),
+            is_video: kind == media::Type::Video,
+        })
+        .collect()
+}
+
+fn open_output(url: &str, streams: &[MappedStream]) -> Result<Output, MediaLibError> {
+    let mut octx = match container_for(url) {
+        Some(container) => format::output_as(url, container),
+        None => format::output(url),
+    }
+    .map_err(ffmpeg_error)?;
+
+    for mapped in streams {
+        let mut ost = octx
+            .add_stream(encoder::find(codec::Id::None))
+            .map_err(ffmpeg_error)?;
+        ost.set_parameters(mapped.parameters.clone());
+        // The input's codec tag is often meaningless in the target container.
+        unsafe {
+            (*ost.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+    }
+
+    octx.write_header().map_err(ffmpeg_error)?;
+    Ok(octx)
+}
+
+/// Copies the input's video and audio to a live target without re-encoding,
+/// reconnecting if the target drops. Network protocols need
+/// `InitOptions::network` set when the library is initialised.
+pub fn restream(
+    input_path: &Path,
+    url: &str,
+    options: &RestreamOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    let started = Instant::now();
+    let mut ictx = open_input(input_path, None)?;
+    let streams = map_streams(&ictx);
+    if streams.is_empty() {
+        return Err(MediaLibError::FFmpegError(
+            "Input has no audio or video to restream".into(),
+        ));
+    }
+    let has_video = streams.iter().any(|mapped| mapped.is_video);
+    let duration = Some(ictx.duration()).filter(|duration| *duration > 0);
+
+    let mut octx = open_output(url, &streams)?;
+    let mut warnings = Vec::new();
+    let mut packets_sent: u64 = 0;
+    let mut bytes_sent: u64 = 0;
+    // Wall clock time and timestamp (in microseconds) of the first packet,
+    // for pacing in realtime mode.
+    let mut anchor: Option<(Instant, i64)> = None;
+    // Output decode timestamps of each stream. Muxers refuse ones that
+    // don't increase, which would otherwise look like a lost connection.
+    let mut output_dts: Vec<Monotonic> = streams.iter().map(|_| Monotonic::new()).collect();
+    // After reconnecting the target has to start again from a keyframe.
+    let mut waiting_for_keyframe = false;
+
+    for (stream, mut packet) in ictx.packets() {
+        let Some(mapped) = streams
+            .iter()
+            .find(|mapped| mapped.input_index == stream.index())
+        else {
+            continue;
+        };
+        job.check()?;
+        if let Some(pts) = packet.pts() {
+            job.progress(timestamp::to_us(pts, mapped.time_base), duration);
+        }
+
+        if waiting_for_keyframe && has_video {
+            if !(mapped.is_video && packet.is_key()) {
+                continue;
+            }
+            waiting_for_keyframe = false;
+        }
+
+        if options.realtime {
+            if let Some(dts) = packet.dts().or(packet.pts()) {
+                let dts_us = timestamp::to_us(dts, mapped.time_base);
+                let (wall_start, first_dts_us) = *anchor.get_or_insert((Instant::now(), dts_us));
+                let due = Duration::from_micros((dts_us - first_dts_us).max(0) as u64);
+                if let Some(wait) = due.checked_sub(wall_start.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        }
+
+        let output_time_base = octx
+            .stream(mapped.output_index)
+            .map(|stream| stream.time_base())
+            .unwrap_or(mapped.time_base);
+        packet.rescale_ts(mapped.time_base, output_time_base);
+        if let Some(dts) = packet.dts() {
+            let monotonic_dts = output_dts[mapped.output_index].next(dts);
+            if monotonic_dts != dts {
+                packet.set_dts(Some(monotonic_dts));
+                // Nothing is shown before it's decoded.
+                if packet.pts().is_some_and(|pts| pts < monotonic_dts) {
+                    packet.set_pts(Some(monotonic_dts));
+                }
+            }
+        }
+        packet.set_position(-1);
+        packet.set_stream(mapped.output_index);
+
+        let size = packet.size() as u64;
+        match packet.write_interleaved(&mut octx) {
+            Ok(()) => {
+                packets_sent += 1;
+                bytes_sent += size;
+            }
+            Err(e) => {
+                warnings.push(format!("Lost connection to {}: {}", url, e));
+                log::warn!("Lost connection to {}: {}, reconnecting", url, e);
+                octx = reconnect(url, &streams, options)?;
+                waiting_for_keyframe = true;
+            }
+        }
+    }
+
+    octx.write_trailer().map_err(ffmpeg_error)?;
+
+    let mut report = ProcessingReport {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        frames_decoded: 0,
+        frames_returned: packets_sent,
+        frames_dropped: 0,
+        hardware_device: stabby::option::Option::None(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    for warning in &warnings {
+        report.warnings.push(warning.as_str().into());
+    }
+    report.add_output(url, bytes_sent);
+    Ok(report)
+}
+
+fn reconnect(
+    url: &str,
+    streams: &[MappedStream],
+    options: &RestreamOptions,
+) -> Result<Output, MediaLibError> {
+    for attempt in 1..=options.max_reconnects {
+        std::thread::sleep(Duration::from_millis(options.reconnect_delay_ms as u64));
+        match open_output(url, streams) {
+            Ok(octx) => {
+                log::info!("Reconnected to {} after {} attempts", url, attempt);
+                return Ok(octx);
+            }
+            Err(e) => log::warn!("Reconnect attempt {} to {} failed: {}", attempt, url, e),
+        }
+    }
+    Err(MediaLibError::FFmpegError(
+        format!(
+            "Lost connection to {} and gave up after {} reconnect attempts",
+            url, options.max_reconnects
+        )
+        .into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_picks_the_container_for_live_targets() {
+        assert_eq!(
+            container_for("rtmp://live.example.com/app/key"),
+            Some("flv")
+        );
+        assert_eq!(
+            container_for("RTMPS://live.example.com/app/key"),
+            Some("flv")
+        );
+        assert_eq!(container_for("srt://127.0.0.1:9000"), Some("mpegts"));
+        assert_eq!(container_for("udp://239.0.0.1:1234"), Some("mpegts"));
+        // Left to FFmpeg to guess from the extension.
+        assert_eq!(container_for("/tmp/restream.flv"), None);
+        assert_eq!(container_for("file:///tmp/restream.ts"), None);
+    }
+}
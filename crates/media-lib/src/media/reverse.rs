@@ -0,0 +1,303 @@
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{
+    DecodeProgress, JobContext, MediaFrameDecoderOptions, MediaLibError, ProcessingReport, Size,
+    StreamHealth, StreamHealthObserverBox,
+};
+
+use super::alpha::AlphaFlattener;
+use super::canvas::{self, FrameScaler};
+use super::deinterlace::Deinterlacer;
+use super::encode_pool::EncodePool;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::job::Job;
+use super::jpeg;
+use super::percent;
+use super::srgb::SrgbConverter;
+use super::still;
+use super::tonemap::ToneMapper;
+
+// How much further back to seek when a seek lands on the GOP we've already
+// returned, which happens with inaccurate indexes.
+const SEEK_BACKOFF_US: i64 = 1_000_000;
+
+type Item = Result<Vec<u8>, MediaLibError>;
+
+/// Returns the frames of a time range as JPEGs, last frame first. Each GOP
+/// is decoded forwards from its keyframe and buffered (scaled down), then
+/// handed out backwards before moving on to the GOP before it.
+pub struct ReverseFrameIterator {
+    video_decoder: HardwareAcceleratedVideoDecoder,
+    scaler: FrameScaler,
+    encode_pool: EncodePool,
+    start_us: i64,
+    end_us: i64,
+    // Frames after this (relative to the start of the input) have already
+    // been returned.
+    cursor_us: i64,
+    // Scaled frames of the current GOP in presentation order.
+    buffered: Vec<Video>,
+    decoder_done: bool,
+    pub target_size: Size,
+    // Differs from `target_size` when letterboxing or cropping.
+    scaled_size: Size,
+    alpha: AlphaFlattener,
+    // Set when wide gamut frames are to be converted, see `convert_to_srgb`.
+    srgb_converter: Option<SrgbConverter>,
+    // Set when HDR frames are to be tone mapped, see `tone_mapping`.
+    tone_mapper: Option<ToneMapper>,
+    // Set when interlaced frames are to be deinterlaced, see `deinterlace`.
+    deinterlacer: Option<Deinterlacer>,
+    // Set by `set_job`, checked before each frame is decoded.
+    job: Option<Job>,
+    started: Instant,
+    frames_returned: u64,
+    // Set once `get` has returned none.
+    finished: bool,
+}
+
+impl ReverseFrameIterator {
+    pub fn new(
+        input_path: &Path,
+        start_us: i64,
+        end_us: i64,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        if end_us < start_us {
+            return Err(MediaLibError::UnknownError(
+                format!("Invalid time range {}us to {}us", start_us, end_us).into(),
+            ));
+        }
+
+        // Dropping packets to keep up makes no sense when going backwards.
+        let options = MediaFrameDecoderOptions {
+            realtime: false,
+            ..options.clone()
+        };
+        let mut video_decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::new(input_path, &options) }?;
+        video_decoder.keyframes_only = false;
+
+        let orientation = if options.auto_rotate {
+            video_decoder.orientation()
+        } else {
+            1
+        };
+        let (scaled_size, target_size) = options.scaling_mode.apply(
+            still::upright_size(video_decoder.size(), orientation),
+            canvas::requested_size(&options),
+        );
+
+        Ok(ReverseFrameIterator {
+            video_decoder,
+            scaler: FrameScaler::new(
+                jpeg::pixel_format(options.jpeg.subsampling),
+                orientation,
+                scaled_size,
+                target_size,
+            ),
+            encode_pool: EncodePool::new(&options),
+            start_us,
+            end_us,
+            cursor_us: end_us,
+            buffered: Vec::new(),
+            decoder_done: false,
+            target_size,
+            scaled_size,
+            alpha: AlphaFlattener::new(options.alpha_background, options.alpha_color),
+            srgb_converter: options.convert_to_srgb.then(SrgbConverter::default),
+            tone_mapper: ToneMapper::new(options.tone_mapping),
+            deinterlacer: options.deinterlace.then(|| Deinterlacer::new(false)),
+            job: None,
+            started: Instant::now(),
+            frames_returned: 0,
+            finished: false,
+        })
+    }
+
+    fn run_scaler(&mut self, decoded: &Video) -> Result<Video, MediaLibError> {
+        let deinterlaced = self
+            .deinterlacer
+            .as_mut()
+            .and_then(|deinterlacer| deinterlacer.apply(decoded));
+        let decoded = deinterlaced.as_ref().unwrap_or(decoded);
+        // Tone mapped frames are BT.709 already.
+        let converted = match self
+            .tone_mapper
+            .as_mut()
+            .and_then(|mapper| mapper.apply(decoded))
+        {
+            Some(mapped) => Some(mapped),
+            None => self
+                .srgb_converter
+                .as_mut()
+                .and_then(|converter| converter.apply(decoded)),
+        };
+        let converted = converted.as_ref().unwrap_or(decoded);
+        let flattened = self.alpha.apply(converted)?;
+        let frame = flattened.as_ref().unwrap_or(converted);
+
+        self.scaler.run(frame)
+    }
+
+    /// Decodes the GOP holding `cursor_us` into `buffered` and moves the
+    /// cursor to just before it.
+    fn decode_previous_gop(&mut self) -> Result<(), MediaLibError> {
+        let start_time_us = self.video_decoder.start_time_us();
+        let mut seek_us = self.cursor_us;
+        loop {
+            self.video_decoder.seek_to_keyframe(seek_us.max(0))?;
+
+            let mut gop_start_us: Option<i64> = None;
+            let mut frames = Vec::new();
+            while let Some(decoded) = self.video_decoder.get_frame() {
+                if let Some(job) = &mut self.job {
+                    job.check()?;
+                }
+                let decoded = decoded?;
+                let Some(time_us) = self.video_decoder.frame_time_us(&decoded) else {
+                    continue;
+                };
+                let time_us = time_us - start_time_us;
+                if time_us > self.cursor_us {
+                    break;
+                }
+                gop_start_us = Some(gop_start_us.map_or(time_us, |start| start.min(time_us)));
+                if time_us >= self.start_us {
+                    frames.push((time_us, self.run_scaler(&decoded)?));
+                }
+            }
+
+            match gop_start_us {
+                Some(gop_start_us) => {
+                    frames.sort_by_key(|(time_us, _)| *time_us);
+                    // Carried through the encode pool for `progress`.
+                    self.buffered = frames
+                        .into_iter()
+                        .map(|(time_us, mut frame)| {
+                            frame.set_pts(Some(time_us));
+                            frame
+                        })
+                        .collect();
+                    self.cursor_us = gop_start_us - 1;
+                    self.decoder_done = gop_start_us <= self.start_us;
+                    return Ok(());
+                }
+                // Nothing before the cursor at all, we're at the start.
+                None if seek_us <= 0 => {
+                    self.decoder_done = true;
+                    return Ok(());
+                }
+                None => seek_us -= SEEK_BACKOFF_US,
+            }
+        }
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        0
+    }
+
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.video_decoder.interrupt_flag()
+    }
+
+    pub fn stream_health(&self) -> StreamHealth {
+        self.video_decoder.stream_health()
+    }
+
+    pub fn set_health_observer(&mut self, observer: StreamHealthObserverBox) {
+        self.video_decoder.set_health_observer(observer);
+    }
+
+    /// How the input is decoded and scaled, for logging.
+    pub fn pipeline_description(&self) -> String {
+        format!(
+            "{}\noutput: yuvj420p {}x{} on a {}x{} canvas, JPEG",
+            self.video_decoder.pipeline_description(),
+            self.scaled_size.width,
+            self.scaled_size.height,
+            self.target_size.width,
+            self.target_size.height
+        )
+    }
+
+    /// Where the frame returned last is, the percentage is of the range
+    /// worked back through.
+    pub fn progress(&self) -> DecodeProgress {
+        let position_us = self.encode_pool.last_pts();
+        let percent = if self.finished {
+            100.0
+        } else {
+            position_us
+                .and_then(|position_us| {
+                    percent(self.end_us - position_us, self.end_us - self.start_us)
+                })
+                .unwrap_or(0.0)
+        };
+        DecodeProgress {
+            position_us: position_us.into(),
+            duration_us: self.video_decoder.duration_us().into(),
+            percent: Some(percent).into(),
+        }
+    }
+
+    pub fn report(&self) -> ProcessingReport {
+        let mut warnings = stabby::vec::Vec::with_capacity(self.video_decoder.warnings.len());
+        for warning in &self.video_decoder.warnings {
+            warnings.push(warning.as_str().into());
+        }
+
+        ProcessingReport {
+            wall_time_ms: self.started.elapsed().as_millis() as u64,
+            frames_decoded: self.video_decoder.frames_decoded,
+            frames_returned: self.frames_returned,
+            frames_dropped: 0,
+            hardware_device: self
+                .video_decoder
+                .hardware_device_name()
+                .map(|name| name.as_str().into())
+                .into(),
+            warnings,
+            outputs: stabby::vec::Vec::new(),
+        }
+    }
+
+    /// Cancellation, a deadline, progress and log tagging for the frames
+    /// still to come, replacing any job set before.
+    pub fn set_job(&mut self, job: JobContext) {
+        self.job = Some(Job::detached(job).interrupt_with(self.interrupt_flag()));
+    }
+
+    pub fn get(&mut self) -> Option<Item> {
+        let _scope = self.job.as_ref().map(Job::enter);
+        while !self.encode_pool.is_full() {
+            if let Some(frame) = self.buffered.pop() {
+                self.encode_pool.submit(frame);
+            } else if self.decoder_done {
+                break;
+            } else if let Err(e) = self.decode_previous_gop() {
+                self.decoder_done = true;
+                self.encode_pool.push_result(Err(e));
+            }
+        }
+
+        let result = self.encode_pool.next();
+        match result {
+            Some(Ok(_)) => {
+                self.frames_returned += 1;
+                // Progress counts back from the end of the range.
+                if let (Some(job), Some(position_us)) = (&mut self.job, self.encode_pool.last_pts())
+                {
+                    job.progress(self.end_us - position_us, Some(self.end_us - self.start_us));
+                }
+            }
+            None => self.finished = true,
+            Some(Err(_)) => {}
+        }
+        result
+    }
+}
@@ -0,0 +1,271 @@
+use std::path::Path;
+
+use media_types::{DetectedSegment, MediaFrameDecoderOptions, MediaLibError, SegmentKind};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use super::audio_decoder::AudioDecoder;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::poster::Exposure;
+
+// Intros are looked for near the start and credits near the end.
+const INTRO_WINDOW_US: i64 = 10 * 60 * 1_000_000;
+const CREDITS_WINDOW_US: i64 = 10 * 60 * 1_000_000;
+// Anything shorter is more likely a shared sound effect than an intro.
+const MIN_SEGMENT_US: i64 = 15_000_000;
+
+// The audio signature has one 32 bit hash per half second block.
+const SIGNATURE_SAMPLE_RATE: u32 = 8000;
+const BLOCK_SAMPLES: usize = 4096;
+const BLOCK_US: i64 = BLOCK_SAMPLES as i64 * 1_000_000 / SIGNATURE_SAMPLE_RATE as i64;
+const BANDS: usize = 16;
+// Band edges span roughly 100Hz to 3.5kHz on a log scale.
+const LOWEST_BAND_HZ: f32 = 100.0;
+const HIGHEST_BAND_HZ: f32 = 3500.0;
+// Blocks this close (out of 31 bits) are the same audio.
+const SAME_BLOCK_DISTANCE: u32 = 6;
+// Mismatched blocks tolerated inside a matching run, for dialogue or
+// effects mixed over the theme.
+const MAX_RUN_GAP: usize = 4;
+
+// Frames are sampled this far apart when looking for credits.
+const VISUAL_SAMPLE_US: i64 = 5_000_000;
+// Credits are text on a dark background: dark overall but not flat black.
+const CREDITS_MAX_BRIGHTNESS: f64 = 80.0;
+const CREDITS_MIN_DETAIL: f64 = 10.0;
+const BLACK_MAX_BRIGHTNESS: f64 = 24.0;
+// Share of samples that have to look like credits from the start of the
+// credits to the end of the input.
+const CREDITS_MIN_SHARE: f64 = 0.7;
+
+/// Half second hashes of the input's audio: which bands got louder than in
+/// the previous block, and which are louder than the band above. None for
+/// inputs without audio.
+fn audio_signature(input_path: &Path) -> Result<Option<Vec<u32>>, MediaLibError> {
+    let Some(mut decoder) = AudioDecoder::open(input_path, SIGNATURE_SAMPLE_RATE)? else {
+        return Ok(None);
+    };
+    let fft = FftPlanner::new().plan_fft_forward(BLOCK_SAMPLES);
+    let bin_hz = SIGNATURE_SAMPLE_RATE as f32 / BLOCK_SAMPLES as f32;
+    let ratio = (HIGHEST_BAND_HZ / LOWEST_BAND_HZ).powf(1.0 / BANDS as f32);
+    let band_edges: Vec<usize> = (0..=BANDS)
+        .map(|band| (LOWEST_BAND_HZ * ratio.powi(band as i32) / bin_hz) as usize)
+        .collect();
+
+    let mut signature = Vec::new();
+    let mut block = Vec::with_capacity(BLOCK_SAMPLES);
+    let mut previous = [0.0f32; BANDS];
+    while let Some(samples) = decoder.next_samples() {
+        for sample in samples? {
+            block.push(sample);
+            if block.len() < BLOCK_SAMPLES {
+                continue;
+            }
+            let energies = band_energies(fft.as_ref(), &block, &band_edges);
+            signature.push(block_hash(&energies, &previous));
+            previous = energies;
+            block.clear();
+        }
+    }
+    Ok(Some(signature))
+}
+
+fn band_energies(fft: &dyn Fft<f32>, block: &[i16], band_edges: &[usize]) -> [f32; BANDS] {
+    let mut buffer: Vec<Complex<f32>> = block
+        .iter()
+        .map(|sample| Complex::new(*sample as f32, 0.0))
+        .collect();
+    fft.process(&mut buffer);
+
+    let mut energies = [0.0; BANDS];
+    for (energy, edges) in energies.iter_mut().zip(band_edges.windows(2)) {
+        *energy = buffer[edges[0]..edges[1].max(edges[0] + 1)]
+            .iter()
+            .map(|bin| bin.norm_sqr())
+            .sum();
+    }
+    energies
+}
+
+fn block_hash(energies: &[f32; BANDS], previous: &[f32; BANDS]) -> u32 {
+    let mut hash = 0u32;
+    for (band, energy) in energies.iter().enumerate() {
+        if *energy > previous[band] {
+            hash |= 1 << band;
+        }
+        if energies.get(band + 1).is_some_and(|above| energy > above) {
+            hash |= 1 << (BANDS + band);
+        }
+    }
+    hash
+}
+
+/// Longest stretch of `a` that also appears somewhere in `b`, as the block
+/// index it starts at in `a` and its length in blocks.
+fn longest_shared_run(a: &[u32], b: &[u32]) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    for offset in -(a.len() as isize - 1)..b.len() as isize {
+        let mut run_start: Option<usize> = None;
+        let mut last_match = 0;
+        for (i, hash) in a.iter().enumerate() {
+            let j = i as isize + offset;
+            if j < 0 || j >= b.len() as isize {
+                continue;
+            }
+            if (hash ^ b[j as usize]).count_ones() <= SAME_BLOCK_DISTANCE {
+                let start = *run_start.get_or_insert(i);
+                last_match = i;
+                let length = i - start + 1;
+                if best.map_or(0, |(_, best_length)| best_length) < length {
+                    best = Some((start, length));
+                }
+            } else if run_start.is_some() && i - last_match > MAX_RUN_GAP {
+                run_start = None;
+            }
+        }
+    }
+    best
+}
+
+/// Audio that this input shares with the other episodes inside `window`
+/// (block indexes), with the share of episodes it was found in.
+fn shared_audio(
+    signature: &[u32],
+    others: &[Vec<u32>],
+    window: std::ops::Range<usize>,
+) -> Option<(i64, i64, f32)> {
+    let window = window.start.min(signature.len())..window.end.min(signature.len());
+    let own = &signature[window.clone()];
+
+    let mut runs = Vec::new();
+    for other in others {
+        if let Some((start, length)) = longest_shared_run(own, other) {
+            let start_us = (window.start + start) as i64 * BLOCK_US;
+            let length_us = length as i64 * BLOCK_US;
+            if length_us >= MIN_SEGMENT_US {
+                runs.push((start_us, start_us + length_us));
+            }
+        }
+    }
+    if runs.is_empty() {
+        return None;
+    }
+
+    // The widest run found in any episode, with how many agree on it.
+    let (start_us, end_us) = runs
+        .iter()
+        .copied()
+        .max_by_key(|(start, end)| end - start)?;
+    let agreeing = runs
+        .iter()
+        .filter(|(start, end)| *start < end_us && *end > start_us)
+        .count();
+    Some((start_us, end_us, agreeing as f32 / others.len() as f32))
+}
+
+/// Looks for credits by sampling frames across the end of the input: the
+/// earliest point from which most samples look like text on a dark
+/// background or black, with the end of the input and the share of samples
+/// that agree.
+fn visual_credits(input_path: &Path) -> Result<Option<(i64, i64, f32)>, MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    let Some(duration_us) = video_decoder.duration_us() else {
+        return Ok(None);
+    };
+
+    let mut samples = Vec::new();
+    let mut timestamp_us = (duration_us - CREDITS_WINDOW_US).max(0);
+    while timestamp_us < duration_us {
+        video_decoder.seek_to_keyframe(timestamp_us)?;
+        if let Some(frame) = video_decoder.get_frame().transpose()? {
+            let exposure = Exposure::measure(&frame)?;
+            let is_credits = exposure.brightness < BLACK_MAX_BRIGHTNESS
+                || (exposure.brightness < CREDITS_MAX_BRIGHTNESS
+                    && exposure.detail >= CREDITS_MIN_DETAIL);
+            samples.push((timestamp_us, is_credits));
+        }
+        timestamp_us += VISUAL_SAMPLE_US;
+    }
+
+    let min_samples = (MIN_SEGMENT_US / VISUAL_SAMPLE_US) as usize;
+    for (index, (start_us, is_credits)) in samples.iter().enumerate() {
+        let remaining = &samples[index..];
+        if !is_credits || remaining.len() < min_samples {
+            continue;
+        }
+        let share = remaining.iter().filter(|(_, credits)| *credits).count() as f64
+            / remaining.len() as f64;
+        if share >= CREDITS_MIN_SHARE {
+            return Ok(Some((*start_us, duration_us, share as f32)));
+        }
+    }
+    Ok(None)
+}
+
+/// Flags the likely intro and credits of an episode. Intros are found as
+/// audio shared with the other episodes of the same series near the start,
+/// so at least one other episode is needed for them. Credits combine shared
+/// audio near the end with frames that look like text on a dark background.
+/// Without audio only the frames are used, and other episodes without audio
+/// are left out.
+pub fn detect_segments(
+    input_path: &Path,
+    other_episodes: &[&Path],
+) -> Result<Vec<DetectedSegment>, MediaLibError> {
+    let signature = audio_signature(input_path)?;
+    let mut others = Vec::with_capacity(other_episodes.len());
+    if signature.is_some() {
+        for episode in other_episodes {
+            others.extend(audio_signature(episode)?);
+        }
+    }
+
+    let blocks = |duration_us: i64| (duration_us / BLOCK_US) as usize;
+    let mut segments = Vec::new();
+
+    let intro = signature
+        .as_ref()
+        .and_then(|signature| shared_audio(signature, &others, 0..blocks(INTRO_WINDOW_US)));
+    if let Some((start_us, end_us, agreement)) = intro {
+        segments.push(DetectedSegment {
+            start_us,
+            end_us,
+            confidence: 0.5 + 0.5 * agreement,
+            kind: SegmentKind::Intro,
+        });
+    }
+
+    let audio = signature.as_ref().and_then(|signature| {
+        let credits_start = signature.len().saturating_sub(blocks(CREDITS_WINDOW_US));
+        shared_audio(signature, &others, credits_start..signature.len())
+    });
+    let visual = visual_credits(input_path)?;
+    let end_us = match (&signature, visual) {
+        (Some(signature), _) => signature.len() as i64 * BLOCK_US,
+        (None, Some((_, duration_us, _))) => duration_us,
+        (None, None) => 0,
+    };
+    let credits = match (audio, visual) {
+        (Some((audio_start_us, _, agreement)), Some((visual_start_us, _, share))) => Some((
+            audio_start_us.min(visual_start_us),
+            0.4 + 0.3 * agreement + 0.3 * share,
+        )),
+        (Some((audio_start_us, _, agreement)), None) => {
+            Some((audio_start_us, 0.3 + 0.3 * agreement))
+        }
+        (None, Some((visual_start_us, _, share))) => Some((visual_start_us, 0.5 * share)),
+        (None, None) => None,
+    };
+    if let Some((start_us, confidence)) = credits {
+        segments.push(DetectedSegment {
+            start_us,
+            end_us,
+            confidence: confidence.min(1.0),
+            kind: SegmentKind::Credits,
+        });
+    }
+
+    Ok(segments)
+}
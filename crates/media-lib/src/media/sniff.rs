@@ -0,0 +1,170 @@
+use std::ffi::{CStr, CString};
+use std::io::Read;
+use std::path::Path;
+
+use ffmpeg_next::ffi::{av_probe_input_format3, AVProbeData, AVPROBE_PADDING_SIZE};
+use media_types::{ContentType, MediaLibError};
+
+// Enough for every container FFmpeg can identify from its header, without
+// reading the whole of a large upload.
+const SNIFF_BYTES: usize = 64 * 1024;
+
+const UNKNOWN_MIME_TYPE: &str = "application/octet-stream";
+
+// Demuxers that don't declare a MIME type of their own, matched on the
+// first name in FFmpeg's list.
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("matroska", "video/x-matroska"),
+    ("avi", "video/x-msvideo"),
+    ("flv", "video/x-flv"),
+    ("mpegts", "video/mp2t"),
+    ("mpeg", "video/mpeg"),
+    ("asf", "video/x-ms-asf"),
+    ("mp3", "audio/mpeg"),
+    ("aac", "audio/aac"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("ogg", "audio/ogg"),
+    ("gif", "image/gif"),
+    ("png_pipe", "image/png"),
+    ("jpeg_pipe", "image/jpeg"),
+    ("webp_pipe", "image/webp"),
+];
+
+/// The mov demuxer reads QuickTime, MP4, M4A and 3GP alike, they're told
+/// apart by the major brand of the leading `ftyp` box. QuickTime files from
+/// before there was one start with some other box.
+fn iso_mime_type(data: &[u8]) -> &'static str {
+    if data.get(4..8) != Some(b"ftyp") {
+        return "video/quicktime";
+    }
+    match data.get(8..12) {
+        Some(b"qt  ") => "video/quicktime",
+        Some(b"M4A ") | Some(b"M4B ") | Some(b"M4P ") => "audio/mp4",
+        Some(brand) if brand.starts_with(b"3gp") => "video/3gpp",
+        Some(brand) if brand.starts_with(b"3g2") => "video/3gpp2",
+        _ => "video/mp4",
+    }
+}
+
+fn mime_type_for(format_name: &str, declared: Option<&str>, data: &[u8]) -> String {
+    let primary = format_name.split(',').next().unwrap_or_default();
+    if primary == "mov" {
+        return iso_mime_type(data).to_string();
+    }
+    MIME_TYPES
+        .iter()
+        .find(|(name, _)| *name == primary)
+        .map(|(_, mime_type)| mime_type.to_string())
+        .or_else(|| {
+            declared
+                .and_then(|declared| declared.split(',').next())
+                .filter(|mime_type| !mime_type.is_empty())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| UNKNOWN_MIME_TYPE.to_string())
+}
+
+/// Identifies the container from the start of an input without opening it.
+/// `file_name` is optional and only used as a hint for formats without a
+/// recognisable header.
+pub fn sniff_bytes(data: &[u8], file_name: Option<&str>) -> Result<ContentType, MediaLibError> {
+    let data = &data[..data.len().min(SNIFF_BYTES)];
+    // The probes read ahead, FFmpeg requires zeroed padding after the data.
+    let mut buffer = Vec::with_capacity(data.len() + AVPROBE_PADDING_SIZE as usize);
+    buffer.extend_from_slice(data);
+    buffer.resize(data.len() + AVPROBE_PADDING_SIZE as usize, 0);
+
+    let file_name = CString::new(file_name.unwrap_or_default())
+        .map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+    let probe_data = AVProbeData {
+        filename: file_name.as_ptr(),
+        buf: buffer.as_mut_ptr(),
+        buf_size: data.len() as i32,
+        mime_type: std::ptr::null(),
+    };
+
+    let mut score = 0;
+    let format = unsafe { av_probe_input_format3(&probe_data, 1, &mut score) };
+    if format.is_null() {
+        return Ok(ContentType {
+            mime_type: UNKNOWN_MIME_TYPE.into(),
+            format_name: "".into(),
+            confidence: 0,
+        });
+    }
+
+    let (format_name, declared) = unsafe {
+        let format = &*format;
+        let name = CStr::from_ptr(format.name).to_string_lossy().to_string();
+        let declared = (!format.mime_type.is_null()).then(|| {
+            CStr::from_ptr(format.mime_type)
+                .to_string_lossy()
+                .to_string()
+        });
+        (name, declared)
+    };
+
+    Ok(ContentType {
+        mime_type: mime_type_for(&format_name, declared.as_deref(), data)
+            .as_str()
+            .into(),
+        format_name: format_name.as_str().into(),
+        confidence: score.clamp(0, 100) as u32,
+    })
+}
+
+/// Like `sniff_bytes`, reading only the start of the file.
+pub fn sniff_file(input_path: &Path) -> Result<ContentType, MediaLibError> {
+    let mut data = Vec::with_capacity(SNIFF_BYTES);
+    std::fs::File::open(input_path)
+        .and_then(|file| file.take(SNIFF_BYTES as u64).read_to_end(&mut data))
+        .map_err(|e| {
+            MediaLibError::UnknownError(
+                format!("Failed to read {}: {}", input_path.display(), e).into(),
+            )
+        })?;
+
+    let file_name = input_path.file_name().map(|name| name.to_string_lossy());
+    sniff_bytes(&data, file_name.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 0x18];
+        data.extend_from_slice(b"ftyp");
+        data.extend_from_slice(brand);
+        data.extend_from_slice(&[0; 12]);
+        data
+    }
+
+    #[test]
+    fn it_tells_iso_files_apart_by_brand() {
+        let mov = "mov,mp4,m4a,3gp,3g2,mj2";
+        assert_eq!(mime_type_for(mov, None, &ftyp(b"isom")), "video/mp4");
+        assert_eq!(mime_type_for(mov, None, &ftyp(b"mp42")), "video/mp4");
+        assert_eq!(mime_type_for(mov, None, &ftyp(b"qt  ")), "video/quicktime");
+        assert_eq!(mime_type_for(mov, None, &ftyp(b"M4A ")), "audio/mp4");
+        assert_eq!(mime_type_for(mov, None, &ftyp(b"3gp5")), "video/3gpp");
+        assert_eq!(
+            mime_type_for(mov, None, b"\0\0\0\x08wide\0\0\0\x10mdat"),
+            "video/quicktime"
+        );
+    }
+
+    #[test]
+    fn it_prefers_the_table_to_the_declared_type() {
+        assert_eq!(
+            mime_type_for("matroska,webm", Some("video/webm"), &[]),
+            "video/x-matroska"
+        );
+        assert_eq!(
+            mime_type_for("webm_dash_manifest", Some("application/xml"), &[]),
+            "application/xml"
+        );
+        assert_eq!(mime_type_for("unknown", None, &[]), UNKNOWN_MIME_TYPE);
+    }
+}
@@ -0,0 +1,160 @@
+use ffmpeg_next::ffi::av_frame_clone;
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::util::frame::{audio::Audio, video::Video};
+use ffmpeg_next::{encoder, filter, Rational};
+use media_types::MediaLibError;
+
+use super::channels::tempo_spec;
+use super::ffmpeg_error;
+use super::filter_graph::{build_graph, FilterGraph};
+use super::timestamp::Sequence;
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 4.0;
+
+// Timestamps going into the video graph, see `VideoSpeed::add`.
+const MICROSECONDS: Rational = Rational(1, 1_000_000);
+
+pub fn check_speed(speed: f64) -> Result<(), MediaLibError> {
+    if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        return Err(MediaLibError::UnknownError(
+            format!("Speed {} is outside {} to {}", speed, MIN_SPEED, MAX_SPEED).into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Retimes video with setpts and evens it back out to the same frame rate
+/// with fps, which drops frames when speeding up and repeats them when
+/// slowing down.
+pub struct VideoSpeed {
+    spec: String,
+    // Built for the first frame, since that's when we know its format.
+    graph: Option<FilterGraph>,
+}
+
+impl VideoSpeed {
+    pub fn new(speed: f64, frame_rate: Rational) -> Self {
+        VideoSpeed {
+            spec: format!("setpts=PTS/{},fps={}", speed, frame_rate),
+            graph: None,
+        }
+    }
+
+    /// Sends `frame`, shown `time_us` into the input, through the filters.
+    pub fn add(&mut self, frame: &Video, time_us: i64) -> Result<(), MediaLibError> {
+        let graph = match &mut self.graph {
+            Some(graph) => graph,
+            slot => slot.insert(FilterGraph::with_time_base(
+                frame,
+                MICROSECONDS,
+                &self.spec,
+            )?),
+        };
+        graph.add(frame, Some(time_us))
+    }
+
+    /// Ends the input, so the last frames come out of `next`.
+    pub fn flush(&mut self) -> Result<(), MediaLibError> {
+        match &mut self.graph {
+            Some(graph) => graph.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// The next frame at the new speed, none until more is added.
+    pub fn next(&mut self) -> Result<Option<Video>, MediaLibError> {
+        match &mut self.graph {
+            Some(graph) => graph.receive(),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Changes the tempo of audio with atempo, which keeps its pitch, and puts
+/// it in the format and frame size an encoder takes.
+pub struct AudioTempo {
+    graph: filter::Graph,
+    timestamps: Sequence,
+    done: bool,
+}
+
+impl AudioTempo {
+    /// For stereo float samples at the encoder's rate, as `AudioDecoder`
+    /// gives when asked for them.
+    pub fn new(speed: f64, encoder: &encoder::Audio) -> Result<Self, MediaLibError> {
+        let rate = encoder.rate();
+        let args = format!(
+            "sample_rate={}:sample_fmt=flt:channel_layout=stereo:time_base=1/{}",
+            rate, rate
+        );
+        let format = format!(
+            "aformat=sample_fmts={}:sample_rates={}:channel_layouts=stereo",
+            encoder.format().name(),
+            rate
+        );
+        let spec = match tempo_spec(speed) {
+            Some(atempo) => format!("{},{}", atempo, format),
+            None => format,
+        };
+        let mut graph = build_graph("abuffer", &args, "abuffersink", &spec)?;
+        // Encoders such as AAC take a fixed number of samples per frame,
+        // zero for the ones that don't.
+        if encoder.frame_size() > 0 {
+            if let Some(mut sink) = graph.get("out") {
+                sink.sink().set_frame_size(encoder.frame_size());
+            }
+        }
+        Ok(AudioTempo {
+            graph,
+            timestamps: Sequence::new(),
+            done: false,
+        })
+    }
+
+    /// Sends `samples` through the filters, following on from the last.
+    pub fn add(&mut self, samples: &Audio) -> Result<(), MediaLibError> {
+        let mut input = unsafe { Audio::wrap(av_frame_clone(samples.as_ptr())) };
+        input.set_pts(Some(self.timestamps.take(samples.samples() as i64)));
+        self.graph
+            .get("in")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter input missing".into()))?
+            .source()
+            .add(&input)
+            .map_err(ffmpeg_error)
+    }
+
+    /// Ends the input, so the last samples come out of `next`.
+    pub fn flush(&mut self) -> Result<(), MediaLibError> {
+        self.graph
+            .get("in")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter input missing".into()))?
+            .source()
+            .flush()
+            .map_err(ffmpeg_error)
+    }
+
+    /// The next block of samples at the new tempo, none until more is
+    /// added.
+    pub fn next(&mut self) -> Result<Option<Audio>, MediaLibError> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut samples = Audio::empty();
+        let received = self
+            .graph
+            .get("out")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter output missing".into()))?
+            .sink()
+            .frame(&mut samples);
+        match received {
+            Ok(()) => Ok(Some(samples)),
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => Ok(None),
+            Err(ffmpeg_next::Error::Eof) => {
+                self.done = true;
+                Ok(None)
+            }
+            Err(e) => Err(ffmpeg_error(e)),
+        }
+    }
+}
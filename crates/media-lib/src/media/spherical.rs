@@ -0,0 +1,205 @@
+use std::ffi::CStr;
+
+use ffmpeg_next::ffi::{
+    av_packet_side_data_get, av_spherical_projection_name, AVPacketSideDataType, AVSphericalMapping,
+};
+use ffmpeg_next::format::stream::Stream;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{MediaLibError, Size, SphericalMetadata};
+
+use super::filter_graph::FilterGraph;
+
+// Field of view of the flat view when the options leave it at zero.
+const DEFAULT_FOV_DEGREES: u32 = 90;
+
+/// The spherical video metadata of `stream`, none for ordinary video.
+pub fn spherical_metadata(stream: &Stream) -> Option<SphericalMetadata> {
+    let mapping = unsafe {
+        let parameters = (*stream.as_ptr()).codecpar;
+        let side_data = av_packet_side_data_get(
+            (*parameters).coded_side_data,
+            (*parameters).nb_coded_side_data,
+            AVPacketSideDataType::AV_PKT_DATA_SPHERICAL,
+        );
+        if side_data.is_null() || (*side_data).size < std::mem::size_of::<AVSphericalMapping>() {
+            return None;
+        }
+        &*((*side_data).data as *const AVSphericalMapping)
+    };
+    let projection = unsafe {
+        let name = av_spherical_projection_name(mapping.projection);
+        if name.is_null() {
+            "unknown".into()
+        } else {
+            CStr::from_ptr(name).to_string_lossy()
+        }
+    };
+    // The angles are 16.16 fixed point.
+    Some(SphericalMetadata {
+        projection: projection.as_ref().into(),
+        yaw: mapping.yaw as f64 / 65536.0,
+        pitch: mapping.pitch as f64 / 65536.0,
+        roll: mapping.roll as f64 / 65536.0,
+    })
+}
+
+/// The flat view of an equirectangular frame of `size`: 16:9 at the
+/// frame's height, which keeps about as much detail as the panorama has
+/// across the view.
+fn flat_view_size(size: Size) -> Size {
+    let height = size.height & !1;
+    Size::new((height * 16 / 9) & !1, height)
+}
+
+/// Turns frames of equirectangular 360° video into a flat view facing the
+/// way the metadata says, see `flatten_360`.
+pub struct Reprojector {
+    spec: String,
+    output_size: Size,
+    // Built for the first frame, and again if frames change format or size.
+    graph: Option<FilterGraph>,
+    // Set once the graph can't be built, frames are passed through as they
+    // are from then on.
+    failed: bool,
+}
+
+impl Reprojector {
+    /// None unless `metadata` is of equirectangular video. A zero
+    /// `fov_degrees` is 90 degrees.
+    pub fn new(metadata: &SphericalMetadata, fov_degrees: u32, input_size: Size) -> Option<Self> {
+        if !metadata.is_equirectangular() {
+            return None;
+        }
+        let h_fov = match fov_degrees {
+            0 => DEFAULT_FOV_DEGREES,
+            fov => fov.min(179),
+        } as f64;
+        let v_fov = 2.0
+            * ((h_fov / 2.0).to_radians().tan() * 9.0 / 16.0)
+                .atan()
+                .to_degrees();
+        let output_size = flat_view_size(input_size);
+        let spec = format!(
+            "v360=input=e:output=flat:h_fov={:.3}:v_fov={:.3}:yaw={:.3}:pitch={:.3}:roll={:.3}:w={}:h={}",
+            h_fov,
+            v_fov,
+            metadata.yaw,
+            metadata.pitch,
+            metadata.roll,
+            output_size.width,
+            output_size.height
+        );
+        Some(Reprojector {
+            spec,
+            output_size,
+            graph: None,
+            failed: false,
+        })
+    }
+
+    /// Size of the frames `apply` returns.
+    pub fn output_size(&self) -> Size {
+        self.output_size
+    }
+
+    fn reproject(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let graph = match self.graph.take() {
+            Some(graph) if graph.accepts(frame) => self.graph.insert(graph),
+            _ => self.graph.insert(FilterGraph::new(frame, &self.spec)?),
+        };
+        graph.run(frame)
+    }
+
+    /// `frame` as a flat view, none when it can't be reprojected. Failing
+    /// is logged once rather than failing the decode.
+    pub fn apply(&mut self, frame: &Video) -> Option<Video> {
+        if self.failed {
+            return None;
+        }
+        match self.reproject(frame) {
+            Ok(flat) => Some(flat),
+            Err(e) => {
+                log::warn!(
+                    "Failed to flatten 360° frames, leaving them as they are: {}",
+                    e
+                );
+                self.failed = true;
+                self.graph = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg_next::filter;
+    use ffmpeg_next::format::Pixel;
+
+    use super::*;
+
+    // A panorama that's bright across the 90° of longitude in front of the
+    // camera and dark everywhere else.
+    fn panorama() -> Video {
+        let (width, height) = (256, 128);
+        let mut frame = Video::new(Pixel::YUV420P, width, height);
+        let stride = frame.stride(0);
+        for row in frame.data_mut(0).chunks_exact_mut(stride) {
+            for (x, luma) in row[..width as usize].iter_mut().enumerate() {
+                let in_front = (width as usize * 3 / 8..width as usize * 5 / 8).contains(&x);
+                *luma = if in_front { 235 } else { 16 };
+            }
+        }
+        frame.data_mut(1).fill(128);
+        frame.data_mut(2).fill(128);
+        frame
+    }
+
+    fn mean_luma(frame: &Video) -> f64 {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let stride = frame.stride(0);
+        let sum: u64 = (0..height)
+            .flat_map(|y| &frame.data(0)[y * stride..y * stride + width])
+            .map(|&luma| luma as u64)
+            .sum();
+        sum as f64 / (width * height) as f64
+    }
+
+    fn metadata(yaw: f64) -> SphericalMetadata {
+        SphericalMetadata {
+            projection: "equirectangular".into(),
+            yaw,
+            pitch: 0.0,
+            roll: 0.0,
+        }
+    }
+
+    #[test]
+    fn it_flattens_the_view_the_metadata_faces() {
+        // Reprojecting needs FFmpeg 4.3 or later.
+        if filter::find("v360").is_none() {
+            return;
+        }
+        let input = panorama();
+        let input_size = Size::new(input.width(), input.height());
+
+        let mut facing_front = Reprojector::new(&metadata(0.0), 60, input_size).unwrap();
+        assert_eq!(facing_front.output_size(), Size::new(226, 128));
+        let flat = facing_front.apply(&input).unwrap();
+        assert_eq!((flat.width(), flat.height()), (226, 128));
+        assert!(mean_luma(&flat) > 200.0, "{}", mean_luma(&flat));
+
+        let mut facing_back = Reprojector::new(&metadata(180.0), 60, input_size).unwrap();
+        let flat = facing_back.apply(&input).unwrap();
+        assert!(mean_luma(&flat) < 50.0, "{}", mean_luma(&flat));
+    }
+
+    #[test]
+    fn it_leaves_other_projections_alone() {
+        let cubemap = SphericalMetadata {
+            projection: "cubemap".into(),
+            ..metadata(0.0)
+        };
+        assert!(Reprojector::new(&cubemap, 0, Size::new(256, 128)).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use ffmpeg_next::color::Primaries;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::MediaLibError;
+
+use super::filter_graph::FilterGraph;
+
+/// Primaries wider than sRGB's, which look washed out when shown as if
+/// they were BT.709.
+pub fn is_wide_gamut(primaries: Primaries) -> bool {
+    matches!(
+        primaries,
+        Primaries::BT2020 | Primaries::SMPTE431 | Primaries::SMPTE432
+    )
+}
+
+/// Filter specs converting to sRGB, best first. zscale is only in builds
+/// with zimg, colorspace is always there but can't handle every transfer.
+const CONVERSIONS: &[&str] = &[
+    "zscale=primaries=bt709:transfer=iec61966-2-1:matrix=bt709:range=full",
+    "colorspace=all=bt709:trc=srgb:range=pc",
+];
+
+/// Converts frames tagged as wide gamut (BT.2020, DCI or Display P3) to
+/// sRGB so thumbnails look the way players show them. Other frames pass
+/// through untouched.
+#[derive(Default)]
+pub struct SrgbConverter {
+    // With the primaries it was built for, it's rebuilt when they or the
+    // format or size change.
+    graph: Option<(Primaries, FilterGraph)>,
+    // Set once none of the conversions work for the input, frames are
+    // passed through as they are from then on.
+    failed: bool,
+}
+
+impl SrgbConverter {
+    fn graph_for(&mut self, frame: &Video) -> Result<&mut FilterGraph, MediaLibError> {
+        let primaries = frame.color_primaries();
+        match &mut self.graph {
+            Some((built_for, graph)) if *built_for == primaries && graph.accepts(frame) => {
+                Ok(graph)
+            }
+            slot => {
+                let mut error = MediaLibError::FFmpegError("No conversion to sRGB".into());
+                for spec in CONVERSIONS {
+                    match FilterGraph::new(frame, spec) {
+                        Ok(graph) => return Ok(&mut slot.insert((primaries, graph)).1),
+                        Err(e) => error = e,
+                    }
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn convert(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        self.graph_for(frame)?.run(frame)
+    }
+
+    /// `frame` converted to sRGB, none when it isn't wide gamut or can't be
+    /// converted. Failing to convert is logged once rather than failing the
+    /// decode, the frame is still worth having with its colours off.
+    pub fn apply(&mut self, frame: &Video) -> Option<Video> {
+        if self.failed || !is_wide_gamut(frame.color_primaries()) {
+            return None;
+        }
+        match self.convert(frame) {
+            Ok(converted) => Some(converted),
+            Err(e) => {
+                log::warn!(
+                    "Failed to convert to sRGB, leaving colours as they are: {}",
+                    e
+                );
+                self.failed = true;
+                self.graph = None;
+                None
+            }
+        }
+    }
+}
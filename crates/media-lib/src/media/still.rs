@@ -0,0 +1,629 @@
+use ffmpeg_next::ffi::{av_display_rotation_get, av_packet_side_data_get, AVPacketSideDataType};
+use ffmpeg_next::format::stream::Stream;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::side_data;
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{MediaLibError, Size};
+
+use super::ffmpeg_error;
+use super::filter_graph::FilterGraph;
+
+// Colorants of sRGB as ICC profiles give them, adapted to the D50 white of
+// the profile connection space. Columns are red, green and blue.
+const SRGB_COLORANTS: [[f32; 3]; 3] = [
+    [0.436_074_7, 0.385_064_9, 0.143_080_4],
+    [0.222_504_5, 0.716_878_6, 0.060_616_9],
+    [0.013_932_2, 0.097_104_5, 0.714_173_3],
+];
+// Profiles this close to sRGB are left alone, rounding would only add noise.
+const SRGB_TOLERANCE: f32 = 0.002;
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn s15_fixed16(data: &[u8], offset: usize) -> Option<f32> {
+    Some(be_u32(data, offset)? as i32 as f32 / 65536.0)
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn invert(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let determinant = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    Some(inverse.map(|row| row.map(|value| value / determinant)))
+}
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut product = [[0.0; 3]; 3];
+    for (row, product_row) in product.iter_mut().enumerate() {
+        for (column, value) in product_row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    product
+}
+
+/// The data of the tag with this signature in an ICC profile.
+fn find_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    // Only the entries that fit in the profile are read, whatever a corrupt
+    // count says.
+    let count = (be_u32(profile, 128)? as usize).min(profile.len().saturating_sub(132) / 12);
+    (0..count).find_map(|index| {
+        let entry = 132 + index * 12;
+        if profile.get(entry..entry + 4)? != signature {
+            return None;
+        }
+        let offset = be_u32(profile, entry + 4)? as usize;
+        let size = be_u32(profile, entry + 8)? as usize;
+        profile.get(offset..offset.checked_add(size)?)
+    })
+}
+
+/// An ICC profile of the matrix/TRC kind (sRGB, Display P3, Adobe RGB and
+/// most camera and phone profiles), as lookups to linear light for each
+/// 8 bit value and the matrix from its primaries to sRGB's.
+struct IccTransform {
+    curves: [[f32; 256]; 3],
+    matrix: [[f32; 3]; 3],
+}
+
+impl IccTransform {
+    /// None for profiles that aren't matrix/TRC RGB, or are sRGB already.
+    fn parse(profile: &[u8]) -> Option<Self> {
+        if profile.get(16..20)? != b"RGB " || profile.get(20..24)? != b"XYZ " {
+            return None;
+        }
+        let mut colorants = [[0.0; 3]; 3];
+        for (column, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let data = find_tag(profile, signature)?;
+            if data.get(0..4)? != b"XYZ " {
+                return None;
+            }
+            for (row, colorant) in colorants.iter_mut().enumerate() {
+                colorant[column] = s15_fixed16(data, 8 + row * 4)?;
+            }
+        }
+        let mut curves = [[0.0; 256]; 3];
+        for (curve, signature) in curves.iter_mut().zip([b"rTRC", b"gTRC", b"bTRC"]) {
+            *curve = Self::curve(find_tag(profile, signature)?)?;
+        }
+
+        let matrix = multiply(&invert(&SRGB_COLORANTS)?, &colorants);
+        let is_srgb = matrix.iter().enumerate().all(|(row, values)| {
+            values.iter().enumerate().all(|(column, value)| {
+                let expected = if row == column { 1.0 } else { 0.0 };
+                (value - expected).abs() < SRGB_TOLERANCE
+            })
+        }) && curves.iter().all(|curve| {
+            curve.iter().enumerate().all(|(index, value)| {
+                (value - srgb_to_linear(index as f32 / 255.0)).abs() < SRGB_TOLERANCE
+            })
+        });
+        (!is_srgb).then_some(IccTransform { curves, matrix })
+    }
+
+    /// Linear light for each 8 bit value from a `curv` or `para` tag.
+    fn curve(data: &[u8]) -> Option<[f32; 256]> {
+        let function: Box<dyn Fn(f32) -> f32> = match data.get(0..4)? {
+            b"curv" => {
+                let count = be_u32(data, 8)? as usize;
+                match count {
+                    0 => Box::new(|x: f32| x),
+                    1 => {
+                        let gamma = be_u16(data, 12)? as f32 / 256.0;
+                        Box::new(move |x: f32| x.powf(gamma))
+                    }
+                    _ => {
+                        let table: Vec<f32> = (0..count)
+                            .map(|index| Some(be_u16(data, 12 + index * 2)? as f32 / 65535.0))
+                            .collect::<Option<_>>()?;
+                        Box::new(move |x: f32| {
+                            let position = x * (table.len() - 1) as f32;
+                            let index = (position as usize).min(table.len() - 2);
+                            let fraction = position - index as f32;
+                            table[index] + (table[index + 1] - table[index]) * fraction
+                        })
+                    }
+                }
+            }
+            b"para" => {
+                let kind = be_u16(data, 8)?;
+                let parameter_count = match kind {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => return None,
+                };
+                let mut p = [0.0f32; 7];
+                for (index, value) in p.iter_mut().take(parameter_count).enumerate() {
+                    *value = s15_fixed16(data, 12 + index * 4)?;
+                }
+                let [g, a, b, c, d, e, f] = p;
+                Box::new(move |x: f32| match kind {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    _ if x >= d => (a * x + b).powf(g) + e,
+                    _ => c * x + f,
+                })
+            }
+            _ => return None,
+        };
+
+        let mut curve = [0.0; 256];
+        for (index, value) in curve.iter_mut().enumerate() {
+            *value = function(index as f32 / 255.0);
+        }
+        Some(curve)
+    }
+
+    fn apply(&self, pixel: &mut [u8]) {
+        let linear = [
+            self.curves[0][pixel[0] as usize],
+            self.curves[1][pixel[1] as usize],
+            self.curves[2][pixel[2] as usize],
+        ];
+        for (out, row) in pixel.iter_mut().zip(&self.matrix) {
+            let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            *out = (linear_to_srgb(value) * 255.0).round() as u8;
+        }
+    }
+}
+
+/// The EXIF orientation that shows a frame the way its display matrix says,
+/// read the way FFmpeg's own autorotation does. Only quarter turns and flips
+/// are recognised, anything else is shown as decoded.
+fn display_matrix_orientation(data: &[u8]) -> u8 {
+    if data.len() < 36 {
+        return 1;
+    }
+    let mut matrix = [0i32; 9];
+    for (value, bytes) in matrix.iter_mut().zip(data.chunks_exact(4)) {
+        *value = i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    // Counterclockwise rotation of the matrix, as a clockwise turn to apply.
+    let rotation = -unsafe { av_display_rotation_get(matrix.as_ptr()) };
+    let rotation = (rotation.round() as i64).rem_euclid(360);
+    match rotation {
+        90 if matrix[3] > 0 => 5,
+        90 => 6,
+        180 => match (matrix[0] < 0, matrix[4] < 0) {
+            (true, true) => 3,
+            (true, false) => 2,
+            (false, true) => 4,
+            (false, false) => 1,
+        },
+        270 if matrix[3] < 0 => 7,
+        270 => 8,
+        // Mirrored but not turned.
+        0 if matrix[0] < 0 => 2,
+        0 if matrix[4] < 0 => 4,
+        _ => 1,
+    }
+}
+
+/// The EXIF orientation that shows `stream` upright, from the display
+/// matrix phones store for video recorded on its side. 1 when it has none.
+pub fn stream_orientation(stream: &Stream) -> u8 {
+    unsafe {
+        let parameters = (*stream.as_ptr()).codecpar;
+        let side_data = av_packet_side_data_get(
+            (*parameters).coded_side_data,
+            (*parameters).nb_coded_side_data,
+            AVPacketSideDataType::AV_PKT_DATA_DISPLAYMATRIX,
+        );
+        if side_data.is_null() {
+            return 1;
+        }
+        display_matrix_orientation(std::slice::from_raw_parts(
+            (*side_data).data,
+            (*side_data).size,
+        ))
+    }
+}
+
+/// The filters turning a picture with this EXIF orientation upright, the
+/// ones FFmpeg's own autorotation uses. None when it's upright already.
+pub fn orientation_filters(orientation: u8) -> Option<&'static str> {
+    match orientation {
+        2 => Some("hflip"),
+        3 => Some("hflip,vflip"),
+        4 => Some("vflip"),
+        5 => Some("transpose=cclock_flip"),
+        6 => Some("transpose=clock"),
+        7 => Some("transpose=clock_flip"),
+        8 => Some("transpose=cclock"),
+        _ => None,
+    }
+}
+
+/// The size of a `size` picture with this EXIF orientation once it's
+/// upright, quarter turns trade width and height.
+pub fn upright_size(size: Size, orientation: u8) -> Size {
+    match orientation {
+        5..=8 => Size::new(size.height, size.width),
+        _ => size,
+    }
+}
+
+/// How a picture has to be turned and recoloured to look the way image
+/// viewers and players show it: its EXIF orientation or display matrix and
+/// embedded ICC profile.
+pub struct PictureCorrection {
+    // EXIF orientation, 1 to 8.
+    orientation: u8,
+    transform: Option<IccTransform>,
+}
+
+impl PictureCorrection {
+    /// None when the frame is shown as decoded, which is the case for
+    /// anything but photos and videos recorded on their side. Without
+    /// `rotate` only the colours are corrected.
+    pub fn for_frame(frame: &Video, rotate: bool) -> Option<Self> {
+        // FFmpeg's image decoders copy EXIF tags into the frame metadata,
+        // video decoders attach the stream's display matrix.
+        let orientation = frame
+            .metadata()
+            .get("Orientation")
+            .and_then(|value| value.trim().parse().ok())
+            .filter(|orientation| (1..=8).contains(orientation))
+            .or_else(|| {
+                frame
+                    .side_data(side_data::Type::DisplayMatrix)
+                    .map(|matrix| display_matrix_orientation(matrix.data()))
+            })
+            .filter(|_| rotate)
+            .unwrap_or(1);
+        let transform = frame
+            .side_data(side_data::Type::IccProfile)
+            .and_then(|profile| IccTransform::parse(profile.data()));
+        if orientation == 1 && transform.is_none() {
+            return None;
+        }
+        Some(PictureCorrection {
+            orientation,
+            transform,
+        })
+    }
+
+    /// EXIF orientation, see `orientation_filters`.
+    pub fn orientation(&self) -> u8 {
+        self.orientation
+    }
+
+    /// Whether width and height trade places.
+    pub fn is_transposed(&self) -> bool {
+        self.orientation >= 5
+    }
+
+    /// `frame` upright and in sRGB, as RGB24. `frame` can be a converted
+    /// copy of the one the correction was made for.
+    pub fn apply(&self, frame: &Video) -> Result<Video, MediaLibError> {
+        let rgb = self.to_srgb(frame)?;
+        match orientation_filters(self.orientation) {
+            Some(filters) => FilterGraph::new(&rgb, filters)?.run(&rgb),
+            None => Ok(rgb),
+        }
+    }
+
+    /// `frame` in sRGB as RGB24 when its ICC profile says it's in another
+    /// colour space, none when it isn't. Turning it upright is left to the
+    /// caller's filters, see `orientation_filters`.
+    pub fn correct_colors(&self, frame: &Video) -> Result<Option<Video>, MediaLibError> {
+        match self.transform {
+            Some(_) => self.to_srgb(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn to_srgb(&self, frame: &Video) -> Result<Video, MediaLibError> {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let mut rgb = Video::empty();
+        Context::get(
+            frame.format(),
+            frame.width(),
+            frame.height(),
+            Pixel::RGB24,
+            frame.width(),
+            frame.height(),
+            Flags::BICUBIC | Flags::ACCURATE_RND | Flags::FULL_CHR_H_INT,
+        )
+        .and_then(|mut scaler| scaler.run(frame, &mut rgb))
+        .map_err(ffmpeg_error)?;
+
+        if let Some(transform) = &self.transform {
+            let stride = rgb.stride(0);
+            for row in rgb.data_mut(0).chunks_mut(stride).take(height) {
+                for pixel in row[..width * 3].chunks_exact_mut(3) {
+                    transform.apply(pixel);
+                }
+            }
+        }
+        Ok(rgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg_next::ffi::{av_display_matrix_flip, av_display_rotation_set};
+
+    use super::*;
+
+    // Display P3's colorants adapted to D50, as in Apple's profile.
+    const DISPLAY_P3_COLORANTS: [[f32; 3]; 3] = [
+        [0.515_121, 0.291_977, 0.157_104],
+        [0.241_182, 0.692_245, 0.066_574],
+        [-0.001_053, 0.041_885, 0.784_073],
+    ];
+
+    /// A matrix/TRC profile with these colorants, columns red, green and
+    /// blue, and sRGB's curve as a parametric one.
+    fn icc_profile(colorants: [[f32; 3]; 3]) -> Vec<u8> {
+        let mut tags: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+        for (column, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let mut data = b"XYZ \0\0\0\0".to_vec();
+            for row in colorants {
+                data.extend(((row[column] * 65536.0).round() as i32).to_be_bytes());
+            }
+            tags.push((signature, data));
+        }
+        let mut curve = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for value in [2.4f32, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+            curve.extend(((value * 65536.0).round() as i32).to_be_bytes());
+        }
+        for signature in [b"rTRC", b"gTRC", b"bTRC"] {
+            tags.push((signature, curve.clone()));
+        }
+
+        let mut profile = vec![0; 128];
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        let mut data: Vec<u8> = Vec::new();
+        for (signature, tag) in &tags {
+            profile.extend(*signature);
+            profile.extend((offset as u32).to_be_bytes());
+            profile.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+            data.extend(tag);
+        }
+        profile.extend(data);
+        profile
+    }
+
+    fn convert(transform: &IccTransform, mut pixel: [u8; 3]) -> [u8; 3] {
+        transform.apply(&mut pixel);
+        pixel
+    }
+
+    #[test]
+    fn it_converts_display_p3_to_srgb() {
+        let transform = IccTransform::parse(&icc_profile(DISPLAY_P3_COLORANTS)).unwrap();
+        // Both share the D65 white, so greys stay as they are.
+        for grey in [0, 128, 255] {
+            assert_eq!(convert(&transform, [grey; 3]), [grey; 3]);
+        }
+        // P3 is the wider gamut, the same values are more saturated in sRGB.
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1);
+        let red = convert(&transform, [200, 100, 100]);
+        assert!(close(red, [215, 93, 97]), "{:?}", red);
+        let green = convert(&transform, [100, 200, 100]);
+        assert!(close(green, [45, 203, 86]), "{:?}", green);
+    }
+
+    #[test]
+    fn it_leaves_srgb_and_other_profiles_alone() {
+        assert!(IccTransform::parse(&icc_profile(SRGB_COLORANTS)).is_none());
+        let mut grey = icc_profile(DISPLAY_P3_COLORANTS);
+        grey[16..20].copy_from_slice(b"GRAY");
+        assert!(IccTransform::parse(&grey).is_none());
+    }
+
+    #[test]
+    fn it_rejects_truncated_profiles() {
+        let profile = icc_profile(DISPLAY_P3_COLORANTS);
+        for length in [0, 20, 130, 132, 132 + 6 * 12, profile.len() - 1] {
+            assert!(
+                IccTransform::parse(&profile[..length]).is_none(),
+                "{} bytes",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn it_only_reads_tag_entries_inside_the_profile() {
+        // A corrupt count mustn't send the lookup through four billion
+        // entries.
+        let mut profile = icc_profile(DISPLAY_P3_COLORANTS);
+        profile[128..132].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(IccTransform::parse(&profile).is_some());
+        assert!(find_tag(&profile, b"wtpt").is_none());
+        assert!(find_tag(&profile[..132], b"rXYZ").is_none());
+    }
+
+    #[test]
+    fn it_reads_the_exif_orientation_from_frame_metadata() {
+        let frame_with = |orientation: &str, matrix: Option<Vec<u8>>| {
+            let mut frame = Video::new(Pixel::RGB24, 4, 2);
+            let key = std::ffi::CString::new("Orientation").unwrap();
+            let orientation = std::ffi::CString::new(orientation).unwrap();
+            unsafe {
+                ffmpeg_next::ffi::av_dict_set(
+                    &mut (*frame.as_mut_ptr()).metadata,
+                    key.as_ptr(),
+                    orientation.as_ptr(),
+                    0,
+                );
+                if let Some(matrix) = matrix {
+                    let side_data = ffmpeg_next::ffi::av_frame_new_side_data(
+                        frame.as_mut_ptr(),
+                        ffmpeg_next::ffi::AVFrameSideDataType::AV_FRAME_DATA_DISPLAYMATRIX,
+                        matrix.len(),
+                    );
+                    std::slice::from_raw_parts_mut((*side_data).data, matrix.len())
+                        .copy_from_slice(&matrix);
+                }
+            }
+            frame
+        };
+
+        let correction = PictureCorrection::for_frame(&frame_with("6", None), true).unwrap();
+        assert_eq!(correction.orientation(), 6);
+        assert!(correction.is_transposed());
+        let upright = correction.apply(&frame_with("6", None)).unwrap();
+        assert_eq!((upright.width(), upright.height()), (2, 4));
+
+        // Photos only carry EXIF, it takes precedence over a display matrix.
+        let frame = frame_with(" 3 ", Some(display_matrix(90.0, false)));
+        let correction = PictureCorrection::for_frame(&frame, true).unwrap();
+        assert_eq!(correction.orientation(), 3);
+        // Out of range values fall back to the display matrix.
+        let frame = frame_with("9", Some(display_matrix(90.0, false)));
+        let correction = PictureCorrection::for_frame(&frame, true).unwrap();
+        assert_eq!(correction.orientation(), 6);
+
+        assert!(PictureCorrection::for_frame(&frame_with("1", None), true).is_none());
+        assert!(PictureCorrection::for_frame(&frame_with("9", None), true).is_none());
+        assert!(PictureCorrection::for_frame(&frame_with("6", None), false).is_none());
+    }
+
+    /// A display matrix turning the picture `degrees` clockwise, mirrored
+    /// left to right first when asked.
+    fn display_matrix(degrees: f64, mirrored: bool) -> Vec<u8> {
+        let mut matrix = [0i32; 9];
+        unsafe {
+            av_display_rotation_set(matrix.as_mut_ptr(), degrees);
+            av_display_matrix_flip(matrix.as_mut_ptr(), mirrored as i32, 0);
+        }
+        matrix
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn it_reads_quarter_turns_from_display_matrices() {
+        assert_eq!(display_matrix_orientation(&display_matrix(0.0, false)), 1);
+        assert_eq!(display_matrix_orientation(&display_matrix(90.0, false)), 6);
+        assert_eq!(display_matrix_orientation(&display_matrix(180.0, false)), 3);
+        assert_eq!(display_matrix_orientation(&display_matrix(270.0, false)), 8);
+    }
+
+    #[test]
+    fn it_reads_mirrored_display_matrices() {
+        assert_eq!(display_matrix_orientation(&display_matrix(0.0, true)), 2);
+        assert_eq!(display_matrix_orientation(&display_matrix(90.0, true)), 5);
+        assert_eq!(display_matrix_orientation(&display_matrix(180.0, true)), 4);
+        assert_eq!(display_matrix_orientation(&display_matrix(270.0, true)), 7);
+        assert_eq!(display_matrix_orientation(&[0; 8]), 1);
+    }
+
+    #[test]
+    fn it_turns_every_orientation_upright() {
+        let (width, height) = (3, 2);
+        let mut frame = Video::new(Pixel::RGB24, width as u32, height as u32);
+        let stride = frame.stride(0);
+        for y in 0..height {
+            for x in 0..width {
+                let value = (y * width + x) as u8 * 40;
+                frame.data_mut(0)[y * stride + x * 3..][..3].copy_from_slice(&[value, 0, 0]);
+            }
+        }
+
+        for orientation in 1..=8u8 {
+            let upright = match orientation_filters(orientation) {
+                Some(filters) => FilterGraph::new(&frame, filters)
+                    .and_then(|mut graph| graph.run(&frame))
+                    .unwrap(),
+                None => frame.clone(),
+            };
+            let size = upright_size(Size::new(width as u32, height as u32), orientation);
+            assert_eq!(
+                Size::new(upright.width(), upright.height()),
+                size,
+                "orientation {}",
+                orientation
+            );
+            let (out_width, out_height) = (size.width as usize, size.height as usize);
+            for y in 0..out_height {
+                for x in 0..out_width {
+                    // Where each upright pixel is in the stored picture, as
+                    // the EXIF specification has it.
+                    let (source_x, source_y) = match orientation {
+                        2 => (width - 1 - x, y),
+                        3 => (width - 1 - x, height - 1 - y),
+                        4 => (x, height - 1 - y),
+                        5 => (y, x),
+                        6 => (y, height - 1 - x),
+                        7 => (width - 1 - y, height - 1 - x),
+                        8 => (width - 1 - y, x),
+                        _ => (x, y),
+                    };
+                    assert_eq!(
+                        upright.data(0)[y * upright.stride(0) + x * 3],
+                        (source_y * width + source_x) as u8 * 40,
+                        "orientation {} at {}x{}",
+                        orientation,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{JpegOptions, MediaFrameDecoderOptions, MediaLibError, Size, StoryboardOptions};
+
+use super::canvas::TileSheet;
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+
+// Longest side of a sprite. JPEGs top out at 65535 pixels a side, and this
+// keeps the RGB sheet under 200MB while it's filled in.
+const MAX_SPRITE_SIDE: u32 = 8192;
+
+/// `00:01:02.345`, as WebVTT times are written.
+fn vtt_time(time_us: i64) -> String {
+    let ms = time_us.max(0) / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// The interval between tiles and how many there are, for tiles of
+/// `tile_size` in rows of `columns` across `duration_us`. Long inputs get
+/// tiles further apart than `interval_us`, so the sprite stays within
+/// `MAX_SPRITE_SIDE`.
+fn tile_layout(
+    tile_size: Size,
+    columns: u32,
+    duration_us: i64,
+    interval_us: i64,
+) -> Result<(i64, u32), MediaLibError> {
+    let max_columns = MAX_SPRITE_SIDE / tile_size.width.max(1);
+    let max_rows = MAX_SPRITE_SIDE / tile_size.height.max(1);
+    if columns > max_columns || max_rows == 0 {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "A row of {} {}x{} tiles is more than {} pixels",
+                columns, tile_size.width, tile_size.height, MAX_SPRITE_SIDE
+            )
+            .into(),
+        ));
+    }
+
+    let duration_us = duration_us.max(1) as u64;
+    let max_tiles = (max_rows as u64 * columns as u64).min(u32::MAX as u64);
+    let interval_us = (interval_us as u64).max(duration_us.div_ceil(max_tiles));
+    let tile_count = duration_us.div_ceil(interval_us) as u32;
+    Ok((interval_us as i64, tile_count))
+}
+
+/// A frame every `interval_ms` of an input tiled in rows into one sprite,
+/// filled in with frames in the order they're shown, with the WebVTT
+/// cues players use to show the right tile over their seekbar.
+pub struct Storyboard {
+    sprite: TileSheet,
+    interval_us: i64,
+    duration_us: i64,
+    tile_count: u32,
+    next_tile: u32,
+}
+
+impl Storyboard {
+    pub fn new(
+        input_size: Size,
+        duration_us: i64,
+        interval_ms: u32,
+        columns: u32,
+        tile_width: u32,
+    ) -> Result<Self, MediaLibError> {
+        if interval_ms == 0 || columns == 0 || tile_width == 0 {
+            return Err(MediaLibError::UnknownError(
+                "A storyboard needs an interval, columns and a tile width".into(),
+            ));
+        }
+
+        let tile_size = input_size.scale_to_width(tile_width);
+        let (interval_us, tile_count) =
+            tile_layout(tile_size, columns, duration_us, interval_ms as i64 * 1000)?;
+        Ok(Storyboard {
+            // Short inputs get a single row only as wide as it needs to be.
+            sprite: TileSheet::new(tile_size, columns, tile_count),
+            interval_us,
+            duration_us,
+            tile_count,
+            next_tile: 0,
+        })
+    }
+
+    /// When the next tile to fill starts, none once they all are.
+    pub fn next_tile_us(&self) -> Option<i64> {
+        (self.next_tile < self.tile_count).then_some(self.next_tile as i64 * self.interval_us)
+    }
+
+    fn is_due(&self, elapsed_us: i64) -> bool {
+        self.next_tile < self.tile_count && elapsed_us >= self.next_tile as i64 * self.interval_us
+    }
+
+    /// Takes `frame`, shown `elapsed_us` into the input, for any tiles
+    /// starting since the last one.
+    pub fn add(&mut self, frame: &Video, elapsed_us: i64) -> Result<(), MediaLibError> {
+        if !self.is_due(elapsed_us) {
+            return Ok(());
+        }
+        let tile = self.sprite.scale(frame)?;
+        while self.is_due(elapsed_us) {
+            self.sprite.place(&tile, self.next_tile);
+            self.next_tile += 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.next_tile == self.tile_count
+    }
+
+    pub fn size(&self) -> Size {
+        self.sprite.size()
+    }
+
+    /// The sprite as a JPEG.
+    pub fn encode(&self, jpeg: &JpegOptions) -> Result<Vec<u8>, MediaLibError> {
+        self.sprite.encode(jpeg)
+    }
+
+    /// A WebVTT cue for each tile, giving its region of the sprite at
+    /// `sprite_url` as a media fragment.
+    pub fn vtt(&self, sprite_url: &str) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for index in 0..self.tile_count {
+            let start_us = index as i64 * self.interval_us;
+            let end_us = (start_us + self.interval_us).min(self.duration_us.max(start_us));
+            let rect = self.sprite.tile_rect(index);
+            let _ = write!(
+                vtt,
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                vtt_time(start_us),
+                vtt_time(end_us),
+                sprite_url,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height
+            );
+        }
+        vtt
+    }
+}
+
+/// A JPEG sprite sheet of a frame every `options.interval_ms` and the
+/// WebVTT cues locating each in it, for seekbar previews. Rather than
+/// decoding the whole input, it seeks to each tile's time and decodes just
+/// the frame shown then.
+pub fn storyboard(
+    input_path: &Path,
+    options: &StoryboardOptions,
+) -> Result<(Vec<u8>, Size, String), MediaLibError> {
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let duration_us = video_decoder.duration_us().ok_or_else(|| {
+        MediaLibError::FFmpegError("Input has no known duration to sample across".into())
+    })?;
+    let mut storyboard = Storyboard::new(
+        video_decoder.size(),
+        duration_us,
+        options.interval_ms,
+        options.columns,
+        options.tile_width,
+    )?;
+
+    let start_time_us = video_decoder.start_time_us();
+    while let Some(tile_us) = storyboard.next_tile_us() {
+        video_decoder.seek(tile_us)?;
+        let Some(decoded) = video_decoder.get_frame().transpose()? else {
+            break;
+        };
+        // The frame a seek lands on is the one shown at the tile's time,
+        // though it can have started a little before it.
+        let elapsed_us = video_decoder
+            .frame_time_us(&decoded)
+            .map_or(tile_us, |time_us| time_us - start_time_us);
+        storyboard.add(&decoded, elapsed_us.max(tile_us))?;
+    }
+
+    Ok((
+        storyboard.encode(&JpegOptions::default())?,
+        storyboard.size(),
+        storyboard.vtt(options.sprite_url.as_str()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_writes_vtt_times() {
+        assert_eq!(vtt_time(0), "00:00:00.000");
+        assert_eq!(vtt_time(62_345_000), "00:01:02.345");
+        assert_eq!(vtt_time(3_723_004_999), "01:02:03.004");
+        assert_eq!(vtt_time(-5), "00:00:00.000");
+    }
+
+    #[test]
+    fn it_cues_each_tile_until_the_end_of_the_input() {
+        let storyboard = Storyboard::new(Size::new(320, 180), 12_000_000, 5000, 2, 160).unwrap();
+        assert_eq!(storyboard.size(), Size::new(320, 180));
+        assert_eq!(
+            storyboard.vtt("sprite.jpg"),
+            "WEBVTT\n\
+             \n00:00:00.000 --> 00:00:05.000\nsprite.jpg#xywh=0,0,160,90\n\
+             \n00:00:05.000 --> 00:00:10.000\nsprite.jpg#xywh=160,0,160,90\n\
+             \n00:00:10.000 --> 00:00:12.000\nsprite.jpg#xywh=0,90,160,90\n"
+        );
+    }
+
+    #[test]
+    fn it_spreads_tiles_out_on_long_inputs() {
+        // A day at one tile a second would be 8640 rows, 777600 pixels tall.
+        let day_us = 86_400_000_000;
+        let (interval_us, tile_count) =
+            tile_layout(Size::new(160, 90), 10, day_us, 1_000_000).unwrap();
+        assert_eq!(tile_count, 910);
+        assert!(interval_us as u64 * tile_count as u64 >= day_us as u64);
+        assert!(tile_count.div_ceil(10) * 90 <= MAX_SPRITE_SIDE);
+
+        assert_eq!(
+            tile_layout(Size::new(160, 90), 10, 60_000_000, 5_000_000).unwrap(),
+            (5_000_000, 12)
+        );
+        assert!(tile_layout(Size::new(1000, 90), 10, 60_000_000, 5_000_000).is_err());
+    }
+}
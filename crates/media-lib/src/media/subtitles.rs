@@ -0,0 +1,207 @@
+use std::path::Path;
+
+use ffmpeg_next::codec;
+use ffmpeg_next::codec::subtitle::Rect;
+use ffmpeg_next::ffi::{avcodec_descriptor_get, AV_CODEC_PROP_TEXT_SUB, AV_NOPTS_VALUE};
+use ffmpeg_next::Subtitle;
+use media_types::{MediaLibError, SubtitleCue};
+
+use super::ffmpeg_error;
+use super::input::{open_input, stream_kind};
+use super::timestamp;
+
+// Fields of an ASS dialogue line before its text, as FFmpeg's decoders
+// write them: ReadOrder, Layer, Style, Name, MarginL, MarginR, MarginV,
+// Effect.
+const ASS_FIELDS_BEFORE_TEXT: usize = 8;
+
+/// Whether `id` is a text subtitle codec (SRT, ASS, mov_text, WebVTT...)
+/// rather than bitmaps (PGS, DVB, VobSub).
+fn is_text_codec(id: codec::Id) -> bool {
+    let descriptor = unsafe { avcodec_descriptor_get(id.into()) };
+    !descriptor.is_null() && unsafe { (*descriptor).props } & AV_CODEC_PROP_TEXT_SUB as i32 != 0
+}
+
+/// The text of an ASS dialogue line without its override tags (`{\i1}`),
+/// with ASS line breaks as `\n`.
+fn plain_text(dialogue: &str) -> String {
+    let text = dialogue
+        .splitn(ASS_FIELDS_BEFORE_TEXT + 1, ',')
+        .nth(ASS_FIELDS_BEFORE_TEXT)
+        .unwrap_or(dialogue);
+
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let mut in_tag = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if !in_tag => in_tag = true,
+            '}' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            '\\' => match chars.next() {
+                Some('N') | Some('n') => plain.push('\n'),
+                Some('h') => plain.push(' '),
+                Some(other) => {
+                    plain.push('\\');
+                    plain.push(other);
+                }
+                None => plain.push('\\'),
+            },
+            _ => plain.push(c),
+        }
+    }
+    plain.trim().to_string()
+}
+
+/// Every caption of the text subtitle stream `stream_index` (see
+/// `probe_media` for the indexes), in the order they're stored. Bitmap
+/// subtitles can't be turned into text and fail.
+pub fn extract_subtitles(
+    input_path: &Path,
+    stream_index: u32,
+) -> Result<Vec<SubtitleCue>, MediaLibError> {
+    let mut ictx = open_input(input_path, None)?;
+    let (time_base, parameters) = {
+        let stream = ictx.stream(stream_index as usize).ok_or_else(|| {
+            MediaLibError::FFmpegError(format!("Input has no stream {}", stream_index).into())
+        })?;
+        let medium = stream.parameters().medium();
+        if medium != ffmpeg_next::media::Type::Subtitle {
+            return Err(MediaLibError::FFmpegError(
+                format!(
+                    "Stream {} is {}, not subtitles",
+                    stream_index,
+                    stream_kind(medium)
+                )
+                .into(),
+            ));
+        }
+        (stream.time_base(), stream.parameters())
+    };
+    let id = parameters.id();
+    if !is_text_codec(id) {
+        return Err(MediaLibError::FFmpegError(
+            format!(
+                "Stream {} holds {} bitmap subtitles, only text can be extracted",
+                stream_index,
+                id.name()
+            )
+            .into(),
+        ));
+    }
+
+    let mut context = codec::context::Context::from_parameters(parameters).map_err(ffmpeg_error)?;
+    // Some decoders work out durations in the stream's time base.
+    unsafe { (*context.as_mut_ptr()).pkt_timebase = time_base.into() };
+    let mut decoder = context.decoder().subtitle().map_err(ffmpeg_error)?;
+
+    // A subtitle file on its own starts at zero, FFmpeg would take its
+    // first caption as the start.
+    let has_media = ictx.streams().any(|stream| {
+        matches!(
+            stream.parameters().medium(),
+            ffmpeg_next::media::Type::Video | ffmpeg_next::media::Type::Audio
+        )
+    });
+    let start_time = match unsafe { (*ictx.as_ptr()).start_time } {
+        AV_NOPTS_VALUE => 0,
+        _ if !has_media => 0,
+        start_time => start_time,
+    };
+    let mut cues = Vec::new();
+    while let Some(next) = ictx.next_packet() {
+        let (index, packet) = next?;
+        if index != stream_index as usize {
+            continue;
+        }
+        let Some(pts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+
+        let mut subtitle = Subtitle::new();
+        match decoder.decode(&packet, &mut subtitle) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            // One broken caption shouldn't cost the rest of them.
+            Err(ffmpeg_next::Error::InvalidData) => {
+                log::warn!("Skipping undecodable subtitle packet");
+                continue;
+            }
+            Err(e) => return Err(ffmpeg_error(e)),
+        }
+
+        let packet_us = timestamp::to_us(pts, time_base) - start_time;
+        let start_us = packet_us + subtitle.start() as i64 * 1000;
+        // Decoders leave the end unset when the packet's duration says it.
+        let end_us = match subtitle.end() {
+            0 | u32::MAX => timestamp::end_us(pts, packet.duration(), time_base) - start_time,
+            end_ms => packet_us + end_ms as i64 * 1000,
+        };
+        let text: Vec<String> = subtitle
+            .rects()
+            .filter_map(|rect| match rect {
+                Rect::Ass(ass) => Some(plain_text(ass.get())),
+                Rect::Text(text) => Some(text.get().trim().to_string()),
+                _ => None,
+            })
+            .filter(|text| !text.is_empty())
+            .collect();
+        if text.is_empty() {
+            continue;
+        }
+        cues.push(SubtitleCue {
+            start_us,
+            end_us: end_us.max(start_us),
+            text: text.join("\n").as_str().into(),
+        });
+    }
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_strips_ass_tags_from_dialogue() {
+        assert_eq!(
+            plain_text("0,0,Default,,0,0,0,,{\\an8}Hello {\\i1}world{\\i0}\\NBye\\hnow"),
+            "Hello world\nBye now"
+        );
+        // Only the fields before the text are split off.
+        assert_eq!(
+            plain_text("1,0,Default,Narrator,0,0,0,,One, two, three"),
+            "One, two, three"
+        );
+        assert_eq!(
+            plain_text("2,0,Default,,0,0,0,,C:\\temp {\\b1}"),
+            "C:\\temp"
+        );
+    }
+
+    #[test]
+    fn it_reads_back_the_captions_of_an_srt_file() {
+        let path = std::env::temp_dir().join(format!("media-lib-{}.srt", std::process::id()));
+        std::fs::write(
+            &path,
+            "1\n00:00:01,000 --> 00:00:02,500\nHello <i>world</i>\n\n\
+             2\n00:00:03,000 --> 00:00:04,250\nTwo\nlines\n",
+        )
+        .unwrap();
+        let cues = extract_subtitles(&path, 0);
+        std::fs::remove_file(&path).unwrap();
+
+        let cues: Vec<_> = cues
+            .unwrap()
+            .into_iter()
+            .map(|cue| (cue.start_us, cue.end_us, cue.text.to_string()))
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                (1_000_000, 2_500_000, "Hello world".to_string()),
+                (3_000_000, 4_250_000, "Two\nlines".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::ffi::{CStr, CString};
+use std::ptr::null;
+
+use ffmpeg_next::decoder;
+use ffmpeg_next::ffi::{
+    av_buffer_unref, av_hwdevice_get_hwframe_constraints, av_hwframe_constraints_free,
+    avcodec_descriptor_get_by_name, AVBufferRef, AVPixelFormat,
+};
+use ffmpeg_next::format::Pixel;
+use media_types::{HardwareSupport, HardwareSupportQuery, MediaLibError};
+
+use super::hardware_accelerated_video_decoder::{default_backends, device_configs, open_backend};
+
+fn unknown(what: &str, name: &str) -> MediaLibError {
+    MediaLibError::UnknownError(format!("Unknown {} '{}'", what, name).into())
+}
+
+fn bit_depth(format: AVPixelFormat) -> u32 {
+    Pixel::from(format)
+        .descriptor()
+        .map(|descriptor| unsafe { (*descriptor.as_ptr()).comp[0].depth } as u32)
+        .unwrap_or(0)
+}
+
+/// The first format frames can be downloaded from `device` in with
+/// `bit_depth` bits, any format when zero. Devices that don't list their
+/// formats are taken at their word.
+unsafe fn download_format(device: *mut AVBufferRef, bit_depth_wanted: u32) -> Option<Pixel> {
+    let mut constraints = av_hwdevice_get_hwframe_constraints(device, null());
+    if constraints.is_null() || (*constraints).valid_sw_formats.is_null() {
+        av_hwframe_constraints_free(&mut constraints);
+        return (bit_depth_wanted <= 8).then_some(Pixel::NV12);
+    }
+    let mut found = None;
+    let mut format = (*constraints).valid_sw_formats;
+    while *format != AVPixelFormat::AV_PIX_FMT_NONE {
+        if bit_depth_wanted == 0 || bit_depth(*format) == bit_depth_wanted {
+            found = Some(Pixel::from(*format));
+            break;
+        }
+        format = format.add(1);
+    }
+    av_hwframe_constraints_free(&mut constraints);
+    found
+}
+
+/// Whether video described by `query` would be decoded on the GPU here and
+/// on which device, found by opening each device the decoder supports the
+/// way opening a file would. Nothing is decoded, so this is cheap enough
+/// for a scheduler to call before routing a job.
+pub fn hardware_support(query: &HardwareSupportQuery) -> Result<HardwareSupport, MediaLibError> {
+    let codec_name: &str = &query.codec;
+    let c_name =
+        CString::new(codec_name).map_err(|e| MediaLibError::UnknownError(e.to_string().into()))?;
+    let descriptor = unsafe { avcodec_descriptor_get_by_name(c_name.as_ptr()) };
+    if descriptor.is_null() {
+        return Err(unknown("codec", codec_name));
+    }
+
+    if let Some(profile) = query.profile.as_ref() {
+        let profile: &str = profile;
+        let mut known = false;
+        unsafe {
+            let mut entry = (*descriptor).profiles;
+            while !entry.is_null() && !(*entry).name.is_null() {
+                let name = CStr::from_ptr((*entry).name).to_string_lossy();
+                if name.eq_ignore_ascii_case(profile) {
+                    known = true;
+                    break;
+                }
+                entry = entry.add(1);
+            }
+        }
+        if !known {
+            return Err(unknown(&format!("{} profile", codec_name), profile));
+        }
+    }
+
+    let codec = decoder::find(unsafe { (*descriptor).id }.into()).ok_or_else(|| {
+        MediaLibError::FFmpegError(format!("No decoder available for {}", codec_name).into())
+    })?;
+    let mut support = HardwareSupport {
+        decoder: codec.name().into(),
+        device: None.into(),
+        pixel_format: None.into(),
+        utilization: stabby::vec::Vec::new(),
+    };
+
+    // The backends a decoder would try, in the same order.
+    let configs = device_configs(&codec);
+    let device_types: Vec<_> = configs.iter().map(|config| config.device_type).collect();
+    for backend in default_backends(&device_types) {
+        let Some(mut hardware) = (unsafe { open_backend(backend, codec.id(), &configs, None) })
+        else {
+            continue;
+        };
+        let format = unsafe { download_format(hardware.device, query.bit_depth) };
+        unsafe { av_buffer_unref(&mut hardware.device) };
+
+        if let Some(format) = format {
+            if let Some(decoder) = hardware.codec {
+                support.decoder = decoder.name().into();
+            }
+            support.device = Some(stabby::string::String::from(backend.name().as_str())).into();
+            support.pixel_format = format
+                .descriptor()
+                .map(|descriptor| stabby::string::String::from(descriptor.name()))
+                .into();
+            break;
+        }
+    }
+
+    // Schedulers with more than one GPU want to know which has room.
+    #[cfg(feature = "nvml")]
+    if support
+        .device
+        .as_ref()
+        .is_some_and(|device| &**device == "cuda")
+    {
+        match super::decoder_utilization() {
+            Ok(devices) => {
+                for device in devices {
+                    support.utilization.push(device);
+                }
+            }
+            Err(e) => log::debug!("No decoder utilization to report: {:?}", e),
+        }
+    }
+    Ok(support)
+}
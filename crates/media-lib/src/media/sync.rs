@@ -0,0 +1,402 @@
+use std::path::Path;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Rational;
+use media_types::{
+    FrameBundle, MediaFrameDecoderOptions, MediaLibError, Size, SyncClock, SyncOptions, SyncedFrame,
+};
+
+use super::alpha::AlphaFlattener;
+use super::canvas::{self, FrameScaler};
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::jpeg::JpegEncoder;
+use super::still;
+use super::timestamp::{frame_duration_us, frames_to_us};
+
+// Bundle interval when none of the inputs say what their frame rate is.
+const DEFAULT_INTERVAL_US: i64 = 40_000;
+
+/// Days from 1970-01-01 to the given date in the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// A `creation_time` tag as written by FFmpeg and cameras, e.g.
+/// `2024-05-01T09:30:00.000000Z` or `2024-05-01 11:30:00+0200`, in
+/// microseconds since the Unix epoch.
+fn parse_creation_time(value: &str) -> Option<i64> {
+    let (date, time) = value.trim().split_once(['T', ' '])?;
+    let mut date = date.split('-').map(|field| field.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    // Whatever follows the seconds is a zone, none means UTC.
+    let zone_at = time.find(['Z', '+', '-']).unwrap_or(time.len());
+    let (time, zone) = time.split_at(zone_at);
+    let mut time = time.split(':');
+    let hours: i64 = time.next()?.parse().ok()?;
+    let minutes: i64 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next().unwrap_or("0").parse().ok()?;
+    let zone_offset_minutes = match zone.split_at(zone.len().min(1)) {
+        ("+", offset) | ("-", offset) => {
+            // The offset is written ±HH:MM, ±HHMM or ±HH.
+            let (zone_hours, zone_minutes) = match offset.split_once(':') {
+                Some(split) => split,
+                None if offset.len() == 4 => offset.split_at(2),
+                None => (offset, "0"),
+            };
+            let offset = zone_hours.parse::<i64>().ok()? * 60 + zone_minutes.parse::<i64>().ok()?;
+            if zone.starts_with('-') {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => 0,
+    };
+
+    let minutes =
+        days_from_civil(year, month, day) * 24 * 60 + hours * 60 + minutes - zone_offset_minutes;
+    Some(minutes * 60_000_000 + (seconds * 1_000_000.0).round() as i64)
+}
+
+/// An SMPTE timecode such as `01:00:00:00`, or `01:00:00;00` for drop
+/// frame, in microseconds at `frame_rate`.
+fn parse_timecode(value: &str, frame_rate: Rational) -> Option<i64> {
+    let (numerator, denominator) = (
+        frame_rate.numerator() as i64,
+        frame_rate.denominator() as i64,
+    );
+    if numerator <= 0 || denominator <= 0 {
+        return None;
+    }
+    let fields = value
+        .trim()
+        .split([':', ';', '.'])
+        .map(|field| field.parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [hours, minutes, seconds, frames] = fields[..] else {
+        return None;
+    };
+
+    // Timecode counts whole frames a second, 30 for 29.97.
+    let nominal_rate = (numerator + denominator / 2) / denominator;
+    let mut frame_number = ((hours * 60 + minutes) * 60 + seconds) * nominal_rate + frames;
+    if value.contains([';', '.']) {
+        // Drop frame timecode skips 2 frame numbers a minute at 29.97 (4 at
+        // 59.94), except every tenth minute.
+        let dropped_per_minute = nominal_rate / 15;
+        let total_minutes = hours * 60 + minutes;
+        frame_number -= dropped_per_minute * (total_minutes - total_minutes / 10);
+    }
+    Some(frames_to_us(frame_number, frame_rate))
+}
+
+/// One input of a synchronized decode, holding on to the frame it shows
+/// now and the one decoded after it.
+struct SyncedInput {
+    video_decoder: HardwareAcceleratedVideoDecoder,
+    // Where the input starts on the shared timeline.
+    offset_us: i64,
+    // Frames with their time from the start of the input.
+    current: Option<(i64, Video)>,
+    next: Option<(i64, Video)>,
+    decoder_done: bool,
+    scaler: FrameScaler,
+    target_size: Size,
+    alpha: AlphaFlattener,
+}
+
+impl SyncedInput {
+    fn open(input_path: &Path, options: &MediaFrameDecoderOptions) -> Result<Self, MediaLibError> {
+        let mut video_decoder =
+            unsafe { HardwareAcceleratedVideoDecoder::new(input_path, options) }?;
+        video_decoder.keyframes_only = false;
+        let orientation = if options.auto_rotate {
+            video_decoder.orientation()
+        } else {
+            1
+        };
+        let (scaled_size, target_size) = options.scaling_mode.apply(
+            still::upright_size(video_decoder.size(), orientation),
+            canvas::requested_size(options),
+        );
+        Ok(SyncedInput {
+            video_decoder,
+            offset_us: 0,
+            current: None,
+            next: None,
+            decoder_done: false,
+            scaler: FrameScaler::new(Pixel::YUVJ420P, orientation, scaled_size, target_size),
+            target_size,
+            alpha: AlphaFlattener::new(options.alpha_background, options.alpha_color),
+        })
+    }
+
+    /// Where the input starts by `clock`, in microseconds on a timeline
+    /// shared by every input lined up by the same clock. None when the tag
+    /// `clock` goes by is missing or can't be read.
+    fn origin_us(&self, clock: SyncClock) -> Option<i64> {
+        match clock {
+            SyncClock::WallClock => parse_creation_time(&self.video_decoder.tag("creation_time")?),
+            SyncClock::Timecode => parse_timecode(
+                &self.video_decoder.tag("timecode")?,
+                self.video_decoder.frame_rate(),
+            ),
+            SyncClock::Start => Some(0),
+        }
+    }
+
+    fn decode_next(&mut self) -> Result<Option<(i64, Video)>, MediaLibError> {
+        while let Some(decoded) = self.video_decoder.get_frame() {
+            let decoded = decoded?;
+            if let Some(time_us) = self.video_decoder.frame_time_us(&decoded) {
+                return Ok(Some((
+                    time_us - self.video_decoder.start_time_us(),
+                    decoded,
+                )));
+            }
+        }
+        self.decoder_done = true;
+        Ok(None)
+    }
+
+    /// Moves to the frame shown at `time_us` on the shared timeline and
+    /// returns its time from the start of the input, none before the input
+    /// starts or after it ends.
+    fn advance_to(&mut self, time_us: i64) -> Result<Option<i64>, MediaLibError> {
+        let local_us = time_us - self.offset_us;
+        loop {
+            if self.next.is_none() && !self.decoder_done {
+                self.next = self.decode_next()?;
+            }
+            match &self.next {
+                Some((next_us, _)) if *next_us <= local_us => self.current = self.next.take(),
+                _ => break,
+            }
+        }
+
+        if self.has_ended(time_us) {
+            return Ok(None);
+        }
+        Ok(self.current.as_ref().map(|(current_us, _)| *current_us))
+    }
+
+    /// Whether every frame of the input is behind `time_us` on the shared
+    /// timeline. The last frame is shown for one frame duration.
+    fn has_ended(&self, time_us: i64) -> bool {
+        if !self.decoder_done || self.next.is_some() {
+            return false;
+        }
+        match &self.current {
+            Some((current_us, _)) => {
+                let frame_duration_us = frame_duration_us(self.video_decoder.frame_rate())
+                    .unwrap_or(DEFAULT_INTERVAL_US);
+                time_us - self.offset_us >= current_us + frame_duration_us
+            }
+            None => true,
+        }
+    }
+
+    fn scale_current(&mut self) -> Result<Video, MediaLibError> {
+        let Some((_, decoded)) = &self.current else {
+            return Err(MediaLibError::UnknownError("No frame to scale".into()));
+        };
+        let flattened = self.alpha.apply(decoded)?;
+        let frame = flattened.as_ref().unwrap_or(decoded);
+
+        self.scaler.run(frame)
+    }
+}
+
+/// Decodes several inputs side by side, e.g. the cameras of a multi-camera
+/// rig, and returns what each shows at the same moments. Inputs are lined
+/// up on a shared timeline starting when the earliest of them starts.
+pub struct SyncedDecoder {
+    inputs: Vec<SyncedInput>,
+    interval_us: i64,
+    next_us: i64,
+    encoder: JpegEncoder,
+}
+
+impl SyncedDecoder {
+    pub fn new(input_paths: &[&Path], options: &SyncOptions) -> Result<Self, MediaLibError> {
+        if input_paths.is_empty() {
+            return Err(MediaLibError::UnknownError(
+                "Nothing to sync, no inputs given".into(),
+            ));
+        }
+        let decoder_options = MediaFrameDecoderOptions {
+            realtime: false,
+            sample_count: 0,
+            start_time_ms: 0,
+            end_time_ms: 0,
+            ..options.decoder.clone()
+        };
+
+        let mut inputs = Vec::with_capacity(input_paths.len());
+        let mut starts_us = Vec::with_capacity(input_paths.len());
+        for (index, input_path) in input_paths.iter().enumerate() {
+            let input = SyncedInput::open(input_path, &decoder_options)?;
+            let origin_us = input.origin_us(options.clock).ok_or_else(|| {
+                MediaLibError::UnknownError(
+                    format!(
+                        "Input {} has no usable {} to sync by",
+                        index,
+                        match options.clock {
+                            SyncClock::Timecode => "timecode",
+                            _ => "creation_time",
+                        }
+                    )
+                    .into(),
+                )
+            })?;
+            let correction_us = options
+                .offsets_ms
+                .get(index)
+                .map_or(0, |&ms| ms as i64 * 1000);
+            starts_us.push(origin_us + correction_us);
+            inputs.push(input);
+        }
+        let earliest_us = starts_us.iter().copied().min().unwrap_or(0);
+        for (input, start_us) in inputs.iter_mut().zip(&starts_us) {
+            input.offset_us = start_us - earliest_us;
+        }
+
+        let interval_us = match options.interval_ms {
+            0 => inputs
+                .iter()
+                .filter_map(|input| frame_duration_us(input.video_decoder.frame_rate()))
+                .min()
+                .unwrap_or(DEFAULT_INTERVAL_US),
+            interval_ms => interval_ms as i64 * 1000,
+        };
+
+        Ok(SyncedDecoder {
+            inputs,
+            interval_us,
+            next_us: 0,
+            encoder: JpegEncoder::with_options(&options.decoder.jpeg),
+        })
+    }
+
+    /// Where each input starts on the shared timeline.
+    pub fn offsets_us(&self) -> Vec<i64> {
+        self.inputs.iter().map(|input| input.offset_us).collect()
+    }
+
+    pub fn get(&mut self) -> Option<Result<FrameBundle, MediaLibError>> {
+        let time_us = self.next_us;
+        if self.inputs.iter().all(|input| input.has_ended(time_us)) {
+            return None;
+        }
+        self.next_us += self.interval_us;
+        Some(self.bundle_at(time_us))
+    }
+
+    fn bundle_at(&mut self, time_us: i64) -> Result<FrameBundle, MediaLibError> {
+        let mut frames = stabby::vec::Vec::new();
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            let Some(timestamp_us) = input.advance_to(time_us)? else {
+                continue;
+            };
+            let jpeg = self.encoder.encode(&input.scale_current()?)?;
+            let mut jpeg_bytes = stabby::vec::Vec::with_capacity(jpeg.len());
+            for byte in jpeg {
+                jpeg_bytes.push(byte);
+            }
+            frames.push(SyncedFrame {
+                input_index: index as u32,
+                timestamp_us,
+                size: input.target_size,
+                jpeg: jpeg_bytes,
+            });
+        }
+        Ok(FrameBundle {
+            timestamp_us: time_us,
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_days_from_the_epoch() {
+        for (date, days) in [
+            ((1970, 1, 1), 0),
+            ((1969, 12, 31), -1),
+            ((2000, 3, 1), 11_017),
+            ((2024, 2, 29), 19_782),
+            ((2024, 5, 1), 19_844),
+            ((1600, 1, 1), -135_140),
+        ] {
+            let (year, month, day) = date;
+            assert_eq!(days_from_civil(year, month, day), days, "{:?}", date);
+        }
+    }
+
+    #[test]
+    fn it_parses_creation_times() {
+        // 2024-05-01T09:30:00Z
+        let utc_us = 1_714_555_800_000_000;
+        for (value, expected) in [
+            ("2024-05-01T09:30:00.000000Z", Some(utc_us)),
+            ("2024-05-01T09:30:00Z", Some(utc_us)),
+            ("2024-05-01 09:30:00", Some(utc_us)),
+            ("2024-05-01T09:30", Some(utc_us)),
+            ("2024-05-01T09:30:00.25Z", Some(utc_us + 250_000)),
+            ("2024-05-01T11:30:00+02:00", Some(utc_us)),
+            ("2024-05-01T11:30:00+0200", Some(utc_us)),
+            ("2024-05-01T11:30:00+02", Some(utc_us)),
+            ("2024-05-01T04:00:00-0530", Some(utc_us)),
+            ("2024-05-01T04:00:00-05:30", Some(utc_us)),
+            ("2024-05-01", None),
+            ("2024-05T09:30:00Z", None),
+            ("yesterday", None),
+        ] {
+            assert_eq!(parse_creation_time(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn it_parses_timecodes() {
+        for (value, frame_rate, expected) in [
+            ("00:00:01:00", Rational(25, 1), Some(1_000_000)),
+            ("01:00:00:00", Rational(25, 1), Some(3_600_000_000)),
+            ("00:00:00:12", Rational(24, 1), Some(500_000)),
+            // 30 frames at 29.97 take 1001/1000 of a second.
+            ("00:00:01:00", Rational(30000, 1001), Some(1_001_000)),
+            // Frames 0 and 1 of minute 1 are skipped, so ;02 follows 59;29.
+            (
+                "00:01:00;02",
+                Rational(30000, 1001),
+                Some(1800 * 1_001_000 / 30),
+            ),
+            // Every tenth minute skips none.
+            (
+                "00:10:00;00",
+                Rational(30000, 1001),
+                Some(17_982 * 1_001_000 / 30),
+            ),
+            (
+                "00:01:00.04",
+                Rational(60000, 1001),
+                Some(3600 * 1_001_000 / 60),
+            ),
+            ("00:00:01", Rational(25, 1), None),
+            ("00:00:aa:00", Rational(25, 1), None),
+            ("00:00:01:00", Rational(0, 1), None),
+        ] {
+            assert_eq!(parse_timecode(value, frame_rate), expected, "{}", value);
+        }
+    }
+}
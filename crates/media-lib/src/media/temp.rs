@@ -0,0 +1,226 @@
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use media_types::MediaLibError;
+
+use super::io_error;
+
+const SESSION_PREFIX: &str = "media-lib-";
+const LOCK_FILE: &str = ".lock";
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+static NEXT_NAME: AtomicU64 = AtomicU64::new(0);
+
+/// The directory this process keeps its temporary files in. It holds a lock
+/// on a file inside it for as long as it lives, which is how the next process
+/// to start can tell a crashed session's directory from a running one's.
+struct Session {
+    path: PathBuf,
+    lock: Option<File>,
+    // Clients sharing the library each start and end the session.
+    users: usize,
+}
+
+impl Session {
+    fn create(base: &Path) -> Result<Self, MediaLibError> {
+        fs::create_dir_all(base).map_err(io_error)?;
+        remove_abandoned(base);
+
+        let path = base.join(format!("{}{}", SESSION_PREFIX, std::process::id()));
+        fs::create_dir_all(&path).map_err(io_error)?;
+        let lock = File::create(path.join(LOCK_FILE)).map_err(io_error)?;
+        lock.try_lock().map_err(|e| {
+            MediaLibError::UnknownError(
+                format!("Temporary directory {} is in use: {}", path.display(), e).into(),
+            )
+        })?;
+        log::debug!("Temporary files go in {}", path.display());
+        Ok(Session {
+            path,
+            lock: Some(lock),
+            users: 1,
+        })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Windows won't remove a directory with an open file in it.
+        self.lock.take();
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            log::warn!(
+                "Failed to remove temporary directory {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Removes session directories under `base` whose process has exited without
+/// cleaning up, i.e. nobody holds their lock any more.
+fn remove_abandoned(base: &Path) {
+    let Ok(entries) = fs::read_dir(base) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_session = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(SESSION_PREFIX));
+        if !is_session {
+            continue;
+        }
+        let path = entry.path();
+        // Directories without a lock file, or ones we can't open, are left
+        // alone.
+        let abandoned = File::open(path.join(LOCK_FILE))
+            .is_ok_and(|lock| !matches!(lock.try_lock(), Err(TryLockError::WouldBlock)));
+        if abandoned {
+            log::info!("Removing abandoned temporary directory {}", path.display());
+            if let Err(e) = fs::remove_dir_all(&path) {
+                log::warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Starts the temporary file session under `base`, the system temporary
+/// directory when None. Later calls only count another user, the first
+/// location wins.
+pub fn start_temp_session(base: Option<&Path>) -> Result<(), MediaLibError> {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    match session.as_mut() {
+        Some(session) => session.users += 1,
+        None => {
+            let base = base
+                .map(Path::to_path_buf)
+                .unwrap_or_else(std::env::temp_dir);
+            *session = Some(Session::create(&base)?);
+        }
+    }
+    Ok(())
+}
+
+/// Ends one user's session, the directory and everything left in it is
+/// removed when the last one ends.
+pub fn end_temp_session() {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = session.as_mut() {
+        current.users -= 1;
+        if current.users == 0 {
+            *session = None;
+        }
+    }
+}
+
+/// A unique path in the session directory, removed along with whatever was
+/// created at it when dropped. Use it for anything an operation writes for
+/// itself: two-pass encoder stats, segments before they're joined, disk
+/// caches.
+pub struct TempPath {
+    path: PathBuf,
+}
+
+impl TempPath {
+    /// `extension` is added without a dot, some muxers pick their format
+    /// from it.
+    pub fn new(extension: &str) -> Result<Self, MediaLibError> {
+        let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+        if session.is_none() {
+            // Only when media-lib is used without initialising it.
+            *session = Some(Session::create(&std::env::temp_dir())?);
+        }
+        let directory = session.as_ref().map(|session| session.path.clone());
+        let mut path = directory.unwrap_or_else(std::env::temp_dir);
+        path.push(NEXT_NAME.fetch_add(1, Ordering::Relaxed).to_string());
+        if !extension.is_empty() {
+            path.set_extension(extension);
+        }
+        Ok(TempPath { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let result = if self.path.is_dir() {
+            fs::remove_dir_all(&self.path)
+        } else {
+            fs::remove_file(&self.path)
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_base(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!(
+            "media-lib-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT_NAME.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn it_removes_temp_files_and_directories_when_dropped() {
+        let file = TempPath::new("mp4").unwrap();
+        let directory = TempPath::new("").unwrap();
+        assert_ne!(file.path(), directory.path());
+        assert_eq!(file.path().extension().unwrap(), "mp4");
+
+        fs::write(file.path(), b"segment").unwrap();
+        fs::create_dir(directory.path()).unwrap();
+        fs::write(directory.path().join("stats.log"), b"pass 1").unwrap();
+        let (file_path, directory_path) =
+            (file.path().to_path_buf(), directory.path().to_path_buf());
+
+        drop(file);
+        drop(directory);
+        assert!(!file_path.exists());
+        assert!(!directory_path.exists());
+
+        // Nothing was ever created at it.
+        drop(TempPath::new("tmp").unwrap());
+    }
+
+    #[test]
+    fn it_only_removes_sessions_nobody_holds() {
+        let base = unique_base("abandoned");
+        let abandoned = base.join(format!("{}1", SESSION_PREFIX));
+        let running = base.join(format!("{}2", SESSION_PREFIX));
+        let unlocked = base.join(format!("{}3", SESSION_PREFIX));
+        let unrelated = base.join("other");
+        for directory in [&abandoned, &running, &unlocked, &unrelated] {
+            fs::create_dir(directory).unwrap();
+        }
+        File::create(abandoned.join(LOCK_FILE)).unwrap();
+        let lock = File::create(running.join(LOCK_FILE)).unwrap();
+        lock.try_lock().unwrap();
+        File::create(unrelated.join(LOCK_FILE)).unwrap();
+
+        remove_abandoned(&base);
+        assert!(!abandoned.exists());
+        assert!(running.exists());
+        assert!(unlocked.exists());
+        assert!(unrelated.exists());
+
+        drop(lock);
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,431 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ffmpeg_next::codec::{self, encoder, Codec};
+use ffmpeg_next::ffi::{
+    av_display_rotation_set, av_packet_side_data_new, AVFieldOrder, AVPacketSideDataType,
+};
+use ffmpeg_next::format::{self, Pixel};
+use ffmpeg_next::util::frame::{audio::Audio, video::Video};
+use ffmpeg_next::{filter, ChannelLayout, Rational};
+use media_types::{MediaLibError, ProcessingReport, TestAssetOptions};
+
+use super::ffmpeg_error;
+use super::filter_graph::source_graph;
+use super::job::Job;
+use super::output::StagedOutput;
+use super::timestamp::{self, Sequence};
+use super::video_writer::{add_audio_stream, write_packets};
+
+// The tone is generated at this rate whatever the container.
+const TONE_SAMPLE_RATE: i32 = 48000;
+
+/// The encoder named in the options, or else the container's default.
+fn video_codec(
+    octx: &format::context::Output,
+    output_path: &Path,
+    options: &TestAssetOptions,
+) -> Result<Codec, MediaLibError> {
+    if !options.codec.is_empty() {
+        return encoder::find_by_name(&options.codec).ok_or_else(|| {
+            MediaLibError::FFmpegError(format!("No encoder named {}", &*options.codec).into())
+        });
+    }
+    let codec_id = octx
+        .format()
+        .codec(output_path, ffmpeg_next::media::Type::Video);
+    encoder::find(codec_id).ok_or_else(|| {
+        MediaLibError::FFmpegError(format!("No encoder available for {:?}", codec_id).into())
+    })
+}
+
+/// The test pattern, encoded frame by frame.
+struct PatternTrack {
+    graph: filter::Graph,
+    encoder: encoder::Video,
+    stream_index: usize,
+    time_base: Rational,
+    timestamps: Sequence,
+    done: bool,
+}
+
+impl PatternTrack {
+    fn new(
+        octx: &mut format::context::Output,
+        codec: Codec,
+        options: &TestAssetOptions,
+    ) -> Result<Self, MediaLibError> {
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        // 4:2:0 when the encoder takes it, as nearly every camera records.
+        let pixel_format = codec
+            .video()
+            .ok()
+            .and_then(|video| video.formats())
+            .and_then(|formats| {
+                let formats: Vec<Pixel> = formats.collect();
+                if formats.contains(&Pixel::YUV420P) {
+                    return Some(Pixel::YUV420P);
+                }
+                formats.first().copied()
+            })
+            .unwrap_or(Pixel::YUV420P);
+        let frame_rate = Rational(options.frame_rate as i32, 1);
+        let time_base = frame_rate.invert();
+
+        let mut ost = octx.add_stream(codec).map_err(ffmpeg_error)?;
+        let stream_index = ost.index();
+        let mut video = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .map_err(ffmpeg_error)?;
+        video.set_width(options.size.width);
+        video.set_height(options.size.height);
+        video.set_format(pixel_format);
+        video.set_time_base(time_base);
+        video.set_frame_rate(Some(frame_rate));
+        video.set_max_b_frames(options.b_frames as usize);
+        let mut flags = codec::Flags::empty();
+        if global_header {
+            flags |= codec::Flags::GLOBAL_HEADER;
+        }
+        if options.interlaced {
+            flags |= codec::Flags::INTERLACED_DCT | codec::Flags::INTERLACED_ME;
+            unsafe { (*video.as_mut_ptr()).field_order = AVFieldOrder::AV_FIELD_TT };
+        }
+        video.set_flags(flags);
+
+        let encoder = video.open().map_err(ffmpeg_error)?;
+        ost.set_parameters(&encoder);
+        ost.set_time_base(time_base);
+        if options.rotation_degrees % 360 != 0 {
+            // After the parameters, setting them replaces the side data.
+            unsafe {
+                let parameters = (*ost.as_mut_ptr()).codecpar;
+                let side_data = av_packet_side_data_new(
+                    &mut (*parameters).coded_side_data,
+                    &mut (*parameters).nb_coded_side_data,
+                    AVPacketSideDataType::AV_PKT_DATA_DISPLAYMATRIX,
+                    9 * std::mem::size_of::<i32>(),
+                    0,
+                );
+                if side_data.is_null() {
+                    return Err(MediaLibError::FFmpegError(
+                        "Failed to allocate the display matrix".into(),
+                    ));
+                }
+                // The matrix turns counterclockwise.
+                av_display_rotation_set(
+                    (*side_data).data as *mut i32,
+                    -(options.rotation_degrees as f64),
+                );
+            }
+        }
+
+        let size = format!("{}x{}", options.size.width, options.size.height);
+        let seconds = options.duration_ms as f64 / 1000.0;
+        let pixel_format = pixel_format
+            .descriptor()
+            .map(|descriptor| descriptor.name())
+            .unwrap_or("yuv420p");
+        // Interlaced frames are woven from two frames of a pattern running
+        // at twice the rate, so the fields really are from different times.
+        let spec = if options.interlaced {
+            format!(
+                "testsrc2=size={}:rate={}:duration={:.3},interlace=scan=tff,format={}",
+                size,
+                options.frame_rate * 2,
+                seconds,
+                pixel_format
+            )
+        } else {
+            format!(
+                "testsrc2=size={}:rate={}:duration={:.3},format={}",
+                size, options.frame_rate, seconds, pixel_format
+            )
+        };
+
+        Ok(PatternTrack {
+            graph: source_graph(&spec, "buffersink")?,
+            encoder,
+            stream_index,
+            time_base,
+            timestamps: Sequence::new(),
+            done: false,
+        })
+    }
+
+    fn position_us(&self) -> i64 {
+        timestamp::to_us(self.timestamps.position(), self.time_base)
+    }
+
+    /// Encodes the next frame, or flushes the encoder after the last one.
+    fn step(&mut self, octx: &mut format::context::Output) -> Result<(), MediaLibError> {
+        let mut frame = Video::empty();
+        let received = self
+            .graph
+            .get("out")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter output missing".into()))?
+            .sink()
+            .frame(&mut frame);
+        match received {
+            Ok(()) => {
+                frame.set_pts(Some(self.timestamps.take(1)));
+                self.encoder.send_frame(&frame).map_err(ffmpeg_error)?;
+            }
+            Err(ffmpeg_next::Error::Eof) => {
+                self.encoder.send_eof().map_err(ffmpeg_error)?;
+                self.done = true;
+            }
+            Err(e) => return Err(ffmpeg_error(e)),
+        }
+        write_packets(&mut self.encoder, octx, self.stream_index, self.time_base)
+    }
+}
+
+/// The sine tone, encoded in the container's default audio codec.
+struct ToneTrack {
+    graph: filter::Graph,
+    encoder: encoder::Audio,
+    stream_index: usize,
+    time_base: Rational,
+    timestamps: Sequence,
+    done: bool,
+}
+
+impl ToneTrack {
+    fn new(
+        octx: &mut format::context::Output,
+        output_path: &Path,
+        options: &TestAssetOptions,
+    ) -> Result<Self, MediaLibError> {
+        let (encoder, stream_index) =
+            add_audio_stream(octx, output_path, TONE_SAMPLE_RATE, ChannelLayout::MONO)?;
+        let sample_format = encoder.format();
+
+        let spec = format!(
+            "sine=frequency={}:sample_rate={}:duration={:.3},aformat=sample_fmts={}:channel_layouts=mono",
+            options.tone_hz,
+            TONE_SAMPLE_RATE,
+            options.duration_ms as f64 / 1000.0,
+            sample_format.name()
+        );
+        let mut graph = source_graph(&spec, "abuffersink")?;
+        // Encoders such as AAC take a fixed number of samples per frame,
+        // zero for the ones that don't.
+        if encoder.frame_size() > 0 {
+            if let Some(mut sink) = graph.get("out") {
+                sink.sink().set_frame_size(encoder.frame_size());
+            }
+        }
+
+        Ok(ToneTrack {
+            graph,
+            encoder,
+            stream_index,
+            time_base: Rational(1, TONE_SAMPLE_RATE),
+            timestamps: Sequence::new(),
+            done: false,
+        })
+    }
+
+    fn position_us(&self) -> i64 {
+        timestamp::to_us(self.timestamps.position(), self.time_base)
+    }
+
+    /// Encodes the next block of samples, or flushes the encoder after the
+    /// last one.
+    fn step(&mut self, octx: &mut format::context::Output) -> Result<(), MediaLibError> {
+        let mut samples = Audio::empty();
+        let received = self
+            .graph
+            .get("out")
+            .ok_or_else(|| MediaLibError::FFmpegError("Filter output missing".into()))?
+            .sink()
+            .frame(&mut samples);
+        match received {
+            Ok(()) => {
+                samples.set_pts(Some(self.timestamps.take(samples.samples() as i64)));
+                self.encoder.send_frame(&samples).map_err(ffmpeg_error)?;
+            }
+            Err(ffmpeg_next::Error::Eof) => {
+                self.encoder.send_eof().map_err(ffmpeg_error)?;
+                self.done = true;
+            }
+            Err(e) => return Err(ffmpeg_error(e)),
+        }
+        write_packets(&mut self.encoder, octx, self.stream_index, self.time_base)
+    }
+}
+
+/// Synthesizes a video at `output_path` as `options` describe, so tests
+/// and bug reproductions don't need somebody's files. The container comes
+/// from the output's extension.
+pub fn generate_test_asset(
+    output_path: &Path,
+    options: &TestAssetOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    if options.size.width == 0 || options.size.height == 0 {
+        return Err(MediaLibError::UnknownError(
+            "A test asset needs a width and height".into(),
+        ));
+    }
+    if options.frame_rate == 0 || options.duration_ms == 0 {
+        return Err(MediaLibError::UnknownError(
+            "A test asset needs a frame rate and duration".into(),
+        ));
+    }
+    if options.rotation_degrees % 90 != 0 {
+        return Err(MediaLibError::UnknownError(
+            format!(
+                "Rotation of {} degrees isn't a multiple of 90",
+                options.rotation_degrees
+            )
+            .into(),
+        ));
+    }
+
+    let started = Instant::now();
+    let output = StagedOutput::new(output_path, &options.output)?;
+    let mut octx = format::output(output.staging_path()).map_err(ffmpeg_error)?;
+    let codec = video_codec(&octx, output_path, options)?;
+    let mut pattern = PatternTrack::new(&mut octx, codec, options)?;
+    let mut tone = match options.tone_hz {
+        0 => None,
+        _ => Some(ToneTrack::new(&mut octx, output_path, options)?),
+    };
+    octx.write_header().map_err(ffmpeg_error)?;
+
+    let duration_us = options.duration_ms as i64 * 1000;
+    loop {
+        job.check()?;
+        let pattern_us = (!pattern.done).then(|| pattern.position_us());
+        let tone_us = tone
+            .as_ref()
+            .filter(|tone| !tone.done)
+            .map(ToneTrack::position_us);
+        // Whichever track is behind goes next, so the muxer never has to
+        // hold on to much of the other.
+        let tone_next = match (pattern_us, tone_us) {
+            (None, None) => break,
+            (Some(pattern_us), Some(tone_us)) => tone_us < pattern_us,
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+        };
+        match (tone_next, tone.as_mut()) {
+            (true, Some(tone)) => tone.step(&mut octx)?,
+            _ => {
+                job.progress(pattern.position_us(), Some(duration_us));
+                pattern.step(&mut octx)?;
+            }
+        }
+    }
+    octx.write_trailer().map_err(ffmpeg_error)?;
+    // Closes the file before it's moved into place.
+    drop(octx);
+    output.commit()?;
+
+    let mut report = ProcessingReport {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        frames_decoded: 0,
+        frames_returned: pattern.timestamps.position() as u64,
+        frames_dropped: 0,
+        hardware_device: None.into(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    let bytes = std::fs::metadata(output_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    report.add_output(&output_path.to_string_lossy(), bytes);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::media::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+    use crate::media::input::open_input;
+    use crate::media::still::stream_orientation;
+    use media_types::{HardwareBackend, JobContext, MediaFrameDecoderOptions, Size};
+
+    fn asset(name: &str, options: &TestAssetOptions) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "media-lib-asset-{}-{}.mp4",
+            name,
+            std::process::id()
+        ));
+        generate_test_asset(&path, options, &mut Job::detached(JobContext::default())).unwrap();
+        path
+    }
+
+    fn small() -> TestAssetOptions {
+        TestAssetOptions {
+            size: Size::new(160, 120),
+            duration_ms: 500,
+            frame_rate: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_stores_the_clockwise_rotation_players_apply() {
+        // As EXIF orientations: 6 turns the image 90° clockwise to show it,
+        // 8 turns it 90° counterclockwise.
+        for (degrees, orientation) in [(0, 1), (90, 6), (180, 3), (270, 8)] {
+            let options = TestAssetOptions {
+                rotation_degrees: degrees,
+                ..small()
+            };
+            let path = asset(&format!("rotated-{}", degrees), &options);
+            let ictx = open_input(&path, None).unwrap();
+            let stream = ictx
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .unwrap();
+            assert_eq!(stream_orientation(&stream), orientation, "{}°", degrees);
+            drop(ictx);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn it_encodes_interlaced_frames_top_field_first() {
+        let software = MediaFrameDecoderOptions {
+            hw_backend: HardwareBackend::SoftwareOnly,
+            ..Default::default()
+        };
+        for interlaced in [false, true] {
+            let options = TestAssetOptions {
+                interlaced,
+                ..small()
+            };
+            let path = asset(&format!("interlaced-{}", interlaced), &options);
+            let mut decoder =
+                unsafe { HardwareAcceleratedVideoDecoder::new(&path, &software) }.unwrap();
+            let frame = decoder.get_frame().unwrap().unwrap();
+            assert_eq!(frame.is_interlaced(), interlaced);
+            if interlaced {
+                assert!(frame.is_top_first());
+            }
+            drop(decoder);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn it_rejects_rotations_other_than_quarter_turns() {
+        let options = TestAssetOptions {
+            rotation_degrees: 45,
+            ..small()
+        };
+        let path = std::env::temp_dir().join("media-lib-asset-never-written.mp4");
+        assert!(
+            generate_test_asset(&path, &options, &mut Job::detached(JobContext::default()))
+                .is_err()
+        );
+        assert!(!path.exists());
+    }
+}
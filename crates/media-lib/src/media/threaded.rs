@@ -0,0 +1,163 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use media_types::{MediaFrameDecoderOptions, MediaLibError, ProcessingReport, Size};
+
+use super::KeyframeIterator;
+
+type Item = Result<Vec<u8>, MediaLibError>;
+
+/// What the worker's iterator says about itself, updated after every frame.
+struct Status {
+    report: ProcessingReport,
+    // Sideways video turns out to be so once its first frame is decoded.
+    size: Size,
+}
+
+impl Status {
+    fn of(iterator: &KeyframeIterator) -> Self {
+        Status {
+            report: iterator.report(),
+            size: iterator.target_size,
+        }
+    }
+}
+
+/// Runs a `KeyframeIterator` on a worker thread of its own, which decodes
+/// up to `frame_queue_size` frames ahead of the caller and waits for room
+/// after that.
+pub struct ThreadedDecoder {
+    frames: Receiver<Item>,
+    interrupt: Arc<AtomicBool>,
+    status: Arc<Mutex<Status>>,
+    worker: Option<JoinHandle<()>>,
+    finished: bool,
+}
+
+impl ThreadedDecoder {
+    /// Opens the input on the worker, failing here when it can't be.
+    pub fn new(
+        input_path: &Path,
+        options: &MediaFrameDecoderOptions,
+    ) -> Result<Self, MediaLibError> {
+        let input_path = input_path.to_path_buf();
+        let options = options.clone();
+        let queue_size = options.frame_queue_size.max(1) as usize;
+        let (frames, frame_receiver) = mpsc::sync_channel::<Item>(queue_size);
+        let (opened, opened_receiver) = mpsc::channel();
+
+        // The decoder holds raw FFmpeg pointers and isn't `Send`, so it's
+        // made on the worker and never leaves it.
+        let worker = std::thread::spawn(move || {
+            let mut iterator = match KeyframeIterator::new(&input_path, &options) {
+                Ok(iterator) => iterator,
+                Err(e) => {
+                    let _ = opened.send(Err(e));
+                    return;
+                }
+            };
+            let status = Arc::new(Mutex::new(Status::of(&iterator)));
+            let handles = (iterator.interrupt_flag(), status.clone());
+            if opened.send(Ok(handles)).is_err() {
+                return;
+            }
+            while let Some(frame) = iterator.get() {
+                if let Ok(mut status) = status.lock() {
+                    *status = Status::of(&iterator);
+                }
+                // A cancelled iterator fails every frame from then on, one
+                // is enough.
+                let cancelled = matches!(&frame, Err(e) if e.is_cancelled());
+                // Gone once the consumer has dropped the decoder.
+                if frames.send(frame).is_err() || cancelled {
+                    break;
+                }
+            }
+            if let Ok(mut status) = status.lock() {
+                *status = Status::of(&iterator);
+            }
+        });
+
+        let opened = opened_receiver.recv().map_err(|_| {
+            MediaLibError::UnknownError("Decoder thread stopped while opening the input".into())
+        });
+        let (interrupt, status) = match opened {
+            Ok(Ok(handles)) => handles,
+            Ok(Err(e)) | Err(e) => {
+                let _ = worker.join();
+                return Err(e);
+            }
+        };
+        Ok(ThreadedDecoder {
+            frames: frame_receiver,
+            interrupt,
+            status,
+            worker: Some(worker),
+            finished: false,
+        })
+    }
+
+    /// The next frame, waiting for the worker if it isn't ready yet.
+    pub fn get(&mut self) -> Option<Item> {
+        if self.finished {
+            return None;
+        }
+        let frame = self.frames.recv().ok();
+        self.finished = frame.is_none();
+        frame
+    }
+
+    /// The next frame if it's ready, none without waiting otherwise.
+    pub fn try_get(&mut self) -> Option<Item> {
+        match self.frames.try_recv() {
+            Ok(frame) => Some(frame),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// See `KeyframeIterator::target_size`, turned for sideways video once
+    /// its first frame has been taken.
+    pub fn size(&self) -> Size {
+        self.with_status(|status| status.size)
+    }
+
+    pub fn report(&self) -> ProcessingReport {
+        self.with_status(|status| status.report.clone())
+    }
+
+    fn with_status<T>(&self, read: impl FnOnce(&Status) -> T) -> T {
+        match self.status.lock() {
+            Ok(status) => read(&*status),
+            Err(poisoned) => read(&*poisoned.into_inner()),
+        }
+    }
+
+    /// See `HardwareAcceleratedVideoDecoder::interrupt_flag`.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupt.clone()
+    }
+}
+
+impl Drop for ThreadedDecoder {
+    fn drop(&mut self) {
+        // Stops a worker blocked in a read, and makes room for one waiting
+        // to queue a frame, so it gets to see it's been stopped. Joined so
+        // the input is closed by the time the decoder is gone.
+        self.interrupt.store(true, Ordering::Relaxed);
+        while self.frames.recv().is_ok() {}
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Rational;
+use media_types::{MediaFrameDecoderOptions, MediaLibError, ProcessingReport, TimelapseOptions};
+
+use super::hardware_accelerated_video_decoder::HardwareAcceleratedVideoDecoder;
+use super::job::Job;
+use super::video_writer::{VideoWriter, WriterSettings};
+
+// Used when the input doesn't say what its frame rate is.
+const DEFAULT_FRAME_RATE: Rational = Rational(30, 1);
+
+/// Running per-pixel sums of a group of frames, all in the writer's format.
+struct FrameAccumulator {
+    planes: Vec<Vec<u32>>,
+    frames: u32,
+}
+
+impl FrameAccumulator {
+    fn new() -> Self {
+        FrameAccumulator {
+            planes: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    fn add(&mut self, frame: &Video) {
+        if self.planes.is_empty() {
+            self.planes = (0..frame.planes())
+                .map(|plane| {
+                    vec![0; (frame.plane_width(plane) * frame.plane_height(plane)) as usize]
+                })
+                .collect();
+        }
+
+        for (plane, sums) in self.planes.iter_mut().enumerate() {
+            let width = frame.plane_width(plane) as usize;
+            let stride = frame.stride(plane);
+            for (sum_row, row) in sums
+                .chunks_exact_mut(width)
+                .zip(frame.data(plane).chunks(stride))
+            {
+                for (sum, value) in sum_row.iter_mut().zip(row) {
+                    *sum += *value as u32;
+                }
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Writes the average of the accumulated frames into `frame` and resets
+    /// the sums.
+    fn take_average(&mut self, frame: &mut Video) {
+        let frames = self.frames.max(1);
+        for (plane, sums) in self.planes.iter_mut().enumerate() {
+            let width = frame.plane_width(plane) as usize;
+            let stride = frame.stride(plane);
+            for (sum_row, row) in sums
+                .chunks_exact_mut(width)
+                .zip(frame.data_mut(plane).chunks_mut(stride))
+            {
+                for (sum, value) in sum_row.iter_mut().zip(row) {
+                    *value = ((*sum + frames / 2) / frames) as u8;
+                    *sum = 0;
+                }
+            }
+        }
+        self.frames = 0;
+    }
+}
+
+/// Condenses `input_path` into a video `options.speedup` times shorter at the
+/// same frame rate, either by keeping every Nth frame or by blending each
+/// group of N frames together.
+pub fn timelapse(
+    input_path: &Path,
+    output_path: &Path,
+    options: &TimelapseOptions,
+    job: &mut Job,
+) -> Result<ProcessingReport, MediaLibError> {
+    if options.speedup == 0 {
+        return Err(MediaLibError::UnknownError(
+            "Time-lapse speedup must be at least 1".into(),
+        ));
+    }
+
+    let started = Instant::now();
+    let mut video_decoder = unsafe {
+        HardwareAcceleratedVideoDecoder::new(input_path, &MediaFrameDecoderOptions::default())
+    }?;
+    video_decoder.keyframes_only = false;
+
+    let frame_rate = match video_decoder.frame_rate() {
+        rate if rate.numerator() > 0 && rate.denominator() > 0 => rate,
+        _ => DEFAULT_FRAME_RATE,
+    };
+    let mut writer = VideoWriter::with_settings(
+        output_path,
+        video_decoder.size(),
+        frame_rate,
+        WriterSettings {
+            output: options.output.clone(),
+            ..WriterSettings::default()
+        },
+    )?;
+    let mut accumulator = FrameAccumulator::new();
+
+    let mut index: u64 = 0;
+    let start_time = video_decoder.start_time_us();
+    while let Some(decoded) = video_decoder.get_frame() {
+        job.check()?;
+        let decoded = decoded?;
+        if let Some(time) = video_decoder.frame_time_us(&decoded) {
+            job.progress(time - start_time, video_decoder.duration_us());
+        }
+        let position = (index % options.speedup as u64) as u32;
+        index += 1;
+
+        if options.blend {
+            let mut converted = writer.convert(&decoded)?;
+            accumulator.add(&converted);
+            if position + 1 == options.speedup {
+                accumulator.take_average(&mut converted);
+                writer.write(&mut converted)?;
+            }
+        } else if position == 0 {
+            let mut converted = writer.convert(&decoded)?;
+            writer.write(&mut converted)?;
+        }
+    }
+
+    // A trailing partial group still covers some of the input.
+    if options.blend && accumulator.frames > 0 {
+        let mut converted = Video::new(writer.format(), writer.size().width, writer.size().height);
+        accumulator.take_average(&mut converted);
+        writer.write(&mut converted)?;
+    }
+
+    let frames_written = writer.finish()?;
+    let mut report = ProcessingReport {
+        wall_time_ms: started.elapsed().as_millis() as u64,
+        frames_decoded: video_decoder.frames_decoded,
+        frames_returned: frames_written,
+        frames_dropped: 0,
+        hardware_device: video_decoder
+            .hardware_device_name()
+            .map(|name| name.as_str().into())
+            .into(),
+        warnings: stabby::vec::Vec::new(),
+        outputs: stabby::vec::Vec::new(),
+    };
+    for warning in &video_decoder.warnings {
+        report.warnings.push(warning.as_str().into());
+    }
+    let bytes = std::fs::metadata(output_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    report.add_output(&output_path.to_string_lossy(), bytes);
+    Ok(report)
+}
@@ -0,0 +1,259 @@
+use ffmpeg_next::{rescale, Rational, Rescale};
+
+/// `timestamp` in `time_base` units as microseconds, rounded to the nearest.
+pub fn to_us(timestamp: i64, time_base: Rational) -> i64 {
+    timestamp.rescale(time_base, rescale::TIME_BASE)
+}
+
+/// When something starting at `timestamp` and lasting `duration`, both in
+/// `time_base` units, is over, in microseconds.
+pub fn end_us(timestamp: i64, duration: i64, time_base: Rational) -> i64 {
+    to_us(timestamp.saturating_add(duration.max(0)), time_base)
+}
+
+/// When frame `frames` starts at `frame_rate`, in microseconds rounded to
+/// the nearest.
+pub fn frames_to_us(frames: i64, frame_rate: Rational) -> i64 {
+    to_us(frames, frame_rate.invert())
+}
+
+/// How long a frame lasts at `frame_rate`, rounded down to whole
+/// microseconds. None for the 0/0 rate of streams that don't know theirs.
+pub fn frame_duration_us(frame_rate: Rational) -> Option<i64> {
+    (frame_rate.numerator() > 0 && frame_rate.denominator() > 0)
+        .then(|| 1_000_000 * frame_rate.denominator() as i64 / frame_rate.numerator() as i64)
+}
+
+/// A break in a stream's timestamps, see `Continuity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discontinuity {
+    Backwards { from_us: i64, to_us: i64 },
+    Gap { from_us: i64, to_us: i64 },
+}
+
+/// The last timestamp of a stream, for spotting where the next one breaks
+/// from it: going backwards, or jumping ahead by more than a gap that can
+/// be explained by the content.
+#[derive(Debug, Default)]
+pub struct Continuity {
+    last_us: Option<i64>,
+}
+
+impl Continuity {
+    pub fn new() -> Self {
+        Continuity::default()
+    }
+
+    /// Takes the next timestamp, returning how it breaks from the one
+    /// before, if it does.
+    pub fn check(&mut self, time_us: i64, max_gap_us: i64) -> Option<Discontinuity> {
+        let last_us = self.last_us.replace(time_us)?;
+        if time_us < last_us {
+            Some(Discontinuity::Backwards {
+                from_us: last_us,
+                to_us: time_us,
+            })
+        } else if time_us - last_us > max_gap_us {
+            Some(Discontinuity::Gap {
+                from_us: last_us,
+                to_us: time_us,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the last timestamp, after a seek or a reconnect.
+    pub fn reset(&mut self) {
+        self.last_us = None;
+    }
+}
+
+/// Timestamps for frames or samples written one after another, each
+/// starting where the one before ended, in whatever units their durations
+/// are in.
+#[derive(Debug, Default)]
+pub struct Sequence {
+    next: i64,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Sequence::default()
+    }
+
+    /// The timestamp of what's written next, lasting `duration`.
+    pub fn take(&mut self, duration: i64) -> i64 {
+        let timestamp = self.next;
+        self.next = self.next.saturating_add(duration.max(0));
+        timestamp
+    }
+
+    /// Where the next timestamp starts, the total duration taken so far.
+    pub fn position(&self) -> i64 {
+        self.next
+    }
+}
+
+/// Keeps a stream's timestamps strictly increasing, as muxers require of
+/// decode timestamps. One that doesn't follow the last is moved to just
+/// after it, whatever its units.
+#[derive(Debug, Default)]
+pub struct Monotonic {
+    last: Option<i64>,
+}
+
+impl Monotonic {
+    pub fn new() -> Self {
+        Monotonic::default()
+    }
+
+    /// `timestamp`, or the one after the last when it isn't past it.
+    pub fn next(&mut self, timestamp: i64) -> i64 {
+        let timestamp = match self.last {
+            Some(last) if timestamp <= last => last.saturating_add(1),
+            _ => timestamp,
+        };
+        self.last = Some(timestamp);
+        timestamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Time bases found in the wild.
+    fn time_base() -> impl Strategy<Value = Rational> {
+        prop::sample::select(vec![
+            (1, 1_000_000),
+            (1, 90_000),
+            (1, 48_000),
+            (1, 44_100),
+            (1, 1000),
+            (1001, 30_000),
+            (1, 25),
+            (1, 1),
+        ])
+        .prop_map(|(numerator, denominator)| Rational::new(numerator, denominator))
+    }
+
+    /// Within a few days of zero either way in microseconds, and as many
+    /// ticks of any of the time bases.
+    fn timestamp() -> impl Strategy<Value = i64> {
+        -(1i64 << 38)..(1i64 << 38)
+    }
+
+    proptest! {
+        #[test]
+        fn conversion_rounds_to_the_nearest_microsecond(
+            timestamp in timestamp(),
+            time_base in time_base(),
+        ) {
+            let numerator = timestamp as i128 * time_base.numerator() as i128 * 1_000_000;
+            let denominator = time_base.denominator() as i128;
+            // Off from the exact time by at most half a microsecond.
+            let error = to_us(timestamp, time_base) as i128 * denominator - numerator;
+            prop_assert!(2 * error.abs() <= denominator);
+        }
+
+        #[test]
+        fn conversion_keeps_order(a in timestamp(), b in timestamp(), time_base in time_base()) {
+            let (earlier, later) = (a.min(b), a.max(b));
+            prop_assert!(to_us(earlier, time_base) <= to_us(later, time_base));
+        }
+
+        #[test]
+        fn ends_are_never_before_starts(
+            timestamp in timestamp(),
+            duration in timestamp(),
+            time_base in time_base(),
+        ) {
+            prop_assert!(end_us(timestamp, duration, time_base) >= to_us(timestamp, time_base));
+        }
+
+        #[test]
+        fn frame_durations_add_up_to_a_second(
+            numerator in 1i64..=240_000,
+            denominator in 1i64..=1001,
+        ) {
+            let rate = Rational::new(numerator as i32, denominator as i32);
+            let duration_us = frame_duration_us(rate).unwrap();
+            // Rounded down, each frame by less than a microsecond, so a
+            // second's worth falls short by less than a microsecond a frame.
+            let frames_us = duration_us * numerator;
+            prop_assert!(frames_us <= 1_000_000 * denominator);
+            prop_assert!(frames_us > 1_000_000 * denominator - numerator);
+            // A second's worth of frames starts a second in, give or take
+            // rounding.
+            prop_assert!((frames_to_us(numerator, rate) - 1_000_000 * denominator).abs() <= 1);
+        }
+
+        #[test]
+        fn sequences_follow_on(durations in prop::collection::vec(0i64..10_000, 0..100)) {
+            let mut sequence = Sequence::new();
+            let mut expected = 0;
+            for duration in durations {
+                prop_assert_eq!(sequence.take(duration), expected);
+                expected += duration;
+            }
+            prop_assert_eq!(sequence.position(), expected);
+        }
+
+        #[test]
+        fn monotonic_timestamps_strictly_increase(
+            timestamps in prop::collection::vec(-1000i64..1000, 0..100),
+        ) {
+            let mut monotonic = Monotonic::new();
+            let mut last = None;
+            for timestamp in timestamps {
+                let next = monotonic.next(timestamp);
+                prop_assert!(next >= timestamp);
+                if let Some(last) = last {
+                    prop_assert!(next > last);
+                    // Timestamps already in order are left alone.
+                    prop_assert!(timestamp <= last || next == timestamp);
+                }
+                last = Some(next);
+            }
+        }
+
+        #[test]
+        fn continuity_reports_every_break(
+            times_us in prop::collection::vec(-1000i64..1000, 0..100),
+        ) {
+            let mut continuity = Continuity::new();
+            let mut last: Option<i64> = None;
+            for time_us in times_us {
+                let found = continuity.check(time_us, 500);
+                let expected = last.and_then(|from_us| match time_us - from_us {
+                    delta if delta < 0 => Some(Discontinuity::Backwards {
+                        from_us,
+                        to_us: time_us,
+                    }),
+                    delta if delta > 500 => Some(Discontinuity::Gap {
+                        from_us,
+                        to_us: time_us,
+                    }),
+                    _ => None,
+                });
+                prop_assert_eq!(found, expected);
+                last = Some(time_us);
+            }
+        }
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(frame_duration_us(Rational::new(0, 0)), None);
+        assert_eq!(frame_duration_us(Rational::new(30_000, 1001)), Some(33_366));
+        assert_eq!(frames_to_us(30, Rational::new(30_000, 1001)), 1_001_000);
+
+        let mut continuity = Continuity::new();
+        assert_eq!(continuity.check(0, 500), None);
+        continuity.reset();
+        assert_eq!(continuity.check(-1_000_000, 500), None);
+    }
+}
@@ -0,0 +1,151 @@
+use ffmpeg_next::color::{Primaries, Range, Space, TransferCharacteristic};
+use ffmpeg_next::util::frame::video::Video;
+use media_types::{MediaLibError, ToneMapping};
+
+use super::filter_graph::FilterGraph;
+
+/// Transfers of HDR video, PQ (HDR10, Dolby Vision profile 8) and HLG.
+pub fn is_hdr(transfer: TransferCharacteristic) -> bool {
+    matches!(
+        transfer,
+        TransferCharacteristic::SMPTE2084 | TransferCharacteristic::ARIB_STD_B67
+    )
+}
+
+/// The filter spec tone mapping to SDR BT.709 with `algorithm`: linearize
+/// with zscale, convert the primaries in float, tone map, then back to
+/// BT.709 with zscale. None for `Off`.
+pub fn tonemap_spec(algorithm: ToneMapping) -> Option<String> {
+    let algorithm = match algorithm {
+        ToneMapping::Off => return None,
+        ToneMapping::Hable => "hable",
+        ToneMapping::Reinhard => "reinhard",
+        ToneMapping::Mobius => "mobius",
+        ToneMapping::Clip => "clip",
+    };
+    Some(format!(
+        "zscale=transfer=linear:npl=100,format=gbrpf32le,zscale=primaries=bt709,\
+         tonemap=tonemap={}:desat=0,zscale=transfer=bt709:matrix=bt709:range=pc,format=yuv420p",
+        algorithm
+    ))
+}
+
+/// Tags `frame` as the full range SDR BT.709 `tonemap_spec` gives, for
+/// frames whose tags were copied from the HDR frame they were mapped from.
+pub fn tag_as_mapped(frame: &mut Video) {
+    frame.set_color_transfer_characteristic(TransferCharacteristic::BT709);
+    frame.set_color_primaries(Primaries::BT709);
+    frame.set_color_space(Space::BT709);
+    frame.set_color_range(Range::JPEG);
+}
+
+/// Tone maps frames of HDR video to SDR so thumbnails don't come out grey
+/// and washed out. SDR frames pass through untouched.
+pub struct ToneMapper {
+    spec: String,
+    // With the transfer it was built for, it's rebuilt when it or the
+    // format or size change.
+    graph: Option<(TransferCharacteristic, FilterGraph)>,
+    // Set once the graph can't be built, e.g. without zimg, frames are
+    // passed through as they are from then on.
+    failed: bool,
+}
+
+impl ToneMapper {
+    /// None when `algorithm` is `Off`.
+    pub fn new(algorithm: ToneMapping) -> Option<Self> {
+        Some(ToneMapper {
+            spec: tonemap_spec(algorithm)?,
+            graph: None,
+            failed: false,
+        })
+    }
+
+    fn map(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let transfer = frame.color_transfer_characteristic();
+        let graph = match &mut self.graph {
+            Some((built_for, graph)) if *built_for == transfer && graph.accepts(frame) => graph,
+            slot => {
+                &mut slot
+                    .insert((transfer, FilterGraph::new(frame, &self.spec)?))
+                    .1
+            }
+        };
+        graph.run(frame)
+    }
+
+    /// `frame` tone mapped to SDR, none when it isn't HDR or can't be
+    /// mapped. Failing is logged once rather than failing the decode.
+    pub fn apply(&mut self, frame: &Video) -> Option<Video> {
+        if self.failed || !is_hdr(frame.color_transfer_characteristic()) {
+            return None;
+        }
+        match self.map(frame) {
+            Ok(mapped) => Some(mapped),
+            Err(e) => {
+                log::warn!(
+                    "Failed to tone map HDR frames, leaving them as they are: {}",
+                    e
+                );
+                self.failed = true;
+                self.graph = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ffmpeg_next::filter;
+    use ffmpeg_next::format::Pixel;
+
+    use super::*;
+
+    // A flat BT.2020 frame at `level` out of 1023, HDR with the PQ and HLG
+    // transfers.
+    fn hdr_frame(transfer: TransferCharacteristic, level: u16) -> Video {
+        let mut frame = Video::new(Pixel::YUV420P10LE, 64, 64);
+        for (plane, value) in [(0, level), (1, 512), (2, 512)] {
+            for sample in frame.data_mut(plane).chunks_exact_mut(2) {
+                sample.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        frame.set_color_transfer_characteristic(transfer);
+        frame.set_color_primaries(Primaries::BT2020);
+        frame.set_color_space(Space::BT2020NCL);
+        frame.set_color_range(Range::MPEG);
+        frame
+    }
+
+    #[test]
+    fn it_maps_hdr_frames_to_sdr() {
+        // Tone mapping needs FFmpeg built with zimg.
+        if filter::find("zscale").is_none() {
+            return;
+        }
+        for transfer in [
+            TransferCharacteristic::SMPTE2084,
+            TransferCharacteristic::ARIB_STD_B67,
+        ] {
+            let mut mapper = ToneMapper::new(ToneMapping::Hable).unwrap();
+            let mut mapped = mapper.apply(&hdr_frame(transfer, 600)).unwrap();
+            assert_eq!(mapped.format(), Pixel::YUV420P);
+            assert_eq!((mapped.width(), mapped.height()), (64, 64));
+            // Bright, but neither clipped to white nor crushed to black.
+            let luma = mapped.data(0)[0];
+            assert!((32..250).contains(&luma), "{:?} gave {}", transfer, luma);
+
+            tag_as_mapped(&mut mapped);
+            assert!(mapper.apply(&mapped).is_none());
+        }
+    }
+
+    #[test]
+    fn it_leaves_sdr_frames_alone() {
+        let mut mapper = ToneMapper::new(ToneMapping::Hable).unwrap();
+        let frame = hdr_frame(TransferCharacteristic::BT709, 600);
+        assert!(mapper.apply(&frame).is_none());
+        assert!(ToneMapper::new(ToneMapping::Off).is_none());
+    }
+}
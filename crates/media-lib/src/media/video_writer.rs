@@ -0,0 +1,377 @@
+use std::path::Path;
+
+use ffmpeg_next::codec::{self, encoder};
+use ffmpeg_next::format::{self, Pixel, Sample};
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::util::frame::{audio::Audio, video::Video};
+use ffmpeg_next::{ChannelLayout, Codec, Dictionary, Packet, Rational};
+use media_types::{MediaLibError, OutputOptions, Size};
+
+use super::ffmpeg_error;
+use super::output::StagedOutput;
+use super::timestamp::{self, Sequence};
+
+/// Sends whatever packets `encoder` has ready to stream `stream_index`.
+pub fn write_packets(
+    encoder: &mut encoder::Encoder,
+    octx: &mut format::context::Output,
+    stream_index: usize,
+    time_base: Rational,
+) -> Result<(), MediaLibError> {
+    let stream_time_base = octx
+        .stream(stream_index)
+        .map(|stream| stream.time_base())
+        .unwrap_or(time_base);
+
+    let mut packet = Packet::empty();
+    loop {
+        match encoder.receive_packet(&mut packet) {
+            Ok(()) => {
+                packet.set_stream(stream_index);
+                packet.rescale_ts(time_base, stream_time_base);
+                packet.write_interleaved(octx).map_err(ffmpeg_error)?;
+            }
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) | Err(ffmpeg_next::Error::Eof) => {
+                return Ok(())
+            }
+            Err(e) => return Err(ffmpeg_error(e)),
+        }
+    }
+}
+
+/// Adds an audio stream in the container's default audio codec to
+/// `octx`, with an encoder opened for `layout` at `sample_rate` in the
+/// first sample format the codec takes. Returns the encoder and the
+/// stream's index; the encoder's time base is one sample.
+pub fn add_audio_stream(
+    octx: &mut format::context::Output,
+    output_path: &Path,
+    sample_rate: i32,
+    layout: ChannelLayout,
+) -> Result<(encoder::Audio, usize), MediaLibError> {
+    let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+    let codec_id = octx
+        .format()
+        .codec(output_path, ffmpeg_next::media::Type::Audio);
+    let codec = encoder::find(codec_id).ok_or_else(|| {
+        MediaLibError::FFmpegError(format!("No audio encoder available for {:?}", codec_id).into())
+    })?;
+    let sample_format = codec
+        .audio()
+        .ok()
+        .and_then(|audio| audio.formats())
+        .and_then(|mut formats| formats.next())
+        .unwrap_or(Sample::F32(format::sample::Type::Planar));
+    let time_base = Rational(1, sample_rate);
+
+    let mut ost = octx.add_stream(codec).map_err(ffmpeg_error)?;
+    let stream_index = ost.index();
+    let mut audio = codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .audio()
+        .map_err(ffmpeg_error)?;
+    audio.set_rate(sample_rate);
+    audio.set_channel_layout(layout);
+    audio.set_format(sample_format);
+    audio.set_time_base(time_base);
+    if global_header {
+        audio.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+    let encoder = audio.open().map_err(ffmpeg_error)?;
+    ost.set_parameters(&encoder);
+    ost.set_time_base(time_base);
+    Ok((encoder, stream_index))
+}
+
+/// The pixel format to feed `codec`, 4:2:0 in system memory if it takes it.
+fn input_format(codec: &Codec) -> Pixel {
+    let formats: Vec<Pixel> = codec
+        .video()
+        .ok()
+        .and_then(|video| video.formats())
+        .map(|formats| formats.collect())
+        .unwrap_or_default();
+    [Pixel::YUV420P, Pixel::NV12]
+        .into_iter()
+        .find(|format| formats.is_empty() || formats.contains(format))
+        .unwrap_or_else(|| formats[0])
+}
+
+/// Encoder and muxer settings for writers that need more than the defaults.
+#[derive(Default)]
+pub struct WriterSettings {
+    /// Muxer to use instead of the one the output's extension names.
+    pub format: Option<&'static str>,
+    /// Video encoders to try in order instead of the container's default,
+    /// the first that opens is used.
+    pub encoders: Vec<Codec>,
+    /// Frames between keyframes, zero lets the encoder pick.
+    pub gop_size: u32,
+    /// Zero lets the encoder pick.
+    pub bit_rate: u64,
+    /// Private options of the muxer, e.g. `movflags`.
+    pub muxer_options: Dictionary<'static>,
+    /// Adds a stereo audio stream at this rate in the container's default
+    /// audio codec, see `write_audio`. Zero for video only.
+    pub audio_sample_rate: u32,
+    /// How the output file is put in place once `finish` completes it.
+    pub output: OutputOptions,
+}
+
+/// The audio stream of a writer that has one.
+struct AudioStream {
+    encoder: encoder::Audio,
+    stream_index: usize,
+    time_base: Rational,
+    timestamps: Sequence,
+}
+
+impl AudioStream {
+    fn new(
+        octx: &mut format::context::Output,
+        output_path: &Path,
+        sample_rate: u32,
+    ) -> Result<Self, MediaLibError> {
+        let (encoder, stream_index) =
+            add_audio_stream(octx, output_path, sample_rate as i32, ChannelLayout::STEREO)?;
+        Ok(AudioStream {
+            encoder,
+            stream_index,
+            time_base: Rational(1, sample_rate as i32),
+            timestamps: Sequence::new(),
+        })
+    }
+}
+
+/// Encodes frames at a constant frame rate into a single video stream, and
+/// optionally samples into an audio stream, with the codec and container
+/// picked from the output path's extension unless the settings say
+/// otherwise.
+pub struct VideoWriter {
+    octx: format::context::Output,
+    output: StagedOutput,
+    encoder: encoder::Video,
+    encoder_name: String,
+    audio: Option<AudioStream>,
+    // Rebuilt whenever incoming frames change size or layout.
+    scaler: Option<(Size, Pixel, Context)>,
+    size: Size,
+    format: Pixel,
+    time_base: Rational,
+    timestamps: Sequence,
+}
+
+impl VideoWriter {
+    pub fn new(
+        output_path: &Path,
+        size: Size,
+        frame_rate: Rational,
+    ) -> Result<Self, MediaLibError> {
+        Self::with_settings(output_path, size, frame_rate, WriterSettings::default())
+    }
+
+    pub fn with_settings(
+        output_path: &Path,
+        size: Size,
+        frame_rate: Rational,
+        settings: WriterSettings,
+    ) -> Result<Self, MediaLibError> {
+        let output = StagedOutput::new(output_path, &settings.output)?;
+        let mut octx = match settings.format {
+            Some(name) => format::output_as(output.staging_path(), name),
+            None => format::output(output.staging_path()),
+        }
+        .map_err(ffmpeg_error)?;
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+
+        let candidates = if settings.encoders.is_empty() {
+            let codec_id = octx
+                .format()
+                .codec(output_path, ffmpeg_next::media::Type::Video);
+            let codec = encoder::find(codec_id).ok_or_else(|| {
+                MediaLibError::FFmpegError(
+                    format!("No encoder available for {:?}", codec_id).into(),
+                )
+            })?;
+            vec![codec]
+        } else {
+            settings.encoders
+        };
+
+        // 4:2:0 needs even dimensions.
+        let size = Size::new(size.width & !1, size.height & !1);
+        let time_base = frame_rate.invert();
+
+        let mut opened = None;
+        let mut last_error = None;
+        for codec in candidates {
+            let format = input_format(&codec);
+            let mut video = codec::context::Context::new_with_codec(codec)
+                .encoder()
+                .video()
+                .map_err(ffmpeg_error)?;
+            video.set_width(size.width);
+            video.set_height(size.height);
+            video.set_format(format);
+            video.set_time_base(time_base);
+            video.set_frame_rate(Some(frame_rate));
+            if settings.gop_size > 0 {
+                video.set_gop(settings.gop_size);
+            }
+            if settings.bit_rate > 0 {
+                video.set_bit_rate(settings.bit_rate as usize);
+            }
+            if global_header {
+                video.set_flags(codec::Flags::GLOBAL_HEADER);
+            }
+            // GPU encoders fail to open quickly when their GPU isn't there.
+            match video.open() {
+                Ok(encoder) => {
+                    opened = Some((codec, encoder, format));
+                    break;
+                }
+                Err(e) => {
+                    log::info!("Encoder {} unavailable: {}", codec.name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let (codec, encoder, format) = match (opened, last_error) {
+            (Some(opened), _) => opened,
+            (None, Some(e)) => return Err(ffmpeg_error(e)),
+            (None, None) => {
+                return Err(MediaLibError::FFmpegError("No video encoder to try".into()))
+            }
+        };
+
+        let mut ost = octx.add_stream(codec).map_err(ffmpeg_error)?;
+        ost.set_parameters(&encoder);
+        ost.set_time_base(time_base);
+        let audio = match settings.audio_sample_rate {
+            0 => None,
+            sample_rate => Some(AudioStream::new(&mut octx, output_path, sample_rate)?),
+        };
+        octx.write_header_with(settings.muxer_options)
+            .map_err(ffmpeg_error)?;
+
+        Ok(VideoWriter {
+            octx,
+            output,
+            encoder,
+            encoder_name: codec.name().to_string(),
+            audio,
+            scaler: None,
+            size,
+            format,
+            time_base,
+            timestamps: Sequence::new(),
+        })
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The pixel format frames are encoded in, see `convert`.
+    pub fn format(&self) -> Pixel {
+        self.format
+    }
+
+    /// FFmpeg's name for the video encoder in use, e.g. "libx264".
+    pub fn encoder_name(&self) -> &str {
+        &self.encoder_name
+    }
+
+    /// Converts a decoded frame to the encoder's size and pixel format.
+    pub fn convert(&mut self, frame: &Video) -> Result<Video, MediaLibError> {
+        let frame_size = Size::new(frame.width(), frame.height());
+        let reusable = self
+            .scaler
+            .as_ref()
+            .is_some_and(|(size, format, _)| *size == frame_size && *format == frame.format());
+        if !reusable {
+            let scaler = Context::get(
+                frame.format(),
+                frame.width(),
+                frame.height(),
+                self.format,
+                self.size.width,
+                self.size.height,
+                Flags::BILINEAR,
+            )
+            .map_err(ffmpeg_error)?;
+            self.scaler = Some((frame_size, frame.format(), scaler));
+        }
+
+        let mut converted = Video::empty();
+        if let Some((_, _, scaler)) = &mut self.scaler {
+            scaler.run(frame, &mut converted).map_err(ffmpeg_error)?;
+        }
+        Ok(converted)
+    }
+
+    /// Writes a frame already in the encoder's format, see `convert`. Frames
+    /// are timed one frame apart whatever their original timestamps were.
+    pub fn write(&mut self, frame: &mut Video) -> Result<(), MediaLibError> {
+        frame.set_pts(Some(self.timestamps.take(1)));
+        self.encoder.send_frame(frame).map_err(ffmpeg_error)?;
+        write_packets(&mut self.encoder, &mut self.octx, 0, self.time_base)
+    }
+
+    /// How much video has been written, in microseconds.
+    pub fn position_us(&self) -> i64 {
+        timestamp::to_us(self.timestamps.position(), self.time_base)
+    }
+
+    /// The audio encoder, for the format, rate and frame size samples must
+    /// be in. None for video only writers.
+    pub fn audio_encoder(&self) -> Option<&encoder::Audio> {
+        self.audio.as_ref().map(|audio| &audio.encoder)
+    }
+
+    /// How much audio has been written, in microseconds.
+    pub fn audio_position_us(&self) -> Option<i64> {
+        self.audio
+            .as_ref()
+            .map(|audio| timestamp::to_us(audio.timestamps.position(), audio.time_base))
+    }
+
+    /// Writes samples already in the audio encoder's format and frame size,
+    /// see `audio_encoder`. They follow on from the last samples written.
+    pub fn write_audio(&mut self, samples: &mut Audio) -> Result<(), MediaLibError> {
+        let audio = self
+            .audio
+            .as_mut()
+            .ok_or_else(|| MediaLibError::UnknownError("Writer has no audio stream".into()))?;
+        samples.set_pts(Some(audio.timestamps.take(samples.samples() as i64)));
+        audio.encoder.send_frame(samples).map_err(ffmpeg_error)?;
+        write_packets(
+            &mut audio.encoder,
+            &mut self.octx,
+            audio.stream_index,
+            audio.time_base,
+        )
+    }
+
+    /// Flushes the encoders and finalises the file, which is only in place
+    /// at the output path from then on. Returns the number of frames written.
+    pub fn finish(mut self) -> Result<u64, MediaLibError> {
+        self.encoder.send_eof().map_err(ffmpeg_error)?;
+        write_packets(&mut self.encoder, &mut self.octx, 0, self.time_base)?;
+        if let Some(audio) = &mut self.audio {
+            audio.encoder.send_eof().map_err(ffmpeg_error)?;
+            write_packets(
+                &mut audio.encoder,
+                &mut self.octx,
+                audio.stream_index,
+                audio.time_base,
+            )?;
+        }
+        self.octx.write_trailer().map_err(ffmpeg_error)?;
+        // Closes the file before it's moved into place.
+        drop(self.octx);
+        self.output.commit()?;
+        Ok(self.timestamps.position() as u64)
+    }
+}
@@ -0,0 +1,74 @@
+use stabby::option::Option;
+use stabby::result::Result;
+use stabby::vec::Vec;
+
+use crate::MediaLibError;
+
+/// How decoded samples are stored, always interleaved.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Signed 16 bit.
+    S16,
+    /// Signed 32 bit.
+    S32,
+    /// 32 bit float from -1.0 to 1.0.
+    F32,
+}
+
+impl SampleFormat {
+    pub fn bytes(&self) -> usize {
+        match self {
+            SampleFormat::S16 => 2,
+            SampleFormat::S32 | SampleFormat::F32 => 4,
+        }
+    }
+}
+
+/// The audio a caller wants, whatever the input has, e.g. 16 kHz mono
+/// `F32` for a speech model.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResampleOptions {
+    pub sample_rate: u32,
+    /// Channels in FFmpeg's default layout for the count, 1 is mono and 2
+    /// stereo. Extra input channels are downmixed.
+    pub channels: u32,
+    pub sample_format: SampleFormat,
+}
+
+impl Default for ResampleOptions {
+    fn default() -> Self {
+        ResampleOptions {
+            sample_rate: 48000,
+            channels: 2,
+            sample_format: SampleFormat::F32,
+        }
+    }
+}
+
+/// Decoded audio in the format a `ResampleOptions` asked for.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct AudioSamples {
+    /// Interleaved samples in native byte order, `frames` of them for each
+    /// channel.
+    pub data: Vec<u8>,
+    pub frames: u32,
+    /// When the first sample is played, from the start of the input. None
+    /// for the samples the resampler holds back until the end.
+    pub timestamp_us: Option<i64>,
+}
+
+pub type AudioSamplesGet = Result<AudioSamples, MediaLibError>;
+
+/// The best audio stream of an input, decoded and resampled.
+#[stabby::stabby]
+pub trait MediaAudioIterator {
+    extern "C" fn get_samples(&mut self) -> Option<AudioSamplesGet>;
+    /// The format the samples are in.
+    extern "C" fn get_format(&mut self) -> ResampleOptions;
+}
+
+pub type MediaAudioIteratorBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaAudioIterator>);
@@ -0,0 +1,89 @@
+use stabby::option::Option;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::export::{EncodedImage, JpegOptions};
+use crate::geometry::Size;
+use crate::output::OutputOptions;
+use crate::preview::AnimationFormat;
+use crate::report::OutputRecord;
+
+/// What `generate_preview_bundle` makes from an input.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct PreviewBundleOptions {
+    /// WebP unless asked for a GIF. Without libwebp in FFmpeg the bundle
+    /// comes without an animation, and a warning saying so.
+    pub animation_format: AnimationFormat,
+    /// Bounds of the poster JPEG.
+    pub poster_size: Size,
+    /// The poster is the frame shown here, or halfway through shorter
+    /// inputs.
+    pub poster_time_ms: u64,
+    /// Cells in the filmstrip, spread evenly across the input.
+    pub filmstrip_count: u32,
+    pub filmstrip_cell_width: u32,
+    /// Time between storyboard tiles, each covers this much of the input.
+    pub storyboard_interval_ms: u32,
+    /// Tiles in each row of the storyboard sprite.
+    pub storyboard_columns: u32,
+    pub storyboard_tile_width: u32,
+    pub animation_start_ms: u64,
+    pub animation_duration_ms: u64,
+    pub animation_fps: u32,
+    /// Bounds of the animation's frames.
+    pub animation_size: Size,
+    /// Also write everything to this directory, as `poster.jpg`,
+    /// `filmstrip.jpg`, `storyboard.jpg`, `storyboard.vtt` and, when there
+    /// is an animation, `preview.gif` or `preview.webp`.
+    pub output_dir: Option<String>,
+    /// How the files in `output_dir` are written.
+    pub output: OutputOptions,
+    /// How the poster, filmstrip and storyboard are encoded.
+    pub jpeg: JpegOptions,
+}
+
+impl Default for PreviewBundleOptions {
+    fn default() -> Self {
+        PreviewBundleOptions {
+            animation_format: AnimationFormat::WebP,
+            poster_size: Size::new(1280, 720),
+            poster_time_ms: 1000,
+            filmstrip_count: 10,
+            filmstrip_cell_width: 160,
+            storyboard_interval_ms: 5000,
+            storyboard_columns: 10,
+            storyboard_tile_width: 160,
+            animation_start_ms: 0,
+            animation_duration_ms: 3000,
+            animation_fps: 10,
+            animation_size: Size::new(320, 180),
+            output_dir: None.into(),
+            output: OutputOptions::default(),
+            jpeg: JpegOptions::default(),
+        }
+    }
+}
+
+/// Everything a media platform shows for a file before it's played, see
+/// `generate_preview_bundle`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct PreviewBundle {
+    pub poster: EncodedImage,
+    /// A JPEG of the filmstrip's cells side by side.
+    pub filmstrip: EncodedImage,
+    /// A JPEG of the storyboard's tiles in rows.
+    pub storyboard: EncodedImage,
+    /// WebVTT cues giving the region of the sprite that previews each stretch
+    /// of the input, for player seekbars. They refer to the sprite as
+    /// `storyboard.jpg`.
+    pub storyboard_vtt: String,
+    /// Empty when the animation couldn't be made, the reason is in
+    /// `warnings`.
+    pub animation: Vec<u8>,
+    /// Parts of the bundle that were left out, and why.
+    pub warnings: Vec<String>,
+    /// The files written to `output_dir`, none without one.
+    pub outputs: Vec<OutputRecord>,
+}
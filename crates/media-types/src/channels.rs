@@ -0,0 +1,32 @@
+use stabby::vec::Vec;
+
+use crate::output::OutputOptions;
+
+/// Which audio channels to export from an input and how to lay them out.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct ChannelExportOptions {
+    /// Channels to export, numbered from 1 as on a broadcast track sheet.
+    /// Empty exports every channel.
+    pub channels: Vec<u32>,
+    /// Write each channel to its own mono file instead of all of them to a
+    /// single file in the order given.
+    pub split: bool,
+    /// Play the audio this many times faster, e.g. 2.0 for a quick preview
+    /// of a podcast, keeping voices at their natural pitch. Below 1.0 slows
+    /// it down.
+    pub speed: f64,
+    /// How the output files are written.
+    pub output: OutputOptions,
+}
+
+impl Default for ChannelExportOptions {
+    fn default() -> Self {
+        ChannelExportOptions {
+            channels: Vec::new(),
+            split: true,
+            speed: 1.0,
+            output: OutputOptions::default(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+/// Checksum used to compare decoded frames across runs without copying
+/// their pixels.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// The zlib CRC-32.
+    Crc32,
+    /// Adler-32, as in FFmpeg's framecrc output.
+    Adler32,
+}
@@ -0,0 +1,41 @@
+use crate::sequence::ImageSequenceOptions;
+
+/// How the manifest of a dataset export is written.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// `manifest.csv` with a header row.
+    Csv,
+    /// `manifest.json`, an array with an object per image.
+    Json,
+}
+
+/// Frames sampled from a set of videos for training a model, with a
+/// manifest of where each image came from.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DatasetExportOptions {
+    /// Which frames are written and how they're named, the numbering runs
+    /// on across inputs.
+    pub sequence: ImageSequenceOptions,
+    pub manifest_format: ManifestFormat,
+    /// Add a `scene_score` from 0 to 1 to every image, how different it is
+    /// from the image before it from the same input. The first image of an
+    /// input scores 1.
+    pub scene_scores: bool,
+    /// Add a `quality_score` from 0 to 1 to every image, low for blurry,
+    /// flat or black frames.
+    pub quality_scores: bool,
+}
+
+impl Default for DatasetExportOptions {
+    fn default() -> Self {
+        DatasetExportOptions {
+            sequence: ImageSequenceOptions::default(),
+            manifest_format: ManifestFormat::Csv,
+            scene_scores: false,
+            quality_scores: false,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use stabby::option::Option;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+/// A content key for an encrypted input, both halves as 32 hex digits.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DecryptionKey {
+    /// The key ID (KID) of the CENC tracks the key is for. Empty makes it
+    /// the key for every track without one of its own, and the key for
+    /// AES-128 inputs.
+    pub key_id: String,
+    pub key: String,
+}
+
+/// Keys for inputs encrypted with keys the caller holds: MP4s with clear
+/// key CENC, and AES-128 encrypted files such as HLS segments. Without
+/// keys encrypted inputs fail to decode.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DecryptionOptions {
+    pub keys: Vec<DecryptionKey>,
+    /// The IV of an input encrypted whole with AES-128 in CBC mode, as 32
+    /// hex digits. Setting it reads the input through FFmpeg's crypto
+    /// protocol with the key without a key ID. Leave it unset for MP4s,
+    /// they carry their IVs with the samples, and for HLS playlists, whose
+    /// segments are decrypted with the keys the playlist names.
+    pub aes_iv: Option<String>,
+}
+
+impl Default for DecryptionOptions {
+    fn default() -> Self {
+        DecryptionOptions {
+            keys: Vec::new(),
+            aes_iv: Option::None(),
+        }
+    }
+}
+
+impl DecryptionOptions {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.aes_iv.is_none()
+    }
+}
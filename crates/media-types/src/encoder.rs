@@ -0,0 +1,98 @@
+use stabby::result::Result;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::{MediaLibError, OutputOptions, ProcessingReport};
+
+/// Codec a frame encoder writes.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+}
+
+/// Layout of the pixels handed to a frame encoder.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawPixelFormat {
+    /// Packed 8 bit red, green, blue.
+    Rgb24,
+    /// Packed 8 bit red, green, blue, alpha. Alpha is dropped.
+    Rgba,
+    /// Planar 4:2:0, the Y plane followed by U and V at half size.
+    Yuv420p,
+}
+
+impl RawPixelFormat {
+    /// Bytes in a tightly packed frame of this size.
+    pub fn frame_bytes(&self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        match self {
+            RawPixelFormat::Rgb24 => width * height * 3,
+            RawPixelFormat::Rgba => width * height * 4,
+            RawPixelFormat::Yuv420p => width * height + 2 * width.div_ceil(2) * height.div_ceil(2),
+        }
+    }
+}
+
+/// One uncompressed frame, rows tightly packed without padding.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct RawVideoFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: RawPixelFormat,
+}
+
+/// How a frame encoder writes its output.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct FrameEncoderOptions {
+    /// Target bits per second, zero leaves it to the encoder.
+    pub bit_rate: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second as a fraction, e.g. 30000/1001.
+    pub frame_rate_numerator: u32,
+    pub frame_rate_denominator: u32,
+    pub codec: VideoCodec,
+    /// Try NVENC, VideoToolbox and the other GPU encoders before falling
+    /// back to x264/x265.
+    pub hardware: bool,
+    /// How the output file is written.
+    pub output: OutputOptions,
+}
+
+impl Default for FrameEncoderOptions {
+    fn default() -> Self {
+        FrameEncoderOptions {
+            bit_rate: 0,
+            width: 1280,
+            height: 720,
+            frame_rate_numerator: 30,
+            frame_rate_denominator: 1,
+            codec: VideoCodec::H264,
+            hardware: true,
+            output: OutputOptions::default(),
+        }
+    }
+}
+
+/// Encodes raw frames into a video file, frames are timed one frame apart
+/// at the configured frame rate.
+#[stabby::stabby]
+pub trait MediaFrameEncoder {
+    /// Frames of any size are scaled to the output size.
+    extern "C" fn encode_frame(&mut self, frame: RawVideoFrame) -> Result<(), MediaLibError>;
+    /// FFmpeg's name for the encoder in use, e.g. "h264_nvenc" or "libx264".
+    extern "C" fn get_encoder_name(&mut self) -> String;
+    /// Flushes the encoder and finalises the file, no frames can be added
+    /// afterwards. The report lists the output file.
+    extern "C" fn finish(&mut self) -> Result<ProcessingReport, MediaLibError>;
+}
+
+pub type MediaFrameEncoderBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaFrameEncoder>);
@@ -0,0 +1,102 @@
+use crate::geometry::Size;
+
+/// File format for a single exported frame.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Tiff,
+    /// Lossless WebP.
+    WebP,
+}
+
+/// How a single frame is exported at full resolution.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct FrameExportOptions {
+    pub format: ImageFormat,
+    /// Keep 16 bits per channel for sources with more than 8, so nothing is
+    /// rounded or dithered away. PNG and TIFF only, JPEG and WebP are
+    /// always 8 bit.
+    pub high_bit_depth: bool,
+    /// A .cube 3D LUT the frame is run through before it's encoded, e.g. to
+    /// turn log footage into something viewable.
+    pub lut_path: stabby::option::Option<stabby::string::String>,
+    /// How JPEGs are encoded, the other formats ignore it.
+    pub jpeg: JpegOptions,
+}
+
+impl Default for FrameExportOptions {
+    fn default() -> Self {
+        FrameExportOptions {
+            format: ImageFormat::Png,
+            high_bit_depth: true,
+            lut_path: None.into(),
+            jpeg: JpegOptions::default(),
+        }
+    }
+}
+
+/// How much colour detail a JPEG keeps next to the brightness.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Colour at half the resolution both ways, the smallest files.
+    Yuv420,
+    /// Colour at half the horizontal resolution.
+    Yuv422,
+    /// Colour at full resolution, for sharp coloured edges such as text
+    /// and screen recordings.
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    /// How many times the chroma planes are halved across and down.
+    pub fn chroma_shift(self) -> (u32, u32) {
+        match self {
+            ChromaSubsampling::Yuv420 => (1, 1),
+            ChromaSubsampling::Yuv422 => (1, 0),
+            ChromaSubsampling::Yuv444 => (0, 0),
+        }
+    }
+}
+
+/// How frames are JPEG encoded.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegOptions {
+    /// Progressive JPEGs show a rough version while they load and come out
+    /// a little smaller, at the cost of encode time. nvJPEG isn't used for
+    /// them.
+    pub progressive: bool,
+    /// Frames are scaled straight to this layout, so frames encoded as PNG
+    /// or WebP keep as much colour detail.
+    pub subsampling: ChromaSubsampling,
+    /// From 1 to 100, values outside are clamped.
+    pub quality: u32,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        // mozjpeg's quality and subsampling, but baseline rather than its
+        // progressive scans.
+        JpegOptions {
+            progressive: false,
+            subsampling: ChromaSubsampling::Yuv420,
+            quality: 75,
+        }
+    }
+}
+
+/// An image encoded in memory, with what a host needs to show it without
+/// decoding it first.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub data: stabby::vec::Vec<u8>,
+    pub size: Size,
+    pub format: ImageFormat,
+}
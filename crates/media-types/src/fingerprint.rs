@@ -0,0 +1,66 @@
+use stabby::vec::Vec;
+
+// Two perceptual hashes this close (out of 64 bits) are treated as the same
+// picture. Re-encodes and rescales typically land well under this.
+const SAME_SHOT_DISTANCE: u32 = 10;
+
+/// Number of differing bits between two perceptual hashes.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// A shot in a video fingerprint, summarised by the perceptual hash of its
+/// first keyframe.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShotSignature {
+    /// 64 bit DCT perceptual hash of the shot's first keyframe.
+    pub hash: u64,
+    /// Start of the shot in microseconds from the first keyframe.
+    pub start_us: i64,
+    pub duration_us: i64,
+}
+
+/// Compact temporal signature of a video, one entry per detected shot.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct VideoFingerprint {
+    pub shots: Vec<ShotSignature>,
+    pub duration_us: i64,
+}
+
+impl VideoFingerprint {
+    // Total duration of the shots in `shots` that have a visually matching
+    // shot anywhere in `other`.
+    fn matched_duration(shots: &[ShotSignature], other: &[ShotSignature]) -> i64 {
+        shots
+            .iter()
+            .filter(|shot| {
+                other.iter().any(|candidate| {
+                    hamming_distance(shot.hash, candidate.hash) <= SAME_SHOT_DISTANCE
+                })
+            })
+            .map(|shot| shot.duration_us.max(0))
+            .sum()
+    }
+
+    /// Similarity score between 0.0 and 1.0, the share of both videos'
+    /// running time covered by shots that also appear in the other one.
+    /// Matching ignores shot order so trimmed or re-cut copies still score
+    /// highly.
+    pub fn similarity(&self, other: &VideoFingerprint) -> f64 {
+        let total: i64 = self
+            .shots
+            .iter()
+            .chain(other.shots.iter())
+            .map(|shot| shot.duration_us.max(0))
+            .sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let matched = Self::matched_duration(&self.shots, &other.shots)
+            + Self::matched_duration(&other.shots, &self.shots);
+        matched as f64 / total as f64
+    }
+}
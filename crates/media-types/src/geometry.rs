@@ -0,0 +1,255 @@
+/// Width and height of a frame or image in pixels.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Size {
+    pub fn new(width: u32, height: u32) -> Self {
+        Size { width, height }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn aspect_ratio(&self) -> AspectRatio {
+        AspectRatio::new(self.width, self.height)
+    }
+
+    fn scale(&self, factor: f64) -> Size {
+        Size {
+            width: ((self.width as f64 * factor).round() as u32).max(1),
+            height: ((self.height as f64 * factor).round() as u32).max(1),
+        }
+    }
+
+    /// Scales to the given width keeping the aspect ratio.
+    pub fn scale_to_width(&self, width: u32) -> Size {
+        if self.is_empty() {
+            return *self;
+        }
+        self.scale(width as f64 / self.width as f64)
+    }
+
+    /// Scales to the given height keeping the aspect ratio.
+    pub fn scale_to_height(&self, height: u32) -> Size {
+        if self.is_empty() {
+            return *self;
+        }
+        self.scale(height as f64 / self.height as f64)
+    }
+
+    /// The largest size with the same aspect ratio that fits inside `bounds`.
+    pub fn scale_to_fit(&self, bounds: Size) -> Size {
+        if self.is_empty() {
+            return *self;
+        }
+        let factor = f64::min(
+            bounds.width as f64 / self.width as f64,
+            bounds.height as f64 / self.height as f64,
+        );
+        self.scale(factor)
+    }
+
+    /// The smallest size with the same aspect ratio that covers `bounds`.
+    pub fn scale_to_fill(&self, bounds: Size) -> Size {
+        if self.is_empty() {
+            return *self;
+        }
+        let factor = f64::max(
+            bounds.width as f64 / self.width as f64,
+            bounds.height as f64 / self.height as f64,
+        );
+        self.scale(factor)
+    }
+}
+
+/// How frames are fitted to a target size with a different aspect ratio.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingMode {
+    /// Scale to exactly the target size, distorting the picture.
+    Stretch,
+    /// Scale to fit inside the target and letterbox the rest in black.
+    Fit,
+    /// Scale to cover the target and crop what's outside it, centered.
+    Fill,
+    /// Scale to the target width, the height follows the aspect ratio.
+    FitWidth,
+    /// Scale to the target height, the width follows the aspect ratio.
+    FitHeight,
+}
+
+impl ScalingMode {
+    /// The size `source` is scaled to and the size of the output it's
+    /// centered in, which differ for `Fit` and `Fill`. A zero target
+    /// dimension follows the aspect ratio from the other.
+    pub fn apply(self, source: Size, target: Size) -> (Size, Size) {
+        if source.is_empty() || (target.width == 0 && target.height == 0) {
+            return (source, source);
+        }
+        if target.width == 0 {
+            let size = source.scale_to_height(target.height);
+            return (size, size);
+        }
+        if target.height == 0 {
+            let size = source.scale_to_width(target.width);
+            return (size, size);
+        }
+        match self {
+            ScalingMode::Stretch => (target, target),
+            ScalingMode::Fit => (source.scale_to_fit(target), target),
+            ScalingMode::Fill => (source.scale_to_fill(target), target),
+            ScalingMode::FitWidth => {
+                let size = source.scale_to_width(target.width);
+                (size, size)
+            }
+            ScalingMode::FitHeight => {
+                let size = source.scale_to_height(target.height);
+                (size, size)
+            }
+        }
+    }
+}
+
+/// A rectangular region of a frame, with the origin at the top left.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_size(size: Size) -> Self {
+        Rect::new(0, 0, size.width, size.height)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    /// Whether the rectangle lies within a frame of `size`, including ones
+    /// so far out that their edges would overflow.
+    pub fn fits_in(&self, size: Size) -> bool {
+        self.x
+            .checked_add(self.width)
+            .is_some_and(|right| right <= size.width)
+            && self
+                .y
+                .checked_add(self.height)
+                .is_some_and(|bottom| bottom <= size.height)
+    }
+
+    /// Centers a rectangle of `size` inside this one.
+    pub fn center(&self, size: Size) -> Rect {
+        Rect {
+            x: self.x + self.width.saturating_sub(size.width) / 2,
+            y: self.y + self.height.saturating_sub(size.height) / 2,
+            width: size.width,
+            height: size.height,
+        }
+    }
+}
+
+/// A reduced width:height ratio, e.g. 16:9.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AspectRatio {
+    pub num: u32,
+    pub den: u32,
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl AspectRatio {
+    pub fn new(num: u32, den: u32) -> Self {
+        let divisor = gcd(num, den).max(1);
+        AspectRatio {
+            num: num / divisor,
+            den: den / divisor,
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        if self.den == 0 {
+            return 0.0;
+        }
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl std::fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.num, self.den)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_scales_keeping_the_aspect_ratio() {
+        let size = Size::new(1920, 1080);
+        assert_eq!(size.scale_to_width(640), Size::new(640, 360));
+        assert_eq!(size.scale_to_height(720), Size::new(1280, 720));
+        assert_eq!(size.scale_to_fit(Size::new(400, 400)), Size::new(400, 225));
+        assert_eq!(size.scale_to_fill(Size::new(400, 400)), Size::new(711, 400));
+
+        // Never scales a side down to nothing.
+        assert_eq!(Size::new(4000, 10).scale_to_width(100), Size::new(100, 1));
+        assert_eq!(Size::new(0, 10).scale_to_width(100), Size::new(0, 10));
+    }
+
+    #[test]
+    fn it_centers_rects() {
+        let outer = Rect::new(10, 20, 100, 50);
+        assert_eq!(outer.center(Size::new(60, 50)), Rect::new(30, 20, 60, 50));
+        // Larger sizes hang off the bottom right rather than wrapping.
+        assert_eq!(outer.center(Size::new(200, 80)), Rect::new(10, 20, 200, 80));
+        assert_eq!(Rect::from_size(Size::new(4, 3)).size(), Size::new(4, 3));
+    }
+
+    #[test]
+    fn it_checks_rects_fit_without_overflowing() {
+        let size = Size::new(640, 360);
+        assert!(Rect::new(0, 0, 640, 360).fits_in(size));
+        assert!(Rect::new(600, 300, 40, 60).fits_in(size));
+        assert!(!Rect::new(601, 300, 40, 60).fits_in(size));
+        assert!(!Rect::new(u32::MAX, 0, 2, 10).fits_in(size));
+        assert!(!Rect::new(0, 10, 10, u32::MAX).fits_in(size));
+    }
+
+    #[test]
+    fn it_reduces_aspect_ratios() {
+        assert_eq!(
+            Size::new(1920, 1080).aspect_ratio(),
+            AspectRatio::new(16, 9)
+        );
+        assert_eq!(AspectRatio::new(1440, 1080).to_string(), "4:3");
+        assert_eq!(AspectRatio::new(0, 0).as_f64(), 0.0);
+        assert_eq!(AspectRatio::new(0, 5), AspectRatio { num: 0, den: 1 });
+    }
+}
@@ -0,0 +1,106 @@
+use stabby::option::Option;
+use stabby::result::Result;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::{
+    ContentLightLevel, MasteringDisplay, MediaLibError, ProcessingReport, SphericalMetadata,
+};
+
+/// The API a frame left on the GPU belongs to, which says what the handles
+/// in its `HwSurface` are.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwSurfaceKind {
+    /// One `CUdeviceptr` per plane, on the CUDA context of the decoder.
+    Cuda,
+    /// A `CVPixelBufferRef`, a `CVMetalTextureCache` makes Metal textures
+    /// of its planes.
+    VideoToolbox,
+    /// A `VASurfaceID` on the decoder's `VADisplay`.
+    Vaapi,
+    /// An `ID3D11Texture2D` array and the index of the frame in it.
+    D3D11,
+    /// An `mfxFrameSurface1` pointer.
+    Qsv,
+}
+
+/// Where a decoded frame is in GPU memory. The handles are only valid while
+/// the `VideoFrame` they came from is alive.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct HwSurface {
+    /// The handles `kind` describes, as integers.
+    pub handles: Vec<u64>,
+    /// Bytes per row of each plane for CUDA, empty otherwise.
+    pub pitches: Vec<u32>,
+    /// FFmpeg's name for the layout of the pixels, e.g. "nv12" or "p010le".
+    pub sw_format: String,
+    pub width: u32,
+    pub height: u32,
+    pub kind: HwSurfaceKind,
+}
+
+/// A decoded frame that hasn't been downloaded to system memory. It holds
+/// on to a surface from the decoder's pool until dropped, raise
+/// `hw_extra_frames` to keep more than a couple of frames at once.
+#[stabby::stabby]
+pub trait VideoFrame {
+    /// Where the frame is on the GPU, none when it ended up in system memory
+    /// after all (e.g. scaled in software).
+    extern "C" fn get_hw_surface(&mut self) -> Option<HwSurface>;
+    /// When the frame is shown, from the start of the input.
+    extern "C" fn get_timestamp_us(&mut self) -> i64;
+    /// Planes of the frame in system memory, e.g. 3 for yuv420p and 2 for
+    /// nv12, see `HwSurface::sw_format`. A frame on the GPU is downloaded
+    /// the first time any of the plane methods is called, zero when that
+    /// fails.
+    extern "C" fn plane_count(&mut self) -> u32;
+    /// Width of a plane in pixels, chroma planes are narrower for
+    /// subsampled formats. Zero past the last plane.
+    extern "C" fn plane_width(&mut self, index: u32) -> u32;
+    /// Height of a plane in rows, zero past the last plane.
+    extern "C" fn plane_height(&mut self, index: u32) -> u32;
+    /// Bytes from one row of a plane to the next, zero past the last plane.
+    extern "C" fn stride(&mut self, index: u32) -> u32;
+    /// A copy of a plane, `plane_height` rows of `stride` bytes. Empty past
+    /// the last plane.
+    extern "C" fn data(&mut self, index: u32) -> Vec<u8>;
+    /// FFmpeg's name for the matrix coefficients the frame is tagged with,
+    /// e.g. "bt709" or "bt2020nc". The colour methods all give "unknown"
+    /// for what the stream doesn't say.
+    extern "C" fn get_color_space(&mut self) -> String;
+    /// e.g. "bt709" or "bt2020".
+    extern "C" fn get_color_primaries(&mut self) -> String;
+    /// e.g. "bt709", "smpte2084" for PQ or "arib-std-b67" for HLG.
+    extern "C" fn get_color_transfer(&mut self) -> String;
+    /// "tv" for limited range, "pc" for full range.
+    extern "C" fn get_color_range(&mut self) -> String;
+    /// The display HDR10 content was mastered on, none when the frame
+    /// doesn't carry it.
+    extern "C" fn get_mastering_display(&mut self) -> Option<MasteringDisplay>;
+    extern "C" fn get_content_light_level(&mut self) -> Option<ContentLightLevel>;
+    /// Whether the frame holds two interlaced fields rather than one
+    /// progressive picture.
+    extern "C" fn get_interlaced_frame(&mut self) -> bool;
+    /// For interlaced frames, whether the top field is shown first.
+    extern "C" fn get_top_field_first(&mut self) -> bool;
+    /// How the frame is projected when it's from 360° video, none for
+    /// ordinary video.
+    extern "C" fn get_spherical(&mut self) -> Option<SphericalMetadata>;
+}
+
+pub type VideoFrameBox = stabby::dynptr!(stabby::boxed::Box<dyn VideoFrame>);
+pub type VideoFrameGet = Result<VideoFrameBox, MediaLibError>;
+
+/// Every frame of an input, left on the GPU it was decoded on.
+#[stabby::stabby]
+pub trait GpuFrameIterator {
+    extern "C" fn get_frame(&mut self) -> Option<VideoFrameGet>;
+    /// Summary of the work done so far, complete once `get_frame` has
+    /// returned none.
+    extern "C" fn get_report(&mut self) -> ProcessingReport;
+}
+
+pub type GpuFrameIteratorBox = stabby::dynptr!(stabby::boxed::Box<dyn GpuFrameIterator>);
@@ -0,0 +1,46 @@
+/// The mastering display colour volume (SMPTE ST 2086) HDR10 content is
+/// graded for. Chromaticities are CIE 1931 x and y, luminance in cd/m².
+/// Streams can leave out either the chromaticities or the luminance, those
+/// are zero then.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasteringDisplay {
+    pub red_x: f64,
+    pub red_y: f64,
+    pub green_x: f64,
+    pub green_y: f64,
+    pub blue_x: f64,
+    pub blue_y: f64,
+    pub white_point_x: f64,
+    pub white_point_y: f64,
+    pub min_luminance: f64,
+    pub max_luminance: f64,
+}
+
+/// Content light levels (CTA-861.3) of HDR10 content, in cd/m². Zero when
+/// the stream doesn't say.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// Brightest pixel anywhere in the content, MaxCLL.
+    pub max_content: u32,
+    /// Highest frame average, MaxFALL.
+    pub max_frame_average: u32,
+}
+
+/// How frames of HDR video (PQ or HLG) are tone mapped to SDR BT.709, see
+/// `MediaFrameDecoderOptions::tone_mapping`.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Leave HDR frames as they are, they come out grey and washed out.
+    Off,
+    /// Filmic curve that keeps detail in the highlights, a good default.
+    Hable,
+    Reinhard,
+    /// Leaves everything up to SDR white as it is and rolls off above.
+    Mobius,
+    /// Cuts everything brighter than SDR white off.
+    Clip,
+}
@@ -0,0 +1,58 @@
+/// How a live input is doing, see `StreamHealth`.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamHealthStatus {
+    Ok,
+    /// Packets are arriving, but unevenly (see `max_jitter_ms`) or with
+    /// gaps or reconnects in the last few seconds.
+    Degraded,
+    /// Nothing has arrived for `stall_timeout_ms`.
+    Stalled,
+}
+
+/// What the health monitor of a decoder has seen of its input. Meant for
+/// live inputs such as camera feeds, files are read faster than real time
+/// and always look jittery.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamHealth {
+    pub status: StreamHealthStatus,
+    /// Bits per second read over the last few seconds, all streams.
+    pub bitrate_bps: u64,
+    /// How unevenly video packets arrive compared to their timestamps, as
+    /// RTP works out interarrival jitter.
+    pub jitter_ms: f64,
+    /// Time since the last packet arrived.
+    pub since_last_packet_ms: u64,
+    /// Discontinuities in the video timestamps since decoding started.
+    pub gaps: u32,
+    /// Times reading the input failed and then picked up again, e.g. the
+    /// protocol got a dropped connection back.
+    pub reconnects: u32,
+}
+
+/// Told whenever a decoder's `StreamHealth::status` changes, on a thread of
+/// the monitor's own so a stall is reported while the decoder is blocked
+/// on it.
+#[stabby::stabby]
+pub trait StreamHealthObserver {
+    extern "C" fn on_health_change(&mut self, health: StreamHealth);
+}
+
+pub type StreamHealthObserverBox =
+    stabby::dynptr!(stabby::boxed::Box<dyn StreamHealthObserver + Send>);
+
+// What inputs that aren't read as a stream (e.g. audio previews) report.
+impl Default for StreamHealth {
+    fn default() -> Self {
+        StreamHealth {
+            status: StreamHealthStatus::Ok,
+            bitrate_bps: 0,
+            jitter_ms: 0.0,
+            since_last_packet_ms: 0,
+            gaps: 0,
+            reconnects: 0,
+        }
+    }
+}
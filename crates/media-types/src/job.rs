@@ -0,0 +1,65 @@
+use stabby::string::String;
+
+/// Hooks the host gives a long running job to follow and stop it.
+#[stabby::stabby]
+pub trait JobObserver {
+    /// Polled between units of work, true stops the job with `Cancelled`.
+    extern "C" fn is_cancelled(&mut self) -> bool;
+    /// `done` out of `total` microseconds of the input processed. `total`
+    /// is zero when the input's duration isn't known.
+    extern "C" fn on_progress(&mut self, done_us: u64, total_us: u64);
+}
+
+pub type JobObserverBox = stabby::dynptr!(stabby::boxed::Box<dyn JobObserver>);
+
+/// Stops a decoder from any thread, see
+/// `MediaKeyFrameIterator::get_cancel_handle`.
+#[stabby::stabby]
+pub trait CancelHandle {
+    /// Aborts the read the decoder is blocked on, if any, and makes it
+    /// return `Cancelled` from its next frame on.
+    extern "C" fn cancel(&self);
+}
+
+pub type CancelHandleBox = stabby::dynptr!(stabby::boxed::Box<dyn CancelHandle + Send + Sync>);
+
+/// Observes nothing and never cancels.
+pub struct NoObserver;
+
+impl JobObserver for NoObserver {
+    extern "C" fn is_cancelled(&mut self) -> bool {
+        false
+    }
+
+    extern "C" fn on_progress(&mut self, _done_us: u64, _total_us: u64) {}
+}
+
+/// What every long running call (transcodes, exports, analysis) takes for
+/// cancellation, a deadline, progress and log tagging, so these work the same
+/// way everywhere.
+#[stabby::stabby]
+pub struct JobContext {
+    pub observer: JobObserverBox,
+    /// Stop with `Cancelled` once the job has run this long, zero for no
+    /// limit.
+    pub deadline_ms: u64,
+    /// Prefixed to media-lib's log lines while the job runs, e.g. a request
+    /// id, empty for none.
+    pub tag: String,
+}
+
+impl JobContext {
+    pub fn new(observer: JobObserverBox) -> Self {
+        JobContext {
+            observer,
+            deadline_ms: 0,
+            tag: String::new(),
+        }
+    }
+}
+
+impl Default for JobContext {
+    fn default() -> Self {
+        JobContext::new(stabby::boxed::Box::new(NoObserver).into())
+    }
+}
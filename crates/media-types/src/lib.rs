@@ -3,6 +3,104 @@ use stabby::result::Result;
 use stabby::string::String;
 use stabby::vec::Vec;
 
+pub mod audio;
+pub mod bundle;
+pub mod channels;
+pub mod checksum;
+pub mod dataset;
+pub mod decryption;
+pub mod encoder;
+pub mod export;
+pub mod fingerprint;
+pub mod geometry;
+pub mod gpu;
+pub mod hdr;
+pub mod health;
+pub mod job;
+pub mod ocr;
+pub mod output;
+pub mod overlay;
+pub mod packet;
+pub mod preview;
+pub mod probe;
+pub mod proxy;
+pub mod redaction;
+pub mod report;
+pub mod restream;
+pub mod segments;
+pub mod sequence;
+pub mod sniff;
+pub mod spherical;
+pub mod storyboard;
+pub mod subtitles;
+pub mod support;
+pub mod sync;
+pub mod test_asset;
+pub mod threaded;
+pub mod timelapse;
+pub use audio::{
+    AudioSamples, AudioSamplesGet, MediaAudioIterator, MediaAudioIteratorBox,
+    MediaAudioIteratorDynMut, ResampleOptions, SampleFormat,
+};
+pub use bundle::{PreviewBundle, PreviewBundleOptions};
+pub use channels::ChannelExportOptions;
+pub use checksum::ChecksumAlgorithm;
+pub use dataset::{DatasetExportOptions, ManifestFormat};
+pub use decryption::{DecryptionKey, DecryptionOptions};
+pub use encoder::{
+    FrameEncoderOptions, MediaFrameEncoder, MediaFrameEncoderBox, MediaFrameEncoderDynMut,
+    RawPixelFormat, RawVideoFrame, VideoCodec,
+};
+pub use export::{ChromaSubsampling, EncodedImage, FrameExportOptions, ImageFormat, JpegOptions};
+pub use fingerprint::{hamming_distance, ShotSignature, VideoFingerprint};
+pub use geometry::{AspectRatio, Rect, ScalingMode, Size};
+pub use gpu::{
+    GpuFrameIterator, GpuFrameIteratorBox, GpuFrameIteratorDynMut, HwSurface, HwSurfaceKind,
+    VideoFrame, VideoFrameBox, VideoFrameDynMut, VideoFrameGet,
+};
+pub use hdr::{ContentLightLevel, MasteringDisplay, ToneMapping};
+pub use health::{
+    StreamHealth, StreamHealthObserver, StreamHealthObserverBox, StreamHealthObserverDynMut,
+    StreamHealthStatus,
+};
+pub use job::{
+    CancelHandle, CancelHandleBox, CancelHandleDyn, JobContext, JobObserver, JobObserverBox,
+    JobObserverDynMut, NoObserver,
+};
+pub use ocr::{
+    OcrImage, OcrOptions, OnScreenText, TextRecognizer, TextRecognizerBox, TextRecognizerDynMut,
+};
+pub use output::OutputOptions;
+pub use overlay::{Corner, OverlayPlacement, OverlayPlacementOptions};
+pub use packet::{
+    MediaPacket, MediaPacketGet, MediaPacketIterator, MediaPacketIteratorBox, TimeBase,
+};
+pub use preview::{
+    AlphaBackground, AnimationFormat, AudioPreviewStyle, Color, PosterSelection, PreviewOptions,
+};
+pub use probe::{DurationSource, MediaInfo, StreamInfo};
+pub use proxy::ScrubProxyOptions;
+pub use redaction::{
+    DetectionImage, RedactionMethod, RedactionOptions, RegionDetector, RegionDetectorBox,
+    RegionDetectorDynMut,
+};
+pub use report::{OutputRecord, ProcessingReport};
+pub use restream::RestreamOptions;
+pub use segments::{DetectedSegment, SegmentKind};
+pub use sequence::ImageSequenceOptions;
+pub use sniff::ContentType;
+pub use spherical::SphericalMetadata;
+pub use storyboard::{EncodedStoryboard, StoryboardOptions};
+pub use subtitles::SubtitleCue;
+pub use support::{DecoderUtilization, HardwareBackend, HardwareSupport, HardwareSupportQuery};
+pub use sync::{
+    FrameBundle, FrameBundleGet, MediaSyncIterator, MediaSyncIteratorBox, MediaSyncIteratorDynMut,
+    SyncClock, SyncOptions, SyncedFrame,
+};
+pub use test_asset::TestAssetOptions;
+pub use threaded::{MediaThreadedDecoder, MediaThreadedDecoderBox, MediaThreadedDecoderDynMut};
+pub use timelapse::TimelapseOptions;
+
 #[stabby::stabby]
 #[repr(stabby)]
 #[derive(Debug, Clone)]
@@ -10,15 +108,45 @@ pub enum MediaLibError {
     FFmpegError(String),
     UnknownError(String),
     ImageError(String),
+    /// The input has no video stream, holds the kinds of streams it does
+    /// have (e.g. "audio", "subtitle").
+    NoVideoStream(Vec<String>),
+    /// The job was cancelled or ran past its deadline, see `JobContext`.
+    Cancelled(String),
+}
+
+fn no_video_stream_message(stream_kinds: &Vec<String>) -> std::string::String {
+    if stream_kinds.is_empty() {
+        return "No video stream found, the input has no streams".to_string();
+    }
+    let kinds: std::vec::Vec<&str> = stream_kinds.iter().map(|kind| &**kind).collect();
+    format!("No video stream found, the input has: {}", kinds.join(", "))
+}
+
+impl MediaLibError {
+    pub fn is_no_video_stream(&self) -> bool {
+        self.match_ref(|_| false, |_| false, |_| false, |_| true, |_| false)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.match_ref(|_| false, |_| false, |_| false, |_| false, |_| true)
+    }
 }
 
 impl std::fmt::Display for MediaLibError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let output = self.match_ref(|e| e.to_string(), |e| e.to_string(), |e| e.to_string());
+        let output = self.match_ref(
+            |e| e.to_string(),
+            |e| e.to_string(),
+            |e| e.to_string(),
+            no_video_stream_message,
+            |e| e.to_string(),
+        );
         write!(f, "{}", output)
     }
 }
 
+/// One frame, encoded in the `image_format` its iterator was opened with.
 pub type MediaKeyFrame = Vec<u8>;
 pub type MediaKeyFrameGet = Result<MediaKeyFrame, MediaLibError>;
 
@@ -27,7 +155,373 @@ pub trait MediaKeyFrameIterator {
     extern "C" fn get_keyframe(&mut self) -> Option<MediaKeyFrameGet>;
     extern "C" fn get_width(&mut self) -> u32;
     extern "C" fn get_height(&mut self) -> u32;
+    extern "C" fn get_size(&mut self) -> Size;
+    /// Number of frames skipped by the realtime drop policy so far.
+    extern "C" fn get_dropped_frames(&mut self) -> u64;
+    /// Summary of the work done so far, complete once `get_keyframe` has
+    /// returned none.
+    extern "C" fn get_report(&mut self) -> ProcessingReport;
+    /// Jumps to `timestamp_us` (from the start of the input), the next frame
+    /// returned is the one shown at that time.
+    extern "C" fn seek(&mut self, timestamp_us: i64) -> Result<(), MediaLibError>;
+    /// Whether the consumer has fallen far enough behind the input that the
+    /// host should stop feeding it, see `high_watermark_ms`.
+    extern "C" fn get_backpressure(&mut self) -> Backpressure;
+    /// How the input is being processed, one `name: value` per line: the
+    /// hardware path, pixel formats and FFmpeg's dump of the filter graph,
+    /// for logging without turning on FFmpeg's debug output. Complete once
+    /// the first frame has been returned.
+    extern "C" fn get_pipeline_description(&mut self) -> String;
+    /// A handle that cancels this iterator from another thread, e.g. to give
+    /// up on a stalled network input. Every call returns a handle to the
+    /// same iterator.
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox;
+    /// How far through the input the frames returned so far have got, for
+    /// a progress bar.
+    extern "C" fn get_progress(&mut self) -> DecodeProgress;
+    /// What the input has been like so far, for live inputs.
+    extern "C" fn get_stream_health(&mut self) -> StreamHealth;
+    /// Has `observer` told the input's health right away and again whenever
+    /// its status changes, replacing any observer set before.
+    extern "C" fn set_health_observer(&mut self, observer: StreamHealthObserverBox);
+    /// Cancellation, a deadline, progress and log tagging for the frames
+    /// still to come, as the other long running calls take them. Replaces
+    /// any job set before.
+    extern "C" fn set_job(&mut self, job: JobContext);
+}
+
+/// Polled by hosts feeding a live input (e.g. writing into a pipe) to pause
+/// ingestion while the consumer catches up, instead of letting it buffer.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Upstream can keep going.
+    Flowing,
+    /// The consumer is behind, upstream should wait until it is `Flowing`
+    /// again.
+    Paused,
+}
+
+/// Where an iterator is in its input, see `MediaKeyFrameIterator::get_progress`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DecodeProgress {
+    /// When the frame returned last is shown, from the start of the input.
+    /// None before the first frame.
+    pub position_us: Option<i64>,
+    /// None when the container doesn't say, e.g. for live streams.
+    pub duration_us: Option<i64>,
+    /// From 0 to 100 of the input (of the range for reversed frames), none
+    /// when there's no duration to go by. Jumps to 100 once the iterator is
+    /// done, even when the last keyframe is well before the end.
+    pub percent: Option<f64>,
+}
+
+/// Frame by frame navigation over an input, frames are numbered in
+/// presentation order from zero.
+#[stabby::stabby]
+pub trait MediaFrameSession {
+    /// The frame after the current one, none at the end of the input.
+    extern "C" fn next_frame(&mut self) -> Option<MediaKeyFrameGet>;
+    /// The frame before the current one, none at the start of the input.
+    extern "C" fn previous_frame(&mut self) -> Option<MediaKeyFrameGet>;
+    extern "C" fn step_to(&mut self, frame_index: u64) -> Option<MediaKeyFrameGet>;
+    /// Index of the frame returned last, none before the first step.
+    extern "C" fn get_frame_index(&mut self) -> Option<u64>;
+    extern "C" fn get_frame_count(&mut self) -> u64;
+    extern "C" fn get_size(&mut self) -> Size;
+    /// Checksum of one plane of the current frame as decoded and scaled,
+    /// before it's encoded. None before the first step or for a plane the
+    /// frame doesn't have.
+    extern "C" fn get_plane_checksum(
+        &mut self,
+        plane: u32,
+        algorithm: ChecksumAlgorithm,
+    ) -> Option<u32>;
+    /// Cancellation, a deadline and log tagging for the frames still to be
+    /// decoded, replacing any job set before. Progress isn't reported, a
+    /// session has no end it's working towards.
+    extern "C" fn set_job(&mut self, job: JobContext);
+}
+
+/// Location of a keyframe in the input, as found in the packet metadata.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct KeyframeInfo {
+    /// Presentation timestamp in microseconds.
+    pub pts_us: i64,
+    /// Byte offset of the packet in the input, when the demuxer knows it.
+    pub byte_offset: Option<u64>,
+}
+
+/// Chromaprint fingerprint of the audio in an input, suitable for AcoustID
+/// lookups or comparing tracks for duplicates.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct AudioFingerprint {
+    /// Compressed, base64 encoded fingerprint as produced by fpcalc.
+    pub fingerprint: String,
+    pub duration_secs: f64,
+}
+
+pub type MediaKeyFrameIteratorBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaKeyFrameIterator>);
+
+pub type MediaFrameSessionBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaFrameSession>);
+
+/// Options controlling how frames are pulled out of an input.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct MediaFrameDecoderOptions {
+    /// Keep up with the input in real time. Once the consumer falls more than
+    /// `max_latency_ms` behind the input, stale frames are dropped and
+    /// decoding resumes from the most recent keyframe.
+    pub realtime: bool,
+    /// For inputs without a video stream, return a single preview image
+    /// (the cover art, or else a waveform of the audio) instead of failing
+    /// with `NoVideoStream`.
+    pub waveform_fallback: bool,
+    /// When decoding on the GPU, scale frames down to the output size there
+    /// before downloading them, so e.g. 8K sources never need full size
+    /// frames in system memory. Ignored for software decoding and devices
+    /// without a scale filter.
+    pub hw_prescale: bool,
+    /// Turn frames of videos recorded sideways (phones store a display
+    /// matrix saying how to show them) and photos with an EXIF orientation
+    /// upright. Off returns frames as stored.
+    pub auto_rotate: bool,
+    /// Keep the decoder once the input is done and hand it to the next one
+    /// opened with the same codec parameters (codec, size, pixel format and
+    /// headers) and hardware options, skipping device and codec setup.
+    /// Worth it for services opening many files from the same cameras.
+    pub reuse_decoders: bool,
+    /// Convert frames tagged as wide gamut (BT.2020, DCI or Display P3) to
+    /// sRGB before they're scaled, so they don't come out desaturated or
+    /// oversaturated next to what players show. Needs FFmpeg built with
+    /// zimg to handle every transfer, frames that can't be converted are
+    /// returned as they are.
+    pub convert_to_srgb: bool,
+    /// Deinterlace frames flagged as interlaced (see `get_interlaced_frame`
+    /// on `VideoFrame`) with bwdif, or yadif in FFmpeg builds without it,
+    /// so they don't come out with combing. Key frames, too far apart to
+    /// compare, are deinterlaced one by one with estdif instead. Frames
+    /// decoded on hardware are deinterlaced once they've been downloaded.
+    pub deinterlace: bool,
+    /// Turn frames of equirectangular 360° video (see `SphericalMetadata`)
+    /// into a flat 16:9 view facing the way the metadata says, see
+    /// `flat_view_fov_degrees`, so thumbnails aren't a stretched panorama.
+    /// Other video is left as it is.
+    pub flatten_360: bool,
+    /// Map local files into memory and read them from there instead of
+    /// with a system call per read, which adds up when seeking to many
+    /// positions in a large file (see `MediaFrameSession`). The file must
+    /// not change while it's read: data appended after opening isn't seen,
+    /// and cutting it shorter kills the process on the next read past the
+    /// new end, so leave this off for files still being written. Ignored
+    /// for network inputs, files that can't be mapped are read as usual,
+    /// with a warning logged.
+    pub memory_map: bool,
+    /// Force or forbid a hardware accelerator, `Auto` leaves the choice to
+    /// `hw_device_types`.
+    pub hw_backend: HardwareBackend,
+    /// Tone map frames of HDR video (PQ or HLG) to SDR BT.709 before
+    /// they're scaled. Needs FFmpeg built with zimg, frames are returned as
+    /// they are without it. Takes the place of `convert_to_srgb` for HDR
+    /// frames.
+    pub tone_mapping: ToneMapping,
+    /// How frames are fitted to `target_width` x `target_height` when the
+    /// aspect ratios differ.
+    pub scaling_mode: ScalingMode,
+    /// How frames with transparency are flattened before they're encoded,
+    /// `Solid` uses `alpha_color`.
+    pub alpha_background: AlphaBackground,
+    pub alpha_color: Color,
+    /// Format frames are encoded in. PNG and WebP are lossless, for hosts
+    /// that compare frames (e.g. by perceptual hash) and can't have JPEG
+    /// artifacts in the way, at several times the size.
+    pub image_format: ImageFormat,
+    /// Size of the frames returned. Zero for either follows the input's
+    /// aspect ratio from the other, zero for both gives frames 360 pixels
+    /// high.
+    pub target_width: u32,
+    pub target_height: u32,
+    pub max_latency_ms: u32,
+    /// In realtime mode, backpressure is `Paused` once the consumer is this
+    /// far behind the input and stays that way until it is back within
+    /// `low_watermark_ms`. Zero never pauses. Should be below
+    /// `max_latency_ms`, past which frames are dropped instead.
+    pub high_watermark_ms: u32,
+    pub low_watermark_ms: u32,
+    /// Number of worker threads used to encode frames while the next
+    /// ones are decoded. Zero encodes on the calling thread.
+    pub encode_threads: u32,
+    /// Frames the worker of `get_threaded_frames` decodes ahead of the
+    /// caller before it waits, at least one.
+    pub frame_queue_size: u32,
+    /// When non-zero, seek to this many evenly spaced points across the
+    /// duration and return one frame from each instead of walking every
+    /// keyframe, so the work is bounded regardless of the file length.
+    pub sample_count: u32,
+    /// Only return every `frame_step`th frame that would otherwise be
+    /// returned, dropping the rest before they're downloaded or scaled.
+    /// Zero and one return every frame.
+    pub frame_step: u32,
+    /// Only return frames shown from `start_time_ms` (from the start of the
+    /// input) up to and including `end_time_ms`. Decoding starts with a seek
+    /// to the start, the frame shown then is returned even when it isn't a
+    /// keyframe. Zero for the end goes on to the end of the input. Sampling
+    /// spreads its points over the range.
+    pub start_time_ms: u32,
+    pub end_time_ms: u32,
+    /// Surfaces allocated up front for hardware decoding, replacing the
+    /// size FFmpeg works out from the codec. Zero keeps FFmpeg's size. Some
+    /// GPUs fail to allocate the default pool for 4K content. Opening fails
+    /// for pools of more than 256 surfaces, counting `hw_extra_frames`.
+    pub hw_initial_pool_size: u32,
+    /// Surfaces added to the hardware pool on top of what the decoder
+    /// needs, for consumers that hold on to decoded frames.
+    pub hw_extra_frames: u32,
+    /// Horizontal field of view of the view `flatten_360` makes, zero for
+    /// 90 degrees.
+    pub flat_view_fov_degrees: u32,
+    /// For `http(s)://`, `rtsp://` and `rtmp://` inputs, give up once a
+    /// connect or read blocks this long. Zero waits as long as the
+    /// protocol does by default, which for most is forever.
+    pub network_timeout_ms: u32,
+    /// The input's health is `Stalled` once nothing has arrived for this
+    /// long, and `Degraded` while video packets arrive with more jitter
+    /// than `max_jitter_ms`. See `get_stream_health`.
+    pub stall_timeout_ms: u32,
+    pub max_jitter_ms: u32,
+    /// Return at most this many frames per second, the first of each
+    /// `1 / max_fps` interval, e.g. 1.0 to sample frames for a model once a
+    /// second. Inputs already below it keep every frame. Zero has no limit.
+    pub max_fps: f64,
+    /// Index of the video stream to decode, for inputs with several angles
+    /// or an embedded preview (see `list_video_streams`). None takes the
+    /// stream FFmpeg considers best.
+    pub stream_index: Option<u32>,
+    /// Demuxer to open the input with (e.g. "mp4" or "mpegts") instead of
+    /// probing, for files with a missing or misleading extension.
+    pub force_format: Option<String>,
+    /// User-Agent sent for HTTP and RTSP inputs instead of FFmpeg's.
+    pub user_agent: Option<String>,
+    /// Which GPU to decode on, as FFmpeg names devices: a DRM render node
+    /// such as `/dev/dri/renderD129` for VAAPI, an index for CUDA. None
+    /// takes the default, or for VAAPI the first render node that opens.
+    pub hw_device: Option<String>,
+    /// Ways to decode, tried in order until one opens: FFmpeg device type
+    /// names ("qsv", "vaapi", "cuda"...) and "software". Leaving "software"
+    /// out makes decoding fail when none of the devices can be used. Empty
+    /// tries Quick Sync, then whatever devices the decoder supports, then
+    /// software.
+    pub hw_device_types: Vec<String>,
+    /// When decoding fails, write what's needed to look into it afterwards
+    /// (the probe, the first packets, the filter graph and the end of the
+    /// log) to a new directory in this one. None writes nothing.
+    pub diagnostics_dir: Option<String>,
+    /// Keys for inputs encrypted with keys the caller holds.
+    pub decryption: DecryptionOptions,
+    /// Quality and layout of the frames when `image_format` is `Jpeg`.
+    pub jpeg: JpegOptions,
+}
+
+impl Default for MediaFrameDecoderOptions {
+    fn default() -> Self {
+        MediaFrameDecoderOptions {
+            realtime: false,
+            target_width: 0,
+            target_height: 0,
+            scaling_mode: ScalingMode::Fit,
+            image_format: ImageFormat::Jpeg,
+            max_latency_ms: 1000,
+            high_watermark_ms: 0,
+            low_watermark_ms: 0,
+            encode_threads: 2,
+            frame_queue_size: 8,
+            sample_count: 0,
+            frame_step: 0,
+            start_time_ms: 0,
+            end_time_ms: 0,
+            hw_initial_pool_size: 0,
+            hw_extra_frames: 0,
+            flat_view_fov_degrees: 90,
+            stream_index: Option::None(),
+            force_format: Option::None(),
+            network_timeout_ms: 0,
+            stall_timeout_ms: 5000,
+            max_jitter_ms: 100,
+            max_fps: 0.0,
+            user_agent: Option::None(),
+            hw_device: Option::None(),
+            hw_device_types: Vec::new(),
+            diagnostics_dir: Option::None(),
+            decryption: DecryptionOptions::default(),
+            jpeg: JpegOptions::default(),
+            waveform_fallback: false,
+            hw_prescale: false,
+            auto_rotate: true,
+            reuse_decoders: false,
+            convert_to_srgb: false,
+            deinterlace: false,
+            flatten_360: false,
+            memory_map: false,
+            hw_backend: HardwareBackend::Auto,
+            tone_mapping: ToneMapping::Off,
+            alpha_background: AlphaBackground::Ignore,
+            alpha_color: Color::new(0xff, 0xff, 0xff),
+        }
+    }
 }
 
 #[stabby::stabby]
 pub struct MediaLibInit {}
+
+/// Verbosity for log output, from nothing at all up to tracing everything.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Process wide settings applied when media-lib is initialised.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    /// FFmpeg's own log level.
+    pub log_level: LogLevel,
+    /// Initialise FFmpeg's networking layer, needed for network inputs.
+    pub network: bool,
+    /// Hardware device types (e.g. "cuda", "videotoolbox") to open once at
+    /// startup so driver loading doesn't land on the first decode. The
+    /// devices stay open and are shared by decoders that don't ask for a
+    /// particular `hw_device`.
+    pub hardware_preheat: Vec<String>,
+    /// Largest single allocation FFmpeg may make, in bytes. Zero keeps
+    /// FFmpeg's default. FFmpeg doesn't let its allocator be replaced, so
+    /// this cap is the only control there is over its allocations.
+    pub max_alloc_bytes: u64,
+    /// Where temporary files (two-pass encoder stats, intermediate segments,
+    /// disk caches) go. Each process gets its own directory in it, removed
+    /// when the client is dropped, and ones left behind by a crash are
+    /// removed on the next start. None uses the system temporary directory.
+    pub temp_dir: Option<String>,
+}
+
+impl Default for InitOptions {
+    fn default() -> Self {
+        InitOptions {
+            log_level: LogLevel::Info,
+            network: false,
+            hardware_preheat: Vec::new(),
+            max_alloc_bytes: 0,
+            temp_dir: None.into(),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::geometry::Rect;
+
+/// A sampled frame prepared for OCR: 8 bit grayscale, tightly packed rows,
+/// dark text on a light background or the other way round.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct OcrImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The host's OCR engine, e.g. a binding to Tesseract or a cloud service.
+#[stabby::stabby]
+pub trait TextRecognizer {
+    /// The text in `image`, empty when there's none.
+    extern "C" fn recognize(&mut self, image: OcrImage) -> String;
+}
+
+pub type TextRecognizerBox = stabby::dynptr!(stabby::boxed::Box<dyn TextRecognizer>);
+
+/// Which frames are read and how they're prepared for OCR.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct OcrOptions {
+    /// Part of the frame to read in the input's pixels, e.g. the lower third
+    /// for captions. Empty for the whole frame.
+    pub region: Rect,
+    /// Time between samples.
+    pub interval_ms: u32,
+    /// The region is enlarged this many times, OCR engines do best with
+    /// text at least 20 pixels tall.
+    pub upscale: u32,
+    /// Stretch the region's levels to the full range, for low contrast text
+    /// such as burnt in timecode.
+    pub stretch_contrast: bool,
+}
+
+impl Default for OcrOptions {
+    fn default() -> Self {
+        OcrOptions {
+            region: Rect::default(),
+            interval_ms: 2000,
+            upscale: 2,
+            stretch_contrast: true,
+        }
+    }
+}
+
+/// Text read on screen, from the first sample that showed it to the last.
+/// Times are in microseconds from the start of the input.
+#[stabby::stabby]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnScreenText {
+    pub start_us: i64,
+    pub end_us: i64,
+    pub text: String,
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How output files are written to disk.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    /// Write to a temporary file next to the destination and rename it into
+    /// place once complete, so readers never observe a partially written file.
+    pub atomic: bool,
+    /// Flush the file (and its directory entry) to disk before returning.
+    pub durable: bool,
+    /// Replace a file already at the destination. Otherwise writing fails
+    /// with `AlreadyExists` and the existing file is left alone.
+    pub overwrite: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            atomic: true,
+            durable: false,
+            overwrite: true,
+        }
+    }
+}
+
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+// The temporary file lives in the same directory as the destination so the
+// final rename never crosses a filesystem boundary. It keeps the extension,
+// muxers pick the container from it.
+fn temp_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = format!(
+        ".{}.{}-{}.tmp",
+        stem,
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    );
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}.{}", name, extension.to_string_lossy())),
+        None => path.with_file_name(name),
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8], durable: bool, replace: bool) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(replace)
+        .create_new(!replace)
+        .truncate(replace)
+        .open(path)?;
+    file.write_all(contents)?;
+    if durable {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::File::open(parent)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    // Directories can't be opened for syncing on Windows, the rename is
+    // already durable once MoveFileEx returns.
+    Ok(())
+}
+
+/// Moves the complete file at `staging` to `path`. Without `replace` it's
+/// linked into place instead of renamed, which fails rather than replacing
+/// a file that turned up at `path` since it was last checked.
+fn move_into_place(staging: &Path, path: &Path, replace: bool) -> std::io::Result<()> {
+    if replace {
+        return fs::rename(staging, path);
+    }
+    fs::hard_link(staging, path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => already_exists(path),
+        _ => e,
+    })?;
+    fs::remove_file(staging)
+}
+
+fn already_exists(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+impl OutputOptions {
+    /// Fails with `AlreadyExists` if there's a file at `path` that isn't to
+    /// be overwritten.
+    pub fn check_destination(&self, path: &Path) -> std::io::Result<()> {
+        if !self.overwrite && path.exists() {
+            return Err(already_exists(path));
+        }
+        Ok(())
+    }
+
+    /// Writes `contents` to `path` according to this policy.
+    pub fn write(&self, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+        if !self.atomic {
+            return write_file(path, contents, self.durable, self.overwrite).map_err(|e| {
+                match e.kind() {
+                    std::io::ErrorKind::AlreadyExists => already_exists(path),
+                    _ => e,
+                }
+            });
+        }
+
+        self.check_destination(path)?;
+        let temp = temp_path(path);
+        let result = write_file(&temp, contents, self.durable, true)
+            .and_then(|_| move_into_place(&temp, path, self.overwrite))
+            .and_then(|_| {
+                if self.durable {
+                    sync_parent_dir(path)
+                } else {
+                    Ok(())
+                }
+            });
+
+        if result.is_err() {
+            // Best effort, the original error is more useful than this one.
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Where to write a file that's produced a piece at a time, e.g. by a
+    /// muxer, before `commit` puts it at `path`.
+    pub fn staging_path(&self, path: &Path) -> PathBuf {
+        if self.atomic {
+            temp_path(path)
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// Puts a complete file written at `staging`, see `staging_path`, in
+    /// place at `path` according to this policy.
+    pub fn commit(&self, staging: &Path, path: &Path) -> std::io::Result<()> {
+        if self.durable {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(staging)?
+                .sync_all()?;
+        }
+        if staging == path {
+            return Ok(());
+        }
+
+        move_into_place(staging, path, self.overwrite)?;
+        if self.durable {
+            sync_parent_dir(path)?;
+        }
+        Ok(())
+    }
+
+    /// Removes what was written at `staging` for a file that won't be
+    /// committed. Files written in place are left as they are.
+    pub fn discard(&self, staging: &Path, path: &Path) {
+        if staging != path {
+            let _ = fs::remove_file(staging);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stages_next_to_the_destination_with_its_extension() {
+        let options = OutputOptions::default();
+        let staging = options.staging_path(Path::new("/videos/clip.mp4"));
+        assert_eq!(staging.parent(), Some(Path::new("/videos")));
+        assert_eq!(staging.extension().unwrap(), "mp4");
+        assert_ne!(staging, options.staging_path(Path::new("/videos/clip.mp4")));
+    }
+
+    #[test]
+    fn it_writes_in_place_when_not_atomic() {
+        let options = OutputOptions {
+            atomic: false,
+            ..Default::default()
+        };
+        let path = Path::new("/videos/clip.mp4");
+        assert_eq!(options.staging_path(path), path);
+    }
+
+    #[test]
+    fn it_keeps_existing_files_unless_told_to_overwrite() {
+        let dir = std::env::temp_dir().join(format!("media-types-output-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frame.jpg");
+        fs::write(&path, b"old").unwrap();
+
+        for atomic in [true, false] {
+            let options = OutputOptions {
+                atomic,
+                overwrite: false,
+                ..Default::default()
+            };
+            let error = options.write(&path, b"new").unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+            assert_eq!(fs::read(&path).unwrap(), b"old");
+        }
+
+        OutputOptions::default().write(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        // Only the destination is left, no temporary files.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_keeps_files_that_appear_while_staging() {
+        let dir = std::env::temp_dir().join(format!("media-types-staged-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("clip.mp4");
+        let options = OutputOptions {
+            overwrite: false,
+            ..Default::default()
+        };
+
+        // Nothing's there when the file is staged, something is by the time
+        // it's committed.
+        options.check_destination(&path).unwrap();
+        let staging = options.staging_path(&path);
+        fs::write(&staging, b"new").unwrap();
+        fs::write(&path, b"old").unwrap();
+        let error = options.commit(&staging, &path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+
+        options.discard(&staging, &path);
+        fs::remove_file(&path).unwrap();
+        let staging = options.staging_path(&path);
+        fs::write(&staging, b"new").unwrap();
+        options.commit(&staging, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use crate::geometry::{Rect, Size};
+
+/// A corner of the frame an overlay can be placed in.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// What to look for when placing an overlay such as a watermark.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct OverlayPlacementOptions {
+    /// Size of the overlay in the input's pixels. A zero width or height is
+    /// a fifth of the frame's width or a tenth of its height.
+    pub overlay: Size,
+    /// Gap between the overlay and the frame's edges, in pixels.
+    pub margin: u32,
+    /// Frames sampled evenly across the input.
+    pub samples: u32,
+}
+
+impl Default for OverlayPlacementOptions {
+    fn default() -> Self {
+        OverlayPlacementOptions {
+            overlay: Size::new(0, 0),
+            margin: 16,
+            samples: 12,
+        }
+    }
+}
+
+/// Where an overlay could go and how busy the picture underneath it is.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayPlacement {
+    /// In the input's pixels.
+    pub region: Rect,
+    /// Detail and motion under the region across the samples, 0.0 for a
+    /// flat unchanging area to 1.0 for the busiest possible.
+    pub activity: f32,
+    pub corner: Corner,
+}
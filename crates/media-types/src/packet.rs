@@ -0,0 +1,55 @@
+use stabby::option::Option;
+use stabby::result::Result;
+use stabby::vec::Vec;
+
+use crate::MediaLibError;
+
+/// Time base of a stream, packet timestamps are counted in units of
+/// `numerator / denominator` seconds.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBase {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+impl TimeBase {
+    /// Converts a timestamp in this time base to microseconds.
+    pub fn to_us(&self, timestamp: i64) -> i64 {
+        if self.denominator == 0 {
+            return 0;
+        }
+        (timestamp as i128 * self.numerator as i128 * 1_000_000 / self.denominator as i128) as i64
+    }
+}
+
+/// An encoded packet straight from the demuxer.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct MediaPacket {
+    pub data: Vec<u8>,
+    /// Presentation timestamp in `time_base` units.
+    pub pts: Option<i64>,
+    /// Decode timestamp in `time_base` units.
+    pub dts: Option<i64>,
+    /// Duration in `time_base` units, none when the demuxer doesn't know it.
+    pub duration: Option<i64>,
+    /// Byte offset of the packet in the input, when the demuxer knows it.
+    pub byte_offset: Option<u64>,
+    pub time_base: TimeBase,
+    pub stream_index: u32,
+    pub is_key: bool,
+    pub is_corrupt: bool,
+}
+
+pub type MediaPacketGet = Result<MediaPacket, MediaLibError>;
+
+#[stabby::stabby]
+pub trait MediaPacketIterator {
+    /// The next packet of any stream, in the order they are stored in the
+    /// input.
+    extern "C" fn get_packet(&mut self) -> Option<MediaPacketGet>;
+    extern "C" fn get_stream_count(&mut self) -> u32;
+}
+
+pub type MediaPacketIteratorBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaPacketIterator>);
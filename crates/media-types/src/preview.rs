@@ -0,0 +1,110 @@
+use crate::export::JpegOptions;
+use crate::geometry::Size;
+
+/// An 8 bit sRGB colour.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// Linear blend towards `other`, `t` of 0.0 is this colour and 1.0 is
+    /// `other`.
+    pub fn mix(&self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        }
+    }
+}
+
+/// How audio is drawn when an input has no picture of its own.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioPreviewStyle {
+    Waveform,
+    Spectrogram,
+}
+
+/// What transparent pixels are shown over before JPEG encoding, which has no
+/// alpha channel.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaBackground {
+    /// Drop the alpha channel, transparent areas keep whatever colour they
+    /// hold, usually black.
+    Ignore,
+    /// A solid background colour.
+    Solid,
+    /// A light grey checkerboard, as image editors show transparency.
+    Checkerboard,
+}
+
+/// How the frame for a video's preview is chosen.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PosterSelection {
+    /// The first keyframe.
+    FirstFrame,
+    /// Shortly after the first chapter that isn't an intro or recap, or the
+    /// first keyframe that isn't black or a mostly flat logo or title card.
+    /// Takes a few seeks, but suits episodic content much better.
+    Smart,
+}
+
+/// Settings for `get_preview_image`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct PreviewOptions {
+    /// Bounds of the preview. Pictures are scaled to fit inside it, rendered
+    /// audio fills it exactly.
+    pub size: Size,
+    pub audio_style: AudioPreviewStyle,
+    pub poster: PosterSelection,
+    /// How transparent pictures are flattened, `Solid` uses `background`.
+    pub alpha_background: AlphaBackground,
+    pub background: Color,
+    /// Colour of the waveform, or of the loudest frequencies in a
+    /// spectrogram, which fades from the background.
+    pub foreground: Color,
+    pub jpeg: JpegOptions,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions {
+            size: Size::new(640, 360),
+            audio_style: AudioPreviewStyle::Waveform,
+            poster: PosterSelection::FirstFrame,
+            alpha_background: AlphaBackground::Ignore,
+            background: Color::new(0x20, 0x20, 0x20),
+            foreground: Color::new(0xe0, 0xe0, 0xe0),
+            jpeg: JpegOptions::default(),
+        }
+    }
+}
+
+/// Container of an animated preview, see `generate_preview_animation`.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// Plays everywhere, but limited to 256 colours a frame.
+    Gif,
+    /// Lossy animated WebP, much smaller than a GIF at full colour. Needs
+    /// FFmpeg built with libwebp.
+    WebP,
+}
@@ -0,0 +1,77 @@
+use stabby::option::Option;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::SphericalMetadata;
+
+/// One stream of an input as described by the container, see `MediaInfo`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    /// e.g. "video", "audio", "subtitle".
+    pub kind: String,
+    /// FFmpeg's codec name, e.g. "h264" or "aac".
+    pub codec_name: String,
+    /// e.g. "yuv420p", empty for streams other than video.
+    pub pixel_format: String,
+    /// Average frames per second, none for streams other than video or when
+    /// the container doesn't say.
+    pub frame_rate: Option<f64>,
+    pub index: u32,
+    /// Zero for streams other than video.
+    pub width: u32,
+    pub height: u32,
+    /// Marked as the one to play by default, e.g. the main angle of a
+    /// multi-angle recording.
+    pub is_default: bool,
+    /// A still image such as cover art or an embedded preview rather than
+    /// video.
+    pub is_attached_picture: bool,
+    /// How the frames are projected for 360° video, none for ordinary
+    /// video and streams other than video.
+    pub spherical: Option<SphericalMetadata>,
+}
+
+/// Where `MediaInfo::duration_us` came from, and so how far to trust it.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationSource {
+    /// There's no duration, e.g. for a live stream read from a pipe.
+    Unknown,
+    /// Recorded in the container or worked out from its timestamps, exact.
+    Container,
+    /// The container doesn't record one (some TS and live dumps), it was
+    /// read from the last timestamps in the file. Exact to a frame or so.
+    Scanned,
+    /// Extrapolated from the size and bitrate, can be well off for
+    /// variable bitrate inputs.
+    Bitrate,
+}
+
+/// What an input holds, read from the container headers without decoding
+/// any frames.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    /// FFmpeg's demuxer name(s), e.g. "mov,mp4,m4a,3gp,3g2,mj2".
+    pub format_name: String,
+    pub streams: Vec<StreamInfo>,
+    /// None for live streams and containers that don't record it, unless
+    /// it could be worked out another way, see `duration_source`.
+    pub duration_us: Option<i64>,
+    /// Overall bits per second, none when unknown.
+    pub bit_rate: Option<u64>,
+    pub duration_source: DurationSource,
+}
+
+impl MediaInfo {
+    pub fn stream_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// The first video stream, if any.
+    pub fn video_stream(&self) -> core::option::Option<&StreamInfo> {
+        self.streams.iter().find(|stream| &*stream.kind == "video")
+    }
+}
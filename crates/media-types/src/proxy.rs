@@ -0,0 +1,41 @@
+use crate::output::OutputOptions;
+
+/// Settings for a low resolution proxy to scrub through in a web player.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct ScrubProxyOptions {
+    /// Zero lets the encoder pick.
+    pub bit_rate: u64,
+    /// Width of the proxy, the height follows the input's aspect ratio.
+    /// Inputs narrower than this keep their size.
+    pub width: u32,
+    /// Length of each fragment, and so how far apart seekable points are.
+    /// Shorter fragments make scrubbing snappier and the file slightly
+    /// larger.
+    pub fragment_duration_ms: u32,
+    /// Playback rate of the proxy against the input, from 0.25 to 4.0.
+    /// Frames are retimed to the same frame rate and audio keeps its pitch.
+    pub speed: f64,
+    /// Include the input's audio as stereo, if it has any.
+    pub audio: bool,
+    /// A .cube 3D LUT every frame is run through before it's encoded, for
+    /// proxies of log footage.
+    pub lut_path: stabby::option::Option<stabby::string::String>,
+    /// How the playlist and the media file are written. The media file is
+    /// put in place first, so the playlist never names a missing file.
+    pub output: OutputOptions,
+}
+
+impl Default for ScrubProxyOptions {
+    fn default() -> Self {
+        ScrubProxyOptions {
+            bit_rate: 500_000,
+            width: 480,
+            fragment_duration_ms: 1000,
+            speed: 1.0,
+            audio: false,
+            lut_path: None.into(),
+            output: OutputOptions::default(),
+        }
+    }
+}
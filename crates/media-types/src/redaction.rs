@@ -0,0 +1,69 @@
+use stabby::vec::Vec;
+
+use crate::geometry::Rect;
+
+/// A frame handed to a `RegionDetector`: packed 8 bit RGB, tightly packed
+/// rows, scaled to `RedactionOptions::detection_width`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DetectionImage {
+    pub pixels: Vec<u8>,
+    /// When the frame is shown, in microseconds from the start of the input.
+    pub pts_us: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The host's detector for what has to be hidden, e.g. a face or licence
+/// plate model.
+#[stabby::stabby]
+pub trait RegionDetector {
+    /// Regions of `image` to hide, in its pixels. Empty when there's
+    /// nothing to hide.
+    extern "C" fn detect(&mut self, image: DetectionImage) -> Vec<Rect>;
+}
+
+pub type RegionDetectorBox = stabby::dynptr!(stabby::boxed::Box<dyn RegionDetector>);
+
+/// How detected regions are hidden.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactionMethod {
+    /// Box blur over the region, `blur_radius` pixels.
+    Blur,
+    /// Fills the region in from its border the way FFmpeg's delogo does.
+    /// Smoother than a blur for small regions, gives nothing away about
+    /// what was there.
+    Delogo,
+}
+
+/// When the detector runs and how what it finds is hidden.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct RedactionOptions {
+    /// Capped to what the frame is big enough for.
+    pub blur_radius: u32,
+    /// Pixels added to every side of a detected region, so the edges of a
+    /// face that moves between detections stay covered.
+    pub padding: u32,
+    /// Frames between detections, the regions found last are hidden on the
+    /// frames in between. 1 runs the detector on every frame.
+    pub detect_interval: u32,
+    /// Width frames are scaled to for the detector, the height follows the
+    /// aspect ratio. Zero hands over full size frames.
+    pub detection_width: u32,
+    pub method: RedactionMethod,
+}
+
+impl Default for RedactionOptions {
+    fn default() -> Self {
+        RedactionOptions {
+            blur_radius: 20,
+            padding: 8,
+            detect_interval: 1,
+            detection_width: 640,
+            method: RedactionMethod::Blur,
+        }
+    }
+}
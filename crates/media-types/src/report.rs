@@ -0,0 +1,65 @@
+use stabby::option::Option;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+/// A file written while processing an input.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct OutputRecord {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// What happened while processing one input, for storing as provenance
+/// alongside the extracted assets.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct ProcessingReport {
+    pub wall_time_ms: u64,
+    /// Every frame that came out of the decoder, including the ones that
+    /// weren't returned.
+    pub frames_decoded: u64,
+    pub frames_returned: u64,
+    pub frames_dropped: u64,
+    /// Hardware device type used for decoding (e.g. "cuda"), none when
+    /// decoding in software.
+    pub hardware_device: Option<String>,
+    /// Recoverable problems seen in the input, such as corrupt packets or
+    /// timestamp discontinuities.
+    pub warnings: Vec<String>,
+    /// Files written from the returned frames. The library doesn't write
+    /// files itself, callers that do fill this in.
+    pub outputs: Vec<OutputRecord>,
+}
+
+impl ProcessingReport {
+    pub fn add_output(&mut self, path: &str, bytes: u64) {
+        self.outputs.push(OutputRecord {
+            path: path.into(),
+            bytes,
+        });
+    }
+}
+
+impl std::fmt::Display for ProcessingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Wall time: {}ms", self.wall_time_ms)?;
+        writeln!(
+            f,
+            "Frames: {} decoded, {} returned, {} dropped",
+            self.frames_decoded, self.frames_returned, self.frames_dropped
+        )?;
+        let hardware_device = self.hardware_device.as_ref().map(|name| &**name);
+        writeln!(f, "Decoder: {}", hardware_device.unwrap_or("software"))?;
+        for warning in self.warnings.iter() {
+            writeln!(f, "Warning: {}", &**warning)?;
+        }
+        let total_bytes: u64 = self.outputs.iter().map(|output| output.bytes).sum();
+        write!(
+            f,
+            "Outputs: {} files, {} bytes",
+            self.outputs.len(),
+            total_bytes
+        )
+    }
+}
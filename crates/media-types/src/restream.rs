@@ -0,0 +1,22 @@
+/// How `restream` publishes to a live target such as rtmp:// or srt://.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct RestreamOptions {
+    /// Reconnect attempts after the target drops before giving up, counted
+    /// per drop.
+    pub max_reconnects: u32,
+    pub reconnect_delay_ms: u32,
+    /// Send packets at the input's own pace instead of as fast as possible,
+    /// which live ingest servers expect when the input is a file.
+    pub realtime: bool,
+}
+
+impl Default for RestreamOptions {
+    fn default() -> Self {
+        RestreamOptions {
+            max_reconnects: 5,
+            reconnect_delay_ms: 2000,
+            realtime: true,
+        }
+    }
+}
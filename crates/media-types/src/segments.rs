@@ -0,0 +1,25 @@
+/// What a detected segment of an episode is.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Intro,
+    Credits,
+    /// SMPTE or EBU colour bars at the head of a broadcast master.
+    ColorBars,
+    /// 1kHz line-up tone, usually alongside the bars.
+    Tone,
+}
+
+/// A range of an input that is likely an intro or credits, for skip
+/// buttons, or part of a technical leader to trim. Times are in
+/// microseconds from the start of the input.
+#[stabby::stabby]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedSegment {
+    pub start_us: i64,
+    pub end_us: i64,
+    /// How sure the detection is, 0.0 to 1.0.
+    pub confidence: f32,
+    pub kind: SegmentKind,
+}
@@ -0,0 +1,43 @@
+use stabby::string::String;
+
+use crate::export::FrameExportOptions;
+use crate::output::OutputOptions;
+
+/// How `export_image_sequence` picks, names and writes the images of an
+/// input, e.g. `frame_000001.png`, `frame_000002.png` and so on.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct ImageSequenceOptions {
+    /// Put before the number in every file name. It can't contain path
+    /// separators or `..`, images only go in the output directory.
+    pub prefix: String,
+    /// Number of the first image.
+    pub start_number: u64,
+    /// Images per `fps_denominator` seconds of the input, taking the first
+    /// frame shown in each interval. Zero writes every frame.
+    pub fps: u32,
+    pub fps_denominator: u32,
+    /// Digits the number is zero padded to, longer numbers aren't cut. At
+    /// most 20, the most a number can have.
+    pub pad_width: u32,
+    /// Threads encoding images in parallel, zero for one per CPU.
+    pub threads: u32,
+    /// Format, bit depth and LUT of each image.
+    pub image: FrameExportOptions,
+    pub output: OutputOptions,
+}
+
+impl Default for ImageSequenceOptions {
+    fn default() -> Self {
+        ImageSequenceOptions {
+            prefix: "frame_".into(),
+            start_number: 1,
+            fps: 0,
+            fps_denominator: 1,
+            pad_width: 6,
+            threads: 0,
+            image: FrameExportOptions::default(),
+            output: OutputOptions::default(),
+        }
+    }
+}
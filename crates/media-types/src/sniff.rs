@@ -0,0 +1,21 @@
+use stabby::string::String;
+
+/// What an input looks like from its first bytes.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct ContentType {
+    /// e.g. "video/mp4", "application/octet-stream" when unrecognised.
+    pub mime_type: String,
+    /// FFmpeg's demuxer name(s), e.g. "mov,mp4,m4a,3gp,3g2,mj2", empty when
+    /// unrecognised.
+    pub format_name: String,
+    /// FFmpeg's probe score out of 100. Low scores mean the match came from
+    /// the file extension rather than the content.
+    pub confidence: u32,
+}
+
+impl ContentType {
+    pub fn is_recognised(&self) -> bool {
+        !self.format_name.is_empty()
+    }
+}
@@ -0,0 +1,22 @@
+use stabby::string::String;
+
+/// How the frames of a 360° video are laid out and which way they face,
+/// from its spherical video metadata (Spherical Video V2 in MP4 and
+/// Matroska).
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct SphericalMetadata {
+    /// FFmpeg's name for the projection, e.g. "equirectangular",
+    /// "cubemap" or "tiled equirectangular".
+    pub projection: String,
+    /// Which way the center of the frame faces, in degrees.
+    pub yaw: f64,
+    pub pitch: f64,
+    pub roll: f64,
+}
+
+impl SphericalMetadata {
+    pub fn is_equirectangular(&self) -> bool {
+        &*self.projection == "equirectangular"
+    }
+}
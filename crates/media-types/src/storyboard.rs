@@ -0,0 +1,39 @@
+use stabby::string::String;
+
+use crate::export::EncodedImage;
+
+/// How a storyboard is sampled and laid out, see `generate_storyboard`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct StoryboardOptions {
+    /// Time between tiles, each covers this much of the input.
+    pub interval_ms: u32,
+    /// Tiles in each row of the sprite.
+    pub columns: u32,
+    /// Width of each tile, the height follows the input's aspect ratio.
+    pub tile_width: u32,
+    /// What the cues call the sprite, i.e. where the player will find it.
+    pub sprite_url: String,
+}
+
+impl Default for StoryboardOptions {
+    fn default() -> Self {
+        StoryboardOptions {
+            interval_ms: 5000,
+            columns: 10,
+            tile_width: 160,
+            sprite_url: "storyboard.jpg".into(),
+        }
+    }
+}
+
+/// A storyboard sprite and the WebVTT that goes with it.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct EncodedStoryboard {
+    /// A JPEG of the tiles in rows.
+    pub sprite: EncodedImage,
+    /// A cue for each tile giving its region of the sprite as a media
+    /// fragment, e.g. `storyboard.jpg#xywh=160,0,160,90`.
+    pub vtt: String,
+}
@@ -0,0 +1,14 @@
+use stabby::string::String;
+
+/// One caption of a text subtitle stream, see `extract_subtitles`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct SubtitleCue {
+    /// When the caption is shown and hidden, in microseconds from the start
+    /// of the input.
+    pub start_us: i64,
+    pub end_us: i64,
+    /// Plain text with formatting (ASS override tags, SRT's italics...)
+    /// removed, lines separated by `\n`.
+    pub text: String,
+}
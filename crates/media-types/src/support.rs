@@ -0,0 +1,167 @@
+use stabby::option::Option;
+use stabby::string::String;
+use stabby::vec::Vec;
+
+use crate::Size;
+
+/// Surfaces FFmpeg's NVDEC decoder allocates for a session at most: the 16
+/// reference frames H.264 and HEVC allow, plus the ones it keeps for output
+/// and reordering.
+const NVDEC_SESSION_SURFACES: u64 = 25;
+
+/// Which accelerator a decoder uses. Anything but `Auto` and
+/// `SoftwareOnly` forces that one, opening fails when it isn't available.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HardwareBackend {
+    /// Whatever works best, in the order `hw_device_types` gives.
+    Auto,
+    /// Never decode on the GPU.
+    SoftwareOnly,
+    Cuda,
+    VideoToolbox,
+    Vaapi,
+    Qsv,
+    D3D11,
+}
+
+impl HardwareBackend {
+    /// FFmpeg's name for the backend's device type, none for `Auto` and
+    /// `SoftwareOnly`.
+    pub fn device_type_name(self) -> core::option::Option<&'static str> {
+        match self {
+            HardwareBackend::Auto | HardwareBackend::SoftwareOnly => None,
+            HardwareBackend::Cuda => Some("cuda"),
+            HardwareBackend::VideoToolbox => Some("videotoolbox"),
+            HardwareBackend::Vaapi => Some("vaapi"),
+            HardwareBackend::Qsv => Some("qsv"),
+            HardwareBackend::D3D11 => Some("d3d11va"),
+        }
+    }
+}
+
+/// A kind of video a scheduler wants to place, see `hardware_support`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct HardwareSupportQuery {
+    /// FFmpeg's name for the codec, e.g. "h264", "hevc", "vp9" or "av1".
+    pub codec: String,
+    /// Profile as FFmpeg names it, e.g. "Main 10". Only checked to be one
+    /// the codec has, drivers that can't decode it fall back to software
+    /// when the file is opened.
+    pub profile: Option<String>,
+    /// Bits per sample, zero for any.
+    pub bit_depth: u32,
+}
+
+impl HardwareSupportQuery {
+    pub fn new(codec: &str) -> Self {
+        HardwareSupportQuery {
+            codec: codec.into(),
+            profile: None.into(),
+            bit_depth: 0,
+        }
+    }
+}
+
+/// Whether this machine can decode a kind of video on the GPU.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct HardwareSupport {
+    /// The decoder FFmpeg would use, e.g. "hevc".
+    pub decoder: String,
+    /// Hardware device type that would be used, e.g. "cuda" or "vaapi".
+    /// None when decoding would happen in software.
+    pub device: Option<String>,
+    /// Format frames come off the device in for this bit depth, e.g.
+    /// "p010le".
+    pub pixel_format: Option<String>,
+    /// How busy each NVIDIA GPU here is when `device` is "cuda", see
+    /// `DecoderUtilization`. Empty for other devices and where NVML can't
+    /// be read, including media-lib built without the `nvml` feature.
+    pub utilization: Vec<DecoderUtilization>,
+}
+
+impl HardwareSupport {
+    pub fn is_hardware(&self) -> bool {
+        self.device.is_some()
+    }
+}
+
+/// How busy the video decoder (NVDEC) of an NVIDIA GPU is, see
+/// `decoder_utilization`. Read from NVML, so it counts every process on the
+/// machine, not just this one.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct DecoderUtilization {
+    /// NVML's index for the GPU, in PCI bus order. CUDA numbers GPUs the
+    /// same way (and so `hw_device` takes it) with
+    /// `CUDA_DEVICE_ORDER=PCI_BUS_ID` set.
+    pub device_index: u32,
+    /// e.g. "NVIDIA L4".
+    pub name: String,
+    /// Share of the last sampling period the decoder was busy, 0 to 100.
+    pub decoder_percent: u32,
+    /// How long NVML averaged `decoder_percent` over.
+    pub sampling_period_us: u32,
+    /// Device memory left for decode surfaces and frames.
+    pub memory_free_bytes: u64,
+    pub memory_total_bytes: u64,
+}
+
+impl DecoderUtilization {
+    /// Whether another decode would likely get a fair share of the
+    /// decoder, i.e. it's busy less than `max_percent` of the time and has
+    /// at least `min_free_bytes` of memory left.
+    pub fn has_headroom(&self, max_percent: u32, min_free_bytes: u64) -> bool {
+        self.decoder_percent < max_percent && self.memory_free_bytes >= min_free_bytes
+    }
+
+    /// Roughly how many more decodes of `size` video with `bit_depth` bits
+    /// fit in the free memory. NVDEC doesn't limit sessions itself, its
+    /// surfaces run out of memory first.
+    pub fn free_sessions(&self, size: Size, bit_depth: u32) -> u64 {
+        let bytes_per_sample = if bit_depth > 8 { 2 } else { 1 };
+        // NV12 or P010, a full size luma plane and half as much chroma.
+        let surface_bytes = size.width as u64 * size.height as u64 * 3 / 2 * bytes_per_sample;
+        match surface_bytes * NVDEC_SESSION_SURFACES {
+            0 => 0,
+            session_bytes => self.memory_free_bytes / session_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utilization(decoder_percent: u32, memory_free_bytes: u64) -> DecoderUtilization {
+        DecoderUtilization {
+            device_index: 0,
+            name: "NVIDIA L4".into(),
+            decoder_percent,
+            sampling_period_us: 1_000_000,
+            memory_free_bytes,
+            memory_total_bytes: 24 << 30,
+        }
+    }
+
+    #[test]
+    fn it_counts_the_sessions_free_memory_fits() {
+        // 25 surfaces of 1080p NV12 take just under 75MiB.
+        let gpu = utilization(40, 1 << 30);
+        assert_eq!(gpu.free_sessions(Size::new(1920, 1080), 8), 13);
+        assert_eq!(gpu.free_sessions(Size::new(1920, 1080), 10), 6);
+        assert_eq!(gpu.free_sessions(Size::new(3840, 2160), 10), 1);
+        assert_eq!(utilization(40, 0).free_sessions(Size::new(640, 360), 8), 0);
+        assert_eq!(gpu.free_sessions(Size::new(0, 0), 8), 0);
+    }
+
+    #[test]
+    fn it_has_headroom_below_both_limits() {
+        assert!(utilization(40, 1 << 30).has_headroom(80, 256 << 20));
+        assert!(!utilization(80, 1 << 30).has_headroom(80, 256 << 20));
+        assert!(!utilization(40, 128 << 20).has_headroom(80, 256 << 20));
+    }
+}
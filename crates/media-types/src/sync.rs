@@ -0,0 +1,83 @@
+use stabby::option::Option;
+use stabby::result::Result;
+use stabby::vec::Vec;
+
+use crate::{MediaFrameDecoderOptions, MediaLibError, Size};
+
+/// What the inputs of a synchronized decode are lined up by.
+#[stabby::stabby]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncClock {
+    /// Each input's `creation_time` tag, when its camera started
+    /// recording. Only as good as the cameras' clocks.
+    WallClock,
+    /// Each input's SMPTE start timecode (a `timecode` tag, e.g. from a
+    /// QuickTime tmcd track), for rigs with jam-synced timecode.
+    Timecode,
+    /// The inputs start together, only `offsets_ms` moves them apart.
+    Start,
+}
+
+/// How `get_synced_frames` lines up and decodes its inputs.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub clock: SyncClock,
+    /// Time between bundles on the shared timeline. Zero takes the frame
+    /// interval of the input with the highest frame rate.
+    pub interval_ms: u32,
+    /// Added to where each input starts on the shared timeline, in input
+    /// order, to correct cameras whose clocks are off. Inputs past the end
+    /// of the list aren't moved.
+    pub offsets_ms: Vec<i32>,
+    /// How each input is decoded and scaled. Realtime decoding, sampling
+    /// and time ranges don't apply.
+    pub decoder: MediaFrameDecoderOptions,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            clock: SyncClock::WallClock,
+            interval_ms: 1000,
+            offsets_ms: Vec::new(),
+            decoder: MediaFrameDecoderOptions::default(),
+        }
+    }
+}
+
+/// One input's frame in a `FrameBundle`.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct SyncedFrame {
+    /// Position of the input in the list it was opened with.
+    pub input_index: u32,
+    /// When the frame is shown in the input's own timeline, from its start.
+    pub timestamp_us: i64,
+    pub size: Size,
+    pub jpeg: Vec<u8>,
+}
+
+/// What every input shows at one moment.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct FrameBundle {
+    /// Time on the shared timeline, from when the earliest input starts.
+    pub timestamp_us: i64,
+    /// In input order. Inputs that haven't started yet or have already
+    /// ended at `timestamp_us` are left out.
+    pub frames: Vec<SyncedFrame>,
+}
+
+pub type FrameBundleGet = Result<FrameBundle, MediaLibError>;
+
+#[stabby::stabby]
+pub trait MediaSyncIterator {
+    /// The next bundle, none once every input has ended.
+    extern "C" fn get_bundle(&mut self) -> Option<FrameBundleGet>;
+    /// Where each input starts on the shared timeline, in input order.
+    extern "C" fn get_offsets_us(&mut self) -> Vec<i64>;
+}
+
+pub type MediaSyncIteratorBox = stabby::dynptr!(stabby::boxed::Box<dyn MediaSyncIterator>);
@@ -0,0 +1,48 @@
+use stabby::string::String;
+
+use crate::output::OutputOptions;
+use crate::Size;
+
+/// What `generate_test_asset` synthesizes: FFmpeg's moving test pattern
+/// with a frame counter, and optionally a sine tone, so tests and bug
+/// reports can describe an input instead of sharing one.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct TestAssetOptions {
+    /// Encode interlaced, top field first, with the two fields of each
+    /// frame captured half a frame apart.
+    pub interlaced: bool,
+    pub size: Size,
+    pub duration_ms: u32,
+    pub frame_rate: u32,
+    /// B-frames the encoder may use between reference frames, zero for
+    /// none.
+    pub b_frames: u32,
+    /// Degrees players turn the video clockwise to show it, as phones
+    /// record video held sideways. Stored as a display matrix, the pixels
+    /// are left as they are.
+    pub rotation_degrees: u32,
+    /// Frequency of a mono sine tone in an audio stream, zero for no audio.
+    pub tone_hz: u32,
+    /// FFmpeg encoder name, e.g. "libx264", "libx265" or "mpeg2video".
+    /// Empty uses the default video encoder of the output's container.
+    pub codec: String,
+    /// How the output file is written.
+    pub output: OutputOptions,
+}
+
+impl Default for TestAssetOptions {
+    fn default() -> Self {
+        TestAssetOptions {
+            interlaced: false,
+            size: Size::new(1280, 720),
+            duration_ms: 5000,
+            frame_rate: 30,
+            b_frames: 0,
+            rotation_degrees: 0,
+            tone_hz: 0,
+            codec: String::new(),
+            output: OutputOptions::default(),
+        }
+    }
+}
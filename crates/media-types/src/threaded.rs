@@ -0,0 +1,28 @@
+use stabby::option::Option;
+
+use crate::{CancelHandleBox, MediaKeyFrameGet, ProcessingReport, Size};
+
+/// Keyframes decoded on a worker thread of their own, up to
+/// `frame_queue_size` ahead of the caller, so taking a frame doesn't wait
+/// for it to be decoded, filtered and encoded. See `get_threaded_frames`.
+#[stabby::stabby]
+pub trait MediaThreadedDecoder {
+    /// The next frame, waiting for the worker when it hasn't got to it yet.
+    /// None once the input is done.
+    extern "C" fn get_frame(&mut self) -> Option<MediaKeyFrameGet>;
+    /// The next frame if the worker has it ready, none right away when it
+    /// doesn't. `is_finished` tells waiting from done.
+    extern "C" fn try_get_frame(&mut self) -> Option<MediaKeyFrameGet>;
+    /// Whether every frame has been taken.
+    extern "C" fn is_finished(&mut self) -> bool;
+    extern "C" fn get_size(&mut self) -> Size;
+    /// Summary of the work done so far, by the worker rather than what has
+    /// been taken. Complete once `is_finished`.
+    extern "C" fn get_report(&mut self) -> ProcessingReport;
+    /// A handle that stops the worker from another thread, the frames it
+    /// has queued can still be taken.
+    extern "C" fn get_cancel_handle(&mut self) -> CancelHandleBox;
+}
+
+pub type MediaThreadedDecoderBox =
+    stabby::dynptr!(stabby::boxed::Box<dyn MediaThreadedDecoder + Send>);
@@ -0,0 +1,25 @@
+use crate::output::OutputOptions;
+
+/// How a time-lapse is condensed from its input.
+#[stabby::stabby]
+#[derive(Debug, Clone)]
+pub struct TimelapseOptions {
+    /// Keep one frame out of every `speedup`, so the output plays this many
+    /// times faster than the input.
+    pub speedup: u32,
+    /// Average each group of `speedup` frames instead of picking one, which
+    /// gives motion blur rather than a jittery result.
+    pub blend: bool,
+    /// How the output file is written.
+    pub output: OutputOptions,
+}
+
+impl Default for TimelapseOptions {
+    fn default() -> Self {
+        TimelapseOptions {
+            speedup: 10,
+            blend: false,
+            output: OutputOptions::default(),
+        }
+    }
+}